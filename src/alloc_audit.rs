@@ -0,0 +1,63 @@
+//! Detection of packages that reference the memory allocator. As for panic machinery, these
+//! references are found by looking each allocator entry point up as if it were an API, under a name
+//! that can't clash with APIs from the config. Packages can be marked with `no_alloc`, in which case
+//! any reference that they make to the allocator is reported. e.g. for dependencies of embedded code
+//! that are supposed to be allocation-free.
+
+use crate::config::ApiName;
+use std::sync::Arc;
+
+/// The name of the pseudo-API. Config validation rejects APIs with this name.
+const API_NAME: &str = "alloc-audit:alloc";
+
+/// The paths through which code allocates.
+const PATHS: &[&str] = &[
+    // The shims through which `alloc` calls the global allocator. Older versions of rustc don't
+    // mangle these.
+    "__rustc::__rust_alloc",
+    "__rustc::__rust_alloc_zeroed",
+    "__rustc::__rust_realloc",
+    "extern::__rust_alloc",
+    "extern::__rust_alloc_zeroed",
+    "extern::__rust_realloc",
+    // Using the global allocator, or some other allocator, directly.
+    "alloc::alloc",
+    "core::alloc::global::GlobalAlloc",
+    // The C library's allocator.
+    "extern::malloc",
+    "extern::calloc",
+    "extern::realloc",
+    "extern::aligned_alloc",
+    "extern::posix_memalign",
+];
+
+fn api_name() -> ApiName {
+    ApiName {
+        name: Arc::from(API_NAME),
+    }
+}
+
+/// Returns whether `api_name` is our pseudo-API.
+pub(crate) fn is_alloc_api(api_name: &ApiName) -> bool {
+    api_name.as_ref() == API_NAME
+}
+
+/// Returns the pseudo-API that we use to find references to the allocator, together with each path
+/// that it includes.
+pub(crate) fn pseudo_apis() -> impl Iterator<Item = (ApiName, &'static str)> {
+    PATHS.iter().map(|path| (api_name(), *path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_alloc_api;
+    use super::pseudo_apis;
+    use crate::config::ApiName;
+
+    #[test]
+    fn api_names() {
+        assert!(pseudo_apis().all(|(api, _)| is_alloc_api(&api)));
+        assert!(pseudo_apis().any(|(_, path)| path == "__rustc::__rust_alloc"));
+        assert!(!is_alloc_api(&ApiName::from("alloc")));
+    }
+}
@@ -0,0 +1,258 @@
+//! Bundles of build artifacts, so that the build can happen in one CI job and the analysis in
+//! another. `pack-artifacts` builds as normal, recording the requests from our subprocesses, then
+//! writes the requests together with the binaries and object files that they refer to into a
+//! zstd-compressed tarball. Passing that tarball via `--artifacts` then extracts the files into the
+//! target directory and replays the requests, as with `--replay-requests`.
+//!
+//! Only files within the target directory are included. Anything else, e.g. the standard library's
+//! rlibs, needs to be present at the same location on the machine doing the analysis, which is
+//! generally true if the same toolchain is installed. Similarly, source paths are only recorded, so
+//! the analysis should be done from a checkout of the same code at the same path.
+
+use crate::proxy::rpc::Request;
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+/// The name of the manifest, both within the bundle, and in the target directory once extracted.
+const MANIFEST_NAME: &str = "cackle-artifacts.json";
+
+/// Bumped whenever the manifest changes in an incompatible way.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Manifest {
+    format_version: u32,
+
+    /// The target directory of the build that produced the bundle.
+    target_dir: PathBuf,
+
+    /// The files in the bundle, relative to `target_dir`.
+    files: Vec<PathBuf>,
+
+    /// The SHA-256 of each input to the build. See `staleness::input_hashes`.
+    pub(crate) input_hashes: BTreeMap<PathBuf, String>,
+
+    /// The requests that our subprocesses sent during the build, in the order they were received.
+    pub(crate) requests: Vec<Request>,
+}
+
+/// Writes a bundle of `requests` and the artifacts that they refer to, to `output`.
+pub(crate) fn pack(
+    requests: Vec<Request>,
+    target_dir: &Path,
+    lock_file: Option<&Path>,
+    output: &Path,
+) -> Result<()> {
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        target_dir: target_dir.to_owned(),
+        files: artifact_paths(&requests, target_dir),
+        input_hashes: crate::staleness::input_hashes(&requests, lock_file),
+        requests,
+    };
+    crate::fs::write(
+        target_dir.join(MANIFEST_NAME),
+        serde_json::to_string(&manifest)?,
+    )?;
+    let mut tar = Command::new("tar")
+        .arg("--create")
+        .arg("--file=-")
+        .arg("--directory")
+        .arg(target_dir)
+        .arg("--null")
+        .arg("--files-from=-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run `tar`")?;
+    // Paths are passed via stdin, since there can be too many for the command line. We write them
+    // from a separate thread to avoid deadlocking when the pipe to tar's stdout fills up.
+    let mut stdin = tar.stdin.take().unwrap();
+    let mut list = Vec::new();
+    for path in std::iter::once(Path::new(MANIFEST_NAME)).chain(manifest.files.iter().map(|p| &**p))
+    {
+        list.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        list.push(0);
+    }
+    let writer = std::thread::spawn(move || stdin.write_all(&list));
+    let compressed = ruzstd::encoding::compress_to_vec(
+        tar.stdout.take().unwrap(),
+        ruzstd::encoding::CompressionLevel::Fastest,
+    );
+    writer
+        .join()
+        .unwrap()
+        .context("Failed to write paths to `tar`")?;
+    let status = tar.wait()?;
+    if !status.success() {
+        bail!("`tar` failed with {status}");
+    }
+    crate::fs::write(output, compressed)
+}
+
+/// Extracts the bundle at `bundle` into `target_dir` and returns its manifest. The paths in the
+/// requests are updated if `target_dir` differs from where the bundle was built.
+pub(crate) fn unpack(bundle: &Path, target_dir: &Path) -> Result<Manifest> {
+    let file = std::fs::File::open(bundle)
+        .with_context(|| format!("Failed to open `{}`", bundle.display()))?;
+    let mut decoder = ruzstd::decoding::StreamingDecoder::new(std::io::BufReader::new(file))
+        .with_context(|| format!("`{}` isn't zstd compressed", bundle.display()))?;
+    std::fs::create_dir_all(target_dir)
+        .with_context(|| format!("Failed to create `{}`", target_dir.display()))?;
+    let mut tar = Command::new("tar")
+        .arg("--extract")
+        .arg("--file=-")
+        .arg("--directory")
+        .arg(target_dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run `tar`")?;
+    let copy_result = std::io::copy(&mut decoder, tar.stdin.as_mut().unwrap());
+    drop(tar.stdin.take());
+    let status = tar.wait()?;
+    copy_result.with_context(|| format!("Failed to decompress `{}`", bundle.display()))?;
+    if !status.success() {
+        bail!(
+            "`tar` failed with {status} extracting `{}`",
+            bundle.display()
+        );
+    }
+    let manifest_path = target_dir.join(MANIFEST_NAME);
+    let mut manifest: Manifest = serde_json::from_str(&crate::fs::read_to_string(&manifest_path)?)
+        .with_context(|| format!("Failed to parse `{}`", manifest_path.display()))?;
+    if manifest.format_version != FORMAT_VERSION {
+        bail!(
+            "`{}` was written by an incompatible version of cackle",
+            bundle.display()
+        );
+    }
+    if manifest.target_dir != target_dir {
+        manifest.requests = manifest
+            .requests
+            .into_iter()
+            .map(|request| rebase_paths(request, &manifest.target_dir, target_dir))
+            .collect::<Result<_>>()?;
+        manifest.target_dir = target_dir.to_owned();
+    }
+    Ok(manifest)
+}
+
+/// Returns the paths, relative to `target_dir`, of the files that we'll need in order to analyse
+/// the linker invocations in `requests`.
+fn artifact_paths(requests: &[Request], target_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = BTreeSet::new();
+    for request in requests {
+        let Request::LinkerInvoked(info) = request else {
+            continue;
+        };
+        let split_dwarf = info.output_file.with_extension("dwp");
+        let candidates = info
+            .object_paths_under(target_dir)
+            .into_iter()
+            .chain([info.output_file.to_path_buf(), split_dwarf]);
+        for path in candidates {
+            if let Ok(relative) = path.strip_prefix(target_dir)
+                && path.is_file()
+            {
+                paths.insert(relative.to_owned());
+            }
+        }
+    }
+    paths.into_iter().collect()
+}
+
+/// Replaces `from` with `to` at the start of any paths in `request`. Requests contain paths in
+/// several places, so rather than handling each, we look at every string.
+fn rebase_paths(request: Request, from: &Path, to: &Path) -> Result<Request> {
+    fn rebase(value: &mut serde_json::Value, from: &Path, to: &Path) {
+        match value {
+            serde_json::Value::String(s) => {
+                if let Ok(rest) = Path::new(s.as_str()).strip_prefix(from) {
+                    *s = to.join(rest).to_string_lossy().into_owned();
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter_mut().for_each(|v| rebase(v, from, to));
+            }
+            serde_json::Value::Object(map) => {
+                map.values_mut().for_each(|v| rebase(v, from, to));
+            }
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(request)?;
+    rebase(&mut value, from, to);
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pack;
+    use super::unpack;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::testing::pkg_id;
+    use crate::proxy::rpc::Request;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target_dir = dir.path().join("target");
+        std::fs::create_dir_all(target_dir.join("debug/deps")).unwrap();
+        let bin = target_dir.join("debug/foo");
+        std::fs::write(&bin, "bin").unwrap();
+        std::fs::write(target_dir.join("debug/deps/libfoo.rlib"), "rlib").unwrap();
+        let link_info = serde_json::from_value(serde_json::json!({
+            "crate_sel": CrateSel::primary(pkg_id("foo")),
+            "object_paths": [
+                target_dir.join("debug/deps/libfoo.rlib"),
+                "/nonexistent/libstd.rlib",
+            ],
+            "output_file": bin,
+            "is_shared": false,
+        }))
+        .unwrap();
+        let bundle = dir.path().join("bundle.tar.zst");
+        pack(
+            vec![Request::LinkerInvoked(link_info)],
+            &target_dir,
+            None,
+            &bundle,
+        )
+        .unwrap();
+
+        let other_target_dir = dir.path().join("other");
+        let manifest = unpack(&bundle, &other_target_dir).unwrap();
+        assert_eq!(
+            manifest.files,
+            vec![
+                std::path::PathBuf::from("debug/deps/libfoo.rlib"),
+                "debug/foo".into()
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(other_target_dir.join("debug/foo")).unwrap(),
+            "bin"
+        );
+        let [Request::LinkerInvoked(info)] = manifest.requests.as_slice() else {
+            panic!("Unexpected requests {:?}", manifest.requests);
+        };
+        assert_eq!(&*info.output_file, other_target_dir.join("debug/foo"));
+        assert_eq!(
+            info.object_paths,
+            vec![
+                other_target_dir.join("debug/deps/libfoo.rlib"),
+                "/nonexistent/libstd.rlib".into()
+            ]
+        );
+    }
+}
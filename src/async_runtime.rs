@@ -0,0 +1,127 @@
+//! Detection of which async runtimes each package binds to. A package binds to a runtime if it
+//! references code in the runtime's crate. We find these references in the same way as we find API
+//! usages - each runtime is looked up as if it were an API, under a name that can't clash with
+//! APIs from the config. Usages of these pseudo-APIs are then collected separately rather than
+//! being checked against permissions. Pulling in more than one runtime is usually a mistake, since
+//! each runtime brings its own executor, reactor and threads, so it can optionally be reported as a
+//! problem. See `common.single_async_runtime`.
+
+use crate::config::ApiName;
+use crate::crate_index::PackageId;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum AsyncRuntime {
+    Tokio,
+    AsyncStd,
+    Smol,
+}
+
+/// Prefix for the names of the pseudo-APIs. Config validation rejects APIs with these names.
+const API_PREFIX: &str = "async-runtime:";
+
+/// For each async runtime, the packages that bind to it.
+pub(crate) type RuntimeBindings = BTreeMap<AsyncRuntime, BTreeSet<PackageId>>;
+
+/// Multiple async runtimes were bound to by packages in the same binary.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MultipleAsyncRuntimes {
+    pub(crate) bin_path: Arc<Path>,
+    pub(crate) bindings: RuntimeBindings,
+}
+
+impl AsyncRuntime {
+    pub(crate) const ALL: &'static [AsyncRuntime] = &[
+        AsyncRuntime::Tokio,
+        AsyncRuntime::AsyncStd,
+        AsyncRuntime::Smol,
+    ];
+
+    /// The name of the runtime's crate, which is also the first part of the names of its symbols.
+    pub(crate) fn crate_name(self) -> &'static str {
+        match self {
+            AsyncRuntime::Tokio => "tokio",
+            AsyncRuntime::AsyncStd => "async_std",
+            AsyncRuntime::Smol => "smol",
+        }
+    }
+
+    /// The name of the pseudo-API used to find references to this runtime.
+    pub(crate) fn api_name(self) -> ApiName {
+        ApiName {
+            name: Arc::from(format!("{API_PREFIX}{self}")),
+        }
+    }
+
+    /// Returns the pseudo-APIs that we use to find references to runtimes, together with the paths
+    /// that they include.
+    pub(crate) fn pseudo_apis() -> impl Iterator<Item = (ApiName, &'static str)> {
+        Self::ALL
+            .iter()
+            .map(|runtime| (runtime.api_name(), runtime.crate_name()))
+    }
+
+    /// Returns the runtime for `api_name` if it's one of our pseudo-APIs.
+    pub(crate) fn from_api_name(api_name: &ApiName) -> Option<AsyncRuntime> {
+        let name = api_name.as_ref().strip_prefix(API_PREFIX)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|runtime| runtime.to_string() == name)
+    }
+}
+
+impl Display for AsyncRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsyncRuntime::Tokio => write!(f, "tokio"),
+            AsyncRuntime::AsyncStd => write!(f, "async-std"),
+            AsyncRuntime::Smol => write!(f, "smol"),
+        }
+    }
+}
+
+impl Display for MultipleAsyncRuntimes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let runtimes: Vec<String> = self.bindings.keys().map(|r| r.to_string()).collect();
+        write!(
+            f,
+            "`{}` uses multiple async runtimes: {}",
+            self.bin_path.display(),
+            runtimes.join(", ")
+        )?;
+        if f.alternate() {
+            writeln!(f)?;
+            for (runtime, pkg_ids) in &self.bindings {
+                let pkg_ids: Vec<String> = pkg_ids.iter().map(|p| p.to_string()).collect();
+                writeln!(f, "  {runtime}: {}", pkg_ids.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncRuntime;
+    use crate::config::ApiName;
+
+    #[test]
+    fn api_names() {
+        for runtime in AsyncRuntime::ALL {
+            assert_eq!(
+                AsyncRuntime::from_api_name(&runtime.api_name()),
+                Some(*runtime)
+            );
+        }
+        assert_eq!(AsyncRuntime::from_api_name(&ApiName::from("tokio")), None);
+        assert_eq!(AsyncRuntime::from_api_name(&ApiName::from("fs")), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Detection of binary files in the sources of dependencies, e.g. precompiled executables, static
+//! libraries or object files. Such files haven't been through our analysis, yet a build script or
+//! proc macro could run them, or they could be linked into our binaries. Each one is reported,
+//! identified by its hash, so that the user can decide whether to trust it. The directory walking
+//! and classification here are also used by `prebuilt` to find executables written by build
+//! scripts.
+
+use std::convert::Infallible;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Magic numbers for executables, libraries and object files.
+const MAGICS: &[(&[u8], BinaryKind)] = &[
+    (b"\x7fELF", BinaryKind::Elf),
+    // Mach-O, 32 and 64 bit, both byte orders.
+    (&[0xfe, 0xed, 0xfa, 0xce], BinaryKind::MachO),
+    (&[0xfe, 0xed, 0xfa, 0xcf], BinaryKind::MachO),
+    (&[0xce, 0xfa, 0xed, 0xfe], BinaryKind::MachO),
+    (&[0xcf, 0xfa, 0xed, 0xfe], BinaryKind::MachO),
+    (b"!<arch>\n", BinaryKind::Archive),
+    (b"\0asm", BinaryKind::Wasm),
+];
+
+/// The magic number of DOS executables. Windows binaries start with a DOS stub, which has at 0x3c
+/// the offset of the PE signature.
+const DOS_MAGIC: &[u8] = b"MZ";
+const PE_OFFSET_POSITION: usize = 0x3c;
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+
+/// The magic number shared by Mach-O universal binaries and Java class files.
+const CAFEBABE: &[u8] = &[0xca, 0xfe, 0xba, 0xbe];
+
+/// A universal binary has an architecture count after its magic, whereas a class file has its
+/// version, the major part of which is at least 45. Like `file`, we take small values to be an
+/// architecture count.
+const MAX_FAT_ARCHS: u32 = 20;
+
+/// Files at least this large that don't look like text are reported even if we don't recognise
+/// them, since there's little reason for a crate to include them other than to hide something.
+const OPAQUE_FILE_SIZE: u64 = 1024 * 1024;
+
+/// How much of each file we read in order to classify it.
+const HEADER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum BinaryKind {
+    Elf,
+    MachO,
+    Pe,
+    Archive,
+    Wasm,
+    JavaClass,
+    /// A large file that doesn't look like text.
+    Opaque,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BinaryFile {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: BinaryKind,
+    pub(crate) sha256: String,
+}
+
+#[derive(Default, Debug)]
+pub(crate) struct BinaryFiles {
+    pub(crate) binaries: Vec<BinaryFile>,
+    /// Files and directories that we couldn't read, so can't say whether they're binaries.
+    pub(crate) unreadable: Vec<WalkError>,
+}
+
+/// A regular file found by `walk_files`.
+pub(crate) struct WalkedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) metadata: std::fs::Metadata,
+}
+
+/// Something that `walk_files` couldn't read.
+#[derive(Debug)]
+pub(crate) struct WalkError {
+    pub(crate) path: PathBuf,
+    pub(crate) error: std::io::Error,
+}
+
+/// Calls `visit` for each regular file under `dir`, descending into subdirectories for which
+/// `descend` returns true. Symlinks aren't followed. Anything that can't be read is passed to
+/// `visit` as an error, then skipped.
+pub(crate) fn walk_files<E>(
+    dir: &Path,
+    descend: impl Fn(&Path) -> bool,
+    mut visit: impl FnMut(Result<WalkedFile, WalkError>) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                visit(Err(WalkError { path: dir, error }))?;
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    visit(Err(WalkError {
+                        path: dir.clone(),
+                        error,
+                    }))?;
+                    continue;
+                }
+            };
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {
+                    if descend(&path) {
+                        dirs.push(path);
+                    }
+                }
+                Ok(file_type) if file_type.is_file() => match entry.metadata() {
+                    Ok(metadata) => visit(Ok(WalkedFile { path, metadata }))?,
+                    Err(error) => visit(Err(WalkError { path, error }))?,
+                },
+                Ok(_) => {}
+                Err(error) => visit(Err(WalkError { path, error }))?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the binary files under `pkg_dir`. We don't look in subdirectories that contain other
+/// packages, or in `target` or `.git`. Symlinks aren't followed. Anything that we fail to read is
+/// returned as unreadable rather than failing the search.
+pub(crate) fn find_binary_files(pkg_dir: &Path) -> BinaryFiles {
+    let mut found = BinaryFiles::default();
+    let Ok(()) = walk_files(
+        pkg_dir,
+        |dir| {
+            dir.file_name()
+                .is_none_or(|name| name != "target" && name != ".git")
+                && !dir.join("Cargo.toml").exists()
+        },
+        |file| -> Result<(), Infallible> {
+            match file.and_then(binary_file) {
+                Ok(Some(binary)) => found.binaries.push(binary),
+                Ok(None) => {}
+                Err(error) => found.unreadable.push(error),
+            }
+            Ok(())
+        },
+    );
+    found.binaries.sort_by(|a, b| a.path.cmp(&b.path));
+    found.unreadable.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// Returns `file` with its kind and SHA-256 if it's a binary.
+fn binary_file(file: WalkedFile) -> Result<Option<BinaryFile>, WalkError> {
+    let unreadable = |error: std::io::Error| WalkError {
+        path: file.path.clone(),
+        error,
+    };
+    let header = read_header(&file.path).map_err(unreadable)?;
+    let Some(kind) = classify(&header, file.metadata.len()) else {
+        return Ok(None);
+    };
+    let bytes = std::fs::read(&file.path).map_err(unreadable)?;
+    Ok(Some(BinaryFile {
+        path: file.path,
+        kind,
+        sha256: crate::prebuilt::sha256_hex(&bytes),
+    }))
+}
+
+fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    std::fs::File::open(path)?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Classifies a file of `size` bytes that starts with `header`, returning `None` if it doesn't
+/// look like a binary.
+fn classify(header: &[u8], size: u64) -> Option<BinaryKind> {
+    if let Some(kind) = classify_magic(header) {
+        return Some(kind);
+    }
+    (size >= OPAQUE_FILE_SIZE && header.contains(&0)).then_some(BinaryKind::Opaque)
+}
+
+/// Returns the kind of binary that `header` starts with the magic number of, if any.
+pub(crate) fn classify_magic(header: &[u8]) -> Option<BinaryKind> {
+    if let Some((_, kind)) = MAGICS.iter().find(|(magic, _)| header.starts_with(magic)) {
+        return Some(*kind);
+    }
+    if header.starts_with(DOS_MAGIC) {
+        // Text files can start with "MZ" too, so we only go by the PE signature.
+        let offset = header.get(PE_OFFSET_POSITION..PE_OFFSET_POSITION + 4)?;
+        let offset = usize::try_from(u32::from_le_bytes(offset.try_into().ok()?)).ok()?;
+        return header
+            .get(offset..)?
+            .starts_with(PE_SIGNATURE)
+            .then_some(BinaryKind::Pe);
+    }
+    let rest = header.strip_prefix(CAFEBABE)?;
+    let count = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?);
+    Some(if count < MAX_FAT_ARCHS {
+        BinaryKind::MachO
+    } else {
+        BinaryKind::JavaClass
+    })
+}
+
+impl BinaryKind {
+    /// Returns whether binaries of this kind can be run directly.
+    pub(crate) fn is_executable(self) -> bool {
+        matches!(self, BinaryKind::Elf | BinaryKind::MachO | BinaryKind::Pe)
+    }
+}
+
+impl std::fmt::Display for BinaryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryKind::Elf => write!(f, "ELF binary"),
+            BinaryKind::MachO => write!(f, "Mach-O binary"),
+            BinaryKind::Pe => write!(f, "Windows binary"),
+            BinaryKind::Archive => write!(f, "static library"),
+            BinaryKind::Wasm => write!(f, "WebAssembly module"),
+            BinaryKind::JavaClass => write!(f, "Java class file"),
+            BinaryKind::Opaque => write!(f, "large binary file"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    /// Writes `contents` to `name` under `dir`, creating directories as needed.
+    pub(crate) fn write_file(dir: &Path, name: &str, contents: &[u8], mode: u32) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryKind;
+    use super::OPAQUE_FILE_SIZE;
+    use super::classify;
+    use super::find_binary_files;
+    use super::testing::write_file;
+    use std::path::Path;
+
+    #[test]
+    fn classification() {
+        assert_eq!(classify(b"\x7fELF\x02\x01", 100), Some(BinaryKind::Elf));
+        assert_eq!(classify(b"!<arch>\nfoo.o", 100), Some(BinaryKind::Archive));
+        assert_eq!(classify(b"fn main() {}", 100), None);
+        assert_eq!(classify(b"\x89PNG\0\0", 100), None);
+        assert_eq!(
+            classify(b"\x89PNG\0\0", OPAQUE_FILE_SIZE),
+            Some(BinaryKind::Opaque)
+        );
+        assert_eq!(classify(b"just a lot of text", OPAQUE_FILE_SIZE), None);
+        // A universal binary with two architectures, then a class file for Java 17.
+        assert_eq!(
+            classify(b"\xca\xfe\xba\xbe\0\0\0\x02", 100),
+            Some(BinaryKind::MachO)
+        );
+        assert_eq!(
+            classify(b"\xca\xfe\xba\xbe\0\0\0\x3d", 100),
+            Some(BinaryKind::JavaClass)
+        );
+        assert_eq!(classify(b"\xca\xfe\xba\xbe", 100), None);
+    }
+
+    #[test]
+    fn pe_signature() {
+        let mut pe = vec![0; 0x84];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x80;
+        pe[0x80..].copy_from_slice(b"PE\0\0");
+        assert_eq!(classify(&pe, 100), Some(BinaryKind::Pe));
+        // A DOS executable, or a text file that happens to start with "MZ".
+        pe[0x80..].copy_from_slice(b"NE\0\0");
+        assert_eq!(classify(&pe, 100), None);
+        assert_eq!(classify(b"MZ is a text file", 100), None);
+        pe[0x3c] = 0xff;
+        assert_eq!(classify(&pe, 100), None);
+    }
+
+    #[test]
+    fn unreadable_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("bin/tool");
+        write_file(dir.path(), "bin/tool", b"\x7fELF...", 0o000);
+        let found = find_binary_files(dir.path());
+        // Root can read the file regardless of its mode, in which case it's just a binary.
+        if std::fs::read(&path).is_ok() {
+            assert_eq!(found.binaries.len(), 1);
+            assert!(found.unreadable.is_empty());
+        } else {
+            assert!(found.binaries.is_empty());
+            assert_eq!(found.unreadable.len(), 1);
+            assert_eq!(found.unreadable[0].path, path);
+        }
+
+        let missing = dir.path().join("missing");
+        let found = find_binary_files(&missing);
+        assert!(found.binaries.is_empty());
+        assert_eq!(found.unreadable.len(), 1);
+        assert_eq!(found.unreadable[0].path, missing);
+    }
+
+    #[test]
+    fn find_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, contents: &[u8]| write_file(dir.path(), name, contents, 0o644);
+        write("src/lib.rs", b"pub fn f() {}");
+        write("bin/serde_derive", b"\x7fELF...");
+        write("target/debug/foo", b"\x7fELF...");
+        write("nested/Cargo.toml", b"[package]");
+        write("nested/tool", b"\x7fELF...");
+        let found = find_binary_files(dir.path());
+        assert!(found.unreadable.is_empty());
+        let files = found.binaries;
+        let names: Vec<_> = files
+            .iter()
+            .map(|file| file.path.strip_prefix(dir.path()).unwrap())
+            .collect();
+        assert_eq!(names, vec![Path::new("bin/serde_derive")]);
+        assert_eq!(files[0].sha256, crate::prebuilt::sha256_hex(b"\x7fELF..."));
+    }
+}
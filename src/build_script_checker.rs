@@ -1,11 +1,19 @@
 use crate::config::Config;
 use crate::config::permissions::PermSel;
 use crate::crate_index::PackageId;
+use crate::prebuilt::WrittenExecutable;
 use crate::problem::DisallowedBuildInstruction;
+use crate::problem::ExecutableWritten;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
 use crate::proxy::rpc::BinExecutionOutput;
+use anyhow::Context;
 use anyhow::Result;
+use sha2::Digest;
+use sha2::Sha256;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
 
 #[derive(Default)]
 pub(crate) struct BuildScriptReport {
@@ -21,11 +29,33 @@ impl BuildScriptReport {
         let mut report = BuildScriptReport::default();
         let crate_sel = &outputs.crate_sel;
         let perm_sel = PermSel::for_build_script(crate_sel.pkg_name());
-        let allow_build_instructions = config
-            .permissions
-            .get(&perm_sel)
+        let pkg_config = config.permissions.get(&perm_sel);
+        let allow_build_instructions = pkg_config
             .map(|cfg| cfg.allow_build_instructions.as_slice())
             .unwrap_or(&[]);
+        let allow_executables = pkg_config
+            .map(|cfg| cfg.allow_executables.as_slice())
+            .unwrap_or(&[]);
+        for executable in &outputs.written_executables {
+            if !allow_executables.contains(&executable.sha256) {
+                report
+                    .problems
+                    .push(Problem::ExecutableWritten(ExecutableWritten {
+                        pkg_id: crate_sel.pkg_id.clone(),
+                        path: executable.path.clone(),
+                        sha256: executable.sha256.clone(),
+                    }));
+            }
+        }
+        for unreadable in &outputs.unreadable_outputs {
+            report.problems.push(Problem::new(format!(
+                "The build script `{}` wrote `{}` to OUT_DIR, which couldn't be read to check \
+                 whether it's an executable: {}",
+                crate_sel.pkg_id,
+                unreadable.path.display(),
+                unreadable.error
+            )));
+        }
         let Ok(stdout) = std::str::from_utf8(&outputs.stdout) else {
             report.problems.push(Problem::new(format!(
                 "The build script `{}` emitted invalid UTF-8",
@@ -51,6 +81,45 @@ impl BuildScriptReport {
     }
 }
 
+/// Returns a digest of the build script's source files, `source_paths`, identifying each by its
+/// path relative to `pkg_dir`. Unlike a hash of the compiled build script, this doesn't change
+/// between toolchains, targets or machines.
+pub(crate) fn sources_digest(pkg_dir: &Path, source_paths: &[PathBuf]) -> Result<String> {
+    let mut sources = source_paths
+        .iter()
+        .map(|path| {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("Failed to read `{}`", path.display()))?;
+            let relative = path.strip_prefix(pkg_dir).unwrap_or(path);
+            Ok((relative.to_owned(), crate::prebuilt::sha256_hex(&bytes)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    sources.sort();
+    sources.dedup();
+    let mut hasher = Sha256::new();
+    for (path, sha256) in sources {
+        hasher.update(path.as_os_str().as_bytes());
+        hasher.update([0]);
+        hasher.update(sha256);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the hash that a build script is pinned to. This covers its sources, via
+/// `sources_digest`, and the executables that it wrote.
+pub(crate) fn pin_hash(sources_digest: &str, executables: &[WrittenExecutable]) -> String {
+    let mut executable_hashes: Vec<&str> = executables.iter().map(|e| e.sha256.as_str()).collect();
+    executable_hashes.sort();
+    executable_hashes.dedup();
+    let mut hasher = Sha256::new();
+    hasher.update(sources_digest);
+    for sha256 in executable_hashes {
+        hasher.update([0]);
+        hasher.update(sha256);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
 /// Cargo instructions that should be harmless, so would just add noise if we were required to
 /// explicitly allow them.
 const ALWAYS_PERMITTED: &[&str] = &[
@@ -98,7 +167,10 @@ mod tests {
     use crate::config::SandboxConfig;
     use crate::crate_index::CrateSel;
     use crate::crate_index::testing::pkg_id;
+    use crate::prebuilt::UnreadableOutput;
+    use crate::prebuilt::WrittenExecutable;
     use crate::problem::DisallowedBuildInstruction;
+    use crate::problem::ExecutableWritten;
     use crate::problem::Problem;
     use crate::problem::ProblemList;
     use crate::proxy::rpc::BinExecutionOutput;
@@ -106,6 +178,16 @@ mod tests {
 
     #[track_caller]
     fn check(stdout: &str, config_str: &str) -> ProblemList {
+        check_with_executables(stdout, Vec::new(), Vec::new(), config_str)
+    }
+
+    #[track_caller]
+    fn check_with_executables(
+        stdout: &str,
+        written_executables: Vec<WrittenExecutable>,
+        unreadable_outputs: Vec<UnreadableOutput>,
+        config_str: &str,
+    ) -> ProblemList {
         let config = config::testing::parse(config_str).unwrap();
         let outputs = BinExecutionOutput {
             exit_code: 0,
@@ -115,6 +197,8 @@ mod tests {
             sandbox_config: SandboxConfig::default(),
             binary_path: PathBuf::new(),
             sandbox_config_display: None,
+            written_executables,
+            unreadable_outputs,
         };
         super::BuildScriptReport::build(&outputs, &config)
             .unwrap()
@@ -165,4 +249,56 @@ mod tests {
             ProblemList::default()
         );
     }
+
+    #[test]
+    fn test_written_executable() {
+        let executables = || {
+            vec![WrittenExecutable {
+                path: PathBuf::from("/out/tool"),
+                sha256: "abcd".to_owned(),
+            }]
+        };
+        assert_eq!(
+            check_with_executables("", executables(), Vec::new(), ""),
+            Problem::ExecutableWritten(ExecutableWritten {
+                pkg_id: pkg_id("my_pkg"),
+                path: PathBuf::from("/out/tool"),
+                sha256: "abcd".to_owned(),
+            })
+            .into()
+        );
+        assert_eq!(
+            check_with_executables(
+                "",
+                executables(),
+                Vec::new(),
+                r#"
+                [pkg.my_pkg.build]
+                allow_executables = [ "abcd" ]
+                "#
+            ),
+            ProblemList::default()
+        );
+    }
+
+    #[test]
+    fn test_unreadable_output() {
+        assert_eq!(
+            check_with_executables(
+                "",
+                Vec::new(),
+                vec![UnreadableOutput {
+                    path: PathBuf::from("/out/tool"),
+                    error: "Permission denied".to_owned(),
+                }],
+                "",
+            ),
+            Problem::new(format!(
+                "The build script `{}` wrote `/out/tool` to OUT_DIR, which couldn't be read to \
+                 check whether it's an executable: Permission denied",
+                pkg_id("my_pkg")
+            ))
+            .into()
+        );
+    }
 }
@@ -0,0 +1,109 @@
+//! The settings that each crate was built with, as far as we can determine them from debug info.
+//! Our analysis is most reliable for code built the way that we ask cargo to build it - without
+//! optimisation and with full debug info. Profile overrides, e.g. `[profile.dev.package."*"]
+//! opt-level = 3`, can mean that some crates are built differently. Optimisation can merge or
+//! eliminate functions, which can cause API usages to be attributed to the wrong place or missed,
+//! so for such crates we report that we have reduced confidence in what we found.
+
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct BuildSettings {
+    /// The distinct producers recorded in the debug info. e.g. "clang LLVM (rustc version 1.95.0
+    /// (59807616e 2026-04-14))".
+    pub(crate) producers: BTreeSet<String>,
+
+    /// Whether any of the code appears to have been optimised. rustc doesn't record its flags in
+    /// the producer, so for Rust code, this is inferred from how many variables have location
+    /// lists, which is much higher with optimisation.
+    pub(crate) optimised: bool,
+
+    /// Whether the code was built with less than full debug info. e.g. `debug = 1` or
+    /// `debug = "line-tables-only"`.
+    pub(crate) reduced_debug_info: bool,
+}
+
+impl BuildSettings {
+    /// Merges in the settings from another unit of the same crate. Any unit showing signs of
+    /// optimisation is enough to say that the crate was optimised. However, a unit can lack types
+    /// simply because its code doesn't need any, so we only consider debug info to be reduced if
+    /// it's reduced in all units.
+    pub(crate) fn merge(&mut self, other: &BuildSettings) {
+        self.producers.extend(other.producers.iter().cloned());
+        self.optimised |= other.optimised;
+        self.reduced_debug_info &= other.reduced_debug_info;
+    }
+
+    /// Returns the reasons why we're less confident than usual in what we found in code built with
+    /// these settings. Empty if the settings are what we expect.
+    pub(crate) fn reduced_confidence_reasons(&self) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+        if self.optimised {
+            reasons.push(
+                "built with optimisation, so functions may have been merged or eliminated and API \
+                 usages attributed to the wrong place",
+            );
+        }
+        if self.reduced_debug_info {
+            reasons.push(
+                "built with reduced debug info, so some functions can't be attributed to a source \
+                 location",
+            );
+        }
+        reasons
+    }
+}
+
+/// Returns whether `producer` says that code was optimised. Only some producers, e.g. GCC, record
+/// their flags, so for others, we return `None`.
+pub(crate) fn producer_optimisation(producer: &str) -> Option<bool> {
+    producer
+        .split_whitespace()
+        .rev()
+        .find_map(|arg| arg.strip_prefix("-O"))
+        .map(|level| level != "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildSettings;
+    use super::producer_optimisation;
+
+    #[test]
+    fn optimisation_from_producer() {
+        assert_eq!(
+            producer_optimisation("GNU C17 13.2.0 -mtune=generic -march=x86-64 -g -O2"),
+            Some(true)
+        );
+        assert_eq!(
+            producer_optimisation("GNU C17 13.2.0 -O3 -g -O0"),
+            Some(false)
+        );
+        assert_eq!(producer_optimisation("GNU C17 13.2.0 -Os"), Some(true));
+        assert_eq!(
+            producer_optimisation("clang LLVM (rustc version 1.95.0 (59807616e 2026-04-14))"),
+            None
+        );
+    }
+
+    #[test]
+    fn merged_settings() {
+        let mut settings = BuildSettings {
+            reduced_debug_info: true,
+            ..BuildSettings::default()
+        };
+        assert_eq!(settings.reduced_confidence_reasons().len(), 1);
+        settings.merge(&BuildSettings {
+            optimised: true,
+            reduced_debug_info: true,
+            ..BuildSettings::default()
+        });
+        assert_eq!(settings.reduced_confidence_reasons().len(), 2);
+        settings.merge(&BuildSettings::default());
+        assert!(settings.optimised);
+        assert!(!settings.reduced_debug_info);
+        assert_eq!(settings.reduced_confidence_reasons().len(), 1);
+    }
+}
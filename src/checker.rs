@@ -1,51 +1,96 @@
 use crate::Args;
 use crate::CheckState;
+use crate::async_runtime::AsyncRuntime;
+use crate::async_runtime::RuntimeBindings;
 use crate::build_script_checker;
+use crate::build_settings::BuildSettings;
+use crate::config::AllowedUsage;
 use crate::config::ApiName;
+use crate::config::ApiSeverity;
+use crate::config::AttributionStrategy;
 use crate::config::Config;
+use crate::config::FFI_API;
+use crate::config::RawConfig;
+use crate::config::RemapPathPrefix;
+use crate::config::ReportMode;
+use crate::config::SandboxKind;
 use crate::config::permissions::PermSel;
 use crate::config::permissions::PermissionScope;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::CrateKind;
+use crate::crate_index::CrateSel;
 use crate::crate_index::PackageId;
+use crate::crate_index::features::FeatureFix;
+use crate::determinism::NondeterminismSource;
+use crate::determinism::NondeterminismSources;
+use crate::disputes::Disputes;
+use crate::explain::SymbolExplanation;
+use crate::facts::Fact;
+use crate::facts::FactStream;
+use crate::feature_gates::FeatureGates;
+use crate::graph::ReferenceGraph;
 use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
+use crate::migration::Baseline;
 use crate::names::Name;
 use crate::names::SymbolOrDebugName;
+use crate::panic_audit::PanicPath;
+use crate::panic_audit::PanicPaths;
+use crate::prebuilt::WrittenExecutable;
 use crate::problem::ApiUsages;
+use crate::problem::BinaryFileInSources;
+use crate::problem::BuildScriptChanged;
 use crate::problem::OffTreeApiUsage;
+use crate::problem::PluginProblem;
 use crate::problem::PossibleExportedApi;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
+use crate::problem::RustcVersionChanged;
 use crate::problem::UnusedAllowApi;
+use crate::problem::UsageBudgetExceeded;
+use crate::problem::VersionPinMismatch;
 use crate::proxy::cargo::profile_name;
 use crate::proxy::rpc;
 use crate::proxy::rpc::UnsafeUsage;
 use crate::proxy::subprocess::SubprocessConfig;
+use crate::resources::ResourceManager;
+use crate::rustc_version::RustcVersion;
 use crate::symbol_graph::NameSource;
 use crate::symbol_graph::UsageDebugData;
+use crate::symbol_graph::UsageOrigin;
 use crate::symbol_graph::backtrace::Backtracer;
 use crate::timing::TimingCollector;
 use crate::tmpdir::TempDir;
+use crate::vfs::RealFs;
+use crate::vfs::Vfs;
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
+use attribution::AttributionQuery;
 use log::info;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use serde::Serialize;
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-mod api_map;
+pub(crate) mod api_map;
+pub(crate) mod attribution;
 pub(crate) mod common_prefix;
+pub(crate) mod config_trial;
+pub(crate) mod path_remap;
 
 pub(crate) struct Checker {
     /// For each name, the set of APIs active for that name and all names that have this name as a
     /// prefix.
     apis_by_prefix: api_map::ApiMap,
+    /// APIs with patterns in their include or exclude lists. These aren't in `apis_by_prefix`.
+    pattern_apis: Vec<(ApiName, api_map::ApiRules)>,
     pub(crate) crate_infos: FxHashMap<PermSel, CrateInfo>,
     config_path: PathBuf,
     pub(crate) config: Arc<Config>,
@@ -55,11 +100,17 @@ pub(crate) struct Checker {
     pub(crate) crate_index: Arc<CrateIndex>,
     pub(crate) sysroot: Arc<Path>,
 
+    /// The version of rustc used for the build, if known.
+    pub(crate) rustc_version: Option<RustcVersion>,
+
     /// Mapping from Rust source paths to the packages that contains them. Generally a source path
     /// will map to a single package, but in rare cases multiple packages could reference the same
     /// path outside of their source tree.
     path_to_pkg_ids: FxHashMap<PathBuf, Vec<PackageId>>,
 
+    /// Path remappings that rustc was asked to apply. See `path_remap.rs`.
+    detected_remaps: Vec<RemapPathPrefix>,
+
     pub(crate) timings: TimingCollector,
 
     backtracers: FxHashMap<Arc<Path>, Backtracer>,
@@ -68,6 +119,97 @@ pub(crate) struct Checker {
     /// corresponding notification that rustc has completed. We defer processing of these until
     /// rustc completes because we need information from the .deps file that rustc writes.
     outstanding_linker_invocations: Vec<LinkInfo>,
+
+    /// Facts about what we've found, for external policy engines.
+    facts: FactStream,
+
+    /// How each crate appears to have been built, based on its debug info.
+    pub(crate) build_settings: FxHashMap<PackageId, BuildSettings>,
+
+    /// The async runtimes that packages bind to, over all binaries that we've analysed.
+    pub(crate) async_runtimes: RuntimeBindings,
+
+    /// The panic paths that packages reference, over all binaries that we've analysed.
+    pub(crate) panic_paths: PanicPaths,
+
+    /// The sources of nondeterminism that packages reference, over all binaries that we've
+    /// analysed. Only populated with `common.determinism_audit`.
+    pub(crate) nondeterminism: NondeterminismSources,
+
+    /// With `--rustc-diagnostics`, diagnostics waiting to be added to the output of rustc for each
+    /// crate.
+    rustc_diagnostics: FxHashMap<CrateSel, Vec<String>>,
+
+    /// Problems that we reported as rustc diagnostics and will report again once the build
+    /// completes.
+    deferred_problems: ProblemList,
+
+    /// Crates whose use of unsafe we deferred reporting. The flattened config permits unsafe for
+    /// these, otherwise rustc would keep failing and we'd keep being asked about the same unsafe.
+    unsafe_deferred: FxHashSet<CrateSel>,
+
+    /// For `cackle graph`, the graph of references in each binary that we've analysed.
+    reference_graphs: Vec<ReferenceGraph>,
+    /// For `cackle explain-symbol`, what we found out about the symbol in each binary.
+    symbol_explanations: Vec<SymbolExplanation>,
+
+    /// Where we read binaries, object files and config from.
+    pub(crate) vfs: Arc<dyn Vfs>,
+
+    /// The cfg attributes that we've found in source files. See `feature_gates.rs`.
+    feature_gates: FeatureGates,
+
+    /// How much work we can do at once, shared with the proxy.
+    pub(crate) resources: Arc<ResourceManager>,
+
+    /// When in migration mode, the problems that are only warnings.
+    pub(crate) migration_baseline: Option<Arc<Baseline>>,
+
+    /// Problems that the user has said look wrong. See `disputes.rs`.
+    disputes: Disputes,
+
+    /// For each native library, the packages whose build scripts asked for it to be linked.
+    native_lib_requesters: FxHashMap<String, Vec<PackageId>>,
+
+    /// The OUT_DIR of each package that has a build script. Object files and archives in one of
+    /// these were built by that package's build script.
+    out_dir_pkg_ids: FxHashMap<PathBuf, PackageId>,
+
+    /// A second configuration that we're evaluating what we find against. See `--with-config`.
+    config_trial: Option<config_trial::ConfigTrial>,
+
+    /// Packages for which rustc has completed building at least one crate.
+    compiled_packages: FxHashSet<PackageId>,
+
+    /// Digests of the sources of build scripts that have been linked and that will run in a
+    /// sandbox, so have their pins checked once they've run. See `build_script_linked`.
+    build_script_sources: FxHashMap<PackageId, String>,
+
+    /// Packages for which we found what they export from rustdoc's output.
+    rustdoc_documented: FxHashSet<PackageId>,
+
+    /// Packages for which we've analysed a binary built for an artifact dependency.
+    analysed_artifacts: FxHashSet<PackageId>,
+}
+
+/// Returns the pseudo-APIs, together with the paths that they include. Pseudo-APIs are how we find
+/// references to things like async runtimes, panic machinery, the allocator and denied items. Their
+/// usages are collected separately rather than being checked against permissions.
+pub(crate) fn pseudo_apis(config: &RawConfig) -> Vec<(ApiName, Cow<'static, str>)> {
+    let determinism = config
+        .common
+        .determinism_audit
+        .then(NondeterminismSource::pseudo_apis);
+    AsyncRuntime::pseudo_apis()
+        .chain(PanicPath::pseudo_apis())
+        .chain(crate::alloc_audit::pseudo_apis())
+        .chain(determinism.into_iter().flatten())
+        .map(|(api_name, path)| (api_name, Cow::Borrowed(path)))
+        .chain(
+            crate::deny_list::pseudo_apis(&config.deny)
+                .map(|(api_name, path)| (api_name, Cow::Owned(path))),
+        )
+        .collect()
 }
 
 #[derive(Default, Debug)]
@@ -78,6 +220,10 @@ pub(crate) struct CrateInfo {
     /// APIs that are allowed for this crate according to cackle.toml, but haven't yet been found to
     /// be used by the crate.
     unused_allowed_apis: FxHashSet<ApiName>,
+
+    /// For APIs with a `max_usages` budget, the source locations from which the crate uses them
+    /// over all the binaries that we've checked. Columns are dropped, so each line counts once.
+    budgeted_usages: FxHashMap<ApiName, FxHashSet<SourceLocation>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -92,7 +238,96 @@ pub(crate) struct ApiUsage {
     pub(crate) to: SymbolOrDebugName,
     pub(crate) to_name: Name,
     pub(crate) to_source: NameSource<'static>,
+    /// How `from` referenced `to`.
+    pub(crate) kind: ReferenceKind,
+    pub(crate) origin: UsageOrigin,
     pub(crate) debug_data: Option<UsageDebugData>,
+    /// Functions from the same crate through which `from` is reached, outermost first. See
+    /// `call_path`.
+    pub(crate) callers: Vec<SymbolOrDebugName>,
+    /// The functions that were inlined into one another to produce the code containing the usage,
+    /// outermost first. Empty if the code wasn't inlined. e.g. a call to `std::fs::write` from a
+    /// `tracing::event!` in `my_fn` would have frames for `my_fn`, `tracing::event` and possibly
+    /// `std::fs::write`.
+    pub(crate) inline_chain: Vec<InlineFrame>,
+}
+
+/// A function in the chain of inlined functions that led to an API usage.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct InlineFrame {
+    pub(crate) function: SymbolOrDebugName,
+    /// Where in `function` the next function was inlined, or for the innermost function, where the
+    /// usage is.
+    pub(crate) location: Option<SourceLocation>,
+}
+
+/// What kind of reference an API usage was, as determined from the relocation, or instruction,
+/// and from the sections containing the reference and its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReferenceKind {
+    /// A direct call or jump to a function, including calls that were inlined.
+    Call,
+
+    /// The address of the target was taken, e.g. to access a static or to store a function
+    /// pointer in a table.
+    Data,
+
+    /// A function pointer in a vtable, so the function may be called via a trait object.
+    VTable,
+
+    /// A system call instruction in the function's machine code. There's no target symbol.
+    Syscall,
+
+    /// Inline assembly in the function, or the function was written in assembly. There's no
+    /// target symbol.
+    Asm,
+
+    /// The function runs before `main`, since a section like `.init_array` points to it. There's
+    /// no target symbol.
+    BeforeMain,
+}
+
+impl ReferenceKind {
+    /// Classifies a reference. `branch` is whether the relocation or instruction was for a call or
+    /// jump. `from_executable` and `target_executable` are whether the reference and its target are
+    /// in executable sections. `via_vtable` is whether the target was found by looking through a
+    /// vtable that the reference pointed to.
+    pub(crate) fn classify(
+        branch: bool,
+        from_executable: bool,
+        via_vtable: bool,
+        target_executable: bool,
+    ) -> Self {
+        if via_vtable && target_executable {
+            ReferenceKind::VTable
+        } else if from_executable && branch {
+            ReferenceKind::Call
+        } else {
+            ReferenceKind::Data
+        }
+    }
+}
+
+impl std::fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceKind::Call => write!(f, "call"),
+            ReferenceKind::Data => write!(f, "data reference"),
+            ReferenceKind::VTable => write!(f, "vtable entry"),
+            ReferenceKind::Syscall => write!(f, "syscall instruction"),
+            ReferenceKind::Asm => write!(f, "inline assembly"),
+            ReferenceKind::BeforeMain => write!(f, "runs before main"),
+        }
+    }
+}
+
+impl ApiUsage {
+    /// Returns a chain of calls leading to this usage, starting from a function that isn't called
+    /// from elsewhere in the same crate and ending with `from`.
+    pub(crate) fn call_path(&self) -> impl Iterator<Item = &SymbolOrDebugName> {
+        self.callers.iter().chain(std::iter::once(&self.from))
+    }
 }
 
 /// A location within a bin file (executable or shared object).
@@ -109,12 +344,19 @@ impl Checker {
         target_dir: PathBuf,
         args: Arc<Args>,
         sysroot: Arc<Path>,
+        rustc_version: Option<RustcVersion>,
         crate_index: Arc<CrateIndex>,
         config_path: PathBuf,
     ) -> Self {
         let timings = TimingCollector::new(args.print_timing);
+        let facts = if args.consumes_facts() {
+            FactStream::new(args.facts_output.clone())
+        } else {
+            FactStream::default()
+        };
         Self {
             apis_by_prefix: Default::default(),
+            pattern_apis: Vec::new(),
             crate_infos: Default::default(),
             config_path,
             config: Default::default(),
@@ -123,71 +365,133 @@ impl Checker {
             args,
             crate_index,
             path_to_pkg_ids: Default::default(),
+            detected_remaps: Vec::new(),
             timings,
             backtracers: Default::default(),
             outstanding_linker_invocations: Default::default(),
             sysroot,
+            rustc_version,
+            facts,
+            build_settings: Default::default(),
+            async_runtimes: Default::default(),
+            panic_paths: Default::default(),
+            nondeterminism: Default::default(),
+            rustc_diagnostics: Default::default(),
+            deferred_problems: Default::default(),
+            unsafe_deferred: FxHashSet::default(),
+            reference_graphs: Vec::new(),
+            symbol_explanations: Vec::new(),
+            vfs: Arc::new(RealFs),
+            feature_gates: FeatureGates::default(),
+            resources: Arc::default(),
+            migration_baseline: None,
+            disputes: Disputes::default(),
+            native_lib_requesters: Default::default(),
+            out_dir_pkg_ids: Default::default(),
+            config_trial: None,
+            compiled_packages: FxHashSet::default(),
+            rustdoc_documented: FxHashSet::default(),
+            build_script_sources: FxHashMap::default(),
+            analysed_artifacts: FxHashSet::default(),
         }
     }
 
     /// Load (or reload) config. Note in the case of reloading, APIs are only ever additive.
     pub(crate) fn load_config(&mut self) -> Result<()> {
-        let config = crate::config::parse_file(&self.config_path, &self.crate_index)?;
-        // Every time we reload our configuration, we rewrite the flattened configuration. The
-        // flattened configuration is used by subprocesses rather than using the original
-        // configuration since using the original would require each subprocess to run `cargo
-        // metadata`.
+        let config =
+            crate::config::parse_file(&self.config_path, &self.crate_index, self.vfs.as_ref())?;
+        self.write_flattened_config(&config)?;
+
+        self.migration_baseline = match &config.raw.migration_mode {
+            Some(migration_mode) => Baseline::load(
+                migration_mode,
+                self.config_path.parent().unwrap_or(Path::new(".")),
+            )?
+            .map(Arc::new),
+            None => None,
+        };
+        // Problems can be disputed from the UI without the config changing, so we reload these
+        // whenever we reload the config.
+        self.disputes = Disputes::load(&crate::disputes::path(&self.config_path))?;
+        self.update_config(config);
+        info!("Config (re)loaded");
+        Ok(())
+    }
+
+    /// Every time we reload our configuration, we rewrite the flattened configuration. The
+    /// flattened configuration is used by subprocesses rather than using the original configuration
+    /// since using the original would require each subprocess to run `cargo metadata`.
+    fn write_flattened_config(&self, config: &Config) -> Result<()> {
         let flattened_path = crate::config::flattened_config_path(self.tmpdir.path());
         if let Some(dir) = flattened_path.parent() {
             std::fs::create_dir_all(dir)
                 .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
         }
+        let mut subprocess_config = SubprocessConfig::from_full_config(config);
+        for crate_sel in &self.unsafe_deferred {
+            subprocess_config.permit_unsafe(crate_sel);
+        }
         // A subprocess might try to read the flattened config while we're updating it. It doesn't
         // matter if it sees the old or the new flattened config, but we don't want it to see a
         // partially written config, so we write first to a temporary file then rename it.
-        crate::fs::write_atomic(
-            &flattened_path,
-            &SubprocessConfig::from_full_config(&config).serialise()?,
-        )?;
-
-        self.update_config(config);
-        info!("Config (re)loaded");
-        Ok(())
+        crate::fs::write_atomic(&flattened_path, &subprocess_config.serialise()?)
     }
 
     pub(crate) fn print_timing(&self) {
         println!("{}", self.timings);
     }
 
+    /// Returns the directory in which we cache what we found in each object file, or `None` if
+    /// the cache is disabled.
+    pub(crate) fn scan_cache_dir(&self) -> Option<PathBuf> {
+        (!self.args.no_scan_cache).then(|| self.target_dir.join("cackle-scan-cache"))
+    }
+
     pub(crate) fn get_backtracer(&self, bin_path: &Path) -> Option<&Backtracer> {
         self.backtracers.get(bin_path)
     }
 
-    fn update_config(&mut self, config: Arc<Config>) {
+    pub(crate) fn update_config(&mut self, config: Arc<Config>) {
+        crate::name_display::set_policy(&config.raw.display);
         self.apis_by_prefix.clear();
-        for api in config.raw.apis.values() {
-            for path in api.include.iter().chain(api.exclude.iter()) {
-                self.apis_by_prefix
-                    .create_entry(crate::names::split_simple(&path.prefix).parts())
-            }
-        }
+        self.pattern_apis.clear();
+        let mut prefix_apis = Vec::new();
         for (api_name, api) in &config.raw.apis {
-            for path in &api.include {
-                let name = &crate::names::split_simple(&path.prefix);
-                self.apis_by_prefix
-                    .mut_tree(name.parts())
-                    .update_subtree(&|apis| {
-                        apis.insert(api_name.clone());
-                    });
+            let rules = api_map::ApiRules::new(api);
+            if rules.has_patterns() {
+                self.pattern_apis.push((api_name.clone(), rules));
+                continue;
             }
+            for (name, _) in rules.prefixes() {
+                self.apis_by_prefix.create_entry(name.parts());
+            }
+            prefix_apis.push((api_name, rules));
+        }
+        let pseudo_apis: Vec<(ApiName, Name)> = pseudo_apis(&config.raw)
+            .into_iter()
+            .map(|(api_name, path)| (api_name, crate::names::split_simple(&path)))
+            .collect();
+        for (_, name) in &pseudo_apis {
+            self.apis_by_prefix.create_entry(name.parts());
         }
-        for (api_name, api_config) in &config.raw.apis {
-            for path in &api_config.exclude {
-                let name = &crate::names::split_simple(&path.prefix);
+        for (api_name, name) in &pseudo_apis {
+            self.apis_by_prefix
+                .mut_tree(name.parts())
+                .update_subtree(&|apis| {
+                    apis.insert(api_name.clone());
+                });
+        }
+        // Each prefix overrides any less specific prefixes for the same API.
+        for (api_name, rules) in &prefix_apis {
+            for (name, exclude) in rules.prefixes() {
                 self.apis_by_prefix
                     .mut_tree(name.parts())
                     .update_subtree(&|apis| {
-                        apis.remove(api_name);
+                        if exclude {
+                            apis.remove(*api_name);
+                        } else {
+                            apis.insert((*api_name).clone());
+                        }
                     });
             }
         }
@@ -203,17 +507,29 @@ impl Checker {
         }
         // Then process with inheritance, but leaving unused_allow_apis alone. We don't want to get
         // warnings that an allow_api was unused when it was inherited and was actually used
-        // elsewhere in the inheritance tree.
+        // elsewhere in the inheritance tree. APIs that depend on a feature are also left out of
+        // unused_allow_apis, since whether they're used can depend on the feature.
         for (perm_sel, crate_config) in &config.permissions.packages {
             let crate_info = self.crate_infos.entry(perm_sel.clone()).or_default();
             for api in &crate_config.allow_apis {
                 crate_info.allowed_apis.insert(api.clone());
             }
+            for (feature, feature_config) in &crate_config.if_feature {
+                if self
+                    .crate_index
+                    .features
+                    .is_enabled(&perm_sel.package_name.0, feature)
+                {
+                    for api in &feature_config.allow_apis {
+                        crate_info.allowed_apis.insert(api.clone());
+                    }
+                }
+            }
         }
         self.config = config;
     }
 
-    fn base_problems(&self) -> ProblemList {
+    fn base_problems(&self) -> Result<ProblemList> {
         let mut problems = ProblemList::default();
         for pkg_id in self.crate_index.proc_macros() {
             if !self
@@ -225,7 +541,87 @@ impl Checker {
                 problems.push(Problem::IsProcMacro(pkg_id.clone()));
             }
         }
-        problems
+        if self.config.raw.common.detect_binary_files {
+            problems.merge(self.binary_file_problems()?);
+        }
+        Ok(problems)
+    }
+
+    /// Reports APIs that a package might export, based on the public items that rustdoc found at
+    /// the top level of its library. Since these are only warnings, we report them when the build
+    /// completes. If we can't read rustdoc's output, we fall back to guessing from symbol names.
+    fn check_rustdoc_exports(&mut self, pkg_id: &PackageId, json_path: &Path) {
+        if self.rustdoc_documented.contains(pkg_id) {
+            return;
+        }
+        let exports = match crate::rustdoc_exports::read_exports(json_path) {
+            Ok(exports) => exports,
+            Err(error) => {
+                log::warn!("{error:#}");
+                return;
+            }
+        };
+        let possible_exports: Vec<_> = self
+            .config
+            .raw
+            .apis
+            .keys()
+            .filter(|api| exports.names.contains(api.name.as_ref()))
+            .map(|api| PossibleExportedApi {
+                pkg_id: pkg_id.clone(),
+                api: api.clone(),
+                symbol: None,
+            })
+            .collect();
+        let mut problems = ProblemList::default();
+        self.possible_exported_api_problems(&possible_exports, &mut problems);
+        self.deferred_problems.merge(problems);
+        self.rustdoc_documented.insert(pkg_id.clone());
+    }
+
+    /// Returns whether we've got rustdoc's view of what `pkg_id` exports.
+    pub(crate) fn has_rustdoc_exports(&self, pkg_id: &PackageId) -> bool {
+        self.rustdoc_documented.contains(pkg_id)
+    }
+
+    /// Reports binary files in the sources of packages other than workspace members, unless the
+    /// package is allowed them.
+    fn binary_file_problems(&self) -> Result<ProblemList> {
+        let mut problems = ProblemList::default();
+        for pkg_id in self.crate_index.package_ids() {
+            if self.crate_index.is_workspace_member(pkg_id) {
+                continue;
+            }
+            let Some(pkg_dir) = self.crate_index.pkg_dir(pkg_id) else {
+                continue;
+            };
+            let allowed = self
+                .config
+                .permissions
+                .get(&PermSel::for_primary(pkg_id.pkg_name()))
+                .map(|pkg_config| pkg_config.allow_binary_files.as_slice())
+                .unwrap_or_default();
+            let found = crate::binary_files::find_binary_files(pkg_dir);
+            for unreadable in found.unreadable {
+                problems.push(Problem::new(format!(
+                    "Couldn't read `{}` in the sources of `{pkg_id}` to check whether it's a \
+                     binary: {}",
+                    unreadable.path.display(),
+                    unreadable.error
+                )));
+            }
+            for file in found.binaries {
+                if !allowed.contains(&file.sha256) {
+                    problems.push(Problem::BinaryFileInSources(BinaryFileInSources {
+                        pkg_id: pkg_id.clone(),
+                        path: file.path,
+                        kind: file.kind,
+                        sha256: file.sha256,
+                    }));
+                }
+            }
+        }
+        Ok(problems)
     }
 
     pub(crate) fn handle_request(
@@ -234,10 +630,20 @@ impl Checker {
         check_state: &mut CheckState,
     ) -> Result<ProblemList> {
         let Some(request) = request else {
-            return Ok(self.base_problems());
+            let problems = self.base_problems()?;
+            return Ok(self.defer_problems_for_baseline(problems));
         };
         match request {
-            rpc::Request::CrateUsesUnsafe(usage) => Ok(self.crate_uses_unsafe(usage)),
+            rpc::Request::CrateUsesUnsafe(usage) => {
+                let problems = self.crate_uses_unsafe(usage)?;
+                let problems = self.defer_disputed_problems(problems);
+                let problems = self.defer_baselined_problems(problems);
+                let problems = self.defer_problems_for_baseline(problems);
+                if problems.is_empty() && self.unsafe_deferred.insert(usage.crate_sel.clone()) {
+                    self.write_flattened_config(&self.config)?;
+                }
+                Ok(problems)
+            }
             rpc::Request::LinkerInvoked(link_info) => {
                 self.outstanding_linker_invocations.push(link_info.clone());
                 Ok(ProblemList::default())
@@ -252,23 +658,58 @@ impl Checker {
                         .into(),
                     )
                 } else if output.crate_sel.kind == CrateKind::BuildScript {
-                    let report =
+                    let mut report =
                         build_script_checker::BuildScriptReport::build(output, &self.config)?;
+                    if let Some(digest) = self.build_script_sources.get(&output.crate_sel.pkg_id) {
+                        report.problems.merge(self.verify_build_script_pin(
+                            &output.crate_sel.pkg_id,
+                            digest,
+                            &output.written_executables,
+                        ));
+                    }
                     crate::sandbox::write_env_vars(
                         &self.target_dir,
                         profile_name(&self.args, &self.config.raw.common),
                         &output.crate_sel,
                         &report.env_vars,
                     )?;
-                    Ok(report.problems)
+                    Ok(self.defer_problems_for_baseline(report.problems))
                 } else {
                     Ok(ProblemList::default())
                 }
             }
             rpc::Request::RustcComplete(info) => {
                 self.record_crate_paths(info)?;
+                self.compiled_packages.insert(info.crate_sel.pkg_id.clone());
+                if let Some(json_path) = &info.rustdoc_json {
+                    self.check_rustdoc_exports(&info.crate_sel.pkg_id, json_path);
+                }
+                if let Some(trial) = &mut self.config_trial {
+                    trial.record_crate_paths(info)?;
+                }
+                for library in &info.link_libs {
+                    let requesters = self
+                        .native_lib_requesters
+                        .entry(library.clone())
+                        .or_default();
+                    if !requesters.contains(&info.crate_sel.pkg_id) {
+                        requesters.push(info.crate_sel.pkg_id.clone());
+                    }
+                }
                 if let Some(link_info) = self.get_link_info(info) {
-                    let problems = self.check_linker_invocation(&link_info, check_state)?;
+                    if self.args.is_packing_artifacts() {
+                        // The binary will be analysed once the bundle is unpacked.
+                        return Ok(ProblemList::default());
+                    }
+                    let mut problems =
+                        self.check_linker_invocation(&link_info, info, check_state)?;
+                    if self.args.rustc_diagnostics {
+                        problems = self.defer_to_rustc_diagnostics(&info.crate_sel, problems);
+                    }
+                    problems = self.defer_disputed_problems(problems);
+                    problems = self.defer_baselined_problems(problems);
+                    problems = self.defer_warnings(problems);
+                    problems = self.defer_problems_for_baseline(problems);
                     if !problems.is_empty() {
                         // Since we found some problems, add our LinkInfo back so that if we fix the
                         // problems via the UI we can recheck once we have fixes.
@@ -283,18 +724,162 @@ impl Checker {
                 info!("Rustc started compiling {crate_sel}");
                 Ok(ProblemList::default())
             }
+            rpc::Request::TakeDiagnostics(_) | rpc::Request::AcquirePermit(_) => {
+                Ok(ProblemList::default())
+            }
+        }
+    }
+
+    /// Converts disallowed API usages by workspace members into diagnostics, to be added to the
+    /// output of rustc for `crate_sel`, and defers them until the build completes, so as not to
+    /// block the build. Returns the remaining problems.
+    fn defer_to_rustc_diagnostics(
+        &mut self,
+        crate_sel: &CrateSel,
+        problems: ProblemList,
+    ) -> ProblemList {
+        let workspace_root = self
+            .crate_index
+            .manifest_path
+            .parent()
+            .unwrap_or(Path::new(""));
+        let mut remaining = ProblemList::default();
+        for problem in problems.take() {
+            match &problem {
+                Problem::DisallowedApiUsage(usages)
+                    if self.crate_index.is_workspace_member(&usages.pkg_id) =>
+                {
+                    self.rustc_diagnostics
+                        .entry(crate_sel.clone())
+                        .or_default()
+                        .extend(crate::rustc_diagnostics::for_api_usages(
+                            usages,
+                            workspace_root,
+                        ));
+                    self.deferred_problems.push(problem);
+                }
+                _ => remaining.push(problem),
+            }
         }
+        remaining
+    }
+
+    /// Defers problems that are in the migration baseline until the build completes. They'll then
+    /// be reported as warnings, so shouldn't block the build or cause the binary to be rechecked.
+    /// Returns the remaining problems.
+    fn defer_baselined_problems(&mut self, problems: ProblemList) -> ProblemList {
+        let Some(baseline) = self.migration_baseline.clone() else {
+            return problems;
+        };
+        let mut remaining = ProblemList::default();
+        for problem in problems.take() {
+            if baseline.contains(&problem) {
+                self.deferred_problems.push(problem);
+            } else {
+                remaining.push(problem);
+            }
+        }
+        remaining
+    }
+
+    /// When we're saving a baseline, defers all problems until the build completes, since they're
+    /// all going into the baseline rather than being fixed. Otherwise returns `problems`.
+    fn defer_problems_for_baseline(&mut self, problems: ProblemList) -> ProblemList {
+        if self.args.baseline_options().is_none() {
+            return problems;
+        }
+        self.deferred_problems.merge(problems);
+        ProblemList::default()
+    }
+
+    /// Defers problems that are only warnings until the build completes, so that they don't block
+    /// the build. Returns the remaining problems.
+    fn defer_warnings(&mut self, problems: ProblemList) -> ProblemList {
+        let mut remaining = ProblemList::default();
+        for problem in problems.take() {
+            if matches!(problem, Problem::Warning(..)) {
+                self.deferred_problems.push(problem);
+            } else {
+                remaining.push(problem);
+            }
+        }
+        remaining
+    }
+
+    /// Excludes problems that the user has disputed, so that the build can proceed. They're reported
+    /// again as warnings once the build completes. Returns the remaining problems.
+    fn defer_disputed_problems(&mut self, problems: ProblemList) -> ProblemList {
+        let mut remaining = ProblemList::default();
+        for problem in problems.take() {
+            if self.disputes.contains(&problem) {
+                self.deferred_problems
+                    .push(Problem::Disputed(Box::new(problem)));
+            } else {
+                remaining.push(problem);
+            }
+        }
+        remaining
+    }
+
+    /// Returns the scope of permissions that apply to code from `pkg_id` in the binary `bin_sel`.
+    /// Packages that are only dependencies of tests can be configured to always use test
+    /// permissions, even if they end up in a non-test binary.
+    pub(crate) fn permission_scope(
+        &self,
+        pkg_id: &PackageId,
+        bin_sel: &CrateSel,
+    ) -> PermissionScope {
+        let scope = PermissionScope::determine(pkg_id, bin_sel);
+        if scope == PermissionScope::All
+            && self.config.raw.common.isolate_test_only_deps
+            && self.crate_index.is_test_only(pkg_id)
+        {
+            return PermissionScope::FromTest;
+        }
+        scope
+    }
+
+    /// Returns which API usages to report, taking into account any override on the command line.
+    pub(crate) fn report_mode(&self) -> ReportMode {
+        self.args.report.unwrap_or(self.config.raw.common.report)
+    }
+
+    pub(crate) fn take_reference_graphs(&mut self) -> Vec<ReferenceGraph> {
+        std::mem::take(&mut self.reference_graphs)
+    }
+
+    pub(crate) fn symbol_explanations(&self) -> &[SymbolExplanation] {
+        &self.symbol_explanations
+    }
+
+    /// Returns the diagnostics that are waiting to be added to the output of rustc for `crate_sel`.
+    pub(crate) fn take_rustc_diagnostics(&mut self, crate_sel: &CrateSel) -> Vec<String> {
+        self.rustc_diagnostics.remove(crate_sel).unwrap_or_default()
+    }
+
+    /// Returns problems that were deferred until the build completed.
+    pub(crate) fn take_deferred_problems(&mut self) -> ProblemList {
+        std::mem::take(&mut self.deferred_problems)
     }
 
     fn check_linker_invocation(
         &mut self,
         info: &LinkInfo,
+        rustc_output: &rpc::RustcOutput,
         check_state: &mut CheckState,
     ) -> Result<ProblemList> {
-        let start = std::time::Instant::now();
+        let start = self.timings.now();
         let mut problems = ProblemList::default();
         if info.crate_sel.kind == CrateKind::BuildScript {
             problems.merge(self.verify_build_script_permitted(&info.crate_sel.pkg_id));
+            problems.merge(self.build_script_linked(rustc_output)?);
+        }
+        if self.config.raw.common.check_native_libs {
+            problems.merge(self.check_native_libs(info)?);
+        }
+        if info.is_artifact_dependency() {
+            self.analysed_artifacts
+                .insert(info.crate_sel.pkg_id.clone());
         }
         problems.merge(self.check_object_paths(
             &info.object_paths_under(&self.target_dir),
@@ -329,6 +914,45 @@ impl Checker {
             let (mut graph_outputs, backtracer) =
                 crate::symbol_graph::scan_objects(paths, link_info, self)?;
             graph_outputs.apis = self.config.raw.apis.clone();
+            for (pkg_id, settings) in &graph_outputs.build_settings {
+                self.build_settings
+                    .entry(pkg_id.clone())
+                    .and_modify(|existing| existing.merge(settings))
+                    .or_insert_with(|| settings.clone());
+            }
+            for (runtime, pkg_ids) in &graph_outputs.async_runtimes {
+                self.async_runtimes
+                    .entry(*runtime)
+                    .or_default()
+                    .extend(pkg_ids.iter().cloned());
+            }
+            for (source, pkg_ids) in &graph_outputs.nondeterminism {
+                self.nondeterminism
+                    .entry(*source)
+                    .or_default()
+                    .extend(pkg_ids.iter().cloned());
+            }
+            for (kind, pkg_ids) in graph_outputs.panic_paths() {
+                self.panic_paths
+                    .entry(kind)
+                    .or_default()
+                    .extend(pkg_ids.into_iter().cloned());
+            }
+            if self.facts.is_enabled() {
+                for api_usages in graph_outputs.api_usages() {
+                    for usage in &api_usages.usages {
+                        self.facts.emit(Fact::api_usage(api_usages, usage))?;
+                    }
+                }
+                for symbol in graph_outputs.symbols.drain(..) {
+                    self.facts.emit(Fact::Symbol(symbol))?;
+                }
+            }
+            if let Some(graph) = graph_outputs.reference_graph.take() {
+                self.reference_graphs.push(graph);
+            }
+            self.symbol_explanations
+                .append(&mut graph_outputs.symbol_explanations);
             check_state.graph_outputs = Some(graph_outputs);
             if let Some(b) = backtracer {
                 self.backtracers.insert(link_info.output_file.clone(), b);
@@ -336,11 +960,19 @@ impl Checker {
         }
         let graph_outputs = check_state.graph_outputs.as_ref().unwrap();
         let problems = graph_outputs.problems(self)?;
+        if let Some(mut trial) = self.config_trial.take() {
+            let result = trial.check(paths, link_info, graph_outputs, &problems);
+            self.config_trial = Some(trial);
+            result?;
+        }
         Ok(problems)
     }
 
-    pub(crate) fn crate_uses_unsafe(&self, usage: &UnsafeUsage) -> ProblemList {
-        Problem::DisallowedUnsafe(usage.clone()).into()
+    pub(crate) fn crate_uses_unsafe(&mut self, usage: &UnsafeUsage) -> Result<ProblemList> {
+        if self.facts.is_enabled() {
+            self.facts.emit(Fact::unsafe_usage(usage))?;
+        }
+        Ok(Problem::DisallowedUnsafe(usage.clone()).into())
     }
 
     pub(crate) fn verify_build_script_permitted(&mut self, pkg_id: &PackageId) -> ProblemList {
@@ -356,6 +988,84 @@ impl Checker {
         Problem::UsesBuildScript(pkg_id.clone()).into()
     }
 
+    /// Returns whether the build script for `pkg_id` needs to match a pinned hash.
+    fn build_script_pinned(&self, pkg_id: &PackageId) -> bool {
+        self.config.raw.common.pin_build_scripts || self.pinned_build_script_hash(pkg_id).is_some()
+    }
+
+    fn pinned_build_script_hash(&self, pkg_id: &PackageId) -> Option<&String> {
+        self.config
+            .permissions
+            .get(&PermSel::for_build_script(pkg_id.name_str()))
+            .and_then(|pkg_config| pkg_config.sha256.as_ref())
+    }
+
+    /// Called when the build script compiled by `output` has been linked. Its pin covers its sources
+    /// and any executables that it writes. If it'll run in a sandbox, then we'll find out what it
+    /// wrote once it has run, so we check the pin then. Otherwise, we check the pin now.
+    fn build_script_linked(&mut self, output: &rpc::RustcOutput) -> Result<ProblemList> {
+        let pkg_id = &output.crate_sel.pkg_id;
+        if !self.build_script_pinned(pkg_id) {
+            return Ok(ProblemList::default());
+        }
+        let pkg_dir = self
+            .crate_index
+            .pkg_dir(pkg_id)
+            .ok_or_else(|| anyhow!("Missing directory for `{pkg_id}`"))?;
+        let digest = build_script_checker::sources_digest(pkg_dir, &output.source_paths)?;
+        let sandboxed = self
+            .config
+            .permissions
+            .sandbox_config_for_package(&PermSel::for_build_script(pkg_id.name_str()))
+            .kind
+            .is_some_and(|kind| kind != SandboxKind::Disabled);
+        if sandboxed {
+            self.build_script_sources.insert(pkg_id.clone(), digest);
+            return Ok(ProblemList::default());
+        }
+        Ok(self.verify_build_script_pin(pkg_id, &digest, &[]))
+    }
+
+    /// Checks the pin for the build script of `pkg_id`, given the digest of its sources and the
+    /// executables that it wrote.
+    fn verify_build_script_pin(
+        &self,
+        pkg_id: &PackageId,
+        sources_digest: &str,
+        executables: &[WrittenExecutable],
+    ) -> ProblemList {
+        if !self.build_script_pinned(pkg_id) {
+            return ProblemList::default();
+        }
+        let expected = self.pinned_build_script_hash(pkg_id);
+        let sha256 = build_script_checker::pin_hash(sources_digest, executables);
+        if expected == Some(&sha256) {
+            return ProblemList::default();
+        }
+        Problem::BuildScriptChanged(BuildScriptChanged {
+            pkg_id: pkg_id.clone(),
+            expected: expected.cloned(),
+            sha256,
+        })
+        .into()
+    }
+
+    /// Checks the native shared libraries that the binary linked by `info` depends on.
+    fn check_native_libs(&self, info: &LinkInfo) -> Result<ProblemList> {
+        let bytes = self
+            .vfs
+            .read(&info.output_file)
+            .with_context(|| format!("Failed to read `{}`", info.output_file.display()))?;
+        let needed = crate::native_libs::needed_libraries(&bytes, &info.output_file)?;
+        Ok(crate::native_libs::check(
+            &needed,
+            &self.native_lib_requesters,
+            &info.crate_sel,
+            &info.output_file,
+            &self.config,
+        ))
+    }
+
     pub(crate) fn pkg_ids_from_source_path<'checker>(
         &'checker self,
         source_path: &Path,
@@ -368,21 +1078,67 @@ impl Checker {
         &'checker self,
         source_path: &Path,
     ) -> Option<Cow<'checker, [PackageId]>> {
-        self.path_to_pkg_ids
-            .get(source_path)
-            .map(|ids| Cow::Borrowed(ids.as_slice()))
-            .or_else(|| {
-                // If the source path is from the rust standard library, or from one of the
-                // precompiled crates that comes with the standard library, then report no crates.
-                if self.is_in_rust_std(source_path) {
-                    return Some(Cow::Owned(vec![]));
-                }
+        self.opt_pkg_ids(&AttributionQuery::for_path(source_path))
+    }
 
-                // Fall-back to just finding the package that contains the source path.
-                self.crate_index
-                    .package_id_for_path(source_path)
-                    .map(|pkg_id| Cow::Owned(vec![pkg_id.clone()]))
-            })
+    pub(crate) fn pkg_ids<'checker>(
+        &'checker self,
+        query: &AttributionQuery,
+    ) -> Result<Cow<'checker, [PackageId]>> {
+        self.opt_pkg_ids(query).ok_or_else(|| {
+            anyhow!(
+                "Couldn't find crate name for {}",
+                query.source_path.display()
+            )
+        })
+    }
+
+    /// Returns the packages that the code described by `query` belongs to, according to the first
+    /// of the configured attribution strategies that gives an answer.
+    pub(crate) fn opt_pkg_ids<'checker>(
+        &'checker self,
+        query: &AttributionQuery,
+    ) -> Option<Cow<'checker, [PackageId]>> {
+        let source_path = self.unremapped_path(query.source_path);
+        let query = AttributionQuery {
+            source_path: &source_path,
+            name_crate: query.name_crate,
+            crate_root: query
+                .crate_root
+                .as_deref()
+                .map(|root| self.unremapped_path(root).into_owned()),
+        };
+        self.config
+            .raw
+            .common
+            .attribution_strategies()
+            .iter()
+            .find_map(|strategy| strategy.attribution().pkg_ids(&query, self))
+    }
+
+    /// Returns the packages that the code described by `query` belongs to, going only by the crate
+    /// named by the function containing the code. Used for binaries without debug info, where we
+    /// don't know which source file code came from. Code from crates that we don't know, or from
+    /// the standard library, isn't attributed to any package.
+    pub(crate) fn pkg_ids_from_name<'checker>(
+        &'checker self,
+        query: &AttributionQuery,
+    ) -> Cow<'checker, [PackageId]> {
+        AttributionStrategy::SymbolPrefix
+            .attribution()
+            .pkg_ids(query, self)
+            .unwrap_or_default()
+    }
+
+    /// Returns the package whose build script wrote `path`, going by whether `path` is in the
+    /// package's OUT_DIR.
+    pub(crate) fn pkg_id_for_out_dir_path(&self, path: &Path) -> Option<&PackageId> {
+        if self.out_dir_pkg_ids.is_empty() {
+            return None;
+        }
+        path.ancestors()
+            .skip(1)
+            .find_map(|dir| self.out_dir_pkg_ids.get(dir))
     }
 
     // Returns whether `source_path` is from the rust standard library or precompiled crates that are
@@ -394,6 +1150,9 @@ impl Checker {
             // Post 2023-10-26
             || source_path.starts_with("/rust/")
             || source_path.starts_with(&self.sysroot)
+            // Sanitizer runtimes come with the compiler, so are treated like the standard library.
+            || (self.config.raw.common.ignore_instrumentation
+                && crate::instrumentation::is_runtime_source(source_path))
     }
 
     /// Returns all APIs that are matched by `name`. e.g. The name `["std", "fs", "write"]` might
@@ -401,8 +1160,52 @@ impl Checker {
     pub(crate) fn apis_for_name_iterator<'a>(
         &self,
         key_it: impl Iterator<Item = &'a str>,
-    ) -> &FxHashSet<ApiName> {
-        self.apis_by_prefix.get(key_it)
+    ) -> Cow<'_, FxHashSet<ApiName>> {
+        if self.pattern_apis.is_empty() {
+            return Cow::Borrowed(self.apis_by_prefix.get(key_it));
+        }
+        let parts: Vec<&str> = key_it.collect();
+        let mut apis = Cow::Borrowed(self.apis_by_prefix.get(parts.iter().copied()));
+        if parts.is_empty() {
+            return apis;
+        }
+        let name = parts.join("::");
+        for (api_name, rules) in &self.pattern_apis {
+            if rules.matches(&parts, &name) {
+                apis.to_mut().insert(api_name.clone());
+            }
+        }
+        apis
+    }
+
+    /// Returns whether the config marks `pkg_id` as `no_panic` for `scope`.
+    pub(crate) fn is_no_panic(&self, pkg_id: &PackageId, scope: PermissionScope) -> bool {
+        self.config
+            .permissions
+            .get(&PermSel::with_scope(pkg_id, scope))
+            .is_some_and(|pkg_config| pkg_config.no_panic)
+    }
+
+    /// Returns whether the config marks `pkg_id` as `no_alloc` for `scope`.
+    pub(crate) fn is_no_alloc(&self, pkg_id: &PackageId, scope: PermissionScope) -> bool {
+        self.config
+            .permissions
+            .get(&PermSel::with_scope(pkg_id, scope))
+            .is_some_and(|pkg_config| pkg_config.no_alloc)
+    }
+
+    /// Returns whether the config exempts `pkg_id` for `scope` from the denial of `path`. See
+    /// `deny_list.rs`.
+    pub(crate) fn is_denied_symbol_allowed(
+        &self,
+        pkg_id: &PackageId,
+        scope: PermissionScope,
+        path: &str,
+    ) -> bool {
+        self.config
+            .permissions
+            .get(&PermSel::with_scope(pkg_id, scope))
+            .is_some_and(|pkg_config| pkg_config.allow_denied_symbols.iter().any(|p| p == path))
     }
 
     /// Reports an API usage. If it's not permitted, then a problem will be added to `problems`.
@@ -411,13 +1214,58 @@ impl Checker {
         api_usage: &ApiUsages,
         problems: &mut ProblemList,
     ) -> Result<()> {
+        if api_usage.scope == PermissionScope::Test && self.config.raw.common.ignore_test_code {
+            return Ok(());
+        }
         let api = &api_usage.api_name;
         let perm_sel = api_usage.perm_sel();
+        // Without `detect_ffi`, we only look for foreign code on behalf of pure packages.
+        if api.as_ref() == FFI_API
+            && !self.config.raw.common.detect_ffi
+            && !self.config.permissions.is_pure(&perm_sel)
+        {
+            return Ok(());
+        }
         if let Some(crate_info) = self.crate_infos.get_mut(&perm_sel)
             && crate_info.allowed_apis.contains(api)
         {
             crate_info.unused_allowed_apis.remove(api);
             self.mark_parent_allow_apis_used(api, &perm_sel);
+            if let Some(problem) = self.version_pin_mismatch(api_usage) {
+                problems.push(problem);
+            }
+            if let Some(problem) = self.usage_budget_exceeded(api_usage) {
+                problems.push(problem);
+            }
+            return Ok(());
+        }
+
+        // Usages that are individually allowed at their current location don't need reporting.
+        let mut usages = self.unpinned_usages(api_usage);
+
+        // Code that's only compiled for tests is checked against the package's test permissions,
+        // even if it seems to have ended up in some other kind of binary. Test permissions inherit
+        // from the permissions that we've just checked, so this can only allow more.
+        if matches!(
+            api_usage.scope,
+            PermissionScope::All | PermissionScope::FromTest
+        ) {
+            let (mut test_usages, other_usages): (Vec<_>, Vec<_>) = usages
+                .into_iter()
+                .partition(|usage| self.is_test_code(&api_usage.pkg_id, usage));
+            if !test_usages.is_empty() {
+                for usage in &mut test_usages {
+                    usage.permission_scope = PermissionScope::Test;
+                }
+                let test_api_usage = ApiUsages {
+                    scope: PermissionScope::Test,
+                    ..api_usage.with_usages(test_usages)
+                };
+                self.api_used(&test_api_usage, problems)?;
+            }
+            usages = other_usages;
+        }
+        if usages.is_empty() {
             return Ok(());
         }
 
@@ -429,7 +1277,7 @@ impl Checker {
 
         let all_deps = self.crate_index.name_prefix_to_pkg_id();
         if let Some(crate_deps) = self.crate_index.transitive_deps(&api_usage.pkg_id) {
-            for usage in &api_usage.usages {
+            for usage in &usages {
                 if let Some(first_name_part) = usage.to_name.parts.first()
                     && !crate_deps.contains(first_name_part)
                     && let Some(pkg_id) = all_deps.get(first_name_part)
@@ -455,13 +1303,27 @@ impl Checker {
             // If we don't know the transitive dependencies of our crate, then just classify
             // everything as on-tree. This currently happens for transitive dependencies of
             // proc-macros due to the arguments we pass to `cargo tree`.
-            on_tree.extend(api_usage.usages.iter().cloned());
+            on_tree.extend(usages.iter().cloned());
         }
 
+        let warn = self
+            .config
+            .raw
+            .apis
+            .get(api)
+            .is_some_and(|api_config| api_config.severity == ApiSeverity::Warn);
+        let mut push = |problem| {
+            if warn {
+                problems.push(Problem::Warning(Box::new(problem)));
+            } else {
+                problems.push(problem);
+            }
+        };
+
         // Report off-tree problems for each off-tree package that we appear to reference.
         for (pkg_id, off_tree_usages) in off_tree {
             let usages = api_usage.with_usages(off_tree_usages);
-            problems.push(Problem::OffTreeApiUsage(OffTreeApiUsage {
+            push(Problem::OffTreeApiUsage(OffTreeApiUsage {
                 usages,
                 referenced_pkg_id: pkg_id.clone(),
             }));
@@ -469,33 +1331,206 @@ impl Checker {
 
         // For any remaining on-tree usages, report a regular disallowed API usage.
         if !on_tree.is_empty() {
-            problems.push(Problem::DisallowedApiUsage(api_usage.with_usages(on_tree)));
+            let mut usages = api_usage.with_usages(on_tree);
+            usages.feature_fix = self.feature_fix(&usages);
+            usages.usage_pins = self.usage_pins(&usages);
+            push(Problem::DisallowedApiUsage(usages));
         }
         Ok(())
     }
 
-    /// Returns whether the to-name of `usage` starts with a crate name that matches the package
-    /// that defined the outer location of the usage.
-    fn is_to_name_from_outer_location(&self, usage: &ApiUsage) -> Result<bool> {
-        if let Some(outer_location) = usage.outer_location.as_ref() {
-            for pkg_id in self
-                .pkg_ids_from_source_path(outer_location.filename())?
-                .as_ref()
-            {
-                let crate_name = pkg_id.crate_name();
-                if usage.to_name.starts_with(crate_name.as_ref()) {
-                    return Ok(true);
-                }
-            }
+    /// Returns a problem if the package of `api_usage` is allowed the API, but its version doesn't
+    /// satisfy `pin_version`.
+    fn version_pin_mismatch(&self, api_usage: &ApiUsages) -> Option<Problem> {
+        let perm_sel = api_usage.perm_sel();
+        let pin_version = self
+            .config
+            .permissions
+            .get(&perm_sel)?
+            .pin_version
+            .as_ref()?;
+        // Invalid requirements are reported when we validate the config.
+        let version_req = cargo_metadata::semver::VersionReq::parse(pin_version).ok()?;
+        if version_req.matches(api_usage.pkg_id.version()) {
+            return None;
         }
-        Ok(false)
+        // Report the most specific selector that sets the pin, since that's what needs updating.
+        let pinned_sel = [
+            perm_sel.clone(),
+            perm_sel.clone_with_scope(PermissionScope::All),
+        ]
+        .into_iter()
+        .find(|sel| {
+            self.config
+                .permissions_no_inheritance
+                .get(sel)
+                .is_some_and(|pkg_config| pkg_config.pin_version.is_some())
+        })
+        .unwrap_or(perm_sel);
+        Some(Problem::VersionPinMismatch(VersionPinMismatch {
+            usages: api_usage.clone(),
+            perm_sel: pinned_sel,
+            pin_version: pin_version.clone(),
+        }))
     }
 
-    pub(crate) fn check_unused(&self) -> Result<ProblemList> {
-        if !self.outstanding_linker_invocations.is_empty() {
-            bail!(
-                "Linker invocations with no matching rustc completion: {}",
-                self.outstanding_linker_invocations.len()
+    /// Records where `api_usage` uses its API, then returns a problem if the package has a
+    /// `max_usages` budget for the API and is now using it from more locations than that.
+    fn usage_budget_exceeded(&mut self, api_usage: &ApiUsages) -> Option<Problem> {
+        let api = &api_usage.api_name;
+        let perm_sel = api_usage.perm_sel();
+        let budget = *self
+            .config
+            .permissions
+            .get(&perm_sel)?
+            .max_usages
+            .get(api)?;
+        let locations = self
+            .crate_infos
+            .get_mut(&perm_sel)?
+            .budgeted_usages
+            .entry(api.clone())
+            .or_default();
+        for usage in &api_usage.usages {
+            let location = &usage.source_location;
+            locations.insert(SourceLocation::new(
+                location.filename(),
+                location.line(),
+                None,
+            ));
+        }
+        let count = locations.len();
+        if count <= budget {
+            return None;
+        }
+        // As for version pins, report the most specific selector that sets the budget.
+        let budget_sel = [
+            perm_sel.clone(),
+            perm_sel.clone_with_scope(PermissionScope::All),
+        ]
+        .into_iter()
+        .find(|sel| {
+            self.config
+                .permissions_no_inheritance
+                .get(sel)
+                .is_some_and(|pkg_config| pkg_config.max_usages.contains_key(api))
+        })
+        .unwrap_or(perm_sel);
+        Some(Problem::UsageBudgetExceeded(UsageBudgetExceeded {
+            usages: api_usage.clone(),
+            perm_sel: budget_sel,
+            budget,
+            count,
+        }))
+    }
+
+    /// Returns whether `usage` is in code that's only compiled for the tests of `pkg_id`, i.e. code
+    /// under `#[cfg(test)]`, or in the package's `tests` or `benches` directories.
+    fn is_test_code(&self, pkg_id: &PackageId, usage: &ApiUsage) -> bool {
+        let location = &usage.source_location;
+        if let Some(pkg_dir) = self.crate_index.pkg_dir(pkg_id)
+            && let Ok(relative) = location.filename().strip_prefix(pkg_dir)
+            && (relative.starts_with("tests") || relative.starts_with("benches"))
+        {
+            return true;
+        }
+        self.feature_gates
+            .is_test_only(self.vfs.as_ref(), location.filename(), location.line())
+    }
+
+    /// Returns the usages from `api_usage` that aren't permitted by an `allow_usages` entry.
+    fn unpinned_usages(&self, api_usage: &ApiUsages) -> Vec<ApiUsage> {
+        let allowed = self
+            .config
+            .permissions
+            .get(&api_usage.perm_sel())
+            .map(|pkg_config| pkg_config.allow_usages.as_slice())
+            .unwrap_or_default();
+        let pkg_dir = self.crate_index.pkg_dir(&api_usage.pkg_id);
+        api_usage
+            .usages
+            .iter()
+            .filter(|usage| {
+                !pkg_dir.is_some_and(|pkg_dir| {
+                    crate::usage_pins::is_allowed(
+                        allowed,
+                        usage,
+                        &api_usage.api_name,
+                        pkg_dir,
+                        self.vfs.as_ref(),
+                    )
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns allowances that would permit each of `usages` at its current location, or nothing
+    /// if that's not possible for some of them.
+    fn usage_pins(&self, usages: &ApiUsages) -> Vec<AllowedUsage> {
+        let Some(pkg_dir) = self.crate_index.pkg_dir(&usages.pkg_id) else {
+            return Vec::new();
+        };
+        let Some(mut pins) = usages
+            .usages
+            .iter()
+            .map(|usage| {
+                crate::usage_pins::pin(usage, &usages.api_name, pkg_dir, self.vfs.as_ref())
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Vec::new();
+        };
+        pins.sort();
+        pins.dedup();
+        pins
+    }
+
+    /// If all of `usages` are in code that's only compiled when some cargo feature of the package
+    /// is enabled, returns the changes needed to stop that feature being enabled. Where there are
+    /// several such features, we pick the one that needs the fewest changes.
+    fn feature_fix(&self, usages: &ApiUsages) -> Option<FeatureFix> {
+        let mut common_features: Option<BTreeSet<String>> = None;
+        for usage in &usages.usages {
+            let location = &usage.source_location;
+            let features = self.feature_gates.required_features(
+                self.vfs.as_ref(),
+                location.filename(),
+                location.line(),
+            );
+            common_features = Some(match common_features {
+                Some(common) => common.intersection(&features).cloned().collect(),
+                None => features,
+            });
+        }
+        common_features?
+            .iter()
+            .filter_map(|feature| self.crate_index.features.fix_for(&usages.pkg_id, feature))
+            .min_by_key(|fix| fix.changes.len())
+    }
+
+    /// Returns whether the to-name of `usage` starts with a crate name that matches the package
+    /// that defined the outer location of the usage.
+    fn is_to_name_from_outer_location(&self, usage: &ApiUsage) -> Result<bool> {
+        if let Some(outer_location) = usage.outer_location.as_ref() {
+            for pkg_id in self
+                .pkg_ids_from_source_path(outer_location.filename())?
+                .as_ref()
+            {
+                let crate_name = pkg_id.crate_name();
+                if usage.to_name.starts_with(crate_name.as_ref()) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    pub(crate) fn check_unused(&self) -> Result<ProblemList> {
+        if !self.outstanding_linker_invocations.is_empty() {
+            bail!(
+                "Linker invocations with no matching rustc completion: {}",
+                self.outstanding_linker_invocations.len()
             );
         }
 
@@ -522,9 +1557,136 @@ impl Checker {
                 problems.push(Problem::UnusedSandboxConfiguration(perm_sel.clone()));
             }
         }
+        problems.merge(self.check_artifact_dependencies());
         Ok(problems)
     }
 
+    /// Checks that for each package that was built and which consumes binaries via artifact
+    /// dependencies, we analysed the binaries that it consumed. Such binaries would otherwise be
+    /// able to use APIs without being checked. Like the unused checks, this requires that we saw
+    /// the whole build.
+    fn check_artifact_dependencies(&self) -> ProblemList {
+        let mut problems = ProblemList::default();
+        for (provider, consumers) in self.crate_index.artifact_consumers() {
+            if self.analysed_artifacts.contains(provider) {
+                continue;
+            }
+            for consumer in consumers {
+                if self.compiled_packages.contains(consumer) {
+                    problems.push(Problem::new(format!(
+                        "`{consumer}` has an artifact dependency on `{provider}`, but no binary \
+                         built for it was analysed"
+                    )));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Records the permissions granted by the current config as facts, then evaluates the policy
+    /// supplied via `--policy`, if any, against all facts gathered during the run.
+    pub(crate) fn check_policy(&mut self) -> Result<ProblemList> {
+        if !self.facts.is_enabled() {
+            return Ok(ProblemList::default());
+        }
+        let mut packages: Vec<_> = self.config.permissions.packages.iter().collect();
+        packages.sort_by_key(|(perm_sel, _)| *perm_sel);
+        for (perm_sel, pkg_config) in packages {
+            self.facts.emit(Fact::permission(perm_sel, pkg_config))?;
+        }
+        let mut problems = ProblemList::default();
+        if let Some(policy_path) = self.args.policy.as_ref() {
+            for violation in
+                crate::policy::evaluate(policy_path, self.facts.facts(), self.tmpdir.path())?
+            {
+                problems.push(Problem::PolicyViolation(violation));
+            }
+        }
+        for plugin in &self.args.plugin {
+            for finding in crate::plugins::run(plugin, self.facts.facts())? {
+                if let Some(problem) = self.plugin_problem(plugin, finding)? {
+                    problems.push(problem);
+                }
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Returns the problem for something that `plugin` found, or `None` if it's a usage of an API
+    /// that the package is allowed.
+    fn plugin_problem(
+        &self,
+        plugin: &Path,
+        finding: crate::plugins::Finding,
+    ) -> Result<Option<Problem>> {
+        let pkg_id = finding
+            .package
+            .as_ref()
+            .map(|name| {
+                self.crate_index
+                    .package_ids()
+                    .filter(|pkg_id| pkg_id.name_str() == name)
+                    .filter(|pkg_id| {
+                        finding
+                            .version
+                            .as_ref()
+                            .is_none_or(|version| pkg_id.version().to_string() == *version)
+                    })
+                    .max_by_key(|pkg_id| pkg_id.version())
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Plugin `{}` reported unknown package `{name}`",
+                            plugin.display()
+                        )
+                    })
+            })
+            .transpose()?;
+        let api = finding
+            .api
+            .as_deref()
+            .map(|name| {
+                let api = ApiName {
+                    name: Arc::from(name),
+                };
+                if !self.config.raw.apis.contains_key(&api) {
+                    bail!(
+                        "Plugin `{}` reported a usage of unknown API `{name}`",
+                        plugin.display()
+                    );
+                }
+                Ok(api)
+            })
+            .transpose()?;
+        if finding.scope.is_some() && pkg_id.is_none() {
+            bail!(
+                "Plugin `{}` reported a scope without a package",
+                plugin.display()
+            );
+        }
+        let scope = finding.scope.unwrap_or(PermissionScope::All);
+        if let (Some(pkg_id), Some(api)) = (&pkg_id, &api) {
+            // As for API usages that we find ourselves, see `api_used`.
+            if scope == PermissionScope::Test && self.config.raw.common.ignore_test_code {
+                return Ok(None);
+            }
+            if self
+                .crate_infos
+                .get(&PermSel::with_scope(pkg_id, scope))
+                .is_some_and(|crate_info| crate_info.allowed_apis.contains(api))
+            {
+                return Ok(None);
+            }
+        }
+        Ok(Some(Problem::PluginProblem(PluginProblem {
+            plugin: plugin.to_owned(),
+            pkg_id,
+            api,
+            scope,
+            message: finding.message,
+        })))
+    }
+
     pub(crate) fn check_for_new_config_version(&self) -> ProblemList {
         let version = self.config.raw.common.version;
         if version < crate::config::MAX_VERSION {
@@ -533,13 +1695,39 @@ impl Checker {
         ProblemList::default()
     }
 
+    /// Checks the rustc version against our table of known issues, failing if it isn't supported,
+    /// then against the version pinned in the config, if any.
+    pub(crate) fn check_rustc_version(&self) -> Result<ProblemList> {
+        let mut problems = ProblemList::default();
+        let Some(version) = self.rustc_version.as_ref() else {
+            return Ok(problems);
+        };
+        if let Some(issue) = version.known_issues().next() {
+            bail!("rustc {version} isn't supported. {}", issue.description);
+        }
+        if let Some(pinned) = self.config.raw.common.rustc_version.as_ref()
+            && !version.matches(pinned)
+        {
+            problems.push(Problem::RustcVersionChanged(RustcVersionChanged {
+                pinned: pinned.clone(),
+                active: version.to_string(),
+            }));
+        }
+        Ok(problems)
+    }
+
     fn record_crate_paths(&mut self, info: &rpc::RustcOutput) -> Result<()> {
+        self.record_remaps(&info.remap_path_prefixes);
         for path in &info.source_paths {
             let selectors = &mut self.path_to_pkg_ids.entry(path.to_owned()).or_default();
             if !selectors.contains(&info.crate_sel.pkg_id) {
                 selectors.push(info.crate_sel.pkg_id.clone());
             }
         }
+        if let Some(out_dir) = &info.out_dir {
+            self.out_dir_pkg_ids
+                .insert(out_dir.clone(), info.crate_sel.pkg_id.clone());
+        }
         Ok(())
     }
 
@@ -597,22 +1785,74 @@ impl Checker {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod testing {
     use super::*;
-    use crate::config::testing::parse;
     use crate::symbol::Symbol;
 
-    fn checker_for_testing() -> Checker {
+    pub(crate) fn checker_for_testing() -> Checker {
         Checker::new(
             Arc::new(TempDir::new(None).unwrap()),
             PathBuf::default(),
             Arc::new(Args::default()),
             Arc::from(Path::new("")),
+            None,
             Arc::new(CrateIndex::default()),
             PathBuf::default(),
         )
     }
 
+    /// Returns a usage of `to` by `from`, which is attributed to the package `pkg`.
+    pub(crate) fn api_usage(pkg: &str, from: &str, to: &str) -> ApiUsage {
+        ApiUsage {
+            bin_location: BinLocation {
+                address: 0,
+                symbol_start: 0,
+            },
+            bin_path: Arc::from(Path::new("bin")),
+            permission_scope: PermissionScope::All,
+            source_location: SourceLocation::new(
+                Path::new("/ws").join(pkg).join("src/lib.rs"),
+                10,
+                Some(5),
+            ),
+            outer_location: None,
+            from: SymbolOrDebugName::Symbol(Symbol::borrowed(from.as_bytes()).to_heap()),
+            to_name: crate::names::split_simple(to),
+            to: SymbolOrDebugName::Symbol(Symbol::borrowed(to.as_bytes()).to_heap()),
+            to_source: NameSource::Symbol(Symbol::borrowed(to.as_bytes()).to_heap()),
+            kind: ReferenceKind::Call,
+            origin: UsageOrigin {
+                package_candidates: vec![crate::crate_index::testing::pkg_id(pkg)],
+                ..Default::default()
+            },
+            debug_data: None,
+            callers: Vec::new(),
+            inline_chain: Vec::new(),
+        }
+    }
+
+    /// Returns `usages` of `api` by `pkg`, in any permission scope.
+    pub(crate) fn api_usages(pkg: &str, api: &str, usages: Vec<ApiUsage>) -> ApiUsages {
+        ApiUsages {
+            pkg_id: crate::crate_index::testing::pkg_id(pkg),
+            scope: PermissionScope::All,
+            api_name: ApiName::new(api),
+            usages,
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::checker_for_testing;
+    use super::*;
+    use crate::config::testing::parse;
+    use crate::crate_index::testing::pkg_id;
+    use crate::problem::Severity;
+    use crate::symbol::Symbol;
+
     #[track_caller]
     fn assert_apis(config: &str, path: &[&str], expected: &[&str]) {
         let mut checker = checker_for_testing();
@@ -645,6 +1885,116 @@ mod tests {
         assert_apis(config, &["std", "env", "exe"], &["env", "env2", "fs"]);
     }
 
+    #[test]
+    fn most_specific_path_wins() {
+        let config = r#"
+                [api.fs]
+                include = ["std::fs", "std::fs::OpenOptions::open"]
+                exclude = ["std::fs::metadata", "std::fs::OpenOptions"]
+
+                [api.fs-write]
+                include = ["/std::fs::(write|OpenOptions)/"]
+                exclude = ["std::fs::OpenOptions::new"]
+                "#;
+        assert_apis(config, &["std", "fs", "read"], &["fs"]);
+        assert_apis(config, &["std", "fs", "metadata"], &[]);
+        assert_apis(config, &["std", "fs", "OpenOptions", "mode"], &["fs-write"]);
+        assert_apis(config, &["std", "fs", "OpenOptions", "new"], &[]);
+        assert_apis(
+            config,
+            &["std", "fs", "OpenOptions", "open"],
+            &["fs", "fs-write"],
+        );
+        assert_apis(config, &["std", "fs", "write"], &["fs", "fs-write"]);
+    }
+
+    #[test]
+    fn api_patterns() {
+        let config = r#"
+                [api.net]
+                include = ["mio::net::*", "/std::net::(tcp|udp)/"]
+                exclude = ["mio::net::addr", "mio::net::unix*"]
+
+                [api.fs]
+                include = ["std::fs"]
+                exclude = ["std::fs::metadata*"]
+                "#;
+        assert_apis(config, &["mio", "net", "TcpStream", "connect"], &["net"]);
+        assert_apis(config, &["mio", "net"], &[]);
+        assert_apis(config, &["mio", "net", "addr", "lookup"], &[]);
+        assert_apis(config, &["mio", "net", "unix_datagram"], &[]);
+        assert_apis(config, &["std", "net", "tcp", "TcpStream"], &["net"]);
+        assert_apis(config, &["std", "net", "ip"], &[]);
+        assert_apis(config, &["std", "fs", "read"], &["fs"]);
+        assert_apis(config, &["std", "fs", "metadata"], &[]);
+        assert_apis(config, &["std", "fs", "metadata_len"], &[]);
+    }
+
+    #[test]
+    fn built_in_env_api() {
+        let config = r#"import_std = ["env", "fs"]"#;
+        assert_apis(config, &["std", "env", "var"], &["env"]);
+        assert_apis(config, &["std", "env", "set_var"], &["env"]);
+        assert_apis(config, &["std", "env", "vars_os"], &["env"]);
+        assert_apis(config, &["extern", "getenv"], &["env"]);
+        assert_apis(config, &["extern", "GetEnvironmentVariableW"], &["env"]);
+        assert_apis(config, &["std", "fs", "read"], &["fs"]);
+    }
+
+    #[test]
+    fn async_runtime_apis() {
+        let config = r#"
+                [api.net]
+                include = ["tokio::net"]
+                exclude = ["tokio::net::addr"]
+                "#;
+        assert_apis(config, &["tokio", "spawn"], &["async-runtime:tokio"]);
+        assert_apis(
+            config,
+            &["tokio", "net", "TcpStream"],
+            &["async-runtime:tokio", "net"],
+        );
+        assert_apis(config, &["tokio", "net", "addr"], &["async-runtime:tokio"]);
+        assert_apis(config, &["async_std", "task"], &["async-runtime:async-std"]);
+        assert_apis(config, &["std", "net"], &[]);
+    }
+
+    #[test]
+    fn test_only_permission_scope() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_test_only_packages(
+                &["app", "proptest"],
+                &["proptest"],
+            ),
+            ..checker_for_testing()
+        };
+        let bin = CrateSel::primary(pkg_id("app"));
+        checker.update_config(parse("").unwrap());
+        assert_eq!(
+            checker.permission_scope(&pkg_id("proptest"), &bin),
+            PermissionScope::All
+        );
+        checker.update_config(parse("isolate_test_only_deps = true").unwrap());
+        assert_eq!(
+            checker.permission_scope(&pkg_id("proptest"), &bin),
+            PermissionScope::FromTest
+        );
+        assert_eq!(
+            checker.permission_scope(&pkg_id("app"), &bin),
+            PermissionScope::All
+        );
+    }
+
+    #[test]
+    fn workspace_crate_api() {
+        let config = r#"
+                [api.db-access]
+                include = ["my_storage::raw"]
+                "#;
+        assert_apis(config, &["my_storage", "raw", "query"], &["db-access"]);
+        assert_apis(config, &["my_storage", "pool", "get"], &[]);
+    }
+
     #[test]
     fn reload_config() {
         let config = parse(
@@ -667,18 +2017,16 @@ mod tests {
         checker.update_config(config.clone());
         let mut problems = ProblemList::default();
 
-        let pkg_id = crate::crate_index::testing::pkg_id("foo");
         let apis = checker
             .apis_for_name_iterator(["std", "fs", "read_to_string"].into_iter())
-            .clone();
+            .into_owned();
         assert_eq!(apis.len(), 1);
         assert_eq!(apis.iter().next().unwrap(), &ApiName::from("fs"));
         for api in apis {
-            let api_usage = ApiUsages {
-                pkg_id: pkg_id.clone(),
-                scope: crate::config::permissions::PermissionScope::All,
-                api_name: api,
-                usages: vec![ApiUsage {
+            let api_usage = testing::api_usages(
+                "foo",
+                api.as_ref(),
+                vec![ApiUsage {
                     bin_location: BinLocation {
                         address: 0,
                         symbol_start: 0,
@@ -691,9 +2039,13 @@ mod tests {
                     to_name: crate::names::split_simple("foo::bar"),
                     to: SymbolOrDebugName::Symbol(Symbol::borrowed(&[])),
                     to_source: NameSource::Symbol(Symbol::borrowed(b"foo::bar")),
+                    kind: crate::checker::ReferenceKind::Call,
+                    origin: Default::default(),
                     debug_data: None,
+                    callers: Vec::new(),
+                    inline_chain: Vec::new(),
                 }],
-            };
+            );
             checker.api_used(&api_usage, &mut problems).unwrap();
         }
 
@@ -704,4 +2056,518 @@ mod tests {
         checker.update_config(config);
         assert!(checker.check_unused().unwrap().is_empty());
     }
+
+    #[test]
+    fn warn_severity() {
+        let config = parse(
+            r#"
+            [api.fs]
+            include = ["std::fs"]
+            [api.net]
+            include = ["std::net"]
+            severity = "warn"
+        "#,
+        )
+        .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(config);
+        let mut problems = ProblemList::default();
+        for (api, to) in [
+            ("fs", "std::fs::read"),
+            ("net", "std::net::TcpStream::connect"),
+        ] {
+            let api_usage =
+                testing::api_usages("foo", api, vec![testing::api_usage("foo", "foo::bar", to)]);
+            checker.api_used(&api_usage, &mut problems).unwrap();
+        }
+        let severities: Vec<_> = problems
+            .take()
+            .iter()
+            .map(|problem| (problem.severity(), matches!(problem, Problem::Warning(..))))
+            .collect();
+        assert_eq!(
+            severities,
+            [(Severity::Error, false), (Severity::Warning, true)]
+        );
+    }
+
+    #[test]
+    fn pinned_version() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo", "bar"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                [pkg.foo]
+                allow_apis = ["fs"]
+                pin_version = "=0.0.0"
+                [pkg.bar]
+                allow_apis = ["fs"]
+                pin_version = "=1.4.2"
+            "#,
+            )
+            .unwrap(),
+        );
+        let mut problems = ProblemList::default();
+        for pkg in ["foo", "bar"] {
+            let api_usage = testing::api_usages(
+                pkg,
+                "fs",
+                vec![testing::api_usage(pkg, "x::y", "std::fs::read")],
+            );
+            checker.api_used(&api_usage, &mut problems).unwrap();
+        }
+        let problems = problems.take();
+        assert_eq!(problems.len(), 1);
+        let Problem::VersionPinMismatch(info) = &problems[0] else {
+            panic!("Unexpected problem {:?}", problems[0]);
+        };
+        assert_eq!(info.usages.pkg_id, pkg_id("bar"));
+        assert_eq!(info.perm_sel, PermSel::for_primary("bar"));
+        assert_eq!(info.pin_version, "=1.4.2");
+        assert!(checker.check_unused().unwrap().is_empty());
+    }
+
+    #[test]
+    fn foreign_code_in_pure_packages() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo", "bar"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(parse("[pkg.foo]\npure = true\n[pkg.bar]").unwrap());
+        let mut problems = ProblemList::default();
+        for pkg in ["foo", "bar"] {
+            let api_usage = testing::api_usages(
+                pkg,
+                FFI_API,
+                vec![testing::api_usage(pkg, "x::y", "extern::qsort")],
+            );
+            checker.api_used(&api_usage, &mut problems).unwrap();
+        }
+        // Without `detect_ffi`, only the pure package's use of foreign code is reported.
+        let problems = problems.take();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].pkg_id(), Some(&pkg_id("foo")));
+    }
+
+    #[test]
+    fn usage_budgets() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(
+            parse(
+                r#"
+                [api.net]
+                include = ["std::net"]
+                [pkg.foo]
+                allow_apis = ["net"]
+                max_usages = { net = 2 }
+            "#,
+            )
+            .unwrap(),
+        );
+        let usage_at = |line, column| {
+            let mut usage = testing::api_usage("foo", "foo::f", "std::net::connect");
+            usage.source_location =
+                SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), line, Some(column));
+            usage
+        };
+        let api_usage = |usages| testing::api_usages("foo", "net", usages);
+        // Two usages on the same line only count once.
+        let mut problems = ProblemList::default();
+        checker
+            .api_used(
+                &api_usage(vec![usage_at(1, 1), usage_at(1, 9), usage_at(2, 1)]),
+                &mut problems,
+            )
+            .unwrap();
+        assert!(problems.is_empty());
+
+        // Locations are counted over all binaries, so a third location exceeds the budget even
+        // when it's found in a different binary.
+        checker
+            .api_used(&api_usage(vec![usage_at(3, 1)]), &mut problems)
+            .unwrap();
+        let problems = problems.take();
+        assert_eq!(problems.len(), 1);
+        let Problem::UsageBudgetExceeded(info) = &problems[0] else {
+            panic!("Unexpected problem {:?}", problems[0]);
+        };
+        assert_eq!(info.perm_sel, PermSel::for_primary("foo"));
+        assert_eq!((info.budget, info.count), (2, 3));
+    }
+
+    #[test]
+    fn plugin_findings() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo", "bar"]),
+            ..checker_for_testing()
+        };
+        checker.update_config(
+            parse(
+                r#"
+                [api.telemetry]
+                include = ["opentelemetry"]
+                [pkg.foo]
+                allow_apis = ["telemetry"]
+                [pkg.bar.build]
+                allow_apis = ["telemetry"]
+            "#,
+            )
+            .unwrap(),
+        );
+        let finding = |package: &str, api: Option<&str>| crate::plugins::Finding {
+            message: "uses telemetry".to_owned(),
+            package: Some(package.to_owned()),
+            version: None,
+            api: api.map(str::to_owned),
+            scope: None,
+        };
+        let build_finding = |package: &str| crate::plugins::Finding {
+            scope: Some(PermissionScope::Build),
+            ..finding(package, Some("telemetry"))
+        };
+        let plugin = Path::new("check-telemetry");
+        // `foo` is allowed the API, so isn't reported, but `bar` isn't.
+        assert_eq!(
+            checker
+                .plugin_problem(plugin, finding("foo", Some("telemetry")))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            checker
+                .plugin_problem(plugin, finding("bar", Some("telemetry")))
+                .unwrap(),
+            Some(Problem::PluginProblem(PluginProblem {
+                plugin: plugin.to_owned(),
+                pkg_id: Some(pkg_id("bar")),
+                api: Some(ApiName::from("telemetry")),
+                scope: PermissionScope::All,
+                message: "uses telemetry".to_owned(),
+            }))
+        );
+        // `bar`'s build script is allowed the API, even though `bar` itself isn't. Its tests aren't
+        // allowed it either.
+        assert_eq!(
+            checker
+                .plugin_problem(plugin, build_finding("bar"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            checker
+                .plugin_problem(
+                    plugin,
+                    crate::plugins::Finding {
+                        scope: Some(PermissionScope::Test),
+                        ..finding("bar", Some("telemetry"))
+                    }
+                )
+                .unwrap()
+                .map(|problem| problem.to_string()),
+            Some("`bar`: uses telemetry".to_owned())
+        );
+        assert!(
+            checker
+                .plugin_problem(
+                    plugin,
+                    crate::plugins::Finding {
+                        package: None,
+                        ..build_finding("bar")
+                    }
+                )
+                .is_err()
+        );
+        // Without an API, the finding is always reported.
+        assert!(
+            checker
+                .plugin_problem(plugin, finding("foo", None))
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            checker
+                .plugin_problem(plugin, finding("baz", None))
+                .is_err()
+        );
+        assert!(
+            checker
+                .plugin_problem(plugin, finding("foo", Some("net")))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn feature_conditional_apis() {
+        let mut crate_index =
+            Arc::into_inner(crate::crate_index::testing::index_with_package_names(&[
+                "app", "reqwest",
+            ]))
+            .unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["app", "reqwest"]),
+            ..checker_for_testing()
+        };
+        let config = parse(
+            r#"
+            [api.net]
+            include = ["std::net"]
+            [pkg.reqwest.if_feature."app/online"]
+            allow_apis = ["net"]
+        "#,
+        )
+        .unwrap();
+        let api_usage = testing::api_usages(
+            "reqwest",
+            "net",
+            vec![testing::api_usage(
+                "reqwest",
+                "reqwest::get",
+                "std::net::TcpStream::connect",
+            )],
+        );
+
+        // Without the feature, the usage is reported.
+        checker.update_config(config.clone());
+        let mut problems = ProblemList::default();
+        checker.api_used(&api_usage, &mut problems).unwrap();
+        assert_eq!(problems.len(), 1);
+
+        crate_index
+            .features
+            .enable_for_testing(pkg_id("app"), "online");
+        let mut checker = Checker {
+            crate_index: Arc::new(crate_index),
+            ..checker_for_testing()
+        };
+        checker.update_config(config);
+        let mut problems = ProblemList::default();
+        checker.api_used(&api_usage, &mut problems).unwrap();
+        assert!(problems.is_empty());
+        assert!(checker.check_unused().unwrap().is_empty());
+    }
+
+    #[test]
+    fn usages_allowed_at_locations() {
+        let source = "fn a() {}\nfn b() {\n    std::fs::read(\"x\");\n}\n";
+        let mut vfs = crate::vfs::testing::MemoryFs::default();
+        vfs.add("/ws/foo/src/lib.rs", source);
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_dirs(&[(
+                "foo", "/ws/foo",
+            )]),
+            vfs: Arc::new(vfs),
+            ..checker_for_testing()
+        };
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+            "#,
+            )
+            .unwrap(),
+        );
+        let mut usage = testing::api_usage("foo", "foo::b", "std::fs::read");
+        usage.source_location = SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 3, None);
+        let api_usage = testing::api_usages("foo", "fs", vec![usage]);
+        let mut problems = ProblemList::default();
+        checker.api_used(&api_usage, &mut problems).unwrap();
+        let problems = problems.take();
+        let [Problem::DisallowedApiUsage(reported)] = problems.as_slice() else {
+            panic!("Unexpected problems {problems:?}");
+        };
+        assert_eq!(reported.usage_pins.len(), 1);
+        let pin = &reported.usage_pins[0];
+        assert_eq!((pin.file.as_str(), pin.line), ("src/lib.rs", 3));
+
+        // Once the usage is allowed at its location, it's no longer reported.
+        checker.update_config(
+            parse(&format!(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                [pkg.foo]
+                allow_usages = [{{ api = "fs", file = "src/lib.rs", line = 3, hash = "{}" }}]
+            "#,
+                pin.hash
+            ))
+            .unwrap(),
+        );
+        let mut problems = ProblemList::default();
+        checker.api_used(&api_usage, &mut problems).unwrap();
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn test_only_code_uses_test_permissions() {
+        let source = indoc::indoc! {r#"
+            fn run() {
+                std::fs::read("x");
+            }
+            #[cfg(test)]
+            mod tests {
+                fn setup() {
+                    std::fs::write("x", "y");
+                }
+            }
+        "#};
+        let lib = Path::new("/ws/foo/src/lib.rs");
+        let usage_at = |line| {
+            let mut usage = testing::api_usage("foo", "foo::f", "std::fs::read");
+            usage.source_location = SourceLocation::new(lib, line, None);
+            usage
+        };
+        let api_usage = testing::api_usages("foo", "fs", vec![usage_at(2), usage_at(7)]);
+        let check = |config: &str| {
+            let mut vfs = crate::vfs::testing::MemoryFs::default();
+            vfs.add(lib, source);
+            let mut checker = Checker {
+                crate_index: crate::crate_index::testing::index_with_package_dirs(&[(
+                    "foo", "/ws/foo",
+                )]),
+                vfs: Arc::new(vfs),
+                ..checker_for_testing()
+            };
+            checker.update_config(parse(config).unwrap());
+            let mut problems = ProblemList::default();
+            checker.api_used(&api_usage, &mut problems).unwrap();
+            problems
+                .take()
+                .into_iter()
+                .map(|problem| {
+                    let Problem::DisallowedApiUsage(usages) = problem else {
+                        panic!("Unexpected problem {problem:?}");
+                    };
+                    let lines: Vec<u32> = usages
+                        .usages
+                        .iter()
+                        .map(|u| u.source_location.line())
+                        .collect();
+                    (usages.scope, lines)
+                })
+                .collect::<Vec<_>>()
+        };
+        let apis = "[api.fs]\ninclude = [\"std::fs\"]\n";
+        assert_eq!(
+            check(apis),
+            vec![
+                (PermissionScope::Test, vec![7]),
+                (PermissionScope::All, vec![2])
+            ]
+        );
+        assert_eq!(
+            check(&format!("{apis}[pkg.foo]\ntest.allow_apis = [\"fs\"]\n")),
+            vec![(PermissionScope::All, vec![2])]
+        );
+        assert_eq!(
+            check(&format!("ignore_test_code = true\n{apis}")),
+            vec![(PermissionScope::All, vec![2])]
+        );
+    }
+
+    #[test]
+    fn build_script_pins() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let build_rs = dir.path().join("build.rs");
+        std::fs::write(&build_rs, "fn main() {}").unwrap();
+        let output = rpc::RustcOutput {
+            crate_sel: CrateSel::build_script(pkg_id("foo")),
+            source_paths: vec![build_rs.clone()],
+            link_libs: Vec::new(),
+            remap_path_prefixes: Vec::new(),
+            out_dir: None,
+            rustdoc_json: None,
+        };
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_dirs(&[(
+                "foo",
+                dir.path().to_str().unwrap(),
+            )]),
+            ..checker_for_testing()
+        };
+        let changed = |problems: ProblemList| {
+            let problems = problems.take();
+            let [Problem::BuildScriptChanged(changed)] = problems.as_slice() else {
+                panic!("Unexpected problems {problems:?}");
+            };
+            changed.clone()
+        };
+        checker.update_config(parse("pin_build_scripts = true\n").unwrap());
+        let unpinned = changed(checker.build_script_linked(&output).unwrap());
+        assert_eq!(unpinned.expected, None);
+
+        // The hash only depends on the sources and where they are within the package, not on where
+        // the package is.
+        let other_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(other_dir.path().join("build.rs"), "fn main() {}").unwrap();
+        let digest =
+            build_script_checker::sources_digest(dir.path(), &output.source_paths).unwrap();
+        assert_eq!(
+            build_script_checker::sources_digest(
+                other_dir.path(),
+                &[other_dir.path().join("build.rs")]
+            )
+            .unwrap(),
+            digest
+        );
+
+        let pinned = |extra: &str| {
+            parse(&format!(
+                "pin_build_scripts = true\n[pkg.foo]\nbuild.sha256 = \"{}\"\n{extra}",
+                unpinned.sha256
+            ))
+            .unwrap()
+        };
+        checker.update_config(pinned(""));
+        assert!(checker.build_script_linked(&output).unwrap().is_empty());
+        std::fs::write(&build_rs, "fn main() { println!(\"changed\"); }").unwrap();
+        let changed_sources = changed(checker.build_script_linked(&output).unwrap());
+        assert_eq!(changed_sources.expected.as_ref(), Some(&unpinned.sha256));
+        std::fs::write(&build_rs, "fn main() {}").unwrap();
+
+        // A sandboxed build script has its pin checked once we know which executables it wrote.
+        checker.update_config(pinned("build.sandbox.kind = \"Bubblewrap\"\n"));
+        assert!(checker.build_script_linked(&output).unwrap().is_empty());
+        let recorded = checker.build_script_sources[&pkg_id("foo")].clone();
+        assert_eq!(recorded, digest);
+        assert!(
+            checker
+                .verify_build_script_pin(&pkg_id("foo"), &recorded, &[])
+                .is_empty()
+        );
+        let downloaded = WrittenExecutable {
+            path: dir.path().join("out/tool"),
+            sha256: "abcd".to_owned(),
+        };
+        let with_executable =
+            changed(checker.verify_build_script_pin(&pkg_id("foo"), &recorded, &[downloaded]));
+        assert_ne!(with_executable.sha256, unpinned.sha256);
+    }
+
+    #[test]
+    fn classify_references() {
+        use ReferenceKind::*;
+        // (branch, from_executable, via_vtable, target_executable)
+        assert_eq!(ReferenceKind::classify(true, true, false, true), Call);
+        // Taking the address of a function, e.g. to pass it as a callback.
+        assert_eq!(ReferenceKind::classify(false, true, false, true), Data);
+        assert_eq!(ReferenceKind::classify(false, true, false, false), Data);
+        // A function pointer in a static table.
+        assert_eq!(ReferenceKind::classify(false, false, false, true), Data);
+        // Code that creates a trait object references the methods in its vtable.
+        assert_eq!(ReferenceKind::classify(false, true, true, true), VTable);
+        // Vtables also reference things other than functions, e.g. supertrait vtables.
+        assert_eq!(ReferenceKind::classify(false, true, true, false), Data);
+    }
 }
@@ -1,4 +1,8 @@
+use crate::config::ApiConfig;
 use crate::config::ApiName;
+use crate::config::ApiPath;
+use crate::config::api_pattern::ApiPattern;
+use crate::names::Name;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
 
@@ -70,3 +74,116 @@ impl ApiMap {
         self.map.clear();
     }
 }
+
+/// The include and exclude paths of an API. Where more than one path matches a name, the most
+/// specific one, i.e. the one that matches the most parts of the name, decides whether the name is
+/// part of the API. Where an include and an exclude are equally specific, the exclude wins.
+pub(crate) struct ApiRules {
+    /// Sorted so that less specific prefixes come first.
+    rules: Vec<ApiRule>,
+}
+
+struct ApiRule {
+    path: ApiPath,
+    matcher: PathMatcher,
+    exclude: bool,
+}
+
+enum PathMatcher {
+    Prefix(Name),
+    Pattern(ApiPattern),
+}
+
+impl ApiRules {
+    /// Returns the rules for `api`. Invalid patterns are ignored, since they're reported when we
+    /// validate the config.
+    pub(crate) fn new(api: &ApiConfig) -> Self {
+        let include = api.include.iter().map(|path| (path, false));
+        let exclude = api.exclude.iter().map(|path| (path, true));
+        let mut rules: Vec<ApiRule> = include
+            .chain(exclude)
+            .filter_map(|(path, exclude)| {
+                let matcher = match ApiPattern::from_path(path).ok()? {
+                    Some(pattern) => PathMatcher::Pattern(pattern),
+                    None => PathMatcher::Prefix(crate::names::split_simple(&path.prefix)),
+                };
+                Some(ApiRule {
+                    path: path.clone(),
+                    matcher,
+                    exclude,
+                })
+            })
+            .collect();
+        rules.sort_by_key(|rule| (rule.matcher.specificity(), rule.exclude));
+        Self { rules }
+    }
+
+    /// Returns whether any of the paths are patterns. Patterns can't be stored in an `ApiMap`, so
+    /// each name needs to be checked against them.
+    pub(super) fn has_patterns(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| matches!(rule.matcher, PathMatcher::Pattern(_)))
+    }
+
+    /// Returns the prefixes, together with whether each is an exclude, ordered such that applying
+    /// each to an `ApiMap` in turn gives the most specific ones precedence.
+    pub(super) fn prefixes(&self) -> impl Iterator<Item = (&Name, bool)> {
+        self.rules.iter().filter_map(|rule| match &rule.matcher {
+            PathMatcher::Prefix(name) => Some((name, rule.exclude)),
+            PathMatcher::Pattern(_) => None,
+        })
+    }
+
+    /// Returns whether the name made up of `parts` is part of the API. `name` should be `parts`
+    /// joined with `::`.
+    pub(crate) fn matches(&self, parts: &[&str], name: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for rule in &self.rules {
+            if let Some(matched) = rule.matcher.matched_parts(parts, name)
+                && best.is_none_or(|best| (matched, rule.exclude) > best)
+            {
+                best = Some((matched, rule.exclude));
+            }
+        }
+        best.is_some_and(|(_, exclude)| !exclude)
+    }
+
+    /// Returns whether `name` is part of the API.
+    pub(crate) fn matches_name(&self, name: &Name) -> bool {
+        let parts: Vec<&str> = name.parts().collect();
+        self.matches(&parts, &parts.join("::"))
+    }
+
+    /// Returns the most specific of the include paths that match `name`. Of those that match the
+    /// same number of parts, the longest is taken to be the most specific.
+    pub(crate) fn most_specific_include(&self, name: &Name) -> Option<&ApiPath> {
+        let parts: Vec<&str> = name.parts().collect();
+        let joined = parts.join("::");
+        self.rules
+            .iter()
+            .filter(|rule| !rule.exclude)
+            .filter_map(|rule| Some((rule.matcher.matched_parts(&parts, &joined)?, &rule.path)))
+            .max_by_key(|(matched, path)| (*matched, path.prefix.len()))
+            .map(|(_, path)| path)
+    }
+}
+
+impl PathMatcher {
+    /// How specific this matcher is when it matches, so far as we can tell without a name.
+    fn specificity(&self) -> usize {
+        match self {
+            PathMatcher::Prefix(name) => name.parts.len(),
+            PathMatcher::Pattern(_) => 0,
+        }
+    }
+
+    fn matched_parts(&self, parts: &[&str], name: &str) -> Option<usize> {
+        match self {
+            PathMatcher::Prefix(prefix) => (prefix.parts.len() <= parts.len()
+                && prefix.parts().zip(parts).all(|(a, b)| a == *b))
+            .then_some(prefix.parts.len()),
+            PathMatcher::Pattern(pattern) => pattern.matched_parts(name),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Attribution of code to the packages that it belongs to. By default, we go by the source file that
+//! the debug info says the code came from, using the source files that rustc reported for each
+//! crate. Unusual setups, e.g. where sources are generated outside of the package or shared between
+//! packages, can cause this to systematically attribute code to the wrong package, so `attribution`
+//! in the config can select other strategies. Strategies are tried in the configured order until
+//! one of them gives an answer.
+
+use super::Checker;
+use crate::config::AttributionStrategy;
+use crate::crate_index::PackageId;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// What we know about some code that we'd like to attribute to a package.
+pub(crate) struct AttributionQuery<'a> {
+    /// The source file that the code came from.
+    pub(crate) source_path: &'a Path,
+
+    /// The crate named by the symbol or debug name of the function containing the code.
+    pub(crate) name_crate: Option<&'a str>,
+
+    /// The root source file of the DWARF compilation unit containing the code.
+    pub(crate) crate_root: Option<PathBuf>,
+}
+
+pub(crate) trait Attribution {
+    /// Returns the packages that the code described by `query` belongs to, or `None` if this
+    /// strategy can't tell.
+    fn pkg_ids<'checker>(
+        &self,
+        query: &AttributionQuery,
+        checker: &'checker Checker,
+    ) -> Option<Cow<'checker, [PackageId]>>;
+}
+
+/// Attributes code to the packages that compiled its source file.
+struct PathAttribution;
+
+/// Attributes code to the package whose crate root is the root of the compilation unit that
+/// contains the code. Code inlined from other crates is attributed to the crate it was inlined
+/// into.
+struct CompilationUnitAttribution;
+
+/// Attributes code to the package whose crate name is the first part of the name of the function
+/// containing the code.
+struct SymbolPrefixAttribution;
+
+impl AttributionQuery<'_> {
+    pub(crate) fn for_path(source_path: &Path) -> AttributionQuery<'_> {
+        AttributionQuery {
+            source_path,
+            name_crate: None,
+            crate_root: None,
+        }
+    }
+}
+
+impl AttributionStrategy {
+    pub(crate) fn attribution(self) -> &'static dyn Attribution {
+        match self {
+            AttributionStrategy::Path => &PathAttribution,
+            AttributionStrategy::CompilationUnit => &CompilationUnitAttribution,
+            AttributionStrategy::SymbolPrefix => &SymbolPrefixAttribution,
+        }
+    }
+}
+
+impl Attribution for PathAttribution {
+    fn pkg_ids<'checker>(
+        &self,
+        query: &AttributionQuery,
+        checker: &'checker Checker,
+    ) -> Option<Cow<'checker, [PackageId]>> {
+        pkg_ids_for_source_file(query.source_path, checker)
+    }
+}
+
+impl Attribution for CompilationUnitAttribution {
+    fn pkg_ids<'checker>(
+        &self,
+        query: &AttributionQuery,
+        checker: &'checker Checker,
+    ) -> Option<Cow<'checker, [PackageId]>> {
+        pkg_ids_for_source_file(query.crate_root.as_deref()?, checker)
+    }
+}
+
+impl Attribution for SymbolPrefixAttribution {
+    fn pkg_ids<'checker>(
+        &self,
+        query: &AttributionQuery,
+        checker: &'checker Checker,
+    ) -> Option<Cow<'checker, [PackageId]>> {
+        let name_crate = query.name_crate?;
+        if STD_CRATES.contains(&name_crate) {
+            return Some(Cow::Owned(vec![]));
+        }
+        checker
+            .crate_index
+            .name_prefix_to_pkg_id()
+            .get(name_crate)
+            .map(|pkg_id| Cow::Owned(vec![pkg_id.clone()]))
+    }
+}
+
+/// Crates that come with the compiler, so don't belong to any package.
+const STD_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+fn pkg_ids_for_source_file<'checker>(
+    source_path: &Path,
+    checker: &'checker Checker,
+) -> Option<Cow<'checker, [PackageId]>> {
+    checker
+        .path_to_pkg_ids
+        .get(source_path)
+        .map(|ids| Cow::Borrowed(ids.as_slice()))
+        .or_else(|| {
+            // If the source path is from the rust standard library, or from one of the precompiled
+            // crates that comes with the standard library, then report no crates.
+            if checker.is_in_rust_std(source_path) {
+                return Some(Cow::Owned(vec![]));
+            }
+
+            // The source paths that rustc reports are canonical, whereas paths in debug info can go
+            // via symlinks or contain `..`.
+            let canonical = source_path
+                .canonicalize()
+                .ok()
+                .filter(|canonical| canonical != source_path);
+            if let Some(pkg_ids) = canonical
+                .as_ref()
+                .and_then(|canonical| checker.path_to_pkg_ids.get(canonical))
+            {
+                return Some(Cow::Borrowed(pkg_ids.as_slice()));
+            }
+
+            // Fall-back to just finding the package that contains the source path, or whose build
+            // script generated it, e.g. C code that a build script extracted into OUT_DIR.
+            let path = canonical.as_deref().unwrap_or(source_path);
+            checker
+                .crate_index
+                .package_id_for_path(path)
+                .or_else(|| checker.pkg_id_for_out_dir_path(path))
+                .map(|pkg_id| Cow::Owned(vec![pkg_id.clone()]))
+        })
+}
+
+/// Returns the root source file of a compilation unit given its name and the directory it was
+/// compiled from. Rustc names units like `src/lib.rs/@/foo.1234abcd-cgu.0`, while C compilers
+/// generally use the name of the source file.
+pub(crate) fn crate_root(compdir: &Path, unit_name: &OsStr) -> PathBuf {
+    let name = unit_name.as_bytes();
+    let root = name
+        .windows(3)
+        .position(|window| window == b"/@/")
+        .map_or(name, |end| &name[..end]);
+    compdir.join(OsStr::from_bytes(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AttributionQuery;
+    use super::crate_root;
+    use crate::checker::Checker;
+    use crate::config::AttributionStrategy;
+    use crate::crate_index::testing::pkg_id;
+    use std::ffi::OsStr;
+    use std::os::unix::prelude::OsStrExt;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn crate_roots() {
+        assert_eq!(
+            crate_root(
+                Path::new("/ws"),
+                "foo/src/lib.rs/@/foo.1234abcd-cgu.0".as_ref()
+            ),
+            Path::new("/ws/foo/src/lib.rs")
+        );
+        assert_eq!(
+            crate_root(
+                Path::new("/ws"),
+                "/registry/bar/src/lib.rs/@/bar.cgu.0".as_ref()
+            ),
+            Path::new("/registry/bar/src/lib.rs")
+        );
+        assert_eq!(
+            crate_root(Path::new("/ws/baz"), "src/shim.c".as_ref()),
+            Path::new("/ws/baz/src/shim.c")
+        );
+        assert_eq!(
+            crate_root(
+                Path::new("/ws"),
+                OsStr::from_bytes(b"caf\xe9/src/lib.rs/@/cafe.cgu.0")
+            ),
+            Path::new(OsStr::from_bytes(b"/ws/caf\xe9/src/lib.rs"))
+        );
+    }
+
+    #[test]
+    fn strategies() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo", "bar"]),
+            ..crate::checker::testing::checker_for_testing()
+        };
+        checker
+            .path_to_pkg_ids
+            .insert(PathBuf::from("/ws/foo/src/lib.rs"), vec![pkg_id("foo")]);
+        checker
+            .path_to_pkg_ids
+            .insert(PathBuf::from("/ws/bar/src/lib.rs"), vec![pkg_id("bar")]);
+        // Code from `bar` that was inlined into `foo`.
+        let query = AttributionQuery {
+            source_path: Path::new("/ws/bar/src/lib.rs"),
+            name_crate: Some("bar"),
+            crate_root: Some(PathBuf::from("/ws/foo/src/lib.rs")),
+        };
+        let attribute = |strategy: AttributionStrategy, query: &AttributionQuery| {
+            strategy
+                .attribution()
+                .pkg_ids(query, &checker)
+                .map(|pkg_ids| pkg_ids.into_owned())
+        };
+        assert_eq!(
+            attribute(AttributionStrategy::Path, &query),
+            Some(vec![pkg_id("bar")])
+        );
+        assert_eq!(
+            attribute(AttributionStrategy::CompilationUnit, &query),
+            Some(vec![pkg_id("foo")])
+        );
+        assert_eq!(
+            attribute(AttributionStrategy::SymbolPrefix, &query),
+            Some(vec![pkg_id("bar")])
+        );
+
+        let path_only = AttributionQuery::for_path(Path::new("/elsewhere/gen.rs"));
+        assert_eq!(
+            attribute(AttributionStrategy::CompilationUnit, &path_only),
+            None
+        );
+        assert_eq!(
+            attribute(AttributionStrategy::SymbolPrefix, &path_only),
+            None
+        );
+        let std_query = AttributionQuery {
+            name_crate: Some("core"),
+            ..AttributionQuery::for_path(Path::new("/elsewhere/gen.rs"))
+        };
+        assert_eq!(
+            attribute(AttributionStrategy::SymbolPrefix, &std_query),
+            Some(vec![])
+        );
+
+        // Without debug info, only the name counts, whatever the configured strategies.
+        let no_debug_info = AttributionQuery {
+            name_crate: Some("bar"),
+            ..AttributionQuery::for_path(Path::new("/ws/foo/src/lib.rs"))
+        };
+        assert_eq!(
+            checker.pkg_ids_from_name(&no_debug_info).as_ref(),
+            [pkg_id("bar")]
+        );
+        assert!(checker.pkg_ids_from_name(&path_only).is_empty());
+    }
+
+    #[test]
+    fn build_script_outputs() {
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_names(&["foo_sys"]),
+            sysroot: Path::new("/sysroot").into(),
+            ..crate::checker::testing::checker_for_testing()
+        };
+        checker.out_dir_pkg_ids.insert(
+            PathBuf::from("/t/debug/build/foo_sys-1234abcd/out"),
+            pkg_id("foo_sys"),
+        );
+        // C sources that the build script extracted or generated.
+        let query = AttributionQuery::for_path(Path::new(
+            "/t/debug/build/foo_sys-1234abcd/out/vendor/shim.c",
+        ));
+        assert_eq!(
+            AttributionStrategy::Path
+                .attribution()
+                .pkg_ids(&query, &checker)
+                .map(|pkg_ids| pkg_ids.into_owned()),
+            Some(vec![pkg_id("foo_sys")])
+        );
+        assert_eq!(
+            checker.pkg_id_for_out_dir_path(Path::new(
+                "/t/debug/build/foo_sys-1234abcd/out/lib/libfoo.a"
+            )),
+            Some(&pkg_id("foo_sys"))
+        );
+        assert_eq!(
+            checker.pkg_id_for_out_dir_path(Path::new("/t/debug/build/foo_sys-1234abcd/output")),
+            None
+        );
+    }
+
+    #[test]
+    fn symlinked_sources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let vendor = dir.path().join("vendor");
+        let real = dir.path().join("real");
+        let generated = dir.path().join("generated");
+        std::fs::create_dir_all(real.join("foo/src")).unwrap();
+        std::fs::create_dir_all(&vendor).unwrap();
+        std::fs::create_dir_all(&generated).unwrap();
+        std::fs::write(real.join("foo/src/shim.c"), "").unwrap();
+        std::fs::write(generated.join("gen.rs"), "").unwrap();
+        // The vendored package is a symlink, as is a directory containing generated code that the
+        // package includes.
+        std::os::unix::fs::symlink(real.join("foo"), vendor.join("foo")).unwrap();
+        std::os::unix::fs::symlink(&generated, dir.path().join("out")).unwrap();
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_dirs(&[(
+                "foo",
+                vendor.join("foo").to_str().unwrap(),
+            )]),
+            sysroot: Path::new("/sysroot").into(),
+            ..crate::checker::testing::checker_for_testing()
+        };
+        // Rustc reports canonical paths.
+        checker.path_to_pkg_ids.insert(
+            generated.join("gen.rs").canonicalize().unwrap(),
+            vec![pkg_id("foo")],
+        );
+        for path in [
+            dir.path().join("out/gen.rs"),
+            vendor.join("foo/../../generated/gen.rs"),
+            real.join("foo/src/shim.c"),
+        ] {
+            let query = AttributionQuery::for_path(&path);
+            assert_eq!(
+                AttributionStrategy::Path
+                    .attribution()
+                    .pkg_ids(&query, &checker)
+                    .map(|pkg_ids| pkg_ids.into_owned()),
+                Some(vec![pkg_id("foo")]),
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Evaluation of what we find against a second configuration, so that policy authors can see what
+//! problems a change to the configuration, e.g. stricter API definitions, would produce before
+//! rolling it out. See `--with-config`. The second configuration is only used for problems found
+//! in binaries - it doesn't affect what's allowed during the build.
+
+use super::Checker;
+use crate::compare::CompareFormat;
+use crate::config::Config;
+use crate::facts::FactStream;
+use crate::link_info::LinkInfo;
+use crate::problem::ProblemList;
+use crate::proxy::rpc;
+use crate::report::Report;
+use crate::symbol_graph::ScanOutputs;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub(crate) struct ConfigTrial {
+    /// A checker that has the trial configuration.
+    checker: Box<Checker>,
+
+    /// The problems that the main configuration produced for each binary. These get replaced if the
+    /// binary is rechecked after the main configuration is changed.
+    main_problems: BTreeMap<Arc<Path>, ProblemList>,
+
+    /// The problems that the trial configuration produced for each binary.
+    trial_problems: BTreeMap<Arc<Path>, ProblemList>,
+}
+
+impl Checker {
+    /// Starts evaluating what we find against the configuration at `config_path` as well as
+    /// against our own configuration.
+    pub(crate) fn start_config_trial(&mut self, config_path: &Path) -> Result<()> {
+        let mut checker = Checker::new(
+            self.tmpdir.clone(),
+            self.target_dir.clone(),
+            self.args.clone(),
+            self.sysroot.clone(),
+            self.rustc_version.clone(),
+            self.crate_index.clone(),
+            config_path.to_owned(),
+        );
+        // Facts are about what was found, so only the main checker collects them.
+        checker.facts = FactStream::default();
+        // We don't use `load_config`, since that would replace the config used by subprocesses.
+        let config = crate::config::parse_file(config_path, &self.crate_index, self.vfs.as_ref())?;
+        checker.update_config(config);
+        self.config_trial = Some(ConfigTrial {
+            checker: Box::new(checker),
+            main_problems: BTreeMap::new(),
+            trial_problems: BTreeMap::new(),
+        });
+        Ok(())
+    }
+
+    /// Returns a comparison of the problems produced by the main configuration with those produced
+    /// by the trial configuration, or `None` if we're not running a trial.
+    pub(crate) fn config_trial_comparison(&self) -> Option<Result<String>> {
+        let trial = self.config_trial.as_ref()?;
+        let main = trial_report(&trial.main_problems, &self.config);
+        let other = trial_report(&trial.trial_problems, &trial.checker.config);
+        Some(
+            crate::compare::compare_reports(&main, &other, CompareFormat::Terminal).map(|diff| {
+                format!(
+                    "Compared with configuration {}:\n{diff}",
+                    trial.checker.config_path.display()
+                )
+            }),
+        )
+    }
+}
+
+impl ConfigTrial {
+    pub(super) fn record_crate_paths(&mut self, info: &rpc::RustcOutput) -> Result<()> {
+        self.checker.record_crate_paths(info)
+    }
+
+    /// Records `main_problems`, which the main configuration produced for the binary described by
+    /// `link_info`, then works out what problems the trial configuration produces for the same
+    /// binary. If the trial configuration defines the same APIs, then we can evaluate `outputs`,
+    /// otherwise we need to scan the binary again.
+    pub(super) fn check(
+        &mut self,
+        paths: &[PathBuf],
+        link_info: &LinkInfo,
+        outputs: &ScanOutputs,
+        main_problems: &ProblemList,
+    ) -> Result<()> {
+        let bin = &link_info.output_file;
+        self.main_problems
+            .insert(bin.clone(), main_problems.clone());
+        if self.trial_problems.contains_key(bin) {
+            // The trial configuration doesn't change, so neither do its problems.
+            return Ok(());
+        }
+        let checker = &mut self.checker;
+        let problems = if outputs.apis == checker.config.raw.apis {
+            outputs.problems(checker)?
+        } else {
+            let (outputs, _) = crate::symbol_graph::scan_objects(paths, link_info, checker)?;
+            outputs.problems(checker)?
+        };
+        self.trial_problems.insert(bin.clone(), problems);
+        Ok(())
+    }
+}
+
+fn trial_report(problems: &BTreeMap<Arc<Path>, ProblemList>, config: &Config) -> Report {
+    Report::from_problems(problems.values().flatten(), config)
+}
@@ -0,0 +1,153 @@
+//! Reversal of the path remapping that rustc does when passed `--remap-path-prefix`, which is
+//! common in CI and reproducible builds. Remapped paths in debug info don't match the source paths
+//! that rustc reports for each crate, so without reversing the remapping, code can't be attributed
+//! to packages. Remappings are detected from the arguments that cargo passes to rustc and can also
+//! be listed in the config. We also reverse the standard remapping of the cargo registry to
+//! `/cargo/registry`, but only for files that belong to a package, since that's also where the
+//! standard library's own dependencies appear to come from in older versions of rustc.
+
+use super::Checker;
+use crate::config::RemapPathPrefix;
+use std::borrow::Cow;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Where cargo registries are commonly remapped to.
+const REMAPPED_REGISTRY: &str = "/cargo/registry";
+
+/// Returns the path remappings that the supplied rustc arguments ask for.
+pub(crate) fn remaps_from_rustc_args(args: impl Iterator<Item = String>) -> Vec<RemapPathPrefix> {
+    let mut remaps = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let value = if arg == "--remap-path-prefix" {
+            args.next()
+        } else {
+            arg.strip_prefix("--remap-path-prefix=").map(str::to_owned)
+        };
+        // Like rustc, we split on the last `=`, since the real path is more likely to contain one.
+        if let Some((from, to)) = value.as_deref().and_then(|value| value.rsplit_once('=')) {
+            remaps.push(RemapPathPrefix {
+                from: PathBuf::from(from),
+                to: PathBuf::from(to),
+            });
+        }
+    }
+    remaps
+}
+
+impl Checker {
+    pub(super) fn record_remaps(&mut self, remaps: &[RemapPathPrefix]) {
+        for remap in remaps {
+            if !self.detected_remaps.contains(remap) {
+                self.detected_remaps.push(remap.clone());
+            }
+        }
+    }
+
+    /// Returns the path remappings to reverse, those from the config first.
+    pub(crate) fn path_remaps(&self) -> impl Iterator<Item = &RemapPathPrefix> {
+        self.config
+            .raw
+            .common
+            .remap_path_prefix
+            .iter()
+            .chain(&self.detected_remaps)
+    }
+
+    /// Returns what `path` was before rustc remapped it, or `path` itself if it wasn't remapped,
+    /// or we can't tell what it was.
+    pub(crate) fn unremapped_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        for remap in self.path_remaps() {
+            if remap.to.as_os_str().is_empty() {
+                continue;
+            }
+            if let Ok(rest) = path.strip_prefix(&remap.to) {
+                return Cow::Owned(remap.from.join(rest));
+            }
+        }
+        if let Ok(rest) = path.strip_prefix(REMAPPED_REGISTRY) {
+            for registry_dir in self.crate_index.registry_dirs() {
+                let candidate = registry_dir.join(rest);
+                if self.path_to_pkg_ids.contains_key(&candidate)
+                    || self.crate_index.package_id_for_path(&candidate).is_some()
+                {
+                    return Cow::Owned(candidate);
+                }
+            }
+        }
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remaps_from_rustc_args;
+    use crate::checker::Checker;
+    use crate::config::RemapPathPrefix;
+    use crate::crate_index::testing::pkg_id;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn remap(from: &str, to: &str) -> RemapPathPrefix {
+        RemapPathPrefix {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        }
+    }
+
+    #[test]
+    fn rustc_args() {
+        let args = [
+            "--crate-name",
+            "foo",
+            "--remap-path-prefix",
+            "/home/me/foo=/build",
+            "--remap-path-prefix=/home/me/a=b=/a",
+            "--remap-path-prefix=invalid",
+        ];
+        assert_eq!(
+            remaps_from_rustc_args(args.iter().map(|arg| arg.to_string())),
+            vec![remap("/home/me/foo", "/build"), remap("/home/me/a=b", "/a")]
+        );
+    }
+
+    #[test]
+    fn unremapped_paths() {
+        let registry_pkg = "/home/me/.cargo/registry/src/index.crates.io-1234/bar-1.0.0";
+        let mut checker = Checker {
+            crate_index: crate::crate_index::testing::index_with_package_dirs(&[
+                ("foo", "/home/me/foo"),
+                ("bar", registry_pkg),
+            ]),
+            sysroot: Arc::from(Path::new("/sysroot")),
+            ..crate::checker::testing::checker_for_testing()
+        };
+        checker.record_remaps(&[remap("/home/me/foo", "/build")]);
+        assert_eq!(
+            checker.unremapped_path(Path::new("/build/src/lib.rs")),
+            Path::new("/home/me/foo/src/lib.rs")
+        );
+        assert_eq!(
+            checker.unremapped_path(Path::new(
+                "/cargo/registry/src/index.crates.io-1234/bar-1.0.0/src/lib.rs"
+            )),
+            Path::new(registry_pkg).join("src/lib.rs")
+        );
+        // Not from any of our packages, so should remain as from the standard library.
+        let std_dep = Path::new("/cargo/registry/src/index.crates.io-1234/hashbrown-0.14.0/lib.rs");
+        assert_eq!(checker.unremapped_path(std_dep), std_dep);
+        assert!(checker.is_in_rust_std(&checker.unremapped_path(std_dep)));
+        assert_eq!(
+            checker.unremapped_path(Path::new("/home/me/foo/src/lib.rs")),
+            Path::new("/home/me/foo/src/lib.rs")
+        );
+        assert_eq!(
+            checker
+                .opt_pkg_ids_from_source_path(Path::new("/build/src/main.rs"))
+                .map(|pkg_ids| pkg_ids.into_owned()),
+            Some(vec![pkg_id("foo")])
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Comparison of two reports written via --report-output. e.g. before and after updating
+//! dependencies or before and after a configuration change.
+
+use crate::outcome;
+use crate::outcome::ExitCode;
+use crate::report::Report;
+use crate::report::ReportedPermissions;
+use crate::report::ReportedProblem;
+use anyhow::Result;
+use clap::Parser;
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CompareOptions {
+    /// The earlier report.
+    report_a: PathBuf,
+
+    /// The later report.
+    report_b: PathBuf,
+
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = CompareFormat::Terminal)]
+    format: CompareFormat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CompareFormat {
+    /// Print output in a human-readable form.
+    Terminal,
+    /// Print output as markdown, e.g. for use in a pull request comment.
+    Markdown,
+    /// Print output in a machine-readable form.
+    Json,
+}
+
+/// The differences between two reports.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+struct Comparison {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rustc_version_change: Option<RustcVersionChange>,
+    problems_added: Vec<ReportedProblem>,
+    problems_removed: Vec<ReportedProblem>,
+    problems_changed: Vec<ChangedProblem>,
+    permission_changes: Vec<PermissionChange>,
+}
+
+/// Symbol mangling and debug info can change between rustc versions, so a change of version may
+/// explain other differences.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct RustcVersionChange {
+    before: String,
+    after: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct ChangedProblem {
+    before: ReportedProblem,
+    after: ReportedProblem,
+}
+
+/// Changes to the permissions of a single package selector.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+struct PermissionChange {
+    package: String,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+pub(crate) fn run(options: &CompareOptions) -> ExitCode {
+    match compare_files(options) {
+        Ok(output) => {
+            print!("{output}");
+            outcome::SUCCESS
+        }
+        Err(error) => {
+            println!("{error:#}");
+            outcome::FAILURE
+        }
+    }
+}
+
+fn compare_files(options: &CompareOptions) -> Result<String> {
+    let a = Report::load(&options.report_a)?;
+    let b = Report::load(&options.report_b)?;
+    compare_reports(&a, &b, options.format)
+}
+
+/// Returns the differences between `a` and `b` in the requested format.
+pub(crate) fn compare_reports(a: &Report, b: &Report, format: CompareFormat) -> Result<String> {
+    let comparison = Comparison::new(a, b);
+    Ok(match format {
+        CompareFormat::Terminal => comparison.to_terminal(),
+        CompareFormat::Markdown => comparison.to_markdown(),
+        CompareFormat::Json => serde_json::to_string_pretty(&comparison)? + "\n",
+    })
+}
+
+impl Comparison {
+    fn new(a: &Report, b: &Report) -> Self {
+        let mut comparison = Comparison::default();
+        if let (Some(before), Some(after)) = (&a.rustc_version, &b.rustc_version)
+            && before != after
+        {
+            comparison.rustc_version_change = Some(RustcVersionChange {
+                before: before.clone(),
+                after: after.clone(),
+            });
+        }
+        let a_problems: BTreeMap<&str, &ReportedProblem> =
+            a.problems.iter().map(|p| (p.summary.as_str(), p)).collect();
+        let b_problems: BTreeMap<&str, &ReportedProblem> =
+            b.problems.iter().map(|p| (p.summary.as_str(), p)).collect();
+        for (summary, before) in &a_problems {
+            match b_problems.get(summary) {
+                None => comparison.problems_removed.push((*before).clone()),
+                Some(after) if after != before => {
+                    comparison.problems_changed.push(ChangedProblem {
+                        before: (*before).clone(),
+                        after: (*after).clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for (summary, after) in &b_problems {
+            if !a_problems.contains_key(summary) {
+                comparison.problems_added.push((*after).clone());
+            }
+        }
+
+        let packages: BTreeSet<&String> =
+            a.permissions.keys().chain(b.permissions.keys()).collect();
+        let default = ReportedPermissions::default();
+        for package in packages {
+            let before = permission_list(a.permissions.get(package).unwrap_or(&default));
+            let after = permission_list(b.permissions.get(package).unwrap_or(&default));
+            let change = PermissionChange {
+                package: package.clone(),
+                added: after.difference(&before).cloned().collect(),
+                removed: before.difference(&after).cloned().collect(),
+            };
+            if !change.added.is_empty() || !change.removed.is_empty() {
+                comparison.permission_changes.push(change);
+            }
+        }
+        comparison
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Comparison::default()
+    }
+
+    fn to_terminal(&self) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            out.push_str("No differences\n");
+            return out;
+        }
+        if let Some(change) = &self.rustc_version_change {
+            let _ = writeln!(
+                out,
+                "{} {} -> {}",
+                "rustc version changed:".bold(),
+                change.before,
+                change.after
+            );
+        }
+        let mut section = |title: &str, problems: &[ReportedProblem], marker: &str| {
+            if !problems.is_empty() {
+                let _ = writeln!(out, "{} ({}):", title.bold(), problems.len());
+                for problem in problems {
+                    let _ = writeln!(out, "  {marker} {}", problem.summary);
+                }
+            }
+        };
+        section(
+            "Problems added",
+            &self.problems_added,
+            &"+".red().to_string(),
+        );
+        section(
+            "Problems removed",
+            &self.problems_removed,
+            &"-".green().to_string(),
+        );
+        if !self.problems_changed.is_empty() {
+            let _ = writeln!(
+                out,
+                "{} ({}):",
+                "Problems changed".bold(),
+                self.problems_changed.len()
+            );
+            for changed in &self.problems_changed {
+                let _ = writeln!(out, "  {} {}", "~".yellow(), changed.after.summary);
+            }
+        }
+        if !self.permission_changes.is_empty() {
+            let _ = writeln!(out, "{}:", "Permission changes".bold());
+            for change in &self.permission_changes {
+                let _ = writeln!(out, "  {}", change.package);
+                for permission in &change.added {
+                    let _ = writeln!(out, "    {} {permission}", "+".red());
+                }
+                for permission in &change.removed {
+                    let _ = writeln!(out, "    {} {permission}", "-".green());
+                }
+            }
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        if self.is_empty() {
+            out.push_str("No differences\n");
+            return out;
+        }
+        if let Some(change) = &self.rustc_version_change {
+            let _ = writeln!(
+                out,
+                "rustc version changed from `{}` to `{}`\n",
+                change.before, change.after
+            );
+        }
+        let mut section = |title: &str, problems: &[ReportedProblem]| {
+            if !problems.is_empty() {
+                let _ = writeln!(out, "### {title} ({})\n", problems.len());
+                for problem in problems {
+                    let _ = writeln!(out, "- {}", problem.summary);
+                }
+                out.push('\n');
+            }
+        };
+        section("Problems added", &self.problems_added);
+        section("Problems removed", &self.problems_removed);
+        if !self.problems_changed.is_empty() {
+            let _ = writeln!(
+                out,
+                "### Problems changed ({})\n",
+                self.problems_changed.len()
+            );
+            for changed in &self.problems_changed {
+                let _ = writeln!(out, "- {}", changed.after.summary);
+            }
+            out.push('\n');
+        }
+        if !self.permission_changes.is_empty() {
+            out.push_str("### Permission changes\n\n");
+            out.push_str("| Package | Added | Removed |\n");
+            out.push_str("|---|---|---|\n");
+            for change in &self.permission_changes {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    change.package,
+                    markdown_list(&change.added),
+                    markdown_list(&change.removed)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Flattens the permissions of a package into a set of strings, one per permission granted.
+fn permission_list(permissions: &ReportedPermissions) -> BTreeSet<String> {
+    let mut list = BTreeSet::new();
+    if permissions.allow_unsafe {
+        match &permissions.allow_unsafe_in {
+            Some(files) => list.extend(files.iter().map(|file| format!("unsafe in {file}"))),
+            None => {
+                list.insert("unsafe".to_owned());
+            }
+        }
+    }
+    if permissions.allow_proc_macro {
+        list.insert("proc_macro".to_owned());
+    }
+    list.extend(
+        permissions
+            .allow_apis
+            .iter()
+            .map(|api| format!("api {api}")),
+    );
+    list.extend(
+        permissions
+            .allow_build_instructions
+            .iter()
+            .map(|instruction| format!("build instruction {instruction}")),
+    );
+    list.extend(
+        permissions
+            .allow_executables
+            .iter()
+            .map(|sha256| format!("executable {sha256}")),
+    );
+    list.extend(
+        permissions
+            .allow_binary_files
+            .iter()
+            .map(|sha256| format!("binary file {sha256}")),
+    );
+    list.extend(
+        permissions
+            .allow_native_libs
+            .iter()
+            .map(|library| format!("native library {library}")),
+    );
+    list
+}
+
+fn markdown_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("`{item}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Comparison;
+    use crate::crate_index::testing::pkg_id;
+    use crate::problem::Problem;
+    use crate::problem::Severity;
+    use crate::report::Report;
+    use crate::report::ReportedPermissions;
+    use crate::report::ReportedProblem;
+
+    fn problem(summary: &str, details: &str) -> ReportedProblem {
+        ReportedProblem {
+            severity: Severity::Error,
+            package: None,
+            summary: summary.to_owned(),
+            details: details.to_owned(),
+        }
+    }
+
+    fn report(problems: Vec<ReportedProblem>, apis: &[&str]) -> Report {
+        Report {
+            format_version: 1,
+            rustc_version: Some("1.95.0".to_owned()),
+            problems,
+            permissions: [(
+                "crab1".to_owned(),
+                ReportedPermissions {
+                    allow_apis: apis.iter().map(|api| api.to_string()).collect(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            build_settings: Default::default(),
+            test_only_packages: Vec::new(),
+            async_runtimes: Default::default(),
+            panic_paths: Default::default(),
+            nondeterminism: Default::default(),
+        }
+    }
+
+    #[test]
+    fn compare_reports() {
+        let a = report(vec![problem("a", "a1"), problem("b", "b1")], &["fs"]);
+        let b = report(vec![problem("b", "b2"), problem("c", "c1")], &["net"]);
+        let comparison = Comparison::new(&a, &b);
+        assert_eq!(comparison.problems_added, vec![problem("c", "c1")]);
+        assert_eq!(comparison.problems_removed, vec![problem("a", "a1")]);
+        assert_eq!(comparison.problems_changed.len(), 1);
+        assert_eq!(comparison.problems_changed[0].after, problem("b", "b2"));
+        assert_eq!(comparison.permission_changes.len(), 1);
+        assert_eq!(comparison.permission_changes[0].added, vec!["api net"]);
+        assert_eq!(comparison.permission_changes[0].removed, vec!["api fs"]);
+
+        assert!(Comparison::new(&a, &a).is_empty());
+
+        let b = Report {
+            rustc_version: Some("1.96.0".to_owned()),
+            ..a.clone()
+        };
+        let comparison = Comparison::new(&a, &b);
+        assert_eq!(
+            comparison.rustc_version_change,
+            Some(super::RustcVersionChange {
+                before: "1.95.0".to_owned(),
+                after: "1.96.0".to_owned(),
+            })
+        );
+        assert!(comparison.problems_added.is_empty());
+    }
+
+    #[test]
+    fn compare_configs() {
+        let main = crate::config::testing::parse("").unwrap();
+        let trial = crate::config::testing::parse(
+            r#"
+            [api.fs]
+            include = ["std::fs"]
+
+            [pkg.crab1]
+            allow_apis = ["fs"]
+            "#,
+        )
+        .unwrap();
+        let build_script = Problem::UsesBuildScript(pkg_id("crab1"));
+        // The same problem can be found in more than one binary.
+        let a = Report::from_problems([&build_script, &build_script].into_iter(), &main);
+        let b = Report::from_problems(std::iter::empty(), &trial);
+        let comparison = Comparison::new(&a, &b);
+        assert_eq!(comparison.problems_removed.len(), 1);
+        assert!(comparison.problems_added.is_empty());
+        assert_eq!(comparison.permission_changes.len(), 1);
+        assert_eq!(comparison.permission_changes[0].added, vec!["api fs"]);
+    }
+
+    #[test]
+    fn markdown_output() {
+        let a = report(vec![], &[]);
+        let b = report(vec![problem("`crab1` uses unsafe", "")], &["fs"]);
+        assert_eq!(
+            Comparison::new(&a, &b).to_markdown(),
+            "### Problems added (1)\n\n\
+             - `crab1` uses unsafe\n\n\
+             ### Permission changes\n\n\
+             | Package | Added | Removed |\n\
+             |---|---|---|\n\
+             | `crab1` | `api fs` |  |\n"
+        );
+    }
+}
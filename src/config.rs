@@ -1,10 +1,11 @@
 use self::permissions::Permissions;
+use crate::config_validation::InvalidConfig;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::PackageId;
 use crate::problem::AvailableApi;
 use crate::problem::Problem;
 use crate::problem::ProblemList;
-use anyhow::Context;
+use crate::vfs::Vfs;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -12,12 +13,15 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 pub(crate) use versions::MAX_VERSION;
 
+pub(crate) mod api_pattern;
 pub(crate) mod built_in;
+pub(crate) mod file_pattern;
 pub(crate) mod permissions;
 pub(crate) mod versions;
 
@@ -50,6 +54,18 @@ pub(crate) struct RawConfig {
 
     #[serde(default)]
     pub(crate) rustc: RustcConfig,
+
+    #[serde(default)]
+    pub(crate) display: DisplayConfig,
+
+    #[serde(default)]
+    pub(crate) limits: LimitsConfig,
+
+    #[serde(default)]
+    pub(crate) deny: DenyConfig,
+
+    #[serde(default)]
+    pub(crate) migration_mode: Option<MigrationModeConfig>,
 }
 
 /// The name of a package. Doesn't include any version information.
@@ -68,7 +84,7 @@ pub(crate) struct CommonConfig {
     #[serde(default)]
     pub(crate) build_flags: Option<Vec<String>>,
 
-    #[serde(default)]
+    #[serde(default, alias = "import_std_api")]
     pub(crate) import_std: Vec<String>,
 
     #[serde(default)]
@@ -76,6 +92,161 @@ pub(crate) struct CommonConfig {
 
     #[serde(default)]
     pub(crate) profile: Option<String>,
+
+    /// Whether to require that every build script has its hash pinned.
+    #[serde(default)]
+    pub(crate) pin_build_scripts: bool,
+
+    /// The rustc version that results were reviewed with. We warn if a different version is used.
+    #[serde(default)]
+    pub(crate) rustc_version: Option<String>,
+
+    /// Whether to ignore API usages in code that isn't reachable from the binary's entry points.
+    #[serde(default)]
+    pub(crate) ignore_unreachable: bool,
+
+    /// Which API usages to report when a location references several symbols belonging to an API.
+    #[serde(default)]
+    pub(crate) report: ReportMode,
+
+    /// Whether to look for system call instructions in the machine code of packages and report
+    /// them as usages of the built-in `raw_syscall` API.
+    #[serde(default)]
+    pub(crate) detect_raw_syscalls: bool,
+
+    /// Whether to look for functions that contain inline assembly and report them as usages of the
+    /// built-in `asm` API.
+    #[serde(default)]
+    pub(crate) detect_asm: bool,
+
+    /// Whether to look for functions that run before `main`, e.g. from `.init_array`, and report
+    /// them as usages of the built-in `before_main` API.
+    #[serde(default)]
+    pub(crate) detect_before_main: bool,
+
+    /// Whether to look for binary files, e.g. precompiled executables, in the sources of
+    /// dependencies. See `binary_files.rs`.
+    #[serde(default)]
+    pub(crate) detect_binary_files: bool,
+
+    /// Whether to find which APIs packages might export from rustdoc's JSON output, rather than
+    /// from the names of symbols. Each library is documented by our rustc wrapper when it's
+    /// compiled. See `rustdoc_exports.rs`.
+    #[serde(default)]
+    pub(crate) rustdoc_exports: bool,
+
+    /// Whether to report references from Rust code to functions and variables that aren't from
+    /// Rust, or from the C library, as usages of the built-in `ffi` API.
+    #[serde(default)]
+    pub(crate) detect_ffi: bool,
+
+    /// How to determine which package code belongs to, in order of preference. Empty means just
+    /// `path`. See `checker/attribution.rs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) attribution: Vec<AttributionStrategy>,
+
+    /// Whether to check the native shared libraries that binaries depend on against
+    /// `allow_native_libs`.
+    #[serde(default)]
+    pub(crate) check_native_libs: bool,
+
+    /// Whether packages that are only dependencies of tests should be checked against test
+    /// permissions wherever they're used and left out of the summary.
+    #[serde(default)]
+    pub(crate) isolate_test_only_deps: bool,
+
+    /// Whether to skip checking usages in a package's own test code, i.e. code that's checked
+    /// against `pkg.N.test` permissions.
+    #[serde(default)]
+    pub(crate) ignore_test_code: bool,
+
+    /// Which package API usages are attributed to when a weak symbol is overridden by a strong
+    /// definition of the same symbol.
+    #[serde(default)]
+    pub(crate) weak_overrides: WeakOverrideAttribution,
+
+    /// Whether to keep GNU symbol version suffixes like `@GLIBC_2.14` on the names of symbols in
+    /// binaries. By default they're stripped, so that the symbols match unversioned references
+    /// from object files.
+    #[serde(default)]
+    pub(crate) keep_symbol_versions: bool,
+
+    /// Whether to report binaries in which packages bind to more than one async runtime.
+    #[serde(default)]
+    pub(crate) single_async_runtime: bool,
+
+    /// Whether to look for references to sources of nondeterminism, such as time, randomness and
+    /// threads, and report which packages make them.
+    #[serde(default)]
+    pub(crate) determinism_audit: bool,
+
+    /// Path remappings, as passed to rustc's `--remap-path-prefix`, to reverse when attributing
+    /// code to packages. Remappings passed to rustc via cargo are detected without needing to be
+    /// listed here.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) remap_path_prefix: Vec<RemapPathPrefix>,
+
+    /// Whether to ignore references to and from sanitizer and fuzzer runtimes, so that binaries
+    /// built with instrumentation can be checked. See `instrumentation.rs`.
+    #[serde(default)]
+    pub(crate) ignore_instrumentation: bool,
+}
+
+/// A remapping of source paths, as done by rustc's `--remap-path-prefix FROM=TO`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RemapPathPrefix {
+    /// The real path prefix.
+    pub(crate) from: PathBuf,
+
+    /// What the prefix appears as in debug info.
+    pub(crate) to: PathBuf,
+}
+
+/// A way of determining which package some code belongs to.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AttributionStrategy {
+    /// The package whose sources include the file that the code came from.
+    Path,
+    /// The package whose crate root is the root of the compilation unit containing the code.
+    CompilationUnit,
+    /// The package whose crate name prefixes the name of the function containing the code.
+    SymbolPrefix,
+}
+
+impl CommonConfig {
+    pub(crate) fn attribution_strategies(&self) -> &[AttributionStrategy] {
+        if self.attribution.is_empty() {
+            &[AttributionStrategy::Path]
+        } else {
+            &self.attribution
+        }
+    }
+}
+
+/// A function can reference several symbols that belong to the same API from a single source
+/// location, e.g. a generic function and the functions that it was inlined from. By default, we
+/// only report the usage with the shortest symbol name, since that's generally the most readable.
+#[derive(Deserialize, Serialize, Debug, Default, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportMode {
+    #[default]
+    Shortest,
+    All,
+}
+
+/// When a weak symbol from one package is overridden by a strong definition from another, only the
+/// overriding code ends up being used. By default, what that code does is attributed to the package
+/// that it came from, like any other code.
+#[derive(Deserialize, Serialize, Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WeakOverrideAttribution {
+    /// The package that provided the strong definition.
+    #[default]
+    Overriding,
+    /// The package that provided the weak definition that was overridden.
+    Overridden,
 }
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
@@ -99,6 +270,71 @@ pub(crate) struct SandboxConfig {
     pub(crate) pass_env: Vec<String>,
 }
 
+/// How much work we do at once. See `resources.rs`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LimitsConfig {
+    /// The total number of jobs - rustc invocations, build scripts and scanner threads - to run at
+    /// once. Defaults to the number of CPUs.
+    #[serde(default)]
+    pub(crate) jobs: Option<NonZeroUsize>,
+
+    /// The number of sandboxes to run at once. Defaults to `jobs`.
+    #[serde(default)]
+    pub(crate) sandboxes: Option<NonZeroUsize>,
+}
+
+/// Items that packages may not reference, regardless of which APIs they're allowed. See
+/// `deny_list.rs`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DenyConfig {
+    /// The full paths of the items, e.g. `std::fs::remove_dir_all` or `extern::system`.
+    #[serde(default)]
+    pub(crate) symbols: Vec<String>,
+}
+
+/// How names are shown in output. See `name_display.rs`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DisplayConfig {
+    /// Names with more characters than this are shortened. By default, names are never shortened.
+    #[serde(default)]
+    pub(crate) max_name_length: Option<usize>,
+
+    #[serde(default)]
+    pub(crate) truncate: Truncation,
+
+    /// Whether to also shorten names in JSON output. By default, JSON always has full names.
+    #[serde(default)]
+    pub(crate) truncate_json: bool,
+}
+
+/// While adopting cackle, problems that are in a baseline report are only warnings, up until the
+/// end date if there is one. See `migration.rs`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MigrationModeConfig {
+    /// A report written by `baseline save` or `--report-output`. Relative paths are relative to
+    /// the config file.
+    pub(crate) baseline: PathBuf,
+
+    /// The last day on which migration mode applies. If not set, it applies indefinitely.
+    #[serde(default)]
+    pub(crate) until: Option<toml::value::Datetime>,
+}
+
+/// Which part of a long name gets replaced with an ellipsis.
+#[derive(Deserialize, Serialize, Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Truncation {
+    /// Keep the start and the end of the name. The end often has the most specific part, e.g. the
+    /// name of the method.
+    #[default]
+    Middle,
+    End,
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq, Hash)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct RustcConfig {
@@ -117,6 +353,20 @@ pub(crate) struct ApiConfig {
 
     #[serde(default)]
     pub(crate) no_auto_detect: Vec<PackageName>,
+
+    /// Whether disallowed usages of this API are errors or just warnings.
+    #[serde(default)]
+    pub(crate) severity: ApiSeverity,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ApiSeverity {
+    /// Disallowed usages are errors.
+    #[default]
+    Deny,
+    /// Disallowed usages are reported, but don't cause the check to fail.
+    Warn,
 }
 
 #[derive(Deserialize, Serialize, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -125,7 +375,7 @@ pub(crate) struct ApiName {
     pub(crate) name: Arc<str>,
 }
 
-/// A path prefix to some API. e.g. `std::net`.
+/// A path prefix to some API. e.g. `std::net`. May also be a pattern, see `api_pattern`.
 #[derive(Deserialize, Serialize, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 #[serde(transparent)]
 pub(crate) struct ApiPath {
@@ -140,21 +390,103 @@ pub(crate) enum SandboxKind {
 
 pub(crate) const SANDBOX_KINDS: &[SandboxKind] = &[SandboxKind::Disabled, SandboxKind::Bubblewrap];
 
+/// The name of the built-in API that's used for system call instructions when
+/// `common.detect_raw_syscalls` is set.
+pub(crate) const RAW_SYSCALL_API: &str = "raw_syscall";
+
+/// The name of the built-in API that's used for inline assembly when `common.detect_asm` is set.
+pub(crate) const ASM_API: &str = "asm";
+
+/// The name of the built-in API that's used for functions that run before `main` when
+/// `common.detect_before_main` is set.
+pub(crate) const BEFORE_MAIN_API: &str = "before_main";
+
+/// The name of the built-in API that's used for references to foreign code when
+/// `common.detect_ffi` is set.
+pub(crate) const FFI_API: &str = "ffi";
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct PackageConfig {
     #[serde(default)]
-    pub(crate) allow_unsafe: bool,
+    pub(crate) allow_unsafe: AllowUnsafe,
+
+    /// If set, then `allow_unsafe` only applies to these source files, which are relative to the
+    /// package root. See `file_pattern.rs` for what these can contain.
+    #[serde(default)]
+    pub(crate) allow_unsafe_in: Option<Vec<String>>,
 
     #[serde(default)]
     pub(crate) allow_build_instructions: Vec<String>,
 
+    /// SHA-256 hashes of executables that the build script is permitted to write into OUT_DIR. e.g.
+    /// prebuilt binaries that the build script downloads. Only executables still in OUT_DIR once the
+    /// build script exits are checked.
+    #[serde(default)]
+    pub(crate) allow_executables: Vec<String>,
+
+    /// SHA-256 hashes of binary files that the package's sources may contain when
+    /// `common.detect_binary_files` is set.
+    #[serde(default)]
+    pub(crate) allow_binary_files: Vec<String>,
+
+    /// Native shared libraries, e.g. `ssl`, that binaries may depend on because of this package.
+    #[serde(default)]
+    pub(crate) allow_native_libs: Vec<String>,
+
+    /// For build scripts, the hash of the sources of the build script that was reviewed and of any
+    /// executables that it wrote. If either changes, it needs to be reviewed again. See
+    /// `build_script_checker::pin_hash`.
+    #[serde(default)]
+    pub(crate) sha256: Option<String>,
+
     #[serde(default)]
     pub(crate) allow_apis: Vec<ApiName>,
 
+    /// Permissions that only apply when a cargo feature is enabled. Keys are features of this
+    /// package, or `pkg/feature` for a feature of some other package.
+    #[serde(default)]
+    pub(crate) if_feature: BTreeMap<String, FeatureConfig>,
+
+    /// Individual usages of APIs that are allowed without allowing the whole API. See
+    /// `usage_pins.rs`.
+    #[serde(default)]
+    pub(crate) allow_usages: Vec<AllowedUsage>,
+
+    /// The most source locations from which the package may use each API, e.g. `{ net = 3 }`. The
+    /// APIs still need to be allowed. This lets us notice when a package that we trusted to use an
+    /// API in a few places starts using it more widely.
+    #[serde(default)]
+    pub(crate) max_usages: BTreeMap<ApiName, usize>,
+
+    /// Items from `deny.symbols` that this package may reference anyway.
+    #[serde(default)]
+    pub(crate) allow_denied_symbols: Vec<String>,
+
     #[serde(default)]
     pub(crate) allow_proc_macro: bool,
 
+    /// Whether to look for references by scanning this package's machine code for direct calls, in
+    /// addition to looking at relocations.
+    #[serde(default)]
+    pub(crate) disassemble: bool,
+
+    /// Whether to report references that this package makes to panic machinery, e.g. via
+    /// `panic!` or `unwrap`.
+    #[serde(default)]
+    pub(crate) no_panic: bool,
+
+    /// Whether to report references that this package makes to the memory allocator.
+    #[serde(default)]
+    pub(crate) no_alloc: bool,
+
+    /// Whether the package should have no capabilities at all, i.e. it may not be granted any APIs,
+    /// unsafe or native libraries, and its references to foreign code are reported even without
+    /// `common.detect_ffi`. This is inherited by the package's `from` selectors, but not by its
+    /// `build` or `test` selectors.
+    #[serde(default)]
+    pub(crate) pure: bool,
+
     pub(crate) build: Option<Box<PackageConfig>>,
     pub(crate) test: Option<Box<PackageConfig>>,
 
@@ -166,6 +498,58 @@ pub(crate) struct PackageConfig {
 
     #[serde(default)]
     pub(crate) import: Option<Vec<String>>,
+
+    /// The date by which these permissions should be reviewed again. See `review.rs`.
+    #[serde(default)]
+    pub(crate) review_by: Option<toml::value::Datetime>,
+
+    /// The version of the package that was current when these permissions were last reviewed.
+    #[serde(default)]
+    pub(crate) reviewed_version: Option<String>,
+
+    /// A version requirement, e.g. `=1.4.2`, that the package must satisfy for `allow_apis` to
+    /// apply. Usages by other versions are reported so that they can be reviewed again.
+    #[serde(default)]
+    pub(crate) pin_version: Option<String>,
+}
+
+/// A usage of `api` at a particular line of `file`, which is relative to the package root. `hash` is
+/// of the code around the line when the usage was allowed.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AllowedUsage {
+    pub(crate) api: ApiName,
+    pub(crate) file: String,
+    pub(crate) line: u32,
+    pub(crate) hash: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FeatureConfig {
+    #[serde(default)]
+    pub(crate) allow_apis: Vec<ApiName>,
+}
+
+/// The value of `allow_unsafe`. A list of source files is shorthand for `allow_unsafe = true`
+/// together with `allow_unsafe_in`. Once the config is parsed, it's always a bool.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged, expecting = "a bool or a list of source files")]
+pub(crate) enum AllowUnsafe {
+    Bool(bool),
+    Files(Vec<String>),
+}
+
+impl AllowUnsafe {
+    pub(crate) fn is_allowed(&self) -> bool {
+        !matches!(self, AllowUnsafe::Bool(false))
+    }
+}
+
+impl Default for AllowUnsafe {
+    fn default() -> Self {
+        AllowUnsafe::Bool(false)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
@@ -175,15 +559,69 @@ pub(crate) struct FromConfig {
     pub(crate) test: Option<Box<PackageConfig>>,
 }
 
-pub(crate) fn parse_file(cackle_path: &Path, crate_index: &CrateIndex) -> Result<Arc<Config>> {
-    let mut raw_config = parse_file_raw(cackle_path)?;
-    raw_config.load_imports(crate_index)?;
-    raw_config.make_paths_absolute(crate_index.manifest_path.parent())?;
-    let config = Config::from_raw(raw_config, crate_index)?;
-    crate::config_validation::validate(&config, cackle_path)?;
+/// Errors from loading a config file.
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+    /// The file couldn't be read.
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// The file isn't valid TOML or doesn't match the config schema.
+    Parse {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+
+    /// APIs couldn't be imported from a package's exported config.
+    Import(anyhow::Error),
+
+    /// The file parsed, but its contents aren't valid. e.g. it refers to APIs that don't exist.
+    Invalid(InvalidConfig),
+
+    Other(anyhow::Error),
+}
+
+pub(crate) fn parse_file(
+    cackle_path: &Path,
+    crate_index: &CrateIndex,
+    vfs: &dyn Vfs,
+) -> Result<Arc<Config>, ConfigError> {
+    let mut raw_config = parse_file_raw(cackle_path, vfs)?;
+    raw_config
+        .load_imports(crate_index, vfs)
+        .map_err(ConfigError::Import)?;
+    raw_config
+        .make_paths_absolute(crate_index.manifest_path.parent())
+        .map_err(ConfigError::Other)?;
+    let config = Config::from_raw(raw_config, crate_index).map_err(ConfigError::Other)?;
+    crate::config_validation::validate(&config, cackle_path).map_err(ConfigError::Invalid)?;
     Ok(config)
 }
 
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, .. } => write!(f, "Failed to open {}", path.display()),
+            ConfigError::Parse { path, .. } => write!(f, "Failed to parse {}", path.display()),
+            ConfigError::Import(error) | ConfigError::Other(error) => Display::fmt(error, f),
+            ConfigError::Invalid(invalid) => Display::fmt(invalid, f),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source.as_ref()),
+            ConfigError::Import(error) | ConfigError::Other(error) => error.source(),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
 impl Config {
     fn from_raw(raw_config: RawConfig, crate_index: &CrateIndex) -> Result<Arc<Config>> {
         let permissions_no_inheritance = Permissions::from_config(&raw_config);
@@ -197,17 +635,48 @@ impl Config {
     }
 }
 
-fn parse_file_raw(cackle_path: &Path) -> Result<RawConfig> {
-    let cackle: String = std::fs::read_to_string(cackle_path)
-        .with_context(|| format!("Failed to open {}", cackle_path.display()))?;
-    let raw_config =
-        parse_raw(&cackle).with_context(|| format!("Failed to parse {}", cackle_path.display()))?;
-    Ok(raw_config)
+fn parse_file_raw(cackle_path: &Path, vfs: &dyn Vfs) -> Result<RawConfig, ConfigError> {
+    let cackle: String = vfs
+        .read_to_string(cackle_path)
+        .map_err(|source| ConfigError::Read {
+            path: cackle_path.to_owned(),
+            source,
+        })?;
+    parse_raw(&cackle).map_err(|source| ConfigError::Parse {
+        path: cackle_path.to_owned(),
+        source,
+    })
 }
 
 fn parse_raw(cackle: &str) -> Result<RawConfig> {
-    let mut config = toml::from_str(cackle)?;
+    let mut config: RawConfig = toml::from_str(cackle)?;
+    for pkg_config in config.packages.values_mut() {
+        pkg_config.expand_unsafe_files();
+    }
     merge_built_ins(&mut config)?;
+    let detected_apis = [
+        (config.common.detect_raw_syscalls, RAW_SYSCALL_API),
+        (config.common.detect_asm, ASM_API),
+        (config.common.detect_before_main, BEFORE_MAIN_API),
+    ];
+    for (enabled, api) in detected_apis {
+        if enabled {
+            // These APIs have no paths. Usages come from scanning machine code, not from names, so
+            // they just need to exist in order to be allowed.
+            config.apis.entry(ApiName::new(api)).or_default();
+        }
+    }
+    if config.detects_ffi() {
+        // This matches all symbols that aren't mangled by Rust. Once we've scanned a binary, we
+        // drop the usages of those that turn out to come from Rust or from the C library.
+        config
+            .apis
+            .entry(ApiName::new(FFI_API))
+            .or_insert_with(|| ApiConfig {
+                include: vec![ApiPath::from_str(crate::demangle::UNMANGLED_NAMESPACE)],
+                ..ApiConfig::default()
+            });
+    }
     versions::apply_runtime_patches(&mut config);
     config.rustc.sandbox.inherit(&config.sandbox);
     Ok(config)
@@ -235,7 +704,7 @@ fn merge_built_ins(config: &mut RawConfig) -> Result<()> {
 }
 
 impl RawConfig {
-    fn load_imports(&mut self, crate_index: &CrateIndex) -> Result<()> {
+    fn load_imports(&mut self, crate_index: &CrateIndex, vfs: &dyn Vfs) -> Result<()> {
         for (pkg_name, pkg_config) in &mut self.packages {
             // If imports are specified, then we leave an empty list of imports. This ensures that
             // later in unused_imports, we can determine whether each package specified imports or
@@ -254,7 +723,7 @@ impl RawConfig {
                 .ok_or_else(|| {
                     anyhow!("Attempted to import APIs from package `{pkg_name}` that wasn't found")
                 })?;
-            let pkg_exports = exported_config_for_package(pkg_id, crate_index)?;
+            let pkg_exports = exported_config_for_package(pkg_id, crate_index, vfs)?;
             for (api_name, api_def) in &pkg_exports.apis {
                 if !imports.iter().any(|imp| imp == api_name.name.as_ref()) {
                     // The user didn't request importing this API, so skip it.
@@ -278,7 +747,7 @@ impl RawConfig {
     /// Return warnings for all packages that export APIs but where we have no import for that
     /// package. Users can suppress this warning by either importing an API, or if they don't want
     /// to import any APIs from this package, by listing `import = []`.
-    pub(crate) fn unused_imports(&self, crate_index: &CrateIndex) -> ProblemList {
+    pub(crate) fn unused_imports(&self, crate_index: &CrateIndex, vfs: &dyn Vfs) -> ProblemList {
         let mut problems = ProblemList::default();
         for pkg_id in crate_index.package_ids() {
             // If our config lists any import for this package, even empty, then we skip this.
@@ -289,7 +758,7 @@ impl RawConfig {
             {
                 continue;
             }
-            let Ok(pkg_exports) = exported_config_for_package(pkg_id, crate_index) else {
+            let Ok(pkg_exports) = exported_config_for_package(pkg_id, crate_index, vfs) else {
                 continue;
             };
             for (api, config) in &pkg_exports.apis {
@@ -305,6 +774,13 @@ impl RawConfig {
 }
 
 impl RawConfig {
+    /// Returns whether we need to look for references to foreign code. As well as when
+    /// `common.detect_ffi` is set, we do this if any package is `pure`, but then only report the
+    /// references from pure packages.
+    pub(crate) fn detects_ffi(&self) -> bool {
+        self.common.detect_ffi || self.packages.values().any(|pkg_config| pkg_config.pure)
+    }
+
     fn make_paths_absolute(&mut self, workspace_root: Option<&Path>) -> Result<()> {
         for pkg_config in self.packages.values_mut() {
             pkg_config.make_paths_absolute(workspace_root)?;
@@ -314,6 +790,30 @@ impl RawConfig {
 }
 
 impl PackageConfig {
+    /// Moves any source files listed in `allow_unsafe` into `allow_unsafe_in`.
+    fn expand_unsafe_files(&mut self) {
+        if let AllowUnsafe::Files(files) = &mut self.allow_unsafe {
+            let files = std::mem::take(files);
+            self.allow_unsafe_in
+                .get_or_insert_with(Vec::new)
+                .extend(files);
+            self.allow_unsafe = AllowUnsafe::Bool(true);
+        }
+        let (from_build, from_test) = match self.from.as_mut() {
+            Some(from) => (from.build.as_mut(), from.test.as_mut()),
+            None => (None, None),
+        };
+        let sub_configs = [
+            self.build.as_mut(),
+            self.test.as_mut(),
+            from_build,
+            from_test,
+        ];
+        for sub_config in sub_configs.into_iter().flatten() {
+            sub_config.expand_unsafe_files();
+        }
+    }
+
     fn make_paths_absolute(&mut self, workspace_root: Option<&Path>) -> Result<()> {
         self.sandbox.make_paths_absolute(workspace_root)?;
         if let Some(sub_config) = self.build.as_mut() {
@@ -351,11 +851,18 @@ fn make_paths_absolute(paths: &mut [PathBuf], workspace_root: Option<&Path>) ->
 }
 
 /// Attempts to load "cackle/export.toml" from the specified package.
-fn exported_config_for_package(pkg_id: &PackageId, crate_index: &CrateIndex) -> Result<RawConfig> {
+fn exported_config_for_package(
+    pkg_id: &PackageId,
+    crate_index: &CrateIndex,
+    vfs: &dyn Vfs,
+) -> Result<RawConfig> {
     let pkg_dir = crate_index
         .pkg_dir(pkg_id)
         .ok_or_else(|| anyhow!("Missing pkg_dir for package `{pkg_id}`"))?;
-    parse_file_raw(&pkg_dir.join("cackle").join("export.toml"))
+    Ok(parse_file_raw(
+        &pkg_dir.join("cackle").join("export.toml"),
+        vfs,
+    )?)
 }
 
 impl Display for ApiName {
@@ -465,8 +972,24 @@ pub(crate) mod testing {
 #[cfg(test)]
 mod tests {
     use super::testing::parse;
+    use crate::config::ASM_API;
+    use crate::config::ApiConfig;
+    use crate::config::ApiName;
+    use crate::config::ApiPath;
+    use crate::config::AttributionStrategy;
+    use crate::config::DisplayConfig;
+    use crate::config::FFI_API;
+    use crate::config::LimitsConfig;
+    use crate::config::RAW_SYSCALL_API;
+    use crate::config::ReportMode;
     use crate::config::SandboxKind;
+    use crate::config::Truncation;
+    use crate::config::built_in::DYNAMIC_LOADING_API;
+    use crate::config::built_in::PROCESS_API;
     use crate::config::permissions::PermSel;
+    use crate::config::permissions::PermissionScope;
+    use std::num::NonZeroUsize;
+    use std::path::Path;
 
     #[test]
     fn empty() {
@@ -475,6 +998,198 @@ mod tests {
         assert!(config.permissions.packages.is_empty());
     }
 
+    #[test]
+    fn report_mode() {
+        assert_eq!(parse("").unwrap().raw.common.report, ReportMode::Shortest);
+        assert_eq!(
+            parse(r#"report = "all""#).unwrap().raw.common.report,
+            ReportMode::All
+        );
+        assert!(parse(r#"report = "some""#).is_err());
+    }
+
+    #[test]
+    fn display_config() {
+        assert_eq!(parse("").unwrap().raw.display, DisplayConfig::default());
+        let config = parse(
+            r#"
+            [display]
+            max_name_length = 80
+            truncate = "end"
+            truncate_json = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.raw.display,
+            DisplayConfig {
+                max_name_length: Some(80),
+                truncate: Truncation::End,
+                truncate_json: true,
+            }
+        );
+        check_unknown_field("[display]");
+    }
+
+    #[test]
+    fn limits_config() {
+        assert_eq!(parse("").unwrap().raw.limits, LimitsConfig::default());
+        let config = parse(
+            r#"
+            [limits]
+            jobs = 4
+            sandboxes = 1
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.raw.limits,
+            LimitsConfig {
+                jobs: NonZeroUsize::new(4),
+                sandboxes: NonZeroUsize::new(1),
+            }
+        );
+        assert!(parse("[limits]\njobs = 0").is_err());
+        check_unknown_field("[limits]");
+    }
+
+    #[test]
+    fn raw_syscall_api() {
+        let raw_syscall = ApiName::new(RAW_SYSCALL_API);
+        assert!(!parse("").unwrap().raw.apis.contains_key(&raw_syscall));
+        let allow = r#"
+            [pkg.foo]
+            allow_apis = ["raw_syscall"]
+            "#;
+        assert!(parse(allow).is_err());
+        let config = parse(&format!("detect_raw_syscalls = true\n{allow}")).unwrap();
+        assert_eq!(config.raw.apis[&raw_syscall], ApiConfig::default());
+        assert!(!config.raw.apis.contains_key(&ApiName::new(ASM_API)));
+        let config = parse("detect_asm = true").unwrap();
+        assert_eq!(
+            config.raw.apis[&ApiName::new(ASM_API)],
+            ApiConfig::default()
+        );
+        assert!(!config.raw.apis.contains_key(&ApiName::new(FFI_API)));
+        let config = parse("detect_ffi = true").unwrap();
+        assert_eq!(
+            config.raw.apis[&ApiName::new(FFI_API)].include,
+            vec![ApiPath::from_str("extern")]
+        );
+    }
+
+    #[test]
+    fn pure_packages() {
+        let config = parse("[pkg.foo]\npure = true\n[pkg.foo.test]\nallow_unsafe = true").unwrap();
+        assert!(config.raw.apis.contains_key(&ApiName::new(FFI_API)));
+        assert!(config.permissions.is_pure(&PermSel::for_primary("foo")));
+        assert!(!config.permissions.is_pure(&PermSel::with_scope(
+            &crate::crate_index::testing::pkg_id("foo"),
+            PermissionScope::Test
+        )));
+        assert!(parse("[api.fs]\n[pkg.foo]\npure = true\nallow_apis = [\"fs\"]").is_err());
+        assert!(parse("[pkg.foo]\npure = true\nallow_unsafe = true").is_err());
+        assert!(
+            parse("[api.fs]\n[pkg.foo]\npure = true\nfrom.build.allow_apis = [\"fs\"]").is_err()
+        );
+    }
+
+    #[test]
+    fn import_dynamic_loading() {
+        let config = parse(r#"import_std = ["dynamic_loading"]"#).unwrap();
+        let api = &config.raw.apis[&ApiName::new(DYNAMIC_LOADING_API)];
+        for path in ["extern::dlopen", "extern::dlsym", "libloading::Library"] {
+            assert!(api.include.contains(&ApiPath::from_str(path)));
+        }
+    }
+
+    #[test]
+    fn import_process() {
+        let config = parse(r#"import_std = ["process"]"#).unwrap();
+        let api = &config.raw.apis[&ApiName::new(PROCESS_API)];
+        for path in [
+            "std::process",
+            "std::os::unix::process",
+            "extern::execve",
+            "extern::posix_spawn",
+            "extern::fork",
+            "extern::CreateProcessW",
+        ] {
+            assert!(api.include.contains(&ApiPath::from_str(path)));
+        }
+        assert!(
+            api.exclude
+                .contains(&ApiPath::from_str("std::process::exit"))
+        );
+    }
+
+    #[test]
+    fn import_std_api() {
+        let config = parse(r#"import_std_api = ["thread", "unsafe_ffi"]"#).unwrap();
+        assert!(
+            config.raw.apis[&ApiName::new("thread")]
+                .include
+                .contains(&ApiPath::from_str("std::thread::spawn"))
+        );
+        assert!(
+            config.raw.apis[&ApiName::new("unsafe_ffi")]
+                .include
+                .contains(&ApiPath::from_str("core::ffi::c_str::CStr::from_ptr"))
+        );
+        assert!(parse(r#"import_std_api = ["threads"]"#).is_err());
+    }
+
+    #[test]
+    fn attribution_strategies() {
+        assert_eq!(
+            parse("").unwrap().raw.common.attribution_strategies(),
+            &[AttributionStrategy::Path]
+        );
+        let config = parse(r#"attribution = ["compilation_unit", "path"]"#).unwrap();
+        assert_eq!(
+            config.raw.common.attribution_strategies(),
+            &[
+                AttributionStrategy::CompilationUnit,
+                AttributionStrategy::Path
+            ]
+        );
+        assert!(parse(r#"attribution = ["guess"]"#).is_err());
+    }
+
+    #[test]
+    fn migration_mode() {
+        assert!(parse("").unwrap().raw.migration_mode.is_none());
+        let config = parse(
+            r#"
+            [migration_mode]
+            baseline = "cackle-baseline.json"
+            until = 2025-06-30
+            "#,
+        )
+        .unwrap();
+        let migration_mode = config.raw.migration_mode.as_ref().unwrap();
+        assert_eq!(migration_mode.baseline, Path::new("cackle-baseline.json"));
+        assert_eq!(migration_mode.until.unwrap().to_string(), "2025-06-30");
+        assert!(
+            parse(
+                r#"
+                [migration_mode]
+                baseline = "cackle-baseline.json"
+                until = 2025-06-30T12:00:00
+                "#,
+            )
+            .is_err()
+        );
+        let config = parse(
+            r#"
+            [migration_mode]
+            baseline = "cackle-baseline.json"
+            "#,
+        )
+        .unwrap();
+        assert!(config.raw.migration_mode.as_ref().unwrap().until.is_none());
+    }
+
     #[track_caller]
     fn check_unknown_field(context: &str) {
         // Make sure that without the unknown field, it parses OK.
@@ -601,4 +1316,59 @@ mod tests {
         assert!(parse("[pkg.x.test.dep]").is_err());
         assert!(parse("[pkg.x.test.test]").is_err());
     }
+
+    #[test]
+    fn parse_file_with_imports() {
+        let mut fs = crate::vfs::testing::MemoryFs::default();
+        fs.add(
+            "/project/cackle.toml",
+            r#"
+            [common]
+            version = 1
+
+            [pkg.foo]
+            import = ["terminate"]
+            "#,
+        );
+        // Packages in the test index have an empty directory.
+        fs.add(
+            "cackle/export.toml",
+            r#"
+            [common]
+            version = 1
+
+            [api.terminate]
+            include = ["foo::exit"]
+            "#,
+        );
+        let crate_index = crate::crate_index::testing::index_with_package_names(&["foo"]);
+        let config =
+            super::parse_file(Path::new("/project/cackle.toml"), &crate_index, &fs).unwrap();
+        assert_eq!(
+            config
+                .raw
+                .apis
+                .keys()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>(),
+            ["foo::terminate"]
+        );
+        assert!(
+            super::parse_file(Path::new("/project/missing.toml"), &crate_index, &fs)
+                .unwrap_err()
+                .to_string()
+                .contains("Failed to open /project/missing.toml")
+        );
+        let error =
+            super::parse_file(Path::new("/project/missing.toml"), &crate_index, &fs).unwrap_err();
+        let super::ConfigError::Read { source, .. } = error else {
+            panic!("Expected read error, got {error}");
+        };
+        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        fs.add("/project/bad.toml", "[common");
+        assert!(matches!(
+            super::parse_file(Path::new("/project/bad.toml"), &crate_index, &fs),
+            Err(super::ConfigError::Parse { .. })
+        ));
+    }
 }
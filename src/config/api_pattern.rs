@@ -0,0 +1,146 @@
+//! Patterns that can be used in place of a path prefix in an API's `include` or `exclude` list.
+//! Globs contain `*` or `?`, e.g. `tokio::net::*` or `std::fs::read*`. Within a part of the name,
+//! `*` matches any number of characters and `?` matches one. A part that's just `**` matches one
+//! or more whole parts. Regular expressions are written between slashes, e.g.
+//! `/std::fs::(read|write)(_to_string)?/`. Either way, the pattern must match from the start of the
+//! name to the end of one of its parts, so like a plain path, a pattern matches everything under
+//! what it matches.
+
+use super::ApiPath;
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+
+#[derive(Debug)]
+pub(crate) struct ApiPattern {
+    regex: Regex,
+}
+
+impl ApiPattern {
+    /// Returns the pattern for `path`, or `None` if `path` is just a prefix.
+    pub(crate) fn from_path(path: &ApiPath) -> Result<Option<ApiPattern>> {
+        let path: &str = path.as_ref();
+        let pattern = if let Some(pattern) = path
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            pattern.to_owned()
+        } else if path.contains(['*', '?']) {
+            glob_to_regex(path, "::", 1)
+        } else {
+            return Ok(None);
+        };
+        let regex = Regex::new(&format!("^(?:{pattern})(?:::|$)"))
+            .with_context(|| format!("Invalid API pattern `{path}`"))?;
+        Ok(Some(ApiPattern { regex }))
+    }
+
+    /// Returns the number of parts of `name`, which should have parts separated by `::`, that this
+    /// pattern matches, or `None` if it doesn't match.
+    pub(crate) fn matched_parts(&self, name: &str) -> Option<usize> {
+        let matched = self.regex.find(name)?.as_str();
+        let matched = matched.strip_suffix("::").unwrap_or(matched);
+        Some(matched.split("::").count())
+    }
+}
+
+/// Returns a regex for `glob`, whose parts are separated by `separator`. Within a part, `*` matches
+/// any number of characters and `?` matches one. A part that's just `**` matches any number of
+/// whole parts, but at least `min_double_star_parts`. Everything else matches literally, so the
+/// regex is always valid.
+pub(super) fn glob_to_regex(glob: &str, separator: &str, min_double_star_parts: usize) -> String {
+    let separator_regex = regex::escape(separator);
+    let part_char = format!("[^{separator_regex}]");
+    let mut out = String::new();
+    let mut parts = glob.split(separator).peekable();
+    while let Some(part) = parts.next() {
+        let is_last = parts.peek().is_none();
+        if part == "**" {
+            if is_last {
+                // Whole parts, then whatever remains of the name, which being at the end, always
+                // counts as at least one part.
+                out.push_str(&format!("(?:{part_char}*{separator_regex})*{part_char}*"));
+            } else {
+                // Whole parts, together with the separator that follows.
+                out.push_str(&format!(
+                    "(?:{part_char}*{separator_regex}){{{min_double_star_parts},}}"
+                ));
+            }
+            continue;
+        }
+        for ch in part.chars() {
+            match ch {
+                '*' => out.push_str(&format!("{part_char}*")),
+                '?' => out.push_str(&part_char),
+                _ => out.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        if !is_last {
+            out.push_str(&separator_regex);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiPattern;
+    use crate::config::ApiPath;
+
+    fn matched_parts(pattern: &str, name: &str) -> Option<usize> {
+        ApiPattern::from_path(&ApiPath::from_str(pattern))
+            .unwrap()
+            .unwrap()
+            .matched_parts(name)
+    }
+
+    #[test]
+    fn not_a_pattern() {
+        assert!(
+            ApiPattern::from_path(&ApiPath::from_str("std::fs"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn globs() {
+        assert_eq!(
+            matched_parts("tokio::net::*", "tokio::net::TcpStream"),
+            Some(3)
+        );
+        assert_eq!(
+            matched_parts("tokio::net::*", "tokio::net::TcpStream::connect"),
+            Some(3)
+        );
+        assert_eq!(matched_parts("tokio::net::*", "tokio::net"), None);
+        assert_eq!(matched_parts("tokio::net::*", "tokio::fs::File"), None);
+        assert_eq!(
+            matched_parts("std::fs::read*", "std::fs::read_dir"),
+            Some(3)
+        );
+        assert_eq!(matched_parts("std::fs::read*", "std::fs::write"), None);
+        assert_eq!(matched_parts("std::fs::rea?", "std::fs::read"), Some(3));
+        assert_eq!(matched_parts("std::fs::rea?", "std::fs::read_dir"), None);
+        assert_eq!(matched_parts("*::net", "tokio::net::TcpStream"), Some(2));
+        assert_eq!(
+            matched_parts("tokio::**::connect", "tokio::net::TcpStream::connect"),
+            Some(4)
+        );
+        assert_eq!(matched_parts("tokio::**::connect", "tokio::connect"), None);
+        // Regex metacharacters in globs match literally.
+        assert_eq!(matched_parts("a.b*", "axb"), None);
+    }
+
+    #[test]
+    fn regexes() {
+        let pattern = "/std::fs::(read|write)(_to_string)?/";
+        assert_eq!(matched_parts(pattern, "std::fs::read"), Some(3));
+        assert_eq!(matched_parts(pattern, "std::fs::read_to_string"), Some(3));
+        assert_eq!(matched_parts(pattern, "std::fs::write::inner"), Some(3));
+        assert_eq!(matched_parts(pattern, "std::fs::read_dir"), None);
+        // Patterns are anchored at the start of the name.
+        assert_eq!(matched_parts(pattern, "mycrate::std::fs::read"), None);
+        assert!(ApiPattern::from_path(&ApiPath::from_str("/std::(/")).is_err());
+    }
+}
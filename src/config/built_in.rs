@@ -3,6 +3,69 @@ use super::ApiName;
 use super::ApiPath;
 use std::collections::BTreeMap;
 
+/// The built-in API for loading code at runtime.
+pub(crate) const DYNAMIC_LOADING_API: &str = "dynamic_loading";
+
+/// The built-in API for running other programs.
+pub(crate) const PROCESS_API: &str = "process";
+
+/// Paths for the built-in `time` API. Besides the standard library, we include C functions (see
+/// `demangle::UNMANGLED_NAMESPACE`) and the vDSO functions that libc calls on Linux, since crates
+/// like rustix call these via the vDSO directly. `getauxval` is included, since it can be used to
+/// locate the vDSO.
+pub(crate) const TIME_PATHS: &[&str] = &[
+    "std::time::Instant::now",
+    "std::time::Instant::elapsed",
+    "std::time::SystemTime::now",
+    "std::time::SystemTime::elapsed",
+    "extern::clock_gettime",
+    "extern::clock_gettime64",
+    "extern::gettimeofday",
+    "extern::time",
+    "extern::__vdso_clock_gettime",
+    "extern::__vdso_gettimeofday",
+    "extern::__vdso_time",
+    "extern::__kernel_clock_gettime",
+    "extern::__kernel_gettimeofday",
+    "extern::getauxval",
+    "rustix::time::clock_gettime",
+    "rustix::time::clock_gettime_dynamic",
+];
+
+/// Paths for the built-in `entropy` API. `getauxval` is included here too, since `AT_RANDOM`
+/// provides random bytes.
+pub(crate) const ENTROPY_PATHS: &[&str] = &[
+    "std::hash::random::RandomState::new",
+    "std::collections::hash::map::RandomState::new",
+    "extern::getrandom",
+    "extern::getentropy",
+    "extern::arc4random",
+    "extern::arc4random_buf",
+    "extern::getauxval",
+    "extern::__vdso_getrandom",
+    "rustix::rand::getrandom",
+    "getrandom::getrandom",
+    "getrandom::fill",
+];
+
+/// Paths for the built-in `float_env` API, which covers changing the floating-point environment,
+/// e.g. the rounding mode or whether denormals are flushed to zero. This changes the results of
+/// floating-point operations in other code running on the same thread.
+pub(crate) const FLOAT_ENV_PATHS: &[&str] = &[
+    "extern::fesetround",
+    "extern::fesetenv",
+    "extern::feholdexcept",
+    "extern::feupdateenv",
+    "extern::fesetexceptflag",
+    "extern::feenableexcept",
+    "extern::fedisableexcept",
+    "extern::_controlfp",
+    "extern::_controlfp_s",
+    "extern::_control87",
+    "core::arch::x86::_mm_setcsr",
+    "core::arch::x86_64::_mm_setcsr",
+];
+
 pub(crate) fn get_built_ins() -> BTreeMap<ApiName, ApiConfig> {
     let mut result = BTreeMap::new();
     result.insert(
@@ -22,7 +85,30 @@ pub(crate) fn get_built_ins() -> BTreeMap<ApiName, ApiConfig> {
             &[],
         ),
     );
-    result.insert(ApiName::from("env"), perm(&["std::env"], &[]));
+    // Environment variables often hold secrets. Besides `std::env`, we include the C and Windows
+    // functions, since crates can call these directly.
+    result.insert(
+        ApiName::from("env"),
+        perm(
+            &[
+                "std::env",
+                "extern::getenv",
+                "extern::secure_getenv",
+                "extern::setenv",
+                "extern::unsetenv",
+                "extern::putenv",
+                "extern::clearenv",
+                "extern::environ",
+                "extern::GetEnvironmentVariableA",
+                "extern::GetEnvironmentVariableW",
+                "extern::SetEnvironmentVariableA",
+                "extern::SetEnvironmentVariableW",
+                "extern::GetEnvironmentStringsA",
+                "extern::GetEnvironmentStringsW",
+            ],
+            &[],
+        ),
+    );
     result.insert(
         ApiName::from("net"),
         perm(
@@ -34,17 +120,126 @@ pub(crate) fn get_built_ins() -> BTreeMap<ApiName, ApiConfig> {
         ApiName::from("unix_sockets"),
         perm(&["std::os::unix::net"], &[]),
     );
+    // Another program isn't analysed, so could do anything. Besides `std::process`, we include the
+    // C and Windows functions that start programs, or that fork the current process, since crates can
+    // call these directly.
     result.insert(
-        ApiName::from("process"),
+        ApiName::from(PROCESS_API),
         perm(
             &[
                 "std::process",
-                "std::unix::process",
-                "std::windows::process",
+                "std::os::unix::process",
+                "std::os::windows::process",
+                "extern::fork",
+                "extern::vfork",
+                "extern::execve",
+                "extern::execv",
+                "extern::execvp",
+                "extern::execvpe",
+                "extern::execl",
+                "extern::execle",
+                "extern::execlp",
+                "extern::fexecve",
+                "extern::posix_spawn",
+                "extern::posix_spawnp",
+                "extern::system",
+                "extern::popen",
+                "extern::CreateProcessA",
+                "extern::CreateProcessW",
+                "extern::CreateProcessAsUserW",
+                "extern::ShellExecuteA",
+                "extern::ShellExecuteW",
+                "extern::ShellExecuteExW",
+                "extern::WinExec",
             ],
             &["std::process::abort", "std::process::exit"],
         ),
     );
+    // Spawning threads. Where std defines these functions changed over time, so we include the old
+    // and new paths.
+    result.insert(
+        ApiName::from("thread"),
+        perm(
+            &[
+                "std::thread::spawn",
+                "std::thread::functions::spawn",
+                "std::thread::Builder::spawn",
+                "std::thread::Builder::spawn_scoped",
+                "std::thread::Builder::spawn_unchecked",
+                "std::thread::builder::Builder::spawn",
+                "std::thread::builder::Builder::spawn_scoped",
+                "std::thread::builder::Builder::spawn_unchecked",
+                "std::thread::scoped::Scope::spawn",
+                "extern::pthread_create",
+                "extern::thrd_create",
+                "extern::CreateThread",
+                "extern::_beginthreadex",
+            ],
+            &[],
+        ),
+    );
+    result.insert(ApiName::from("time"), perm(TIME_PATHS, &[]));
+    result.insert(ApiName::from("entropy"), perm(ENTROPY_PATHS, &[]));
+    // Signal handlers affect the whole process, so installing one is worth reviewing. Besides the
+    // libc functions, we include the crates commonly used to install handlers.
+    result.insert(
+        ApiName::from("signal"),
+        perm(
+            &[
+                "extern::sigaction",
+                "extern::signal",
+                "extern::sigset",
+                "extern::bsd_signal",
+                "extern::__sysv_signal",
+                "nix::sys::signal::sigaction",
+                "nix::sys::signal::signal",
+                "rustix::runtime::kernel_sigaction",
+                "signal_hook_registry::register",
+                "signal_hook_registry::register_sigaction",
+                "signal_hook_registry::register_signal_unchecked",
+                "signal_hook_registry::register_unchecked",
+            ],
+            &[],
+        ),
+    );
+    // Code that's loaded at runtime is invisible to us, so could use any API. Besides the C
+    // functions, on both Unix and Windows, we include `libloading`, which most crates use.
+    result.insert(
+        ApiName::from(DYNAMIC_LOADING_API),
+        perm(
+            &[
+                "extern::dlopen",
+                "extern::dlmopen",
+                "extern::dlsym",
+                "extern::dlvsym",
+                "extern::LoadLibraryA",
+                "extern::LoadLibraryW",
+                "extern::LoadLibraryExA",
+                "extern::LoadLibraryExW",
+                "extern::GetProcAddress",
+                "libloading::Library",
+                "libloading::os::unix::Library",
+                "libloading::os::windows::Library",
+            ],
+            &[],
+        ),
+    );
+    result.insert(ApiName::from("float_env"), perm(FLOAT_ENV_PATHS, &[]));
+    // Functions that turn raw pointers, such as those passed across FFI, back into references or
+    // owned values. The pointers need to be valid, which the compiler can't check.
+    result.insert(
+        ApiName::from("unsafe_ffi"),
+        perm(
+            &[
+                "core::ffi::c_str::CStr::from_ptr",
+                "alloc::ffi::c_str::CString::from_raw",
+                "core::slice::raw::from_raw_parts",
+                "core::slice::raw::from_raw_parts_mut",
+                "alloc::string::String::from_raw_parts",
+            ],
+            &[],
+        ),
+    );
     result.insert(
         ApiName::from("terminate"),
         perm(&["std::process::abort", "std::process::exit"], &[]),
@@ -57,5 +252,6 @@ fn perm(include: &[&str], exclude: &[&str]) -> ApiConfig {
         include: include.iter().map(|s| ApiPath::from_str(s)).collect(),
         exclude: exclude.iter().map(|s| ApiPath::from_str(s)).collect(),
         no_auto_detect: Vec::new(),
+        severity: Default::default(),
     }
 }
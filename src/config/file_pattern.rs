@@ -0,0 +1,79 @@
+//! Patterns for the source files listed in `allow_unsafe_in`. Paths are relative to the package
+//! root. A plain path matches that file, or if it's a directory, e.g. `src/simd`, every file under
+//! it, which is how a whole module can be allowed. Globs contain `*` or `?`, e.g. `src/simd/*.rs`.
+//! Within a path component, `*` matches any number of characters and `?` matches one. A component
+//! that's just `**` matches any number of directories.
+
+use super::api_pattern::glob_to_regex;
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+use std::path::Path;
+
+#[derive(Debug)]
+pub(crate) enum FilePattern {
+    /// A file, or a directory and everything under it.
+    Path(String),
+    Glob(Regex),
+}
+
+impl FilePattern {
+    pub(crate) fn new(pattern: &str) -> Result<Self> {
+        let pattern = pattern.trim_end_matches('/');
+        if !pattern.contains(['*', '?']) {
+            return Ok(FilePattern::Path(pattern.to_owned()));
+        }
+        let regex = Regex::new(&format!("^{}(?:/|$)", glob_to_regex(pattern, "/", 0)))
+            .with_context(|| format!("Invalid file pattern `{pattern}`"))?;
+        Ok(FilePattern::Glob(regex))
+    }
+
+    /// Returns whether the file at `path`, which should be relative to the package root, is
+    /// matched by this pattern.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        let Some(path) = path.to_str() else {
+            return false;
+        };
+        let path = path.replace('\\', "/");
+        match self {
+            FilePattern::Path(prefix) => path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+            FilePattern::Glob(regex) => regex.is_match(&path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilePattern;
+    use std::path::Path;
+
+    fn check(pattern: &str, path: &str) -> bool {
+        FilePattern::new(pattern).unwrap().matches(Path::new(path))
+    }
+
+    #[test]
+    fn plain_paths() {
+        assert!(check("src/ffi.rs", "src/ffi.rs"));
+        assert!(!check("src/ffi.rs", "src/ffi.rs.bak"));
+        assert!(check("src/simd", "src/simd/x86.rs"));
+        assert!(check("src/simd/", "src/simd/arch/x86.rs"));
+        assert!(!check("src/simd", "src/simd_utils.rs"));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(check("src/simd/*.rs", "src/simd/x86.rs"));
+        assert!(!check("src/simd/*.rs", "src/simd/arch/x86.rs"));
+        assert!(!check("src/simd/*.rs", "src/lib.rs"));
+        assert!(check("src/simd/**/*.rs", "src/simd/x86.rs"));
+        assert!(check("src/simd/**/*.rs", "src/simd/arch/x86.rs"));
+        assert!(check("src/simd/**", "src/simd/arch/x86.rs"));
+        assert!(check("**/ffi.rs", "src/sys/ffi.rs"));
+        assert!(check("src/ffi?.rs", "src/ffi2.rs"));
+        assert!(!check("src/ffi?.rs", "src/ffi.rs"));
+        // Regex metacharacters match literally.
+        assert!(!check("src/a.*", "src/ab"));
+    }
+}
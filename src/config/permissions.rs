@@ -1,3 +1,4 @@
+use super::AllowUnsafe;
 use super::PackageConfig;
 use super::PackageName;
 use super::RawConfig;
@@ -115,10 +116,26 @@ impl Permissions {
             .unwrap_or_default()
     }
 
+    /// Returns whether `perm_sel` is marked `pure`, meaning that it can't be granted anything.
+    pub(crate) fn is_pure(&self, perm_sel: &PermSel) -> bool {
+        self.packages
+            .get(perm_sel)
+            .is_some_and(|crate_config| crate_config.pure)
+    }
+
     pub(crate) fn unsafe_permitted_for_crate(&self, crate_sel: &CrateSel) -> bool {
         self.packages
             .get(&PermSel::for_non_build_output(crate_sel))
-            .is_some_and(|crate_config| crate_config.allow_unsafe)
+            .is_some_and(|crate_config| crate_config.allow_unsafe.is_allowed())
+    }
+
+    /// Returns the source files to which unsafe is restricted for `crate_sel`, or None if unsafe is
+    /// either not permitted or permitted anywhere in the crate.
+    pub(crate) fn unsafe_files_for_crate(&self, crate_sel: &CrateSel) -> Option<&[String]> {
+        self.packages
+            .get(&PermSel::for_non_build_output(crate_sel))
+            .filter(|crate_config| crate_config.allow_unsafe.is_allowed())
+            .and_then(|crate_config| crate_config.allow_unsafe_in.as_deref())
     }
 
     pub(crate) fn get(&self, perm_sel: &PermSel) -> Option<&PackageConfig> {
@@ -156,6 +173,9 @@ fn apply_inheritance(packages: &mut FxHashMap<PermSel, PackageConfig>, config: &
     for (perm_sel, config) in dep.iter_mut() {
         if let Some(parent) = all.get(&perm_sel.clone_with_scope(PermissionScope::All)) {
             config.inherit(parent);
+            // This is the same code, just used from somewhere else, so it's pure if the package is.
+            // A package's own build script and tests are different code, so aren't necessarily.
+            config.pure |= parent.pure;
         }
     }
     for (perm_sel, config) in local.iter_mut() {
@@ -182,8 +202,39 @@ impl PackageConfig {
             &mut self.allow_build_instructions,
             &other.allow_build_instructions,
         );
+        merge_string_vec(&mut self.allow_executables, &other.allow_executables);
+        merge_string_vec(&mut self.allow_binary_files, &other.allow_binary_files);
+        merge_string_vec(&mut self.allow_native_libs, &other.allow_native_libs);
+        merge_string_vec(&mut self.allow_denied_symbols, &other.allow_denied_symbols);
+        merge_string_vec(&mut self.allow_usages, &other.allow_usages);
+        for (feature, other_config) in &other.if_feature {
+            let config = self.if_feature.entry(feature.clone()).or_default();
+            merge_string_vec(&mut config.allow_apis, &other_config.allow_apis);
+        }
+        for (api, budget) in &other.max_usages {
+            self.max_usages.entry(api.clone()).or_insert(*budget);
+        }
         self.allow_proc_macro |= other.allow_proc_macro;
-        self.allow_unsafe |= other.allow_unsafe;
+        self.disassemble |= other.disassemble;
+        self.no_panic |= other.no_panic;
+        self.no_alloc |= other.no_alloc;
+        if self.pin_version.is_none() {
+            self.pin_version = other.pin_version.clone();
+        }
+        if other.allow_unsafe.is_allowed() {
+            if !self.allow_unsafe.is_allowed() {
+                self.allow_unsafe_in = other.allow_unsafe_in.clone();
+            } else if let (Some(files), Some(other_files)) = (
+                self.allow_unsafe_in.as_mut(),
+                other.allow_unsafe_in.as_ref(),
+            ) {
+                merge_string_vec(files, other_files);
+            } else {
+                // Unsafe is permitted anywhere at one of the levels, so it's permitted anywhere.
+                self.allow_unsafe_in = None;
+            }
+            self.allow_unsafe = AllowUnsafe::Bool(true);
+        }
         self.sandbox.inherit(&other.sandbox);
     }
 }
@@ -418,11 +469,90 @@ fn test_inheritance() {
     .unwrap();
 
     let bar1_dep_test_config = config.permissions.get(&bar1_dep_test).unwrap();
-    assert!(bar1_dep_test_config.allow_unsafe);
+    assert!(bar1_dep_test_config.allow_unsafe.is_allowed());
     let bar1_test_config = config
         .permissions
         .get(&bar1.clone_with_scope(PermissionScope::Test))
         .unwrap();
-    assert!(bar1_test_config.allow_unsafe);
+    assert!(bar1_test_config.allow_unsafe.is_allowed());
     assert_eq!(bar1_test_config.allow_apis, &["fs", "process"])
 }
+
+#[test]
+fn test_unsafe_file_inheritance() {
+    let bar1 = PermSel::for_primary("bar1");
+    let mut crate_index = CrateIndex::default();
+    for scope in [PermissionScope::FromTest, PermissionScope::Test] {
+        crate_index
+            .permission_selectors
+            .insert(bar1.clone_with_scope(scope));
+    }
+    let raw = super::parse_raw(
+        r#"
+        [common]
+        version = 1
+
+        [pkg.bar1]
+        allow_unsafe = true
+        allow_unsafe_in = ["src/ffi.rs"]
+
+        [pkg.bar1.test]
+        allow_unsafe = true
+        allow_unsafe_in = ["src/test_ffi.rs"]
+    "#,
+    )
+    .unwrap();
+    let config = crate::config::Config::from_raw(raw, &crate_index).unwrap();
+    let crate_sel = CrateSel::primary(crate::crate_index::testing::pkg_id("bar1"));
+    assert_eq!(
+        config.permissions.unsafe_files_for_crate(&crate_sel),
+        Some(["src/ffi.rs".to_owned()].as_slice())
+    );
+    let test_config = config
+        .permissions
+        .get(&bar1.clone_with_scope(PermissionScope::Test))
+        .unwrap();
+    assert_eq!(
+        test_config.allow_unsafe_in.as_deref(),
+        Some(["src/ffi.rs".to_owned(), "src/test_ffi.rs".to_owned()].as_slice())
+    );
+}
+
+#[test]
+fn test_unsafe_files_in_allow_unsafe() {
+    let bar1 = PermSel::for_primary("bar1");
+    let mut crate_index = CrateIndex::default();
+    for scope in [PermissionScope::FromTest, PermissionScope::Test] {
+        crate_index
+            .permission_selectors
+            .insert(bar1.clone_with_scope(scope));
+    }
+    let raw = super::parse_raw(
+        r#"
+        [common]
+        version = 1
+
+        [pkg.bar1]
+        allow_unsafe = ["src/simd/*.rs"]
+
+        [pkg.bar1.test]
+        allow_unsafe = ["src/test_ffi.rs"]
+    "#,
+    )
+    .unwrap();
+    let config = crate::config::Config::from_raw(raw, &crate_index).unwrap();
+    let crate_sel = CrateSel::primary(crate::crate_index::testing::pkg_id("bar1"));
+    assert!(config.permissions.unsafe_permitted_for_crate(&crate_sel));
+    assert_eq!(
+        config.permissions.unsafe_files_for_crate(&crate_sel),
+        Some(["src/simd/*.rs".to_owned()].as_slice())
+    );
+    let test_config = config
+        .permissions
+        .get(&bar1.clone_with_scope(PermissionScope::Test))
+        .unwrap();
+    assert_eq!(
+        test_config.allow_unsafe_in.as_deref(),
+        Some(["src/simd/*.rs".to_owned(), "src/test_ffi.rs".to_owned()].as_slice())
+    );
+}
@@ -1,6 +1,9 @@
 //! This module is responsible for applying automatic edits to cackle.toml.
 
+use crate::checker::api_map::ApiRules;
 use crate::checker::common_prefix::common_to_prefixes;
+use crate::config::AllowedUsage;
+use crate::config::ApiConfig;
 use crate::config::ApiName;
 use crate::config::ApiPath;
 use crate::config::Config;
@@ -66,6 +69,7 @@ pub(crate) struct EditOpts {
 pub(crate) fn fixes_for_problem(problem: &Problem, config: &Config) -> Vec<Box<dyn Edit>> {
     let mut edits: Vec<Box<dyn Edit>> = Vec::new();
     match problem {
+        Problem::Warning(problem) => return fixes_for_problem(problem, config),
         Problem::MissingConfiguration(_) => {
             edits.push(Box::new(CreateRecommendedConfig));
             edits.push(Box::new(CreateCustomConfig));
@@ -86,7 +90,18 @@ pub(crate) fn fixes_for_problem(problem: &Problem, config: &Config) -> Vec<Box<d
             edits.push(Box::new(IgnoreApi(available.clone())));
         }
         Problem::DisallowedApiUsage(usage) => {
-            usage.add_allow_api_fixes(&mut edits);
+            usage.add_narrower_api_fixes(&mut edits, config);
+            // Pure packages can't be granted anything, so the only fixes are to API definitions.
+            if !config.permissions.is_pure(&usage.perm_sel()) {
+                usage.add_allow_api_fixes(&mut edits);
+                if !usage.usage_pins.is_empty() {
+                    edits.push(Box::new(AllowUsagesAtLocations {
+                        perm_sel: usage.perm_sel(),
+                        pins: usage.usage_pins.clone(),
+                    }));
+                }
+            }
+            usage.add_only_used_path_fix(&mut edits, config);
             let _ = usage.add_exclude_fixes(&mut edits, config);
         }
         Problem::IsProcMacro(pkg_id) => {
@@ -108,9 +123,62 @@ pub(crate) fn fixes_for_problem(problem: &Problem, config: &Config) -> Vec<Box<d
         Problem::DisallowedBuildInstruction(failure) => {
             edits.append(&mut edits_for_build_instruction(failure));
         }
-        Problem::DisallowedUnsafe(failure) => edits.push(Box::new(AllowUnsafe {
-            perm_sel: PermSel::for_non_build_output(&failure.crate_sel),
+        Problem::BuildScriptChanged(failure) => edits.push(Box::new(PinBuildScript {
+            perm_sel: PermSel::for_build_script(failure.pkg_id.name_str()),
+            sha256: failure.sha256.clone(),
+        })),
+        Problem::ExecutableWritten(failure) => edits.push(Box::new(AllowExecutable {
+            perm_sel: PermSel::for_build_script(failure.pkg_id.name_str()),
+            sha256: failure.sha256.clone(),
+        })),
+        Problem::BinaryFileInSources(failure) => edits.push(Box::new(AllowBinaryFile {
+            perm_sel: PermSel::for_primary(failure.pkg_id.name_str()),
+            sha256: failure.sha256.clone(),
+        })),
+        Problem::DisallowedNativeLibrary(failure) => edits.push(Box::new(AllowNativeLibrary {
+            perm_sel: failure.perm_sel.clone(),
+            library: failure.library.clone(),
+        })),
+        Problem::VersionPinMismatch(info) => edits.push(Box::new(UpdatePinVersion {
+            perm_sel: info.perm_sel.clone(),
+            version: info.usages.pkg_id.version().to_string(),
         })),
+        Problem::UsageBudgetExceeded(info) => edits.push(Box::new(IncreaseUsageBudget {
+            perm_sel: info.perm_sel.clone(),
+            api: info.usages.api_name.clone(),
+            budget: info.count,
+        })),
+        Problem::DeniedSymbol(usages) => {
+            if let Some(path) = crate::deny_list::denied_path(&usages.api_name) {
+                edits.push(Box::new(AllowDeniedSymbol {
+                    perm_sel: PermSel::with_scope(&usages.pkg_id, usages.scope),
+                    path: path.to_owned(),
+                }));
+            }
+        }
+        Problem::DisallowedUnsafe(failure) => {
+            let perm_sel = PermSel::for_non_build_output(&failure.crate_sel);
+            if config.permissions.is_pure(&perm_sel) {
+                return edits;
+            }
+            let restricted = config
+                .permissions
+                .unsafe_files_for_crate(&failure.crate_sel)
+                .is_some();
+            if !restricted {
+                edits.push(Box::new(AllowUnsafe {
+                    perm_sel: perm_sel.clone(),
+                }));
+            }
+            // If some of the unsafe is outside the package, then we can't restrict to files.
+            if let Some(files) = failure.pkg_relative_files().filter(|f| !f.is_empty()) {
+                edits.push(Box::new(AllowUnsafeInFiles {
+                    perm_sel,
+                    files,
+                    restricted,
+                }));
+            }
+        }
         Problem::UnusedAllowApi(failure) => edits.push(Box::new(RemoveUnusedAllowApis {
             unused: failure.clone(),
         })),
@@ -129,8 +197,13 @@ pub(crate) fn fixes_for_problem(problem: &Problem, config: &Config) -> Vec<Box<d
             // have shown up elsewhere and it seems nicer to just degrade to not show those edits.
             let _ = info.usages.add_include_fixes(&mut edits, config);
             let _ = info.usages.add_exclude_fixes(&mut edits, config);
-            info.usages.add_allow_api_fixes(&mut edits);
+            if !config.permissions.is_pure(&info.usages.perm_sel()) {
+                info.usages.add_allow_api_fixes(&mut edits);
+            }
         }
+        Problem::RustcVersionChanged(info) => edits.push(Box::new(PinRustcVersion {
+            version: info.active.clone(),
+        })),
         Problem::NewConfigVersionAvailable(version) => {
             if let Some(version) = crate::config::versions::VERSIONS.get(*version as usize) {
                 edits.push(Box::new(UpdateConfigVersion {
@@ -141,6 +214,24 @@ pub(crate) fn fixes_for_problem(problem: &Problem, config: &Config) -> Vec<Box<d
                 }));
             }
         }
+        Problem::PluginProblem(info) => {
+            if let (Some(pkg_id), Some(api)) = (&info.pkg_id, &info.api)
+                && !config
+                    .permissions
+                    .is_pure(&PermSel::with_scope(pkg_id, info.scope))
+            {
+                edits.push(Box::new(AllowApiUsage {
+                    usage: ApiUsages {
+                        pkg_id: pkg_id.clone(),
+                        scope: info.scope,
+                        api_name: api.clone(),
+                        usages: Vec::new(),
+                        feature_fix: None,
+                        usage_pins: Vec::new(),
+                    },
+                }));
+            }
+        }
         _ => {}
     }
     edits
@@ -246,6 +337,12 @@ impl ConfigEditor {
         Ok(())
     }
 
+    pub(crate) fn set_rustc_version(&mut self, version: &str) -> Result<()> {
+        let table = self.table(["common"].into_iter())?;
+        table.insert("rustc_version", toml_edit::value(version));
+        Ok(())
+    }
+
     pub(crate) fn toggle_std_import(&mut self, api: &str) -> Result<()> {
         let imports = self
             .common_table()?
@@ -319,33 +416,81 @@ impl ApiUsages {
     fn add_exclude_fixes(&self, edits: &mut Vec<Box<dyn Edit>>, config: &Config) -> Result<()> {
         let api_config = config.get_api_config(&self.api_name)?;
         let common_to_prefixes = common_to_prefixes(self)?;
+        let only_used_path = self.only_used_path();
         for prefix in common_to_prefixes {
-            if api_config
-                .include
-                .iter()
-                .any(|inc| inc.prefix.as_ref() == prefix)
-            {
-                // Adding an exclude that exactly matches an include doesn't make sense - you'd be
-                // better off removing the include instead.
+            // If only a single path was used, then we've already proposed excluding it.
+            if only_used_path.as_deref() == Some(prefix.as_str()) {
                 continue;
             }
-            // Only propose excluding a path if it's an extension of an existing inclusion. e.g. if
-            // we have an include of `std::path` then we can exclude `std::path::Path`, but not
-            // `std::collections::HashMap`.
-            if api_config.include.iter().any(|inc| {
-                prefix
-                    .strip_prefix(inc.prefix.as_ref())
-                    .is_some_and(|remaining| remaining.starts_with("::"))
-            }) {
+            if can_exclude(api_config, &prefix) {
                 edits.push(Box::new(ExcludeFromApi {
                     api: self.api_name.clone(),
                     api_path: ApiPath::from_str(&prefix),
+                    only_used: false,
                 }));
             }
         }
         Ok(())
     }
 
+    /// Proposes allowing an API that is narrower than the one that was used, if all the usages
+    /// would also have matched the narrower API. e.g. if `fs` includes `std::fs` and `fs_read`
+    /// includes `std::fs::read`, then when only `std::fs::read` was used, we can allow `fs_read`.
+    /// These are put first, so that the least privileged option is the default.
+    fn add_narrower_api_fixes(&self, edits: &mut Vec<Box<dyn Edit>>, config: &Config) {
+        let Ok(api_config) = config.get_api_config(&self.api_name) else {
+            return;
+        };
+        if self.usages.is_empty() {
+            return;
+        }
+        for (api_name, narrower) in &config.raw.apis {
+            if *api_name == self.api_name || !is_narrower(narrower, api_config) {
+                continue;
+            }
+            // As when we check usages, the most specific of the includes and excludes wins.
+            let rules = ApiRules::new(narrower);
+            if self
+                .usages
+                .iter()
+                .all(|usage| rules.matches_name(&usage.to_name))
+            {
+                edits.push(Box::new(AllowNarrowerApi {
+                    usage: ApiUsages {
+                        api_name: api_name.clone(),
+                        ..self.clone()
+                    },
+                    broader: self.api_name.clone(),
+                    paths: narrower.include.clone(),
+                }));
+            }
+        }
+    }
+
+    /// Proposes excluding the path that was used from the API if all usages were of that one path.
+    fn add_only_used_path_fix(&self, edits: &mut Vec<Box<dyn Edit>>, config: &Config) {
+        let Ok(api_config) = config.get_api_config(&self.api_name) else {
+            return;
+        };
+        if let Some(path) = self.only_used_path()
+            && can_exclude(api_config, &path)
+        {
+            edits.push(Box::new(ExcludeFromApi {
+                api: self.api_name.clone(),
+                api_path: ApiPath::from_str(&path),
+                only_used: true,
+            }));
+        }
+    }
+
+    /// Returns the path of the thing that was used, if all usages were of the same thing.
+    fn only_used_path(&self) -> Option<String> {
+        let (first, rest) = self.usages.split_first()?;
+        rest.iter()
+            .all(|usage| usage.to_name == first.to_name)
+            .then(|| first.to_name.parts().collect::<Vec<_>>().join("::"))
+    }
+
     fn add_allow_api_fixes(&self, edits: &mut Vec<Box<dyn Edit>>) {
         edits.push(Box::new(AllowApiUsage {
             usage: self.clone(),
@@ -363,6 +508,44 @@ impl ApiUsages {
     }
 }
 
+/// Returns whether it makes sense to exclude `path` from `api_config`.
+fn can_exclude(api_config: &ApiConfig, path: &str) -> bool {
+    if api_config
+        .include
+        .iter()
+        .any(|inc| inc.prefix.as_ref() == path)
+    {
+        // Adding an exclude that exactly matches an include doesn't make sense - you'd be better
+        // off removing the include instead.
+        return false;
+    }
+    // Only propose excluding a path if it's an extension of an existing inclusion. e.g. if we have
+    // an include of `std::path` then we can exclude `std::path::Path`, but not
+    // `std::collections::HashMap`.
+    api_config
+        .include
+        .iter()
+        .any(|inc| is_strict_extension(path, inc.prefix.as_ref()))
+}
+
+/// Returns whether `path` starts with `prefix`, but has more parts.
+fn is_strict_extension(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|remaining| remaining.starts_with("::"))
+}
+
+/// Returns whether everything included by `narrower` is a strict subset of what's included by
+/// `broader`.
+fn is_narrower(narrower: &ApiConfig, broader: &ApiConfig) -> bool {
+    !narrower.include.is_empty()
+        && narrower.include.iter().all(|path| {
+            broader
+                .include
+                .iter()
+                .any(|b| is_strict_extension(path.prefix.as_ref(), b.prefix.as_ref()))
+        })
+}
+
 fn pkg_path(perm_sel: &PermSel) -> impl Iterator<Item = &str> + Clone {
     std::iter::once("pkg")
         .chain(std::iter::once(perm_sel.package_name.as_ref()))
@@ -717,11 +900,21 @@ impl Edit for ExtendApi {
 struct ExcludeFromApi {
     api: ApiName,
     api_path: ApiPath,
+
+    /// Whether `api_path` is the only thing that was used.
+    only_used: bool,
 }
 
 impl Edit for ExcludeFromApi {
     fn title(&self) -> String {
-        format!("Exclude `{}` from API `{}`", self.api_path, self.api)
+        if self.only_used {
+            format!(
+                "Only `{}` was used: exclude it from API `{}`",
+                self.api_path, self.api
+            )
+        } else {
+            format!("Exclude `{}` from API `{}`", self.api_path, self.api)
+        }
     }
 
     fn help(&self) -> Cow<'static, str> {
@@ -845,6 +1038,58 @@ impl Edit for AllowApiUsage {
     }
 }
 
+/// Allows a package an API that's narrower than the one that it used in place of the broader API.
+struct AllowNarrowerApi {
+    /// The usages, but with the narrower API.
+    usage: ApiUsages,
+
+    /// The API that the usages were reported against.
+    broader: ApiName,
+
+    /// The paths included by the narrower API, which we exclude from the broader API.
+    paths: Vec<ApiPath>,
+}
+
+impl Edit for AllowNarrowerApi {
+    fn title(&self) -> String {
+        let pkg = &self.usage.pkg_id;
+        let api = &self.usage.api_name;
+        format!(
+            "Only `{api}` was used: allow `{pkg}` to use `{api}` instead of `{}`",
+            self.broader
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        format!(
+            "Everything that was used is also part of the narrower API `{}`. Allow `{}` to use \
+             just that, and exclude its paths from `{}` so that they're only classified as `{}`. \
+             Other packages that use those paths will then need to be allowed `{}`",
+            self.usage.api_name,
+            self.usage.pkg_id,
+            self.broader,
+            self.usage.api_name,
+            self.usage.api_name,
+        )
+        .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        for path in &self.paths {
+            ExcludeFromApi {
+                api: self.broader.clone(),
+                api_path: path.clone(),
+                only_used: false,
+            }
+            .apply(editor, opts)?;
+        }
+        AllowApiUsage {
+            usage: self.usage.clone(),
+        }
+        .apply(editor, opts)
+    }
+}
+
 struct RemoveUnusedAllowApis {
     unused: UnusedAllowApi,
 }
@@ -922,6 +1167,24 @@ impl Edit for UpdateConfigVersion {
     }
 }
 
+struct PinRustcVersion {
+    version: String,
+}
+
+impl Edit for PinRustcVersion {
+    fn title(&self) -> String {
+        format!("Pin rustc version {}", self.version)
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Edit config to record that results have been reviewed with this rustc version.".into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, _opts: &EditOpts) -> Result<()> {
+        editor.set_rustc_version(&self.version)
+    }
+}
+
 struct UpdateConfigVersionPreserveBehaviour {
     version: Version,
 }
@@ -1035,174 +1298,633 @@ impl Edit for AllowBuildInstruction {
     }
 }
 
-struct DisableSandbox {
+struct PinBuildScript {
     perm_sel: PermSel,
+    sha256: String,
 }
 
-impl Edit for DisableSandbox {
+impl Edit for PinBuildScript {
     fn title(&self) -> String {
-        format!("Disable sandbox for `{}`", self.perm_sel)
+        format!("Pin `{}` to SHA-256 {}", self.perm_sel, self.sha256)
     }
 
     fn help(&self) -> Cow<'static, str> {
-        "Don't run this crate's build script (build.rs) in a sandbox. You might select this \
-         option if the build script is doing something weird like writing to the source \
-         directory, but you've checked it over and you trust it."
+        "Record the hash of this build script in the config. If the build script changes, e.g. \
+         because a git dependency or patched source changed, it will need to be reviewed and \
+         pinned again."
             .into()
     }
 
     fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
-        let table = editor.pkg_sandbox_table(&self.perm_sel)?;
-        set_table_value(table, "kind", toml_edit::value("Disabled"), opts);
+        let table = editor.pkg_table(&self.perm_sel)?;
+        set_table_value(table, "sha256", toml_edit::value(&self.sha256), opts);
         Ok(())
     }
 }
 
-struct AllowUnsafe {
+struct AllowExecutable {
     perm_sel: PermSel,
+    sha256: String,
 }
 
-impl Edit for AllowUnsafe {
+impl Edit for AllowExecutable {
     fn title(&self) -> String {
-        format!("Allow package `{}` to use unsafe code", self.perm_sel)
+        format!(
+            "Allow build script for `{}` to write executable with SHA-256 {}",
+            self.perm_sel, self.sha256
+        )
     }
 
     fn help(&self) -> Cow<'static, str> {
-        "Allow this crate to use unsafe code. With unsafe code, this crate could do just about \
-         anything, so this is like a bit like a wildcard permission. Crates that use unsafe \
-         sometimes export APIs that you might want to restrict - e.g. network or filesystem APIs. \
-         so you should have a think about if this crate falls into that category and if it does, \
-         add some API definitions for it."
+        "Allow this crate's build.rs to write an executable with exactly this content into \
+         OUT_DIR. If the content changes, e.g. because a different version is downloaded, then it \
+         will need to be allowed again."
             .into()
     }
 
     fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
         let table = editor.pkg_table(&self.perm_sel)?;
-        set_table_value(table, "allow_unsafe", toml_edit::value(true), opts);
-        Ok(())
+        add_to_array(
+            table,
+            "allow_executables",
+            &[&self.sha256],
+            opts.comment.as_deref(),
+        )
     }
 }
 
-struct SandboxAllowNetwork {
+struct AllowBinaryFile {
     perm_sel: PermSel,
+    sha256: String,
 }
 
-impl Edit for SandboxAllowNetwork {
+impl Edit for AllowBinaryFile {
     fn title(&self) -> String {
-        format!("Permit network from sandbox for `{}`", self.perm_sel)
+        format!(
+            "Allow `{}` to contain binary file with SHA-256 {}",
+            self.perm_sel, self.sha256
+        )
     }
 
     fn help(&self) -> Cow<'static, str> {
-        "Allow this crate's build script (build.rs) to access the network. This might be necessary \
-         if the build script is downloading stuff from the Internet."
+        "Allow this package's sources to contain a binary file with exactly this content. If the \
+         content changes, e.g. in a new version of the package, then it will need to be allowed \
+         again."
             .into()
     }
 
     fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
-        let table = editor.pkg_sandbox_table(&self.perm_sel)?;
-        set_table_value(table, "allow_network", toml_edit::value(true), opts);
-        Ok(())
+        let table = editor.pkg_table(&self.perm_sel)?;
+        add_to_array(
+            table,
+            "allow_binary_files",
+            &[&self.sha256],
+            opts.comment.as_deref(),
+        )
     }
 }
 
-impl Display for dyn Edit {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.title())
-    }
+struct AllowNativeLibrary {
+    perm_sel: PermSel,
+    library: String,
 }
 
-fn set_table_value(
-    table: &mut toml_edit::Table,
-    key: &str,
-    item: toml_edit::Item,
-    opts: &EditOpts,
-) {
-    table[key] = item;
-    if let Some(comment) = &opts.comment
-        && let Some(mut key) = table.key_mut(key)
-    {
-        *key.leaf_decor_mut() = toml_edit::Decor::new(format!("# {comment}\n"), " ");
+impl Edit for AllowNativeLibrary {
+    fn title(&self) -> String {
+        format!(
+            "Allow `{}` to link native library `{}`",
+            self.perm_sel, self.library
+        )
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::ConfigEditor;
-    use super::Edit;
-    use super::InlineStdApi;
-    use crate::config::ApiName;
-    use crate::config::Config;
-    use crate::config::SandboxConfig;
-    use crate::config::permissions::PermSel;
-    use crate::config::permissions::PermissionScope;
-    use crate::config_editor::fixes_for_problem;
-    use crate::crate_index::CrateSel;
-    use crate::crate_index::PackageId;
-    use crate::crate_index::testing::pkg_id;
-    use crate::location::SourceLocation;
-    use crate::problem::ApiUsages;
-    use crate::problem::DisallowedBuildInstruction;
-    use crate::problem::Problem;
-    use crate::proxy::rpc::BinExecutionOutput;
-    use indoc::indoc;
-    use std::path::Path;
-    use std::path::PathBuf;
-    use std::sync::Arc;
+    fn help(&self) -> Cow<'static, str> {
+        "Allow binaries to depend on this shared library because of this package.".into()
+    }
 
-    fn disallowed_api(pkg_id: PackageId, scope: PermissionScope, api: &'static str) -> Problem {
-        Problem::DisallowedApiUsage(ApiUsages {
-            pkg_id: pkg_id.clone(),
-            scope,
-            api_name: ApiName::from(api),
-            usages: Vec::new(),
-        })
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        add_to_array(
+            table,
+            "allow_native_libs",
+            &[&self.library],
+            opts.comment.as_deref(),
+        )
     }
+}
 
-    #[track_caller]
-    fn check(initial_config: &str, problem: &Problem, fix_index: usize, expected: &str) {
-        let config = crate::config::testing::parse(initial_config).unwrap();
-        let mut editor = ConfigEditor::from_toml_string(initial_config).unwrap();
-        let edit = &fixes_for_problem(problem, &config)[fix_index];
-        edit.apply(&mut editor, &Default::default()).unwrap();
-        let updated_toml = editor.to_toml();
-        assert_eq!(updated_toml, expected);
+struct AllowUsagesAtLocations {
+    perm_sel: PermSel,
+    pins: Vec<AllowedUsage>,
+}
 
-        // Apply the edit a second time and make sure that the result doesn't change.
-        let mut editor = ConfigEditor::from_toml_string(&updated_toml).unwrap();
-        edit.apply(&mut editor, &Default::default()).unwrap();
-        assert_eq!(editor.to_toml(), expected);
+impl Edit for AllowUsagesAtLocations {
+    fn title(&self) -> String {
+        let locations = self
+            .pins
+            .iter()
+            .map(|pin| format!("{}:{}", pin.file, pin.line))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Allow `{}` only at {locations}", self.perm_sel)
     }
 
-    #[test]
-    fn fix_missing_api_no_existing_config() {
-        check(
-            "",
-            &disallowed_api(pkg_id("crab1"), PermissionScope::All, "fs"),
-            0,
-            indoc! {r#"
-                [pkg.crab1]
-                allow_apis = [
-                    "fs",
-                ]
-            "#,
-            },
-        );
+    fn help(&self) -> Cow<'static, str> {
+        "Allow just these usages, rather than the whole API. The code around each usage is hashed, \
+         so if it changes, the usage will be reported again for review."
+            .into()
     }
 
-    #[test]
-    fn fix_missing_api_build_script() {
-        check(
-            "",
-            &disallowed_api(pkg_id("crab1"), PermissionScope::Build, "fs"),
-            0,
-            indoc! {r#"
-                [pkg.crab1]
-                build.allow_apis = [
-                    "fs",
-                ]
-            "#,
-            },
-        );
+    fn apply(&self, editor: &mut ConfigEditor, _opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        let array = get_or_create_array(table, "allow_usages")?;
+        for pin in &self.pins {
+            // An existing allowance for the same location no longer matches the code, so replace it.
+            array.retain(|existing| {
+                let Some(existing) = existing.as_inline_table() else {
+                    return true;
+                };
+                existing.get("api").and_then(Value::as_str) != Some(pin.api.as_ref())
+                    || existing.get("file").and_then(Value::as_str) != Some(pin.file.as_str())
+                    || existing.get("line").and_then(Value::as_integer) != Some(i64::from(pin.line))
+            });
+            let mut entry = toml_edit::InlineTable::new();
+            entry.insert("api", pin.api.as_ref().into());
+            entry.insert("file", pin.file.as_str().into());
+            entry.insert("line", i64::from(pin.line).into());
+            entry.insert("hash", pin.hash.as_str().into());
+            array.push_formatted(Value::InlineTable(entry).decorated("\n    ", ""));
+        }
+        Ok(())
+    }
+}
+
+struct UpdatePinVersion {
+    perm_sel: PermSel,
+    version: String,
+}
+
+impl Edit for UpdatePinVersion {
+    fn title(&self) -> String {
+        format!("Pin `{}` to version {}", self.perm_sel, self.version)
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Mark this version of the package as reviewed, so that its permissions apply to it. Check \
+         that the package's use of the API is still reasonable before selecting this."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        set_table_value(
+            table,
+            "pin_version",
+            toml_edit::value(format!("={}", self.version)),
+            opts,
+        );
+        Ok(())
+    }
+}
+
+struct IncreaseUsageBudget {
+    perm_sel: PermSel,
+    api: ApiName,
+    budget: usize,
+}
+
+impl Edit for IncreaseUsageBudget {
+    fn title(&self) -> String {
+        format!(
+            "Allow `{}` to use `{}` from {} locations",
+            self.perm_sel, self.api, self.budget
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Increase the budget for this API to the number of locations that now use it. Check that \
+         the new usages are reasonable before selecting this."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, _opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        let budgets = table
+            .entry("max_usages")
+            .or_insert_with(|| toml_edit::value(toml_edit::InlineTable::new()));
+        let budgets = budgets
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("pkg.{}.max_usages should be a table", self.perm_sel))?;
+        budgets.insert(
+            self.api.as_ref(),
+            toml_edit::value(i64::try_from(self.budget)?),
+        );
+        Ok(())
+    }
+}
+
+struct AllowDeniedSymbol {
+    perm_sel: PermSel,
+    path: String,
+}
+
+impl Edit for AllowDeniedSymbol {
+    fn title(&self) -> String {
+        format!(
+            "Exempt `{}` from the denial of `{}`",
+            self.perm_sel, self.path
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Allow this package to reference the denied item. Items are generally denied because \
+         they're easy to misuse, so you should check that this package uses it correctly."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        add_to_array(
+            table,
+            "allow_denied_symbols",
+            &[&self.path],
+            opts.comment.as_deref(),
+        )
+    }
+}
+
+struct DisableSandbox {
+    perm_sel: PermSel,
+}
+
+impl Edit for DisableSandbox {
+    fn title(&self) -> String {
+        format!("Disable sandbox for `{}`", self.perm_sel)
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Don't run this crate's build script (build.rs) in a sandbox. You might select this \
+         option if the build script is doing something weird like writing to the source \
+         directory, but you've checked it over and you trust it."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_sandbox_table(&self.perm_sel)?;
+        set_table_value(table, "kind", toml_edit::value("Disabled"), opts);
+        Ok(())
+    }
+}
+
+struct AllowUnsafe {
+    perm_sel: PermSel,
+}
+
+impl Edit for AllowUnsafe {
+    fn title(&self) -> String {
+        format!("Allow package `{}` to use unsafe code", self.perm_sel)
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Allow this crate to use unsafe code. With unsafe code, this crate could do just about \
+         anything, so this is like a bit like a wildcard permission. Crates that use unsafe \
+         sometimes export APIs that you might want to restrict - e.g. network or filesystem APIs. \
+         so you should have a think about if this crate falls into that category and if it does, \
+         add some API definitions for it."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        set_table_value(table, "allow_unsafe", toml_edit::value(true), opts);
+        Ok(())
+    }
+}
+
+struct AllowUnsafeInFiles {
+    perm_sel: PermSel,
+    files: Vec<String>,
+    /// Whether unsafe is already restricted to particular files.
+    restricted: bool,
+}
+
+impl Edit for AllowUnsafeInFiles {
+    fn title(&self) -> String {
+        let only = if self.restricted { "" } else { "only " };
+        format!(
+            "Allow package `{}` to use unsafe code {only}in {}",
+            self.perm_sel,
+            self.files
+                .iter()
+                .map(|file| format!("`{file}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Allow this crate to use unsafe code, but only in the files where it's currently used. If \
+         unsafe later shows up in other files of this crate, it will be reported, so that you can \
+         check whether it's reasonable. This is useful for crates that are mostly safe, with \
+         unsafe confined to say an FFI module."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_table(&self.perm_sel)?;
+        // If the files are listed in `allow_unsafe` itself, then add to that list.
+        if table
+            .get("allow_unsafe")
+            .is_some_and(|item| item.is_array())
+        {
+            return add_to_array(table, "allow_unsafe", &self.files, opts.comment.as_deref());
+        }
+        set_table_value(table, "allow_unsafe", toml_edit::value(true), opts);
+        add_to_array(
+            table,
+            "allow_unsafe_in",
+            &self.files,
+            opts.comment.as_deref(),
+        )
+    }
+}
+
+struct SandboxAllowNetwork {
+    perm_sel: PermSel,
+}
+
+impl Edit for SandboxAllowNetwork {
+    fn title(&self) -> String {
+        format!("Permit network from sandbox for `{}`", self.perm_sel)
+    }
+
+    fn help(&self) -> Cow<'static, str> {
+        "Allow this crate's build script (build.rs) to access the network. This might be necessary \
+         if the build script is downloading stuff from the Internet."
+            .into()
+    }
+
+    fn apply(&self, editor: &mut ConfigEditor, opts: &EditOpts) -> Result<()> {
+        let table = editor.pkg_sandbox_table(&self.perm_sel)?;
+        set_table_value(table, "allow_network", toml_edit::value(true), opts);
+        Ok(())
+    }
+}
+
+impl Display for dyn Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title())
+    }
+}
+
+fn set_table_value(
+    table: &mut toml_edit::Table,
+    key: &str,
+    item: toml_edit::Item,
+    opts: &EditOpts,
+) {
+    table[key] = item;
+    if let Some(comment) = &opts.comment
+        && let Some(mut key) = table.key_mut(key)
+    {
+        *key.leaf_decor_mut() = toml_edit::Decor::new(format!("# {comment}\n"), " ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigEditor;
+    use super::Edit;
+    use super::InlineStdApi;
+    use crate::config::AllowedUsage;
+    use crate::config::ApiName;
+    use crate::config::Config;
+    use crate::config::SandboxConfig;
+    use crate::config::permissions::PermSel;
+    use crate::config::permissions::PermissionScope;
+    use crate::config_editor::fixes_for_problem;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::PackageId;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::problem::ApiUsages;
+    use crate::problem::BinaryFileInSources;
+    use crate::problem::BuildScriptChanged;
+    use crate::problem::DisallowedBuildInstruction;
+    use crate::problem::DisallowedNativeLibrary;
+    use crate::problem::Problem;
+    use crate::problem::RustcVersionChanged;
+    use crate::proxy::rpc::BinExecutionOutput;
+    use indoc::indoc;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn disallowed_api(pkg_id: PackageId, scope: PermissionScope, api: &'static str) -> Problem {
+        Problem::DisallowedApiUsage(ApiUsages {
+            pkg_id: pkg_id.clone(),
+            scope,
+            api_name: ApiName::from(api),
+            usages: Vec::new(),
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        })
+    }
+
+    #[track_caller]
+    fn check(initial_config: &str, problem: &Problem, fix_index: usize, expected: &str) {
+        let config = crate::config::testing::parse(initial_config).unwrap();
+        let mut editor = ConfigEditor::from_toml_string(initial_config).unwrap();
+        let edit = &fixes_for_problem(problem, &config)[fix_index];
+        edit.apply(&mut editor, &Default::default()).unwrap();
+        let updated_toml = editor.to_toml();
+        assert_eq!(updated_toml, expected);
+
+        // Apply the edit a second time and make sure that the result doesn't change.
+        let mut editor = ConfigEditor::from_toml_string(&updated_toml).unwrap();
+        edit.apply(&mut editor, &Default::default()).unwrap();
+        assert_eq!(editor.to_toml(), expected);
+    }
+
+    fn std_fs_usages(to: &[&str]) -> Problem {
+        Problem::DisallowedApiUsage(ApiUsages {
+            pkg_id: pkg_id("crab1"),
+            scope: PermissionScope::All,
+            api_name: ApiName::from("fs"),
+            usages: to
+                .iter()
+                .map(|to| crate::checker::testing::api_usage("crab1", "crab1::load", to))
+                .collect(),
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn fix_with_narrower_api() {
+        let config = indoc! {r#"
+            [api.fs]
+            include = ["std::fs"]
+
+            [api.fs_read]
+            include = ["std::fs::read", "std::fs::read_to_string"]
+        "#};
+        let problem = std_fs_usages(&["std::fs::read", "std::fs::read_to_string"]);
+        check(
+            config,
+            &problem,
+            0,
+            indoc! {r#"
+                [api.fs]
+                include = ["std::fs"]
+                exclude = [
+                    "std::fs::read",
+                    "std::fs::read_to_string",
+                ]
+
+                [api.fs_read]
+                include = ["std::fs::read", "std::fs::read_to_string"]
+
+                [pkg.crab1]
+                allow_apis = [
+                    "fs_read",
+                ]
+            "#},
+        );
+        // The broader API is still offered.
+        check(
+            config,
+            &problem,
+            1,
+            indoc! {r#"
+                [api.fs]
+                include = ["std::fs"]
+
+                [api.fs_read]
+                include = ["std::fs::read", "std::fs::read_to_string"]
+
+                [pkg.crab1]
+                allow_apis = [
+                    "fs",
+                ]
+            "#},
+        );
+
+        // If something outside the narrower API was used, then only the broader API is offered.
+        let parsed = crate::config::testing::parse(config).unwrap();
+        let problem = std_fs_usages(&["std::fs::read", "std::fs::write"]);
+        let titles: Vec<String> = fixes_for_problem(&problem, &parsed)
+            .iter()
+            .map(|edit| edit.title())
+            .collect();
+        assert_eq!(titles, vec!["Allow `crab1` to use `fs` API"]);
+    }
+
+    #[test]
+    fn fix_with_only_used_path() {
+        let config = indoc! {r#"
+            [api.fs]
+            include = ["std::fs"]
+        "#};
+        let problem = std_fs_usages(&["std::fs::read_to_string", "std::fs::read_to_string"]);
+        let parsed = crate::config::testing::parse(config).unwrap();
+        let fixes = fixes_for_problem(&problem, &parsed);
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(
+            fixes[1].title(),
+            "Only `std::fs::read_to_string` was used: exclude it from API `fs`"
+        );
+        check(
+            config,
+            &problem,
+            1,
+            indoc! {r#"
+                [api.fs]
+                include = ["std::fs"]
+                exclude = [
+                    "std::fs::read_to_string",
+                ]
+            "#},
+        );
+    }
+
+    #[test]
+    fn fix_missing_api_no_existing_config() {
+        check(
+            "",
+            &disallowed_api(pkg_id("crab1"), PermissionScope::All, "fs"),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_apis = [
+                    "fs",
+                ]
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn pin_rustc_version() {
+        check(
+            "",
+            &Problem::RustcVersionChanged(RustcVersionChanged {
+                pinned: "1.94.0".to_owned(),
+                active: "1.95.0 (59807616e)".to_owned(),
+            }),
+            0,
+            indoc! {r#"
+                [common]
+                rustc_version = "1.95.0 (59807616e)"
+            "#},
+        );
+    }
+
+    #[test]
+    fn pin_build_script() {
+        let problem = |expected: Option<&str>| {
+            Problem::BuildScriptChanged(BuildScriptChanged {
+                pkg_id: pkg_id("crab1"),
+                expected: expected.map(str::to_owned),
+                sha256: "abcd".to_owned(),
+            })
+        };
+        check(
+            "",
+            &problem(None),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                build.sha256 = "abcd"
+            "#},
+        );
+        check(
+            indoc! {r#"
+                [pkg.crab1]
+                build.sha256 = "0123"
+            "#},
+            &problem(Some("0123")),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                build.sha256 = "abcd"
+            "#},
+        );
+    }
+
+    #[test]
+    fn fix_missing_api_build_script() {
+        check(
+            "",
+            &disallowed_api(pkg_id("crab1"), PermissionScope::Build, "fs"),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                build.allow_apis = [
+                    "fs",
+                ]
+            "#,
+            },
+        );
     }
 
     #[test]
@@ -1255,6 +1977,195 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fix_disallowed_native_library() {
+        let problem = Problem::DisallowedNativeLibrary(DisallowedNativeLibrary {
+            pkg_id: pkg_id("crab1"),
+            perm_sel: PermSel::for_primary("crab1"),
+            library: "ssl".to_owned(),
+            bin_path: Arc::from(Path::new("/bin/app")),
+        });
+        check(
+            "",
+            &problem,
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_native_libs = [
+                    "ssl",
+                ]
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_binary_file_in_sources() {
+        let problem = Problem::BinaryFileInSources(BinaryFileInSources {
+            pkg_id: pkg_id("crab1"),
+            path: PathBuf::from("/registry/crab1/bin/tool"),
+            kind: crate::binary_files::BinaryKind::Elf,
+            sha256: "abcd".to_owned(),
+        });
+        check(
+            "",
+            &problem,
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_binary_files = [
+                    "abcd",
+                ]
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_version_pin_mismatch() {
+        let problem = Problem::VersionPinMismatch(crate::problem::VersionPinMismatch {
+            usages: ApiUsages {
+                pkg_id: pkg_id("crab1"),
+                scope: PermissionScope::All,
+                api_name: ApiName::from("fs"),
+                usages: Vec::new(),
+                feature_fix: None,
+                usage_pins: Vec::new(),
+            },
+            perm_sel: PermSel::for_primary("crab1"),
+            pin_version: "=0.0.0-old".to_owned(),
+        });
+        check(
+            indoc! {r#"
+                [api.fs]
+                [pkg.crab1]
+                allow_apis = ["fs"]
+                pin_version = "=0.0.0-old"
+            "#},
+            &problem,
+            0,
+            indoc! {r#"
+                [api.fs]
+                [pkg.crab1]
+                allow_apis = ["fs"]
+                pin_version = "=0.0.0"
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_usage_budget_exceeded() {
+        let problem = Problem::UsageBudgetExceeded(crate::problem::UsageBudgetExceeded {
+            usages: ApiUsages {
+                pkg_id: pkg_id("crab1"),
+                scope: PermissionScope::All,
+                api_name: ApiName::from("net"),
+                usages: Vec::new(),
+                feature_fix: None,
+                usage_pins: Vec::new(),
+            },
+            perm_sel: PermSel::for_primary("crab1"),
+            budget: 3,
+            count: 4,
+        });
+        check(
+            indoc! {r#"
+                [api.net]
+                [pkg.crab1]
+                allow_apis = ["net"]
+                max_usages = { net = 3 }
+            "#},
+            &problem,
+            0,
+            indoc! {r#"
+                [api.net]
+                [pkg.crab1]
+                allow_apis = ["net"]
+                max_usages = { net = 4 }
+            "#,
+            },
+        );
+        check(
+            indoc! {r#"
+                [api.net]
+                [pkg.crab1]
+                allow_apis = ["net"]
+            "#},
+            &problem,
+            0,
+            indoc! {r#"
+                [api.net]
+                [pkg.crab1]
+                allow_apis = ["net"]
+                max_usages = { net = 4 }
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_usage_at_location() {
+        let pin = |line, hash: &str| AllowedUsage {
+            api: ApiName::from("fs"),
+            file: "src/lib.rs".to_owned(),
+            line,
+            hash: hash.to_owned(),
+        };
+        let problem = Problem::DisallowedApiUsage(ApiUsages {
+            pkg_id: pkg_id("crab1"),
+            scope: PermissionScope::All,
+            api_name: ApiName::from("fs"),
+            usages: Vec::new(),
+            feature_fix: None,
+            usage_pins: vec![pin(4, "new"), pin(9, "abc")],
+        });
+        check(
+            indoc! {r#"
+                [api.fs]
+                [pkg.crab1]
+                allow_usages = [
+                    { api = "fs", file = "src/lib.rs", line = 4, hash = "old" },
+                ]
+            "#},
+            &problem,
+            1,
+            indoc! {r#"
+                [api.fs]
+                [pkg.crab1]
+                allow_usages = [
+                    { api = "fs", file = "src/lib.rs", line = 4, hash = "new" },
+                    { api = "fs", file = "src/lib.rs", line = 9, hash = "abc" },
+                ]
+            "#,
+            },
+        );
+    }
+
+    #[test]
+    fn fix_denied_symbol() {
+        let problem = Problem::DeniedSymbol(ApiUsages {
+            pkg_id: pkg_id("crab1"),
+            scope: PermissionScope::FromTest,
+            api_name: ApiName::from("denied:std::fs::remove_dir_all"),
+            usages: Vec::new(),
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        });
+        check(
+            "",
+            &problem,
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                from.test.allow_denied_symbols = [
+                    "std::fs::remove_dir_all",
+                ]
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn fix_missing_api_existing_config() {
         check(
@@ -1323,6 +2234,7 @@ mod tests {
             "",
             &Problem::DisallowedUnsafe(crate::proxy::rpc::UnsafeUsage {
                 crate_sel: CrateSel::primary(pkg_id("crab1")),
+                pkg_dir: PathBuf::from("/crab1"),
                 locations: vec![SourceLocation::new(Path::new("main.rs"), 10, None)],
             }),
             0,
@@ -1334,6 +2246,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fix_allow_unsafe_in_files() {
+        let usage = |file: &str| {
+            Problem::DisallowedUnsafe(crate::proxy::rpc::UnsafeUsage {
+                crate_sel: CrateSel::primary(pkg_id("crab1")),
+                pkg_dir: PathBuf::from("/crab1"),
+                locations: vec![SourceLocation::new(Path::new(file), 10, None)],
+            })
+        };
+        check(
+            "",
+            &usage("/crab1/src/ffi.rs"),
+            1,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_unsafe = true
+                allow_unsafe_in = [
+                    "src/ffi.rs",
+                ]
+            "#,
+            },
+        );
+        // Once unsafe is restricted to some files, the only fix offered is to extend the list.
+        check(
+            indoc! {r#"
+                [pkg.crab1]
+                allow_unsafe = true
+                allow_unsafe_in = [
+                    "src/ffi.rs",
+                ]
+            "#},
+            &usage("/crab1/src/lib.rs"),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_unsafe = true
+                allow_unsafe_in = [
+                    "src/ffi.rs",
+                    "src/lib.rs",
+                ]
+            "#,
+            },
+        );
+        check(
+            indoc! {r#"
+                [pkg.crab1]
+                allow_unsafe = [
+                    "src/ffi.rs",
+                ]
+            "#},
+            &usage("/crab1/src/lib.rs"),
+            0,
+            indoc! {r#"
+                [pkg.crab1]
+                allow_unsafe = [
+                    "src/ffi.rs",
+                    "src/lib.rs",
+                ]
+            "#,
+            },
+        );
+    }
+
     #[test]
     fn build_script_failed() {
         let crate_sel = CrateSel::build_script(pkg_id("crab1"));
@@ -1349,6 +2324,8 @@ mod tests {
                 },
                 binary_path: PathBuf::new(),
                 sandbox_config_display: None,
+                written_executables: Vec::new(),
+                unreadable_outputs: Vec::new(),
             },
             crate_sel,
         });
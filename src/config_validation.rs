@@ -1,6 +1,10 @@
+use crate::async_runtime::AsyncRuntime;
 use crate::config::ApiName;
 use crate::config::Config;
 use crate::config::MAX_VERSION;
+use crate::config::api_pattern::ApiPattern;
+use crate::determinism::NondeterminismSource;
+use crate::panic_audit::PanicPath;
 use rustc_hash::FxHashSet;
 use std::fmt::Display;
 use std::path::Path;
@@ -18,6 +22,14 @@ enum Problem {
     DuplicateAllowedApi(ApiName),
     UnsupportedVersion(i64),
     InvalidPkgSelector(String),
+    UnsafeFilesWithoutAllowUnsafe(String),
+    MigrationEndNotDate(toml::value::Datetime),
+    ReviewByNotDate(String, toml::value::Datetime),
+    InvalidReviewedVersion(String, String),
+    InvalidPinVersion(String, String),
+    ReservedApiName(ApiName),
+    InvalidApiPattern(ApiName, String),
+    PureWithPermission(String, &'static str),
 }
 
 pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), InvalidConfig> {
@@ -26,6 +38,26 @@ pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), Invali
         problems.push(Problem::UnsupportedVersion(config.raw.common.version));
     }
     let permission_names: FxHashSet<_> = config.raw.apis.keys().collect();
+    for api_name in config.raw.apis.keys() {
+        if AsyncRuntime::from_api_name(api_name).is_some()
+            || PanicPath::from_api_name(api_name).is_some()
+            || crate::alloc_audit::is_alloc_api(api_name)
+            || NondeterminismSource::from_api_name(api_name).is_some()
+            || crate::deny_list::denied_path(api_name).is_some()
+        {
+            problems.push(Problem::ReservedApiName(api_name.clone()));
+        }
+    }
+    for (api_name, api_config) in &config.raw.apis {
+        for path in api_config.include.iter().chain(&api_config.exclude) {
+            if let Err(error) = ApiPattern::from_path(path) {
+                problems.push(Problem::InvalidApiPattern(
+                    api_name.clone(),
+                    format!("{error:#}"),
+                ));
+            }
+        }
+    }
     for (perm_sel, crate_config) in &config.permissions_no_inheritance.packages {
         let mut used = FxHashSet::default();
         for permission_name in &crate_config.allow_apis {
@@ -36,6 +68,47 @@ pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), Invali
                 problems.push(Problem::DuplicateAllowedApi(permission_name.clone()))
             }
         }
+        for allowed in &crate_config.allow_usages {
+            if !permission_names.contains(&allowed.api) {
+                problems.push(Problem::UnknownPermission(allowed.api.clone()));
+            }
+        }
+        for permission_name in crate_config.max_usages.keys() {
+            if !permission_names.contains(permission_name) {
+                problems.push(Problem::UnknownPermission(permission_name.clone()));
+            }
+        }
+        for feature_config in crate_config.if_feature.values() {
+            for permission_name in &feature_config.allow_apis {
+                if !permission_names.contains(permission_name) {
+                    problems.push(Problem::UnknownPermission(permission_name.clone()));
+                }
+            }
+        }
+        if crate_config.allow_unsafe_in.is_some() && !crate_config.allow_unsafe.is_allowed() {
+            problems.push(Problem::UnsafeFilesWithoutAllowUnsafe(perm_sel.to_string()));
+        }
+        if let Some(review_by) = crate_config.review_by
+            && (review_by.date.is_none() || review_by.time.is_some())
+        {
+            problems.push(Problem::ReviewByNotDate(perm_sel.to_string(), review_by));
+        }
+        if let Some(version) = &crate_config.reviewed_version
+            && cargo_metadata::semver::Version::parse(version).is_err()
+        {
+            problems.push(Problem::InvalidReviewedVersion(
+                perm_sel.to_string(),
+                version.clone(),
+            ));
+        }
+        if let Some(version_req) = &crate_config.pin_version
+            && cargo_metadata::semver::VersionReq::parse(version_req).is_err()
+        {
+            problems.push(Problem::InvalidPinVersion(
+                perm_sel.to_string(),
+                version_req.clone(),
+            ));
+        }
         if crate_config.build.is_some() {
             problems.push(Problem::InvalidPkgSelector(format!("{perm_sel}.build")));
         }
@@ -46,6 +119,40 @@ pub(crate) fn validate(config: &Config, config_path: &Path) -> Result<(), Invali
             problems.push(Problem::InvalidPkgSelector(format!("{perm_sel}.dep")));
         }
     }
+    // We check pure packages after inheritance, since `from` selectors inherit both `pure` and
+    // whatever their package is granted.
+    for (perm_sel, crate_config) in &config.permissions.packages {
+        if crate_config.pure {
+            let granted = [
+                ("allow_apis", !crate_config.allow_apis.is_empty()),
+                ("allow_usages", !crate_config.allow_usages.is_empty()),
+                (
+                    "if_feature",
+                    crate_config
+                        .if_feature
+                        .values()
+                        .any(|feature_config| !feature_config.allow_apis.is_empty()),
+                ),
+                ("allow_unsafe", crate_config.allow_unsafe.is_allowed()),
+                (
+                    "allow_native_libs",
+                    !crate_config.allow_native_libs.is_empty(),
+                ),
+            ];
+            for (field, _) in granted.into_iter().filter(|(_, granted)| *granted) {
+                problems.push(Problem::PureWithPermission(perm_sel.to_string(), field));
+            }
+        }
+    }
+    if let Some(until) = config
+        .raw
+        .migration_mode
+        .as_ref()
+        .and_then(|migration_mode| migration_mode.until)
+        && (until.date.is_none() || until.time.is_some())
+    {
+        problems.push(Problem::MigrationEndNotDate(until));
+    }
     if problems.is_empty() {
         Ok(())
     } else {
@@ -71,6 +178,35 @@ impl Display for InvalidConfig {
                 Problem::InvalidPkgSelector(sel) => {
                     write!(f, "  Unsupported package selector `pkg.{sel}`")?
                 }
+                Problem::UnsafeFilesWithoutAllowUnsafe(sel) => write!(
+                    f,
+                    "  `pkg.{sel}` sets `allow_unsafe_in` without setting `allow_unsafe`"
+                )?,
+                Problem::MigrationEndNotDate(until) => write!(
+                    f,
+                    "  `migration_mode.until` should be a date like 2025-06-30, got `{until}`"
+                )?,
+                Problem::ReviewByNotDate(sel, review_by) => write!(
+                    f,
+                    "  `pkg.{sel}.review_by` should be a date like 2025-06-30, got `{review_by}`"
+                )?,
+                Problem::InvalidReviewedVersion(sel, version) => write!(
+                    f,
+                    "  `pkg.{sel}.reviewed_version` should be a version like 1.2.3, got `{version}`"
+                )?,
+                Problem::InvalidPinVersion(sel, version_req) => write!(
+                    f,
+                    "  `pkg.{sel}.pin_version` should be a version requirement like =1.2.3, got \
+                     `{version_req}`"
+                )?,
+                Problem::ReservedApiName(x) => {
+                    write!(f, "  API name '{}' is reserved for internal use", x.name)?
+                }
+                Problem::InvalidApiPattern(x, error) => write!(f, "  API '{}': {error}", x.name)?,
+                Problem::PureWithPermission(sel, field) => write!(
+                    f,
+                    "  `pkg.{sel}` is marked `pure`, so it can't set `{field}`"
+                )?,
             }
         }
         Ok(())
@@ -1,3 +1,5 @@
+use serde::Deserialize;
+use serde::Serialize;
 use std::hash::Hash;
 use std::ops::Deref;
 use std::sync::Arc;
@@ -97,6 +99,80 @@ impl<T: Ord + ?Sized> Ord for CowArc<'_, T> {
     }
 }
 
+/// Bytes are almost always valid UTF-8, in which case we serialise them as a string, since that's
+/// much more compact in formats like JSON.
+impl Serialize for CowArc<'_, [u8]> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(self.data()) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.serialize_bytes(self.data()),
+        }
+    }
+}
+
+impl Serialize for CowArc<'_, str> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.data())
+    }
+}
+
+/// Deserialised data is always stored on the heap.
+impl<'de> Deserialize<'de> for CowArc<'_, [u8]> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string or bytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(v.as_bytes().to_vec())
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(bytes)
+            }
+        }
+
+        Ok(CowArc::Heap(Arc::from(
+            deserializer.deserialize_any(BytesVisitor)?,
+        )))
+    }
+}
+
+impl<'de> Deserialize<'de> for CowArc<'_, str> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(CowArc::Heap(Arc::from(String::deserialize(deserializer)?)))
+    }
+}
+
+#[test]
+fn serde_round_trip() {
+    for bytes in [&b"_ZN3foo3barE"[..], &[0xff, 0x00]] {
+        let json = serde_json::to_string(&Bytes::Borrowed(bytes)).unwrap();
+        let parsed: Bytes = serde_json::from_str(&json).unwrap();
+        assert_eq!(&*parsed, bytes);
+    }
+    assert_eq!(
+        serde_json::to_string(&Bytes::Borrowed(b"sym")).unwrap(),
+        "\"sym\""
+    );
+}
+
 #[test]
 fn comparison() {
     fn hash(sym: &Bytes) -> u64 {
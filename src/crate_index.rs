@@ -1,12 +1,14 @@
 //! This module extracts various bits of information from cargo metadata, such as which paths belong
 //! to which crates, which are proc macros etc.
 
+use self::features::FeatureIndex;
 use self::lib_tree::LibTree;
 use crate::config::PackageName;
 use crate::config::permissions::PermSel;
 use crate::config::permissions::PermissionScope;
 use anyhow::Context;
 use anyhow::Result;
+use anyhow::anyhow;
 use anyhow::bail;
 use cargo_metadata::DependencyKind;
 use cargo_metadata::TargetKind;
@@ -18,11 +20,15 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::borrow::Cow;
 use std::fmt::Display;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+pub(crate) mod artifact_deps;
+pub(crate) mod features;
 pub(crate) mod lib_tree;
+mod test_only;
 
 #[derive(Default, Debug)]
 pub(crate) struct CrateIndex {
@@ -32,9 +38,18 @@ pub(crate) struct CrateIndex {
     pkg_name_to_ids: FxHashMap<Arc<str>, Vec<PackageId>>,
     lib_tree: LibTree,
     pub(crate) permission_selectors: FxHashSet<PermSel>,
+    pub(crate) features: FeatureIndex,
+    /// Packages that are only in the dependency graph because workspace members depend on them
+    /// from tests.
+    test_only: FxHashSet<PackageId>,
+    /// Packages that provide binaries to other packages via artifact dependencies, together with
+    /// the packages that depend on them in that way.
+    artifact_consumers: FxHashMap<PackageId, Vec<PackageId>>,
+    /// The cargo registry directories that packages were extracted into, e.g. `~/.cargo/registry`.
+    registry_dirs: Vec<PathBuf>,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub(crate) struct PackageId {
     name: Arc<str>,
     version: Version,
@@ -64,6 +79,7 @@ pub(crate) struct PackageInfo {
     pub(crate) description: Option<String>,
     pub(crate) documentation: Option<String>,
     is_proc_macro: bool,
+    is_workspace_member: bool,
 }
 
 /// The name of the environment variable that we use to pass a list of non-unique package names to
@@ -76,9 +92,24 @@ pub(crate) const MULTIPLE_VERSION_PKG_NAMES_ENV: &str = "CACKLE_MULTIPLE_VERSION
 impl CrateIndex {
     pub(crate) fn new(dir: &Path) -> Result<Self> {
         let manifest_path = dir.join("Cargo.toml");
-        let metadata = cargo_metadata::MetadataCommand::new()
+        // We run cargo metadata ourselves rather than using `exec`, since we need the raw JSON in
+        // order to find artifact dependencies.
+        let output = cargo_metadata::MetadataCommand::new()
             .manifest_path(&manifest_path)
-            .exec()?;
+            .cargo_command()
+            .output()
+            .context("Failed to run cargo metadata")?;
+        if !output.status.success() {
+            bail!(
+                "cargo metadata failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let json = std::str::from_utf8(&output.stdout)?
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .ok_or_else(|| anyhow!("cargo metadata didn't produce any JSON"))?;
+        let metadata = cargo_metadata::MetadataCommand::parse(json)?;
         let mut mapping = CrateIndex {
             manifest_path,
             ..Self::default()
@@ -88,12 +119,14 @@ impl CrateIndex {
             *name_counts.entry(&package.name).or_default() += 1;
         }
         let mut direct_deps: FxHashMap<PackageId, Vec<Arc<str>>> = FxHashMap::default();
+        let mut pkg_ids_by_metadata_id = FxHashMap::default();
         for package in &metadata.packages {
             let pkg_id = PackageId {
                 name: Arc::from(package.name.as_str()),
                 version: package.version.clone(),
                 name_is_unique: name_counts.get(&package.name) == Some(&1),
             };
+            pkg_ids_by_metadata_id.insert(package.id.repr.as_str(), pkg_id.clone());
             let mut is_proc_macro = false;
             let mut has_build_script = false;
             let mut has_test = false;
@@ -128,6 +161,7 @@ impl CrateIndex {
                         description: package.description.clone(),
                         documentation: package.documentation.clone(),
                         is_proc_macro,
+                        is_workspace_member: metadata.workspace_members.contains(&package.id),
                     },
                 );
                 add_permission_selectors(
@@ -141,12 +175,14 @@ impl CrateIndex {
                     .entry(Arc::from(package.name.as_str()))
                     .or_default()
                     .push(pkg_id.clone());
-                mapping
-                    .dir_to_pkg_id
-                    .insert(dir.as_std_path().to_owned(), pkg_id.clone());
+                mapping.add_package_dir(dir.as_std_path(), &pkg_id);
             }
         }
         mapping.lib_tree = LibTree::from_workspace(dir, &mapping.pkg_name_to_ids)?;
+        mapping.features = FeatureIndex::new(&metadata, &pkg_ids_by_metadata_id);
+        mapping.test_only = test_only::test_only_packages(&metadata, &pkg_ids_by_metadata_id);
+        mapping.artifact_consumers =
+            artifact_deps::artifact_consumers(json, &pkg_ids_by_metadata_id)?;
         for package_ids in mapping.pkg_name_to_ids.values_mut() {
             package_ids.sort_by_key(|pkg_id| pkg_id.version.clone());
         }
@@ -189,6 +225,28 @@ impl CrateIndex {
         self.package_infos.keys()
     }
 
+    /// Returns whether `pkg_id` is a member of the workspace being analysed, as opposed to a
+    /// dependency.
+    pub(crate) fn is_workspace_member(&self, pkg_id: &PackageId) -> bool {
+        self.package_infos
+            .get(pkg_id)
+            .is_some_and(|info| info.is_workspace_member)
+    }
+
+    /// Returns whether `pkg_id` is only in the dependency graph because of dev-dependencies of
+    /// workspace members.
+    pub(crate) fn is_test_only(&self, pkg_id: &PackageId) -> bool {
+        self.test_only.contains(pkg_id)
+    }
+
+    /// Returns the packages that provide binaries via artifact dependencies, together with the
+    /// packages that consume those binaries.
+    pub(crate) fn artifact_consumers(&self) -> impl Iterator<Item = (&PackageId, &[PackageId])> {
+        self.artifact_consumers
+            .iter()
+            .map(|(provider, consumers)| (provider, consumers.as_slice()))
+    }
+
     pub(crate) fn proc_macros(&self) -> impl Iterator<Item = &PackageId> {
         self.package_infos.iter().filter_map(|(pkg_id, info)| {
             if info.is_proc_macro {
@@ -199,12 +257,35 @@ impl CrateIndex {
         })
     }
 
+    fn add_package_dir(&mut self, dir: &Path, pkg_id: &PackageId) {
+        self.dir_to_pkg_id.insert(dir.to_owned(), pkg_id.clone());
+        // Packages can be reached via symlinks, e.g. if the vendor directory or cargo's home
+        // directory is a symlink. Source paths are canonicalised, so we need where the package
+        // really is too.
+        if let Ok(canonical) = dir.canonicalize()
+            && canonical != dir
+        {
+            self.dir_to_pkg_id
+                .entry(canonical)
+                .or_insert_with(|| pkg_id.clone());
+        }
+        if let Some(registry_dir) = registry_dir(dir)
+            && !self.registry_dirs.iter().any(|d| d == registry_dir)
+        {
+            self.registry_dirs.push(registry_dir.to_owned());
+        }
+    }
+
     /// Returns the ID of the package that contains the specified path, if any. This is used as a
     /// fallback if we can't locate a source file in the deps emitted by rustc. This can happen for
     /// example in the case of crates that compile C code, since the C code won't be in the deps
     /// file. This function however doesn't differentiate between the build script for a package and
-    /// the other source files in that package, so should only be used as a fallback.
-    pub(crate) fn package_id_for_path(&self, mut path: &Path) -> Option<&PackageId> {
+    /// the other source files in that package, so should only be used as a fallback. Where package
+    /// directories are nested, e.g. for git checkouts of workspaces or for nested workspaces, the
+    /// innermost package is the one that contains the path.
+    pub(crate) fn package_id_for_path(&self, path: &Path) -> Option<&PackageId> {
+        let path = without_parent_dirs(path);
+        let mut path = path.as_ref();
         loop {
             if let Some(pkg_id) = self.dir_to_pkg_id.get(path) {
                 return Some(pkg_id);
@@ -213,6 +294,11 @@ impl CrateIndex {
         }
     }
 
+    /// Returns the cargo registry directories that packages came from.
+    pub(crate) fn registry_dirs(&self) -> &[PathBuf] {
+        &self.registry_dirs
+    }
+
     /// Returns the transitive deps for `pkg_id`. All deps will be in "crate form", i.e. with '-'
     /// replaced with '_'.
     pub(crate) fn transitive_deps(&self, pkg_id: &PackageId) -> Option<&FxHashSet<Arc<str>>> {
@@ -225,6 +311,33 @@ impl CrateIndex {
     }
 }
 
+/// Returns `path` with any `..` components resolved. Debug info can contain paths like
+/// `/ws/foo/../bar/src/lib.rs`, e.g. for path dependencies outside of the workspace, which would
+/// otherwise appear to be in `foo`. We do this without looking at the filesystem, since the path
+/// might not exist.
+fn without_parent_dirs(path: &Path) -> Cow<'_, Path> {
+    if !path.components().any(|c| c == Component::ParentDir) {
+        return Cow::Borrowed(path);
+    }
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        if component == Component::ParentDir {
+            resolved.pop();
+        } else {
+            resolved.push(component);
+        }
+    }
+    Cow::Owned(resolved)
+}
+
+/// Returns the registry directory that the package in `pkg_dir` came from, if it came from a
+/// registry. Cargo extracts packages to `registry/src/{index}/{name}-{version}`.
+fn registry_dir(pkg_dir: &Path) -> Option<&Path> {
+    let src = pkg_dir.parent()?.parent()?;
+    let registry = src.parent()?;
+    (src.file_name()? == "src" && registry.file_name()? == "registry").then_some(registry)
+}
+
 fn add_permission_selectors(
     permission_selectors: &mut FxHashSet<PermSel>,
     pkg_name: &str,
@@ -389,6 +502,7 @@ pub(crate) mod testing {
     use super::PackageInfo;
     use cargo_metadata::semver::Version;
     use rustc_hash::FxHashSet;
+    use std::path::Path;
     use std::sync::Arc;
 
     pub(crate) fn pkg_id(name: &str) -> PackageId {
@@ -410,6 +524,7 @@ pub(crate) mod testing {
                         description: Default::default(),
                         documentation: Default::default(),
                         is_proc_macro: Default::default(),
+                        is_workspace_member: Default::default(),
                     },
                 )
             })
@@ -418,12 +533,48 @@ pub(crate) mod testing {
         for pkg_name in package_names {
             super::add_permission_selectors(&mut permission_selectors, pkg_name, false, false);
         }
+        let pkg_name_to_ids = package_names
+            .iter()
+            .map(|name| (Arc::from(*name), vec![pkg_id(name)]))
+            .collect();
+        let lib_tree = super::LibTree {
+            lib_name_to_pkg_id: package_names
+                .iter()
+                .map(|name| (Arc::from(name.replace('-', "_")), pkg_id(name)))
+                .collect(),
+            ..Default::default()
+        };
         Arc::new(CrateIndex {
             package_infos,
             permission_selectors,
+            pkg_name_to_ids,
+            lib_tree,
             ..CrateIndex::default()
         })
     }
+
+    /// Like `index_with_package_names`, but with `test_only` being only dependencies of tests.
+    pub(crate) fn index_with_test_only_packages(
+        package_names: &[&str],
+        test_only: &[&str],
+    ) -> Arc<CrateIndex> {
+        let mut index = Arc::into_inner(index_with_package_names(package_names)).unwrap();
+        index.test_only = test_only.iter().map(|name| pkg_id(name)).collect();
+        Arc::new(index)
+    }
+
+    /// Like `index_with_package_names`, but with each package in the supplied directory.
+    pub(crate) fn index_with_package_dirs(packages: &[(&str, &str)]) -> Arc<CrateIndex> {
+        let names: Vec<&str> = packages.iter().map(|(name, _)| *name).collect();
+        let mut index = Arc::into_inner(index_with_package_names(&names)).unwrap();
+        for (name, dir) in packages {
+            index.add_package_dir(Path::new(dir), &pkg_id(name));
+            if let Some(info) = index.package_infos.get_mut(&pkg_id(name)) {
+                info.directory = (*dir).into();
+            }
+        }
+        Arc::new(index)
+    }
 }
 
 #[test]
@@ -455,3 +606,48 @@ fn test_crate_index() {
         ],
     );
 }
+
+#[test]
+fn test_package_id_for_path() {
+    use testing::pkg_id;
+
+    let checkouts = "/home/me/.cargo/git/checkouts/repo-1a2b3c/abc1234";
+    let index = testing::index_with_package_dirs(&[
+        // A git checkout of a workspace whose root is also a package.
+        ("repo", checkouts),
+        ("repo-sub", &format!("{checkouts}/crates/sub")),
+        // Vendored packages, with and without versions in their directory names.
+        ("vendored", "/ws/vendor/vendored"),
+        ("vendored-old", "/ws/vendor/vendored-old-0.1.0"),
+        // A workspace nested within another workspace's package.
+        ("outer", "/ws"),
+        ("inner", "/ws/tools/inner"),
+        ("inner-member", "/ws/tools/inner/member"),
+    ]);
+    let check = |path: &str, expected: Option<&str>| {
+        assert_eq!(
+            index.package_id_for_path(Path::new(path)),
+            expected.map(pkg_id).as_ref(),
+            "{path}"
+        );
+    };
+    check(&format!("{checkouts}/src/lib.rs"), Some("repo"));
+    check(
+        &format!("{checkouts}/crates/sub/src/lib.rs"),
+        Some("repo-sub"),
+    );
+    check(
+        &format!("{checkouts}/crates/sub/../../src/lib.rs"),
+        Some("repo"),
+    );
+    check("/ws/vendor/vendored/src/lib.rs", Some("vendored"));
+    check(
+        "/ws/vendor/vendored-old-0.1.0/c/shim.c",
+        Some("vendored-old"),
+    );
+    check("/ws/src/main.rs", Some("outer"));
+    check("/ws/tools/inner/src/main.rs", Some("inner"));
+    check("/ws/tools/inner/member/src/lib.rs", Some("inner-member"));
+    check("/ws/tools/inner/member/../../../src/main.rs", Some("outer"));
+    check("/elsewhere/src/lib.rs", None);
+}
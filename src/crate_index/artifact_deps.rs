@@ -0,0 +1,126 @@
+//! Detection of artifact dependencies (`-Z bindeps`), where a package depends on a binary built
+//! from another package, e.g. to run it from a build script or embed it. Cargo builds the binary
+//! into a separate directory, which we treat specially. The `cargo_metadata` crate doesn't expose
+//! which dependencies are artifact dependencies, so we get this from the raw JSON.
+
+use super::PackageId;
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct RawMetadata {
+    resolve: Option<RawResolve>,
+}
+
+#[derive(Deserialize)]
+struct RawResolve {
+    nodes: Vec<RawNode>,
+}
+
+#[derive(Deserialize)]
+struct RawNode {
+    id: String,
+    #[serde(default)]
+    deps: Vec<RawDep>,
+}
+
+#[derive(Deserialize)]
+struct RawDep {
+    pkg: String,
+    #[serde(default)]
+    dep_kinds: Vec<RawDepKind>,
+}
+
+#[derive(Deserialize)]
+struct RawDepKind {
+    artifact: Option<String>,
+}
+
+/// Returns a map from each package that provides artifacts to the packages that depend on them.
+pub(super) fn artifact_consumers(
+    metadata_json: &str,
+    pkg_ids: &FxHashMap<&str, PackageId>,
+) -> Result<FxHashMap<PackageId, Vec<PackageId>>> {
+    let raw: RawMetadata = serde_json::from_str(metadata_json)?;
+    let mut consumers: FxHashMap<PackageId, Vec<PackageId>> = FxHashMap::default();
+    for node in raw.resolve.iter().flat_map(|resolve| &resolve.nodes) {
+        for dep in &node.deps {
+            if !dep.dep_kinds.iter().any(|kind| kind.artifact.is_some()) {
+                continue;
+            }
+            if let (Some(provider), Some(consumer)) =
+                (pkg_ids.get(dep.pkg.as_str()), pkg_ids.get(node.id.as_str()))
+            {
+                consumers
+                    .entry(provider.clone())
+                    .or_default()
+                    .push(consumer.clone());
+            }
+        }
+    }
+    Ok(consumers)
+}
+
+/// Returns whether `path` is somewhere that cargo puts artifacts for artifact dependencies, e.g.
+/// `target/debug/deps/artifact/foo-1234/bin/foo-1234`.
+pub(crate) fn is_artifact_path(path: &Path) -> bool {
+    path.ancestors().any(|dir| {
+        dir.file_name().is_some_and(|name| name == "artifact")
+            && dir
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .is_some_and(|name| name == "deps")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::artifact_consumers;
+    use super::is_artifact_path;
+    use crate::crate_index::testing::pkg_id;
+    use rustc_hash::FxHashMap;
+    use std::path::Path;
+
+    #[test]
+    fn consumers() {
+        let json = r#"{
+            "packages": [],
+            "resolve": {
+                "nodes": [
+                    {
+                        "id": "app-id",
+                        "deps": [
+                            {
+                                "pkg": "tool-id",
+                                "dep_kinds": [{"kind": null, "artifact": "bin", "bin_name": "tool"}]
+                            },
+                            {
+                                "pkg": "serde-id",
+                                "dep_kinds": [{"kind": null}]
+                            }
+                        ]
+                    },
+                    {"id": "tool-id", "deps": []}
+                ]
+            }
+        }"#;
+        let mut pkg_ids = FxHashMap::default();
+        pkg_ids.insert("app-id", pkg_id("app"));
+        pkg_ids.insert("tool-id", pkg_id("tool"));
+        pkg_ids.insert("serde-id", pkg_id("serde"));
+        let consumers = artifact_consumers(json, &pkg_ids).unwrap();
+        assert_eq!(consumers.len(), 1);
+        assert_eq!(consumers.get(&pkg_id("tool")), Some(&vec![pkg_id("app")]));
+    }
+
+    #[test]
+    fn artifact_paths() {
+        assert!(is_artifact_path(Path::new(
+            "/t/cackle/deps/artifact/tool-46f2/bin/tool-46f2"
+        )));
+        assert!(!is_artifact_path(Path::new("/t/cackle/deps/tool-46f2")));
+        assert!(!is_artifact_path(Path::new("/t/artifact/bin/tool")));
+    }
+}
@@ -0,0 +1,423 @@
+//! Which cargo features are enabled for each package and why. When all of a package's usages of an
+//! API are in code that's only compiled with some feature, this lets us work out what changes to
+//! Cargo.toml files would stop that feature from being enabled.
+
+use super::PackageId;
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+/// Don't follow chains of features through more than this many packages. Such chains are generally
+/// short and the user would be unlikely to want to make changes to many packages anyway.
+const MAX_DEPTH: usize = 10;
+
+#[derive(Default, Debug)]
+pub(crate) struct FeatureIndex {
+    packages: FxHashMap<PackageId, PackageFeatures>,
+}
+
+#[derive(Default, Debug)]
+struct PackageFeatures {
+    /// The features declared by the package and what each of them enables.
+    declared: BTreeMap<String, Vec<String>>,
+
+    /// The features that are enabled in the resolved dependency graph.
+    enabled: BTreeSet<String>,
+
+    /// Declarations of dependencies on this package by other packages.
+    dependents: Vec<Dependent>,
+
+    is_workspace_member: bool,
+}
+
+#[derive(Debug)]
+struct Dependent {
+    pkg_id: PackageId,
+
+    /// The name by which the dependent refers to us in its Cargo.toml, i.e. after any renaming.
+    dep_name: String,
+
+    /// The features that the dependent explicitly requests.
+    features: Vec<String>,
+
+    uses_default_features: bool,
+}
+
+/// A set of changes to Cargo.toml files that would result in `feature` of `pkg_id` no longer being
+/// enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FeatureFix {
+    pub(crate) pkg_id: PackageId,
+    pub(crate) feature: String,
+    pub(crate) changes: Vec<FeatureChange>,
+}
+
+/// A change to the Cargo.toml of `manifest_of`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FeatureChange {
+    pub(crate) manifest_of: PackageId,
+    pub(crate) kind: FeatureChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum FeatureChangeKind {
+    /// Stop requesting `feature` from dependency `dependency`.
+    RemoveDependencyFeature { dependency: String, feature: String },
+
+    /// Set `default-features = false` for dependency `dependency`.
+    DisableDefaultFeatures { dependency: String },
+
+    /// Remove `value` from what feature `feature` enables.
+    RemoveFeatureValue { feature: String, value: String },
+}
+
+impl FeatureIndex {
+    pub(crate) fn new(
+        metadata: &cargo_metadata::Metadata,
+        pkg_ids: &FxHashMap<&str, PackageId>,
+    ) -> Self {
+        let mut index = FeatureIndex::default();
+        let packages_by_id: FxHashMap<&str, &cargo_metadata::Package> = metadata
+            .packages
+            .iter()
+            .map(|package| (package.id.repr.as_str(), package))
+            .collect();
+        for package in &metadata.packages {
+            if let Some(pkg_id) = pkg_ids.get(package.id.repr.as_str()) {
+                let features = index.packages.entry(pkg_id.clone()).or_default();
+                features.declared = package.features.clone();
+                features.is_workspace_member = metadata.workspace_members.contains(&package.id);
+            }
+        }
+        let Some(resolve) = &metadata.resolve else {
+            return index;
+        };
+        for node in &resolve.nodes {
+            let (Some(pkg_id), Some(package)) = (
+                pkg_ids.get(node.id.repr.as_str()),
+                packages_by_id.get(node.id.repr.as_str()),
+            ) else {
+                continue;
+            };
+            if let Some(features) = index.packages.get_mut(pkg_id) {
+                features.enabled = node.features.iter().map(|f| f.to_string()).collect();
+            }
+            for node_dep in &node.deps {
+                let Some(dep_pkg_id) = pkg_ids.get(node_dep.pkg.repr.as_str()) else {
+                    continue;
+                };
+                let Some(dep_features) = index.packages.get_mut(dep_pkg_id) else {
+                    continue;
+                };
+                for declaration in &package.dependencies {
+                    if declaration.name == dep_pkg_id.name_str()
+                        && declaration.req.matches(dep_pkg_id.version())
+                    {
+                        dep_features.dependents.push(Dependent {
+                            pkg_id: pkg_id.clone(),
+                            dep_name: declaration
+                                .rename
+                                .clone()
+                                .unwrap_or_else(|| declaration.name.clone()),
+                            features: declaration.features.clone(),
+                            uses_default_features: declaration.uses_default_features,
+                        });
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    /// Returns whether `feature` is enabled for any version of the package named `pkg_name`. The
+    /// feature may also be given as `other/feature`, in which case it's a feature of the package
+    /// `other`, e.g. a feature of a workspace member that controls whether a dependency is used.
+    pub(crate) fn is_enabled(&self, pkg_name: &str, feature: &str) -> bool {
+        let (pkg_name, feature) = feature.split_once('/').unwrap_or((pkg_name, feature));
+        self.packages.iter().any(|(pkg_id, features)| {
+            pkg_id.name_str() == pkg_name && features.enabled.contains(feature)
+        })
+    }
+
+    /// Returns the changes needed to stop `feature` of `pkg_id` from being enabled. Changes are
+    /// only made to the Cargo.toml files of workspace members, since those are the ones that the
+    /// user controls. Returns `None` if there's no way to disable the feature with such changes, or
+    /// if we can't tell why the feature is enabled, e.g. because it was requested on the command
+    /// line.
+    pub(crate) fn fix_for(&self, pkg_id: &PackageId, feature: &str) -> Option<FeatureFix> {
+        let mut changes = Vec::new();
+        let mut visited = FxHashSet::default();
+        self.add_changes(pkg_id, feature, 0, &mut visited, &mut changes)?;
+        if changes.is_empty() {
+            return None;
+        }
+        Some(FeatureFix {
+            pkg_id: pkg_id.clone(),
+            feature: feature.to_owned(),
+            changes,
+        })
+    }
+
+    fn add_changes<'a>(
+        &'a self,
+        pkg_id: &'a PackageId,
+        feature: &'a str,
+        depth: usize,
+        visited: &mut FxHashSet<(&'a PackageId, &'a str)>,
+        changes: &mut Vec<FeatureChange>,
+    ) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        if !visited.insert((pkg_id, feature)) {
+            return Some(());
+        }
+        let package = self.packages.get(pkg_id)?;
+        if !package.enabled.contains(feature) {
+            return Some(());
+        }
+        let implying = package.features_implying(feature);
+        for dependent in &package.dependents {
+            let dependent_package = self.packages.get(&dependent.pkg_id)?;
+            let dependent_is_member = dependent_package.is_workspace_member;
+            let add = |changes: &mut Vec<FeatureChange>, kind| {
+                let change = FeatureChange {
+                    manifest_of: dependent.pkg_id.clone(),
+                    kind,
+                };
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+            };
+            for requested in &dependent.features {
+                if implying.contains(requested.as_str()) {
+                    if !dependent_is_member {
+                        return None;
+                    }
+                    add(
+                        changes,
+                        FeatureChangeKind::RemoveDependencyFeature {
+                            dependency: dependent.dep_name.clone(),
+                            feature: requested.clone(),
+                        },
+                    );
+                }
+            }
+            if dependent.uses_default_features && implying.contains("default") {
+                if !dependent_is_member {
+                    return None;
+                }
+                add(
+                    changes,
+                    FeatureChangeKind::DisableDefaultFeatures {
+                        dependency: dependent.dep_name.clone(),
+                    },
+                );
+            }
+            for (dependent_feature, values) in &dependent_package.declared {
+                if !dependent_package.enabled.contains(dependent_feature) {
+                    continue;
+                }
+                for value in values {
+                    let Some((dep_name, dep_feature)) = value.split_once('/') else {
+                        continue;
+                    };
+                    if dep_name.trim_end_matches('?') != dependent.dep_name
+                        || !implying.contains(dep_feature)
+                    {
+                        continue;
+                    }
+                    if dependent_is_member {
+                        add(
+                            changes,
+                            FeatureChangeKind::RemoveFeatureValue {
+                                feature: dependent_feature.clone(),
+                                value: value.clone(),
+                            },
+                        );
+                    } else {
+                        self.add_changes(
+                            &dependent.pkg_id,
+                            dependent_feature,
+                            depth + 1,
+                            visited,
+                            changes,
+                        )?;
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+impl PackageFeatures {
+    /// Returns the enabled features of this package that directly or indirectly enable `feature`,
+    /// including `feature` itself.
+    fn features_implying<'a>(&'a self, feature: &'a str) -> FxHashSet<&'a str> {
+        let mut implying = FxHashSet::default();
+        implying.insert(feature);
+        loop {
+            let mut changed = false;
+            for (name, values) in &self.declared {
+                if self.enabled.contains(name)
+                    && !implying.contains(name.as_str())
+                    && values.iter().any(|value| implying.contains(value.as_str()))
+                {
+                    implying.insert(name.as_str());
+                    changed = true;
+                }
+            }
+            if !changed {
+                return implying;
+            }
+        }
+    }
+}
+
+impl Display for FeatureChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "In the Cargo.toml of `{}`, ", self.manifest_of)?;
+        match &self.kind {
+            FeatureChangeKind::RemoveDependencyFeature {
+                dependency,
+                feature,
+            } => write!(
+                f,
+                "remove `{feature}` from the features of dependency `{dependency}`"
+            ),
+            FeatureChangeKind::DisableDefaultFeatures { dependency } => write!(
+                f,
+                "set `default-features = false` for dependency `{dependency}`"
+            ),
+            FeatureChangeKind::RemoveFeatureValue { feature, value } => {
+                write!(f, "remove `{value}` from feature `{feature}`")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl FeatureIndex {
+    pub(crate) fn enable_for_testing(&mut self, pkg_id: PackageId, feature: &str) {
+        self.packages
+            .entry(pkg_id)
+            .or_default()
+            .enabled
+            .insert(feature.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dependent;
+    use super::FeatureIndex;
+    use super::PackageFeatures;
+    use crate::crate_index::testing::pkg_id;
+
+    fn package(declared: &[(&str, &[&str])], enabled: &[&str], member: bool) -> PackageFeatures {
+        PackageFeatures {
+            declared: declared
+                .iter()
+                .map(|(name, values)| {
+                    (
+                        name.to_string(),
+                        values.iter().map(|v| v.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            enabled: enabled.iter().map(|f| f.to_string()).collect(),
+            dependents: Vec::new(),
+            is_workspace_member: member,
+        }
+    }
+
+    fn dependent(name: &str, features: &[&str], uses_default_features: bool) -> Dependent {
+        Dependent {
+            pkg_id: pkg_id(name),
+            dep_name: "net-lib".to_owned(),
+            features: features.iter().map(|f| f.to_string()).collect(),
+            uses_default_features,
+        }
+    }
+
+    fn fix(index: &FeatureIndex, feature: &str) -> Option<Vec<String>> {
+        index
+            .fix_for(&pkg_id("net-lib"), feature)
+            .map(|fix| fix.changes.iter().map(|c| c.to_string()).collect())
+    }
+
+    #[test]
+    fn enabled_features() {
+        let mut index = FeatureIndex::default();
+        index.packages.insert(
+            pkg_id("app"),
+            package(&[("online", &[])], &["online"], true),
+        );
+        index
+            .packages
+            .insert(pkg_id("net-lib"), package(&[("tls", &[])], &[], false));
+        assert!(index.is_enabled("app", "online"));
+        assert!(index.is_enabled("net-lib", "app/online"));
+        assert!(!index.is_enabled("net-lib", "online"));
+        assert!(!index.is_enabled("net-lib", "tls"));
+        assert!(!index.is_enabled("net-lib", "other/online"));
+    }
+
+    #[test]
+    fn feature_fixes() {
+        // `app` (a workspace member) depends on `net-lib` with default features plus `tls`.
+        // `middle` (not a member) enables `net-lib/http` via its `client` feature.
+        let mut index = FeatureIndex::default();
+        let mut net_lib = package(
+            &[
+                ("default", &["std"]),
+                ("std", &[]),
+                ("tls", &[]),
+                ("http", &[]),
+            ],
+            &["default", "std", "tls", "http"],
+            false,
+        );
+        net_lib.dependents = vec![
+            dependent("app", &["tls"], true),
+            dependent("middle", &[], true),
+        ];
+        index.packages.insert(pkg_id("net-lib"), net_lib);
+        index
+            .packages
+            .insert(pkg_id("app"), package(&[], &[], true));
+        let mut middle = package(&[("client", &["net-lib/http"])], &["client"], false);
+        middle.dependents = vec![Dependent {
+            pkg_id: pkg_id("app"),
+            dep_name: "middle".to_owned(),
+            features: vec!["client".to_owned()],
+            uses_default_features: false,
+        }];
+        index.packages.insert(pkg_id("middle"), middle);
+
+        assert_eq!(
+            fix(&index, "tls"),
+            Some(vec![
+                "In the Cargo.toml of `app`, remove `tls` from the features of dependency \
+                 `net-lib`"
+                    .to_owned()
+            ])
+        );
+        assert_eq!(
+            fix(&index, "http"),
+            Some(vec![
+                "In the Cargo.toml of `app`, remove `client` from the features of dependency \
+                 `middle`"
+                    .to_owned()
+            ])
+        );
+        // `std` is enabled by default, but `middle` also uses default features and it isn't a
+        // workspace member, so there's nothing that the user can change.
+        assert_eq!(fix(&index, "std"), None);
+        // Features that aren't enabled don't need fixing.
+        assert_eq!(fix(&index, "other"), None);
+    }
+}
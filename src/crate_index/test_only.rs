@@ -0,0 +1,92 @@
+//! Detection of packages that are only in the dependency graph because workspace members use them
+//! from tests, i.e. via dev-dependencies. Such packages can still end up linked into non-test
+//! binaries under some build invocations, e.g. due to feature unification.
+
+use super::PackageId;
+use cargo_metadata::DependencyKind;
+use cargo_metadata::Metadata;
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+use std::hash::Hash;
+
+/// Returns the packages that can only be reached from workspace members by following at least one
+/// dev-dependency.
+pub(super) fn test_only_packages(
+    metadata: &Metadata,
+    pkg_ids: &FxHashMap<&str, PackageId>,
+) -> FxHashSet<PackageId> {
+    let Some(resolve) = &metadata.resolve else {
+        return FxHashSet::default();
+    };
+    let deps: FxHashMap<&str, Vec<(&str, bool)>> = resolve
+        .nodes
+        .iter()
+        .map(|node| {
+            let node_deps = node
+                .deps
+                .iter()
+                .map(|dep| {
+                    let dev_only = !dep.dep_kinds.is_empty()
+                        && dep
+                            .dep_kinds
+                            .iter()
+                            .all(|info| info.kind == DependencyKind::Development);
+                    (dep.pkg.repr.as_str(), dev_only)
+                })
+                .collect();
+            (node.id.repr.as_str(), node_deps)
+        })
+        .collect();
+    let roots = metadata.workspace_members.iter().map(|id| id.repr.as_str());
+    dev_only_reachable(roots, &deps)
+        .into_iter()
+        .filter_map(|id| pkg_ids.get(id).cloned())
+        .collect()
+}
+
+/// Returns the nodes that are reachable from `roots`, but only via an edge that's marked as being
+/// dev-only.
+fn dev_only_reachable<T: Copy + Eq + Hash>(
+    roots: impl Iterator<Item = T> + Clone,
+    deps: &FxHashMap<T, Vec<(T, bool)>>,
+) -> FxHashSet<T> {
+    let reachable = |include_dev: bool| {
+        let mut seen: FxHashSet<T> = roots.clone().collect();
+        let mut pending: Vec<T> = seen.iter().copied().collect();
+        while let Some(node) = pending.pop() {
+            for &(dep, dev_only) in deps.get(&node).into_iter().flatten() {
+                if (include_dev || !dev_only) && seen.insert(dep) {
+                    pending.push(dep);
+                }
+            }
+        }
+        seen
+    };
+    let production = reachable(false);
+    reachable(true)
+        .into_iter()
+        .filter(|node| !production.contains(node))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dev_only_reachable;
+    use rustc_hash::FxHashMap;
+
+    #[test]
+    fn dev_only() {
+        let mut deps = FxHashMap::default();
+        deps.insert(
+            "app",
+            vec![("serde", false), ("proptest", true), ("log", true)],
+        );
+        deps.insert("proptest", vec![("rand", false), ("log", false)]);
+        deps.insert("serde", vec![("log", false)]);
+        let mut test_only: Vec<&str> = dev_only_reachable(["app"].into_iter(), &deps)
+            .into_iter()
+            .collect();
+        test_only.sort();
+        assert_eq!(test_only, vec!["proptest", "rand"]);
+    }
+}
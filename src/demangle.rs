@@ -3,6 +3,15 @@
 //! mangled symbol and obtain the parts of symbol as references into the original string, thus
 //! avoiding heap allocation. This demangler was built experimentally based on observed mangled
 //! symbols. We almost certainly get stuff wrong.
+//!
+//! C++ symbols, e.g. from native code in -sys crates, are also broken apart, for both the Itanium
+//! ABI and MSVC. Like C functions, they're put in `UNMANGLED_NAMESPACE`. We only extract the path
+//! of the function or variable, e.g. `extern::openssl::SSL_connect`. Template arguments and
+//! parameter types are skipped.
+//!
+//! For Rust's v0 mangling, generic arguments are produced in full, so each type in them becomes a
+//! separate name. e.g. `<lib::Foo<std::fs::File>>::new` includes the name `std::fs::File`, which
+//! would match an API that includes `std::fs`.
 
 use anyhow::Result;
 use anyhow::anyhow;
@@ -16,15 +25,44 @@ pub(crate) enum DemangleToken<'data> {
     UnsupportedEscape(&'data str),
 }
 
+/// The namespace in which we put the names of symbols that aren't mangled, e.g. C functions. Since
+/// `extern` is a keyword, it can't collide with the name of a crate.
+pub(crate) const UNMANGLED_NAMESPACE: &str = "extern";
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum DemangleIterator<'data> {
+    /// A symbol using Rust's v0 mangling.
     V0 {
-        remaining: &'data str,
+        /// The symbol after `_R`.
+        symbol: &'data str,
+        /// How many tokens we've produced so far.
+        produced: usize,
     },
     Legacy {
         outer: &'data str,
         inner: Option<&'data str>,
     },
+    Unmangled {
+        namespace: Option<&'static str>,
+        name: Option<&'data str>,
+    },
+    /// A C++ symbol mangled according to the Itanium ABI.
+    Itanium {
+        /// A name to produce before continuing with `remaining`.
+        pending: Option<&'static str>,
+        /// What's left of the name, starting just after the `N` for nested names.
+        remaining: &'data str,
+        /// Whether the name is nested, i.e. terminated by `E`, rather than a single identifier.
+        nested: bool,
+    },
+    /// A C++ symbol mangled by MSVC.
+    Msvc {
+        namespace: Option<&'static str>,
+        /// The name followed by its enclosing scopes, innermost first, separated by `@`.
+        scopes: &'data str,
+        /// The part of `scopes` that we haven't yet produced. We produce from the end.
+        remaining: Option<&'data str>,
+    },
     Empty,
 }
 
@@ -39,7 +77,10 @@ impl<'data> DemangleIterator<'data> {
     pub(crate) fn new(data: &'data str) -> Self {
         // Check for V0 mangling (_R...)
         if let Some(rest) = data.strip_prefix("_R") {
-            return Self::V0 { remaining: rest };
+            return Self::V0 {
+                symbol: rest,
+                produced: 0,
+            };
         }
 
         // Check for legacy mangling (_ZN...E)
@@ -54,6 +95,99 @@ impl<'data> DemangleIterator<'data> {
     }
 }
 
+/// Returns an iterator for a symbol that isn't mangled, e.g. a C function. The symbol is named as
+/// if it were in `UNMANGLED_NAMESPACE`, e.g. `extern::clock_gettime`. Any symbol version is
+/// dropped. If the symbol doesn't look like an identifier, then no tokens are produced.
+impl<'data> DemangleIterator<'data> {
+    pub(crate) fn unmangled(data: &'data str) -> Self {
+        if data.starts_with("_Z") {
+            return Self::itanium(data);
+        }
+        if data.starts_with('?') {
+            return Self::msvc(data);
+        }
+        let name = data.split_once('@').map_or(data, |(name, _version)| name);
+        let is_identifier = name
+            .bytes()
+            .next()
+            .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+        if !is_identifier {
+            return Self::Empty;
+        }
+        Self::Unmangled {
+            namespace: Some(UNMANGLED_NAMESPACE),
+            name: Some(name),
+        }
+    }
+}
+
+impl<'data> DemangleIterator<'data> {
+    /// Returns an iterator for a C++ symbol mangled according to the Itanium ABI, e.g.
+    /// `_ZN7openssl11SSL_connectEP3ssl`.
+    fn itanium(data: &'data str) -> Self {
+        let data = data.split_once('@').map_or(data, |(name, _version)| name);
+        // Drop suffixes added by the compiler, e.g. `.cold`.
+        let data = data.split_once('.').map_or(data, |(name, _suffix)| name);
+        if data.starts_with("_ZN") && data.ends_with('E') {
+            // Nested names without parameters look like Rust's legacy mangling, most likely with a
+            // suffix such as `.llvm.1234`, so we don't treat them as C++.
+            return Self::Empty;
+        }
+        let Some(rest) = data.strip_prefix("_Z") else {
+            return Self::Empty;
+        };
+        // Internal linkage.
+        let rest = rest.strip_prefix('L').unwrap_or(rest);
+        if let Some(rest) = rest.strip_prefix('N') {
+            return Self::Itanium {
+                pending: Some(UNMANGLED_NAMESPACE),
+                remaining: rest,
+                nested: true,
+            };
+        }
+        if rest.starts_with(|c: char| c.is_ascii_digit()) || rest.starts_with("St") {
+            return Self::Itanium {
+                pending: Some(UNMANGLED_NAMESPACE),
+                remaining: rest,
+                nested: false,
+            };
+        }
+        // Local entities, vtables, guard variables etc.
+        Self::Empty
+    }
+
+    /// Returns an iterator for a C++ symbol mangled by MSVC, e.g. `?connect@ssl@openssl@@YAHXZ`.
+    fn msvc(data: &'data str) -> Self {
+        let Some(mut rest) = data.strip_prefix('?') else {
+            return Self::Empty;
+        };
+        if let Some(special) = rest.strip_prefix('?') {
+            // Constructors, destructors, operators and various compiler-generated symbols are
+            // identified by a code, then the class that they belong to.
+            rest = match special.as_bytes() {
+                [b'$', ..] => return Self::Empty,
+                [b'_', _, ..] => &special[2..],
+                [_, ..] => &special[1..],
+                [] => return Self::Empty,
+            };
+        }
+        let Some((scopes, _signature)) = rest.split_once("@@") else {
+            return Self::Empty;
+        };
+        // Templates and nested names have their own `@@` terminators, which we don't attempt to
+        // handle.
+        if scopes.is_empty() || scopes.split('@').any(|scope| scope.starts_with('?')) {
+            return Self::Empty;
+        }
+        Self::Msvc {
+            namespace: Some(UNMANGLED_NAMESPACE),
+            scopes,
+            remaining: Some(scopes),
+        }
+    }
+}
+
 /// An iterator that processes a non-mangled string and provides the same tokens as
 /// `DemangleIterator`.
 impl<'data> NonMangledIterator<'data> {
@@ -81,11 +215,6 @@ fn symbol(esc: &str) -> Result<char> {
     }
 }
 
-// V0 mangling helpers
-fn is_base62_digit(b: u8) -> bool {
-    b.is_ascii_alphanumeric()
-}
-
 fn parse_decimal(data: &str) -> Option<(u64, &str)> {
     let mut value = 0u64;
     let mut len = 0;
@@ -104,18 +233,6 @@ fn parse_decimal(data: &str) -> Option<(u64, &str)> {
     }
 }
 
-fn parse_disambiguator(data: &str) -> Option<&str> {
-    if let Some(rest) = data.strip_prefix('s') {
-        // Disambiguator - skip 's' and everything until '_'
-        if let Some(end) = rest.find('_') {
-            return Some(&rest[end + 1..]); // Skip past the '_'
-        }
-        None
-    } else {
-        Some(data)
-    }
-}
-
 fn parse_undisambiguated_identifier(data: &str) -> Option<(&str, &str)> {
     // Check for punycode (u followed by decimal length)
     if let Some(rest) = data.strip_prefix('u') {
@@ -141,175 +258,473 @@ fn parse_undisambiguated_identifier(data: &str) -> Option<(&str, &str)> {
     }
 }
 
-fn parse_identifier(data: &str) -> Option<(&str, &str)> {
-    // First handle the optional disambiguator
-    let data_after_disambiguator = parse_disambiguator(data)?;
-    // Then parse the actual identifier
-    parse_undisambiguated_identifier(data_after_disambiguator)
+/// Walks a symbol that uses Rust's v0 mangling, producing tokens in the order that they appear in
+/// the demangled symbol. That differs from the order in the symbol, e.g. an instantiation of a
+/// generic function starts with the `I` that introduces its generic arguments, but the path of the
+/// function comes before the arguments once demangled. Each type in the generic arguments is a
+/// separate path, so `lib::Foo<std::fs::File>::new` gives the names `lib::Foo`, `std::fs::File` and
+/// `new`. Rather than keeping a stack of where we're up to, we walk the symbol from the start for
+/// each token and stop at the one that we want. Symbols are short, so this is cheaper than
+/// allocating.
+///
+/// Trait impls are named by the path where the impl is, e.g. `std::net::socket_addr`, followed by
+/// `<Type as Trait>`, so that the name of the symbol starts with the crate that contains the code.
+/// Inherent impls of a named type are named by the type, e.g.
+/// `alloc::ffi::c_str::CString::from_raw`, as with legacy mangling. The type is necessarily from
+/// the crate that contains the impl.
+struct V0Walker<'data> {
+    /// The whole symbol after `_R`. Back-references are offsets into this.
+    symbol: &'data str,
+    /// How many more tokens to skip before we get to the one that we want.
+    skip: usize,
+    found: Option<DemangleToken<'data>>,
+    depth: u32,
+    /// Whether we're walking part of the symbol that doesn't produce tokens.
+    muted: bool,
 }
 
-impl<'data> Iterator for DemangleIterator<'data> {
-    type Item = DemangleToken<'data>;
+/// Why a walk stopped. Either we found the token that we were after, or we couldn't parse the
+/// symbol.
+struct V0Stop;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            DemangleIterator::V0 { remaining } => {
-                if remaining.is_empty() {
-                    return None;
-                }
+type V0Result<'data> = Result<&'data str, V0Stop>;
 
-                let tag = remaining.as_bytes()[0];
-                let rest = &remaining[1..];
+/// How deeply paths and types can nest, including via back-references, before we give up.
+const MAX_V0_DEPTH: u32 = 100;
 
-                macro_rules! set_remaining {
-                    ($val:expr) => {
-                        *remaining = $val
-                    };
+impl<'data> V0Walker<'data> {
+    fn emit(&mut self, token: DemangleToken<'data>) -> Result<(), V0Stop> {
+        if self.muted {
+            return Ok(());
+        }
+        if self.skip == 0 {
+            self.found = Some(token);
+            return Err(V0Stop);
+        }
+        self.skip -= 1;
+        Ok(())
+    }
+
+    fn text(&mut self, text: &'data str) -> Result<(), V0Stop> {
+        self.emit(DemangleToken::Text(text))
+    }
+
+    fn char(&mut self, ch: char) -> Result<(), V0Stop> {
+        self.emit(DemangleToken::Char(ch))
+    }
+
+    fn nested(
+        &mut self,
+        data: &'data str,
+        walk: fn(&mut Self, &'data str) -> V0Result<'data>,
+    ) -> V0Result<'data> {
+        if self.depth >= MAX_V0_DEPTH {
+            return Err(V0Stop);
+        }
+        self.depth += 1;
+        let result = walk(self, data);
+        self.depth -= 1;
+        result
+    }
+
+    fn path(&mut self, data: &'data str) -> V0Result<'data> {
+        self.nested(data, Self::path_inner)
+    }
+
+    fn path_inner(&mut self, data: &'data str) -> V0Result<'data> {
+        let tag = *data.as_bytes().first().ok_or(V0Stop)?;
+        let rest = &data[1..];
+        match tag {
+            b'C' => {
+                let (ident, rest) = v0_identifier(rest)?;
+                self.text(ident)?;
+                Ok(rest)
+            }
+            b'N' => {
+                let namespace = *rest.as_bytes().first().ok_or(V0Stop)?;
+                let rest = self.path(&rest[1..])?;
+                let (ident, rest) = v0_identifier(rest)?;
+                if namespace.is_ascii_uppercase() {
+                    // Closures and shims, which are displayed like `{closure#0}`.
+                    self.char('{')?;
+                    self.text(if namespace == b'C' { "closure" } else { "shim" })?;
+                    self.char('}')?;
+                } else if !ident.is_empty() {
+                    self.text(ident)?;
+                }
+                Ok(rest)
+            }
+            b'M' => {
+                // Inherent impl: `M <impl-path> <type>`.
+                let impl_path = skip_v0_disambiguator(rest)?;
+                let type_ = self.muted_walk(impl_path, Self::path)?;
+                if self.is_path_type(type_) {
+                    return self.type_(type_);
                 }
+                self.path(impl_path)?;
+                self.char('<')?;
+                let rest = self.type_(type_)?;
+                self.char('>')?;
+                Ok(rest)
+            }
+            b'X' => {
+                // Trait impl: `X <impl-path> <type> <trait-path>`.
+                let rest = self.path(skip_v0_disambiguator(rest)?)?;
+                self.char('<')?;
+                let rest = self.type_(rest)?;
+                self.as_trait(rest)
+            }
+            b'Y' => {
+                // `<type as trait>` without an impl, e.g. for a default method.
+                self.char('<')?;
+                let rest = self.type_(rest)?;
+                self.as_trait(rest)
+            }
+            b'I' => {
+                let rest = self.path(rest)?;
+                self.char('<')?;
+                let rest = self.generic_args(rest)?;
+                self.char('>')?;
+                Ok(rest)
+            }
+            b'B' => self.back_reference(rest, Self::path),
+            _ => Err(V0Stop),
+        }
+    }
 
-                match tag {
-                    b'C' => {
-                        // Crate root - followed by identifier
-                        if let Some((ident, new_rest)) = parse_identifier(rest) {
-                            set_remaining!(new_rest);
-                            return Some(DemangleToken::Text(ident));
-                        }
-                    }
-                    b'N' => {
-                        // Namespace tag - indicates we're in a path
-                        // The next character tells us what kind of item
-                        set_remaining!(rest);
-                        if !rest.is_empty() {
-                            let next_tag = rest.as_bytes()[0];
-                            match next_tag {
-                                b't' => {
-                                    // Type in namespace
-                                    set_remaining!(&rest[1..]);
-                                    return self.next();
-                                }
-                                b'v' => {
-                                    // Value (function/static) in namespace
-                                    set_remaining!(&rest[1..]);
-                                    return self.next();
-                                }
-                                b'C' => {
-                                    // Crate root in path
-                                    return self.next();
-                                }
-                                _ => {
-                                    // Try to parse as identifier directly
-                                    if let Some((ident, new_rest)) = parse_identifier(rest) {
-                                        set_remaining!(new_rest);
-                                        return Some(DemangleToken::Text(ident));
-                                    }
-                                }
-                            }
-                        }
-                        return self.next();
-                    }
-                    b'v' | b't' | b'm' => {
-                        // Value/type/module item - followed by path
-                        set_remaining!(rest);
-                        return self.next();
-                    }
-                    b'M' => {
-                        // Inherent impl
-                        set_remaining!(rest);
-                        if let Some((_, new_rest)) = skip_v0_type(rest) {
-                            set_remaining!(new_rest);
-                        }
-                        return self.next();
-                    }
-                    b'X' => {
-                        // Trait impl: X [<disambiguator>] <impl-path> <self-type> <trait-path>
-                        // Skip the optional disambiguator, then parse the impl-path naturally.
-                        // The impl-path contains the crate/module info we need for API detection.
-                        set_remaining!(parse_disambiguator(rest).unwrap_or(rest));
-                        return self.next();
-                    }
-                    b'Y' => {
-                        // <type as trait>: Y <self-type> <trait-path> (no disambiguator)
-                        set_remaining!(rest);
-                        return self.next();
-                    }
-                    b'B' => {
-                        // Back-reference: B<base62>_ - skip it as we don't resolve back-refs
-                        if let Some(pos) = rest.find('_') {
-                            set_remaining!(&rest[pos + 1..]);
-                        } else {
-                            set_remaining!(rest);
-                        }
-                        return self.next();
-                    }
-                    b'I' => {
-                        // Generic args start
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char('<'));
-                    }
-                    b'E' => {
-                        // End marker (generic args or other)
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char('>'));
-                    }
-                    b'p' => {
-                        // Pointer
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char('*'));
-                    }
-                    b'R' | b'Q' => {
-                        // Reference
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char('&'));
-                    }
-                    b'A' | b'S' => {
-                        // Array or Slice
-                        set_remaining!(rest);
-                        if tag == b'A' {
-                            return Some(DemangleToken::Char('['));
-                        }
-                        return self.next();
-                    }
-                    b'T' => {
-                        // Tuple
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char('('));
-                    }
-                    b'e' => {
-                        // Empty tuple
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Text("()"));
-                    }
-                    b'u' => {
-                        // Unsigned integer
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Text("u"));
-                    }
-                    b'i' => {
-                        // Signed integer
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Text("i"));
-                    }
-                    b'_' => {
-                        // Separator in generic args
-                        set_remaining!(rest);
-                        return Some(DemangleToken::Char(','));
-                    }
-                    _ if is_base62_digit(tag) => {
-                        // This is an identifier (starts with digit or letter)
-                        if let Some((ident, new_rest)) = parse_identifier(remaining) {
-                            set_remaining!(new_rest);
-                            return Some(DemangleToken::Text(ident));
-                        }
-                    }
-                    _ => {
-                        // Unknown tag - skip it
-                        set_remaining!(rest);
-                        return self.next();
+    /// Produces ` as <trait-path>>`.
+    fn as_trait(&mut self, data: &'data str) -> V0Result<'data> {
+        self.char(' ')?;
+        self.text("as")?;
+        self.char(' ')?;
+        let rest = self.path(data)?;
+        self.char('>')?;
+        Ok(rest)
+    }
+
+    fn generic_args(&mut self, mut data: &'data str) -> V0Result<'data> {
+        let mut first = true;
+        while let Some(rest) = data.strip_prefix(|c| c != 'E') {
+            if !first {
+                self.char(',')?;
+                self.char(' ')?;
+            }
+            first = false;
+            data = match data.as_bytes()[0] {
+                b'L' => skip_v0_base62(rest)?,
+                b'K' => self.constant(rest)?,
+                _ => self.type_(data)?,
+            };
+        }
+        data.strip_prefix('E').ok_or(V0Stop)
+    }
+
+    fn type_(&mut self, data: &'data str) -> V0Result<'data> {
+        self.nested(data, Self::type_inner)
+    }
+
+    fn type_inner(&mut self, data: &'data str) -> V0Result<'data> {
+        let tag = *data.as_bytes().first().ok_or(V0Stop)?;
+        let rest = &data[1..];
+        if let Some(name) = basic_type_name(tag) {
+            if tag == b'u' {
+                self.char('(')?;
+                self.char(')')?;
+            } else {
+                self.text(name)?;
+            }
+            return Ok(rest);
+        }
+        match tag {
+            b'C' | b'N' | b'M' | b'X' | b'Y' | b'I' => self.path(data),
+            b'B' => self.back_reference(rest, Self::type_),
+            b'A' | b'S' => {
+                self.char('[')?;
+                let mut rest = self.type_(rest)?;
+                if tag == b'A' {
+                    self.char(';')?;
+                    self.char(' ')?;
+                    rest = self.constant(rest)?;
+                }
+                self.char(']')?;
+                Ok(rest)
+            }
+            b'T' => {
+                self.char('(')?;
+                let mut rest = rest;
+                let mut first = true;
+                while !rest.starts_with('E') {
+                    if !first {
+                        self.char(',')?;
+                        self.char(' ')?;
                     }
+                    first = false;
+                    rest = self.type_(rest)?;
+                }
+                self.char(')')?;
+                Ok(&rest[1..])
+            }
+            b'R' | b'Q' => {
+                self.char('&')?;
+                let rest = match rest.strip_prefix('L') {
+                    Some(lifetime) => skip_v0_base62(lifetime)?,
+                    None => rest,
+                };
+                self.type_(rest)
+            }
+            b'P' | b'O' => {
+                self.char('*')?;
+                self.type_(rest)
+            }
+            b'F' => self.fn_signature(rest),
+            b'D' => self.dyn_bounds(rest),
+            _ => Err(V0Stop),
+        }
+    }
+
+    /// `F [<binder>] [U] [K <abi>] {<type>} E <type>`. The return type isn't preceded by `->`,
+    /// since `>` would look like the end of a list of generic arguments.
+    ///
+    /// Here and for other types, we don't produce keywords like `fn`, `dyn` and `const`, since
+    /// they'd be treated as names.
+    fn fn_signature(&mut self, data: &'data str) -> V0Result<'data> {
+        let mut rest = match data.strip_prefix('G') {
+            Some(binder) => skip_v0_base62(binder)?,
+            None => data,
+        };
+        rest = rest.strip_prefix('U').unwrap_or(rest);
+        if let Some(abi) = rest.strip_prefix('K') {
+            rest = match abi.strip_prefix('C') {
+                Some(after) => after,
+                None => v0_undisambiguated_identifier(abi)?.1,
+            };
+        }
+        self.char('(')?;
+        let mut first = true;
+        while !rest.starts_with('E') {
+            if !first {
+                self.char(',')?;
+                self.char(' ')?;
+            }
+            first = false;
+            rest = self.type_(rest)?;
+        }
+        self.char(')')?;
+        self.char(' ')?;
+        self.type_(&rest[1..])
+    }
+
+    /// `D [<binder>] {<path> {p <identifier> <type>}} E <lifetime>`. Associated types, e.g.
+    /// `Output = u8`, are produced as just the type, since the name of the associated type would be
+    /// produced as a separate name.
+    fn dyn_bounds(&mut self, data: &'data str) -> V0Result<'data> {
+        let mut rest = match data.strip_prefix('G') {
+            Some(binder) => skip_v0_base62(binder)?,
+            None => data,
+        };
+        let mut first = true;
+        while !rest.starts_with('E') {
+            if !first {
+                self.char(' ')?;
+                self.char('+')?;
+                self.char(' ')?;
+            }
+            first = false;
+            rest = self.path(rest)?;
+            let mut first_binding = true;
+            while let Some(binding) = rest.strip_prefix('p') {
+                self.char(if first_binding { '<' } else { ',' })?;
+                first_binding = false;
+                let (_name, after) = v0_undisambiguated_identifier(binding)?;
+                rest = self.type_(after)?;
+            }
+            if !first_binding {
+                self.char('>')?;
+            }
+        }
+        let lifetime = rest[1..].strip_prefix('L').ok_or(V0Stop)?;
+        skip_v0_base62(lifetime)
+    }
+
+    /// Constants, e.g. array lengths and const generic arguments. Their values don't name anything,
+    /// so we don't produce anything for them.
+    fn constant(&mut self, data: &'data str) -> V0Result<'data> {
+        let tag = *data.as_bytes().first().ok_or(V0Stop)?;
+        let rest = &data[1..];
+        match tag {
+            b'p' => {
+                self.text("_")?;
+                Ok(rest)
+            }
+            b'B' => self.back_reference(rest, Self::constant),
+            _ if basic_type_name(tag).is_some() => {
+                let rest = rest.strip_prefix('n').unwrap_or(rest);
+                let (digits, rest) = rest.split_once('_').ok_or(V0Stop)?;
+                if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(V0Stop);
+                }
+                Ok(rest)
+            }
+            // Structured constants, e.g. arrays and structs, which a stable rustc doesn't produce.
+            _ => Err(V0Stop),
+        }
+    }
+
+    /// Walks `data` with `walk` without producing any tokens, returning what follows.
+    fn muted_walk(
+        &mut self,
+        data: &'data str,
+        walk: fn(&mut Self, &'data str) -> V0Result<'data>,
+    ) -> V0Result<'data> {
+        let muted = std::mem::replace(&mut self.muted, true);
+        let result = walk(self, data);
+        self.muted = muted;
+        result
+    }
+
+    /// Returns whether the type at the start of `data` is named by a path, e.g. a struct, as
+    /// opposed to e.g. a slice or a pointer.
+    fn is_path_type(&self, mut data: &'data str) -> bool {
+        for _ in 0..MAX_V0_DEPTH {
+            match data.as_bytes().first() {
+                Some(b'C' | b'N' | b'M' | b'X' | b'Y' | b'I') => return true,
+                Some(b'B') => {
+                    let position = self.symbol.len() - data.len();
+                    let Some(target) = parse_v0_base62(&data[1..])
+                        .ok()
+                        .and_then(|(offset, _)| usize::try_from(offset).ok())
+                        .filter(|offset| *offset < position)
+                        .and_then(|offset| self.symbol.get(offset..))
+                    else {
+                        return false;
+                    };
+                    data = target;
                 }
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// `B <base-62-number>`, which repeats something from earlier in the symbol. `data` starts
+    /// just after the `B`.
+    fn back_reference(
+        &mut self,
+        data: &'data str,
+        walk: fn(&mut Self, &'data str) -> V0Result<'data>,
+    ) -> V0Result<'data> {
+        let position = self.symbol.len() - data.len() - 1;
+        let (offset, rest) = parse_v0_base62(data)?;
+        // Back-references can only refer to earlier parts of the symbol, which also means that
+        // they can't loop.
+        let target = usize::try_from(offset)
+            .ok()
+            .filter(|offset| *offset < position)
+            .and_then(|offset| self.symbol.get(offset..))
+            .ok_or(V0Stop)?;
+        self.nested(target, walk)?;
+        Ok(rest)
+    }
+}
+
+/// Returns the name of a v0 basic type, e.g. `h` for `u8`.
+fn basic_type_name(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        b'a' => "i8",
+        b'b' => "bool",
+        b'c' => "char",
+        b'd' => "f64",
+        b'e' => "str",
+        b'f' => "f32",
+        b'h' => "u8",
+        b'i' => "isize",
+        b'j' => "usize",
+        b'l' => "i32",
+        b'm' => "u32",
+        b'n' => "i128",
+        b'o' => "u128",
+        b's' => "i16",
+        b't' => "u16",
+        b'u' => "()",
+        b'v' => "...",
+        b'x' => "i64",
+        b'y' => "u64",
+        b'z' => "!",
+        b'p' => "_",
+        _ => return None,
+    })
+}
+
+/// Parses `_` or base-62 digits followed by `_`, returning the number and what follows.
+fn parse_v0_base62(data: &str) -> Result<(u64, &str), V0Stop> {
+    let (digits, rest) = data.split_once('_').ok_or(V0Stop)?;
+    if digits.is_empty() {
+        return Ok((0, rest));
+    }
+    let mut value = 0u64;
+    for b in digits.bytes() {
+        let digit = match b {
+            b'0'..=b'9' => b - b'0',
+            b'a'..=b'z' => b - b'a' + 10,
+            b'A'..=b'Z' => b - b'A' + 36,
+            _ => return Err(V0Stop),
+        };
+        value = value
+            .checked_mul(62)
+            .and_then(|value| value.checked_add(u64::from(digit)))
+            .ok_or(V0Stop)?;
+    }
+    Ok((value + 1, rest))
+}
+
+fn skip_v0_base62(data: &str) -> V0Result<'_> {
+    parse_v0_base62(data).map(|(_, rest)| rest)
+}
+
+/// Skips the disambiguator, `s <base-62-number>`, if there is one.
+fn skip_v0_disambiguator(data: &str) -> V0Result<'_> {
+    match data.strip_prefix('s') {
+        Some(rest) => skip_v0_base62(rest),
+        None => Ok(data),
+    }
+}
+
+/// Parses `[<disambiguator>] [u] <decimal-number> [_] <bytes>`, returning the identifier and what
+/// follows. Punycode identifiers, marked by `u`, are returned still encoded.
+fn v0_identifier(data: &str) -> Result<(&str, &str), V0Stop> {
+    v0_undisambiguated_identifier(skip_v0_disambiguator(data)?)
+}
+
+fn v0_undisambiguated_identifier(data: &str) -> Result<(&str, &str), V0Stop> {
+    let data = data.strip_prefix('u').unwrap_or(data);
+    let (len, rest) = parse_decimal(data).ok_or(V0Stop)?;
+    // If the identifier starts with a digit or `_`, it's separated from the length by `_`.
+    let rest = rest.strip_prefix('_').unwrap_or(rest);
+    let len = usize::try_from(len).map_err(|_| V0Stop)?;
+    if !rest.is_char_boundary(len.min(rest.len())) || len > rest.len() {
+        return Err(V0Stop);
+    }
+    Ok(rest.split_at(len))
+}
 
-                // If we couldn't parse, stop iteration
-                None
+impl<'data> Iterator for DemangleIterator<'data> {
+    type Item = DemangleToken<'data>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DemangleIterator::V0 { symbol, produced } => {
+                let mut walker = V0Walker {
+                    symbol,
+                    skip: *produced,
+                    found: None,
+                    depth: 0,
+                    muted: false,
+                };
+                // The walk always stops early, either because it found the token or because the
+                // symbol was invalid. The instantiating crate that follows the path is ignored.
+                let _ = walker.path(walker.symbol);
+                *produced += 1;
+                walker.found
             }
             DemangleIterator::Legacy { outer, inner } => {
                 // Legacy mangling
@@ -359,86 +774,151 @@ impl<'data> Iterator for DemangleIterator<'data> {
 
                 Some(DemangleToken::Text(part))
             }
-            DemangleIterator::Empty => None,
-        }
-    }
-}
-
-// Helper to skip over a V0 type during parsing
-fn skip_v0_type(data: &str) -> Option<((), &str)> {
-    if data.is_empty() {
-        return None;
-    }
-
-    let tag = data.as_bytes()[0];
-    let rest = &data[1..];
-
-    match tag {
-        b'C' => {
-            // Crate-relative path
-            parse_identifier(rest).map(|(_, r)| ((), r))
-        }
-        b'N' => {
-            // Nested path - recursively skip
-            skip_v0_type(rest)
-        }
-        b'p' | b'R' | b'Q' => {
-            // Pointer/reference - skip the pointee type
-            skip_v0_type(rest)
-        }
-        b'A' | b'S' => {
-            // Array/slice - skip the element type
-            skip_v0_type(rest)
-        }
-        b'T' => {
-            // Tuple - skip until we find the end
-            let mut current = rest;
-            loop {
-                if current.is_empty() {
-                    return None;
+            DemangleIterator::Unmangled { namespace, name } => namespace
+                .take()
+                .or_else(|| name.take())
+                .map(DemangleToken::Text),
+            DemangleIterator::Itanium {
+                pending,
+                remaining,
+                nested,
+            } => {
+                if let Some(name) = pending.take() {
+                    return Some(DemangleToken::Text(name));
                 }
-                if current.as_bytes()[0] == b'E' {
-                    return Some(((), &current[1..]));
-                }
-                if let Some((_, new_rest)) = skip_v0_type(current) {
-                    current = new_rest;
-                } else {
-                    return None;
+                loop {
+                    let bytes = remaining.as_bytes();
+                    match bytes {
+                        [] | [b'E', ..] | [b'U', ..] => {
+                            // End of the nested name, or an unnamed type or lambda, which we don't
+                            // name.
+                            *remaining = "";
+                            return None;
+                        }
+                        [b'0'..=b'9', ..] => {
+                            let (ident, rest) = parse_undisambiguated_identifier(remaining)?;
+                            *remaining = if *nested { rest } else { "" };
+                            if !ident.is_empty() {
+                                return Some(DemangleToken::Text(ident));
+                            }
+                        }
+                        [b'S', b't', ..] => {
+                            *remaining = &remaining[2..];
+                            return Some(DemangleToken::Text("std"));
+                        }
+                        [b'S', abbreviation, ..] if abbreviation.is_ascii_lowercase() => {
+                            *pending = match abbreviation {
+                                b'a' => Some("allocator"),
+                                b'b' => Some("basic_string"),
+                                b's' => Some("basic_string"),
+                                b'i' => Some("basic_istream"),
+                                b'o' => Some("basic_ostream"),
+                                b'd' => Some("basic_iostream"),
+                                _ => None,
+                            };
+                            *remaining = &remaining[2..];
+                            return Some(DemangleToken::Text("std"));
+                        }
+                        [b'S', ..] => {
+                            // A substitution of an earlier component. We don't keep track of
+                            // components, so can't resolve these.
+                            *remaining = remaining.split_once('_').map_or("", |(_, rest)| rest);
+                        }
+                        [b'I', ..] => {
+                            *remaining = skip_itanium_args(&remaining[1..]).unwrap_or("");
+                        }
+                        [b'C' | b'D', b'0'..=b'9' | b'I', ..] => {
+                            // Constructor or destructor. The class was the previous component.
+                            *remaining = &remaining[2..];
+                        }
+                        [b'B', ..] => {
+                            // ABI tag, e.g. `B5cxx11`.
+                            *remaining = parse_undisambiguated_identifier(&remaining[1..])
+                                .map_or("", |(_, rest)| rest);
+                        }
+                        [b'K' | b'V' | b'r' | b'R' | b'O' | b'L', ..] => {
+                            // CV and reference qualifiers.
+                            *remaining = &remaining[1..];
+                        }
+                        [first, _, ..] if first.is_ascii_lowercase() => {
+                            // An operator, which we don't name.
+                            *remaining = &remaining[2..];
+                        }
+                        _ => {
+                            *remaining = "";
+                            return None;
+                        }
+                    }
                 }
             }
-        }
-        b'I' => {
-            // Generic args - skip the path and all args
-            if let Some((_, mut current)) = skip_v0_type(rest) {
+            DemangleIterator::Msvc {
+                namespace,
+                scopes,
+                remaining,
+            } => {
+                if let Some(name) = namespace.take() {
+                    return Some(DemangleToken::Text(name));
+                }
                 loop {
-                    if current.is_empty() {
-                        return None;
-                    }
-                    if current.as_bytes()[0] == b'E' {
-                        return Some(((), &current[1..]));
-                    }
-                    if let Some((_, new_rest)) = skip_v0_type(current) {
-                        current = new_rest;
-                    } else {
-                        return None;
+                    let data = remaining.take()?;
+                    let scope = match data.rsplit_once('@') {
+                        Some((rest, scope)) => {
+                            *remaining = Some(rest);
+                            scope
+                        }
+                        None => data,
+                    };
+                    // A single digit refers back to an earlier name in the symbol.
+                    let scope = match scope.as_bytes() {
+                        [digit @ b'0'..=b'9'] => {
+                            scopes.split('@').nth((digit - b'0') as usize).unwrap_or("")
+                        }
+                        _ => scope,
+                    };
+                    if !scope.is_empty() {
+                        return Some(DemangleToken::Text(scope));
                     }
                 }
             }
-            None
-        }
-        b'e' | b'u' | b'i' | b'f' | b'b' | b'c' | b'z' | b'v' => {
-            // Primitive types
-            Some(((), rest))
-        }
-        _ if is_base62_digit(tag) => {
-            // Identifier
-            parse_identifier(data).map(|(_, r)| ((), r))
+            DemangleIterator::Empty => None,
         }
-        _ => {
-            // Unknown - try to skip
-            Some(((), rest))
+    }
+}
+
+/// Skips the template arguments of an Itanium-mangled name. `data` should start just after the
+/// `I`. Returns what follows the arguments.
+fn skip_itanium_args(mut data: &str) -> Option<&str> {
+    let mut depth = 1;
+    while depth > 0 {
+        match data.as_bytes() {
+            [] => return None,
+            [b'0'..=b'9', ..] => {
+                (_, data) = parse_undisambiguated_identifier(data)?;
+            }
+            [b'L', ..] => {
+                // A literal, e.g. `Li5E`.
+                (_, data) = data.split_once('E')?;
+            }
+            [b'S', b'a'..=b'z', ..] => {
+                // A standard abbreviation, e.g. `St` for `std`.
+                data = &data[2..];
+            }
+            [b'S' | b'T', ..] => {
+                // A substitution or template parameter, e.g. `S0_` or `T_`.
+                (_, data) = data.split_once('_')?;
+            }
+            [b'N' | b'I' | b'X' | b'J', ..] => {
+                depth += 1;
+                data = &data[1..];
+            }
+            [b'E', ..] => {
+                depth -= 1;
+                data = &data[1..];
+            }
+            [_, ..] => data = &data[1..],
         }
     }
+    Some(data)
 }
 
 /// A lookup table for determining whether a character should end a token.
@@ -534,6 +1014,57 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[track_caller]
+    fn check_cpp(mangled: &str, expected: &[&str]) {
+        let actual: Vec<&str> = DemangleIterator::unmangled(mangled)
+            .map(|token| match token {
+                DemangleToken::Text(text) => text,
+                other => panic!("Unexpected token {other:?}"),
+            })
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_itanium() {
+        check_cpp(
+            "_ZN7openssl11SSL_connectEP3ssl",
+            &["extern", "openssl", "SSL_connect"],
+        );
+        check_cpp("_Z11SSL_connectP6ssl_st", &["extern", "SSL_connect"]);
+        check_cpp(
+            "_ZNSt6vectorIiSaIiEE9push_backERKi",
+            &["extern", "std", "vector", "push_back"],
+        );
+        check_cpp(
+            "_ZNK3foo3Bar3getB5cxx11Ev",
+            &["extern", "foo", "Bar", "get"],
+        );
+        check_cpp("_ZN3foo3BarC2Ev", &["extern", "foo", "Bar"]);
+        check_cpp("_ZNSs4sizeEv", &["extern", "std", "basic_string", "size"]);
+        check_cpp("_ZN3foo3barEv@GLIBCXX_3.4", &["extern", "foo", "bar"]);
+        check_cpp(
+            "_ZN3foo7processINS_4TypeELi5EEEvT_",
+            &["extern", "foo", "process"],
+        );
+        check_cpp("_ZL6helperv.cold", &["extern", "helper"]);
+        check_cpp("_ZN3foo3bar17h0123456789abcdefE.llvm.1234", &[]);
+        check_cpp("_ZTV3Foo", &[]);
+        check_cpp("_ZZ3foovE1x", &[]);
+    }
+
+    #[test]
+    fn test_msvc() {
+        check_cpp(
+            "?connect@ssl@openssl@@YAHXZ",
+            &["extern", "openssl", "ssl", "connect"],
+        );
+        check_cpp("??0Bar@foo@@QEAA@XZ", &["extern", "foo", "Bar"]);
+        check_cpp("??_7Bar@foo@@6B@", &["extern", "foo", "Bar"]);
+        check_cpp("?f@@YAXXZ", &["extern", "f"]);
+        check_cpp("??$max@H@std@@YAHHH@Z", &[]);
+    }
+
     #[test]
     fn test_non_mangled() {
         check("", &[]);
@@ -782,6 +1313,63 @@ mod tests {
         assert_eq!(sym.crate_name(), Some("std"));
     }
 
+    #[test]
+    fn test_v0_generic_args() {
+        fn tokens(mangled: &str) -> String {
+            DemangleIterator::new(mangled)
+                .map(|token| match token {
+                    DemangleToken::Text(text) => format!("[{text}]"),
+                    DemangleToken::Char(ch) => ch.to_string(),
+                    DemangleToken::UnsupportedEscape(esc) => esc.to_owned(),
+                })
+                .collect()
+        }
+        // <lib::Foo<std::fs::File>>::new
+        assert_eq!(
+            tokens("_RNvMCsgS0eDBJFJRB_3libINtB2_3FooNtNtCsjrHSEGnQ3l9_3std2fs4FileE3newB2_"),
+            "[lib][Foo]<[std][fs][File]>[new]"
+        );
+        // <alloc::ffi::c_str::CString>::from_raw
+        assert_eq!(
+            tokens("_RNvMs_NtNtCslNYArtu3iFV_5alloc3ffi5c_strNtB4_7CString8from_raw"),
+            "[alloc][ffi][c_str][CString][from_raw]"
+        );
+        // <[u8]>::len, where the type isn't a path, so is named by where the impl is.
+        assert_eq!(
+            tokens("_RNvMNtCsgEmfK2I1SDS_4core5sliceSh3len"),
+            "[core][slice]<[[u8]]>[len]"
+        );
+        // lib::gen::<std::path::PathBuf>
+        assert_eq!(
+            tokens("_RINvCsgS0eDBJFJRB_3lib3genNtNtCsjrHSEGnQ3l9_3std4path7PathBufEB2_"),
+            "[lib][gen]<[std][path][PathBuf]>"
+        );
+        // core::hint::black_box::<&dyn for<'a> core::ops::function::Fn<(&'a [u8; 3usize], (i32,
+        // bool)), Output = *const u8>>
+        assert_eq!(
+            tokens(
+                "_RINvNtCsgEmfK2I1SDS_4core4hint9black_boxRDG_INtNtNtB4_3ops8function2FnTRL0_Ahj3_\
+                 TlbEEEp6OutputPhEL_ECsgS0eDBJFJRB_3lib"
+            ),
+            "[core][hint][black_box]<&[core][ops][function][Fn]<(&[[u8]; ], ([i32], [bool]))><*[u8]>>"
+        );
+        // <lib::run::{closure#1} as core::ops::function::FnOnce<(&[u8; 3usize], (i32, bool))>>
+        //     ::call_once::{shim:vtable#0}
+        assert_eq!(
+            tokens(
+                "_RNSNvYNCNvCsgS0eDBJFJRB_3lib3runs_0INtNtNtCsgEmfK2I1SDS_4core3ops8function6FnOnce\
+                 TRAhj3_TlbEEE9call_once6vtableB8_"
+            ),
+            "<[lib][run]{[closure]} [as] [core][ops][function][FnOnce]<(&[[u8]; ], ([i32], \
+             [bool]))>>[call_once]{[shim]}"
+        );
+        // Back-references that refer to something that contains them would never end.
+        assert_eq!(tokens("_RNvB_3foo"), "");
+        assert_eq!(tokens("_RNvNvB0_3foo3bar"), "");
+        // Invalid symbols produce whatever we got before we found the problem.
+        assert_eq!(tokens("_RINvCs0_5crate8functionQ"), "[crate][function]<&");
+    }
+
     #[test]
     fn test_v0_nested_path() {
         // Test nested module paths
@@ -0,0 +1,73 @@
+//! Items that packages may not reference, e.g. `std::fs::remove_dir_all` or `extern::system`,
+//! no matter which APIs they're allowed. Unlike APIs, which include everything under a path, a
+//! denied item only matches a reference to exactly that item. References are found by looking each
+//! item up as if it were an API, under a name that can't clash with APIs from the config. Packages
+//! can be exempted from particular items with `allow_denied_symbols`.
+
+use crate::config::ApiName;
+use crate::config::DenyConfig;
+use crate::names::Name;
+use std::sync::Arc;
+
+/// Prefix for the names of the pseudo-APIs. Config validation rejects APIs with these names.
+const API_PREFIX: &str = "denied:";
+
+fn api_name(path: &str) -> ApiName {
+    ApiName {
+        name: Arc::from(format!("{API_PREFIX}{path}")),
+    }
+}
+
+/// Returns the denied item for `api_name` if it's one of our pseudo-APIs.
+pub(crate) fn denied_path(api_name: &ApiName) -> Option<&str> {
+    api_name.as_ref().strip_prefix(API_PREFIX)
+}
+
+/// Returns whether `name` is exactly the item denied by `path`, rather than something under it.
+pub(crate) fn is_exact_match(path: &str, name: &Name) -> bool {
+    name.parts().eq(path.split("::"))
+}
+
+/// Returns the pseudo-APIs that we use to find references to denied items, together with the path
+/// of each item.
+pub(crate) fn pseudo_apis(config: &DenyConfig) -> impl Iterator<Item = (ApiName, String)> + '_ {
+    config
+        .symbols
+        .iter()
+        .map(|path| (api_name(path), path.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::denied_path;
+    use super::is_exact_match;
+    use super::pseudo_apis;
+    use crate::config::ApiName;
+    use crate::config::DenyConfig;
+    use crate::names::split_simple;
+
+    #[test]
+    fn api_names() {
+        let config = DenyConfig {
+            symbols: vec!["std::fs::remove_dir_all".to_owned()],
+        };
+        for (api_name, path) in pseudo_apis(&config) {
+            assert_eq!(denied_path(&api_name), Some(path.as_str()));
+        }
+        assert_eq!(denied_path(&ApiName::from("fs")), None);
+    }
+
+    #[test]
+    fn exact_matches() {
+        let path = "std::fs::remove_dir_all";
+        assert!(is_exact_match(
+            path,
+            &split_simple("std::fs::remove_dir_all")
+        ));
+        assert!(!is_exact_match(path, &split_simple("std::fs")));
+        assert!(!is_exact_match(
+            path,
+            &split_simple("std::fs::remove_dir_all::inner")
+        ));
+    }
+}
@@ -4,6 +4,8 @@ use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -15,7 +17,9 @@ pub(crate) fn source_files_from_rustc_args(
     let Some(deps_path) = deps_path_from_rustc_args(args)? else {
         return Ok(vec![]);
     };
-    let deps = std::fs::read_to_string(&deps_path)
+    // Source paths, e.g. of files pulled in via `include_bytes!`, needn't be valid UTF-8, so we
+    // parse the deps file as bytes.
+    let deps = std::fs::read(&deps_path)
         .with_context(|| format!("Failed to read deps file `{}`", deps_path.display()))?;
     Ok(parse_deps(&deps)?
         .into_iter()
@@ -23,32 +27,33 @@ pub(crate) fn source_files_from_rustc_args(
         .collect())
 }
 
-fn parse_deps(deps_text: &str) -> Result<Vec<PathBuf>> {
+fn parse_deps(deps_text: &[u8]) -> Result<Vec<PathBuf>> {
     let mut deps = Vec::new();
-    for line in deps_text.lines() {
-        if let Some(filename) = line.strip_suffix(':') {
+    for line in deps_text.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(filename) = line.strip_suffix(b":") {
             // Unescape Makefile escaping (e.g., "\ " -> " ")
             let unescaped = unescape_makefile_path(filename);
-            deps.push(PathBuf::from(unescaped));
+            deps.push(PathBuf::from(OsString::from_vec(unescaped)));
         }
     }
     Ok(deps)
 }
 
-fn unescape_makefile_path(path: &str) -> String {
-    let mut result = String::new();
-    let mut chars = path.chars();
-    while let Some(ch) = chars.next() {
-        if ch == '\\' {
+fn unescape_makefile_path(path: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut bytes = path.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'\\' {
             // Check if this is an escaped character
-            if let Some(next_ch) = chars.next() {
-                result.push(next_ch);
+            if let Some(&next_byte) = bytes.next() {
+                result.push(next_byte);
             } else {
                 // Trailing backslash, keep it
-                result.push(ch);
+                result.push(byte);
             }
         } else {
-            result.push(ch);
+            result.push(byte);
         }
     }
     result
@@ -96,6 +101,8 @@ mod tests {
     use super::deps_path_from_rustc_args;
     use super::parse_deps;
     use anyhow::Result;
+    use std::ffi::OsStr;
+    use std::os::unix::prelude::OsStrExt;
     use std::path::PathBuf;
 
     fn deps_path(args: &[&str]) -> Result<Option<PathBuf>> {
@@ -164,7 +171,8 @@ mod tests {
 
     #[test]
     fn test_parse_deps() {
-        let deps = parse_deps(indoc::indoc! {r#"
+        let deps = parse_deps(
+            indoc::indoc! {r#"
             /some/path/foo-1235.rmeta: foo/src/lib.rs /some/absolute/path/extra.rs
 
             /some/path/foo-1235.rlib: foo/src/lib.rs /some/absolute/path/extra.rs
@@ -173,7 +181,9 @@ mod tests {
             /some/absolute/path/extra.rs:
 
             # env-dep:OUT_DIR=/some/path/target/debug/build/foo-1235/out
-            "#})
+            "#}
+            .as_bytes(),
+        )
         .unwrap();
         assert_eq!(
             path_strings(&deps),
@@ -183,16 +193,34 @@ mod tests {
 
     #[test]
     fn test_parse_deps_with_spaces() {
-        let deps = parse_deps(indoc::indoc! {r#"
+        let deps = parse_deps(
+            indoc::indoc! {r#"
             /some/path/foo-1235.rmeta: foo/src/lib.rs /path\ with\ spaces/extra.rs
 
             foo/src/lib.rs:
             /path\ with\ spaces/extra.rs:
-            "#})
+            "#}
+            .as_bytes(),
+        )
         .unwrap();
         assert_eq!(
             path_strings(&deps),
             &["foo/src/lib.rs", "/path with spaces/extra.rs"]
         )
     }
+
+    #[test]
+    fn test_parse_deps_non_utf8() {
+        let deps = parse_deps(
+            b"/p/foo.rmeta: src/lib.rs /vendor/caf\xe9.bin\n\nsrc/lib.rs:\n/vendor/caf\xe9.bin:\n",
+        )
+        .unwrap();
+        assert_eq!(
+            deps,
+            vec![
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from(OsStr::from_bytes(b"/vendor/caf\xe9.bin")),
+            ]
+        );
+    }
 }
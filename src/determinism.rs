@@ -0,0 +1,140 @@
+//! Detection of which packages reference sources of nondeterminism, for users who need computations
+//! to be reproducible, e.g. in consensus-critical code or lockstep simulations. As for async
+//! runtimes, these references are found by looking each source up as if it were an API, under a
+//! name that can't clash with APIs from the config. Since most programs use at least some of these
+//! sources, looking for them is opt-in. See `common.determinism_audit`.
+
+use crate::config::ApiName;
+use crate::config::built_in;
+use crate::crate_index::PackageId;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum NondeterminismSource {
+    /// Changing the floating-point environment, e.g. the rounding mode.
+    FloatEnv,
+
+    /// Spawning threads, whose scheduling can affect the order in which things happen.
+    Threads,
+
+    /// Atomic operations, which generally mean that the outcome depends on thread scheduling.
+    Atomics,
+
+    /// Reading the time.
+    Time,
+
+    /// Obtaining random bytes, including for the random seeds used by `HashMap`.
+    Randomness,
+}
+
+/// Prefix for the names of the pseudo-APIs. Config validation rejects APIs with these names.
+const API_PREFIX: &str = "determinism:";
+
+/// For each source of nondeterminism, the packages that reference it.
+pub(crate) type NondeterminismSources = BTreeMap<NondeterminismSource, BTreeSet<PackageId>>;
+
+impl NondeterminismSource {
+    pub(crate) const ALL: &'static [NondeterminismSource] = &[
+        NondeterminismSource::FloatEnv,
+        NondeterminismSource::Threads,
+        NondeterminismSource::Atomics,
+        NondeterminismSource::Time,
+        NondeterminismSource::Randomness,
+    ];
+
+    /// The paths that references to this source are to. Where there's a built-in API for the
+    /// source, we use the same paths.
+    fn paths(self) -> &'static [&'static str] {
+        match self {
+            NondeterminismSource::FloatEnv => built_in::FLOAT_ENV_PATHS,
+            // Newer versions of the standard library define these in submodules of `std::thread`.
+            // `Builder::spawn` is generic, so can't be matched by path, but a `Builder` is only
+            // useful for spawning threads.
+            NondeterminismSource::Threads => &[
+                "std::thread::spawn",
+                "std::thread::functions::spawn",
+                "std::thread::scope",
+                "std::thread::scoped::scope",
+                "std::thread::Builder",
+                "std::thread::builder::Builder",
+                "extern::pthread_create",
+            ],
+            NondeterminismSource::Atomics => &["core::sync::atomic"],
+            NondeterminismSource::Time => built_in::TIME_PATHS,
+            NondeterminismSource::Randomness => built_in::ENTROPY_PATHS,
+        }
+    }
+
+    fn api_name(self) -> ApiName {
+        ApiName {
+            name: Arc::from(format!("{API_PREFIX}{self}")),
+        }
+    }
+
+    /// Returns the source for `api_name` if it's one of our pseudo-APIs.
+    pub(crate) fn from_api_name(api_name: &ApiName) -> Option<NondeterminismSource> {
+        let name = api_name.as_ref().strip_prefix(API_PREFIX)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|source| source.to_string() == name)
+    }
+
+    /// Returns the pseudo-APIs that we use to find references to sources of nondeterminism,
+    /// together with the paths that they include.
+    pub(crate) fn pseudo_apis() -> impl Iterator<Item = (ApiName, &'static str)> {
+        Self::ALL.iter().flat_map(|source| {
+            source
+                .paths()
+                .iter()
+                .map(move |path| (source.api_name(), *path))
+        })
+    }
+}
+
+impl Display for NondeterminismSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NondeterminismSource::FloatEnv => write!(f, "float-env"),
+            NondeterminismSource::Threads => write!(f, "threads"),
+            NondeterminismSource::Atomics => write!(f, "atomics"),
+            NondeterminismSource::Time => write!(f, "time"),
+            NondeterminismSource::Randomness => write!(f, "randomness"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NondeterminismSource;
+    use crate::config::ApiName;
+
+    #[test]
+    fn api_names() {
+        for source in NondeterminismSource::ALL {
+            assert_eq!(
+                NondeterminismSource::from_api_name(&source.api_name()),
+                Some(*source)
+            );
+            // Serialised names should match what we display in the summary.
+            assert_eq!(
+                serde_json::to_value(source).unwrap(),
+                serde_json::Value::String(source.to_string())
+            );
+        }
+        assert_eq!(
+            NondeterminismSource::from_api_name(&ApiName::from("time")),
+            None
+        );
+        let float_env = NondeterminismSource::pseudo_apis()
+            .find(|(_, path)| *path == "extern::fesetround")
+            .map(|(api, _)| NondeterminismSource::from_api_name(&api));
+        assert_eq!(float_env, Some(Some(NondeterminismSource::FloatEnv)));
+    }
+}
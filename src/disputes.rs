@@ -0,0 +1,316 @@
+//! Problems that the user thinks are false positives. Disputing a problem from the UI records what
+//! we knew about it in a triage file next to the config, so that it can be looked into later, e.g.
+//! with `cargo acl disputed list`, or attached to a bug report. Until the dispute is removed from
+//! the file, the problem is excluded from the check, so that analysis can proceed. Disputed
+//! problems are still reported as warnings, so that they don't get forgotten.
+
+use crate::checker::api_map::ApiRules;
+use crate::config::Config;
+use crate::outcome;
+use crate::outcome::ExitCode;
+use crate::problem::Problem;
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The name of the triage file, which lives in the same directory as the config file.
+const DISPUTES_FILENAME: &str = "cackle-disputed.toml";
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct DisputedOptions {
+    #[command(subcommand)]
+    command: DisputedCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum DisputedCommand {
+    /// List problems that have been disputed, together with what we knew about each of them.
+    List,
+}
+
+/// The contents of the triage file.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct DisputeFile {
+    #[serde(default, rename = "disputed")]
+    disputes: Vec<Dispute>,
+}
+
+/// A problem that the user has said looks wrong.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Dispute {
+    /// The problem's summary, as it would appear in a report. As for migration baselines, problems
+    /// with the same summary are considered to be the same problem.
+    summary: String,
+
+    /// When the problem was disputed.
+    date: toml::value::Datetime,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    usages: Vec<DisputedUsage>,
+}
+
+/// What we knew about a single API usage from a disputed problem.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct DisputedUsage {
+    from: String,
+
+    to: String,
+
+    /// The API path from the config that `to` matched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+
+    location: String,
+
+    /// The packages that the code at `location` could have been attributed to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attributed_to: Vec<String>,
+}
+
+/// The summaries of the problems that have been disputed.
+#[derive(Debug, Default)]
+pub(crate) struct Disputes {
+    summaries: FxHashSet<String>,
+}
+
+/// Returns the path of the triage file that goes with the config file at `config_path`.
+pub(crate) fn path(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(DISPUTES_FILENAME)
+}
+
+impl DisputeFile {
+    /// Loads the triage file at `path`. A missing file has no disputes.
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse `{}`", path.display()))
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        crate::fs::write_atomic(path, &toml::to_string(self)?)
+    }
+
+    fn to_terminal(&self) -> String {
+        use std::fmt::Write;
+
+        if self.disputes.is_empty() {
+            return "No disputed problems\n".to_owned();
+        }
+        let mut out = String::new();
+        for dispute in &self.disputes {
+            let _ = writeln!(out, "{} (disputed {})", dispute.summary, dispute.date);
+            for usage in &dispute.usages {
+                let _ = write!(
+                    out,
+                    "  {} -> {} at {}",
+                    usage.from, usage.to, usage.location
+                );
+                if let Some(pattern) = &usage.pattern {
+                    let _ = write!(out, ", matched `{pattern}`");
+                }
+                if !usage.attributed_to.is_empty() {
+                    let _ = write!(out, ", attributed to {}", usage.attributed_to.join(", "));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+impl Disputes {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            summaries: DisputeFile::load(path)?
+                .disputes
+                .into_iter()
+                .map(|dispute| dispute.summary)
+                .collect(),
+        })
+    }
+
+    pub(crate) fn contains(&self, problem: &Problem) -> bool {
+        self.summaries.contains(&summary(problem))
+    }
+}
+
+/// Records `problem` as disputed in the triage file at `path`.
+pub(crate) fn record(path: &Path, problem: &Problem, config: &Config) -> Result<()> {
+    let mut file = DisputeFile::load(path)?;
+    let dispute = Dispute::new(problem, config);
+    if file.disputes.iter().all(|d| d.summary != dispute.summary) {
+        file.disputes.push(dispute);
+        file.write(path)?;
+    }
+    Ok(())
+}
+
+/// Reports have full names, so we produce summaries in the same way.
+fn summary(problem: &Problem) -> String {
+    crate::name_display::for_json(|| problem.to_string())
+}
+
+impl Dispute {
+    fn new(problem: &Problem, config: &Config) -> Self {
+        let mut dispute = Dispute {
+            summary: summary(problem),
+            date: toml::value::Datetime {
+                date: Some(crate::migration::today()),
+                time: None,
+                offset: None,
+            },
+            package: problem.pkg_id().map(|pkg_id| pkg_id.to_string()),
+            api: None,
+            usages: Vec::new(),
+        };
+        let usages = match problem {
+            Problem::DisallowedApiUsage(usages) => usages,
+            Problem::OffTreeApiUsage(info) => &info.usages,
+            _ => return dispute,
+        };
+        let rules = config.raw.apis.get(&usages.api_name).map(ApiRules::new);
+        dispute.api = Some(usages.api_name.to_string());
+        crate::name_display::for_json(|| {
+            for usage in &usages.usages {
+                let disputed = DisputedUsage {
+                    from: usage.from.to_string(),
+                    to: usage.to_name.to_string(),
+                    pattern: rules
+                        .as_ref()
+                        .and_then(|rules| rules.most_specific_include(&usage.to_name))
+                        .map(|path| path.to_string()),
+                    location: usage.source_location.to_string(),
+                    attributed_to: usage
+                        .origin
+                        .package_candidates
+                        .iter()
+                        .map(|pkg_id| pkg_id.to_string())
+                        .collect(),
+                };
+                if !dispute.usages.contains(&disputed) {
+                    dispute.usages.push(disputed);
+                }
+            }
+        });
+        dispute
+    }
+}
+
+pub(crate) fn run(options: &DisputedOptions, config_path: &Path) -> ExitCode {
+    let DisputedCommand::List = options.command;
+    let path = path(config_path);
+    match DisputeFile::load(&path) {
+        Ok(file) => {
+            print!("{}", file.to_terminal());
+            outcome::SUCCESS
+        }
+        Err(error) => {
+            println!("{error:#}");
+            outcome::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DisputeFile;
+    use super::Disputes;
+    use super::record;
+    use crate::checker::ApiUsage;
+    use crate::checker::BinLocation;
+    use crate::checker::ReferenceKind;
+    use crate::config::ApiName;
+    use crate::config::permissions::PermissionScope;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::problem::ApiUsages;
+    use crate::problem::Problem;
+    use crate::symbol::Symbol;
+    use crate::symbol_graph::NameSource;
+    use crate::symbol_graph::UsageOrigin;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn record_and_load() {
+        let config = crate::config::testing::parse(
+            r#"
+            [api.fs]
+            include = ["std::fs", "std::fs::read"]
+            "#,
+        )
+        .unwrap();
+        let usage = ApiUsage {
+            bin_location: BinLocation {
+                address: 0,
+                symbol_start: 0,
+            },
+            bin_path: Arc::from(Path::new("bin")),
+            permission_scope: PermissionScope::All,
+            source_location: SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 10, Some(5)),
+            outer_location: None,
+            from: SymbolOrDebugName::Symbol(Symbol::borrowed(b"foo::load")),
+            to_name: crate::names::split_simple("std::fs::read"),
+            to: SymbolOrDebugName::Symbol(Symbol::borrowed(b"std::fs::read")),
+            to_source: NameSource::Symbol(Symbol::borrowed(b"std::fs::read")),
+            kind: ReferenceKind::Call,
+            origin: UsageOrigin {
+                package_candidates: vec![pkg_id("foo")],
+                ..Default::default()
+            },
+            debug_data: None,
+            callers: Vec::new(),
+            inline_chain: Vec::new(),
+        };
+        let problem = Problem::DisallowedApiUsage(ApiUsages {
+            pkg_id: pkg_id("foo"),
+            scope: PermissionScope::All,
+            api_name: ApiName::from("fs"),
+            usages: vec![usage.clone(), usage],
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        });
+        let other = Problem::UsesBuildScript(pkg_id("bar"));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("cackle-disputed.toml");
+        assert!(!Disputes::load(&path).unwrap().contains(&problem));
+        record(&path, &problem, &config).unwrap();
+        // Disputing the same problem again doesn't add another entry.
+        record(&path, &problem, &config).unwrap();
+        let disputes = Disputes::load(&path).unwrap();
+        assert!(disputes.contains(&problem));
+        assert!(!disputes.contains(&other));
+
+        let file = DisputeFile::load(&path).unwrap();
+        assert_eq!(file.disputes.len(), 1);
+        let dispute = &file.disputes[0];
+        assert_eq!(dispute.package.as_deref(), Some("foo"));
+        assert_eq!(dispute.api.as_deref(), Some("fs"));
+        assert_eq!(dispute.usages.len(), 1);
+        let usage = &dispute.usages[0];
+        assert_eq!(usage.from, "foo::load");
+        assert_eq!(usage.to, "std::fs::read");
+        assert_eq!(usage.pattern.as_deref(), Some("std::fs::read"));
+        assert_eq!(usage.attributed_to, vec!["foo"]);
+    }
+}
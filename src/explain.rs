@@ -0,0 +1,172 @@
+//! Explanation of what we know about a symbol: how its name splits into names, which APIs those
+//! names match and, for each binary containing the symbol, its debug info and which package it's
+//! attributed to. This is useful for figuring out why a usage was or wasn't reported. Attribution
+//! depends on the source files that rustc reports for each crate, so we only know it after a build.
+
+use crate::checker::Checker;
+use crate::config::ApiName;
+use crate::crate_index::PackageId;
+use crate::demangle::DemangleToken;
+use crate::location::SourceLocation;
+use crate::names::DebugName;
+use crate::names::NamesIterator;
+use crate::symbol::Symbol;
+use anyhow::Result;
+use clap::Parser;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct ExplainSymbolOptions {
+    /// The symbol to explain. Either a mangled symbol, e.g. `_ZN3std2fs5write17h0123456789abcdefE`,
+    /// or a demangled name without the hash, e.g. `std::fs::write`, which explains all symbols that
+    /// demangle to that name.
+    pub(crate) symbol: String,
+}
+
+/// What we found out about a symbol in one binary.
+pub(crate) struct SymbolExplanation {
+    pub(crate) bin_path: Arc<Path>,
+    pub(crate) symbol: Symbol<'static>,
+    pub(crate) address: Option<u64>,
+    pub(crate) weak: bool,
+    pub(crate) debug_name: Option<DebugName<'static>>,
+    pub(crate) source_location: Option<SourceLocation>,
+    /// The packages that code in the symbol is attributed to. Empty if we don't know, or if it's
+    /// from the standard library.
+    pub(crate) pkg_ids: Vec<PackageId>,
+    pub(crate) in_rust_std: bool,
+}
+
+/// Returns a description of what we know about the symbol in `options`, having found
+/// `explanations` in the binaries that were analysed.
+pub(crate) fn describe(
+    options: &ExplainSymbolOptions,
+    explanations: &[SymbolExplanation],
+    checker: &Checker,
+) -> Result<String> {
+    let mut out = String::new();
+    let symbol = Symbol::borrowed(options.symbol.as_bytes());
+    writeln!(out, "Symbol `{}`", options.symbol)?;
+    if symbol.is_mangled() {
+        writeln!(out, "  Demangled: {symbol}")?;
+        describe_names(&mut out, "  ", symbol.names()?, checker)?;
+    } else {
+        // Most likely a demangled name that the user copied from our output.
+        writeln!(out, "  Names:")?;
+        let parts: Vec<&str> = options.symbol.split("::").collect();
+        describe_name(&mut out, "  ", &parts, checker)?;
+    }
+    if explanations.is_empty() {
+        writeln!(out, "Not found in any binary that was analysed")?;
+    }
+    for explanation in explanations {
+        writeln!(out, "In `{}`", explanation.bin_path.display())?;
+        if explanation.symbol != symbol {
+            writeln!(out, "  Symbol: {:?}", explanation.symbol)?;
+            describe_names(&mut out, "    ", explanation.symbol.names()?, checker)?;
+        }
+        match explanation.address {
+            Some(address) => {
+                write!(out, "  Address: {address:#x}")?;
+                if explanation.weak {
+                    write!(out, " (weak)")?;
+                }
+                writeln!(out)?;
+            }
+            None => writeln!(
+                out,
+                "  Not in the symbol table, e.g. because it was inlined"
+            )?,
+        }
+        if let Some(debug_name) = &explanation.debug_name {
+            writeln!(out, "  Debug name: {debug_name}")?;
+            describe_names(&mut out, "    ", debug_name.names_iterator(), checker)?;
+            writeln!(
+                out,
+                "    Names from the symbol are only used if the debug name doesn't match any API"
+            )?;
+        } else {
+            writeln!(out, "  No debug info")?;
+        }
+        if let Some(location) = &explanation.source_location {
+            writeln!(out, "  Defined at: {location}")?;
+        }
+        if explanation.in_rust_std {
+            writeln!(out, "  Attributed to: the Rust standard library")?;
+        } else if explanation.pkg_ids.is_empty() {
+            writeln!(out, "  Attributed to: unknown")?;
+        } else {
+            let pkg_ids: Vec<String> = explanation
+                .pkg_ids
+                .iter()
+                .map(|pkg_id| format!("`{pkg_id}`"))
+                .collect();
+            writeln!(out, "  Attributed to: {}", pkg_ids.join(", "))?;
+        }
+    }
+    Ok(out)
+}
+
+/// Writes each name in `names`, together with the APIs that it matches.
+fn describe_names<'data, I: Clone + Iterator<Item = DemangleToken<'data>>>(
+    out: &mut String,
+    indent: &str,
+    mut names: NamesIterator<'data, I>,
+    checker: &Checker,
+) -> Result<()> {
+    writeln!(out, "{indent}Names:")?;
+    while let Some((parts, _)) = names.next_name()? {
+        let parts: Vec<&str> = parts.collect();
+        if !parts.is_empty() {
+            describe_name(out, indent, &parts, checker)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the name made up of `parts`, together with the APIs that it matches.
+fn describe_name(out: &mut String, indent: &str, parts: &[&str], checker: &Checker) -> Result<()> {
+    let apis = checker.apis_for_name_iterator(parts.iter().copied());
+    let mut apis: Vec<&ApiName> = apis.iter().collect();
+    apis.sort();
+    let name = parts.join("::");
+    if apis.is_empty() {
+        writeln!(out, "{indent}  {name}: no APIs")?;
+    } else {
+        let apis: Vec<String> = apis.iter().map(|api| format!("`{api}`")).collect();
+        writeln!(out, "{indent}  {name}: {}", apis.join(", "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExplainSymbolOptions;
+    use super::describe;
+    use crate::checker::testing::checker_for_testing;
+    use crate::config::testing::parse;
+
+    #[test]
+    fn names_and_apis() {
+        let mut checker = checker_for_testing();
+        checker.update_config(
+            parse(
+                r#"
+                [api.fs]
+                include = ["std::fs"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let options = ExplainSymbolOptions {
+            symbol: "_ZN3std2fs5write17h0123456789abcdefE".to_owned(),
+        };
+        assert_eq!(
+            describe(&options, &[], &checker).unwrap(),
+            "Symbol `_ZN3std2fs5write17h0123456789abcdefE`\n  Demangled: std::fs::write\n  \
+             Names:\n    std::fs::write: `fs`\nNot found in any binary that was analysed\n"
+        );
+    }
+}
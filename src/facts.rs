@@ -0,0 +1,425 @@
+//! A stream of facts about what analysis found, intended for consumption by external policy engines
+//! such as Open Policy Agent. Each fact is written as a single line of JSON. The format is intended
+//! to be stable, so fields may be added, but existing fields shouldn't be renamed or removed.
+
+use crate::checker::ApiUsage;
+use crate::checker::ReferenceKind;
+use crate::config::PackageConfig;
+use crate::config::permissions::PermSel;
+use crate::config::permissions::PermissionScope;
+use crate::location::SourceLocation;
+use crate::name_display::shorten_for_json;
+use crate::names::SymbolOrDebugName;
+use crate::problem::ApiUsages;
+use crate::proxy::rpc::UnsafeUsage;
+use crate::symbol_graph::NameSource;
+use anyhow::Context;
+use anyhow::Result;
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Incremented if we ever make an incompatible change to the format of facts.
+const FACT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "fact", rename_all = "snake_case")]
+pub(crate) enum Fact {
+    /// A package was found to reference an API.
+    ApiUsage(ApiUsageFact),
+
+    /// A package that isn't permitted to use unsafe was found to use it.
+    Unsafe(UnsafeFact),
+
+    /// The permissions granted to a package selector by the configuration.
+    Permission(PermissionFact),
+
+    /// A symbol defined in a binary. These are only gathered when there are plugins, since there
+    /// are a lot of them. See `plugins.rs`.
+    Symbol(SymbolFact),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ApiUsageFact {
+    pub(crate) package: String,
+    pub(crate) version: String,
+    pub(crate) scope: PermissionScope,
+    pub(crate) api: String,
+    pub(crate) location: SourceLocation,
+    pub(crate) provenance: Box<Provenance>,
+}
+
+/// Where an API usage was found and what it referenced.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Provenance {
+    /// The linked binary in which the reference was found.
+    pub(crate) bin_path: PathBuf,
+    /// The function or variable containing the reference.
+    pub(crate) from: String,
+    /// The name that matched the API.
+    pub(crate) to: String,
+    /// Whether the reference was a call, a data reference or a vtable entry.
+    pub(crate) reference: ReferenceKind,
+    /// The location of the outer, non-inlined function or variable that contained the reference.
+    pub(crate) outer_location: Option<SourceLocation>,
+    /// The object file, or archive, containing the relocation that produced the reference. `None`
+    /// if the reference wasn't found via a relocation.
+    #[serde(serialize_with = "crate::path_serde::option::serialize")]
+    pub(crate) object_file: Option<PathBuf>,
+    /// The member of the archive `object_file` containing the relocation.
+    #[serde(serialize_with = "crate::path_serde::option::serialize")]
+    pub(crate) archive_member: Option<PathBuf>,
+    /// The section containing the relocation.
+    pub(crate) section: Option<String>,
+    /// The offset of the relocation within `section`.
+    pub(crate) relocation_offset: Option<u64>,
+    /// The name of the DWARF compilation unit containing the reference.
+    #[serde(serialize_with = "crate::path_serde::option::serialize")]
+    pub(crate) compilation_unit: Option<PathBuf>,
+    /// Whether `from` was named by debug info, e.g. because it was inlined, or by the symbol table.
+    pub(crate) from_name_source: NameSourceKind,
+    /// Whether the name that matched the API came from debug info or from the symbol table.
+    pub(crate) to_name_source: NameSourceKind,
+    /// The packages that the source file of the reference belongs to, any of which may have been
+    /// attributed the usage. Each is written as `name version`.
+    pub(crate) package_candidates: Vec<String>,
+    /// The functions that were inlined into one another to produce the code containing the
+    /// reference, outermost first. Empty if the code wasn't inlined.
+    pub(crate) inline_chain: Vec<InlineFrameFact>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct InlineFrameFact {
+    pub(crate) function: String,
+    /// Where in `function` the next function was inlined, or for the innermost function, where the
+    /// reference is.
+    pub(crate) location: Option<SourceLocation>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NameSourceKind {
+    Symbol,
+    DebugName,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct UnsafeFact {
+    pub(crate) package: String,
+    pub(crate) version: String,
+    pub(crate) crate_kind: String,
+    pub(crate) locations: Vec<SourceLocation>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PermissionFact {
+    pub(crate) package: String,
+    pub(crate) scope: PermissionScope,
+    pub(crate) allow_apis: Vec<String>,
+    pub(crate) allow_unsafe: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) allow_unsafe_in: Option<Vec<String>>,
+    pub(crate) allow_proc_macro: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SymbolFact {
+    pub(crate) bin_path: PathBuf,
+    /// The symbol as it appears in the binary, i.e. mangled.
+    pub(crate) symbol: String,
+    /// The demangled name, or the name from debug info if there is one.
+    pub(crate) name: String,
+    /// The crate that the symbol's name says it's from, if any.
+    pub(crate) crate_name: Option<String>,
+    /// The packages that the symbol was attributed to, each written as `name version`.
+    pub(crate) packages: Vec<String>,
+    pub(crate) location: SourceLocation,
+}
+
+#[derive(Serialize)]
+struct VersionedFact<'a> {
+    format_version: u32,
+    #[serde(flatten)]
+    fact: &'a Fact,
+}
+
+/// Collects facts and, if an output path was supplied, writes them out as they're produced. The
+/// default stream is disabled and ignores everything it's given.
+#[derive(Default)]
+pub(crate) struct FactStream {
+    enabled: bool,
+    output_path: Option<PathBuf>,
+    output: Option<File>,
+    facts: Vec<Fact>,
+    seen: FxHashSet<Fact>,
+}
+
+impl FactStream {
+    /// Returns a stream that collects facts, writing them to `output_path` if supplied.
+    pub(crate) fn new(output_path: Option<PathBuf>) -> Self {
+        Self {
+            enabled: true,
+            output_path,
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether facts are being collected. Callers should check this before building facts
+    /// that are expensive to produce.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Records `fact`. Facts that have already been recorded are ignored, since we can end up
+    /// scanning the same binary more than once if the configuration changes.
+    pub(crate) fn emit(&mut self, fact: Fact) -> Result<()> {
+        if !self.enabled || !self.seen.insert(fact.clone()) {
+            return Ok(());
+        }
+        if let Some(path) = self.output_path.as_ref() {
+            if self.output.is_none() {
+                self.output = Some(
+                    File::create(path)
+                        .with_context(|| format!("Failed to create `{}`", path.display()))?,
+                );
+            }
+            if let Some(output) = self.output.as_mut() {
+                let line = fact.to_json_line()?;
+                output
+                    .write_all(line.as_bytes())
+                    .with_context(|| format!("Failed to write to `{}`", path.display()))?;
+            }
+        }
+        self.facts.push(fact);
+        Ok(())
+    }
+
+    pub(crate) fn facts(&self) -> &[Fact] {
+        &self.facts
+    }
+}
+
+impl Fact {
+    /// Returns the fact as a line of JSON, including the trailing newline.
+    pub(crate) fn to_json_line(&self) -> Result<String> {
+        let mut line = serde_json::to_string(&VersionedFact {
+            format_version: FACT_FORMAT_VERSION,
+            fact: self,
+        })?;
+        line.push('\n');
+        Ok(line)
+    }
+
+    pub(crate) fn api_usage(usages: &ApiUsages, usage: &ApiUsage) -> Self {
+        Fact::ApiUsage(ApiUsageFact {
+            package: usages.pkg_id.name_str().to_owned(),
+            version: usages.pkg_id.version().to_string(),
+            scope: usages.scope,
+            api: usages.api_name.to_string(),
+            location: usage.source_location.clone(),
+            provenance: Box::new(Provenance {
+                bin_path: usage.bin_path.to_path_buf(),
+                from: shorten_for_json(&usage.from),
+                to: shorten_for_json(&usage.to),
+                reference: usage.kind,
+                outer_location: usage.outer_location.clone(),
+                object_file: usage.origin.object_file.clone(),
+                archive_member: usage.origin.archive_member.clone(),
+                section: usage.origin.section.clone(),
+                relocation_offset: usage.origin.relocation_offset,
+                compilation_unit: usage.origin.compilation_unit.clone(),
+                from_name_source: match usage.from {
+                    SymbolOrDebugName::Symbol(_) => NameSourceKind::Symbol,
+                    SymbolOrDebugName::DebugName(_) => NameSourceKind::DebugName,
+                },
+                to_name_source: match usage.to_source {
+                    NameSource::Symbol(_) => NameSourceKind::Symbol,
+                    NameSource::DebugName(_) => NameSourceKind::DebugName,
+                },
+                package_candidates: usage
+                    .origin
+                    .package_candidates
+                    .iter()
+                    .map(|pkg_id| format!("{} {}", pkg_id.name_str(), pkg_id.version()))
+                    .collect(),
+                inline_chain: usage
+                    .inline_chain
+                    .iter()
+                    .map(|frame| InlineFrameFact {
+                        function: shorten_for_json(&frame.function),
+                        location: frame.location.clone(),
+                    })
+                    .collect(),
+            }),
+        })
+    }
+
+    pub(crate) fn unsafe_usage(usage: &UnsafeUsage) -> Self {
+        let pkg_id = usage.crate_sel.pkg_id();
+        Fact::Unsafe(UnsafeFact {
+            package: pkg_id.name_str().to_owned(),
+            version: pkg_id.version().to_string(),
+            crate_kind: format!("{:?}", usage.crate_sel.kind),
+            locations: usage.locations.clone(),
+        })
+    }
+
+    pub(crate) fn permission(perm_sel: &PermSel, config: &PackageConfig) -> Self {
+        let mut allow_apis: Vec<String> = config.allow_apis.iter().map(|a| a.to_string()).collect();
+        allow_apis.sort();
+        Fact::Permission(PermissionFact {
+            package: perm_sel.package_name.to_string(),
+            scope: perm_sel.scope,
+            allow_apis,
+            allow_unsafe: config.allow_unsafe.is_allowed(),
+            allow_unsafe_in: config.allow_unsafe_in.clone(),
+            allow_proc_macro: config.allow_proc_macro,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fact;
+    use super::FactStream;
+    use crate::checker::ApiUsage;
+    use crate::checker::BinLocation;
+    use crate::checker::InlineFrame;
+    use crate::checker::ReferenceKind;
+    use crate::config::AllowUnsafe;
+    use crate::config::ApiName;
+    use crate::config::PackageConfig;
+    use crate::config::permissions::PermSel;
+    use crate::config::permissions::PermissionScope;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::names::DebugName;
+    use crate::names::Namespace;
+    use crate::names::SymbolOrDebugName;
+    use crate::problem::ApiUsages;
+    use crate::symbol::Symbol;
+    use crate::symbol_graph::NameSource;
+    use crate::symbol_graph::UsageOrigin;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn facts_written_as_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("facts.jsonl");
+        let mut stream = FactStream::new(Some(path.clone()));
+        let config = PackageConfig {
+            allow_unsafe: AllowUnsafe::Bool(true),
+            ..Default::default()
+        };
+        let fact = Fact::permission(&PermSel::for_build_script("crab1"), &config);
+        stream.emit(fact.clone()).unwrap();
+        // Duplicates are dropped.
+        stream.emit(fact).unwrap();
+        assert_eq!(stream.facts().len(), 1);
+        assert!(stream.is_enabled());
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written,
+            "{\"format_version\":1,\"fact\":\"permission\",\"package\":\"crab1\",\"scope\":\"Build\",\
+             \"allow_apis\":[],\"allow_unsafe\":true,\"allow_proc_macro\":false}\n"
+        );
+    }
+
+    #[test]
+    fn disabled_stream_ignores_facts() {
+        let mut stream = FactStream::default();
+        assert!(!stream.is_enabled());
+        stream
+            .emit(Fact::permission(
+                &PermSel::for_build_script("crab1"),
+                &PackageConfig::default(),
+            ))
+            .unwrap();
+        assert!(stream.facts().is_empty());
+    }
+
+    #[test]
+    fn api_usage_provenance() {
+        let usage = ApiUsage {
+            bin_location: BinLocation {
+                address: 0,
+                symbol_start: 0,
+            },
+            bin_path: Arc::from(Path::new("bin")),
+            permission_scope: PermissionScope::All,
+            source_location: SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 10, Some(5)),
+            outer_location: None,
+            from: SymbolOrDebugName::DebugName(DebugName::new(Namespace::top_level("foo"), "read")),
+            to_name: crate::names::split_simple("std::fs::read"),
+            to: SymbolOrDebugName::Symbol(Symbol::borrowed(b"std::fs::read")),
+            to_source: NameSource::Symbol(Symbol::borrowed(b"std::fs::read")),
+            kind: ReferenceKind::Call,
+            origin: UsageOrigin {
+                object_file: Some("libfoo.rlib".into()),
+                archive_member: Some("foo.o".into()),
+                section: Some(".text.read".to_owned()),
+                relocation_offset: Some(8),
+                compilation_unit: Some("src/lib.rs/@/foo.cgu.0".into()),
+                package_candidates: vec![pkg_id("foo")],
+            },
+            debug_data: None,
+            callers: Vec::new(),
+            inline_chain: vec![
+                InlineFrame {
+                    function: SymbolOrDebugName::DebugName(DebugName::new(
+                        Namespace::top_level("foo"),
+                        "read",
+                    )),
+                    location: Some(SourceLocation::new(
+                        Path::new("/ws/foo/src/lib.rs"),
+                        10,
+                        None,
+                    )),
+                },
+                InlineFrame {
+                    function: SymbolOrDebugName::Symbol(Symbol::borrowed(b"std::fs::read")),
+                    location: None,
+                },
+            ],
+        };
+        let usages = ApiUsages {
+            pkg_id: pkg_id("foo"),
+            scope: PermissionScope::All,
+            api_name: ApiName::from("fs"),
+            usages: vec![usage.clone()],
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        };
+        let Fact::ApiUsage(fact) = Fact::api_usage(&usages, &usage) else {
+            panic!("Expected an API usage fact");
+        };
+        assert_eq!(
+            serde_json::to_value(&fact.provenance).unwrap(),
+            serde_json::json!({
+                "bin_path": "bin",
+                "from": "foo::read",
+                "to": "std::fs::read",
+                "reference": "call",
+                "outer_location": null,
+                "object_file": "libfoo.rlib",
+                "archive_member": "foo.o",
+                "section": ".text.read",
+                "relocation_offset": 8,
+                "compilation_unit": "src/lib.rs/@/foo.cgu.0",
+                "from_name_source": "debug_name",
+                "to_name_source": "symbol",
+                "package_candidates": ["foo 0.0.0"],
+                "inline_chain": [
+                    {
+                        "function": "foo::read",
+                        "location": {"filename": "/ws/foo/src/lib.rs", "line": 10, "column": null},
+                    },
+                    {"function": "std::fs::read", "location": null},
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,486 @@
+//! Determines which cargo features some source code is only compiled with, by looking at the
+//! `#[cfg(feature = "...")]` attributes on the items that contain it and on the `mod` declarations
+//! of the modules that contain it. We also find code that's only compiled for tests, i.e. that's
+//! under `#[cfg(test)]`. This works from the tokenised source, so it doesn't know about
+//! things like `#[path]` attributes or code produced by macros. It's only used to make suggestions,
+//! so that's OK.
+
+use crate::vfs::Vfs;
+use ra_ap_rustc_lexer::TokenKind;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Modules are rarely nested this deeply. The limit guards against loops, e.g. due to symlinks.
+const MAX_MODULE_DEPTH: usize = 32;
+
+/// What we found in each source file that we've looked at, so that each file is only read and
+/// parsed once, even though a file's gates affect every file in the modules that it declares.
+#[derive(Default)]
+pub(crate) struct FeatureGates {
+    /// `None` for files that we couldn't read.
+    files: Mutex<FxHashMap<PathBuf, Option<Arc<FileGates>>>>,
+}
+
+impl FeatureGates {
+    /// Returns the features that must all be enabled for the code at `line` of `path` to be
+    /// compiled.
+    pub(crate) fn required_features(
+        &self,
+        vfs: &dyn Vfs,
+        path: &Path,
+        line: u32,
+    ) -> BTreeSet<String> {
+        let mut features = BTreeSet::new();
+        self.for_each_enclosing_file(vfs, path, line, |gates, line| {
+            features.extend(gates.requirements_at(line).filter_map(
+                |requirement| match requirement {
+                    Requirement::Feature(feature) => Some(feature.clone()),
+                    Requirement::Test => None,
+                },
+            ));
+        });
+        features
+    }
+
+    /// Returns whether the code at `line` of `path` is only compiled for tests.
+    pub(crate) fn is_test_only(&self, vfs: &dyn Vfs, path: &Path, line: u32) -> bool {
+        let mut test_only = false;
+        self.for_each_enclosing_file(vfs, path, line, |gates, line| {
+            test_only |= gates
+                .requirements_at(line)
+                .any(|requirement| *requirement == Requirement::Test);
+        });
+        test_only
+    }
+
+    /// Calls `f` with what we found in `path` and in each file that declares a module containing
+    /// it, together with the line within that file.
+    fn for_each_enclosing_file(
+        &self,
+        vfs: &dyn Vfs,
+        path: &Path,
+        line: u32,
+        mut f: impl FnMut(&FileGates, u32),
+    ) {
+        let mut path = path.to_owned();
+        let mut line = line;
+        for _ in 0..MAX_MODULE_DEPTH {
+            let Some(gates) = self.file(vfs, &path) else {
+                break;
+            };
+            f(&gates, line);
+            let Some((parent, parent_line)) = self.parent_declaration(vfs, &path) else {
+                break;
+            };
+            path = parent;
+            line = parent_line;
+        }
+    }
+
+    /// Returns the file that declares the module whose source is in `path` and the line of the
+    /// declaration. Returns `None` for crate roots.
+    fn parent_declaration(&self, vfs: &dyn Vfs, path: &Path) -> Option<(PathBuf, u32)> {
+        let stem = path.file_stem()?.to_str()?;
+        let dir = path.parent()?;
+        let (name, parent_dir) = match stem {
+            "lib" | "main" => return None,
+            "mod" => (dir.file_name()?.to_str()?, dir.parent()?),
+            _ => (stem, dir),
+        };
+        let candidates = [
+            parent_dir.join("lib.rs"),
+            parent_dir.join("main.rs"),
+            parent_dir.join("mod.rs"),
+            parent_dir.with_extension("rs"),
+        ];
+        candidates.into_iter().find_map(|candidate| {
+            let line = self
+                .file(vfs, &candidate)?
+                .modules
+                .iter()
+                .find(|(module, _)| module == name)?
+                .1;
+            Some((candidate, line))
+        })
+    }
+
+    /// Returns what we found in `path`, reading it if we haven't already.
+    fn file(&self, vfs: &dyn Vfs, path: &Path) -> Option<Arc<FileGates>> {
+        let mut files = self.files.lock().unwrap();
+        files
+            .entry(path.to_owned())
+            .or_insert_with(|| {
+                let source = vfs.read_to_string(path).ok()?;
+                Some(Arc::new(FileGates::parse(&source)))
+            })
+            .clone()
+    }
+}
+
+/// Something that a cfg predicate requires in order to be true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Feature(String),
+    Test,
+}
+
+/// What we found in a single source file.
+#[derive(Default, Debug)]
+struct FileGates {
+    /// Requirements of inner attributes that apply to the whole file.
+    file_requirements: Vec<Requirement>,
+
+    /// Items that are only compiled with some features or only for tests.
+    gates: Vec<Gate>,
+
+    /// Declarations of modules in other files, e.g. `mod foo;`, with the line of each.
+    modules: Vec<(String, u32)>,
+}
+
+#[derive(Debug)]
+struct Gate {
+    requirements: Vec<Requirement>,
+    start_line: u32,
+    end_line: u32,
+}
+
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    line: u32,
+}
+
+impl FileGates {
+    fn parse(source: &str) -> Self {
+        let tokens = tokenize(source);
+        let mut result = FileGates::default();
+        let mut brace_depth = 0_usize;
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i].kind {
+                TokenKind::Pound => {
+                    let inner = tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Bang);
+                    let open = i + 1 + usize::from(inner);
+                    if tokens
+                        .get(open)
+                        .is_some_and(|t| t.kind == TokenKind::OpenBracket)
+                    {
+                        let close = matching_close(&tokens, open);
+                        let requirements = cfg_requirements(&tokens[open + 1..close]);
+                        if inner {
+                            // Inner attributes within a block only apply to that block, which we
+                            // don't bother with.
+                            if brace_depth == 0 {
+                                result.file_requirements.extend(requirements);
+                            }
+                        } else if !requirements.is_empty() {
+                            let end = item_end(&tokens, close + 1);
+                            result.gates.push(Gate {
+                                requirements,
+                                start_line: tokens[i].line,
+                                end_line: tokens[end].line,
+                            });
+                        }
+                        i = close + 1;
+                        continue;
+                    }
+                }
+                TokenKind::OpenBrace => brace_depth += 1,
+                TokenKind::CloseBrace => brace_depth = brace_depth.saturating_sub(1),
+                TokenKind::Ident if tokens[i].text == "mod" => {
+                    if let (Some(name), Some(semi)) = (tokens.get(i + 1), tokens.get(i + 2))
+                        && name.kind == TokenKind::Ident
+                        && semi.kind == TokenKind::Semi
+                    {
+                        result.modules.push((name.text.to_owned(), tokens[i].line));
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        result
+    }
+
+    /// Returns the requirements of the gates that contain `line`.
+    fn requirements_at(&self, line: u32) -> impl Iterator<Item = &Requirement> {
+        self.file_requirements.iter().chain(
+            self.gates
+                .iter()
+                .filter(move |gate| gate.start_line <= line && line <= gate.end_line)
+                .flat_map(|gate| &gate.requirements),
+        )
+    }
+}
+
+/// Returns the tokens of `source`, other than whitespace and comments.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut line = 1;
+    for token in ra_ap_rustc_lexer::tokenize(source, ra_ap_rustc_lexer::FrontmatterAllowed::No) {
+        let new_offset = offset + usize::try_from(token.len).unwrap();
+        let text = &source[offset..new_offset];
+        if !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            tokens.push(Token {
+                kind: token.kind,
+                text,
+                line,
+            });
+        }
+        line += text.matches('\n').count() as u32;
+        offset = new_offset;
+    }
+    tokens
+}
+
+fn is_open(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::OpenParen | TokenKind::OpenBracket | TokenKind::OpenBrace
+    )
+}
+
+fn is_close(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::CloseParen | TokenKind::CloseBracket | TokenKind::CloseBrace
+    )
+}
+
+/// Returns the index of the token that closes the bracket at `open`, or of the last token if it
+/// isn't closed.
+fn matching_close(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        if is_open(token.kind) {
+            depth += 1;
+        } else if is_close(token.kind) {
+            depth -= 1;
+            if depth == 0 {
+                return index;
+            }
+        }
+    }
+    tokens.len() - 1
+}
+
+/// Returns the index of the last token of the item, statement, field or match arm that starts at
+/// `start`.
+fn item_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0_i32;
+    // Commas between generic parameters don't end the item. We don't know for sure which `<` and
+    // `>` are brackets, but `->` and `=>` at least definitely aren't.
+    let mut angle_depth = 0_i32;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token.kind {
+            kind if is_open(kind) => depth += 1,
+            kind if is_close(kind) => {
+                depth -= 1;
+                if depth < 0 {
+                    // We reached the end of whatever contains the item.
+                    return index.saturating_sub(1).max(start.min(tokens.len() - 1));
+                }
+                if depth == 0 && kind == TokenKind::CloseBrace {
+                    return index;
+                }
+            }
+            TokenKind::Lt if depth == 0 => angle_depth += 1,
+            TokenKind::Gt if depth == 0 => {
+                let is_arrow =
+                    index > 0 && matches!(tokens[index - 1].kind, TokenKind::Minus | TokenKind::Eq);
+                if !is_arrow && angle_depth > 0 {
+                    angle_depth -= 1;
+                }
+            }
+            TokenKind::Semi if depth == 0 => return index,
+            TokenKind::Comma if depth == 0 && angle_depth == 0 => return index,
+            _ => {}
+        }
+    }
+    tokens.len() - 1
+}
+
+/// Returns what's required by the tokens within an attribute, if it's a `cfg` attribute.
+fn cfg_requirements(tokens: &[Token]) -> Vec<Requirement> {
+    match tokens {
+        [cfg, open, predicate @ .., _close]
+            if cfg.text == "cfg" && open.kind == TokenKind::OpenParen =>
+        {
+            predicate_requirements(predicate)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns what's required for a cfg predicate to be true. We only understand `feature = "..."`,
+/// `test` and `all(...)`, since other predicates don't require any particular feature.
+fn predicate_requirements(tokens: &[Token]) -> Vec<Requirement> {
+    match tokens {
+        [name, eq, value]
+            if name.text == "feature"
+                && eq.kind == TokenKind::Eq
+                && matches!(value.kind, TokenKind::Literal { .. }) =>
+        {
+            vec![Requirement::Feature(
+                value.text.trim_matches('"').to_owned(),
+            )]
+        }
+        [name] if name.text == "test" => vec![Requirement::Test],
+        [name, open, inner @ .., _close]
+            if name.text == "all" && open.kind == TokenKind::OpenParen =>
+        {
+            let mut requirements = Vec::new();
+            let mut depth = 0;
+            let mut start = 0;
+            for (index, token) in inner.iter().enumerate() {
+                if is_open(token.kind) {
+                    depth += 1;
+                } else if is_close(token.kind) {
+                    depth -= 1;
+                } else if token.kind == TokenKind::Comma && depth == 0 {
+                    requirements.extend(predicate_requirements(&inner[start..index]));
+                    start = index + 1;
+                }
+            }
+            requirements.extend(predicate_requirements(&inner[start..]));
+            requirements
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeatureGates;
+    use super::FileGates;
+    use super::Requirement;
+    use crate::vfs::testing::MemoryFs;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    fn features_at(source: &str, line: u32) -> Vec<String> {
+        let gates = FileGates::parse(source);
+        let features: BTreeSet<String> = gates
+            .requirements_at(line)
+            .filter_map(|requirement| match requirement {
+                Requirement::Feature(feature) => Some(feature.clone()),
+                Requirement::Test => None,
+            })
+            .collect();
+        features.into_iter().collect()
+    }
+
+    fn is_test_at(source: &str, line: u32) -> bool {
+        FileGates::parse(source)
+            .requirements_at(line)
+            .any(|requirement| *requirement == Requirement::Test)
+    }
+
+    #[test]
+    fn test_only_code() {
+        let source = indoc::indoc! {r#"
+            fn helper() {}
+
+            #[cfg(test)]
+            mod tests {
+                fn setup() {
+                    std::fs::write("x", "y");
+                }
+            }
+
+            #[cfg(all(test, unix))]
+            fn unix_helper() {}
+
+            #[cfg(any(test, feature = "testing"))]
+            pub fn fixture() {}
+        "#};
+        assert!(!is_test_at(source, 1));
+        assert!(is_test_at(source, 6));
+        assert!(is_test_at(source, 11));
+        assert!(!is_test_at(source, 14));
+        assert!(is_test_at("#![cfg(test)]\nfn foo() {}\n", 2));
+    }
+
+    #[test]
+    fn gated_items() {
+        let source = indoc::indoc! {r#"
+            #[cfg(feature = "net")]
+            #[inline]
+            pub fn connect<A, B>() -> Result<(), Error> {
+                open();
+            }
+
+            fn not_gated() {
+                #[cfg(all(feature = "fs", unix, feature = "std"))]
+                read();
+                match x {
+                    #[cfg(feature = "tls")]
+                    Kind::Tls => encrypt(),
+                    Kind::Plain => send(),
+                }
+                #[cfg(any(feature = "a", feature = "b"))]
+                an_or_b();
+            }
+
+            #[cfg(feature = "jobs")]
+            mod jobs;
+            mod other;
+        "#};
+        assert_eq!(features_at(source, 4), ["net"]);
+        assert_eq!(features_at(source, 6), Vec::<String>::new());
+        assert_eq!(features_at(source, 7), Vec::<String>::new());
+        assert_eq!(features_at(source, 9), ["fs", "std"]);
+        assert_eq!(features_at(source, 12), ["tls"]);
+        assert_eq!(features_at(source, 13), Vec::<String>::new());
+        assert_eq!(features_at(source, 16), Vec::<String>::new());
+        let gates = FileGates::parse(source);
+        assert_eq!(
+            gates.modules,
+            [("jobs".to_owned(), 20), ("other".to_owned(), 21)]
+        );
+        assert_eq!(features_at(source, 20), ["jobs"]);
+        assert_eq!(features_at(source, 21), Vec::<String>::new());
+
+        assert_eq!(
+            features_at("#![cfg(feature = \"x\")]\nfn foo() {}\n", 2),
+            ["x"]
+        );
+    }
+
+    #[test]
+    fn gated_modules() {
+        let mut fs = MemoryFs::default();
+        fs.add("/p/src/lib.rs", "#[cfg(feature = \"net\")]\nmod net;\n");
+        fs.add("/p/src/net.rs", "mod tls;\nfn connect() {}\n");
+        fs.add(
+            "/p/src/net/tls.rs",
+            "#![cfg(feature = \"tls\")]\nfn encrypt() {}\n",
+        );
+        let gates = FeatureGates::default();
+        let required = |path: &str, line| {
+            gates
+                .required_features(&fs, &Path::new("/p/src").join(path), line)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(required("net.rs", 2), ["net"]);
+        assert_eq!(required("net/tls.rs", 2), ["net", "tls"]);
+        assert_eq!(required("lib.rs", 1), ["net"]);
+        assert_eq!(required("missing.rs", 1), Vec::<String>::new());
+
+        fs.add("/p/src/lib.rs", "#[cfg(test)]\nmod net;\n");
+        // What we found in each file is cached.
+        assert!(!gates.is_test_only(&fs, Path::new("/p/src/net/tls.rs"), 2));
+        let gates = FeatureGates::default();
+        assert!(gates.is_test_only(&fs, Path::new("/p/src/net/tls.rs"), 2));
+        assert!(!gates.is_test_only(&fs, Path::new("/p/src/lib.rs"), 3));
+    }
+}
@@ -0,0 +1,631 @@
+//! Export of the references that we find in each binary as a graph, so that users can visualise how
+//! a function that uses a permission is reached, or post-process what we found. Nodes are symbols
+//! and APIs. There's an edge for each relocation in an object file that references one symbol from
+//! another and from each function that uses an API to that API.
+
+use crate::config::ApiName;
+use crate::crate_index::PackageId;
+use crate::location::SourceLocation;
+use crate::name_display::shorten;
+use crate::name_display::shorten_for_json;
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::ValueEnum;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct GraphOptions {
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    format: GraphFormat,
+
+    /// Only include functions from this crate.
+    #[arg(long = "crate", value_name = "NAME")]
+    crate_name: Option<String>,
+
+    /// Only include functions from which this API can be reached.
+    #[arg(long)]
+    api: Option<String>,
+
+    /// Write the graph to this file rather than to stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum GraphFormat {
+    /// GraphViz DOT. e.g. render with `dot -Tsvg`.
+    Dot,
+    /// JSON with full details of each node and edge, for consumption by other tools.
+    Json,
+}
+
+/// The graph of references within a single binary.
+#[derive(Debug)]
+pub(crate) struct ReferenceGraph {
+    bin_path: Arc<Path>,
+    nodes: Vec<GraphNode>,
+    edges: BTreeSet<GraphEdge>,
+    symbol_indexes: FxHashMap<u64, usize>,
+    api_indexes: FxHashMap<ApiName, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct GraphNode {
+    name: String,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Symbol {
+        /// The symbol as it appears in the binary, if it's different from the node's name.
+        symbol: Option<String>,
+        pkg_ids: Vec<PackageId>,
+        source_location: Option<SourceLocation>,
+    },
+    Api,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct GraphEdge {
+    from: usize,
+    to: usize,
+    kind: EdgeKind,
+    /// The address in the binary of the reference.
+    address: u64,
+    source_location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKind {
+    /// A reference via a relocation of kind `relocation`, e.g. `Relative`, if it was via a
+    /// relocation.
+    Reference { relocation: Option<String> },
+    /// A use of an API due to a reference to `target`.
+    ApiUsage { target: String },
+}
+
+/// Details of a symbol that we add to a graph.
+pub(crate) struct GraphSymbol {
+    pub(crate) name: String,
+    pub(crate) symbol: Option<String>,
+    pub(crate) pkg_ids: Vec<PackageId>,
+    pub(crate) source_location: Option<SourceLocation>,
+}
+
+impl GraphOptions {
+    /// Returns just the parts of `graph` that were asked for.
+    pub(crate) fn filter(&self, graph: ReferenceGraph) -> ReferenceGraph {
+        graph.filtered(self.crate_name.as_deref(), self.api.as_deref())
+    }
+}
+
+impl ReferenceGraph {
+    pub(crate) fn new(bin_path: Arc<Path>) -> Self {
+        Self {
+            bin_path,
+            nodes: Vec::new(),
+            edges: BTreeSet::new(),
+            symbol_indexes: FxHashMap::default(),
+            api_indexes: FxHashMap::default(),
+        }
+    }
+
+    /// Adds the symbol at `address`. References and API usages are only recorded for symbols that
+    /// have been added.
+    pub(crate) fn add_symbol(&mut self, address: u64, info: GraphSymbol) {
+        let nodes = &mut self.nodes;
+        self.symbol_indexes.entry(address).or_insert_with(|| {
+            nodes.push(GraphNode {
+                name: info.name,
+                kind: NodeKind::Symbol {
+                    symbol: info.symbol,
+                    pkg_ids: info.pkg_ids,
+                    source_location: info.source_location,
+                },
+            });
+            nodes.len() - 1
+        });
+    }
+
+    /// Records a reference from the symbol at `from` to the symbol at `to`, via a relocation at
+    /// `address`.
+    pub(crate) fn add_reference(
+        &mut self,
+        from: u64,
+        to: u64,
+        relocation: Option<String>,
+        address: u64,
+        source_location: Option<SourceLocation>,
+    ) {
+        if let (Some(&from), Some(&to)) =
+            (self.symbol_indexes.get(&from), self.symbol_indexes.get(&to))
+        {
+            self.edges.insert(GraphEdge {
+                from,
+                to,
+                kind: EdgeKind::Reference { relocation },
+                address,
+                source_location,
+            });
+        }
+    }
+
+    /// Records that the function at `from` uses `api` by referencing `target` at `address`.
+    pub(crate) fn add_api_usage(
+        &mut self,
+        from: u64,
+        api: &ApiName,
+        target: String,
+        address: u64,
+        source_location: SourceLocation,
+    ) {
+        let Some(&from) = self.symbol_indexes.get(&from) else {
+            return;
+        };
+        let nodes = &mut self.nodes;
+        let to = *self.api_indexes.entry(api.clone()).or_insert_with(|| {
+            nodes.push(GraphNode {
+                name: api.to_string(),
+                kind: NodeKind::Api,
+            });
+            nodes.len() - 1
+        });
+        self.edges.insert(GraphEdge {
+            from,
+            to,
+            kind: EdgeKind::ApiUsage { target },
+            address,
+            source_location: Some(source_location),
+        });
+    }
+
+    /// Returns a graph containing only symbols from `crate_name` and only those from which `api`
+    /// can be reached. APIs are dropped unless they're used by a function that we keep.
+    fn filtered(self, crate_name: Option<&str>, api: Option<&str>) -> Self {
+        let in_crate: Vec<bool> = self
+            .nodes
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::Symbol { pkg_ids, .. } => crate_name
+                    .is_none_or(|name| pkg_ids.iter().any(|pkg_id| &*pkg_id.pkg_name() == name)),
+                NodeKind::Api => api.is_none_or(|api| node.name == api),
+            })
+            .collect();
+        let mut keep = vec![false; self.nodes.len()];
+        if api.is_some() {
+            let mut callers: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+            for edge in &self.edges {
+                callers.entry(edge.to).or_default().push(edge.from);
+            }
+            let mut pending: Vec<usize> = self
+                .api_indexes
+                .values()
+                .filter(|index| in_crate[**index])
+                .flat_map(|index| callers.get(index).into_iter().flatten().copied())
+                .collect();
+            while let Some(index) = pending.pop() {
+                if in_crate[index] && !keep[index] {
+                    keep[index] = true;
+                    pending.extend(callers.get(&index).into_iter().flatten());
+                }
+            }
+        } else {
+            for (index, node) in self.nodes.iter().enumerate() {
+                keep[index] = in_crate[index] && matches!(node.kind, NodeKind::Symbol { .. });
+            }
+        }
+        for edge in &self.edges {
+            if keep[edge.from]
+                && in_crate[edge.to]
+                && matches!(self.nodes[edge.to].kind, NodeKind::Api)
+            {
+                keep[edge.to] = true;
+            }
+        }
+
+        let mut new_indexes = vec![None; self.nodes.len()];
+        let mut graph = ReferenceGraph::new(self.bin_path);
+        for (index, node) in self.nodes.into_iter().enumerate() {
+            if keep[index] {
+                new_indexes[index] = Some(graph.nodes.len());
+                graph.nodes.push(node);
+            }
+        }
+        for (address, index) in self.symbol_indexes {
+            if let Some(new_index) = new_indexes[index] {
+                graph.symbol_indexes.insert(address, new_index);
+            }
+        }
+        for (api, index) in self.api_indexes {
+            if let Some(new_index) = new_indexes[index] {
+                graph.api_indexes.insert(api, new_index);
+            }
+        }
+        graph.edges = self
+            .edges
+            .into_iter()
+            .filter_map(|edge| {
+                Some(GraphEdge {
+                    from: new_indexes[edge.from]?,
+                    to: new_indexes[edge.to]?,
+                    ..edge
+                })
+            })
+            .collect();
+        graph
+    }
+}
+
+/// Writes `graphs` in the requested format to the requested output.
+pub(crate) fn write(graphs: &[ReferenceGraph], options: &GraphOptions) -> Result<()> {
+    let output = match options.format {
+        GraphFormat::Dot => to_dot(graphs),
+        GraphFormat::Json => to_json(graphs)?,
+    };
+    if let Some(path) = &options.output {
+        std::fs::write(path, output)
+            .with_context(|| format!("Failed to write `{}`", path.display()))?;
+    } else {
+        print!("{output}");
+    }
+    Ok(())
+}
+
+/// Returns `graphs` in GraphViz DOT format, with each binary in a separate cluster. Binaries with
+/// nothing left after filtering are omitted.
+fn to_dot(graphs: &[ReferenceGraph]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph cackle {\n");
+    out.push_str("  node [shape=box];\n");
+    let non_empty = graphs.iter().filter(|graph| !graph.nodes.is_empty());
+    for (graph_index, graph) in non_empty.enumerate() {
+        let _ = writeln!(out, "  subgraph \"cluster_{graph_index}\" {{");
+        let _ = writeln!(
+            out,
+            "    label={};",
+            dot_string(&graph.bin_path.display().to_string())
+        );
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let attributes = match node.kind {
+                NodeKind::Symbol { .. } => "",
+                NodeKind::Api => ", shape=ellipse, style=filled, fillcolor=lightpink",
+            };
+            let _ = writeln!(
+                out,
+                "    \"{graph_index}:{index}\" [label={}{attributes}];",
+                dot_string(&shorten(&node.name))
+            );
+        }
+        // DOT is for visualisation, so we only show one edge for each pair of nodes, even if
+        // there are several references between them.
+        let edges: BTreeSet<(usize, usize, Option<&str>)> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.from != edge.to)
+            .map(|edge| {
+                let label = match &edge.kind {
+                    EdgeKind::Reference { .. } => None,
+                    EdgeKind::ApiUsage { target } => Some(target.as_str()),
+                };
+                (edge.from, edge.to, label)
+            })
+            .collect();
+        for (from, to, label) in edges {
+            let _ = write!(
+                out,
+                "    \"{graph_index}:{from}\" -> \"{graph_index}:{to}\""
+            );
+            if let Some(label) = label {
+                let _ = write!(out, " [label={}]", dot_string(label));
+            }
+            out.push_str(";\n");
+        }
+        out.push_str("  }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    binaries: Vec<JsonBinary<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonBinary<'a> {
+    path: &'a Path,
+    nodes: Vec<JsonNode<'a>>,
+    edges: Vec<JsonEdge<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    id: usize,
+    kind: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    crates: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_location: Option<&'a SourceLocation>,
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    from: usize,
+    to: usize,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relocation: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_location: Option<&'a SourceLocation>,
+}
+
+/// Returns `graphs` as JSON. Like with DOT, binaries with nothing left after filtering are
+/// omitted. Node IDs are indexes into the binary's list of nodes.
+fn to_json(graphs: &[ReferenceGraph]) -> Result<String> {
+    let binaries = graphs
+        .iter()
+        .filter(|graph| !graph.nodes.is_empty())
+        .map(|graph| JsonBinary {
+            path: &graph.bin_path,
+            nodes: graph
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(id, node)| match &node.kind {
+                    NodeKind::Symbol {
+                        symbol,
+                        pkg_ids,
+                        source_location,
+                    } => JsonNode {
+                        id,
+                        kind: "symbol",
+                        name: shorten_for_json(&node.name),
+                        symbol: symbol.as_deref(),
+                        crates: pkg_ids.iter().map(|pkg_id| pkg_id.to_string()).collect(),
+                        source_location: source_location.as_ref(),
+                    },
+                    NodeKind::Api => JsonNode {
+                        id,
+                        kind: "api",
+                        name: node.name.clone(),
+                        symbol: None,
+                        crates: Vec::new(),
+                        source_location: None,
+                    },
+                })
+                .collect(),
+            edges: graph
+                .edges
+                .iter()
+                .map(|edge| {
+                    let (kind, relocation, target) = match &edge.kind {
+                        EdgeKind::Reference { relocation } => {
+                            ("reference", relocation.as_deref(), None)
+                        }
+                        EdgeKind::ApiUsage { target } => ("api_usage", None, Some(target.as_str())),
+                    };
+                    JsonEdge {
+                        from: edge.from,
+                        to: edge.to,
+                        kind,
+                        relocation,
+                        target,
+                        address: format!("0x{:x}", edge.address),
+                        source_location: edge.source_location.as_ref(),
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&JsonGraph { binaries })? + "\n")
+}
+
+/// Returns `value` as a quoted DOT string.
+fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GraphSymbol;
+    use super::ReferenceGraph;
+    use crate::config::ApiName;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn example_graph() -> ReferenceGraph {
+        // foo::main -> foo::run -> bar::read -> fs and foo::main -> foo::log -> bar::write -> fs.
+        // foo::run also uses net directly.
+        let mut graph = ReferenceGraph::new(Arc::from(Path::new("target/debug/foo")));
+        for (address, name, pkg) in [
+            (1, "foo::main", "foo"),
+            (2, "foo::run", "foo"),
+            (3, "bar::read", "bar"),
+            (4, "foo::log", "foo"),
+            (5, "bar::write", "bar"),
+        ] {
+            graph.add_symbol(
+                address,
+                GraphSymbol {
+                    name: name.to_owned(),
+                    symbol: (address == 1).then(|| "_ZN3foo4main17h0123456789abcdefE".to_owned()),
+                    pkg_ids: vec![pkg_id(pkg)],
+                    source_location: (address == 1)
+                        .then(|| SourceLocation::new(Path::new("src/main.rs"), 1, None)),
+                },
+            );
+        }
+        for (from, to, address) in [
+            (1, 2, 0x10),
+            (1, 2, 0x18),
+            (2, 3, 0x20),
+            (1, 4, 0x14),
+            (4, 5, 0x40),
+            (2, 2, 0x24),
+            (7, 1, 0x70),
+        ] {
+            graph.add_reference(
+                from,
+                to,
+                Some("PltRelative".to_owned()),
+                address,
+                (address == 0x10)
+                    .then(|| SourceLocation::new(Path::new("src/main.rs"), 2, Some(5))),
+            );
+        }
+        for (from, api, target, address) in [
+            (3, "fs", "std::fs::read", 0x30),
+            (5, "fs", "std::fs::write", 0x50),
+            (2, "net", "std::net::TcpStream", 0x28),
+        ] {
+            graph.add_api_usage(
+                from,
+                &ApiName::from(api),
+                target.to_owned(),
+                address,
+                SourceLocation::new(Path::new("src/lib.rs"), 10, None),
+            );
+        }
+        graph
+    }
+
+    fn node_names(graph: &ReferenceGraph) -> Vec<&str> {
+        graph.nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn filter_graph() {
+        let graph = example_graph();
+        assert_eq!(graph.nodes.len(), 7);
+        // References from unknown symbols are ignored.
+        assert_eq!(graph.edges.len(), 9);
+
+        let foo = example_graph().filtered(Some("foo"), None);
+        assert_eq!(
+            node_names(&foo),
+            ["foo::main", "foo::run", "foo::log", "net"]
+        );
+        assert_eq!(foo.edges.len(), 5);
+
+        let fs = example_graph().filtered(None, Some("fs"));
+        assert_eq!(
+            node_names(&fs),
+            [
+                "foo::main",
+                "foo::run",
+                "bar::read",
+                "foo::log",
+                "bar::write",
+                "fs"
+            ]
+        );
+        assert_eq!(fs.edges.len(), 8);
+
+        let net = example_graph().filtered(None, Some("net"));
+        assert_eq!(node_names(&net), ["foo::main", "foo::run", "net"]);
+
+        // None of foo's functions reach fs without going via bar.
+        let foo_fs = example_graph().filtered(Some("foo"), Some("fs"));
+        assert!(foo_fs.nodes.is_empty());
+        let bar_fs = example_graph().filtered(Some("bar"), Some("fs"));
+        assert_eq!(node_names(&bar_fs), ["bar::read", "bar::write", "fs"]);
+    }
+
+    #[test]
+    fn dot_output() {
+        let graph = example_graph().filtered(None, Some("net"));
+        let empty = example_graph().filtered(Some("foo"), Some("fs"));
+        assert_eq!(
+            super::to_dot(&[empty, graph]),
+            r#"digraph cackle {
+  node [shape=box];
+  subgraph "cluster_0" {
+    label="target/debug/foo";
+    "0:0" [label="foo::main"];
+    "0:1" [label="foo::run"];
+    "0:2" [label="net", shape=ellipse, style=filled, fillcolor=lightpink];
+    "0:0" -> "0:1";
+    "0:1" -> "0:2" [label="std::net::TcpStream"];
+  }
+}
+"#
+        );
+        assert_eq!(super::dot_string(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+
+    #[test]
+    fn json_output() {
+        let graph = example_graph().filtered(Some("foo"), Some("net"));
+        let json: serde_json::Value =
+            serde_json::from_str(&super::to_json(&[graph]).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "binaries": [{
+                    "path": "target/debug/foo",
+                    "nodes": [
+                        {
+                            "id": 0,
+                            "kind": "symbol",
+                            "name": "foo::main",
+                            "symbol": "_ZN3foo4main17h0123456789abcdefE",
+                            "crates": ["foo"],
+                            "source_location": {"filename": "src/main.rs", "line": 1, "column": null},
+                        },
+                        {"id": 1, "kind": "symbol", "name": "foo::run", "crates": ["foo"]},
+                        {"id": 2, "kind": "api", "name": "net"},
+                    ],
+                    "edges": [
+                        {
+                            "from": 0,
+                            "to": 1,
+                            "kind": "reference",
+                            "relocation": "PltRelative",
+                            "address": "0x10",
+                            "source_location": {"filename": "src/main.rs", "line": 2, "column": 5},
+                        },
+                        {
+                            "from": 0,
+                            "to": 1,
+                            "kind": "reference",
+                            "relocation": "PltRelative",
+                            "address": "0x18",
+                        },
+                        {
+                            "from": 1,
+                            "to": 1,
+                            "kind": "reference",
+                            "relocation": "PltRelative",
+                            "address": "0x24",
+                        },
+                        {
+                            "from": 1,
+                            "to": 2,
+                            "kind": "api_usage",
+                            "target": "std::net::TcpStream",
+                            "address": "0x28",
+                            "source_location": {"filename": "src/lib.rs", "line": 10, "column": null},
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Recognition of code and references that come from sanitizer and fuzzing instrumentation rather
+//! than from packages. When a binary is built with a sanitizer or for fuzzing, the compiler inserts
+//! calls to runtime functions like `__asan_report_load8` or `__sanitizer_cov_trace_pc_guard` into
+//! every function and links in a runtime that intercepts and calls lots of libc functions. Left
+//! alone, these would produce a large number of API usages that have nothing to do with what the
+//! packages themselves do. With `common.ignore_instrumentation`, we ignore references to and from
+//! instrumentation runtimes, so that the same binaries that get fuzzed can be checked.
+
+use crate::demangle::UNMANGLED_NAMESPACE;
+use crate::names::SymbolAndName;
+use std::path::Component;
+use std::path::Path;
+
+/// Prefixes of the names of C functions and variables belonging to sanitizer and fuzzer runtimes.
+const PREFIXES: &[&str] = &[
+    "__asan_",
+    "__hwasan_",
+    "__msan_",
+    "__tsan_",
+    "__lsan_",
+    "__dfsan_",
+    "__ubsan_",
+    "__sanitizer_",
+    "__sancov_",
+    "__interceptor_",
+    "___interceptor_",
+    "__afl_",
+    "LLVMFuzzer",
+];
+
+/// C++ namespaces used by sanitizer and fuzzer runtimes.
+const NAMESPACES: &[&str] = &[
+    "__asan",
+    "__hwasan",
+    "__msan",
+    "__tsan",
+    "__lsan",
+    "__dfsan",
+    "__ubsan",
+    "__sanitizer",
+    "__interception",
+    "fuzzer",
+];
+
+/// Returns whether `names` is the name of something that belongs to a sanitizer or fuzzer runtime.
+pub(crate) fn is_instrumentation(names: &SymbolAndName) -> bool {
+    if let Some(symbol) = &names.symbol
+        && let Ok(mut it) = symbol.names()
+        && let Ok(Some((parts, _))) = it.next_name()
+    {
+        return is_instrumentation_name(parts);
+    }
+    if let Some(debug_name) = &names.debug_name
+        && let Ok(Some((parts, _))) = debug_name.names_iterator().next_name()
+    {
+        return is_instrumentation_name(parts);
+    }
+    false
+}
+
+fn is_instrumentation_name<'a>(mut parts: impl Iterator<Item = &'a str>) -> bool {
+    let mut first = parts.next();
+    if first == Some(UNMANGLED_NAMESPACE) {
+        first = parts.next();
+    }
+    first.is_some_and(|part| {
+        NAMESPACES.contains(&part) || PREFIXES.iter().any(|prefix| part.starts_with(prefix))
+    })
+}
+
+/// Returns whether `source_path` is part of the sources of LLVM's compiler-rt, which is where the
+/// sanitizer runtimes that come with the compiler are built from.
+pub(crate) fn is_runtime_source(source_path: &Path) -> bool {
+    let mut components = source_path.components();
+    while let Some(component) = components.next() {
+        if component == Component::Normal("compiler-rt".as_ref()) {
+            return components.next() == Some(Component::Normal("lib".as_ref()));
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_instrumentation;
+    use super::is_runtime_source;
+    use crate::names::DebugName;
+    use crate::names::Namespace;
+    use crate::names::SymbolAndName;
+    use crate::symbol::Symbol;
+    use std::path::Path;
+
+    fn symbol(name: &str) -> SymbolAndName<'_> {
+        SymbolAndName {
+            symbol: Some(Symbol::borrowed(name.as_bytes())),
+            debug_name: None,
+        }
+    }
+
+    #[test]
+    fn instrumentation_names() {
+        assert!(is_instrumentation(&symbol("__asan_report_load8")));
+        assert!(is_instrumentation(&symbol(
+            "__sanitizer_cov_trace_pc_guard"
+        )));
+        assert!(is_instrumentation(&symbol("LLVMFuzzerTestOneInput")));
+        // fuzzer::Fuzzer::RunOne(unsigned char const*, unsigned long, ...)
+        assert!(is_instrumentation(&symbol(
+            "_ZN6fuzzer6Fuzzer6RunOneEPKhmbPNS_9InputInfoEbPb"
+        )));
+        assert!(is_instrumentation(&SymbolAndName {
+            symbol: None,
+            debug_name: Some(DebugName::new(
+                Namespace::top_level("__sanitizer"),
+                "internal_memcpy"
+            )),
+        }));
+
+        assert!(!is_instrumentation(&symbol("open")));
+        assert!(!is_instrumentation(&symbol(
+            "_ZN3std2fs4read17h0123456789abcdefE"
+        )));
+        assert!(!is_instrumentation(&SymbolAndName::default()));
+    }
+
+    #[test]
+    fn runtime_sources() {
+        assert!(is_runtime_source(Path::new(
+            "/checkout/src/llvm-project/compiler-rt/lib/asan/asan_rtl.cpp"
+        )));
+        assert!(!is_runtime_source(Path::new("/ws/compiler-rt/src/lib.rs")));
+        assert!(!is_runtime_source(Path::new("/ws/src/lib.rs")));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::crate_index::CrateSel;
 use anyhow::Result;
+use anyhow::anyhow;
 use anyhow::bail;
 use serde::Deserialize;
 use serde::Serialize;
@@ -32,6 +33,22 @@ impl LinkInfo {
         })
     }
 
+    /// Returns information for analysing a staticlib. Rustc produces staticlibs without invoking
+    /// the linker, so we link the archive into a shared object ourselves, purely so that we have a
+    /// binary to analyse. `output_file` is that shared object.
+    pub(crate) fn for_staticlib(
+        crate_sel: CrateSel,
+        archive: PathBuf,
+        output_file: Arc<Path>,
+    ) -> Self {
+        LinkInfo {
+            crate_sel,
+            object_paths: vec![archive],
+            output_file,
+            is_shared: true,
+        }
+    }
+
     /// Filters `object_paths` to just those under `dir`.
     pub(crate) fn object_paths_under(&self, dir: &Path) -> Vec<PathBuf> {
         self.object_paths
@@ -41,10 +58,71 @@ impl LinkInfo {
             .collect()
     }
 
-    /// Returns whether the output of the linker is an executable (not a shared object).
+    /// Returns whether the output of the linker is an executable that we can run (not a shared
+    /// object or a wasm module).
     pub(crate) fn is_executable(&self) -> bool {
-        !self.is_shared
+        !self.is_shared && self.output_file.extension().is_none_or(|ext| ext != "wasm")
+    }
+
+    /// Returns whether the output of the linker is a binary that cargo built for an artifact
+    /// dependency (`-Z bindeps`), i.e. for use by another package rather than for the user to run.
+    pub(crate) fn is_artifact_dependency(&self) -> bool {
+        crate::crate_index::artifact_deps::is_artifact_path(&self.output_file)
+    }
+}
+
+/// Crate types for which rustc invokes the linker.
+const LINKED_CRATE_TYPES: &[&str] = &["bin", "cdylib", "dylib", "proc-macro"];
+
+/// Returns the path of the staticlib that rustc will produce given the supplied arguments. Returns
+/// `None` if rustc isn't producing a staticlib, or if it's also producing something that it'll link,
+/// since we'll analyse that instead.
+pub(crate) fn staticlib_path_from_rustc_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<PathBuf>> {
+    let mut crate_name = None;
+    let mut extra = String::new();
+    let mut out_dir = None;
+    let mut crate_types = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "-C" {
+            let Some(arg) = args.next() else {
+                bail!("Missing argument to -C");
+            };
+            if let Some(rest) = arg.strip_prefix("extra-filename=") {
+                extra = rest.to_owned();
+            }
+        } else if arg == "--out-dir" {
+            let Some(arg) = args.next() else {
+                bail!("Missing argument to --out-dir");
+            };
+            out_dir = Some(arg);
+        } else if arg == "--crate-name" {
+            let Some(arg) = args.next() else {
+                bail!("Missing argument to --crate-name");
+            };
+            crate_name = Some(arg);
+        } else if arg == "--crate-type" {
+            let Some(arg) = args.next() else {
+                bail!("Missing argument to --crate-type");
+            };
+            crate_types.extend(arg.split(',').map(str::to_owned));
+        } else if arg == "--test" {
+            crate_types.push("bin".to_owned());
+        }
+    }
+    if !crate_types.iter().any(|t| t == "staticlib")
+        || crate_types
+            .iter()
+            .any(|t| LINKED_CRATE_TYPES.contains(&t.as_str()))
+    {
+        return Ok(None);
     }
+    let crate_name = crate_name.ok_or_else(|| anyhow!("Missing --crate-name"))?;
+    let out_dir = out_dir.ok_or_else(|| anyhow!("Missing --out-dir"))?;
+    Ok(Some(
+        Path::new(&out_dir).join(format!("lib{crate_name}{extra}.a")),
+    ))
 }
 
 fn get_output_file() -> Result<Arc<Path>> {
@@ -69,3 +147,59 @@ fn has_supported_extension(path: &Path) -> bool {
         .and_then(|ext| ext.to_str())
         .is_some_and(|ext| EXTENSIONS.contains(&ext))
 }
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::LinkInfo;
+    use crate::crate_index::CrateSel;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    pub(crate) fn link_info(crate_sel: CrateSel, output_file: &str) -> LinkInfo {
+        LinkInfo {
+            crate_sel,
+            object_paths: Vec::new(),
+            output_file: Arc::from(Path::new(output_file)),
+            is_shared: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::staticlib_path_from_rustc_args;
+    use std::path::PathBuf;
+
+    fn staticlib_path(args: &[&str]) -> Option<PathBuf> {
+        staticlib_path_from_rustc_args(args.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn staticlib_paths() {
+        let args = [
+            "--crate-name",
+            "foo",
+            "--crate-type",
+            "staticlib",
+            "--crate-type",
+            "rlib",
+            "-C",
+            "extra-filename=-0188200cb614ae3d",
+            "--out-dir",
+            "/t/debug/deps",
+        ];
+        assert_eq!(
+            staticlib_path(&args),
+            Some(PathBuf::from("/t/debug/deps/libfoo-0188200cb614ae3d.a"))
+        );
+        // If rustc is also invoking the linker, then we'll check the linker's output instead.
+        assert_eq!(
+            staticlib_path(&[&args[..], &["--crate-type", "cdylib"]].concat()),
+            None
+        );
+        assert_eq!(
+            staticlib_path(&["--crate-name", "foo", "--crate-type", "lib"]),
+            None
+        );
+    }
+}
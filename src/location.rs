@@ -6,6 +6,7 @@ use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub(crate) struct SourceLocation {
+    #[serde(with = "crate::path_serde")]
     filename: Arc<Path>,
     line: u32,
     column: Option<u32>,
@@ -7,27 +7,56 @@
 #![allow(clippy::assigning_clones)]
 #![allow(clippy::needless_borrows_for_generic_args)]
 
+mod alloc_audit;
+mod artifacts;
+mod async_runtime;
+mod binary_files;
 mod build_script_checker;
+mod build_settings;
 mod checker;
 mod colour;
+mod compare;
 mod config;
 mod config_editor;
 mod config_validation;
 mod cowarc;
 mod crate_index;
 mod demangle;
+mod deny_list;
 mod deps;
+mod determinism;
+mod disputes;
 pub(crate) mod events;
+mod explain;
+mod facts;
+mod feature_gates;
 pub(crate) mod fs;
+mod graph;
+mod instrumentation;
 pub(crate) mod link_info;
 pub(crate) mod location;
 mod logging;
+mod migration;
+mod name_display;
 mod names;
+mod native_libs;
 mod outcome;
+mod panic_audit;
+mod path_serde;
+mod plugins;
+mod policy;
+mod prebuilt;
 pub(crate) mod problem;
 pub(crate) mod problem_store;
 mod proxy;
+mod report;
+mod resources;
+mod review;
+mod rustc_diagnostics;
+mod rustc_version;
+mod rustdoc_exports;
 mod sandbox;
+mod staleness;
 mod summary;
 pub(crate) mod symbol;
 mod symbol_graph;
@@ -35,8 +64,11 @@ mod timing;
 mod tmpdir;
 mod ui;
 mod unsafe_checker;
+mod usage_pins;
+mod vfs;
 
 use crate::proxy::subprocess::PROXY_BIN_ARG;
+use crate::resources::ResourceManager;
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
@@ -44,17 +76,28 @@ use anyhow::bail;
 use checker::Checker;
 use clap::Parser;
 use clap::Subcommand;
+use compare::CompareOptions;
+use config::ReportMode;
 use crate_index::CrateIndex;
+use crate_index::CrateSel;
+use disputes::DisputedOptions;
 use events::AppEvent;
+use explain::ExplainSymbolOptions;
+use graph::GraphOptions;
 use log::info;
+use migration::BaselineOptions;
 use outcome::ExitCode;
 use outcome::Outcome;
 use problem::Problem;
+use problem::StaleArtifacts;
 use problem_store::ProblemStoreRef;
 use proxy::CargoOutputWaiter;
 use proxy::cargo::CargoOptions;
 use proxy::cargo::profile_name;
 use proxy::rpc::Request;
+use review::ReviewOptions;
+use rustc_version::RustcVersion;
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -117,11 +160,25 @@ struct Args {
     #[arg(long)]
     profile: Option<String>,
 
+    /// The number of jobs - rustc invocations, build scripts and scanner threads - to run at once.
+    /// Overrides limits.jobs in config.
+    #[arg(long, short)]
+    jobs: Option<NonZeroUsize>,
+
     /// Features to pass to cargo. Overrides common.features in config.
     #[arg(long)]
     features: Option<String>,
 
-    /// Print how long various things take to run.
+    /// Analyse test and bench binaries, even if common.build_flags doesn't select them.
+    #[arg(long)]
+    include_tests: bool,
+
+    /// Which API usages to report when a location references several symbols belonging to an API.
+    /// Overrides common.report in config.
+    #[arg(long)]
+    report: Option<ReportMode>,
+
+    /// Print how long various things take to run. Timing is only collected when this is set.
     #[arg(long)]
     print_timing: bool,
 
@@ -147,6 +204,11 @@ struct Args {
     #[arg(long, hide = true)]
     replay_requests: bool,
 
+    /// Instead of running `cargo build`, analyse the artifacts in a bundle written by the
+    /// `pack-artifacts` subcommand, e.g. in an earlier CI job.
+    #[arg(long)]
+    artifacts: Option<PathBuf>,
+
     /// Temporary directory for Cackle to use. This is intended for testing purposes.
     #[arg(long, hide = true)]
     tmpdir: Option<PathBuf>,
@@ -165,10 +227,56 @@ struct Args {
     #[arg(long)]
     auto_accept_fixes: bool,
 
+    /// Write facts about what was found (API usages, unsafe, granted permissions) to this file as a
+    /// stream of JSON lines, for consumption by external policy engines.
+    #[arg(long)]
+    facts_output: Option<PathBuf>,
+
+    /// A Rego policy to evaluate against the facts gathered during analysis. Requires `opa`. The
+    /// policy should be in package `cackle` and add a message to `deny` for each violation.
+    #[arg(long)]
+    policy: Option<PathBuf>,
+
+    /// A checker plugin to run once analysis is complete. The plugin is given the facts gathered
+    /// during analysis on stdin and should write a line of JSON to stdout for each problem that it
+    /// finds. Can be given more than once.
+    #[arg(long)]
+    plugin: Vec<PathBuf>,
+
+    /// Also evaluate what's found in binaries against this configuration. Use with --compare.
+    #[arg(long, requires = "compare")]
+    with_config: Option<PathBuf>,
+
+    /// Once finished, print how the problems produced by the configuration given to --with-config
+    /// differ from those produced by the main configuration.
+    #[arg(long, requires = "with_config")]
+    compare: bool,
+
+    /// Write a report of outstanding problems and granted permissions to this file as JSON. Reports
+    /// from different runs can be compared with the `compare` subcommand.
+    #[arg(long)]
+    report_output: Option<PathBuf>,
+
+    /// If the debug info for a binary has been stripped and can't be found locally, try to fetch it
+    /// by build ID from the debuginfod servers listed in DEBUGINFOD_URLS. Requires `curl`.
+    #[arg(long)]
+    debuginfod: bool,
+
     /// Disable backtraces (may reduce peak memory consumption).
     #[arg(long)]
     no_backtrace: bool,
 
+    /// Don't use the cache of what was found in each object file by previous runs. The cache is
+    /// stored in the target directory.
+    #[arg(long)]
+    no_scan_cache: bool,
+
+    /// Experimental. Report disallowed API usages in workspace members as compiler warnings during
+    /// the build, so that they appear in cargo's output. They're still reported as problems once
+    /// the build completes.
+    #[arg(long)]
+    rustc_diagnostics: bool,
+
     // We may at some point allow this to be a short flag, but should probably wait a few releases.
     // -p was previously accepted for --path.
     /// Packages to build and analyse.
@@ -190,10 +298,92 @@ enum Command {
     /// Run `cargo run`, analysing whatever gets built.
     Run(CargoOptions),
 
+    /// Compare two reports written by --report-output.
+    Compare(CompareOptions),
+
+    /// Build and analyse, then write out the graph of references between functions and from
+    /// functions to APIs.
+    Graph(GraphOptions),
+
+    /// Build and analyse, then print what we know about a symbol, such as which APIs its names
+    /// match and which package it's attributed to.
+    ExplainSymbol(ExplainSymbolOptions),
+
+    /// Work with problems that have been disputed from the UI.
+    Disputed(DisputedOptions),
+
+    /// Record the problems that exist now, so that only new problems fail the check. See
+    /// `migration_mode` in the config.
+    Baseline(BaselineOptions),
+
+    /// Find permissions that are due to be reviewed again.
+    Review(ReviewOptions),
+
+    /// Build, but instead of analysing the binaries, write them and whatever else is needed to
+    /// analyse them to a bundle. The bundle can then be analysed elsewhere with `--artifacts`.
+    PackArtifacts(PackArtifactsOptions),
+
     #[command(hide = true, name = PROXY_BIN_ARG)]
     ProxyBin(ProxyBinOptions),
 }
 
+impl Args {
+    /// Returns whether we're building and analysing the same things as when no subcommand is given,
+    /// as opposed to, say, just whatever `cargo test` builds.
+    fn is_default_build(&self) -> bool {
+        matches!(
+            self.command,
+            None | Some(
+                Command::Graph(_)
+                    | Command::ExplainSymbol(_)
+                    | Command::PackArtifacts(_)
+                    | Command::Baseline(_)
+            )
+        )
+    }
+
+    /// Returns whether we're building in order to write a bundle of artifacts, rather than to
+    /// analyse them.
+    fn is_packing_artifacts(&self) -> bool {
+        matches!(self.command, Some(Command::PackArtifacts(_)))
+    }
+
+    /// Returns whether facts will be written out or evaluated against a policy, in which case it's
+    /// worth collecting extra details about each API usage.
+    fn consumes_facts(&self) -> bool {
+        self.facts_output.is_some() || self.policy.is_some() || !self.plugin.is_empty()
+    }
+
+    /// Returns the options for saving a baseline, if that's what we're doing.
+    fn baseline_options(&self) -> Option<&BaselineOptions> {
+        match &self.command {
+            Some(Command::Baseline(options)) => Some(options),
+            _ => None,
+        }
+    }
+
+    fn graph_options(&self) -> Option<&GraphOptions> {
+        match &self.command {
+            Some(Command::Graph(options)) => Some(options),
+            _ => None,
+        }
+    }
+
+    fn explain_symbol_options(&self) -> Option<&ExplainSymbolOptions> {
+        match &self.command {
+            Some(Command::ExplainSymbol(options)) => Some(options),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct PackArtifactsOptions {
+    /// Where to write the bundle, e.g. `artifacts.tar.zst`.
+    #[arg(long, short)]
+    output: PathBuf,
+}
+
 #[derive(Parser, Debug, Clone)]
 pub(crate) struct ProxyBinOptions {
     #[arg(allow_hyphen_values = true)]
@@ -216,6 +406,15 @@ fn main() -> Result<()> {
     let outer = OuterArgs::parse();
     let OuterCommand::Acl(mut args) = outer.command;
     args.colour = args.colour.detect();
+    if let Some(Command::Compare(options)) = &args.command {
+        // Comparing reports doesn't need a crate to analyse, so we don't create a `Cackle`.
+        std::process::exit(compare::run(options).code());
+    }
+    if let Some(Command::Disputed(options)) = &args.command {
+        // Likewise, listing disputed problems only needs to know where the config is.
+        let root_path = root_path(&args)?;
+        std::process::exit(disputes::run(options, &config_path(&args, &root_path)).code());
+    }
     if let Some(log_file) = &args.log_file {
         logging::init(log_file, args.log_level)?;
     }
@@ -239,6 +438,8 @@ struct Cackle {
     cargo_output_waiter: Option<CargoOutputWaiter>,
     crate_index: Arc<CrateIndex>,
     abort_sender: Sender<()>,
+    /// When packing artifacts, the requests that we've received so far.
+    recorded_requests: Mutex<Vec<Request>>,
 }
 
 impl Cackle {
@@ -249,10 +450,7 @@ impl Cackle {
             .canonicalize()
             .with_context(|| format!("Failed to read directory `{}`", root_path.display()))?;
 
-        let config_path = args
-            .cackle_path
-            .clone()
-            .unwrap_or_else(|| root_path.join("cackle.toml"));
+        let config_path = config_path(&args, &root_path);
 
         let crate_index = Arc::new(CrateIndex::new(&root_path)?);
         let target_dir = root_path.join(
@@ -266,6 +464,11 @@ impl Cackle {
             target_dir.clone(),
             args.clone(),
             determine_sysroot(&root_path)?,
+            // The version only feeds checks against known issues and the pinned version, so we
+            // carry on without it if detection fails.
+            RustcVersion::detect(&root_path)
+                .map_err(|error| log::warn!("{error:#}"))
+                .ok(),
             crate_index.clone(),
             config_path.clone(),
         )));
@@ -293,6 +496,7 @@ impl Cackle {
             target_dir,
             abort_sender,
             cargo_output_waiter: None,
+            recorded_requests: Mutex::default(),
         })
     }
 
@@ -302,6 +506,9 @@ impl Cackle {
         if let Some(Command::Summary(options)) = &self.args.command {
             return self.print_summary(options);
         }
+        if let Some(Command::Review(options)) = &self.args.command {
+            return self.print_due_reviews(options);
+        }
         let mut error = None;
         let exit_code = match self.run(abort_recv) {
             Err(e) => {
@@ -322,9 +529,43 @@ impl Cackle {
         if let Some(error) = error {
             println!();
             println!("Error: {error:#}");
+            if let Some(hint) = error_hint(&error) {
+                println!("{hint}");
+            }
         }
 
-        let checker = self.checker.lock().unwrap();
+        let mut checker = self.checker.lock().unwrap();
+        if let Some(report_path) = &self.args.report_output {
+            let report = report::Report::new(&self.problem_store.lock(), &checker);
+            if let Err(error) = report.write(report_path) {
+                println!("{error:#}");
+                return outcome::FAILURE;
+            }
+        }
+        if let Some(comparison) = checker.config_trial_comparison() {
+            match comparison {
+                Ok(comparison) => print!("{comparison}"),
+                Err(error) => {
+                    println!("{error:#}");
+                    return outcome::FAILURE;
+                }
+            }
+        }
+        if let Some(options) = self.args.graph_options()
+            && let Err(error) = graph::write(&checker.take_reference_graphs(), options)
+        {
+            println!("{error:#}");
+            return outcome::FAILURE;
+        }
+        if let Some(options) = self.args.explain_symbol_options() {
+            match explain::describe(options, checker.symbol_explanations(), &checker) {
+                Ok(description) => print!("{description}"),
+                Err(error) => {
+                    println!("{error:#}");
+                    return outcome::FAILURE;
+                }
+            }
+        }
         if self.args.print_path_to_crate_map {
             checker.print_path_to_crate_map();
         }
@@ -336,7 +577,10 @@ impl Cackle {
                 "Completed successfully for configuration {}",
                 self.config_path.display()
             );
-            let summary = summary::Summary::new(&self.crate_index, &checker.config);
+            let summary = summary::Summary::new(&self.crate_index, &checker.config)
+                .with_async_runtimes(&checker.async_runtimes)
+                .with_panic_paths(&checker.panic_paths)
+                .with_nondeterminism(&checker.nondeterminism);
             println!("{summary}");
         }
         exit_code
@@ -353,6 +597,15 @@ impl Cackle {
         outcome::SUCCESS
     }
 
+    fn print_due_reviews(&self, options: &ReviewOptions) -> ExitCode {
+        let mut checker = self.checker.lock().unwrap();
+        if let Err(error) = checker.load_config() {
+            println!("{error:#}");
+            return outcome::FAILURE;
+        }
+        review::run(options, &checker.config, &self.crate_index)
+    }
+
     fn run(&mut self, abort_recv: Receiver<()>) -> Result<ExitCode> {
         if self.maybe_create_config()? == Outcome::GiveUp {
             info!("Gave up creating initial configuration");
@@ -362,11 +615,18 @@ impl Cackle {
             let should_run_cargo_clean = self.should_run_cargo_clean();
             let checker = &mut self.checker.lock().unwrap();
             checker.load_config()?;
+            if let Some(path) = &self.args.with_config {
+                checker.start_config_trial(path)?;
+            }
 
             if should_run_cargo_clean {
                 proxy::clean(&self.root_path, &self.args, &checker.config.raw.common)?;
             }
         }
+        let rustc_problems = self.checker.lock().unwrap().check_rustc_version()?;
+        if !rustc_problems.is_empty() {
+            self.problem_store.fix_problems(rustc_problems);
+        }
         if !self.args.ignore_newer_config_versions {
             let update_problems = self.checker.lock().unwrap().check_for_new_config_version();
             if !update_problems.is_empty() {
@@ -377,9 +637,10 @@ impl Cackle {
         let mut initial_outcome = self.new_request_handler(None).handle_request()?;
         let config = self.checker.lock().unwrap().config.clone();
         let crate_index = self.checker.lock().unwrap().crate_index.clone();
+        let vfs = self.checker.lock().unwrap().vfs.clone();
         initial_outcome = initial_outcome.and(
             self.problem_store
-                .fix_problems(config.raw.unused_imports(&crate_index)),
+                .fix_problems(config.raw.unused_imports(&crate_index, vfs.as_ref())),
         );
 
         {
@@ -390,11 +651,13 @@ impl Cackle {
             // unnecessary reload here.
             checker.load_config()?;
         }
+        let resources = Arc::new(ResourceManager::new(&config.raw.limits, self.args.jobs));
+        self.checker.lock().unwrap().resources = resources.clone();
 
         let root_path = self.root_path.clone();
         let args = self.args.clone();
         let build_result = if initial_outcome == Outcome::Continue {
-            if self.args.replay_requests {
+            if self.args.replay_requests || self.args.artifacts.is_some() {
                 self.replay_requests()
             } else {
                 let cargo_runner = proxy::CargoRunner {
@@ -404,6 +667,7 @@ impl Cackle {
                     config: &config,
                     args: &args,
                     crate_index: &crate_index,
+                    resources: &resources,
                 };
                 let r = cargo_runner.invoke_cargo_build(
                     abort_recv,
@@ -414,6 +678,9 @@ impl Cackle {
                         {
                             println!("Failed to save request: {error}");
                         }
+                        if self.args.is_packing_artifacts() {
+                            self.recorded_requests.lock().unwrap().push(request.clone());
+                        }
                         self.new_request_handler(Some(request))
                     },
                 );
@@ -422,7 +689,7 @@ impl Cackle {
                         self.cargo_output_waiter = Some(output_waiter);
                         Ok(())
                     }
-                    Err(e) => Err(e),
+                    Err(e) => Err(e.into()),
                 }
             }
         } else {
@@ -437,9 +704,38 @@ impl Cackle {
         // We only check if the build failed if there were no ACL check errors.
         build_result?;
 
+        if let Some(Command::PackArtifacts(options)) = &self.args.command {
+            let requests = std::mem::take(&mut *self.recorded_requests.lock().unwrap());
+            artifacts::pack(
+                requests,
+                &self.target_dir,
+                self.lock_file().as_deref(),
+                &options.output,
+            )?;
+            return Ok(outcome::SUCCESS);
+        }
+
+        let mut policy_problems = self.checker.lock().unwrap().check_policy()?;
+        policy_problems.merge(self.checker.lock().unwrap().take_deferred_problems());
+
+        let args = self.args.clone();
+        if let Some(options) = args.baseline_options() {
+            // As below, we only know what config is unused after a clean build.
+            if self.should_run_cargo_clean() || self.args.artifacts.is_some() {
+                policy_problems.merge(self.checker.lock().unwrap().check_unused()?);
+            }
+            let checker = self.checker.lock().unwrap();
+            migration::save(options, policy_problems, &checker.config, &self.config_path)?;
+            return Ok(outcome::SUCCESS);
+        }
+        if self.problem_store.fix_problems(policy_problems) != Outcome::Continue {
+            return Ok(outcome::FAILURE);
+        }
+
         // If we didn't run `cargo clean` when we started, then our records of what is an isn't used
-        // won't be complete, so we shouldn't emit unused warnings.
-        if self.should_run_cargo_clean() {
+        // won't be complete, so we shouldn't emit unused warnings. Bundles of artifacts are always
+        // from a clean build.
+        if self.should_run_cargo_clean() || self.args.artifacts.is_some() {
             let unused_problems = self.checker.lock().unwrap().check_unused()?;
             let resolution = self.problem_store.fix_problems(unused_problems);
             if resolution != Outcome::Continue {
@@ -451,7 +747,7 @@ impl Cackle {
     }
 
     fn should_run_cargo_clean(&mut self) -> bool {
-        !self.args.replay_requests && self.args.command.is_none()
+        !self.args.replay_requests && self.args.artifacts.is_none() && self.args.is_default_build()
     }
 
     fn new_request_handler(&self, request: Option<Request>) -> RequestHandler {
@@ -481,19 +777,53 @@ impl Cackle {
             .join("saved-cackle-rpcs")
     }
 
-    fn replay_requests(&self) -> Result<()> {
-        let rpcs_dir = &self.saved_request_path();
-        let mut rpc_paths: Vec<PathBuf> = rpcs_dir
-            .read_dir()
-            .with_context(|| format!("Failed to read saved RPCs dir `{}`", rpcs_dir.display()))?
-            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
-            .collect();
-        rpc_paths.sort();
-        for path in rpc_paths {
-            info!("Replaying RPC `{}`", path.display());
+    fn replay_requests(&mut self) -> Result<()> {
+        let (labels, requests, modified_inputs) = if let Some(bundle) = &self.args.artifacts {
+            let manifest = artifacts::unpack(bundle, &self.target_dir)?;
+            let labels: Vec<String> = (0..manifest.requests.len())
+                .map(|index| format!("{} #{index}", bundle.display()))
+                .collect();
+            let modified_inputs = staleness::changed_inputs(&manifest.input_hashes)?;
+            (labels, manifest.requests, modified_inputs)
+        } else {
+            let rpcs_dir = &self.saved_request_path();
+            let mut rpc_paths: Vec<PathBuf> = rpcs_dir
+                .read_dir()
+                .with_context(|| format!("Failed to read saved RPCs dir `{}`", rpcs_dir.display()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            rpc_paths.sort();
+            let requests = rpc_paths
+                .iter()
+                .map(|path| {
+                    let request_str = crate::fs::read_to_string(path)?;
+                    serde_json::from_str(&request_str)
+                        .with_context(|| format!("Failed to parse request `{}`", path.display()))
+                })
+                .collect::<Result<Vec<Request>>>()?;
+            // We're about to analyse artifacts from an earlier build, so check that they're
+            // still current.
+            let modified_inputs = staleness::stale_inputs(&requests, self.lock_file().as_deref());
+            let labels = rpc_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            (labels, requests, modified_inputs)
+        };
+        if !modified_inputs.is_empty()
+            && self
+                .problem_store
+                .fix_problems(Problem::StaleArtifacts(StaleArtifacts { modified_inputs }).into())
+                == Outcome::GiveUp
+        {
+            bail!("Artifacts are stale");
+        }
+        for (label, request) in labels.iter().zip(requests) {
+            info!("Replaying RPC `{label}`");
             if self
-                .replay_request(&path)
-                .with_context(|| format!("Replay of request `{}` failed", path.display()))?
+                .new_request_handler(Some(request))
+                .handle_request()
+                .with_context(|| format!("Replay of request `{label}` failed"))?
                 == Outcome::GiveUp
             {
                 bail!("Request gave error");
@@ -502,11 +832,11 @@ impl Cackle {
         Ok(())
     }
 
-    fn replay_request(&self, path: &Path) -> Result<Outcome> {
-        let request_str = crate::fs::read_to_string(path)?;
-        let request: Request = serde_json::from_str(&request_str)?;
-        let mut handler = self.new_request_handler(Some(request));
-        handler.handle_request()
+    fn lock_file(&self) -> Option<PathBuf> {
+        self.root_path
+            .ancestors()
+            .map(|dir| dir.join("Cargo.lock"))
+            .find(|path| path.exists())
     }
 
     fn save_request(&self, request: &Request) -> Result<()> {
@@ -522,7 +852,48 @@ impl Cackle {
     }
 }
 
-fn root_path(args: &Arc<Args>) -> Result<PathBuf> {
+/// Returns a suggestion of what the user might do about `error`, if we have one for that kind of
+/// error.
+fn error_hint(error: &anyhow::Error) -> Option<&'static str> {
+    use config::ConfigError;
+    use proxy::ProxyError;
+    use sandbox::SandboxError;
+    use symbol_graph::ScanError;
+
+    if let Some(ProxyError::Request(inner)) = error.downcast_ref() {
+        return error_hint(inner);
+    }
+    if let Some(error) = error.downcast_ref::<ScanError>() {
+        return match error {
+            ScanError::DebugInfo { .. } => Some(
+                "Cackle uses debug info to determine which crate code came from. Check that \
+                 debug info hasn't been disabled for the profile being built.",
+            ),
+            ScanError::CorruptBinary { .. } | ScanError::CorruptObject { .. } => {
+                Some("This might be due to a stale build artifact. Try running `cargo clean`.")
+            }
+            ScanError::Read { .. } | ScanError::Other(_) => None,
+        };
+    }
+    if let Some(SandboxError::Unavailable { .. }) = error.downcast_ref() {
+        return Some(
+            "Alternatively, the sandbox can be disabled by setting `kind = \"Disabled\"` in the \
+             `[sandbox]` section of cackle.toml.",
+        );
+    }
+    if let Some(ConfigError::Parse { .. } | ConfigError::Invalid(_)) = error.downcast_ref() {
+        return Some("See CONFIG.md for details of the config format.");
+    }
+    None
+}
+
+fn config_path(args: &Args, root_path: &Path) -> PathBuf {
+    args.cackle_path
+        .clone()
+        .unwrap_or_else(|| root_path.join("cackle.toml"))
+}
+
+fn root_path(args: &Args) -> Result<PathBuf> {
     if let Some(path) = args.path.clone() {
         return Ok(path);
     }
@@ -597,6 +968,13 @@ impl RequestHandler {
             }
         }
     }
+
+    fn take_diagnostics(&self, crate_sel: &CrateSel) -> Vec<String> {
+        self.checker
+            .lock()
+            .unwrap()
+            .take_rustc_diagnostics(crate_sel)
+    }
 }
 
 /// Directly invokes a wrapped binary, where the binary and arguments were passed to us by the
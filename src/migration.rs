@@ -0,0 +1,438 @@
+//! Migration mode, which helps large codebases adopt cackle gradually. A baseline report, written
+//! by `cargo acl baseline save` or `--report-output`, records the problems that existed when
+//! migration started. Until the configured end date, if there is one, problems that are in the
+//! baseline are reported as warnings, while any new problems fail the check as usual. Once the end
+//! date has passed, all problems are errors again.
+
+use crate::config::Config;
+use crate::config::MigrationModeConfig;
+use crate::problem::Problem;
+use crate::problem::ProblemList;
+use crate::report::Report;
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use rustc_hash::FxHashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use toml::value::Date;
+
+/// Where `baseline save` writes the baseline if the config doesn't say, relative to the config.
+const DEFAULT_BASELINE: &str = "cackle-baseline.json";
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct BaselineOptions {
+    #[command(subcommand)]
+    command: BaselineCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum BaselineCommand {
+    /// Build and analyse, then record all outstanding problems as the baseline, without failing.
+    Save(SaveOptions),
+}
+
+#[derive(Parser, Debug, Clone)]
+struct SaveOptions {
+    /// Where to write the baseline. Defaults to `migration_mode.baseline` if that's set, otherwise
+    /// `cackle-baseline.json` next to the config file.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// The problems that existed when migration started.
+#[derive(Debug)]
+pub(crate) struct Baseline {
+    /// The summaries of the problems. As when comparing reports, problems with the same summary are
+    /// considered to be the same problem.
+    summaries: FxHashSet<String>,
+}
+
+impl Baseline {
+    /// Loads the baseline, provided that migration mode hasn't yet ended. `config_dir` is the
+    /// directory containing the config file.
+    pub(crate) fn load(config: &MigrationModeConfig, config_dir: &Path) -> Result<Option<Self>> {
+        if let Some(until) = &config.until {
+            // Validation ensures that this is a date.
+            let Some(until) = until.date else {
+                return Ok(None);
+            };
+            if today() > until {
+                return Ok(None);
+            }
+        }
+        let path = config_dir.join(&config.baseline);
+        let report = Report::load(&path)
+            .with_context(|| format!("Failed to load migration baseline `{}`", path.display()))?;
+        Ok(Some(Baseline {
+            summaries: report
+                .problems
+                .into_iter()
+                .map(|problem| problem.summary)
+                .collect(),
+        }))
+    }
+
+    /// Returns whether `problem` was already present when migration started.
+    pub(crate) fn contains(&self, problem: &Problem) -> bool {
+        // Reports have full names, so we need to produce the summary in the same way.
+        self.summaries
+            .contains(&crate::name_display::for_json(|| problem.to_string()))
+    }
+}
+
+/// Writes `problems` as the baseline. `config_path` is the path of the config file.
+pub(crate) fn save(
+    options: &BaselineOptions,
+    problems: ProblemList,
+    config: &Config,
+    config_path: &Path,
+) -> Result<()> {
+    let BaselineCommand::Save(save_options) = &options.command;
+    let path = match &save_options.output {
+        Some(output) => output.clone(),
+        None => config_path.parent().unwrap_or(Path::new(".")).join(
+            config
+                .raw
+                .migration_mode
+                .as_ref()
+                .map_or(Path::new(DEFAULT_BASELINE), |migration_mode| {
+                    migration_mode.baseline.as_path()
+                }),
+        ),
+    };
+    let problems = problems.take();
+    let report = Report::from_problems(problems.iter(), config);
+    report.write(&path)?;
+    println!(
+        "Recorded {} problem(s) in baseline `{}`",
+        report.problems.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Returns the current date in UTC.
+pub(crate) fn today() -> Date {
+    days_from_today(0)
+}
+
+/// Returns the date `days` days after the current date in UTC.
+pub(crate) fn days_from_today(days: u64) -> Date {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    date_from_days(seconds / (24 * 60 * 60) + days)
+}
+
+/// Converts a number of days since 1970-01-01 to a date. See
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn date_from_days(days: u64) -> Date {
+    const DAYS_PER_ERA: u64 = 146_097;
+    let z = days + 719_468;
+    let era = z / DAYS_PER_ERA;
+    let day_of_era = z % DAYS_PER_ERA;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    Date {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Baseline;
+    use super::BaselineCommand;
+    use super::BaselineOptions;
+    use super::SaveOptions;
+    use super::date_from_days;
+    use crate::Args;
+    use crate::CheckState;
+    use crate::Command;
+    use crate::checker::Checker;
+    use crate::config::MigrationModeConfig;
+    use crate::config::SandboxConfig;
+    use crate::config::testing::parse;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::testing::index_with_package_dirs;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::problem::Problem;
+    use crate::proxy::rpc;
+    use crate::proxy::subprocess::SubprocessConfig;
+    use crate::report::Report;
+    use crate::report::ReportedProblem;
+    use crate::tmpdir::TempDir;
+    use std::path::Path;
+    use std::sync::Arc;
+    use toml::value::Date;
+
+    /// Returns a checker that's saving a baseline to `dir/baseline.json`, for packages `foo` and
+    /// `bar` with sources in `dir/foo` and `dir/bar`.
+    fn saving_checker(dir: &Path, tmpdir: &Arc<TempDir>) -> Checker {
+        let args = Args {
+            command: Some(Command::Baseline(BaselineOptions {
+                command: BaselineCommand::Save(SaveOptions {
+                    output: Some(dir.join("baseline.json")),
+                }),
+            })),
+            ..Args::default()
+        };
+        let foo_dir = dir.join("foo");
+        let bar_dir = dir.join("bar");
+        Checker::new(
+            tmpdir.clone(),
+            dir.join("target"),
+            Arc::new(args),
+            Arc::from(Path::new("")),
+            None,
+            index_with_package_dirs(&[
+                ("foo", foo_dir.to_str().unwrap()),
+                ("bar", bar_dir.to_str().unwrap()),
+            ]),
+            dir.join("cackle.toml"),
+        )
+    }
+
+    fn unsafe_usage(pkg: &str, dir: &Path) -> rpc::UnsafeUsage {
+        rpc::UnsafeUsage {
+            crate_sel: CrateSel::primary(pkg_id(pkg)),
+            pkg_dir: dir.join(pkg),
+            locations: vec![SourceLocation::new(
+                dir.join(pkg).join("src/lib.rs"),
+                1,
+                None,
+            )],
+        }
+    }
+
+    /// When saving a baseline, whatever request a problem comes from, it's deferred so that the
+    /// build can run to completion and every problem makes it into the baseline.
+    #[test]
+    fn save_defers_problems_from_all_requests() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = dir.path();
+        let foo_dir = dir.join("foo");
+        std::fs::create_dir_all(foo_dir.join("bin")).unwrap();
+        std::fs::create_dir_all(dir.join("bar")).unwrap();
+        std::fs::write(foo_dir.join("bin/tool"), b"\x7fELF...").unwrap();
+        let build_rs = foo_dir.join("build.rs");
+        std::fs::write(&build_rs, "fn main() {}").unwrap();
+        let build_script = dir.join("build-script-build");
+        let status = std::process::Command::new("rustc")
+            .arg(&build_rs)
+            .arg("-o")
+            .arg(&build_script)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let tmpdir = Arc::new(TempDir::new(None).unwrap());
+        let mut checker = saving_checker(dir, &tmpdir);
+        checker.update_config(
+            parse("detect_binary_files = true\npin_build_scripts = true\n").unwrap(),
+        );
+        let mut check_state = CheckState::default();
+        let mut handle = |request: Option<rpc::Request>| {
+            let problems = checker.handle_request(&request, &mut check_state).unwrap();
+            assert!(problems.is_empty(), "{request:?} returned {problems:?}");
+        };
+
+        handle(None);
+        handle(Some(rpc::Request::CrateUsesUnsafe(unsafe_usage(
+            "foo", dir,
+        ))));
+        let build_sel = CrateSel::build_script(pkg_id("foo"));
+        handle(Some(rpc::Request::BinExecutionComplete(
+            rpc::BinExecutionOutput {
+                exit_code: 0,
+                stdout: b"cargo:rustc-link-search=somewhere\n".to_vec(),
+                stderr: Vec::new(),
+                crate_sel: build_sel.clone(),
+                sandbox_config: SandboxConfig::default(),
+                binary_path: build_script.clone(),
+                sandbox_config_display: None,
+                written_executables: Vec::new(),
+                unreadable_outputs: Vec::new(),
+            },
+        )));
+        handle(Some(rpc::Request::LinkerInvoked(
+            crate::link_info::testing::link_info(build_sel.clone(), build_script.to_str().unwrap()),
+        )));
+        handle(Some(rpc::Request::RustcComplete(rpc::RustcOutput {
+            crate_sel: build_sel,
+            source_paths: vec![build_rs],
+            link_libs: Vec::new(),
+            remap_path_prefixes: Vec::new(),
+            out_dir: None,
+            rustdoc_json: None,
+        })));
+
+        let deferred = checker.take_deferred_problems().take();
+        let has = |f: fn(&Problem) -> bool| deferred.iter().any(f);
+        assert!(
+            has(|p| matches!(p, Problem::BinaryFileInSources(..))),
+            "{deferred:?}"
+        );
+        assert!(
+            has(|p| matches!(p, Problem::DisallowedUnsafe(..))),
+            "{deferred:?}"
+        );
+        assert!(
+            has(|p| matches!(p, Problem::DisallowedBuildInstruction(..))),
+            "{deferred:?}"
+        );
+        assert!(
+            has(|p| matches!(p, Problem::BuildScriptChanged(..))),
+            "{deferred:?}"
+        );
+    }
+
+    /// Rustc stops when a crate uses unsafe that isn't permitted, so when saving a baseline, the
+    /// flattened config that subprocesses read is rewritten to permit it.
+    #[test]
+    fn save_lets_rustc_continue_after_unsafe() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = dir.path();
+        let tmpdir = Arc::new(TempDir::new(None).unwrap());
+        let mut checker = saving_checker(dir, &tmpdir);
+        let usage = unsafe_usage("foo", dir);
+        let problems = checker
+            .handle_request(
+                &Some(rpc::Request::CrateUsesUnsafe(usage.clone())),
+                &mut CheckState::default(),
+            )
+            .unwrap();
+        assert!(problems.is_empty());
+        let flattened =
+            SubprocessConfig::parse_file(&crate::config::flattened_config_path(tmpdir.path()))
+                .unwrap();
+        assert!(
+            flattened
+                .permissions
+                .unsafe_permitted_for_crate(&usage.crate_sel)
+        );
+        assert!(
+            !flattened
+                .permissions
+                .unsafe_permitted_for_crate(&CrateSel::primary(pkg_id("bar")))
+        );
+    }
+
+    /// Saving a baseline while one is already in effect keeps the problems that were already in
+    /// it, as well as adding new ones.
+    #[test]
+    fn resave_keeps_baselined_problems() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = dir.path();
+        let tmpdir = Arc::new(TempDir::new(None).unwrap());
+        let mut checker = saving_checker(dir, &tmpdir);
+        let config = parse("").unwrap();
+        checker.update_config(config.clone());
+        let check_unsafe = |checker: &mut Checker, pkg: &str| {
+            let problems = checker
+                .handle_request(
+                    &Some(rpc::Request::CrateUsesUnsafe(unsafe_usage(pkg, dir))),
+                    &mut CheckState::default(),
+                )
+                .unwrap();
+            assert!(problems.is_empty());
+        };
+        check_unsafe(&mut checker, "foo");
+        let options = checker.args.baseline_options().unwrap().clone();
+        super::save(
+            &options,
+            checker.take_deferred_problems(),
+            &config,
+            &dir.join("cackle.toml"),
+        )
+        .unwrap();
+        let migration_mode = MigrationModeConfig {
+            baseline: "baseline.json".into(),
+            until: None,
+        };
+        let load = || Baseline::load(&migration_mode, dir).unwrap().unwrap();
+        assert_eq!(load().summaries.len(), 1);
+
+        // The second time around, `foo`'s problem is in the baseline and `bar` has a new one.
+        let mut checker = saving_checker(dir, &tmpdir);
+        checker.update_config(config.clone());
+        checker.migration_baseline = Some(Arc::new(load()));
+        check_unsafe(&mut checker, "foo");
+        check_unsafe(&mut checker, "bar");
+        let problems = checker.take_deferred_problems();
+        assert_eq!(problems.len(), 2);
+        super::save(&options, problems, &config, &dir.join("cackle.toml")).unwrap();
+        let baseline = load();
+        assert_eq!(baseline.summaries.len(), 2);
+        let unsafe_problem = |pkg| Problem::DisallowedUnsafe(unsafe_usage(pkg, dir));
+        assert!(baseline.contains(&unsafe_problem("foo")));
+        assert!(baseline.contains(&unsafe_problem("bar")));
+    }
+
+    #[test]
+    fn dates() {
+        let date = |year, month, day| Date { year, month, day };
+        assert_eq!(date_from_days(0), date(1970, 1, 1));
+        assert_eq!(date_from_days(59), date(1970, 3, 1));
+        assert_eq!(date_from_days(11_016), date(2000, 2, 29));
+        assert_eq!(date_from_days(20_010), date(2024, 10, 14));
+    }
+
+    #[test]
+    fn baseline() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let existing = Problem::UsesBuildScript(pkg_id("foo"));
+        let new = Problem::UsesBuildScript(pkg_id("bar"));
+        let report = Report {
+            format_version: 1,
+            rustc_version: None,
+            problems: vec![ReportedProblem {
+                severity: existing.severity(),
+                package: None,
+                summary: existing.to_string(),
+                details: String::new(),
+            }],
+            permissions: Default::default(),
+            build_settings: Default::default(),
+            test_only_packages: Vec::new(),
+            async_runtimes: Default::default(),
+            panic_paths: Default::default(),
+            nondeterminism: Default::default(),
+        };
+        report.write(&dir.path().join("baseline.json")).unwrap();
+        let config = |until: &str| MigrationModeConfig {
+            baseline: "baseline.json".into(),
+            until: Some(until.parse().unwrap()),
+        };
+
+        let baseline = Baseline::load(&config("9999-12-31"), dir.path())
+            .unwrap()
+            .unwrap();
+        assert!(baseline.contains(&existing));
+        assert!(!baseline.contains(&new));
+
+        assert!(
+            Baseline::load(&config("2000-01-01"), dir.path())
+                .unwrap()
+                .is_none()
+        );
+        assert!(Baseline::load(&config("9999-12-31"), &dir.path().join("missing")).is_err());
+
+        // Without an end date, the baseline always applies.
+        let config = MigrationModeConfig {
+            baseline: "baseline.json".into(),
+            until: None,
+        };
+        assert!(Baseline::load(&config, dir.path()).unwrap().is_some());
+    }
+}
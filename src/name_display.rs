@@ -0,0 +1,95 @@
+//! Shortening of long names for display. Monomorphised generic functions can have names that are
+//! hundreds of characters long, which makes output hard to read. The policy comes from the
+//! `[display]` section of the config and is applied wherever we show names to the user. It's held
+//! globally, since names are mostly shown from `Display` implementations, which don't have access
+//! to the config.
+
+use crate::config::DisplayConfig;
+use crate::config::Truncation;
+use std::cell::Cell;
+use std::fmt::Display;
+use std::sync::RwLock;
+
+static POLICY: RwLock<DisplayConfig> = RwLock::new(DisplayConfig {
+    max_name_length: None,
+    truncate: Truncation::Middle,
+    truncate_json: false,
+});
+
+thread_local! {
+    /// Whether we're currently producing JSON.
+    static IN_JSON: Cell<bool> = const { Cell::new(false) };
+}
+
+const ELLIPSIS: char = '…';
+
+pub(crate) fn set_policy(config: &DisplayConfig) {
+    *POLICY.write().unwrap() = config.clone();
+}
+
+/// Returns `name`, shortened if it's longer than the configured maximum.
+pub(crate) fn shorten(name: &impl Display) -> String {
+    let name = name.to_string();
+    let policy = POLICY.read().unwrap();
+    let Some(max_length) = policy.max_name_length else {
+        return name;
+    };
+    if IN_JSON.get() && !policy.truncate_json {
+        return name;
+    }
+    truncate(name, max_length, policy.truncate)
+}
+
+/// Returns `name`, shortened only if the config says to also shorten names in JSON output.
+pub(crate) fn shorten_for_json(name: &impl Display) -> String {
+    for_json(|| shorten(name))
+}
+
+/// Runs `f`, which is producing JSON output, applying the policy for JSON to any names that it
+/// shortens.
+pub(crate) fn for_json<R>(f: impl FnOnce() -> R) -> R {
+    let previous = IN_JSON.replace(true);
+    let result = f();
+    IN_JSON.set(previous);
+    result
+}
+
+fn truncate(name: String, max_length: usize, truncation: Truncation) -> String {
+    let length = name.chars().count();
+    if length <= max_length {
+        return name;
+    }
+    let keep = max_length.saturating_sub(1);
+    let mut result: String = match truncation {
+        Truncation::Middle => name.chars().take(keep.div_ceil(2)).collect(),
+        Truncation::End => name.chars().take(keep).collect(),
+    };
+    result.push(ELLIPSIS);
+    if truncation == Truncation::Middle {
+        result.extend(name.chars().skip(length - keep / 2));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate;
+    use crate::config::Truncation;
+
+    #[test]
+    fn truncation() {
+        let name = "core::ptr::drop_in_place<alloc::vec::Vec<u8>>";
+        assert_eq!(truncate(name.to_owned(), 100, Truncation::Middle), name);
+        assert_eq!(truncate(name.to_owned(), name.len(), Truncation::End), name);
+        assert_eq!(
+            truncate(name.to_owned(), 20, Truncation::Middle),
+            "core::ptr:…:Vec<u8>>"
+        );
+        assert_eq!(
+            truncate(name.to_owned(), 20, Truncation::End),
+            "core::ptr::drop_in_…"
+        );
+        assert_eq!(truncate("αβγδε".to_owned(), 4, Truncation::Middle), "αβ…ε");
+        assert_eq!(truncate("abc".to_owned(), 0, Truncation::End), "…");
+    }
+}
@@ -5,25 +5,27 @@ use crate::symbol::Symbol;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
+use serde::Deserialize;
+use serde::Serialize;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::sync::Arc;
 
 /// A name of something. e.g. `std::path::Path`.
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
 pub(crate) struct Name {
     /// The components of this name. e.g. ["std", "path", "Path"]
     pub(crate) parts: Vec<Arc<str>>,
 }
 
 /// A name obtained from debug info.
-#[derive(Eq, PartialEq, Hash, Clone, Debug, PartialOrd, Ord)]
+#[derive(Eq, PartialEq, Hash, Clone, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub(crate) struct DebugName<'input> {
     pub(crate) namespace: Namespace,
     pub(crate) name: Utf8Bytes<'input>,
 }
 
-#[derive(Eq, PartialEq, Hash, Clone, Debug, PartialOrd, Ord)]
+#[derive(Eq, PartialEq, Hash, Clone, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub(crate) struct Namespace {
     pub(crate) parts: Arc<[Arc<str>]>,
 }
@@ -34,7 +36,7 @@ pub(crate) struct SymbolAndName<'input> {
     pub(crate) debug_name: Option<DebugName<'input>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub(crate) enum SymbolOrDebugName {
     Symbol(Symbol<'static>),
     DebugName(DebugName<'static>),
@@ -374,6 +376,18 @@ enum NamesIteratorState<I> {
 }
 
 impl SymbolAndName<'_> {
+    /// Returns the name of the crate that the name starts with, if any.
+    pub(crate) fn crate_name(&self) -> Option<&str> {
+        if let Some(first) = self
+            .debug_name
+            .as_ref()
+            .and_then(|debug_name| debug_name.namespace.parts.first())
+        {
+            return Some(first);
+        }
+        self.symbol.as_ref()?.crate_name()
+    }
+
     pub(crate) fn symbol_or_debug_name(&self) -> Result<SymbolOrDebugName> {
         if let Some(debug_name) = self.debug_name.as_ref() {
             return Ok(SymbolOrDebugName::DebugName(debug_name.to_heap()));
@@ -0,0 +1,294 @@
+//! Tracking of the native shared libraries that binaries depend on. We read the DT_NEEDED entries
+//! of each binary that gets linked, then attribute each library to the packages whose build
+//! scripts asked for it to be linked with `cargo:rustc-link-lib`. Cargo passes these to rustc as
+//! `-l` flags when compiling the package, so we get them from rustc's arguments, which works even
+//! when the build script isn't sandboxed. Libraries that no build script asked for, e.g. because
+//! they came from a `#[link]` attribute, are attributed to the package of the binary. Each package
+//! must then be allowed the libraries attributed to it.
+
+use crate::config::Config;
+use crate::config::permissions::PermSel;
+use crate::crate_index::CrateSel;
+use crate::crate_index::PackageId;
+use crate::problem::DisallowedNativeLibrary;
+use crate::problem::Problem;
+use crate::problem::ProblemList;
+use anyhow::Context;
+use anyhow::Result;
+use object::Endianness;
+use object::read::elf::ElfFile32;
+use object::read::elf::ElfFile64;
+use object::read::elf::FileHeader;
+use object::read::elf::Sym;
+use rustc_hash::FxHashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Libraries that the standard library itself links against, so would just add noise if we
+/// required them to be allowed.
+const ALWAYS_PERMITTED: &[&str] = &["c", "m", "gcc_s", "pthread", "dl", "rt", "util"];
+
+/// Prefixes of the names of dynamic loaders, which show up in DT_NEEDED on some platforms.
+const LOADER_PREFIXES: &[&str] = &["ld-linux", "ld64"];
+
+/// Returns the names of the native libraries that the supplied rustc arguments ask to be linked.
+pub(crate) fn link_libs_from_rustc_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut libraries = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let value = if arg == "-l" {
+            args.next()
+        } else {
+            arg.strip_prefix("-l").map(str::to_owned)
+        };
+        if let Some(name) = value.as_deref().and_then(link_lib_name) {
+            libraries.push(name.to_owned());
+        }
+    }
+    libraries
+}
+
+/// Returns the name of the library from the value of a `-l` flag, which has the form
+/// `[KIND[:MODIFIERS]=]NAME[:RENAME]`.
+fn link_lib_name(value: &str) -> Option<&str> {
+    let name = value.split_once('=').map_or(value, |(_kind, name)| name);
+    let name = name.split_once(':').map_or(name, |(name, _rename)| name);
+    (!name.is_empty()).then_some(name)
+}
+
+/// Returns the names of the shared libraries listed as DT_NEEDED by the binary at `bin_path`,
+/// without any `lib` prefix or `.so` suffix. Binaries that aren't ELF have no libraries.
+pub(crate) fn needed_libraries(bytes: &[u8], bin_path: &Path) -> Result<Vec<String>> {
+    let context = || format!("Failed to read dynamic section of `{}`", bin_path.display());
+    let mut libraries = match object::FileKind::parse(bytes) {
+        Ok(object::FileKind::Elf64) => {
+            elf_needed(&ElfFile64::<Endianness>::parse(bytes).with_context(context)?)
+                .with_context(context)?
+        }
+        Ok(object::FileKind::Elf32) => {
+            elf_needed(&ElfFile32::<Endianness>::parse(bytes).with_context(context)?)
+                .with_context(context)?
+        }
+        _ => Vec::new(),
+    };
+    libraries.sort();
+    libraries.dedup();
+    Ok(libraries)
+}
+
+/// Returns the names of the symbols that the binary at `bin_path` imports from shared libraries
+/// other than those that the standard library links against. We go by the library named by each
+/// symbol's version. Symbols without a version are counted unless every library that the binary
+/// needs is one that the standard library links against. Binaries that aren't ELF have no imports.
+pub(crate) fn foreign_imports(bytes: &[u8], bin_path: &Path) -> Result<Vec<Vec<u8>>> {
+    let context = || format!("Failed to read dynamic symbols of `{}`", bin_path.display());
+    match object::FileKind::parse(bytes) {
+        Ok(object::FileKind::Elf64) => {
+            elf_foreign_imports(&ElfFile64::<Endianness>::parse(bytes).with_context(context)?)
+                .with_context(context)
+        }
+        Ok(object::FileKind::Elf32) => {
+            elf_foreign_imports(&ElfFile32::<Endianness>::parse(bytes).with_context(context)?)
+                .with_context(context)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn elf_foreign_imports<Elf: FileHeader<Endian = Endianness>>(
+    file: &object::read::elf::ElfFile<Elf>,
+) -> object::Result<Vec<Vec<u8>>> {
+    let endian = file.endian();
+    let sections = file.elf_section_table();
+    let symbols = sections.symbols(endian, file.data(), object::elf::SHT_DYNSYM)?;
+    let versions = sections.versions(endian, file.data())?;
+    let only_system_libraries = elf_needed(file)?
+        .iter()
+        .all(|library| is_system_library(library));
+    let mut imports = Vec::new();
+    for (index, symbol) in symbols.enumerate() {
+        if !symbol.is_undefined(endian) {
+            continue;
+        }
+        let name = symbols.symbol_name(endian, symbol)?;
+        if name.is_empty() {
+            continue;
+        }
+        let file = match &versions {
+            Some(versions) => versions
+                .version(versions.version_index(endian, index))?
+                .and_then(|version| version.file()),
+            None => None,
+        };
+        let foreign = match file {
+            Some(file) => !is_system_library(library_name(&String::from_utf8_lossy(file))),
+            None => !only_system_libraries,
+        };
+        if foreign {
+            imports.push(name.to_owned());
+        }
+    }
+    Ok(imports)
+}
+
+fn elf_needed<Elf: FileHeader<Endian = Endianness>>(
+    file: &object::read::elf::ElfFile<Elf>,
+) -> object::Result<Vec<String>> {
+    let table = file.elf_dynamic_table()?;
+    let mut libraries = Vec::new();
+    for entry in &table {
+        if entry.tag == object::elf::DT_NEEDED {
+            let soname = String::from_utf8_lossy(table.string(entry)?);
+            libraries.push(library_name(&soname).to_owned());
+        }
+    }
+    Ok(libraries)
+}
+
+/// Converts a shared object name like `libssl.so.3` to the name that would be passed to the
+/// linker, e.g. `ssl`.
+fn library_name(soname: &str) -> &str {
+    let name = soname.strip_prefix("lib").unwrap_or(soname);
+    name.find(".so").map_or(name, |end| &name[..end])
+}
+
+/// Returns whether `library` is one that the standard library links against, or the dynamic loader.
+fn is_system_library(library: &str) -> bool {
+    ALWAYS_PERMITTED.contains(&library)
+        || LOADER_PREFIXES
+            .iter()
+            .any(|prefix| library.starts_with(prefix))
+}
+
+/// Checks the libraries needed by a binary against what's allowed. `requesters` maps from library
+/// names to the packages whose build scripts asked for them.
+pub(crate) fn check(
+    needed: &[String],
+    requesters: &FxHashMap<String, Vec<PackageId>>,
+    crate_sel: &CrateSel,
+    bin_path: &Arc<Path>,
+    config: &Config,
+) -> ProblemList {
+    let mut problems = ProblemList::default();
+    for library in needed {
+        if is_system_library(library) {
+            continue;
+        }
+        let owners: Vec<(PackageId, PermSel)> = match requesters.get(library) {
+            Some(pkg_ids) => pkg_ids
+                .iter()
+                .map(|pkg_id| (pkg_id.clone(), PermSel::for_primary(pkg_id.pkg_name())))
+                .collect(),
+            None => vec![(
+                crate_sel.pkg_id.clone(),
+                PermSel::for_non_build_output(crate_sel),
+            )],
+        };
+        for (pkg_id, perm_sel) in owners {
+            let allowed = config
+                .permissions
+                .get(&perm_sel)
+                .is_some_and(|pkg_config| pkg_config.allow_native_libs.contains(library));
+            if !allowed {
+                problems.push(Problem::DisallowedNativeLibrary(DisallowedNativeLibrary {
+                    pkg_id,
+                    perm_sel,
+                    library: library.clone(),
+                    bin_path: bin_path.clone(),
+                }));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use super::is_system_library;
+    use super::library_name;
+    use super::link_libs_from_rustc_args;
+    use crate::config;
+    use crate::config::permissions::PermSel;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::testing::pkg_id;
+    use crate::problem::DisallowedNativeLibrary;
+    use crate::problem::Problem;
+    use crate::problem::ProblemList;
+    use rustc_hash::FxHashMap;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn rustc_args() {
+        let args = [
+            "--crate-name",
+            "openssl_sys",
+            "-l",
+            "dylib=ssl",
+            "-lcrypto",
+            "-l",
+            "static:+whole-archive=foo:bar",
+            "-L",
+            "native=/lib",
+            "-l",
+            "",
+        ];
+        assert_eq!(
+            link_libs_from_rustc_args(args.into_iter().map(String::from)),
+            vec!["ssl", "crypto", "foo"]
+        );
+    }
+
+    #[test]
+    fn library_names() {
+        assert_eq!(library_name("libssl.so.3"), "ssl");
+        assert_eq!(library_name("libz.so"), "z");
+        assert_eq!(library_name("ld-linux-x86-64.so.2"), "ld-linux-x86-64");
+    }
+
+    #[test]
+    fn attribution() {
+        let config = config::testing::parse(
+            r#"
+            [pkg.openssl-sys]
+            allow_native_libs = ["ssl"]
+            "#,
+        )
+        .unwrap();
+        let mut requesters = FxHashMap::default();
+        requesters.insert("ssl".to_owned(), vec![pkg_id("openssl-sys")]);
+        requesters.insert("crypto".to_owned(), vec![pkg_id("openssl-sys")]);
+        let bin_path: Arc<Path> = Arc::from(Path::new("/bin/app"));
+        let needed = ["c", "ssl", "crypto", "z", "ld-linux-x86-64"].map(String::from);
+        let problems = check(
+            &needed,
+            &requesters,
+            &CrateSel::primary(pkg_id("app")),
+            &bin_path,
+            &config,
+        );
+        let mut expected = ProblemList::default();
+        expected.push(Problem::DisallowedNativeLibrary(DisallowedNativeLibrary {
+            pkg_id: pkg_id("openssl-sys"),
+            perm_sel: PermSel::for_primary("openssl-sys"),
+            library: "crypto".to_owned(),
+            bin_path: bin_path.clone(),
+        }));
+        expected.push(Problem::DisallowedNativeLibrary(DisallowedNativeLibrary {
+            pkg_id: pkg_id("app"),
+            perm_sel: PermSel::for_primary("app"),
+            library: "z".to_owned(),
+            bin_path: bin_path.clone(),
+        }));
+        assert_eq!(problems, expected);
+    }
+
+    #[test]
+    fn system_libraries() {
+        assert!(is_system_library("c"));
+        assert!(is_system_library("gcc_s"));
+        assert!(is_system_library(library_name("ld-linux-x86-64.so.2")));
+        assert!(!is_system_library("ssl"));
+    }
+}
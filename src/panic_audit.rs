@@ -0,0 +1,160 @@
+//! Detection of which packages reference panic machinery and which reference abort. As for async
+//! runtimes, these references are found by looking each kind of path up as if it were an API, under
+//! a name that can't clash with APIs from the config. Packages can be marked with `no_panic`, in
+//! which case any reference that they make to panic machinery is reported. e.g. for embedded or
+//! safety-critical code where a panic isn't acceptable.
+
+use crate::checker::ApiUsage;
+use crate::config::ApiName;
+use crate::crate_index::PackageId;
+use crate::location::SourceLocation;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PanicPath {
+    /// Explicit panics, e.g. via `panic!`, `assert!` or indexing out of bounds.
+    Panic,
+
+    /// Panics from `unwrap` or `expect` on an `Option` or `Result`.
+    Unwrap,
+
+    /// Aborting the process without unwinding.
+    Abort,
+}
+
+/// Prefix for the names of the pseudo-APIs. Config validation rejects APIs with these names.
+const API_PREFIX: &str = "panic-audit:";
+
+/// For each kind of panic path, the packages that reference it.
+pub(crate) type PanicPaths = BTreeMap<PanicPath, BTreeSet<PackageId>>;
+
+impl PanicPath {
+    pub(crate) const ALL: &'static [PanicPath] =
+        &[PanicPath::Panic, PanicPath::Unwrap, PanicPath::Abort];
+
+    /// The paths that references to this kind of panic path are to.
+    fn paths(self) -> &'static [&'static str] {
+        match self {
+            // The panic handler is `rust_begin_unwind`, which newer versions of rustc mangle.
+            PanicPath::Panic => &[
+                "core::panicking",
+                "std::panicking",
+                "__rustc::rust_begin_unwind",
+                "extern::rust_begin_unwind",
+            ],
+            // `unwrap` and `expect` are generic, so references to them are split into several names
+            // and can't be matched by path. Instead, we match the non-generic functions that they
+            // call when they fail.
+            PanicPath::Unwrap => &[
+                "core::option::unwrap_failed",
+                "core::option::expect_failed",
+                "core::result::unwrap_failed",
+            ],
+            PanicPath::Abort => &["std::process::abort", "core::intrinsics::abort"],
+        }
+    }
+
+    /// Returns whether reaching this path means a panic.
+    pub(crate) fn is_panic(self) -> bool {
+        self != PanicPath::Abort
+    }
+
+    fn api_name(self) -> ApiName {
+        ApiName {
+            name: Arc::from(format!("{API_PREFIX}{self}")),
+        }
+    }
+
+    /// Returns the panic path for `api_name` if it's one of our pseudo-APIs.
+    pub(crate) fn from_api_name(api_name: &ApiName) -> Option<PanicPath> {
+        let name = api_name.as_ref().strip_prefix(API_PREFIX)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|path| path.to_string() == name)
+    }
+
+    /// Returns the pseudo-APIs that we use to find references to panic paths, together with the
+    /// paths that they include.
+    pub(crate) fn pseudo_apis() -> impl Iterator<Item = (ApiName, &'static str)> {
+        Self::ALL.iter().flat_map(|kind| {
+            kind.paths()
+                .iter()
+                .map(move |path| (kind.api_name(), *path))
+        })
+    }
+}
+
+/// Returns where `usage` makes the call that can panic. If the code that references the panic
+/// machinery was inlined, e.g. from `Option::unwrap`, then the usage's own location is in that code,
+/// so we instead return where the outermost inlined function was called from.
+pub(crate) fn call_site(usage: &ApiUsage) -> &SourceLocation {
+    usage
+        .inline_chain
+        .first()
+        .and_then(|frame| frame.location.as_ref())
+        .unwrap_or(&usage.source_location)
+}
+
+impl Display for PanicPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PanicPath::Panic => write!(f, "panic"),
+            PanicPath::Unwrap => write!(f, "unwrap"),
+            PanicPath::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PanicPath;
+    use super::call_site;
+    use crate::checker::InlineFrame;
+    use crate::checker::testing::api_usage;
+    use crate::config::ApiName;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::symbol::Symbol;
+    use std::path::Path;
+
+    #[test]
+    fn api_names() {
+        for kind in PanicPath::ALL {
+            assert_eq!(PanicPath::from_api_name(&kind.api_name()), Some(*kind));
+        }
+        assert_eq!(PanicPath::from_api_name(&ApiName::from("panic")), None);
+        assert!(
+            PanicPath::pseudo_apis().any(|(api, path)| path == "core::result::unwrap_failed"
+                && PanicPath::from_api_name(&api) == Some(PanicPath::Unwrap))
+        );
+    }
+
+    #[test]
+    fn call_sites() {
+        let mut usage = api_usage("foo", "foo::f", "core::option::unwrap_failed");
+        assert_eq!(call_site(&usage), &usage.source_location);
+
+        let caller = SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 20, Some(9));
+        usage.source_location = SourceLocation::new(Path::new("/rust/core/src/option.rs"), 1, None);
+        usage.inline_chain = vec![
+            InlineFrame {
+                function: SymbolOrDebugName::Symbol(Symbol::borrowed(b"foo::f").to_heap()),
+                location: Some(caller.clone()),
+            },
+            InlineFrame {
+                function: SymbolOrDebugName::Symbol(
+                    Symbol::borrowed(b"core::option::Option::unwrap").to_heap(),
+                ),
+                location: Some(usage.source_location.clone()),
+            },
+        ];
+        assert_eq!(call_site(&usage), &caller);
+    }
+}
@@ -0,0 +1,141 @@
+//! Serialisation of paths that works for paths that aren't valid UTF-8. Serde's own implementation
+//! fails for such paths, which would stop us from caching or reporting anything found in a source
+//! file with, say, a Latin-1 name in vendored C code. Paths that are valid UTF-8 are serialised as
+//! strings, as serde would. Others are serialised as their bytes. Use with `#[serde(with = ...)]`.
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub(crate) fn serialize<P: AsRef<Path>, S: Serializer>(
+    path: &P,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Encoded(path.as_ref()).serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, P: From<PathBuf>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<P, D::Error> {
+    Ok(P::from(Decoded::deserialize(deserializer)?.into_path()))
+}
+
+/// For sequences of paths.
+pub(crate) mod vec {
+    use super::Decoded;
+    use super::Encoded;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+    use std::path::PathBuf;
+
+    pub(crate) fn serialize<S: Serializer>(
+        paths: &[PathBuf],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Encoded(path)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        Ok(Vec::<Decoded>::deserialize(deserializer)?
+            .into_iter()
+            .map(Decoded::into_path)
+            .collect())
+    }
+}
+
+/// For optional paths.
+pub(crate) mod option {
+    use super::Encoded;
+    use serde::Serialize;
+    use serde::Serializer;
+    use std::path::PathBuf;
+
+    pub(crate) fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Encoded).serialize(serializer)
+    }
+}
+
+struct Encoded<'a>(&'a Path);
+
+impl Serialize for Encoded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(path) = self.0.to_str() {
+            serializer.serialize_str(path)
+        } else {
+            serializer.collect_seq(self.0.as_os_str().as_bytes())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Decoded {
+    Utf8(String),
+    Bytes(Vec<u8>),
+}
+
+impl Decoded {
+    fn into_path(self) -> PathBuf {
+        match self {
+            Decoded::Utf8(path) => PathBuf::from(path),
+            Decoded::Bytes(bytes) => PathBuf::from(OsString::from_vec(bytes)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde::Serialize;
+    use std::ffi::OsStr;
+    use std::os::unix::prelude::OsStrExt;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Paths {
+        #[serde(with = "super")]
+        one: Arc<Path>,
+        #[serde(with = "super::vec")]
+        many: Vec<PathBuf>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let latin1 = Path::new(OsStr::from_bytes(b"/vendor/caf\xe9/lib.c"));
+        let long = PathBuf::from(format!("/{}/lib.rs", "a".repeat(5000)));
+        let paths = Paths {
+            one: Arc::from(latin1),
+            many: vec![
+                long.clone(),
+                latin1.to_owned(),
+                PathBuf::from("/src/main.rs"),
+            ],
+        };
+        let json = serde_json::to_string(&paths).unwrap();
+        assert_eq!(serde_json::from_str::<Paths>(&json).unwrap(), paths);
+
+        // UTF-8 paths are serialised just as serde would.
+        assert_eq!(
+            serde_json::to_value(&Paths {
+                one: Arc::from(Path::new("/src/lib.rs")),
+                many: vec![long.clone()],
+            })
+            .unwrap(),
+            serde_json::json!({"one": "/src/lib.rs", "many": [long]})
+        );
+    }
+}
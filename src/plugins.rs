@@ -0,0 +1,125 @@
+//! Checker plugins, for policies that don't fit into API definitions, e.g. "we don't use any
+//! telemetry SDKs". A plugin is a program that we run once analysis is complete. We write every
+//! fact that we gathered to its stdin, one line of JSON per fact, in the same format as
+//! `--facts-output`, then close stdin. In addition to the usual facts, plugins get a `symbol` fact
+//! for each symbol defined in an analysed binary. The plugin writes a line of JSON to stdout for
+//! each problem that it finds, e.g.:
+//!
+//! {"message": "uses a telemetry SDK", "package": "foo", "api": "telemetry"}
+//!
+//! `package`, `version`, `api` and `scope` are optional. If the plugin names both a package and an
+//! API, then the finding is a usage of that API and is only reported if the package isn't allowed
+//! the API. `scope` is as in `api_usage` facts, e.g. "Build" for a usage from the package's build
+//! script, and determines which of the package's permissions are checked, defaulting to "All".
+
+use crate::config::permissions::PermissionScope;
+use crate::facts::Fact;
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Finding {
+    pub(crate) message: String,
+    pub(crate) package: Option<String>,
+    pub(crate) version: Option<String>,
+    pub(crate) api: Option<String>,
+    #[serde(default)]
+    pub(crate) scope: Option<PermissionScope>,
+}
+
+/// Runs the plugin `command`, passing it `facts`. Returns what the plugin found.
+pub(crate) fn run(command: &Path, facts: &[Fact]) -> Result<Vec<Finding>> {
+    let mut input = String::new();
+    for fact in facts {
+        input.push_str(&fact.to_json_line()?);
+    }
+    let mut child = Command::new(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run plugin `{}`", command.display()))?;
+    let mut stdin = child.stdin.take().unwrap();
+    // We write from a separate thread, since the plugin might produce enough output to block
+    // before it's read all of its input.
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run plugin `{}`", command.display()))?;
+    // If the plugin exited without reading all of its input, the write fails, which is fine.
+    let _ = writer.join();
+    if !output.status.success() {
+        bail!(
+            "Plugin `{}` failed:\n{}",
+            command.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let stdout = std::str::from_utf8(&output.stdout)
+        .with_context(|| format!("Output of plugin `{}` isn't UTF-8", command.display()))?;
+    parse_findings(stdout).with_context(|| format!("Invalid output from `{}`", command.display()))
+}
+
+fn parse_findings(output: &str) -> Result<Vec<Finding>> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).with_context(|| format!("Failed to parse line `{line}`"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Finding;
+    use super::parse_findings;
+    use crate::config::permissions::PermissionScope;
+
+    #[test]
+    fn parse_plugin_output() {
+        assert_eq!(
+            parse_findings(
+                "{\"message\": \"uses telemetry\", \"package\": \"foo\", \"api\": \"telemetry\"}\n\
+                 \n\
+                 {\"message\": \"bad\"}\n\
+                 {\"message\": \"downloads\", \"package\": \"foo\", \"api\": \"net\", \
+                 \"scope\": \"Build\"}\n"
+            )
+            .unwrap(),
+            vec![
+                Finding {
+                    message: "uses telemetry".to_owned(),
+                    package: Some("foo".to_owned()),
+                    version: None,
+                    api: Some("telemetry".to_owned()),
+                    scope: None,
+                },
+                Finding {
+                    message: "bad".to_owned(),
+                    package: None,
+                    version: None,
+                    api: None,
+                    scope: None,
+                },
+                Finding {
+                    message: "downloads".to_owned(),
+                    package: Some("foo".to_owned()),
+                    version: None,
+                    api: Some("net".to_owned()),
+                    scope: Some(PermissionScope::Build),
+                },
+            ]
+        );
+        assert!(parse_findings("{\"msg\": \"x\"}").is_err());
+        assert!(parse_findings("not json").is_err());
+        assert!(parse_findings("{\"message\": \"x\", \"scope\": \"Elsewhere\"}").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! Evaluation of user-supplied policies written in Rego against the facts gathered during analysis.
+//! We don't embed a Rego interpreter, instead we invoke `opa eval`, which needs to be on the PATH.
+
+use crate::facts::Fact;
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+/// The query that we evaluate. Policies should be in package `cackle` and add a message to `deny`
+/// for each violation.
+const DENY_QUERY: &str = "data.cackle.deny";
+
+#[derive(Serialize)]
+struct PolicyInput<'a> {
+    facts: &'a [Fact],
+}
+
+/// Evaluates the Rego policy in `policy_path` against `facts`. Returns a message for each policy
+/// violation.
+pub(crate) fn evaluate(policy_path: &Path, facts: &[Fact], tmpdir: &Path) -> Result<Vec<String>> {
+    let input_path = tmpdir.join("policy-input.json");
+    crate::fs::write(&input_path, serde_json::to_string(&PolicyInput { facts })?)?;
+    let output = Command::new("opa")
+        .arg("eval")
+        .arg("--format")
+        .arg("json")
+        .arg("--data")
+        .arg(policy_path)
+        .arg("--input")
+        .arg(&input_path)
+        .arg(DENY_QUERY)
+        .output()
+        .context("Failed to run `opa`. Is Open Policy Agent installed?")?;
+    if !output.status.success() {
+        bail!(
+            "`opa eval` of policy `{}` failed:\n{}",
+            policy_path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let stdout = std::str::from_utf8(&output.stdout).context("opa output isn't UTF-8")?;
+    violations_from_opa_output(stdout)
+}
+
+/// Extracts the violation messages from the JSON output of `opa eval`. If `deny` is undefined,
+/// there's no result and thus no violations.
+fn violations_from_opa_output(output: &str) -> Result<Vec<String>> {
+    let value: Value = serde_json::from_str(output).context("Failed to parse opa output")?;
+    let mut violations = Vec::new();
+    let results = value.get("result").and_then(Value::as_array);
+    for result in results.into_iter().flatten() {
+        let expressions = result.get("expressions").and_then(Value::as_array);
+        for expression in expressions.into_iter().flatten() {
+            match expression.get("value") {
+                Some(Value::Array(values)) => {
+                    violations.extend(values.iter().map(|v| match v {
+                        Value::String(message) => message.clone(),
+                        other => other.to_string(),
+                    }));
+                }
+                Some(Value::Bool(false)) | Some(Value::Null) | None => {}
+                Some(other) => bail!("`{DENY_QUERY}` should be a set, got `{other}`"),
+            }
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::violations_from_opa_output;
+
+    #[test]
+    fn parse_opa_output() {
+        assert_eq!(
+            violations_from_opa_output(
+                r#"{"result":[{"expressions":[{"value":["a uses net",{"pkg":"b"}],
+                "text":"data.cackle.deny","location":{"row":1,"col":1}}]}]}"#
+            )
+            .unwrap(),
+            vec!["a uses net".to_owned(), r#"{"pkg":"b"}"#.to_owned()]
+        );
+        assert!(violations_from_opa_output("{}").unwrap().is_empty());
+        assert!(
+            violations_from_opa_output(r#"{"result":[{"expressions":[{"value":"x"}]}]}"#).is_err()
+        );
+    }
+}
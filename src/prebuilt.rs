@@ -0,0 +1,167 @@
+//! Detection of executables that build scripts write into OUT_DIR. Some build scripts download
+//! prebuilt binaries and then run them. Such binaries haven't been through our analysis, so we
+//! report each one, identified by its hash, so that the user can decide whether to trust it. The
+//! sandbox doesn't tell us what got executed, so all we can see is which executables were left in
+//! OUT_DIR once the build script exited.
+
+use crate::binary_files::BinaryKind;
+use crate::binary_files::WalkError;
+use crate::binary_files::WalkedFile;
+use crate::binary_files::classify_magic;
+use crate::binary_files::walk_files;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use std::convert::Infallible;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) struct WrittenExecutable {
+    pub(crate) path: PathBuf,
+    pub(crate) sha256: String,
+}
+
+/// A file or directory that a build script wrote, but which we couldn't read, so can't say whether
+/// it's an executable.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) struct UnreadableOutput {
+    pub(crate) path: PathBuf,
+    pub(crate) error: String,
+}
+
+#[derive(Default, Debug)]
+pub(crate) struct WrittenExecutables {
+    pub(crate) executables: Vec<WrittenExecutable>,
+    pub(crate) unreadable: Vec<UnreadableOutput>,
+}
+
+/// Returns all executables in `dir` and its subdirectories. Symlinks aren't followed. Anything that
+/// we fail to read is returned as unreadable rather than failing the search, since a build script
+/// that leaves such files behind is something that the user should hear about, not a reason to
+/// abort the build.
+pub(crate) fn find_written_executables(dir: &Path) -> WrittenExecutables {
+    let mut found = WrittenExecutables::default();
+    let Ok(()) = walk_files(
+        dir,
+        |_| true,
+        |file| -> Result<(), Infallible> {
+            match file.and_then(written_executable) {
+                Ok(Some(executable)) => found.executables.push(executable),
+                Ok(None) => {}
+                Err(e) => found.unreadable.push(UnreadableOutput {
+                    path: e.path,
+                    error: e.error.to_string(),
+                }),
+            }
+            Ok(())
+        },
+    );
+    found.executables.sort_by(|a, b| a.path.cmp(&b.path));
+    found.unreadable.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// Returns `file` with its SHA-256 if it's an executable. We require the executable bit as well as
+/// a recognised format, so that e.g. libraries produced by the `cc` crate aren't included. Scripts
+/// can run arbitrary code too, so they count.
+fn written_executable(file: WalkedFile) -> Result<Option<WrittenExecutable>, WalkError> {
+    if file.metadata.permissions().mode() & 0o111 == 0 {
+        return Ok(None);
+    }
+    let bytes = match std::fs::read(&file.path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            return Err(WalkError {
+                path: file.path,
+                error,
+            });
+        }
+    };
+    let is_executable =
+        bytes.starts_with(b"#!") || classify_magic(&bytes).is_some_and(BinaryKind::is_executable);
+    Ok(is_executable.then(|| WrittenExecutable {
+        path: file.path,
+        sha256: sha256_hex(&bytes),
+    }))
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_written_executables;
+    use super::sha256_hex;
+    use crate::binary_files::testing::write_file;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    #[test]
+    fn written_executables() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let write =
+            |name: &str, contents: &[u8], mode: u32| write_file(dir.path(), name, contents, mode);
+        write("bin/tool", b"\x7fELF...", 0o755);
+        write("run.sh", b"#!/bin/sh\n", 0o755);
+        write("libfoo.a", b"!<arch>\n", 0o644);
+        write("data.bin", b"\x7fELF...", 0o644);
+        write("notes.txt", b"hello", 0o755);
+        write("Foo.class", b"\xca\xfe\xba\xbe\0\0\0\x3d", 0o755);
+        write("universal", b"\xca\xfe\xba\xbe\0\0\0\x02", 0o755);
+        let found = find_written_executables(dir.path());
+        let names: Vec<_> = found
+            .executables
+            .iter()
+            .map(|e| e.path.strip_prefix(dir.path()).unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Path::new("bin/tool"),
+                Path::new("run.sh"),
+                Path::new("universal")
+            ]
+        );
+        assert_eq!(found.executables[0].sha256, sha256_hex(b"\x7fELF..."));
+        assert!(found.unreadable.is_empty());
+    }
+
+    #[test]
+    fn unreadable_outputs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tool");
+        std::fs::write(&path, b"\x7fELF...").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o111)).unwrap();
+        let found = find_written_executables(dir.path());
+        // Root can read the file regardless of its mode, in which case it's just an executable.
+        if std::fs::read(&path).is_ok() {
+            assert_eq!(found.executables.len(), 1);
+            assert!(found.unreadable.is_empty());
+        } else {
+            assert!(found.executables.is_empty());
+            assert_eq!(found.unreadable.len(), 1);
+            assert_eq!(found.unreadable[0].path, path);
+        }
+
+        let missing = dir.path().join("missing");
+        let found = find_written_executables(&missing);
+        assert!(found.executables.is_empty());
+        assert_eq!(found.unreadable.len(), 1);
+        assert_eq!(found.unreadable[0].path, missing);
+    }
+
+    #[test]
+    fn hash() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -1,20 +1,36 @@
 //! Some problem - either an error or a permissions problem or similar. We generally collect
 //! multiple problems and report them all, although in the case of errors, we usually stop.
 
+use crate::async_runtime::MultipleAsyncRuntimes;
+use crate::binary_files::BinaryKind;
 use crate::checker::ApiUsage;
+use crate::checker::ReferenceKind;
+use crate::config::AllowedUsage;
 use crate::config::ApiConfig;
 use crate::config::ApiName;
 use crate::config::ApiPath;
+use crate::config::built_in::DYNAMIC_LOADING_API;
+use crate::config::built_in::PROCESS_API;
 use crate::config::permissions::PermSel;
 use crate::config::permissions::PermissionScope;
 use crate::crate_index::CrateKind;
 use crate::crate_index::CrateSel;
 use crate::crate_index::PackageId;
+use crate::crate_index::features::FeatureFix;
+use crate::location::SourceLocation;
+use crate::name_display::shorten;
+use crate::names::Name;
 use crate::names::SymbolOrDebugName;
+use crate::panic_audit::PanicPath;
 use crate::proxy::rpc::BinExecutionOutput;
 use crate::proxy::rpc::UnsafeUsage;
 use crate::symbol::Symbol;
+use crate::symbol_graph::object_file_path::ObjectFilePath;
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use serde::Serialize;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
@@ -31,12 +47,22 @@ pub(crate) enum Problem {
     Message(String),
     MissingConfiguration(PathBuf),
     UsesBuildScript(PackageId),
+    BuildScriptChanged(BuildScriptChanged),
     DisallowedUnsafe(UnsafeUsage),
     IsProcMacro(PackageId),
     DisallowedApiUsage(ApiUsages),
     OffTreeApiUsage(OffTreeApiUsage),
+    /// A package uses an API that it's allowed, but its version doesn't satisfy the config's
+    /// `pin_version`, so the usages need reviewing again.
+    VersionPinMismatch(VersionPinMismatch),
+    /// A package uses an API that it's allowed from more source locations than `max_usages`
+    /// permits.
+    UsageBudgetExceeded(UsageBudgetExceeded),
     ExecutionFailed(BinExecutionFailed),
     DisallowedBuildInstruction(DisallowedBuildInstruction),
+    ExecutableWritten(ExecutableWritten),
+    BinaryFileInSources(BinaryFileInSources),
+    DisallowedNativeLibrary(DisallowedNativeLibrary),
     UnusedPackageConfig(PermSel),
     UnusedAllowApi(UnusedAllowApi),
     SelectSandbox,
@@ -45,6 +71,28 @@ pub(crate) enum Problem {
     PossibleExportedApi(PossibleExportedApi),
     UnusedSandboxConfiguration(PermSel),
     NewConfigVersionAvailable(i64),
+    PolicyViolation(String),
+    PluginProblem(PluginProblem),
+    LtoBitcode(LtoBitcode),
+    MissingDebugInfo(MissingDebugInfo),
+    MissingObjects(MissingObjects),
+    MultipleAsyncRuntimes(MultipleAsyncRuntimes),
+    /// A package marked `no_panic` references panic machinery. The API is one of the panic audit's
+    /// pseudo-APIs. See `panic_audit.rs`.
+    DisallowedPanic(ApiUsages),
+    /// A package marked `no_alloc` references the memory allocator. The API is the allocator
+    /// audit's pseudo-API. See `alloc_audit.rs`.
+    DisallowedAlloc(ApiUsages),
+    /// A package references an item from `deny.symbols` without being exempted. The API is one of
+    /// the deny list's pseudo-APIs. See `deny_list.rs`.
+    DeniedSymbol(ApiUsages),
+    RustcVersionChanged(RustcVersionChanged),
+    StaleArtifacts(StaleArtifacts),
+    /// A problem that the user has said looks wrong. See `disputes.rs`.
+    Disputed(Box<Problem>),
+    /// A problem that the config says should only be a warning, e.g. a disallowed usage of an API
+    /// with `severity = "warn"`.
+    Warning(Box<Problem>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -59,6 +107,12 @@ pub(crate) struct ApiUsages {
     pub(crate) scope: PermissionScope,
     pub(crate) api_name: ApiName,
     pub(crate) usages: Vec<ApiUsage>,
+    /// If all the usages are only compiled with some cargo feature, the changes that would stop
+    /// that feature from being enabled.
+    pub(crate) feature_fix: Option<FeatureFix>,
+    /// Allowances that would permit each of the usages at its current location. Empty if some of
+    /// the usages aren't in source files that we could read.
+    pub(crate) usage_pins: Vec<AllowedUsage>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -67,6 +121,24 @@ pub(crate) struct OffTreeApiUsage {
     pub(crate) referenced_pkg_id: PackageId,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct VersionPinMismatch {
+    pub(crate) usages: ApiUsages,
+    /// The selector whose config sets `pin_version`.
+    pub(crate) perm_sel: PermSel,
+    pub(crate) pin_version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct UsageBudgetExceeded {
+    pub(crate) usages: ApiUsages,
+    /// The selector whose config sets the budget.
+    pub(crate) perm_sel: PermSel,
+    pub(crate) budget: usize,
+    /// How many distinct source locations use the API, over all the binaries checked so far.
+    pub(crate) count: usize,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct UnusedAllowApi {
     pub(crate) perm_sel: PermSel,
@@ -79,6 +151,99 @@ pub(crate) struct DisallowedBuildInstruction {
     pub(crate) instruction: String,
 }
 
+/// The rustc version used for the build differs from the version pinned in the config.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct RustcVersionChanged {
+    pub(crate) pinned: String,
+    pub(crate) active: String,
+}
+
+/// Inputs to the build that were modified after the artifacts that we're analysing were produced.
+/// This can happen when replaying saved requests.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct StaleArtifacts {
+    pub(crate) modified_inputs: Vec<PathBuf>,
+}
+
+/// Object files that contain LLVM bitcode rather than machine code, which happens when LTO is
+/// enabled. We can't find references in bitcode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct LtoBitcode {
+    pub(crate) bin_path: Arc<Path>,
+    pub(crate) profile: String,
+    pub(crate) object_paths: Vec<ObjectFilePath>,
+}
+
+/// A binary without the debug info or symbol table that most of our analysis relies on, which
+/// happens when the profile sets `debug = false` or `strip`. Only a reduced, symbol-only analysis was
+/// done for it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MissingDebugInfo {
+    pub(crate) bin_path: Arc<Path>,
+    pub(crate) profile: String,
+    /// Whether the symbol table was also missing.
+    pub(crate) stripped: bool,
+}
+
+/// Crates linked into a binary for which none of the files that we were given contained an object
+/// file, e.g. because they were `.rmeta` files from a pipelined build. We skip such files, but
+/// don't want the crates' API usages to go unchecked without saying so.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MissingObjects {
+    pub(crate) bin_path: Arc<Path>,
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+/// A build script whose hash doesn't match the one pinned in the config, or which has no pinned hash
+/// when hashes are required.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BuildScriptChanged {
+    pub(crate) pkg_id: PackageId,
+    pub(crate) expected: Option<String>,
+    pub(crate) sha256: String,
+}
+
+/// An executable that a build script wrote into OUT_DIR, most likely a prebuilt binary that it
+/// downloaded. We can't tell whether the build script ran it, only that it was still there once the
+/// build script exited.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ExecutableWritten {
+    pub(crate) pkg_id: PackageId,
+    pub(crate) path: PathBuf,
+    pub(crate) sha256: String,
+}
+
+/// A binary file, e.g. a precompiled executable, in a package's sources that isn't allowed by
+/// `allow_binary_files`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct BinaryFileInSources {
+    pub(crate) pkg_id: PackageId,
+    pub(crate) path: PathBuf,
+    pub(crate) kind: BinaryKind,
+    pub(crate) sha256: String,
+}
+
+/// Something that a checker plugin found. See `plugins.rs`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PluginProblem {
+    pub(crate) plugin: PathBuf,
+    pub(crate) pkg_id: Option<PackageId>,
+    /// The API that the plugin considers this to be a usage of.
+    pub(crate) api: Option<ApiName>,
+    /// Which of the package's permissions the usage of `api` was checked against.
+    pub(crate) scope: PermissionScope,
+    pub(crate) message: String,
+}
+
+/// A native shared library that a binary depends on, attributed to a package that isn't allowed it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DisallowedNativeLibrary {
+    pub(crate) pkg_id: PackageId,
+    pub(crate) perm_sel: PermSel,
+    pub(crate) library: String,
+    pub(crate) bin_path: Arc<Path>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct AvailableApi {
     pub(crate) pkg_id: PackageId,
@@ -92,7 +257,9 @@ pub(crate) struct AvailableApi {
 pub(crate) struct PossibleExportedApi {
     pub(crate) pkg_id: PackageId,
     pub(crate) api: ApiName,
-    pub(crate) symbol: Symbol<'static>,
+    /// The symbol that suggested the export, or `None` if rustdoc told us that the package has a
+    /// public module with the same name as the API.
+    pub(crate) symbol: Option<Symbol<'static>>,
 }
 
 impl PossibleExportedApi {
@@ -149,7 +316,7 @@ impl<'a> IntoIterator for &'a ProblemList {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum Severity {
     Warning,
     Error,
@@ -166,7 +333,11 @@ impl Problem {
             | Problem::UnusedPackageConfig(..)
             | Problem::PossibleExportedApi(..)
             | Problem::NewConfigVersionAvailable(..)
-            | Problem::AvailableApi(..) => Severity::Warning,
+            | Problem::RustcVersionChanged(..)
+            | Problem::StaleArtifacts(..)
+            | Problem::AvailableApi(..)
+            | Problem::Disputed(..)
+            | Problem::Warning(..) => Severity::Warning,
             _ => Severity::Error,
         }
     }
@@ -188,13 +359,58 @@ impl Problem {
                 scope: api_usage.scope,
                 api_name: api_usage.api_name.clone(),
                 usages: Default::default(),
+                feature_fix: None,
+                usage_pins: Vec::new(),
+            }),
+            Problem::DisallowedPanic(usages) => Problem::DisallowedPanic(ApiUsages {
+                usages: Default::default(),
+                ..usages.clone()
+            }),
+            Problem::DisallowedAlloc(usages) => Problem::DisallowedAlloc(ApiUsages {
+                usages: Default::default(),
+                ..usages.clone()
+            }),
+            Problem::DeniedSymbol(usages) => Problem::DeniedSymbol(ApiUsages {
+                usages: Default::default(),
+                ..usages.clone()
+            }),
+            Problem::OffTreeApiUsage(info) => Problem::OffTreeApiUsage(OffTreeApiUsage {
+                usages: ApiUsages {
+                    usages: Default::default(),
+                    feature_fix: None,
+                    usage_pins: Vec::new(),
+                    ..info.usages.clone()
+                },
+                referenced_pkg_id: info.referenced_pkg_id.clone(),
             }),
             Problem::PossibleExportedApi(info) => {
                 Problem::PossibleExportedApi(PossibleExportedApi {
-                    symbol: Symbol::borrowed(&[]),
+                    symbol: None,
                     ..info.clone()
                 })
             }
+            Problem::VersionPinMismatch(info) => Problem::VersionPinMismatch(VersionPinMismatch {
+                usages: ApiUsages {
+                    usages: Default::default(),
+                    feature_fix: None,
+                    usage_pins: Vec::new(),
+                    ..info.usages.clone()
+                },
+                ..info.clone()
+            }),
+            Problem::UsageBudgetExceeded(info) => {
+                Problem::UsageBudgetExceeded(UsageBudgetExceeded {
+                    usages: ApiUsages {
+                        usages: Default::default(),
+                        feature_fix: None,
+                        usage_pins: Vec::new(),
+                        ..info.usages.clone()
+                    },
+                    count: 0,
+                    ..info.clone()
+                })
+            }
+            Problem::Warning(problem) => Problem::Warning(Box::new(problem.deduplication_key())),
             _ => self.clone(),
         }
     }
@@ -202,8 +418,21 @@ impl Problem {
     /// Merges `other` into `self`. Should only be called with two problems that are not equal, but
     /// which have equal deduplication_keys.
     pub(crate) fn merge(&mut self, other: Problem) {
-        if let (Problem::DisallowedApiUsage(a), Problem::DisallowedApiUsage(b)) = (self, other) {
-            a.merge(b);
+        match (self, other) {
+            (Problem::DisallowedApiUsage(a), Problem::DisallowedApiUsage(b))
+            | (Problem::DisallowedPanic(a), Problem::DisallowedPanic(b))
+            | (Problem::DisallowedAlloc(a), Problem::DisallowedAlloc(b))
+            | (Problem::DeniedSymbol(a), Problem::DeniedSymbol(b)) => a.merge(b),
+            (Problem::OffTreeApiUsage(a), Problem::OffTreeApiUsage(b)) => a.usages.merge(b.usages),
+            (Problem::VersionPinMismatch(a), Problem::VersionPinMismatch(b)) => {
+                a.usages.merge(b.usages)
+            }
+            (Problem::UsageBudgetExceeded(a), Problem::UsageBudgetExceeded(b)) => {
+                a.usages.merge(b.usages);
+                a.count = a.count.max(b.count);
+            }
+            (Problem::Warning(a), Problem::Warning(b)) => a.merge(*b),
+            _ => {}
         }
     }
 
@@ -212,12 +441,18 @@ impl Problem {
             Problem::Message(_) => None,
             Problem::MissingConfiguration(_) => None,
             Problem::UsesBuildScript(pkg_id) => Some(pkg_id),
+            Problem::BuildScriptChanged(d) => Some(&d.pkg_id),
             Problem::DisallowedUnsafe(d) => Some(d.crate_sel.pkg_id()),
             Problem::IsProcMacro(pkg_id) => Some(pkg_id),
             Problem::DisallowedApiUsage(d) => Some(&d.pkg_id),
             Problem::OffTreeApiUsage(d) => Some(&d.usages.pkg_id),
+            Problem::VersionPinMismatch(d) => Some(&d.usages.pkg_id),
+            Problem::UsageBudgetExceeded(d) => Some(&d.usages.pkg_id),
             Problem::ExecutionFailed(d) => Some(d.crate_sel.pkg_id()),
             Problem::DisallowedBuildInstruction(d) => Some(&d.pkg_id),
+            Problem::ExecutableWritten(d) => Some(&d.pkg_id),
+            Problem::BinaryFileInSources(d) => Some(&d.pkg_id),
+            Problem::DisallowedNativeLibrary(d) => Some(&d.pkg_id),
             Problem::UnusedPackageConfig(_) => None,
             Problem::UnusedAllowApi(_) => None,
             Problem::SelectSandbox => None,
@@ -226,6 +461,18 @@ impl Problem {
             Problem::PossibleExportedApi(d) => Some(&d.pkg_id),
             Problem::UnusedSandboxConfiguration(_) => None,
             Problem::NewConfigVersionAvailable(_) => None,
+            Problem::PolicyViolation(_) => None,
+            Problem::PluginProblem(d) => d.pkg_id.as_ref(),
+            Problem::LtoBitcode(_) => None,
+            Problem::MissingDebugInfo(_) => None,
+            Problem::MissingObjects(_) => None,
+            Problem::MultipleAsyncRuntimes(_) => None,
+            Problem::DisallowedPanic(d) => Some(&d.pkg_id),
+            Problem::DisallowedAlloc(d) => Some(&d.pkg_id),
+            Problem::DeniedSymbol(d) => Some(&d.pkg_id),
+            Problem::RustcVersionChanged(_) => None,
+            Problem::StaleArtifacts(_) => None,
+            Problem::Disputed(problem) | Problem::Warning(problem) => problem.pkg_id(),
         }
     }
 }
@@ -240,6 +487,31 @@ impl Display for Problem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Problem::Message(message) => write!(f, "{message}")?,
+            Problem::PolicyViolation(message) => write!(f, "Policy violation: {message}")?,
+            Problem::PluginProblem(info) => {
+                if let Some(pkg_id) = &info.pkg_id {
+                    write!(f, "`{pkg_id}`: ")?;
+                }
+                write!(f, "{}", info.message)?;
+                if f.alternate() {
+                    write!(f, "\nReported by plugin `{}`", info.plugin.display())?;
+                    if let (Some(pkg_id), Some(api)) = (&info.pkg_id, &info.api) {
+                        write!(
+                            f,
+                            " as a usage of API `{api}`, which `{}` isn't allowed",
+                            PermSel::with_scope(pkg_id, info.scope)
+                        )?;
+                    }
+                }
+            }
+            Problem::Disputed(problem) => {
+                if f.alternate() {
+                    write!(f, "Disputed: {problem:#}")?
+                } else {
+                    write!(f, "Disputed: {problem}")?
+                }
+            }
+            Problem::Warning(problem) => problem.fmt(f)?,
             Problem::NewConfigVersionAvailable(version) => {
                 write!(f, "Newer config version {version} is available")?
             }
@@ -259,6 +531,7 @@ impl Display for Problem {
                     CrateSel::primary(pkg_id.clone()),
                 )?;
             }
+            Problem::BuildScriptChanged(info) => info.fmt(f)?,
             Problem::IsProcMacro(pkg_name) => write!(
                 f,
                 "`{}` is a proc macro",
@@ -276,6 +549,39 @@ impl Display for Problem {
                     display_usages(f, &info.usages.usages)?;
                 }
             }
+            Problem::VersionPinMismatch(info) => {
+                write!(
+                    f,
+                    "`{}` uses API `{}`, which is only allowed for versions `{}` of the package",
+                    info.usages.pkg_id, info.usages.api_name, info.pin_version
+                )?;
+                if f.alternate() {
+                    writeln!(f)?;
+                    writeln!(
+                        f,
+                        "  Version {} needs reviewing. Once you're happy with it, update \
+                         `pkg.{}.pin_version`",
+                        info.usages.pkg_id.version(),
+                        info.perm_sel
+                    )?;
+                    display_usages(f, &info.usages.usages)?;
+                }
+            }
+            Problem::UsageBudgetExceeded(info) => {
+                write!(
+                    f,
+                    "`{}` uses API `{}` from {} locations, but `pkg.{}.max_usages` only allows {}",
+                    info.usages.pkg_id,
+                    info.usages.api_name,
+                    info.count,
+                    info.perm_sel,
+                    info.budget
+                )?;
+                if f.alternate() {
+                    writeln!(f)?;
+                    display_usages(f, &info.usages.usages)?;
+                }
+            }
             Problem::ExecutionFailed(info) => info.fmt(f)?,
             Problem::DisallowedBuildInstruction(info) => {
                 write!(
@@ -285,6 +591,60 @@ impl Display for Problem {
                     info.instruction
                 )?;
             }
+            Problem::ExecutableWritten(info) => {
+                write!(
+                    f,
+                    "{}'s build script wrote executable `{}` to OUT_DIR",
+                    CrateSel::primary(info.pkg_id.clone()),
+                    info.path.file_name().unwrap_or_default().to_string_lossy()
+                )?;
+                if f.alternate() {
+                    write!(
+                        f,
+                        "\nPath: {}\nSHA-256: {}\nThis is most likely a prebuilt binary that \
+                         the build script downloaded. Cackle can't tell whether the build script \
+                         ran it, but if it did, then code was executed that hasn't been analysed. \
+                         Only allow it if you trust where it came from.",
+                        info.path.display(),
+                        info.sha256
+                    )?;
+                }
+            }
+            Problem::BinaryFileInSources(info) => {
+                write!(
+                    f,
+                    "`{}` contains {} `{}`",
+                    CrateSel::primary(info.pkg_id.clone()),
+                    info.kind,
+                    info.path.file_name().unwrap_or_default().to_string_lossy()
+                )?;
+                if f.alternate() {
+                    write!(
+                        f,
+                        "\nPath: {}\nSHA-256: {}\nBinary files in a package's sources haven't \
+                         been analysed, but a build script or proc macro could run them, or they \
+                         could be linked into your binaries. Only allow it if you trust where it \
+                         came from.",
+                        info.path.display(),
+                        info.sha256
+                    )?;
+                }
+            }
+            Problem::DisallowedNativeLibrary(info) => {
+                write!(
+                    f,
+                    "`{}` links native library `{}`",
+                    info.perm_sel, info.library
+                )?;
+                if f.alternate() {
+                    write!(
+                        f,
+                        "\nBinary: {}\nNative libraries contain code that hasn't been analysed. \
+                         Only allow it if you trust the library.",
+                        info.bin_path.display()
+                    )?;
+                }
+            }
             Problem::UnusedPackageConfig(pkg_name) => {
                 write!(
                     f,
@@ -307,13 +667,24 @@ impl Display for Problem {
             }
             Problem::PossibleExportedApi(info) => {
                 if f.alternate() {
-                    write!(
-                        f,
-                        "Package `{}` provides symbol `{}`. A top-level module has the same name \
-                         as the API `{}`. If this module is public (we can't tell), then consider \
-                         adjusting the API includes.",
-                        info.pkg_id, info.symbol, info.api
-                    )?;
+                    if let Some(symbol) = &info.symbol {
+                        write!(
+                            f,
+                            "Package `{}` provides symbol `{symbol}`. A top-level module has the \
+                             same name as the API `{}`. If this module is public (we can't tell), \
+                             then consider adjusting the API includes.",
+                            info.pkg_id, info.api
+                        )?;
+                    } else {
+                        write!(
+                            f,
+                            "Package `{}` publicly exports `{}` at its top level, which has the \
+                             same name as the API `{}`. Consider adjusting the API includes.",
+                            info.pkg_id,
+                            info.api_path(),
+                            info.api
+                        )?;
+                    }
                 } else {
                     write!(
                         f,
@@ -329,6 +700,74 @@ impl Display for Problem {
                      Perhaps you meant to configure `{crate_name}.build.sandbox`"
                 )?;
             }
+            Problem::LtoBitcode(info) => info.fmt(f)?,
+            Problem::MissingDebugInfo(info) => info.fmt(f)?,
+            Problem::MissingObjects(info) => info.fmt(f)?,
+            Problem::MultipleAsyncRuntimes(info) => info.fmt(f)?,
+            Problem::DisallowedPanic(info) => {
+                let kind = PanicPath::from_api_name(&info.api_name)
+                    .map(|kind| kind.to_string())
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "`{}` is marked `no_panic`, but references panic machinery ({kind})",
+                    info.pkg_id
+                )?;
+                if f.alternate() {
+                    writeln!(f)?;
+                    display_usages(f, &info.usages)?;
+                    // The locations above are within any inlined code, so for that, say where it was
+                    // called from too.
+                    let call_sites: BTreeSet<_> = info
+                        .usages
+                        .iter()
+                        .filter(|usage| !usage.inline_chain.is_empty())
+                        .map(crate::panic_audit::call_site)
+                        .filter(|location| location.line() != 0)
+                        .collect();
+                    if !call_sites.is_empty() {
+                        writeln!(f, "  Inlined code that can panic is called from:")?;
+                        for location in call_sites {
+                            writeln!(f, "    {location}")?;
+                        }
+                    }
+                }
+            }
+            Problem::DisallowedAlloc(info) => {
+                write!(
+                    f,
+                    "`{}` is marked `no_alloc`, but references the memory allocator",
+                    info.pkg_id
+                )?;
+                if f.alternate() {
+                    writeln!(f)?;
+                    display_usages(f, &info.usages)?;
+                }
+            }
+            Problem::DeniedSymbol(info) => {
+                let path = crate::deny_list::denied_path(&info.api_name).unwrap_or_default();
+                write!(f, "`{}` references denied item `{path}`", info.pkg_id)?;
+                if f.alternate() {
+                    writeln!(f)?;
+                    display_usages(f, &info.usages)?;
+                }
+            }
+            Problem::RustcVersionChanged(info) => {
+                write!(
+                    f,
+                    "rustc version {} differs from pinned version {}",
+                    info.active, info.pinned
+                )?;
+                if f.alternate() {
+                    write!(
+                        f,
+                        "\nSymbol mangling and debug info can change between rustc versions, so \
+                         results may differ from those previously reviewed. Once you're happy \
+                         with the results, update `common.rustc_version`."
+                    )?;
+                }
+            }
+            Problem::StaleArtifacts(info) => info.fmt(f)?,
         }
         Ok(())
     }
@@ -337,30 +776,64 @@ impl Display for Problem {
 impl Display for ApiUsages {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
-            writeln!(
+            write!(
                 f,
                 "'{}' uses disallowed API `{}`",
                 self.pkg_id, self.api_name
             )?;
+            display_scope(f, self.scope)?;
+            writeln!(f)?;
             display_usages(f, &self.usages)?;
+            if self.api_name.as_ref() == DYNAMIC_LOADING_API {
+                writeln!(
+                    f,
+                    "  Note: Code that is loaded at runtime isn't analysed, so it could use any API"
+                )?;
+            }
+            if self.api_name.as_ref() == PROCESS_API {
+                writeln!(
+                    f,
+                    "  Warning: HIGH RISK. Programs that are run aren't analysed, so could do \
+                     anything that the user can"
+                )?;
+            }
+            if let Some(fix) = &self.feature_fix {
+                writeln!(
+                    f,
+                    "  All usages are only compiled with feature `{}`. Preferred fix:",
+                    fix.feature
+                )?;
+                for change in &fix.changes {
+                    writeln!(f, "    {change}")?;
+                }
+            }
         } else {
             write!(f, "`{}` uses the `{}` API", self.pkg_id, self.api_name)?;
-            match self.scope {
-                PermissionScope::All => {}
-                PermissionScope::Build => " in its build script".fmt(f)?,
-                PermissionScope::Test => " in its test(s)".fmt(f)?,
-                PermissionScope::FromBuild => {
-                    " in code included in a build script from another package".fmt(f)?
-                }
-                PermissionScope::FromTest => {
-                    " in code included in a test from another package".fmt(f)?
-                }
+            display_scope(f, self.scope)?;
+            if self.api_name.as_ref() == PROCESS_API {
+                write!(f, " (high risk)")?;
             }
         }
         Ok(())
     }
 }
 
+/// Describes where usages in `scope` were found, e.g. in a test.
+fn display_scope(
+    f: &mut std::fmt::Formatter,
+    scope: PermissionScope,
+) -> Result<(), std::fmt::Error> {
+    match scope {
+        PermissionScope::All => Ok(()),
+        PermissionScope::Build => " in its build script".fmt(f),
+        PermissionScope::Test => " in its test(s)".fmt(f),
+        PermissionScope::FromBuild => {
+            " in code included in a build script from another package".fmt(f)
+        }
+        PermissionScope::FromTest => " in code included in a test from another package".fmt(f),
+    }
+}
+
 impl Display for UnusedAllowApi {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
@@ -375,6 +848,129 @@ impl Display for UnusedAllowApi {
     }
 }
 
+impl Display for BuildScriptChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.expected.is_some() {
+            write!(
+                f,
+                "Build script for `{}` has changed since it was pinned",
+                self.pkg_id
+            )?;
+        } else {
+            write!(f, "Build script for `{}` isn't pinned", self.pkg_id)?;
+        }
+        if f.alternate() {
+            if let Some(expected) = &self.expected {
+                write!(f, "\nPinned SHA-256: {expected}")?;
+            }
+            write!(
+                f,
+                "\nSHA-256: {}\nBuild scripts run arbitrary code at build time. Review the build \
+                 script's sources and any executables that it wrote, then pin the new hash.",
+                self.sha256
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for StaleArtifacts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} input(s) modified since the artifacts being analysed were built",
+            self.modified_inputs.len()
+        )?;
+        if f.alternate() {
+            writeln!(
+                f,
+                "\nWhat's found may not correspond to the current code. To fix, rerun without \
+                 `--replay-requests` or `--artifacts` so that everything gets rebuilt. Modified \
+                 inputs:"
+            )?;
+            for path in &self.modified_inputs {
+                writeln!(f, "  {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for LtoBitcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Objects linked into `{}` contain LLVM bitcode, which can't be analysed",
+            self.bin_path.display()
+        )?;
+        if f.alternate() {
+            writeln!(
+                f,
+                "\nThis happens when LTO is enabled. To fix, disable LTO for \
+                 profile `{0}`, e.g. by adding the following to Cargo.toml:\n\n\
+                 [profile.{0}]\nlto = \"off\"\n\n\
+                 Also check that `-Clinker-plugin-lto` isn't passed via RUSTFLAGS. Alternatively, \
+                 set `disassemble = true` for packages in cackle.toml to find their direct calls \
+                 via the linked binary. Objects containing bitcode:",
+                self.profile
+            )?;
+            for path in &self.object_paths {
+                writeln!(f, "  {path}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for MissingDebugInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let missing = if self.stripped {
+            "was stripped"
+        } else {
+            "has no debug info"
+        };
+        write!(
+            f,
+            "`{}` {missing}, so only a reduced analysis was possible",
+            self.bin_path.display()
+        )?;
+        if f.alternate() {
+            writeln!(
+                f,
+                "\nAPI usages were found in the object files linked into it and attributed to \
+                 packages by symbol name alone. Source locations, inlined code and calls through \
+                 vtables weren't available, so some usages may have been missed. To fix, enable \
+                 debug info for profile `{0}`, e.g. by adding the following to Cargo.toml:\n\n\
+                 [profile.{0}]\ndebug = true\nstrip = false",
+                self.profile
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MissingObjects {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No object files were found for some crates linked into `{}`",
+            self.bin_path.display()
+        )?;
+        if f.alternate() {
+            writeln!(
+                f,
+                "\nAPI usages by these crates weren't checked. This can happen when a build \
+                 leaves only metadata (`.rmeta`) for a crate. Rebuilding, e.g. after `cargo clean`, \
+                 should fix it. Files without objects:"
+            )?;
+            for path in &self.paths {
+                writeln!(f, "  {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Display for BinExecutionFailed {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let pkg_id = &self.output.crate_sel.pkg_id;
@@ -404,7 +1000,7 @@ impl Display for BinExecutionFailed {
     }
 }
 
-fn display_usages(
+pub(crate) fn display_usages(
     f: &mut std::fmt::Formatter,
     usages: &Vec<ApiUsage>,
 ) -> Result<(), std::fmt::Error> {
@@ -423,19 +1019,52 @@ fn display_usages(
             by_from.entry(&usage.from).or_default().push(usage);
         }
         for (from, local_usages) in &by_from {
-            writeln!(f, "    {from}")?;
+            writeln!(f, "    {}", shorten(from))?;
             for u in local_usages {
-                write!(f, "      -> {} [{}", u.to_source, u.source_location.line(),)?;
-                if let Some(column) = u.source_location.column() {
-                    write!(f, ":{column}")?;
+                write!(f, "      -> {}", shorten(&u.to_source))?;
+                if u.kind != ReferenceKind::Call {
+                    write!(f, " ({})", u.kind)?;
+                }
+                // Lines start at 1, so 0 means that we don't know the line, e.g. for a binary
+                // without debug info.
+                if u.source_location.line() != 0 {
+                    write!(f, " [{}", u.source_location.line())?;
+                    if let Some(column) = u.source_location.column() {
+                        write!(f, ":{column}")?;
+                    }
+                    write!(f, "]")?;
+                }
+                writeln!(f)?;
+                if !u.callers.is_empty() {
+                    writeln!(f, "         via {}", display_call_path(u))?;
+                }
+                if !u.inline_chain.is_empty() {
+                    writeln!(f, "         inlined via {}", display_inline_chain(u))?;
                 }
-                writeln!(f, "]")?;
             }
         }
     }
     Ok(())
 }
 
+/// Returns the names of the functions in the usage's inline chain.
+pub(crate) fn display_inline_chain(usage: &ApiUsage) -> String {
+    usage
+        .inline_chain
+        .iter()
+        .map(|frame| shorten(&frame.function))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+pub(crate) fn display_call_path(usage: &ApiUsage) -> String {
+    usage
+        .call_path()
+        .map(shorten)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
 impl From<Problem> for ProblemList {
     fn from(value: Problem) -> Self {
         Self {
@@ -454,11 +1083,36 @@ impl std::hash::Hash for ApiUsages {
 }
 
 impl ApiUsages {
-    fn merge(&mut self, mut b: ApiUsages) {
+    /// Merges usages found in another binary, or another build of the same binary. Usages at a
+    /// source location that we already have a usage of the same name for are dropped, since
+    /// otherwise in a workspace with several binaries, code shared between binaries gets reported
+    /// once for each binary.
+    fn merge(&mut self, b: ApiUsages) {
         if self.pkg_id != b.pkg_id || self.api_name != b.api_name || self.scope != b.scope {
             panic!("Attempted to merge ApiUsages with incompatible attributes");
         }
-        self.usages.append(&mut b.usages);
+        let existing: FxHashSet<(&SourceLocation, &Name)> = self
+            .usages
+            .iter()
+            .map(|u| (&u.source_location, &u.to_name))
+            .collect();
+        let new_usages: Vec<ApiUsage> = b
+            .usages
+            .into_iter()
+            .filter(|u| !existing.contains(&(&u.source_location, &u.to_name)))
+            .collect();
+        self.usages.extend(new_usages);
+        // The fix only applies if it'd remove all the usages.
+        if self.feature_fix != b.feature_fix {
+            self.feature_fix = None;
+        }
+        if self.usage_pins.is_empty() || b.usage_pins.is_empty() {
+            self.usage_pins.clear();
+        } else {
+            self.usage_pins.extend(b.usage_pins);
+            self.usage_pins.sort();
+            self.usage_pins.dedup();
+        }
     }
 
     pub(crate) fn with_usages(&self, usages: Vec<ApiUsage>) -> Self {
@@ -467,6 +1121,8 @@ impl ApiUsages {
             scope: self.scope,
             api_name: self.api_name.clone(),
             usages,
+            feature_fix: self.feature_fix.clone(),
+            usage_pins: Vec::new(),
         }
     }
 
@@ -474,3 +1130,31 @@ impl ApiUsages {
         PermSel::with_scope(&self.pkg_id, self.scope)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::testing::api_usage;
+    use crate::checker::testing::api_usages;
+    use crate::config::built_in::DYNAMIC_LOADING_API;
+    use crate::config::built_in::PROCESS_API;
+
+    #[test]
+    fn api_usage_notes() {
+        let usages = |api| api_usages("foo", api, vec![api_usage("foo", "foo::run", "x::y")]);
+
+        let process = usages(PROCESS_API);
+        assert!(format!("{process:#}").contains("Warning: HIGH RISK."));
+        assert_eq!(
+            process.to_string(),
+            "`foo` uses the `process` API (high risk)"
+        );
+
+        let dynamic_loading = usages(DYNAMIC_LOADING_API);
+        assert!(format!("{dynamic_loading:#}").contains("Note: Code that is loaded at runtime"));
+        assert!(!format!("{dynamic_loading:#}").contains("HIGH RISK"));
+        assert_eq!(
+            dynamic_loading.to_string(),
+            "`foo` uses the `dynamic_loading` API"
+        );
+    }
+}
@@ -244,10 +244,21 @@ impl<'a> Iterator for ProblemStoreIterator<'a> {
 #[cfg(test)]
 mod tests {
     use super::ProblemStore;
+    use crate::checker::ApiUsage;
+    use crate::checker::BinLocation;
+    use crate::checker::ReferenceKind;
+    use crate::config::permissions::PermissionScope;
     use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::problem::ApiUsages;
     use crate::problem::Problem;
     use crate::problem::ProblemList;
     use crate::problem_store::ProblemId;
+    use crate::symbol::Symbol;
+    use crate::symbol_graph::NameSource;
+    use std::path::Path;
+    use std::sync::Arc;
     use std::sync::mpsc::TryRecvError;
     use std::sync::mpsc::channel;
 
@@ -323,6 +334,62 @@ mod tests {
         assert_eq!(done2.try_recv(), Ok(crate::outcome::Outcome::GiveUp));
     }
 
+    fn usage(bin: &str, line: u32, to: &str) -> ApiUsage {
+        ApiUsage {
+            bin_location: BinLocation {
+                address: u64::from(line),
+                symbol_start: 0,
+            },
+            bin_path: Arc::from(Path::new(bin)),
+            permission_scope: PermissionScope::All,
+            source_location: SourceLocation::new(Path::new("lib.rs"), line, None),
+            outer_location: None,
+            from: SymbolOrDebugName::Symbol(Symbol::borrowed(b"f")),
+            to: SymbolOrDebugName::Symbol(Symbol::borrowed(&[])),
+            to_name: crate::names::split_simple(to),
+            to_source: NameSource::Symbol(Symbol::borrowed(&[])),
+            kind: ReferenceKind::Call,
+            origin: Default::default(),
+            debug_data: None,
+            callers: Vec::new(),
+            inline_chain: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn usages_from_multiple_binaries() {
+        let problems = |usages| {
+            ProblemList::from(Problem::DisallowedApiUsage(ApiUsages {
+                pkg_id: pkg_id("crab1"),
+                scope: PermissionScope::All,
+                api_name: "fs".into(),
+                usages,
+                feature_fix: None,
+                usage_pins: Vec::new(),
+            }))
+        };
+        let mut store = ProblemStore::new(channel().0);
+        store.add(problems(vec![
+            usage("bin1", 1, "std::fs::read"),
+            usage("bin1", 1, "std::fs::write"),
+        ]));
+        store.add(problems(vec![
+            usage("bin2", 1, "std::fs::read"),
+            usage("bin2", 2, "std::fs::read"),
+        ]));
+        let all: Vec<_> = store.deduplicated_into_iter().collect();
+        assert_eq!(all.len(), 1);
+        let Problem::DisallowedApiUsage(usages) = all[0].1 else {
+            panic!("Unexpected problem {}", all[0].1);
+        };
+        let found: Vec<_> = usages
+            .usages
+            .iter()
+            .map(|u| (u.bin_path.to_str().unwrap(), u.source_location.line()))
+            .collect();
+        assert_eq!(found, vec![("bin1", 1), ("bin1", 1), ("bin2", 2)]);
+    }
+
     #[test]
     fn deduplicated_iteration() {
         let mut store = ProblemStore::new(channel().0);
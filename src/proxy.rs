@@ -28,9 +28,13 @@ use crate::config::Config;
 use crate::crate_index::CrateIndex;
 use crate::outcome::ExitCode;
 use crate::outcome::Outcome;
+use crate::resources::PermitKind;
+use crate::resources::ResourceManager;
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::bail;
+use std::fmt::Display;
+use std::io::Read;
 use std::io::Write;
 use std::os::unix::net::UnixListener;
 use std::os::unix::net::UnixStream;
@@ -38,6 +42,7 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 use std::process::Stdio;
+use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use std::sync::mpsc::channel;
@@ -55,6 +60,11 @@ const ORIG_LINKER_ENV: &str = "CACKLE_ORIG_LINKER";
 pub(crate) const TARGET_DIR: &str = "CACKLE_TARGET_DIR";
 pub(crate) const MANIFEST_DIR: &str = "CACKLE_MANIFEST_DIR";
 const RUSTC_PATH: &str = "CACKLE_RUSTC_PATH";
+/// Set when our rustc wrapper should add diagnostics from the parent process to rustc's output.
+const RUSTC_DIAGNOSTICS_ENV: &str = "CACKLE_RUSTC_DIAGNOSTICS";
+/// Set when our rustc wrapper should also document libraries with rustdoc, so that we can see what
+/// they export.
+const RUSTDOC_EXPORTS_ENV: &str = "CACKLE_RUSTDOC_EXPORTS";
 
 /// Environment variables that we need to allow through to rustc when we run rustc in a sandbox.
 pub(crate) const RUSTC_ENV_VARS: &[&str] = &[
@@ -70,6 +80,7 @@ pub(crate) struct CargoRunner<'a> {
     pub(crate) args: &'a Args,
     pub(crate) crate_index: &'a CrateIndex,
     pub(crate) target_dir: &'a Path,
+    pub(crate) resources: &'a Arc<ResourceManager>,
 }
 
 #[derive(Default)]
@@ -99,6 +110,20 @@ impl CargoRunner<'_> {
         abort_recv: Receiver<()>,
         abort_sender: Sender<()>,
         request_creator: impl Fn(Request) -> RequestHandler,
+    ) -> Result<CargoOutputWaiter, ProxyError> {
+        self.run_cargo_build(abort_recv, abort_sender, request_creator)
+            .map_err(|error| {
+                error
+                    .downcast::<ProxyError>()
+                    .unwrap_or_else(ProxyError::Other)
+            })
+    }
+
+    fn run_cargo_build(
+        &self,
+        abort_recv: Receiver<()>,
+        abort_sender: Sender<()>,
+        request_creator: impl Fn(Request) -> RequestHandler,
     ) -> Result<CargoOutputWaiter> {
         if !std::env::var(SOCKET_ENV).unwrap_or_default().is_empty() {
             panic!("{SOCKET_ENV} is already set. Missing call to handle_wrapped_binaries?");
@@ -124,7 +149,10 @@ impl CargoRunner<'_> {
         for pkg in &self.args.package {
             command.arg("-p").arg(pkg);
         }
-        if self.args.command.is_none() {
+        if let Some(jobs) = self.resources.cargo_jobs() {
+            command.arg("--jobs").arg(jobs.to_string());
+        }
+        if self.args.is_default_build() {
             let default_build_flags = ["--all-targets".to_owned()];
             for flag in self
                 .config
@@ -136,6 +164,9 @@ impl CargoRunner<'_> {
             {
                 command.arg(flag);
             }
+            if self.args.include_tests {
+                command.arg("--tests").arg("--benches");
+            }
         }
         let rustc_path = rustup_rustc_path().unwrap_or_else(|_| PathBuf::from("rustc"));
         if let Some(target) = &self.args.target {
@@ -158,6 +189,12 @@ impl CargoRunner<'_> {
             .env(MANIFEST_DIR, self.manifest_dir)
             .env(RUSTC_PATH, rustc_path)
             .env("RUSTC_WRAPPER", cackle_exe()?);
+        if self.args.rustc_diagnostics {
+            command.env(RUSTC_DIAGNOSTICS_ENV, "1");
+        }
+        if self.config.raw.common.rustdoc_exports {
+            command.env(RUSTDOC_EXPORTS_ENV, "1");
+        }
 
         self.crate_index.add_internal_env(&mut command);
 
@@ -195,10 +232,10 @@ impl CargoRunner<'_> {
                 // Deleting the socket is best-effort only, so we don't report an error if we can't.
                 let _ = std::fs::remove_file(&ipc_path);
                 if let Ok(error) = error_recv.try_recv() {
-                    return Err(error);
+                    return Err(ProxyError::Request(error).into());
                 }
                 if status.code() != Some(0) {
-                    bail!("`cargo` exited with non-zero exit status");
+                    return Err(ProxyError::CargoFailed.into());
                 }
                 break;
             }
@@ -212,6 +249,13 @@ impl CargoRunner<'_> {
             if let Ok((mut connection, _)) = listener.accept() {
                 let request: rpc::Request = rpc::read_from_stream(&mut connection)
                     .context("Malformed request from subprocess")?;
+                if let Request::AcquirePermit(kind) = request {
+                    let resources = self.resources.clone();
+                    std::thread::Builder::new()
+                        .name("Permit holder".to_owned())
+                        .spawn(move || hold_permit(&resources, kind, connection))?;
+                    continue;
+                }
                 let request_handler = (request_creator)(request);
                 let error_send = error_send.clone();
                 let abort_sender = abort_sender.clone();
@@ -234,6 +278,37 @@ impl CargoRunner<'_> {
     }
 }
 
+/// Errors from running cargo.
+#[derive(Debug)]
+pub(crate) enum ProxyError {
+    /// Cargo exited with a non-zero exit status.
+    CargoFailed,
+
+    /// Handling a request from one of our subprocesses failed.
+    Request(anyhow::Error),
+
+    /// We failed to run cargo or to communicate with our subprocesses.
+    Other(anyhow::Error),
+}
+
+impl Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::CargoFailed => write!(f, "`cargo` exited with non-zero exit status"),
+            ProxyError::Request(error) | ProxyError::Other(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProxyError::CargoFailed => None,
+            ProxyError::Request(error) | ProxyError::Other(error) => error.source(),
+        }
+    }
+}
+
 /// Returns the path to rustc as provided by rustup. If rustup is available, then we bypass it when
 /// running rustc, since rustup sometimes (at least in CI) seems to write to ~/.rustup, which our
 /// sandbox configuration doesn't allow. We don't want to allow write access to ~/.rustup because
@@ -295,6 +370,11 @@ fn process_request(
     mut connection: UnixStream,
     abort_sender: Sender<()>,
 ) -> Result<()> {
+    if let Some(Request::TakeDiagnostics(crate_sel)) = &request_handler.request {
+        let diagnostics = request_handler.take_diagnostics(crate_sel);
+        rpc::write_to_stream(&diagnostics, &mut connection)?;
+        return Ok(());
+    }
     let response = request_handler.handle_request();
     let can_continue = response.as_ref().unwrap_or(&Outcome::GiveUp);
     if can_continue == &Outcome::GiveUp {
@@ -307,6 +387,16 @@ fn process_request(
     Ok(())
 }
 
+/// Gives a slot to a subprocess once one is free, then holds it for the subprocess until it closes
+/// its connection, which it does when it's done or if it dies.
+fn hold_permit(resources: &ResourceManager, kind: PermitKind, mut connection: UnixStream) {
+    let _permit = resources.acquire(kind);
+    if rpc::write_to_stream(&Outcome::Continue, &mut connection).is_ok() {
+        // Nothing more gets sent, so this returns once the connection is closed.
+        let _ = connection.read(&mut [0]);
+    }
+}
+
 fn run_command(command: &mut Command) -> Result<ExitCode> {
     Ok(command
         .status()
@@ -69,8 +69,10 @@ pub(crate) fn command(
         .arg("--config")
         .arg(format!("profile.{DEFAULT_PROFILE_NAME}.incremental=false"));
 
-    // We don't currently support split debug info.
-    command.arg("--config").arg("split-debuginfo=\"off\"");
+    // Split debug info is supported, but it's simpler and faster if everything is in the binary.
+    command.arg("--config").arg(format!(
+        "profile.{DEFAULT_PROFILE_NAME}.split-debuginfo=\"off\""
+    ));
 
     let profile = profile_name(args, config);
     command.arg("--profile").arg(profile);
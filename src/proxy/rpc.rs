@@ -1,10 +1,14 @@
 //! Defines the communication protocol between the proxy subprocesses and the parent process.
 
+use crate::config::RemapPathPrefix;
 use crate::config::SandboxConfig;
 use crate::crate_index::CrateSel;
 use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
 use crate::outcome::Outcome;
+use crate::prebuilt::UnreadableOutput;
+use crate::prebuilt::WrittenExecutable;
+use crate::resources::PermitKind;
 use anyhow::Context;
 use anyhow::Result;
 use serde::Deserialize;
@@ -29,11 +33,13 @@ impl RpcClient {
     pub(crate) fn crate_uses_unsafe(
         &self,
         crate_sel: &CrateSel,
+        pkg_dir: PathBuf,
         locations: Vec<SourceLocation>,
     ) -> Result<Outcome> {
         let mut ipc = self.connect()?;
         let request = Request::CrateUsesUnsafe(UnsafeUsage {
             crate_sel: crate_sel.clone(),
+            pkg_dir,
             locations,
         });
         write_to_stream(&request, &mut ipc)?;
@@ -65,6 +71,23 @@ impl RpcClient {
         read_from_stream(&mut ipc)
     }
 
+    /// Returns diagnostics, as lines of rustc's JSON diagnostic format, that the parent process would
+    /// like to be shown as part of the output of rustc for `crate_sel`. See `rustc_diagnostics.rs`.
+    pub(crate) fn take_diagnostics(&self, crate_sel: &CrateSel) -> Result<Vec<String>> {
+        let mut ipc = self.connect()?;
+        write_to_stream(&Request::TakeDiagnostics(crate_sel.clone()), &mut ipc)?;
+        read_from_stream(&mut ipc)
+    }
+
+    /// Waits until the parent process gives us a slot of the specified kind. The slot is held until
+    /// the returned permit is dropped. See `resources.rs`.
+    pub(crate) fn acquire_permit(&self, kind: PermitKind) -> Result<HeldPermit> {
+        let mut ipc = self.connect()?;
+        write_to_stream(&Request::AcquirePermit(kind), &mut ipc)?;
+        let _: Outcome = read_from_stream(&mut ipc)?;
+        Ok(HeldPermit { _connection: ipc })
+    }
+
     /// Creates a new connection to the socket. We only send a single request/response on each
     /// connection because it makes things simpler. In general a single request/response is all we
     /// need anyway.
@@ -78,7 +101,7 @@ impl RpcClient {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub(crate) enum Request {
     /// Advises that the specified crate failed to compile because it uses unsafe.
     CrateUsesUnsafe(UnsafeUsage),
@@ -86,6 +109,19 @@ pub(crate) enum Request {
     BinExecutionComplete(BinExecutionOutput),
     RustcStarted(CrateSel),
     RustcComplete(RustcOutput),
+    /// Requests diagnostics to be added to the output of rustc. Unlike other requests, the response
+    /// is a list of diagnostics rather than an `Outcome`.
+    TakeDiagnostics(CrateSel),
+    /// Requests a slot for running something. The response is sent once the slot is free. The slot
+    /// is then held until the connection is closed. These are handled by the proxy rather than the
+    /// checker and aren't recorded with other requests.
+    AcquirePermit(PermitKind),
+}
+
+/// A slot given to us by the parent process, which is returned when this is dropped, by closing our
+/// connection.
+pub(crate) struct HeldPermit {
+    _connection: UnixStream,
 }
 
 /// The output from running a binary such as a build script or a test.
@@ -100,20 +136,63 @@ pub(crate) struct BinExecutionOutput {
     /// A display string for how the sandbox was configured (e.g. the command line). Only present if
     /// the exit code is non-zero.
     pub(crate) sandbox_config_display: Option<String>,
+    /// For build scripts, the executables found in OUT_DIR after the build script ran.
+    pub(crate) written_executables: Vec<WrittenExecutable>,
+    /// For build scripts, anything in OUT_DIR that we couldn't read while looking for executables.
+    #[serde(default)]
+    pub(crate) unreadable_outputs: Vec<UnreadableOutput>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
 pub(crate) struct RustcOutput {
     pub(crate) crate_sel: CrateSel,
+    #[serde(with = "crate::path_serde::vec")]
     pub(crate) source_paths: Vec<PathBuf>,
+    /// Native libraries that rustc was asked to link, generally because the build script asked for
+    /// them.
+    #[serde(default)]
+    pub(crate) link_libs: Vec<String>,
+    /// Path remappings that rustc was asked to apply to the paths in debug info.
+    #[serde(default)]
+    pub(crate) remap_path_prefixes: Vec<RemapPathPrefix>,
+    /// The directory that the package's build script wrote its outputs to, if it has a build
+    /// script. Native code that the build script compiled ends up here.
+    #[serde(default)]
+    pub(crate) out_dir: Option<PathBuf>,
+    /// Rustdoc's JSON output for the crate, if we were asked to produce it and rustdoc succeeded.
+    #[serde(default)]
+    pub(crate) rustdoc_json: Option<PathBuf>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
 pub(crate) struct UnsafeUsage {
     pub(crate) crate_sel: CrateSel,
+    /// The root directory of the package that the crate is from.
+    pub(crate) pkg_dir: PathBuf,
     pub(crate) locations: Vec<SourceLocation>,
 }
 
+impl UnsafeUsage {
+    /// Returns the files in which unsafe was found, relative to the package directory. Returns None
+    /// if any are outside the package directory.
+    pub(crate) fn pkg_relative_files(&self) -> Option<Vec<String>> {
+        let mut files = self
+            .locations
+            .iter()
+            .map(|location| {
+                location
+                    .filename()
+                    .strip_prefix(&self.pkg_dir)
+                    .ok()
+                    .map(|path| path.to_string_lossy().into_owned())
+            })
+            .collect::<Option<Vec<String>>>()?;
+        files.sort();
+        files.dedup();
+        Some(files)
+    }
+}
+
 /// Writes `value` to `stream`. The format used is the length followed by `value` serialised as
 /// JSON.
 pub(crate) fn write_to_stream<T: Serialize>(value: &T, stream: &mut impl Write) -> Result<()> {
@@ -143,6 +222,7 @@ mod tests {
     fn serialize_deserialize() {
         let req = Request::CrateUsesUnsafe(UnsafeUsage {
             crate_sel: CrateSel::primary(crate::crate_index::testing::pkg_id("foo")),
+            pkg_dir: PathBuf::from("/foo"),
             locations: vec![SourceLocation::new(Path::new("src/main.rs"), 42, None)],
         });
         let mut buf = Vec::new();
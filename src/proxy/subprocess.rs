@@ -3,13 +3,17 @@
 
 use super::CONFIG_PATH_ENV;
 use super::ExitCode;
+use super::RUSTC_DIAGNOSTICS_ENV;
+use super::RUSTDOC_EXPORTS_ENV;
 use super::cackle_exe;
 use super::errors::get_disallowed_unsafe_locations;
 use super::rpc::BinExecutionOutput;
 use super::rpc::RustcOutput;
 use super::run_command;
+use crate::config::AllowUnsafe;
 use crate::config::Config;
 use crate::config::RustcConfig;
+use crate::config::file_pattern::FilePattern;
 use crate::config::permissions::PermSel;
 use crate::config::permissions::Permissions;
 use crate::crate_index::CrateKind;
@@ -18,6 +22,7 @@ use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
 use crate::outcome::Outcome;
 use crate::proxy::rpc::RpcClient;
+use crate::resources::PermitKind;
 use crate::sandbox::RustcSandboxInputs;
 use crate::unsafe_checker;
 use anyhow::Context;
@@ -139,6 +144,7 @@ fn proxy_binary(
     rpc_client: &RpcClient,
     args: &[String],
 ) -> Result<ExitCode> {
+    let _job = rpc_client.acquire_permit(PermitKind::Job)?;
     loop {
         let config = SubprocessConfig::from_env()?;
         let perm_sel = PermSel::for_non_build_output(crate_sel);
@@ -158,7 +164,15 @@ fn proxy_binary(
                 .into());
         };
 
+        let sandbox_permit = rpc_client.acquire_permit(PermitKind::Sandbox)?;
         let output = sandbox.run(&command)?;
+        drop(sandbox_permit);
+        let written = match (crate_sel.kind, std::env::var_os("OUT_DIR")) {
+            (CrateKind::BuildScript, Some(out_dir)) if output.status.success() => {
+                crate::prebuilt::find_written_executables(Path::new(&out_dir))
+            }
+            _ => Default::default(),
+        };
         let rpc_response = rpc_client.bin_execution_complete({
             let exit_code = output.status.code().unwrap_or(-1);
             BinExecutionOutput {
@@ -170,6 +184,8 @@ fn proxy_binary(
                 binary_path: orig_bin.clone(),
                 sandbox_config_display: (exit_code != 0)
                     .then(|| sandbox.display_to_run(&command).to_string()),
+                written_executables: written.executables,
+                unreadable_outputs: written.unreadable,
             }
         })?;
         match rpc_response {
@@ -200,6 +216,7 @@ fn proxy_rustc(rpc_client: &RpcClient) -> Result<ExitCode> {
     if std::env::args().any(|arg| arg == "--test") {
         crate_sel.kind = CrateKind::Test;
     }
+    let _job = rpc_client.acquire_permit(PermitKind::Job)?;
     let mut runner = RustcRunner::new(crate_sel);
     rpc_client.rustc_started(&runner.crate_sel)?;
     loop {
@@ -208,7 +225,15 @@ fn proxy_rustc(rpc_client: &RpcClient) -> Result<ExitCode> {
             RustcRunStatus::GiveUp => return Ok(crate::outcome::FAILURE),
             RustcRunStatus::Done(output) => {
                 std::io::stdout().lock().write_all(&output.stdout)?;
-                std::io::stderr().lock().write_all(&output.stderr)?;
+                let mut stderr = std::io::stderr().lock();
+                stderr.write_all(&output.stderr)?;
+                if output.status.success() && std::env::var_os(RUSTC_DIAGNOSTICS_ENV).is_some() {
+                    // Cargo asked rustc for JSON diagnostics, so it'll render these along with
+                    // rustc's own.
+                    for diagnostic in rpc_client.take_diagnostics(&runner.crate_sel)? {
+                        writeln!(stderr, "{diagnostic}")?;
+                    }
+                }
                 return Ok(output.status.into());
             }
         }
@@ -262,17 +287,48 @@ impl RustcRunner {
 
         if output.status.code() == Some(0) {
             let source_paths = crate::deps::source_files_from_rustc_args(std::env::args())?;
+            let rustdoc_json = if std::env::var_os(RUSTDOC_EXPORTS_ENV).is_some()
+                && self.crate_sel.kind == CrateKind::Primary
+            {
+                self.run_rustdoc(&config)?
+            } else {
+                None
+            };
+            if let Some(archive) =
+                crate::link_info::staticlib_path_from_rustc_args(std::env::args())?
+            {
+                rpc_client.linker_invoked(link_staticlib(&self.crate_sel, archive)?)?;
+            }
             // Tell the main process that rustc has completed. If the linker was invoked, then
             // this will trigger checking of the linker inputs/outputs.
             let response = rpc_client.rustc_complete(RustcOutput {
                 crate_sel: self.crate_sel.clone(),
                 source_paths: source_paths.clone(),
+                link_libs: crate::native_libs::link_libs_from_rustc_args(std::env::args()),
+                remap_path_prefixes: crate::checker::path_remap::remaps_from_rustc_args(
+                    std::env::args(),
+                ),
+                out_dir: std::env::var_os("OUT_DIR").map(PathBuf::from),
+                rustdoc_json,
             })?;
             if response != Outcome::Continue {
                 return Ok(RustcRunStatus::GiveUp);
             }
             if !unsafe_permitted {
                 unsafe_locations.extend(find_unsafe_in_sources(&source_paths)?);
+            } else if let Some(allowed_files) = config.unsafe_file_patterns(&self.crate_sel)? {
+                // Unsafe is only permitted in some files, so report any that's found elsewhere.
+                let pkg_dir = pkg_dir()?;
+                unsafe_locations.extend(find_unsafe_in_sources(&source_paths)?.into_iter().filter(
+                    |location| {
+                        !location
+                            .filename()
+                            .strip_prefix(&pkg_dir)
+                            .is_ok_and(|path| {
+                                allowed_files.iter().any(|allowed| allowed.matches(path))
+                            })
+                    },
+                ));
             }
         } else {
             unsafe_locations.extend(get_disallowed_unsafe_locations(&output)?);
@@ -280,7 +336,8 @@ impl RustcRunner {
         if !unsafe_locations.is_empty() {
             unsafe_locations.sort();
             unsafe_locations.dedup();
-            let response = rpc_client.crate_uses_unsafe(&self.crate_sel, unsafe_locations)?;
+            let response =
+                rpc_client.crate_uses_unsafe(&self.crate_sel, pkg_dir()?, unsafe_locations)?;
             if response == Outcome::Continue {
                 return Ok(RustcRunStatus::Retry);
             } else {
@@ -291,6 +348,28 @@ impl RustcRunner {
         Ok(RustcRunStatus::Done(output))
     }
 
+    /// Documents the library that rustc just compiled as JSON, returning the path to the JSON file
+    /// if it worked. Rustdoc runs proc macros too, so we run it in the same sandbox as rustc. If
+    /// rustdoc fails, we carry on without its output.
+    fn run_rustdoc(&self, config: &SubprocessConfig) -> Result<Option<PathBuf>> {
+        let rustdoc = rustc_path_from_env()?.with_file_name("rustdoc");
+        let Some((mut command, json_path)) =
+            crate::rustdoc_exports::rustdoc_command(&rustdoc, std::env::args().skip(2))
+        else {
+            return Ok(None);
+        };
+        let succeeded = match crate::sandbox::for_rustc(
+            &config.rustc,
+            &RustcSandboxInputs::from_env(&self.crate_sel)?,
+        )? {
+            Some(sandbox) => sandbox
+                .run(&command)
+                .is_ok_and(|output| output.status.success()),
+            None => command.output().is_ok_and(|output| output.status.success()),
+        };
+        Ok(succeeded.then_some(json_path))
+    }
+
     fn get_command(&self, unsafe_permitted: bool) -> Result<Command> {
         let mut args = std::env::args().skip(2).peekable();
         let mut command = Command::new(rustc_path_from_env()?);
@@ -340,6 +419,13 @@ impl RustcRunner {
     }
 }
 
+/// Returns the canonical path to the root of the package that we're compiling.
+fn pkg_dir() -> Result<PathBuf> {
+    let dir = path_from_env("CARGO_MANIFEST_DIR")?;
+    dir.canonicalize()
+        .with_context(|| format!("Failed to canonicalize `{}`", dir.display()))
+}
+
 /// Searches for the unsafe keyword in the specified paths.
 fn find_unsafe_in_sources(paths: &[PathBuf]) -> Result<Vec<SourceLocation>> {
     let mut locations = Vec::new();
@@ -360,7 +446,9 @@ fn proxy_linker(
     // Invoke the actual linker first, since the parent process uses the output file to aid with
     // analysis.
     let exit_status = invoke_real_linker(args)?;
-    if exit_status.is_ok() && link_info.is_executable() {
+    // Binaries for artifact dependencies are consumed by other packages, which might embed them or
+    // copy them elsewhere, so they need to be the real binary, not our wrapper.
+    if exit_status.is_ok() && link_info.is_executable() && !link_info.is_artifact_dependency() {
         setup_bin_wrapper(&mut link_info)?;
     }
     // We ignore the return value here since this is an infallible operation. The parent process
@@ -370,25 +458,81 @@ fn proxy_linker(
 }
 
 fn invoke_real_linker(
-    args: std::iter::Peekable<std::env::Args>,
+    mut args: std::iter::Peekable<std::env::Args>,
 ) -> Result<ExitCode, anyhow::Error> {
-    let orig_linker = std::env::var(super::ORIG_LINKER_ENV)
-        .ok()
-        .unwrap_or_else(default_linker);
-    let mut command = Command::new(orig_linker);
+    let mut command = Command::new(orig_linker(args.peek().map(String::as_str))?);
     command.args(args);
     run_command(&mut command)
 }
 
+fn orig_linker(first_arg: Option<&str>) -> Result<PathBuf> {
+    match std::env::var_os(super::ORIG_LINKER_ENV) {
+        Some(linker) => Ok(PathBuf::from(linker)),
+        None => default_linker(first_arg),
+    }
+}
+
+/// Links the staticlib `archive` into a shared object next to it, then returns information about
+/// the link, as if rustc had invoked the linker. The shared object is only used for analysis. We
+/// include the whole archive, since there's no entry point from which to determine what's used.
+fn link_staticlib(crate_sel: &CrateSel, archive: PathBuf) -> Result<LinkInfo> {
+    let output_file = archive.with_extension("cackle.so");
+    let output = Command::new(orig_linker(None)?)
+        .arg("-shared")
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-Wl,--whole-archive")
+        .arg(&archive)
+        .arg("-Wl,--no-whole-archive")
+        .output()
+        .context("Failed to run linker")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to link staticlib `{}` for analysis:\n{}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(LinkInfo::for_staticlib(
+        crate_sel.clone(),
+        archive,
+        Arc::from(output_file.as_path()),
+    ))
+}
+
 /// Returns our best guess as to the default linker.
-fn default_linker() -> String {
-    // Ideally we'd have a way to ask rustc what linker it wants to use, for now we just guess.
-    "cc".to_owned()
+fn default_linker(first_arg: Option<&str>) -> Result<PathBuf> {
+    // Ideally we'd have a way to ask rustc what linker it wants to use, for now we just guess. When
+    // rustc wants to invoke lld directly, e.g. for wasm, it passes `-flavor` as the first argument.
+    if first_arg == Some("-flavor") {
+        return rust_lld_path();
+    }
+    Ok(PathBuf::from("cc"))
+}
+
+/// Returns the path to the copy of lld that's shipped with rustc.
+fn rust_lld_path() -> Result<PathBuf> {
+    let output = Command::new(rustc_path_from_env()?)
+        .arg("--print")
+        .arg("sysroot")
+        .output()
+        .context("Failed to run rustc to determine sysroot")?;
+    let sysroot = PathBuf::from(std::str::from_utf8(&output.stdout)?.trim());
+    let rustlib = sysroot.join("lib").join("rustlib");
+    for entry in std::fs::read_dir(&rustlib)
+        .with_context(|| format!("Failed to read `{}`", rustlib.display()))?
+    {
+        let path = entry?.path().join("bin").join("rust-lld");
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+    bail!("Couldn't find rust-lld in `{}`", rustlib.display());
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
 pub(crate) struct SubprocessConfig {
-    permissions: Permissions,
+    pub(crate) permissions: Permissions,
     rustc: RustcConfig,
 }
 
@@ -407,7 +551,32 @@ impl SubprocessConfig {
         }
     }
 
-    fn parse_file(path: &Path) -> Result<Self> {
+    /// Permits `crate_sel` to use unsafe anywhere, regardless of what the full config says.
+    pub(crate) fn permit_unsafe(&mut self, crate_sel: &CrateSel) {
+        let crate_config = self
+            .permissions
+            .packages
+            .entry(PermSel::for_non_build_output(crate_sel))
+            .or_default();
+        crate_config.allow_unsafe = AllowUnsafe::Bool(true);
+        crate_config.allow_unsafe_in = None;
+    }
+
+    /// Returns the compiled patterns of the source files to which unsafe is restricted for
+    /// `crate_sel`, or None if unsafe is either not permitted or permitted anywhere in the crate.
+    fn unsafe_file_patterns(&self, crate_sel: &CrateSel) -> Result<Option<Vec<FilePattern>>> {
+        self.permissions
+            .unsafe_files_for_crate(crate_sel)
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| FilePattern::new(pattern))
+                    .collect()
+            })
+            .transpose()
+    }
+
+    pub(crate) fn parse_file(path: &Path) -> Result<Self> {
         let toml = crate::fs::read_to_string(path)?;
         Self::deserialise(&toml)
     }
@@ -441,8 +610,12 @@ fn config_roundtrips() {
     let crate_root = std::path::PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
     let test_crates_dir = crate_root.join("test_crates");
     let crate_index = crate::crate_index::CrateIndex::new(&test_crates_dir).unwrap();
-    let full_config =
-        crate::config::parse_file(&test_crates_dir.join("cackle.toml"), &crate_index).unwrap();
+    let full_config = crate::config::parse_file(
+        &test_crates_dir.join("cackle.toml"),
+        &crate_index,
+        &crate::vfs::RealFs,
+    )
+    .unwrap();
     let subprocess_config = SubprocessConfig::from_full_config(&full_config);
 
     let roundtripped_config =
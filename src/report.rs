@@ -0,0 +1,267 @@
+//! A report of the outcome of a run - the problems that were still outstanding when we finished and
+//! the permissions granted by the configuration. Reports are written as JSON so that reports from
+//! different runs can be compared. See `compare.rs`.
+
+use crate::async_runtime::AsyncRuntime;
+use crate::build_settings::BuildSettings;
+use crate::checker::Checker;
+use crate::config::Config;
+use crate::config::PackageConfig;
+use crate::crate_index::PackageId;
+use crate::determinism::NondeterminismSource;
+use crate::panic_audit::PanicPath;
+use crate::problem::Problem;
+use crate::problem::Severity;
+use crate::problem_store::ProblemStore;
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Incremented if we ever make an incompatible change to the format of reports.
+const REPORT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Report {
+    pub(crate) format_version: u32,
+    /// The version of rustc used for the build, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rustc_version: Option<String>,
+    pub(crate) problems: Vec<ReportedProblem>,
+    /// The permissions granted by the configuration, keyed by package selector (e.g. `crab1` or
+    /// `crab1.build`).
+    pub(crate) permissions: BTreeMap<String, ReportedPermissions>,
+    /// How each crate appears to have been built and how confident we are in what we found in it as
+    /// a result.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) build_settings: BTreeMap<String, ReportedBuildSettings>,
+    /// Packages that are only in the dependency graph because workspace members depend on them
+    /// from tests.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) test_only_packages: Vec<String>,
+    /// The async runtimes that each package binds to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) async_runtimes: BTreeMap<String, Vec<AsyncRuntime>>,
+    /// Whether each package references panic machinery or abort.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) panic_paths: BTreeMap<String, Vec<PanicPath>>,
+    /// The sources of nondeterminism that each package references, if we looked for them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) nondeterminism: BTreeMap<String, Vec<NondeterminismSource>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReportedProblem {
+    pub(crate) severity: Severity,
+    pub(crate) package: Option<String>,
+    /// A one-line description of the problem. Problems from different reports with the same summary
+    /// are considered to be the same problem.
+    pub(crate) summary: String,
+    /// A more detailed description, including things like source locations.
+    pub(crate) details: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct ReportedPermissions {
+    #[serde(default)]
+    pub(crate) allow_apis: Vec<String>,
+    #[serde(default)]
+    pub(crate) allow_unsafe: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) allow_unsafe_in: Option<Vec<String>>,
+    #[serde(default)]
+    pub(crate) allow_proc_macro: bool,
+    #[serde(default)]
+    pub(crate) allow_build_instructions: Vec<String>,
+    #[serde(default)]
+    pub(crate) allow_executables: Vec<String>,
+    #[serde(default)]
+    pub(crate) allow_binary_files: Vec<String>,
+    #[serde(default)]
+    pub(crate) allow_native_libs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReportedBuildSettings {
+    #[serde(flatten)]
+    pub(crate) settings: BuildSettings,
+    pub(crate) confidence: Confidence,
+    /// Why confidence is reduced, if it is.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) notes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Confidence {
+    /// The crate was built the way we expect, so what we found is as reliable as we can make it.
+    Normal,
+
+    /// The crate was built in a way that makes our analysis less reliable. e.g. with optimisation.
+    Reduced,
+}
+
+impl Report {
+    /// Returns a report of the problems in `problem_store` and of what `checker` found.
+    pub(crate) fn new(problem_store: &ProblemStore, checker: &Checker) -> Self {
+        let config = &checker.config;
+        let crate_index = &checker.crate_index;
+        let mut problems: Vec<ReportedProblem> = crate::name_display::for_json(|| {
+            problem_store
+                .deduplicated_into_iter()
+                .map(|(_, problem)| ReportedProblem::new(problem))
+                .collect()
+        });
+        problems.sort_by(|a, b| a.summary.cmp(&b.summary));
+        let permissions = reported_permissions(config);
+        let build_settings = checker
+            .build_settings
+            .iter()
+            .map(|(pkg_id, settings)| (pkg_id.to_string(), settings.into()))
+            .collect();
+        let mut test_only_packages: Vec<String> = crate_index
+            .package_ids()
+            .filter(|pkg_id| crate_index.is_test_only(pkg_id))
+            .map(|pkg_id| pkg_id.to_string())
+            .collect();
+        test_only_packages.sort();
+        Report {
+            format_version: REPORT_FORMAT_VERSION,
+            rustc_version: checker
+                .rustc_version
+                .as_ref()
+                .map(|version| version.to_string()),
+            problems,
+            permissions,
+            build_settings,
+            test_only_packages,
+            async_runtimes: by_package(&checker.async_runtimes),
+            panic_paths: by_package(&checker.panic_paths),
+            nondeterminism: by_package(&checker.nondeterminism),
+        }
+    }
+
+    /// Returns a report with just the supplied problems and the permissions granted by `config`.
+    /// Used for comparing configurations against each other, rather than for recording a run.
+    pub(crate) fn from_problems<'a>(
+        problems: impl Iterator<Item = &'a Problem>,
+        config: &Config,
+    ) -> Self {
+        let mut problems: Vec<ReportedProblem> =
+            crate::name_display::for_json(|| problems.map(ReportedProblem::new).collect());
+        problems.sort_by(|a, b| a.summary.cmp(&b.summary));
+        problems.dedup();
+        Report {
+            format_version: REPORT_FORMAT_VERSION,
+            rustc_version: None,
+            problems,
+            permissions: reported_permissions(config),
+            build_settings: BTreeMap::new(),
+            test_only_packages: Vec::new(),
+            async_runtimes: BTreeMap::new(),
+            panic_paths: BTreeMap::new(),
+            nondeterminism: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        crate::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let json = crate::fs::read_to_string(path)?;
+        let report: Report = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse report `{}`", path.display()))?;
+        if report.format_version > REPORT_FORMAT_VERSION {
+            bail!(
+                "Report `{}` has format version {}, but the maximum supported is {}",
+                path.display(),
+                report.format_version,
+                REPORT_FORMAT_VERSION
+            );
+        }
+        Ok(report)
+    }
+}
+
+impl ReportedProblem {
+    fn new(problem: &Problem) -> Self {
+        ReportedProblem {
+            severity: problem.severity(),
+            package: problem.pkg_id().map(|pkg_id| pkg_id.name_str().to_owned()),
+            summary: problem.to_string(),
+            details: format!("{problem:#}"),
+        }
+    }
+}
+
+/// Inverts a map from things that we found to the packages that they were found in.
+fn by_package<T: Copy>(found: &BTreeMap<T, BTreeSet<PackageId>>) -> BTreeMap<String, Vec<T>> {
+    let mut by_package: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for (thing, pkg_ids) in found {
+        for pkg_id in pkg_ids {
+            by_package
+                .entry(pkg_id.to_string())
+                .or_default()
+                .push(*thing);
+        }
+    }
+    by_package
+}
+
+fn reported_permissions(config: &Config) -> BTreeMap<String, ReportedPermissions> {
+    config
+        .permissions_no_inheritance
+        .packages
+        .iter()
+        .map(|(perm_sel, pkg_config)| (perm_sel.to_string(), pkg_config.into()))
+        .collect()
+}
+
+impl From<&PackageConfig> for ReportedPermissions {
+    fn from(config: &PackageConfig) -> Self {
+        let mut allow_apis: Vec<String> = config.allow_apis.iter().map(|a| a.to_string()).collect();
+        allow_apis.sort();
+        let mut allow_build_instructions = config.allow_build_instructions.clone();
+        allow_build_instructions.sort();
+        let mut allow_executables = config.allow_executables.clone();
+        allow_executables.sort();
+        let mut allow_binary_files = config.allow_binary_files.clone();
+        allow_binary_files.sort();
+        let mut allow_native_libs = config.allow_native_libs.clone();
+        allow_native_libs.sort();
+        ReportedPermissions {
+            allow_apis,
+            allow_unsafe: config.allow_unsafe.is_allowed(),
+            allow_unsafe_in: config.allow_unsafe_in.clone(),
+            allow_proc_macro: config.allow_proc_macro,
+            allow_build_instructions,
+            allow_executables,
+            allow_binary_files,
+            allow_native_libs,
+        }
+    }
+}
+
+impl From<&BuildSettings> for ReportedBuildSettings {
+    fn from(settings: &BuildSettings) -> Self {
+        let notes: Vec<String> = settings
+            .reduced_confidence_reasons()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        ReportedBuildSettings {
+            settings: settings.clone(),
+            confidence: if notes.is_empty() {
+                Confidence::Normal
+            } else {
+                Confidence::Reduced
+            },
+            notes,
+        }
+    }
+}
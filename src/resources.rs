@@ -0,0 +1,198 @@
+//! Coordination of how much work happens at once. Cargo runs rustc and build scripts in parallel,
+//! build scripts may each be run in a sandbox and each binary is scanned by several threads. Left
+//! to themselves, each of these would size itself to the whole machine, which on a shared CI
+//! machine can add up to far more than we were given. Instead, there's a single budget of jobs.
+//! Cargo is limited to that many jobs and each rustc or binary that cargo runs via our proxy holds
+//! a job slot while it runs. The scanner runs while the rustc that invoked the linker waits, so it
+//! gets that rustc's slot, plus whatever slots are free when it starts. Concurrent sandboxes can be
+//! limited separately, since each sandboxed build script can use a lot of memory.
+//!
+//! Slots are held by subprocesses via a connection to the main process, which stays open until the
+//! subprocess is done with the slot. See `rpc::RpcClient::acquire_permit`. That way, slots are
+//! returned even if a subprocess gets killed.
+
+use crate::config::LimitsConfig;
+use serde::Deserialize;
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::sync::Condvar;
+use std::sync::Mutex;
+
+/// What a subprocess can ask for a slot for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PermitKind {
+    /// Running rustc or a binary such as a build script.
+    Job,
+    /// Running something in a sandbox.
+    Sandbox,
+}
+
+pub(crate) struct ResourceManager {
+    /// The total number of jobs, if it was limited by configuration rather than being the number of
+    /// CPUs.
+    configured_jobs: Option<usize>,
+    jobs: Semaphore,
+    sandboxes: Semaphore,
+}
+
+/// Slots that are returned when this is dropped.
+pub(crate) struct Permit<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl ResourceManager {
+    /// Creates a manager with the limits from `config`, or failing that, one job per CPU. `jobs`,
+    /// if set, overrides `config.jobs`.
+    pub(crate) fn new(config: &LimitsConfig, jobs: Option<NonZeroUsize>) -> Self {
+        let configured_jobs = jobs.or(config.jobs).map(NonZeroUsize::get);
+        let total = configured_jobs.unwrap_or_else(num_cpus);
+        Self {
+            configured_jobs,
+            jobs: Semaphore::new(total),
+            sandboxes: Semaphore::new(config.sandboxes.map_or(total, NonZeroUsize::get)),
+        }
+    }
+
+    /// The value to pass as cargo's `--jobs`, if any. When jobs aren't limited, we leave cargo to
+    /// use its default, which is also the number of CPUs.
+    pub(crate) fn cargo_jobs(&self) -> Option<usize> {
+        self.configured_jobs
+    }
+
+    /// Waits for a slot of the specified kind to be free, then takes it.
+    pub(crate) fn acquire(&self, kind: PermitKind) -> Permit<'_> {
+        match kind {
+            PermitKind::Job => self.jobs.acquire(),
+            PermitKind::Sandbox => self.sandboxes.acquire(),
+        }
+    }
+
+    /// Takes up to `max` job slots, but only those that are currently free. Used for work that can
+    /// make do with fewer threads.
+    pub(crate) fn take_free_jobs(&self, max: usize) -> Permit<'_> {
+        self.jobs.take_free(max)
+    }
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new(&LimitsConfig::default(), None)
+    }
+}
+
+impl Permit<'_> {
+    /// How many slots this permit holds.
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            *self.semaphore.available.lock().unwrap() += self.count;
+            self.semaphore.released.notify_all();
+        }
+    }
+}
+
+impl Semaphore {
+    fn new(count: usize) -> Self {
+        Self {
+            available: Mutex::new(count),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> Permit<'_> {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+        Permit {
+            semaphore: self,
+            count: 1,
+        }
+    }
+
+    fn take_free(&self, max: usize) -> Permit<'_> {
+        let mut available = self.available.lock().unwrap();
+        let count = (*available).min(max);
+        *available -= count;
+        Permit {
+            semaphore: self,
+            count,
+        }
+    }
+}
+
+fn num_cpus() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PermitKind;
+    use super::ResourceManager;
+    use crate::config::LimitsConfig;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn job_slots() {
+        let config = LimitsConfig {
+            jobs: NonZeroUsize::new(3),
+            sandboxes: NonZeroUsize::new(1),
+        };
+        let resources = ResourceManager::new(&config, None);
+        assert_eq!(resources.cargo_jobs(), Some(3));
+        assert_eq!(
+            ResourceManager::new(&config, NonZeroUsize::new(2)).cargo_jobs(),
+            Some(2)
+        );
+        assert_eq!(
+            ResourceManager::new(&LimitsConfig::default(), None).cargo_jobs(),
+            None
+        );
+
+        let rustc = resources.acquire(PermitKind::Job);
+        // The scanner only gets what's free, even if it'd like more.
+        let scan = resources.take_free_jobs(8);
+        assert_eq!(scan.count(), 2);
+        assert_eq!(resources.take_free_jobs(8).count(), 0);
+        drop(scan);
+        assert_eq!(resources.take_free_jobs(1).count(), 1);
+        drop(rustc);
+    }
+
+    #[test]
+    fn acquire_waits_for_release() {
+        let config = LimitsConfig {
+            jobs: NonZeroUsize::new(4),
+            sandboxes: NonZeroUsize::new(2),
+        };
+        let resources = ResourceManager::new(&config, None);
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = resources.acquire(PermitKind::Sandbox);
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,243 @@
+//! Scheduled re-review of permissions. Permissions can say when they should next be reviewed, via
+//! `review_by`, and which version of the package they were reviewed against, via
+//! `reviewed_version`. `cargo acl review due` lists permissions whose review date is near or has
+//! passed, or whose package has changed version since the review, most urgent first. It's intended
+//! to be run periodically, e.g. weekly in CI, as a report that doesn't fail the build.
+
+use crate::config::Config;
+use crate::crate_index::CrateIndex;
+use crate::outcome;
+use crate::outcome::ExitCode;
+use crate::summary::OutputFormat;
+use cargo_metadata::semver::Version;
+use clap::Parser;
+use clap::Subcommand;
+use serde::Serialize;
+use std::fmt::Display;
+use toml::value::Date;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct ReviewOptions {
+    #[command(subcommand)]
+    command: ReviewCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ReviewCommand {
+    /// List permissions that are due to be reviewed again, most urgent first.
+    Due(DueOptions),
+}
+
+#[derive(Parser, Debug, Clone)]
+struct DueOptions {
+    /// Permissions with a `review_by` date within this many days are listed as due.
+    #[arg(long, default_value_t = 30)]
+    within_days: u64,
+
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    output_format: OutputFormat,
+}
+
+/// A group of permissions that should be reviewed, together with why.
+#[derive(Serialize, Debug, PartialEq)]
+struct DueReview {
+    /// e.g. `crab1` or `crab1.build`.
+    selector: String,
+    /// Most urgent first.
+    reasons: Vec<Reason>,
+}
+
+/// Why permissions should be reviewed. Ordered by how urgent the review is.
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+enum Reason {
+    /// The `review_by` date has passed. Dates are formatted like 2025-06-30, so that they sort in
+    /// date order.
+    Overdue { review_by: String },
+    /// The `review_by` date is soon.
+    Due { review_by: String },
+    /// The package in the dependency tree isn't the version that was reviewed.
+    VersionChanged {
+        reviewed_version: String,
+        current_versions: Vec<String>,
+    },
+}
+
+pub(crate) fn run(options: &ReviewOptions, config: &Config, crate_index: &CrateIndex) -> ExitCode {
+    let ReviewCommand::Due(options) = &options.command;
+    let due = due_reviews(
+        config,
+        crate_index,
+        crate::migration::today(),
+        crate::migration::days_from_today(options.within_days),
+    );
+    match options.output_format {
+        OutputFormat::Human => {
+            if due.is_empty() {
+                println!("No permissions are due for review");
+            }
+            for review in &due {
+                println!("{review}");
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(&due) {
+            Ok(json) => println!("{json}"),
+            Err(error) => {
+                println!("{error:#}");
+                return outcome::FAILURE;
+            }
+        },
+    }
+    outcome::SUCCESS
+}
+
+/// Returns the permissions in `config` that should be reviewed, most urgent first. Those with a
+/// `review_by` date before `today` are overdue and those with one up to and including `horizon`
+/// are due.
+fn due_reviews(
+    config: &Config,
+    crate_index: &CrateIndex,
+    today: Date,
+    horizon: Date,
+) -> Vec<DueReview> {
+    let mut due = Vec::new();
+    for (perm_sel, pkg_config) in &config.permissions_no_inheritance.packages {
+        let mut reasons = Vec::new();
+        if let Some(review_by) = pkg_config.review_by.and_then(|datetime| datetime.date) {
+            if review_by < today {
+                reasons.push(Reason::Overdue {
+                    review_by: review_by.to_string(),
+                });
+            } else if review_by <= horizon {
+                reasons.push(Reason::Due {
+                    review_by: review_by.to_string(),
+                });
+            }
+        }
+        if let Some(reviewed_version) = &pkg_config.reviewed_version {
+            let mut current: Vec<&Version> = crate_index
+                .package_ids()
+                .filter(|pkg_id| pkg_id.name_str() == perm_sel.package_name.as_ref())
+                .map(|pkg_id| pkg_id.version())
+                .collect();
+            current.sort();
+            let reviewed = Version::parse(reviewed_version).ok();
+            if current
+                .iter()
+                .any(|version| Some(*version) != reviewed.as_ref())
+            {
+                reasons.push(Reason::VersionChanged {
+                    reviewed_version: reviewed_version.clone(),
+                    current_versions: current.iter().map(|v| v.to_string()).collect(),
+                });
+            }
+        }
+        if !reasons.is_empty() {
+            due.push(DueReview {
+                selector: perm_sel.to_string(),
+                reasons,
+            });
+        }
+    }
+    due.sort_by(|a, b| {
+        a.reasons
+            .first()
+            .cmp(&b.reasons.first())
+            .then_with(|| a.selector.cmp(&b.selector))
+    });
+    due
+}
+
+impl Display for DueReview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pkg.{}: ", self.selector)?;
+        for (i, reason) in self.reasons.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            reason.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Overdue { review_by } => write!(f, "review was due by {review_by}"),
+            Reason::Due { review_by } => write!(f, "review due by {review_by}"),
+            Reason::VersionChanged {
+                reviewed_version,
+                current_versions,
+            } => write!(
+                f,
+                "reviewed at version {reviewed_version}, now {}",
+                current_versions.join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::due_reviews;
+    use crate::config::testing::parse;
+    use toml::value::Date;
+
+    #[test]
+    fn due() {
+        let config = parse(
+            r#"
+            [api.fs]
+            include = ["std::fs"]
+
+            [pkg.crab1]
+            allow_apis = ["fs"]
+            review_by = 2026-09-01
+
+            [pkg.crab2]
+            allow_unsafe = true
+            reviewed_version = "0.0.0"
+
+            [pkg.crab2.build]
+            allow_apis = ["fs"]
+            review_by = 2026-10-20
+
+            [pkg.crab3]
+            allow_apis = ["fs"]
+            review_by = 2027-01-01
+            reviewed_version = "0.1.0"
+
+            [pkg.crab4]
+            allow_apis = ["fs"]
+            review_by = 2030-01-01
+            "#,
+        )
+        .unwrap();
+        let date = |year, month, day| Date { year, month, day };
+        let due = due_reviews(
+            &config,
+            &crate::crate_index::testing::index_with_package_names(&[
+                "crab1", "crab2", "crab3", "crab4",
+            ]),
+            date(2026, 10, 14),
+            date(2026, 11, 13),
+        );
+        let due: Vec<String> = due.iter().map(|review| review.to_string()).collect();
+        assert_eq!(
+            due,
+            vec![
+                "pkg.crab1: review was due by 2026-09-01",
+                "pkg.crab2.build: review due by 2026-10-20",
+                "pkg.crab3: reviewed at version 0.1.0, now 0.0.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_review_metadata() {
+        assert!(parse("[pkg.crab1]\nreview_by = 2026-09-01T12:00:00\n").is_err());
+        assert!(parse("[pkg.crab1]\nreviewed_version = \"1.x\"\n").is_err());
+    }
+}
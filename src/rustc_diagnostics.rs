@@ -0,0 +1,221 @@
+//! Reporting of problems as rustc diagnostics. Cargo runs rustc with `--error-format=json` and
+//! renders each diagnostic that rustc writes to stderr. When our rustc wrapper writes additional
+//! diagnostics in the same format, cargo displays them as if they came from the compiler. This lets
+//! API usages in workspace members show up in the compiler output that developers already watch,
+//! rather than only in Cackle's own UI.
+
+use crate::config::permissions::PermSel;
+use crate::location::SourceLocation;
+use crate::name_display::shorten;
+use crate::problem::ApiUsages;
+use crate::problem::display_call_path;
+use crate::problem::display_inline_chain;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The subset of rustc's JSON diagnostic format that cargo makes use of.
+#[derive(Serialize, Debug)]
+struct Diagnostic {
+    #[serde(rename = "$message_type")]
+    message_type: &'static str,
+    message: String,
+    code: Option<DiagnosticCode>,
+    level: &'static str,
+    spans: Vec<DiagnosticSpan>,
+    children: Vec<Diagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct DiagnosticCode {
+    code: &'static str,
+    explanation: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct DiagnosticSpan {
+    file_name: String,
+    byte_start: u32,
+    byte_end: u32,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+    text: Vec<()>,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+    expansion: Option<()>,
+}
+
+const DISALLOWED_API_CODE: &str = "cackle::disallowed_api";
+
+/// Returns a JSON diagnostic for each distinct source location in `usages`. Paths under
+/// `workspace_root` are made relative to it, the same as rustc does for workspace members.
+pub(crate) fn for_api_usages(usages: &ApiUsages, workspace_root: &Path) -> Vec<String> {
+    let perm_sel = PermSel::with_scope(&usages.pkg_id, usages.scope);
+    let message = format!(
+        "`{}` uses disallowed API `{}`",
+        usages.pkg_id, usages.api_name
+    );
+    let help = format!(
+        "if this is intended, add \"{}\" to `pkg.{perm_sel}.allow_apis` in cackle.toml",
+        usages.api_name
+    );
+    let mut diagnostics = Vec::new();
+    let mut seen = BTreeSet::new();
+    for usage in &usages.usages {
+        if !seen.insert(&usage.source_location) {
+            continue;
+        }
+        let mut notes = vec![format!("reference to `{}`", shorten(&usage.to_source))];
+        if !usage.callers.is_empty() {
+            notes.push(format!("reached via {}", display_call_path(usage)));
+        }
+        if !usage.inline_chain.is_empty() {
+            notes.push(format!("inlined via {}", display_inline_chain(usage)));
+        }
+        let diagnostic = Diagnostic::warning(
+            message.clone(),
+            span(&usage.source_location, workspace_root),
+            notes,
+            help.clone(),
+        );
+        // Serialising a structure of strings and integers can't fail.
+        diagnostics.push(serde_json::to_string(&diagnostic).unwrap());
+    }
+    diagnostics
+}
+
+fn span(location: &SourceLocation, workspace_root: &Path) -> DiagnosticSpan {
+    let filename = location.filename();
+    let column = location.column().unwrap_or(1);
+    DiagnosticSpan {
+        file_name: filename
+            .strip_prefix(workspace_root)
+            .unwrap_or(filename)
+            .display()
+            .to_string(),
+        byte_start: 0,
+        byte_end: 0,
+        line_start: location.line(),
+        line_end: location.line(),
+        column_start: column,
+        column_end: column,
+        is_primary: true,
+        text: Vec::new(),
+        label: None,
+        suggested_replacement: None,
+        suggestion_applicability: None,
+        expansion: None,
+    }
+}
+
+impl Diagnostic {
+    fn warning(message: String, span: DiagnosticSpan, notes: Vec<String>, help: String) -> Self {
+        // We don't have the source text, so we render in the same way that rustc does when the
+        // source isn't available.
+        let mut rendered = format!(
+            "warning[{DISALLOWED_API_CODE}]: {message}\n --> {}:{}:{}\n  |\n",
+            span.file_name, span.line_start, span.column_start
+        );
+        let mut children: Vec<Diagnostic> = notes
+            .into_iter()
+            .map(|note| Self::child("note", note))
+            .collect();
+        children.push(Self::child("help", help));
+        for child in &children {
+            let _ = writeln!(rendered, "  = {}: {}", child.level, child.message);
+        }
+        rendered.push('\n');
+        Self {
+            message_type: "diagnostic",
+            message,
+            code: Some(DiagnosticCode {
+                code: DISALLOWED_API_CODE,
+                explanation: None,
+            }),
+            level: "warning",
+            spans: vec![span],
+            children,
+            rendered: Some(rendered),
+        }
+    }
+
+    fn child(level: &'static str, message: String) -> Self {
+        Self {
+            message_type: "diagnostic",
+            message,
+            code: None,
+            level,
+            spans: Vec::new(),
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::ApiUsage;
+    use crate::checker::BinLocation;
+    use crate::config::ApiName;
+    use crate::config::permissions::PermissionScope;
+    use crate::crate_index::testing::pkg_id;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::problem::ApiUsages;
+    use crate::symbol::Symbol;
+    use crate::symbol_graph::NameSource;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn api_usage_diagnostic() {
+        let usage = ApiUsage {
+            bin_location: BinLocation {
+                address: 0,
+                symbol_start: 0,
+            },
+            bin_path: Arc::from(Path::new("bin")),
+            permission_scope: PermissionScope::All,
+            source_location: SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 10, Some(5)),
+            outer_location: None,
+            from: SymbolOrDebugName::Symbol(Symbol::borrowed(b"foo::read")),
+            to_name: crate::names::split_simple("std::fs::read"),
+            to: SymbolOrDebugName::Symbol(Symbol::borrowed(b"std::fs::read")),
+            to_source: NameSource::Symbol(Symbol::borrowed(b"std::fs::read")),
+            kind: crate::checker::ReferenceKind::Call,
+            origin: Default::default(),
+            debug_data: None,
+            callers: Vec::new(),
+            inline_chain: Vec::new(),
+        };
+        let usages = ApiUsages {
+            pkg_id: pkg_id("foo"),
+            scope: PermissionScope::Test,
+            api_name: ApiName::from("fs"),
+            // A second reference from the same location shouldn't produce a second diagnostic.
+            usages: vec![usage.clone(), usage],
+            feature_fix: None,
+            usage_pins: Vec::new(),
+        };
+        let diagnostics = super::for_api_usages(&usages, Path::new("/ws"));
+        assert_eq!(diagnostics.len(), 1);
+        let json: serde_json::Value = serde_json::from_str(&diagnostics[0]).unwrap();
+        assert_eq!(json["$message_type"], "diagnostic");
+        assert_eq!(json["level"], "warning");
+        assert_eq!(json["spans"][0]["file_name"], "foo/src/lib.rs");
+        assert_eq!(json["spans"][0]["line_start"], 10);
+        assert_eq!(json["spans"][0]["column_start"], 5);
+        let rendered = json["rendered"].as_str().unwrap();
+        assert!(rendered.starts_with(
+            "warning[cackle::disallowed_api]: `foo` uses disallowed API `fs`\n \
+             --> foo/src/lib.rs:10:5\n"
+        ));
+        assert!(rendered.contains("`pkg.foo.test.allow_apis`"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Determines the version of rustc used for the build. Details such as symbol mangling and debug
+//! info can change between versions, so we record the version in reports, allow it to be pinned in
+//! the config and keep a table of versions that we know we can't work with.
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use std::fmt::Display;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct RustcVersion {
+    /// e.g. "1.95.0" or "1.96.0-nightly".
+    pub(crate) release: String,
+
+    pub(crate) commit_hash: Option<String>,
+}
+
+/// An issue that stops us from working with some versions of rustc. We stop with an error when we
+/// find one, rather than failing obscurely later.
+pub(crate) struct KnownIssue {
+    applies: fn(&RustcVersion) -> bool,
+    pub(crate) description: &'static str,
+}
+
+const KNOWN_ISSUES: &[KnownIssue] = &[KnownIssue {
+    applies: |version| version.major_minor().is_some_and(|v| v < (1, 63)),
+    description: "Cackle passes profile settings to cargo via `--config`, which requires \
+                      Rust 1.63 or later",
+}];
+
+impl RustcVersion {
+    /// Runs `rustc -vV` in `dir`, so that any toolchain override for that directory applies.
+    pub(crate) fn detect(dir: &Path) -> Result<Self> {
+        let output = std::process::Command::new("rustc")
+            .current_dir(dir)
+            .arg("-vV")
+            .output()
+            .context("Failed to run `rustc -vV`")?;
+        if !output.status.success() {
+            bail!(
+                "`rustc -vV` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Self::parse(std::str::from_utf8(&output.stdout).context("rustc version isn't UTF-8")?)
+    }
+
+    fn parse(output: &str) -> Result<Self> {
+        let mut release = None;
+        let mut commit_hash = None;
+        for line in output.lines() {
+            if let Some(value) = line.strip_prefix("release: ") {
+                release = Some(value.trim().to_owned());
+            } else if let Some(value) = line.strip_prefix("commit-hash: ") {
+                // Locally built compilers report a commit hash of "unknown".
+                commit_hash = Some(value.trim().to_owned()).filter(|hash| hash != "unknown");
+            }
+        }
+        Ok(Self {
+            release: release.context("rustc version output has no release")?,
+            commit_hash,
+        })
+    }
+
+    fn major_minor(&self) -> Option<(u32, u32)> {
+        let mut parts = self.release.split(['.', '-']);
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    }
+
+    /// Returns the entries from our table of known issues that apply to this version.
+    pub(crate) fn known_issues(&self) -> impl Iterator<Item = &'static KnownIssue> {
+        KNOWN_ISSUES.iter().filter(|issue| (issue.applies)(self))
+    }
+
+    /// Returns whether this version matches `pinned`, which is either a release, e.g. "1.95.0" or
+    /// a release followed by a commit hash, e.g. "1.95.0 (59807616e)".
+    pub(crate) fn matches(&self, pinned: &str) -> bool {
+        let (release, hash) = match pinned.split_once(' ') {
+            Some((release, hash)) => (release, Some(hash.trim_matches(['(', ')']))),
+            None => (pinned, None),
+        };
+        release == self.release
+            && hash.is_none_or(|hash| {
+                self.commit_hash
+                    .as_ref()
+                    .is_some_and(|full| full.starts_with(hash))
+            })
+    }
+}
+
+impl Display for RustcVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.release)?;
+        if let Some(hash) = self.commit_hash.as_ref() {
+            write!(f, " ({})", &hash[..hash.len().min(9)])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RustcVersion;
+
+    const STABLE: &str = "rustc 1.95.0 (59807616e 2026-04-14)
+binary: rustc
+commit-hash: 59807616e1fa2540724bfbac14d7976d7e4a3860
+commit-date: 2026-04-14
+host: x86_64-unknown-linux-gnu
+release: 1.95.0
+LLVM version: 22.1.2
+";
+
+    fn version(release: &str) -> RustcVersion {
+        RustcVersion {
+            release: release.to_owned(),
+            commit_hash: None,
+        }
+    }
+
+    fn issue_count(release: &str) -> usize {
+        version(release).known_issues().count()
+    }
+
+    #[test]
+    fn parse_version() {
+        let version = RustcVersion::parse(STABLE).unwrap();
+        assert_eq!(version.release, "1.95.0");
+        assert_eq!(version.to_string(), "1.95.0 (59807616e)");
+        assert!(version.matches("1.95.0"));
+        assert!(version.matches("1.95.0 (59807616e)"));
+        assert!(!version.matches("1.95.0 (0123abcde)"));
+        assert!(!version.matches("1.94.0"));
+        assert!(RustcVersion::parse("").is_err());
+    }
+
+    #[test]
+    fn known_issues() {
+        assert_eq!(issue_count("1.95.0"), 0);
+        assert_eq!(issue_count("1.62.1"), 1);
+        assert_eq!(issue_count("1.63.0"), 0);
+        assert_eq!(issue_count("1.96.0-nightly"), 0);
+    }
+}
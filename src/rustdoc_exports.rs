@@ -0,0 +1,309 @@
+//! Finding the public top-level items of packages from rustdoc's JSON output. This is an
+//! alternative to guessing, from the names of symbols in binaries, which modules a package might
+//! export. Unlike the symbol names, rustdoc knows what's actually public, including items that are
+//! re-exported from private modules.
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use rustc_hash::FxHashSet;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The oldest version of rustdoc's JSON format that we support. Older versions laid out items
+/// differently.
+const MIN_FORMAT_VERSION: u64 = 35;
+
+/// Rustc flags that take a value and that we pass through to rustdoc, since they affect what's in
+/// the crate or where its dependencies are found.
+const KEPT_FLAGS: &[&str] = &[
+    "--crate-name",
+    "--crate-type",
+    "--edition",
+    "--cfg",
+    "--check-cfg",
+    "-L",
+    "--extern",
+    "--cap-lints",
+    "--target",
+    "--sysroot",
+];
+
+/// Rustc flags that take a value and that are only relevant to compiling, so aren't passed to
+/// rustdoc.
+const DROPPED_FLAGS: &[&str] = &[
+    "--out-dir",
+    "-o",
+    "--emit",
+    "--json",
+    "--error-format",
+    "--color",
+    "--diagnostic-width",
+    "--remap-path-prefix",
+    "--print",
+    "-C",
+    "-Z",
+    "-A",
+    "-W",
+    "-D",
+    "-F",
+    "--force-warn",
+];
+
+/// Returns a command that documents, as JSON, the library that rustc is compiling with
+/// `rustc_args`, together with the path of the JSON file that it'll write. Returns `None` if rustc
+/// isn't compiling a library. The JSON goes in rustc's output directory, named so as not to clash
+/// with other packages of the same name. Since the JSON output isn't yet stable, we need
+/// `RUSTC_BOOTSTRAP` in order to get it from a stable toolchain.
+pub(crate) fn rustdoc_command(
+    rustdoc: &Path,
+    rustc_args: impl Iterator<Item = String>,
+) -> Option<(Command, PathBuf)> {
+    let mut rustc_args = rustc_args.peekable();
+    let mut kept_args = Vec::new();
+    let mut crate_name = None;
+    let mut crate_types = Vec::new();
+    let mut source = None;
+    let mut out_dir = None;
+    let mut extra_filename = String::new();
+    while let Some(arg) = rustc_args.next() {
+        let (flag, value) = if let Some((flag, value)) = arg
+            .split_once('=')
+            .filter(|(flag, _)| flag.starts_with("--"))
+        {
+            (flag.to_owned(), value.to_owned())
+        } else if KEPT_FLAGS.contains(&arg.as_str()) || DROPPED_FLAGS.contains(&arg.as_str()) {
+            let value = rustc_args.next()?;
+            (arg, value)
+        } else if !arg.starts_with("--") && arg.starts_with('-') && arg.len() > 2 {
+            // A short flag with its value attached, e.g. `-Ldependency=...`.
+            (arg[..2].to_owned(), arg[2..].to_owned())
+        } else {
+            if !arg.starts_with('-') && source.is_none() {
+                source = Some(arg);
+            }
+            continue;
+        };
+        match flag.as_str() {
+            "--crate-name" => crate_name = Some(value.clone()),
+            "--crate-type" => crate_types.push(value.clone()),
+            "--out-dir" => out_dir = Some(PathBuf::from(&value)),
+            "-C" => {
+                if let Some(extra) = value.strip_prefix("extra-filename=") {
+                    extra_filename = extra.to_owned();
+                }
+            }
+            _ => {}
+        }
+        if KEPT_FLAGS.contains(&flag.as_str()) {
+            kept_args.push(flag);
+            kept_args.push(value);
+        }
+    }
+    if !crate_types.iter().any(|t| t == "lib" || t == "rlib") {
+        return None;
+    }
+    let crate_name = crate_name?;
+    let doc_dir = out_dir?.join(format!("{crate_name}{extra_filename}.rustdoc"));
+    let json_path = doc_dir.join(format!("{crate_name}.json"));
+    let mut command = Command::new(rustdoc);
+    command
+        .args(kept_args)
+        .arg(source?)
+        .arg("-o")
+        .arg(doc_dir)
+        .args(["-Zunstable-options", "--output-format", "json"])
+        .env("RUSTC_BOOTSTRAP", "1");
+    Some((command, json_path))
+}
+
+/// The public items at the top level of a crate, as found in rustdoc's JSON output.
+#[derive(Debug)]
+pub(crate) struct CrateExports {
+    pub(crate) names: BTreeSet<String>,
+}
+
+pub(crate) fn read_exports(path: &Path) -> Result<CrateExports> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    parse_exports(&json).with_context(|| format!("Failed to parse `{}`", path.display()))
+}
+
+fn parse_exports(json: &str) -> Result<CrateExports> {
+    let doc: Value = serde_json::from_str(json)?;
+    let format_version = doc["format_version"].as_u64().unwrap_or_default();
+    if format_version < MIN_FORMAT_VERSION {
+        bail!("Unsupported rustdoc JSON format version {format_version}");
+    }
+    let index = doc["index"]
+        .as_object()
+        .ok_or_else(|| anyhow!("Missing `index`"))?;
+    let root = id_key(&doc["root"]).ok_or_else(|| anyhow!("Missing `root`"))?;
+    let mut names = BTreeSet::new();
+    let mut visited = FxHashSet::default();
+    let mut modules = vec![root];
+    while let Some(module_id) = modules.pop() {
+        if !visited.insert(module_id.clone()) {
+            continue;
+        }
+        let Some(items) = index
+            .get(&module_id)
+            .and_then(|module| module["inner"]["module"]["items"].as_array())
+        else {
+            continue;
+        };
+        for item in items.iter().filter_map(|id| index.get(&id_key(id)?)) {
+            if item["visibility"] != "public" {
+                continue;
+            }
+            let reexport = &item["inner"]["use"];
+            if reexport["is_glob"] == true {
+                // Everything public in the module is exported. If the module is from another
+                // crate, it's not in the index, so we ignore it.
+                modules.extend(id_key(&reexport["id"]));
+            } else if let Some(name) = reexport["name"].as_str().or(item["name"].as_str()) {
+                names.insert(name.to_owned());
+            }
+        }
+    }
+    Ok(CrateExports { names })
+}
+
+/// Returns the key in `index` for the item with ID `id`. IDs are numbers in recent format
+/// versions and were strings in older ones.
+fn id_key(id: &Value) -> Option<String> {
+    match id {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_exports;
+    use super::rustdoc_command;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    #[test]
+    fn top_level_names() {
+        // Cut down from the output for a crate with a public module `fs`, a private module `inner`
+        // containing modules `net` and `process`, a `pub use inner::net` and a `pub use inner::*`.
+        let json = r#"{
+            "root": 53,
+            "crate_version": "0.2.0",
+            "format_version": 57,
+            "index": {
+                "53": {"name": "rx", "visibility": "public",
+                       "inner": {"module": {"is_crate": true, "items": [1, 7, 52, 6]}}},
+                "1": {"name": "fs", "visibility": "public", "inner": {"module": {"items": [0]}}},
+                "0": {"name": "read", "visibility": "public", "inner": {"function": {}}},
+                "3": {"name": "net", "visibility": "public", "inner": {"module": {"items": []}}},
+                "4": {"name": "process", "visibility": "public",
+                      "inner": {"module": {"items": []}}},
+                "5": {"name": "inner", "visibility": "crate",
+                      "inner": {"module": {"items": [3, 4], "is_stripped": true}}},
+                "6": {"name": "env", "visibility": "crate", "inner": {"module": {"items": []}}},
+                "7": {"name": null, "visibility": "public",
+                      "inner": {"use": {"source": "inner::net", "name": "net", "id": 3,
+                                        "is_glob": false}}},
+                "52": {"name": null, "visibility": "public",
+                       "inner": {"use": {"source": "inner", "name": "inner", "id": 5,
+                                         "is_glob": true}}}
+            }
+        }"#;
+        let exports = parse_exports(json).unwrap();
+        assert_eq!(
+            exports.names,
+            ["fs", "net", "process"]
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<BTreeSet<_>>()
+        );
+
+        let old_format = json.replace("\"format_version\": 57", "\"format_version\": 20");
+        assert!(parse_exports(&old_format).is_err());
+    }
+
+    #[test]
+    fn rustdoc_args_from_rustc_args() {
+        let rustc_args = [
+            "--crate-name",
+            "rx",
+            "--edition=2021",
+            "src/lib.rs",
+            "--error-format=json",
+            "--json=diagnostic-rendered-ansi,artifacts,future-incompat",
+            "--crate-type",
+            "lib",
+            "--emit=dep-info,metadata,link",
+            "-C",
+            "embed-bitcode=no",
+            "-Ccodegen-units=1",
+            "--cfg",
+            "feature=\"std\"",
+            "-C",
+            "metadata=8e1d",
+            "-C",
+            "extra-filename=-8e1d",
+            "--out-dir",
+            "/t/cackle/deps",
+            "-L",
+            "dependency=/t/cackle/deps",
+            "--extern",
+            "libc=/t/cackle/deps/liblibc-12ab.rmeta",
+            "--cap-lints",
+            "allow",
+        ];
+        let (command, json_path) = rustdoc_command(
+            Path::new("/rust/bin/rustdoc"),
+            rustc_args.iter().map(|arg| arg.to_string()),
+        )
+        .unwrap();
+        assert_eq!(command.get_program(), "/rust/bin/rustdoc");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "--crate-name",
+                "rx",
+                "--edition",
+                "2021",
+                "--crate-type",
+                "lib",
+                "--cfg",
+                "feature=\"std\"",
+                "-L",
+                "dependency=/t/cackle/deps",
+                "--extern",
+                "libc=/t/cackle/deps/liblibc-12ab.rmeta",
+                "--cap-lints",
+                "allow",
+                "src/lib.rs",
+                "-o",
+                "/t/cackle/deps/rx-8e1d.rustdoc",
+                "-Zunstable-options",
+                "--output-format",
+                "json",
+            ]
+        );
+        assert_eq!(
+            json_path,
+            Path::new("/t/cackle/deps/rx-8e1d.rustdoc/rx.json")
+        );
+
+        // Binaries and proc macros aren't documented.
+        let bin_args = rustc_args.map(|arg| if arg == "lib" { "bin" } else { arg });
+        assert!(
+            rustdoc_command(
+                Path::new("rustdoc"),
+                bin_args.iter().map(|arg| arg.to_string())
+            )
+            .is_none()
+        );
+    }
+}
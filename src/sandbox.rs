@@ -5,7 +5,6 @@ use crate::config::permissions::PermSel;
 use crate::crate_index::CrateSel;
 use anyhow::Context;
 use anyhow::Result;
-use anyhow::bail;
 use std::ffi::OsStr;
 use std::fmt::Display;
 use std::path::Path;
@@ -16,7 +15,7 @@ mod bubblewrap;
 
 pub(crate) trait Sandbox {
     /// Runs `command` inside the sandbox.
-    fn run(&self, command: &Command) -> Result<std::process::Output>;
+    fn run(&self, command: &Command) -> Result<std::process::Output, SandboxError>;
 
     /// Bind a tmpfs at `dir`.
     fn tmpfs(&mut self, dir: &Path);
@@ -58,13 +57,39 @@ pub(crate) trait Sandbox {
     fn display_to_run(&self, command: &Command) -> Box<dyn Display>;
 }
 
-pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandbox>>> {
+/// Errors from setting up or running a sandbox.
+#[derive(Debug)]
+pub(crate) enum SandboxError {
+    /// The program that implements the sandbox couldn't be run, e.g. because it isn't installed.
+    Unavailable {
+        program: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// The sandbox config says to bind a directory that doesn't exist.
+    MissingBindDirectory(PathBuf),
+
+    /// The sandbox config says to bind something that isn't a directory.
+    BindNotDirectory(PathBuf),
+
+    /// Something else that we need in order to set up the sandbox wasn't available, e.g. an
+    /// environment variable.
+    Setup(anyhow::Error),
+}
+
+pub(crate) fn from_config(
+    config: &SandboxConfig,
+) -> Result<Option<Box<dyn Sandbox>>, SandboxError> {
     let mut sandbox = match &config.kind {
         None | Some(SandboxKind::Disabled) => return Ok(None),
         Some(SandboxKind::Bubblewrap) => Box::<bubblewrap::Bubblewrap>::default(),
     };
 
-    let home = PathBuf::from(std::env::var("HOME").context("Couldn't get HOME env var")?);
+    let home = PathBuf::from(
+        std::env::var("HOME")
+            .context("Couldn't get HOME env var")
+            .map_err(SandboxError::Setup)?,
+    );
     // We allow access to the root of the filesystem, but only selected parts of the user's home
     // directory. The home directory is where sensitive stuff is most likely to live. e.g. access
     // tokens, credentials, ssh keys etc.
@@ -87,7 +112,9 @@ pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandb
     }
 
     // Allow read access to the crate's root source directory.
-    sandbox.ro_bind(Path::new(&get_env("CARGO_MANIFEST_DIR")?));
+    sandbox.ro_bind(Path::new(
+        &get_env("CARGO_MANIFEST_DIR").map_err(SandboxError::Setup)?,
+    ));
 
     // LD_LIBRARY_PATH is set when running `cargo test` on crates that normally compile as
     // cdylibs - e.g. proc macros. If we don't pass it through, those tests will fail to find
@@ -97,22 +124,17 @@ pub(crate) fn from_config(config: &SandboxConfig) -> Result<Option<Box<dyn Sandb
 
     for dir in &config.bind_writable {
         if !dir.exists() {
-            bail!(
-                "Sandbox config says to bind directory `{}`, but that doesn't exist",
-                dir.display()
-            );
+            return Err(SandboxError::MissingBindDirectory(dir.clone()));
         }
         if !dir.is_dir() {
-            bail!(
-                "Sandbox config says to bind directory `{}`, but that isn't a directory",
-                dir.display()
-            );
+            return Err(SandboxError::BindNotDirectory(dir.clone()));
         }
         sandbox.writable_bind(dir);
     }
     for dir in &config.make_writable {
         std::fs::create_dir_all(dir)
-            .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+            .with_context(|| format!("Failed to create directory `{}`", dir.display()))
+            .map_err(SandboxError::Setup)?;
         sandbox.writable_bind(dir);
     }
     for arg in &config.extra_args {
@@ -227,20 +249,56 @@ pub(crate) fn available_kind() -> SandboxKind {
     }
 }
 
-pub(crate) fn verify_kind(kind: SandboxKind) -> Result<()> {
+pub(crate) fn verify_kind(kind: SandboxKind) -> Result<(), SandboxError> {
     if kind == SandboxKind::Bubblewrap
-        && std::process::Command::new("bwrap")
+        && let Err(source) = std::process::Command::new("bwrap")
             .arg("--version")
             .output()
-            .is_err()
     {
-        anyhow::bail!(
-            "Failed to run `bwrap`, perhaps it needs to be installed? On systems with apt you can `sudo apt install bubblewrap`"
-        );
+        return Err(SandboxError::Unavailable {
+            program: PathBuf::from("bwrap"),
+            source,
+        });
     }
     Ok(())
 }
 
+impl Display for SandboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxError::Unavailable { program, .. } if program == Path::new("bwrap") => write!(
+                f,
+                "Failed to run `bwrap`, perhaps it needs to be installed? On systems with apt you \
+                 can `sudo apt install bubblewrap`"
+            ),
+            SandboxError::Unavailable { program, .. } => {
+                write!(f, "Failed to run sandbox command: {}", program.display())
+            }
+            SandboxError::MissingBindDirectory(dir) => write!(
+                f,
+                "Sandbox config says to bind directory `{}`, but that doesn't exist",
+                dir.display()
+            ),
+            SandboxError::BindNotDirectory(dir) => write!(
+                f,
+                "Sandbox config says to bind directory `{}`, but that isn't a directory",
+                dir.display()
+            ),
+            SandboxError::Setup(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SandboxError::Unavailable { source, .. } => Some(source),
+            SandboxError::MissingBindDirectory(_) | SandboxError::BindNotDirectory(_) => None,
+            SandboxError::Setup(error) => error.source(),
+        }
+    }
+}
+
 fn env_vars_file(target_dir: &Path, profile: &str, crate_sel: &CrateSel) -> PathBuf {
     env_vars_dir(target_dir, profile).join(crate_sel.pkg_id.to_string())
 }
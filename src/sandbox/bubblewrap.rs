@@ -1,10 +1,11 @@
 use super::Sandbox;
-use anyhow::Context;
+use super::SandboxError;
 use anyhow::Result;
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::fmt::Display;
 use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
 
 #[derive(Default)]
@@ -79,14 +80,14 @@ impl Sandbox for Bubblewrap {
         self.arg("--share-net");
     }
 
-    fn run(&self, command: &Command) -> Result<std::process::Output> {
+    fn run(&self, command: &Command) -> Result<std::process::Output, SandboxError> {
         let mut command = self.command(command);
-        command.output().with_context(|| {
-            format!(
-                "Failed to run sandbox command: {}",
-                Path::new(command.get_program()).display()
-            )
-        })
+        command
+            .output()
+            .map_err(|source| SandboxError::Unavailable {
+                program: PathBuf::from(command.get_program()),
+                source,
+            })
     }
 
     fn display_to_run(&self, command: &Command) -> Box<dyn Display> {
@@ -0,0 +1,179 @@
+//! Detection of build artifacts that no longer correspond to the code. When replaying saved
+//! requests, we analyse whatever an earlier build left in the target directory rather than building
+//! again. If sources or Cargo.lock have been modified since then, what we find in those artifacts
+//! may not reflect the code that's about to be committed, so approving it would be a mistake.
+//!
+//! Artifacts packed by `pack-artifacts` are generally analysed on a different machine with a fresh
+//! checkout, so modification times don't tell us anything. For those, we compare hashes of the
+//! inputs instead.
+
+use crate::proxy::rpc::Request;
+use anyhow::Context;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Returns the inputs to the build that produced `requests` that have since been modified or
+/// removed. `lock_file` is the path to Cargo.lock, if there is one.
+pub(crate) fn stale_inputs(requests: &[Request], lock_file: Option<&Path>) -> Vec<PathBuf> {
+    // Inputs modified after the last link definitely aren't reflected in the artifacts. We don't
+    // compare against earlier links, since build scripts, which are linked early, can write sources
+    // into OUT_DIR.
+    let Some(built) = requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::LinkerInvoked(info) => modified(&info.output_file),
+            _ => None,
+        })
+        .max()
+    else {
+        return Vec::new();
+    };
+    source_paths(requests)
+        .into_iter()
+        .filter(|path| modified(path).is_none_or(|time| time > built))
+        .chain(lock_file.filter(|path| modified(path).is_some_and(|time| time > built)))
+        .map(Path::to_owned)
+        .collect()
+}
+
+/// Returns the SHA-256 of each input to the build that produced `requests`. Sources that no longer
+/// exist, e.g. because they were written by a build script to a directory that's since been
+/// removed, are skipped.
+pub(crate) fn input_hashes(
+    requests: &[Request],
+    lock_file: Option<&Path>,
+) -> BTreeMap<PathBuf, String> {
+    let mut hashes = BTreeMap::new();
+    for path in source_paths(requests).into_iter().chain(lock_file) {
+        let Ok(bytes) = std::fs::read(path) else {
+            continue;
+        };
+        hashes.insert(path.to_owned(), crate::prebuilt::sha256_hex(&bytes));
+    }
+    hashes
+}
+
+/// Returns the inputs from `hashes`, as returned by `input_hashes`, that have since been modified
+/// or removed.
+pub(crate) fn changed_inputs(hashes: &BTreeMap<PathBuf, String>) -> Result<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+    for (path, expected) in hashes {
+        let hash = match std::fs::read(path) {
+            Ok(bytes) => crate::prebuilt::sha256_hex(&bytes),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read `{}`", path.display()));
+            }
+        };
+        if hash != *expected {
+            changed.push(path.clone());
+        }
+    }
+    Ok(changed)
+}
+
+fn source_paths(requests: &[Request]) -> BTreeSet<&Path> {
+    requests
+        .iter()
+        .flat_map(|request| match request {
+            Request::RustcComplete(output) => output.source_paths.as_slice(),
+            _ => &[],
+        })
+        .map(PathBuf::as_path)
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::changed_inputs;
+    use super::input_hashes;
+    use super::stale_inputs;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::testing::pkg_id;
+    use crate::link_info::LinkInfo;
+    use crate::proxy::rpc::Request;
+    use crate::proxy::rpc::RustcOutput;
+    use std::path::Path;
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    #[test]
+    fn modified_after_build() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let build_time = SystemTime::now() - Duration::from_secs(100);
+        let write = |name: &str, time: SystemTime| {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(time).unwrap();
+            path
+        };
+        let lib = write("lib.rs", build_time - Duration::from_secs(10));
+        let main = write("main.rs", build_time + Duration::from_secs(10));
+        let lock = write("Cargo.lock", build_time - Duration::from_secs(10));
+        let bin = write("bin", build_time);
+        let link_info: LinkInfo = serde_json::from_value(serde_json::json!({
+            "crate_sel": CrateSel::primary(pkg_id("foo")),
+            "object_paths": [],
+            "output_file": bin,
+            "is_shared": false,
+        }))
+        .unwrap();
+        let requests = vec![
+            Request::RustcComplete(RustcOutput {
+                crate_sel: link_info.crate_sel.clone(),
+                source_paths: vec![lib.clone(), main.clone(), dir.path().join("removed.rs")],
+                link_libs: Vec::new(),
+                remap_path_prefixes: Vec::new(),
+                out_dir: None,
+                rustdoc_json: None,
+            }),
+            Request::LinkerInvoked(link_info),
+        ];
+        assert_eq!(
+            stale_inputs(&requests, Some(&lock)),
+            vec![main, dir.path().join("removed.rs")]
+        );
+        assert!(stale_inputs(&requests[..1], Some(&lock)).is_empty());
+
+        let lock = write("Cargo.lock", build_time + Duration::from_secs(10));
+        assert!(stale_inputs(&requests, Some(&lock)).contains(&lock));
+        assert!(!stale_inputs(&requests, None).contains(&lock));
+        assert_eq!(
+            stale_inputs(&requests, Some(Path::new("/nonexistent"))).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn changed_hashes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let lib = dir.path().join("lib.rs");
+        let main = dir.path().join("main.rs");
+        let lock = dir.path().join("Cargo.lock");
+        for path in [&lib, &main, &lock] {
+            std::fs::write(path, "a").unwrap();
+        }
+        let requests = vec![Request::RustcComplete(RustcOutput {
+            crate_sel: CrateSel::primary(pkg_id("foo")),
+            source_paths: vec![lib.clone(), main.clone(), dir.path().join("gone.rs")],
+            link_libs: Vec::new(),
+            remap_path_prefixes: Vec::new(),
+            out_dir: None,
+            rustdoc_json: None,
+        })];
+        let hashes = input_hashes(&requests, Some(&lock));
+        assert_eq!(hashes.len(), 3);
+        assert!(changed_inputs(&hashes).unwrap().is_empty());
+        std::fs::write(&main, "b").unwrap();
+        std::fs::remove_file(&lock).unwrap();
+        assert_eq!(changed_inputs(&hashes).unwrap(), vec![lock, main]);
+    }
+}
@@ -1,11 +1,19 @@
+use crate::async_runtime::AsyncRuntime;
+use crate::async_runtime::RuntimeBindings;
 use crate::config::Config;
 use crate::config::PackageConfig;
 use crate::config::permissions::PermSel;
 use crate::crate_index::CrateIndex;
+use crate::crate_index::PackageId;
+use crate::determinism::NondeterminismSource;
+use crate::determinism::NondeterminismSources;
+use crate::panic_audit::PanicPath;
+use crate::panic_audit::PanicPaths;
 use clap::{Parser, ValueEnum};
 use rustc_hash::FxHashMap;
 use serde_json::Value;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::fmt::Display;
 
@@ -61,6 +69,20 @@ pub(crate) struct SummaryOptions {
 struct PackageSummary {
     pub(crate) name: PermSel,
     pub(crate) permissions: Vec<String>,
+    /// Whether the package is only a dependency of tests.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) test_only: bool,
+    /// The async runtimes that the package was found to bind to. Only known after a build.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) async_runtimes: Vec<AsyncRuntime>,
+    /// Whether the package was found to reference panic machinery or abort. Only known after a
+    /// build.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) panic_paths: Vec<PanicPath>,
+    /// The sources of nondeterminism that the package was found to reference. Only known after a
+    /// build with `common.determinism_audit`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) nondeterminism: Vec<NondeterminismSource>,
 }
 
 impl PackageSummary {
@@ -77,8 +99,10 @@ impl Summary {
     pub(crate) fn new(crate_index: &CrateIndex, config: &Config) -> Self {
         let pkg_configs: FxHashMap<&PermSel, &PackageConfig> =
             config.permissions.packages.iter().collect();
+        let isolate_test_only = config.raw.common.isolate_test_only_deps;
         let mut packages: Vec<PackageSummary> = crate_index
             .package_ids()
+            .filter(|pkg_id| !(isolate_test_only && crate_index.is_test_only(pkg_id)))
             .map(|pkg_id| {
                 let mut permissions = Vec::new();
                 let pkg_name = PermSel::for_primary(pkg_id.name_str());
@@ -88,7 +112,7 @@ impl Summary {
                         if pkg_config.allow_proc_macro {
                             permissions.push(format!("proc_macro{suffix}"));
                         }
-                        if pkg_config.allow_unsafe {
+                        if pkg_config.allow_unsafe.is_allowed() {
                             permissions.push(format!("unsafe{suffix}"));
                         }
                         for api in &pkg_config.allow_apis {
@@ -99,6 +123,10 @@ impl Summary {
                 PackageSummary {
                     name: pkg_name,
                     permissions,
+                    test_only: crate_index.is_test_only(pkg_id),
+                    async_runtimes: Vec::new(),
+                    panic_paths: Vec::new(),
+                    nondeterminism: Vec::new(),
                 }
             })
             .collect();
@@ -107,6 +135,31 @@ impl Summary {
         Self { packages }
     }
 
+    /// Records which async runtimes each package binds to, as found when analysing binaries.
+    pub(crate) fn with_async_runtimes(mut self, bindings: &RuntimeBindings) -> Self {
+        for pkg in &mut self.packages {
+            pkg.async_runtimes = found_in(&pkg.name, bindings);
+        }
+        self
+    }
+
+    /// Records which packages reference panic machinery or abort, as found when analysing binaries.
+    pub(crate) fn with_panic_paths(mut self, panic_paths: &PanicPaths) -> Self {
+        for pkg in &mut self.packages {
+            pkg.panic_paths = found_in(&pkg.name, panic_paths);
+        }
+        self
+    }
+
+    /// Records which sources of nondeterminism each package references, as found when analysing
+    /// binaries.
+    pub(crate) fn with_nondeterminism(mut self, sources: &NondeterminismSources) -> Self {
+        for pkg in &mut self.packages {
+            pkg.nondeterminism = found_in(&pkg.name, sources);
+        }
+        self
+    }
+
     pub(crate) fn print(&self, options: &SummaryOptions) {
         let options = options.with_defaults();
         let mut json_map = HashMap::new();
@@ -159,7 +212,23 @@ impl Summary {
 
     fn print_by_crate(&self) {
         for pkg in &self.packages {
-            println!("{}: {}", pkg.name, pkg.permissions.join(", "));
+            let label = if pkg.test_only { " (test only)" } else { "" };
+            print!("{}{label}: {}", pkg.name, pkg.permissions.join(", "));
+            if !pkg.async_runtimes.is_empty() {
+                let runtimes: Vec<String> =
+                    pkg.async_runtimes.iter().map(|r| r.to_string()).collect();
+                print!(" (async runtimes: {})", runtimes.join(", "));
+            }
+            if !pkg.panic_paths.is_empty() {
+                let paths: Vec<String> = pkg.panic_paths.iter().map(|p| p.to_string()).collect();
+                print!(" (can {})", paths.join(", "));
+            }
+            if !pkg.nondeterminism.is_empty() {
+                let sources: Vec<String> =
+                    pkg.nondeterminism.iter().map(|s| s.to_string()).collect();
+                print!(" (nondeterminism: {})", sources.join(", "));
+            }
+            println!();
         }
     }
 
@@ -293,6 +362,48 @@ impl Display for Summary {
                 .filter(|p| p.is_proc_macro_with_other_permissions())
                 .count()
         )?;
+        let mut runtime_counts: BTreeMap<AsyncRuntime, usize> = BTreeMap::new();
+        for pkg in &self.packages {
+            for runtime in &pkg.async_runtimes {
+                *runtime_counts.entry(*runtime).or_default() += 1;
+            }
+        }
+        for (runtime, count) in runtime_counts {
+            writeln!(f, "packages_using_{}: {count}", runtime.crate_name())?;
+        }
+        if self.packages.iter().any(|pkg| !pkg.panic_paths.is_empty()) {
+            writeln!(
+                f,
+                "packages_that_can_panic: {}",
+                self.packages
+                    .iter()
+                    .filter(|pkg| pkg.panic_paths.iter().any(|path| path.is_panic()))
+                    .count()
+            )?;
+        }
+        let mut source_counts: BTreeMap<NondeterminismSource, usize> = BTreeMap::new();
+        for pkg in &self.packages {
+            for source in &pkg.nondeterminism {
+                *source_counts.entry(*source).or_default() += 1;
+            }
+        }
+        for (source, count) in source_counts {
+            let source = source.to_string().replace('-', "_");
+            writeln!(f, "packages_with_nondeterminism_from_{source}: {count}")?;
+        }
         Ok(())
     }
 }
+
+/// Returns the things in `found` that were found in the package selected by `perm_sel`.
+fn found_in<T: Copy>(perm_sel: &PermSel, found: &BTreeMap<T, BTreeSet<PackageId>>) -> Vec<T> {
+    found
+        .iter()
+        .filter(|(_, pkg_ids)| {
+            pkg_ids
+                .iter()
+                .any(|pkg_id| *perm_sel == PermSel::for_primary(pkg_id.name_str()))
+        })
+        .map(|(thing, _)| *thing)
+        .collect()
+}
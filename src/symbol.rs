@@ -4,13 +4,16 @@ use crate::demangle::DemangleToken;
 use crate::names::NamesIterator;
 use anyhow::Result;
 use rustc_demangle::demangle;
+use serde::Deserialize;
+use serde::Serialize;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::str::Utf8Error;
 
 /// A symbol from an object file. The symbol might be valid UTF-8 or not. It also may or may not be
 /// mangled. Storage may be borrowed or on the heap.
-#[derive(Eq, Clone, Ord, PartialEq, PartialOrd, Hash)]
+#[derive(Eq, Clone, Ord, PartialEq, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub(crate) struct Symbol<'data> {
     bytes: Bytes<'data>,
 }
@@ -40,8 +43,22 @@ impl<'data> Symbol<'data> {
     }
 
     /// Splits the name of this symbol into names. See `crate::names::split_names` for details.
+    /// Symbols that aren't mangled get a name in `demangle::UNMANGLED_NAMESPACE`.
     pub(crate) fn names<'a>(&'a self) -> Result<NamesIterator<'a, DemangleIterator<'a>>> {
-        Ok(NamesIterator::new(DemangleIterator::new(self.to_str()?)))
+        let data = self.to_str()?;
+        let mut it = DemangleIterator::new(data);
+        if matches!(it, DemangleIterator::Empty) {
+            it = DemangleIterator::unmangled(data);
+        }
+        Ok(NamesIterator::new(it))
+    }
+
+    /// Returns whether this symbol is a mangled Rust symbol. Unmangled symbols are generally either
+    /// C functions or Rust functions with `#[no_mangle]`, either of which can be referenced by name
+    /// from code that we don't see.
+    pub(crate) fn is_mangled(&self) -> bool {
+        self.to_str()
+            .is_ok_and(|data| !matches!(DemangleIterator::new(data), DemangleIterator::Empty))
     }
 
     pub(crate) fn len(&self) -> usize {
@@ -154,6 +171,45 @@ mod tests {
         assert_eq!(Symbol::borrowed(b"foo").module_name(), None);
     }
 
+    #[test]
+    fn test_names_unmangled() {
+        assert_eq!(
+            get_name_vecs(Symbol::borrowed(b"clock_gettime").names().unwrap()),
+            vec![vec!["extern", "clock_gettime"]]
+        );
+        assert_eq!(
+            get_name_vecs(Symbol::borrowed(b"getauxval@GLIBC_2.16").names().unwrap()),
+            vec![vec!["extern", "getauxval"]]
+        );
+        assert!(get_name_vecs(Symbol::borrowed(b".Lanon.1").names().unwrap()).is_empty());
+        let cpp = Symbol::borrowed(b"_ZN7openssl11SSL_connectEP3ssl");
+        assert_eq!(
+            get_name_vecs(cpp.names().unwrap()),
+            vec![vec!["extern", "openssl", "SSL_connect"]]
+        );
+        assert!(!cpp.is_mangled());
+    }
+
+    #[test]
+    fn test_names_v0() {
+        // <lib::Foo<std::fs::File>>::new
+        let symbol = Symbol::borrowed(
+            b"_RNvMCsgS0eDBJFJRB_3libINtB2_3FooNtNtCsjrHSEGnQ3l9_3std2fs4FileE3newB2_",
+        );
+        assert_eq!(
+            get_name_vecs(symbol.names().unwrap()),
+            vec![vec!["lib", "Foo"], vec!["std", "fs", "File"], vec!["new"],]
+        );
+        assert_eq!(symbol.crate_name(), Some("lib"));
+
+        // lib::gen::<&str>
+        let symbol = Symbol::borrowed(b"_RINvCsgS0eDBJFJRB_3lib3genReEB2_");
+        assert_eq!(
+            get_name_vecs(symbol.names().unwrap()),
+            vec![vec!["lib", "gen"], vec!["str"]]
+        );
+    }
+
     #[test]
     fn test_names_literal_number() {
         let symbol = Symbol::borrowed(b"_ZN104_$LT$proc_macro2..Span$u20$as$u20$syn..span..IntoSpans$LT$$u5b$proc_macro2..Span$u3b$$u20$1$u5d$$GT$$GT$10into_spans17h8cc941d826bfc6f7E");
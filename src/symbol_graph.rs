@@ -1,61 +1,147 @@
 //! This module builds a graph of relationships between symbols and linker sections. Provided code
 //! was compiled with one symbol per section, which it should have been, there should be a 1:1
-//! relationship between symbols and sections.
+//! relationship between symbols and sections. Objects from other build systems, e.g. C code built
+//! by -sys crates without `-ffunction-sections`, can have many symbols in a section. For those, we
+//! attribute each reference to the symbol whose address range it falls within.
 //!
 //! We also parse the Dwarf debug information to determine what source file each linker section came
 //! from.
 
 use self::backtrace::Backtracer;
+use self::call_graph::CallGraph;
 use self::dwarf::SymbolDebugInfo;
+use self::frame_cache::FrameCache;
 use self::object_file_path::ObjectFilePath;
+use self::split_dwarf::SplitDwarf;
+use self::split_dwarf::SplitDwarfSections;
+use crate::async_runtime::AsyncRuntime;
+use crate::async_runtime::MultipleAsyncRuntimes;
+use crate::async_runtime::RuntimeBindings;
+use crate::build_settings::BuildSettings;
 use crate::checker::ApiUsage;
 use crate::checker::BinLocation;
 use crate::checker::Checker;
+use crate::checker::InlineFrame;
+use crate::checker::ReferenceKind;
+use crate::checker::attribution::AttributionQuery;
+use crate::config::ASM_API;
 use crate::config::ApiConfig;
 use crate::config::ApiName;
+use crate::config::BEFORE_MAIN_API;
+use crate::config::FFI_API;
+use crate::config::RAW_SYSCALL_API;
+use crate::config::ReportMode;
+use crate::config::WeakOverrideAttribution;
+use crate::config::permissions::PermSel;
 use crate::config::permissions::PermissionScope;
 use crate::crate_index::CrateSel;
 use crate::crate_index::PackageId;
+use crate::determinism::NondeterminismSource;
+use crate::determinism::NondeterminismSources;
+use crate::explain::SymbolExplanation;
+use crate::facts::SymbolFact;
+use crate::graph::GraphSymbol;
+use crate::graph::ReferenceGraph;
 use crate::link_info::LinkInfo;
 use crate::location::SourceLocation;
+use crate::name_display::shorten_for_json;
 use crate::names::DebugName;
 use crate::names::Name;
 use crate::names::SymbolAndName;
 use crate::names::SymbolOrDebugName;
+use crate::panic_audit::PanicPath;
+use crate::prebuilt::sha256_hex;
 use crate::problem::ApiUsages;
+use crate::problem::LtoBitcode;
+use crate::problem::MissingDebugInfo;
+use crate::problem::MissingObjects;
 use crate::problem::PossibleExportedApi;
+use crate::problem::Problem;
 use crate::problem::ProblemList;
+use crate::proxy::cargo::profile_name;
 use crate::symbol::Symbol;
+use crate::timing::TimingCollector;
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
-use ar::Archive;
 use gimli::DwarfSections;
 use gimli::EndianSlice;
-use gimli::LittleEndian;
+use gimli::RunTimeEndian;
 use log::debug;
+use log::info;
 use log::trace;
+use object::Architecture;
+use object::ComdatKind;
+use object::CompressionFormat;
 use object::Object;
+use object::ObjectComdat;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::ReadCache;
+use object::ReadRef;
+use object::RelocationEncoding;
+use object::RelocationFlags;
+use object::RelocationKind;
 use object::RelocationTarget;
+use object::SectionFlags;
 use object::SectionIndex;
+use object::SectionKind;
+use object::SymbolFlags;
+use object::SymbolKind;
+use object::read::archive::ArchiveFile;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::hash_map::Entry;
+use std::ffi::OsStr;
 use std::fmt::Display;
-use std::fs::File;
 use std::io::Read;
+use std::io::Seek;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 pub(crate) mod backtrace;
+mod call_graph;
+mod debug_link;
+mod debuginfod;
+mod disassemble;
 mod dwarf;
+mod frame_cache;
+mod inline_asm;
 pub(crate) mod object_file_path;
+mod scan_cache;
+mod split_dwarf;
+mod thin_archive;
+mod wasm;
+
+use scan_cache::CachedCall;
+use scan_cache::CachedGroupUsages;
+use scan_cache::CachedObject;
+use scan_cache::CachedReference;
+use scan_cache::CachedUsage;
+use scan_cache::RelativeLocation;
+use scan_cache::ScanCache;
+use scan_cache::ScanSettings;
+use wasm::WasmModule;
+
+/// The size above which we parse object files in place rather than reading them all at once.
+const MAX_BUFFERED_OBJECT_SIZE: u64 = 32 * 1024 * 1024;
+
+/// How much of an object file we read at once when hashing one that we're parsing in place.
+const HASH_CHUNK_SIZE: u64 = 1024 * 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Filetype {
@@ -63,13 +149,74 @@ enum Filetype {
     Other,
 }
 
-struct ApiUsageCollector<'input, 'backtracer> {
+/// Collects API usages from a binary. Object files are processed in parallel, each by a separate
+/// collector, which is then merged into the main collector. See `process_files`.
+struct ApiUsageCollector<'input, 'bin> {
     outputs: ScanOutputs,
-    backtracer: Option<&'backtracer mut Backtracer>,
 
-    bin: BinInfo<'input>,
+    /// References that we've found, as the location of the reference and the address referenced.
+    /// These are passed to the backtracer once we're done. `None` if backtraces are disabled.
+    back_references: Option<Vec<(BinLocation, u64)>>,
+
+    /// Calls between functions in the binary, as the address of the calling function and the
+    /// address of the called function. Used to find call paths to API usages and, with
+    /// `ignore_unreachable`, which functions are reachable. Unless we need to know what's
+    /// reachable, we only record calls between functions of the same package, since call paths
+    /// don't leave the package.
+    calls: FxHashSet<(u64, u64)>,
+
+    /// All references that we've found, for `cackle graph`. `None` if we're not exporting a graph.
+    graph_references: Option<Vec<GraphReference>>,
+
+    /// Addresses of functions that we consider to be reachable regardless of whether anything that
+    /// we found calls them. e.g. functions in `.init_array`, which run before main.
+    roots: FxHashSet<u64>,
+
+    /// The packages that the function at each address belongs to. Memoised, since we look this up
+    /// for both ends of every call.
+    pkg_ids_by_address: FxHashMap<u64, Vec<PackageId>>,
+
+    /// For weak symbols that were overridden, the address of the overriding definition and the
+    /// package that provided the weak definition. Only populated when the config asks for API
+    /// usages to be attributed to the overridden package.
+    weak_overrides: FxHashMap<u64, PackageId>,
+
+    /// When the object that we're processing contains native code that a package's build script
+    /// built, e.g. C code in a `-sys` crate, the package that built it. API usages from the object
+    /// are attributed to this package regardless of what debug info says. See
+    /// `native_object_pkg_id`.
+    native_pkg_id: Option<PackageId>,
+
+    /// With `common.detect_ffi`, the symbols defined by native objects, i.e. those for which we
+    /// found a `native_pkg_id`. See `retain_foreign_usages`.
+    native_definitions: FxHashSet<Symbol<'static>>,
+
+    bin: &'bin BinInfo<'input>,
+    scan_cache: Option<&'bin ScanCache>,
     debug_enabled: bool,
+    /// Object files larger than this are parsed in place rather than being read all at once.
+    max_buffered_object_size: u64,
     new_api_usages: FxHashMap<ApiUsageGroupKey, Vec<SingleApiUsage>>,
+    /// The signatures of the COMDAT groups in the objects that we've processed. When several
+    /// objects contain the same group, e.g. an inline C++ function, the linker keeps the copy from
+    /// the first object and discards the rest.
+    groups: FxHashSet<Symbol<'static>>,
+    /// API usages from sections in COMDAT groups, by group signature. These are kept separate from
+    /// `new_api_usages` until `merge`, when we know whether an earlier object, processed by another
+    /// thread, had the same group.
+    group_api_usages: FxHashMap<Symbol<'static>, FxHashMap<ApiUsageGroupKey, Vec<SingleApiUsage>>>,
+    /// The number of object files that we've found, including ones that contain LLVM bitcode.
+    objects_found: usize,
+    /// Timings for the per-relocation work. These are merged into the checker's timings once we're
+    /// done, since we only have shared access to the checker while processing object files.
+    timings: TimingCollector,
+}
+
+struct GraphReference {
+    location: BinLocation,
+    target_address: u64,
+    /// `None` for references that we found other than via a relocation, e.g. by disassembly.
+    relocation: Option<RelocationKind>,
 }
 
 struct SingleApiUsage {
@@ -86,11 +233,32 @@ struct BinInfo<'input> {
     crate_sel: CrateSel,
     symbol_addresses: FxHashMap<Symbol<'input>, u64>,
     /// Symbols that we've already determined have no APIs. This is an optimisation that lets us
-    /// skip these symbols when we see them again.
-    symbol_has_no_apis: FxHashMap<Symbol<'input>, bool>,
+    /// skip these symbols when we see them again. The values are atomic so that the cache can be
+    /// shared by the threads that process object files.
+    symbol_has_no_apis: FxHashMap<Symbol<'input>, AtomicBool>,
 
     /// Information about each symbol obtained from the debug info.
     symbol_debug_info: FxHashMap<Symbol<'input>, SymbolDebugInfo<'input>>,
+
+    /// Symbols that are still weak in the binary. A symbol that's weak in an object file, but not
+    /// here, was overridden by a strong definition from elsewhere.
+    weak_symbols: FxHashSet<Symbol<'input>>,
+
+    /// Addresses of global symbols that code we don't analyse, such as the C runtime, the standard
+    /// library or the dynamic linker, could refer to by name. These include `main` and functions
+    /// with `#[no_mangle]`.
+    entry_points: Vec<u64>,
+
+    /// The address ranges of the binary's executable sections.
+    executable_ranges: Vec<std::ops::Range<u64>>,
+
+    /// Whether the binary lacks debug info for our packages or lacks a symbol table, in which case
+    /// object files are analysed going only by symbol names. See `process_object_symbols`.
+    symbol_only: bool,
+
+    /// With `common.detect_ffi`, the symbols that the binary imports from shared libraries other
+    /// than the C library and others that the standard library links against.
+    foreign_imports: FxHashSet<Symbol<'static>>,
 }
 
 #[derive(Default)]
@@ -101,15 +269,48 @@ pub(crate) struct ScanOutputs {
     /// once computed, they won't be recomputed.
     base_problems: ProblemList,
 
+    /// Object files that we skipped because they contain LLVM bitcode rather than machine code.
+    bitcode_objects: Vec<ObjectFilePath>,
+
+    /// Files that we were given that didn't contain any object files, e.g. `.rmeta` files.
+    files_without_objects: Vec<PathBuf>,
+
     possible_exported_apis: Vec<PossibleExportedApi>,
 
+    /// How each crate in the binary appears to have been built, one entry per unit of debug info.
+    pub(crate) build_settings: Vec<(PackageId, BuildSettings)>,
+
     /// The API definitions used to produce these outputs. Used to determine if we need to recompute
     /// API usages.
     pub(crate) apis: BTreeMap<ApiName, ApiConfig>,
+
+    /// For `cackle graph`, the references that we found in the binary.
+    pub(crate) reference_graph: Option<ReferenceGraph>,
+
+    /// For `cackle explain-symbol`, what we found out about the symbol in this binary.
+    pub(crate) symbol_explanations: Vec<SymbolExplanation>,
+
+    /// For plugins, the symbols defined in the binary.
+    pub(crate) symbols: Vec<SymbolFact>,
+
+    /// The async runtimes that packages in the binary bind to.
+    pub(crate) async_runtimes: RuntimeBindings,
+
+    /// References to panic machinery and to abort. See `panic_audit.rs`.
+    panic_usages: FxHashMap<(PackageId, ApiName), ApiUsages>,
+
+    /// References to the allocator. See `alloc_audit.rs`.
+    alloc_usages: FxHashMap<(PackageId, ApiName), ApiUsages>,
+
+    /// References to denied items. See `deny_list.rs`.
+    denied_usages: FxHashMap<(PackageId, ApiName), ApiUsages>,
+
+    /// The sources of nondeterminism that packages in the binary reference. See `determinism.rs`.
+    pub(crate) nondeterminism: NondeterminismSources,
 }
 
-struct ObjectIndex<'obj, 'data> {
-    obj: &'obj object::File<'data>,
+struct ObjectIndex<'obj, 'data, R: ReadRef<'data>> {
+    obj: &'obj object::File<'data, R>,
 
     section_infos: Vec<SectionInfo<'data>>,
 }
@@ -117,89 +318,275 @@ struct ObjectIndex<'obj, 'data> {
 #[derive(Clone, Default)]
 struct SectionInfo<'data> {
     first_symbol: Option<SymbolInfo<'data>>,
+
+    /// All symbols defined in the section, sorted by offset, then by preference. Empty unless the
+    /// symbols are at more than one offset, since then references are all attributed to
+    /// `first_symbol`.
+    symbols: Vec<SymbolInfo<'data>>,
+
+    /// The signature of the COMDAT group that the section belongs to, if any.
+    group: Option<Symbol<'data>>,
 }
 
 #[derive(Clone)]
 struct SymbolInfo<'data> {
-    /// The first symbol in the section.
     symbol: Symbol<'data>,
 
-    /// The offset of the symbol.
+    /// The offset of the symbol within its section.
     offset: u64,
+
+    binding: SymbolBinding,
+}
+
+/// How a symbol is defined. Ordered by preference when several symbols start at the same offset in
+/// a section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SymbolBinding {
+    Strong,
+    /// The definition is used unless there's a strong definition elsewhere.
+    Weak,
+    /// A GNU indirect function. The symbol's address is that of a resolver function that returns the
+    /// implementation to use. The resolver generally has its own symbol at the same address, which
+    /// is the one that the debug info describes.
+    Ifunc,
+}
+
+/// Errors from scanning a binary and the object files that it was linked from.
+#[derive(Debug)]
+pub(crate) enum ScanError {
+    /// The binary couldn't be read.
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// The binary couldn't be parsed.
+    CorruptBinary {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+
+    /// The binary's debug info, or its split debug info, couldn't be loaded or processed. We need
+    /// debug info to determine which package each bit of code came from.
+    DebugInfo {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+
+    /// An object file or archive that was linked into the binary couldn't be read or processed.
+    CorruptObject {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+
+    Other(anyhow::Error),
 }
 
 pub(crate) fn scan_objects(
     paths: &[PathBuf],
     link_info: &LinkInfo,
     checker: &mut Checker,
+) -> Result<(ScanOutputs, Option<Backtracer>), ScanError> {
+    // Errors that we want to classify are created as `ScanError`s, then passed up as
+    // `anyhow::Error`s, which lets us keep using `?` within the scanner.
+    scan(paths, link_info, checker).map_err(|error| {
+        error
+            .downcast::<ScanError>()
+            .unwrap_or_else(ScanError::Other)
+    })
+}
+
+fn scan(
+    paths: &[PathBuf],
+    link_info: &LinkInfo,
+    checker: &mut Checker,
 ) -> Result<(ScanOutputs, Option<Backtracer>)> {
     log::info!("Scanning {}", link_info.output_file.display());
-    let start = Instant::now();
-    let file_bytes = std::fs::read(&link_info.output_file)
-        .with_context(|| format!("Failed to read `{}`", link_info.output_file.display()))?;
-    checker.timings.add_timing(start, "Read bin file");
+    let start = checker.timings.now();
+    let file_bytes =
+        checker
+            .vfs
+            .read(&link_info.output_file)
+            .map_err(|source| ScanError::Read {
+                path: link_info.output_file.to_path_buf(),
+                source,
+            })?;
+    let start = checker.timings.add_timing(start, "Read bin file");
+    let is_wasm = wasm::is_wasm(&file_bytes);
+    let debug_file_bytes = if is_wasm {
+        None
+    } else {
+        debug_link::load_separate_debug_file(
+            &file_bytes,
+            &link_info.output_file,
+            checker.args.debuginfod,
+        )?
+    };
+    checker
+        .timings
+        .add_timing(start, "Read separate debug file");
 
     // Backtraces require that we keep a bunch of stuff around, which uses up memory, so we only do
-    // it if the UI is active and if we haven't explicitly disabled backtraces.
-    let backtraces = !checker.args.no_backtrace && !checker.args.no_ui;
+    // it if the UI is active and if we haven't explicitly disabled backtraces. Backtraces aren't yet
+    // supported for wasm.
+    let backtraces = !checker.args.no_backtrace && !checker.args.no_ui && !is_wasm;
     let mut backtracer = backtraces.then(|| Backtracer::new(checker.sysroot.clone()));
-    let outputs =
-        scan_object_with_bin_bytes(&file_bytes, checker, backtracer.as_mut(), link_info, paths)?;
+    let outputs = scan_object_with_bin_bytes(
+        &file_bytes,
+        debug_file_bytes.as_deref(),
+        checker,
+        backtracer.as_mut(),
+        link_info,
+        paths,
+    )?;
 
     if let Some(b) = backtracer.as_mut() {
-        b.provide_bin_bytes(file_bytes);
+        // Backtraces only need the debug info, so if it's in a separate file, that's all we need.
+        b.provide_bin_bytes(
+            link_info.output_file.clone(),
+            debug_file_bytes.unwrap_or(file_bytes),
+        );
     }
     Ok((outputs, backtracer))
 }
 
 fn scan_object_with_bin_bytes(
-    bin_file_bytes: &Vec<u8>,
+    bin_file_bytes: &[u8],
+    debug_file_bytes: Option<&[u8]>,
     checker: &mut Checker,
     backtracer: Option<&mut Backtracer>,
     link_info: &LinkInfo,
     paths: &[PathBuf],
 ) -> Result<ScanOutputs> {
-    let start = Instant::now();
-    let obj = object::File::parse(bin_file_bytes.as_slice())
-        .with_context(|| format!("Failed to parse {}", link_info.output_file.display()))?;
-    let owned_dwarf_sections = DwarfSections::load(|id| load_section(&obj, id))?;
-    let dwarf = owned_dwarf_sections
-        .borrow(|section| gimli::EndianSlice::new(section, gimli::LittleEndian));
+    let start = checker.timings.now();
+    let bin = Bin::parse(bin_file_bytes, debug_file_bytes).map_err(|source| {
+        ScanError::CorruptBinary {
+            path: link_info.output_file.to_path_buf(),
+            source,
+        }
+    })?;
+    let owned_dwarf_sections = DwarfSections::load(|id| bin.load_section(id))?;
+    let endian = bin.endian();
+    let dwarf = owned_dwarf_sections.borrow(|section| gimli::EndianSlice::new(section, endian));
     let start = checker.timings.add_timing(start, "Parse bin");
-    let debug_artifacts =
-        dwarf::DebugArtifacts::from_dwarf(&dwarf, checker).with_context(|| {
+    let debug_info_error = |source| ScanError::DebugInfo {
+        path: link_info.output_file.to_path_buf(),
+        source,
+    };
+    let split_dwarf_sections = SplitDwarfSections::load(&dwarf, &link_info.output_file)
+        .context("Failed to load split debug info")
+        .map_err(debug_info_error)?;
+    let split_dwarf = split_dwarf_sections
+        .borrow(&dwarf)
+        .map_err(debug_info_error)?;
+    let start = checker.timings.add_timing(start, "Load split debug info");
+    let debug_artifacts = dwarf::DebugArtifacts::from_dwarf(&dwarf, &split_dwarf, checker)
+        .map_err(debug_info_error)?;
+    let start = checker.timings.add_timing(start, "Read debug artifacts");
+    // addr2line contexts can't be shared between threads, so each thread that processes object
+    // files creates its own.
+    let new_ctx = || {
+        addr2line::Context::from_dwarf(
+            owned_dwarf_sections.borrow(|section| gimli::EndianSlice::new(section, endian)),
+        )
+        .with_context(|| {
             format!(
-                "Failed while processing debug info for `{}`",
+                "Failed in addr2line for `{}`",
                 link_info.output_file.display()
             )
-        })?;
-    let start = checker.timings.add_timing(start, "Read debug artifacts");
-    let ctx = addr2line::Context::from_dwarf(dwarf).with_context(|| {
-        format!(
-            "Failed in addr2line for `{}`",
-            link_info.output_file.display()
-        )
-    })?;
+        })
+    };
+    let ctx = new_ctx()?;
     let start = checker.timings.add_timing(start, "Build addr2line context");
+    let build_settings = debug_artifacts
+        .unit_settings
+        .iter()
+        .flat_map(|(source_path, settings)| {
+            checker
+                .opt_pkg_ids_from_source_path(source_path)
+                .unwrap_or_default()
+                .iter()
+                .map(|pkg_id| (pkg_id.clone(), settings.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
     let no_api_symbol_hashes = debug_artifacts
         .symbol_debug_info
         .keys()
-        .map(|symbol| (symbol.clone(), false))
+        .map(|symbol| (symbol.clone(), AtomicBool::new(false)))
         .collect();
-    let mut collector = ApiUsageCollector {
-        outputs: Default::default(),
-        backtracer,
-        bin: BinInfo {
-            filename: link_info.output_file.clone(),
-            crate_sel: link_info.crate_sel.clone(),
-            symbol_addresses: Default::default(),
-            symbol_debug_info: debug_artifacts.symbol_debug_info,
-            symbol_has_no_apis: no_api_symbol_hashes,
-        },
-        debug_enabled: checker.args.debug,
-        new_api_usages: FxHashMap::default(),
+    let mut bin_info = BinInfo {
+        filename: link_info.output_file.clone(),
+        crate_sel: link_info.crate_sel.clone(),
+        symbol_addresses: Default::default(),
+        symbol_debug_info: debug_artifacts.symbol_debug_info,
+        symbol_has_no_apis: no_api_symbol_hashes,
+        weak_symbols: Default::default(),
+        entry_points: Vec::new(),
+        executable_ranges: Vec::new(),
+        symbol_only: false,
+        foreign_imports: Default::default(),
     };
-    collector.bin.load_symbols(&obj)?;
+    if checker.config.raw.detects_ffi() {
+        bin_info.foreign_imports =
+            crate::native_libs::foreign_imports(bin_file_bytes, &link_info.output_file)?
+                .iter()
+                .map(|name| Symbol::borrowed(name).to_heap())
+                .collect();
+    }
+    match &bin {
+        Bin::Object { .. } => bin_info.load_symbols(
+            bin.symbols_obj(),
+            !checker.config.raw.common.keep_symbol_versions,
+        )?,
+        Bin::Wasm(module) => bin_info.load_wasm_symbols(module),
+    }
+    // The standard library comes with debug info, so a binary can have some even when none of our
+    // packages were compiled with it.
+    let missing_debug_info = debug_artifacts
+        .unit_settings
+        .iter()
+        .all(|(source_path, _)| checker.is_in_rust_std(source_path));
+    let stripped = bin_info.symbol_addresses.is_empty();
+    if matches!(bin, Bin::Object { .. }) && (missing_debug_info || stripped) {
+        bin_info.symbol_only = true;
+    }
+    // Cache entries don't include debug data or the references needed for `cackle graph`, so we
+    // bypass the cache when either is requested. Entries also need symbol addresses, which a
+    // symbol-only analysis may not have.
+    let scan_cache = checker
+        .scan_cache_dir()
+        .filter(|_| {
+            !checker.args.debug && checker.args.graph_options().is_none() && !bin_info.symbol_only
+        })
+        .map(|dir| {
+            let common = &checker.config.raw.common;
+            ScanCache::new(
+                dir,
+                &ScanSettings {
+                    apis: checker.config.raw.apis.clone(),
+                    pseudo_apis: crate::checker::pseudo_apis(&checker.config.raw),
+                    attribution: common.attribution_strategies().to_vec(),
+                    remaps: checker.path_remaps().cloned().collect(),
+                    backtraces: backtracer.is_some(),
+                    all_calls: common.ignore_unreachable,
+                    ignore_instrumentation: common.ignore_instrumentation,
+                },
+            )
+        })
+        .transpose()?;
+    let mut collector = ApiUsageCollector::new(
+        &bin_info,
+        scan_cache.as_ref(),
+        backtracer.is_some(),
+        checker.args.graph_options().is_some(),
+        checker.args.debug,
+        checker.timings.child(),
+    );
+    collector.outputs.build_settings = build_settings;
+    if let Some(options) = checker.args.explain_symbol_options() {
+        collector.outputs.symbol_explanations = bin_info.explain_symbol(&options.symbol, checker);
+    }
     let start = checker.timings.add_timing(start, "Load symbols from bin");
     for f in debug_artifacts.inlined_functions {
         let from = Node {
@@ -210,6 +597,7 @@ fn scan_object_with_bin_bytes(
             Some(UsageDebugData::Inlined(InlinedDebugData::from_offset(
                 Some(f.bin_location.address),
                 &ctx,
+                &split_dwarf,
             )?))
         } else {
             None
@@ -219,6 +607,8 @@ fn scan_object_with_bin_bytes(
             None,
             &from,
             &f.to,
+            ReferenceKind::Call,
+            None,
             checker,
             debug_data.as_ref(),
         )?;
@@ -228,189 +618,1762 @@ fn scan_object_with_bin_bytes(
         .add_timing(start, "Process inlined references");
     collector.find_possible_exports(checker);
     let start = checker.timings.add_timing(start, "Find possible exports");
-    for path in paths {
+    match &bin {
+        Bin::Object { obj, .. } => {
+            collector.process_files(paths, checker, new_ctx, &split_dwarf)?;
+            if !collector.outputs.bitcode_objects.is_empty() {
+                collector
+                    .outputs
+                    .base_problems
+                    .push(Problem::LtoBitcode(LtoBitcode {
+                        bin_path: link_info.output_file.clone(),
+                        profile: profile_name(&checker.args, &checker.config.raw.common).to_owned(),
+                        object_paths: std::mem::take(&mut collector.outputs.bitcode_objects),
+                    }));
+            }
+            let crates_without_objects =
+                crates_without_objects(paths, &collector.outputs.files_without_objects);
+            if !crates_without_objects.is_empty() {
+                collector
+                    .outputs
+                    .base_problems
+                    .push(Problem::MissingObjects(MissingObjects {
+                        bin_path: link_info.output_file.clone(),
+                        paths: crates_without_objects,
+                    }));
+            }
+            if bin_info.symbol_only {
+                collector
+                    .outputs
+                    .base_problems
+                    .push(Problem::MissingDebugInfo(MissingDebugInfo {
+                        bin_path: link_info.output_file.clone(),
+                        profile: profile_name(&checker.args, &checker.config.raw.common).to_owned(),
+                        stripped,
+                    }));
+            }
+            let start = checker.timings.add_timing(start, "Process object files");
+            if checker
+                .config
+                .permissions
+                .packages
+                .values()
+                .any(|pkg_config| pkg_config.disassemble)
+            {
+                collector.process_direct_branches(
+                    obj,
+                    bin.symbols_obj(),
+                    checker,
+                    &ctx,
+                    &split_dwarf,
+                )?;
+                checker.timings.add_timing(start, "Find direct branches");
+            }
+            if checker.config.raw.common.detect_raw_syscalls {
+                let start = checker.timings.now();
+                collector.process_raw_syscalls(
+                    obj,
+                    bin.symbols_obj(),
+                    checker,
+                    &ctx,
+                    &split_dwarf,
+                )?;
+                checker.timings.add_timing(start, "Find raw syscalls");
+            }
+            if checker.config.raw.common.detect_asm {
+                let start = checker.timings.now();
+                collector.process_inline_asm(bin.symbols_obj(), checker, &ctx, &split_dwarf)?;
+                checker.timings.add_timing(start, "Find inline assembly");
+            }
+        }
+        Bin::Wasm(module) => {
+            // All references are found from the linked module, so we don't need to look at the
+            // object files.
+            collector.process_wasm_references(module, checker, &ctx, &split_dwarf)?;
+            checker.timings.add_timing(start, "Process wasm references");
+        }
+    }
+    collector.reattribute_weak_overrides(checker);
+    // Without the object files, we don't know what's reachable, so we only filter when we've
+    // processed them. A symbol-only analysis doesn't record calls, so can't tell either.
+    if checker.config.raw.common.ignore_unreachable
+        && matches!(bin, Bin::Object { .. })
+        && !bin_info.symbol_only
+    {
+        let start = checker.timings.now();
+        collector.remove_unreachable_usages();
+        checker
+            .timings
+            .add_timing(start, "Remove unreachable usages");
+    }
+    if checker.config.raw.detects_ffi() {
+        collector.retain_foreign_usages(checker);
+    }
+    collector.emit_api_usages(checker.report_mode());
+    if checker.config.raw.common.single_async_runtime && collector.outputs.async_runtimes.len() > 1
+    {
         collector
-            .process_file(path, checker, &ctx)
-            .with_context(|| format!("Failed to process `{}`", path.display()))?;
+            .outputs
+            .base_problems
+            .push(Problem::MultipleAsyncRuntimes(MultipleAsyncRuntimes {
+                bin_path: link_info.output_file.clone(),
+                bindings: collector.outputs.async_runtimes.clone(),
+            }));
+    }
+    if checker.args.consumes_facts() {
+        let start = checker.timings.now();
+        collector.add_compilation_units(&ctx, &split_dwarf);
+        checker.timings.add_timing(start, "Find compilation units");
+    }
+    if !checker.args.plugin.is_empty() {
+        collector.outputs.symbols = collector.symbol_facts(checker)?;
+    }
+    if let Some(options) = checker.args.graph_options() {
+        let graph = collector.reference_graph(checker, &ctx)?;
+        collector.outputs.reference_graph = Some(options.filter(graph));
+    }
+    let start = checker.timings.now();
+    collector.find_call_paths(checker)?;
+    let start = checker.timings.add_timing(start, "Find call paths");
+    collector.find_inline_chains(&ctx, &split_dwarf)?;
+    checker.timings.add_timing(start, "Find inline chains");
+    if let (Some(b), Some(back_references)) = (backtracer, collector.back_references.take()) {
+        for (bin_location, target_address) in back_references {
+            b.add_reference(bin_location, target_address);
+        }
+    }
+    checker.timings.merge(&collector.timings);
+    Ok(collector.outputs)
+}
+
+/// The linked binary that we're scanning. We only ever have one of these at a time, so the size
+/// difference between the variants doesn't matter.
+#[allow(clippy::large_enum_variant)]
+enum Bin<'data> {
+    Object {
+        obj: object::File<'data>,
+        /// A separate debug file, if there is one.
+        debug_obj: Option<object::File<'data>>,
+    },
+    Wasm(WasmModule<'data>),
+}
+
+impl<'data> Bin<'data> {
+    fn parse(bin_file_bytes: &'data [u8], debug_file_bytes: Option<&'data [u8]>) -> Result<Self> {
+        if wasm::is_wasm(bin_file_bytes) {
+            return Ok(Bin::Wasm(WasmModule::parse(bin_file_bytes)?));
+        }
+        let obj = object::File::parse(bin_file_bytes)?;
+        let debug_obj = debug_file_bytes
+            .map(object::File::parse)
+            .transpose()
+            .context("Failed to parse separate debug file")?;
+        Ok(Bin::Object { obj, debug_obj })
+    }
+
+    fn load_section(&self, id: gimli::SectionId) -> Result<Cow<'data, [u8]>> {
+        match self {
+            Bin::Object { obj, debug_obj } => load_section(debug_obj.as_ref().unwrap_or(obj), id),
+            Bin::Wasm(module) => Ok(Cow::Borrowed(module.custom_section(id.name()))),
+        }
+    }
+
+    fn endian(&self) -> RunTimeEndian {
+        match self {
+            Bin::Object { obj, debug_obj } => endian(debug_obj.as_ref().unwrap_or(obj)),
+            Bin::Wasm(_) => RunTimeEndian::Little,
+        }
+    }
+
+    /// Returns the object containing the symbol table. A separate debug file, if there is one,
+    /// generally also holds the symbol table, since that's usually stripped from the binary along
+    /// with the debug info. Panics if we're not an object.
+    fn symbols_obj(&self) -> &object::File<'data> {
+        let Bin::Object { obj, debug_obj } = self else {
+            panic!("symbols_obj called on non-object");
+        };
+        match debug_obj {
+            Some(debug_obj) if obj.symbols().next().is_none() => debug_obj,
+            _ => obj,
+        }
+    }
+}
+
+impl ScanOutputs {
+    pub(crate) fn api_usages(&self) -> impl Iterator<Item = &ApiUsages> {
+        self.api_usages.values()
+    }
+
+    /// Returns, for each kind of panic path, the packages that reference it.
+    pub(crate) fn panic_paths(&self) -> BTreeMap<PanicPath, BTreeSet<&PackageId>> {
+        let mut paths: BTreeMap<PanicPath, BTreeSet<&PackageId>> = BTreeMap::new();
+        for (pkg_id, api_name) in self.panic_usages.keys() {
+            if let Some(kind) = PanicPath::from_api_name(api_name) {
+                paths.entry(kind).or_default().insert(pkg_id);
+            }
+        }
+        paths
+    }
+
+    pub(crate) fn problems(&self, checker: &mut Checker) -> Result<ProblemList> {
+        let mut problems: ProblemList = self.base_problems.clone();
+        for api_usages in self.api_usages.values() {
+            checker.api_used(api_usages, &mut problems)?;
+        }
+        for usages in self.panic_usages.values() {
+            if PanicPath::from_api_name(&usages.api_name).is_some_and(PanicPath::is_panic)
+                && checker.is_no_panic(&usages.pkg_id, usages.scope)
+            {
+                problems.push(Problem::DisallowedPanic(usages.clone()));
+            }
+        }
+        for usages in self.alloc_usages.values() {
+            if checker.is_no_alloc(&usages.pkg_id, usages.scope) {
+                problems.push(Problem::DisallowedAlloc(usages.clone()));
+            }
+        }
+        for usages in self.denied_usages.values() {
+            if let Some(path) = crate::deny_list::denied_path(&usages.api_name)
+                && !checker.is_denied_symbol_allowed(&usages.pkg_id, usages.scope, path)
+            {
+                problems.push(Problem::DeniedSymbol(usages.clone()));
+            }
+        }
+        checker.possible_exported_api_problems(&self.possible_exported_apis, &mut problems);
+
+        Ok(problems)
+    }
+}
+
+impl<'input, 'bin> ApiUsageCollector<'input, 'bin> {
+    fn new(
+        bin: &'bin BinInfo<'input>,
+        scan_cache: Option<&'bin ScanCache>,
+        backtraces: bool,
+        graph: bool,
+        debug_enabled: bool,
+        timings: TimingCollector,
+    ) -> Self {
+        Self {
+            outputs: Default::default(),
+            back_references: backtraces.then(Vec::new),
+            calls: FxHashSet::default(),
+            graph_references: graph.then(Vec::new),
+            roots: FxHashSet::default(),
+            pkg_ids_by_address: FxHashMap::default(),
+            weak_overrides: FxHashMap::default(),
+            native_pkg_id: None,
+            native_definitions: FxHashSet::default(),
+            bin,
+            scan_cache,
+            debug_enabled,
+            max_buffered_object_size: MAX_BUFFERED_OBJECT_SIZE,
+            new_api_usages: FxHashMap::default(),
+            groups: FxHashSet::default(),
+            group_api_usages: FxHashMap::default(),
+            objects_found: 0,
+            timings,
+        }
+    }
+
+    /// Processes the object files at `paths`, splitting them between up to as many threads as we
+    /// have CPUs, depending on how many job slots are free. Each thread needs its own addr2line context, since they're not thread-safe, so we're
+    /// given a function that creates one. Results are merged in the order of `paths`, so that our
+    /// output doesn't depend on how the work was split up.
+    fn process_files(
+        &mut self,
+        paths: &[PathBuf],
+        checker: &Checker,
+        new_ctx: impl Fn() -> Result<addr2line::Context<EndianSlice<'input, RunTimeEndian>>> + Sync,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        // We have the job slot of the rustc that invoked the linker, so only need more for the other
+        // threads. We only take those that are free, rather than waiting. See `resources.rs`.
+        let wanted_threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+        let extra_jobs = checker
+            .resources
+            .take_free_jobs(wanted_threads.saturating_sub(1));
+        let num_threads = 1 + extra_jobs.count();
+        let next_index = AtomicUsize::new(0);
+        let bin = self.bin;
+        let scan_cache = self.scan_cache;
+        let backtraces = self.back_references.is_some();
+        let graph = self.graph_references.is_some();
+        let debug_enabled = self.debug_enabled;
+        let timings = self.timings.child();
+        let worker = || -> Result<Vec<(usize, ApiUsageCollector<'input, 'bin>)>> {
+            let ctx = new_ctx()?;
+            let mut processed = Vec::new();
+            loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    return Ok(processed);
+                };
+                let mut collector = ApiUsageCollector::new(
+                    bin,
+                    scan_cache,
+                    backtraces,
+                    graph,
+                    debug_enabled,
+                    timings.child(),
+                );
+                if let Err(error) = collector.process_file(path, checker, &ctx, split_dwarf) {
+                    // Stop the other threads from picking up more work.
+                    next_index.store(paths.len(), Ordering::Relaxed);
+                    return Err(ScanError::CorruptObject {
+                        path: path.clone(),
+                        source: error,
+                    }
+                    .into());
+                }
+                processed.push((index, collector));
+            }
+        };
+        let mut processed = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_threads).map(|_| scope.spawn(&worker)).collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect::<Result<Vec<_>>>()
+        })?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        processed.sort_by_key(|(index, _)| *index);
+        for (_, collector) in processed {
+            self.merge(collector);
+        }
+        Ok(())
+    }
+
+    /// Merges the results from `other`, which processed some object files, into ours. We must be
+    /// given the results for object files in link order, since for COMDAT groups that we've already
+    /// seen, we discard what `other` found.
+    fn merge(&mut self, mut other: ApiUsageCollector) {
+        for (key, usages) in other.new_api_usages {
+            self.new_api_usages.entry(key).or_default().extend(usages);
+        }
+        for group in other.groups {
+            let group_usages = other.group_api_usages.remove(&group);
+            if self.groups.insert(group)
+                && let Some(group_usages) = group_usages
+            {
+                for (key, usages) in group_usages {
+                    self.new_api_usages.entry(key).or_default().extend(usages);
+                }
+            }
+        }
+        self.outputs
+            .bitcode_objects
+            .extend(other.outputs.bitcode_objects);
+        self.outputs
+            .files_without_objects
+            .extend(other.outputs.files_without_objects);
+        if let (Some(ours), Some(theirs)) = (self.back_references.as_mut(), other.back_references) {
+            ours.extend(theirs);
+        }
+        if let (Some(ours), Some(theirs)) = (self.graph_references.as_mut(), other.graph_references)
+        {
+            ours.extend(theirs);
+        }
+        self.calls.extend(other.calls);
+        self.roots.extend(other.roots);
+        self.weak_overrides.extend(other.weak_overrides);
+        self.native_definitions.extend(other.native_definitions);
+        self.timings.merge(&other.timings);
+    }
+
+    /// Processes the object file or archive at `filename`. Archive members are parsed in place via
+    /// a cache of the parts of the archive that have been read, rather than being read into memory
+    /// first, since rlibs with debug info can be hundreds of megabytes. We don't memory-map files,
+    /// since that'd require unsafe code and mapped files that get truncated while we're reading
+    /// them, e.g. by a concurrent build, cause a crash rather than an error. All reads go via the
+    /// checker's VFS.
+    fn process_file(
+        &mut self,
+        filename: &Path,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let objects_found = self.objects_found;
+        self.process_file_objects(filename, checker, ctx, split_dwarf)?;
+        if self.objects_found == objects_found {
+            debug!("Found no object files in `{}`", filename.display());
+            self.outputs.files_without_objects.push(filename.to_owned());
+        }
+        Ok(())
+    }
+
+    fn process_file_objects(
+        &mut self,
+        filename: &Path,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let vfs = checker.vfs.as_ref();
+        match Filetype::from_filename(filename) {
+            Filetype::Archive if thin_archive::is_thin_archive(vfs, filename)? => {
+                let archive_bytes = vfs
+                    .read(filename)
+                    .with_context(|| format!("Failed to read `{}`", filename.display()))?;
+                for member in thin_archive::members(filename, &archive_bytes)? {
+                    let object_file_path = ObjectFilePath::in_thin_archive(filename, &member.name);
+                    let mut cache = ReadCache::new(vfs.open(&member.path).with_context(|| {
+                        format!(
+                            "Failed to read `{}`, referenced by thin archive `{}`",
+                            member.path.display(),
+                            filename.display()
+                        )
+                    })?);
+                    self.process_whole_file(
+                        &object_file_path,
+                        &mut cache,
+                        checker,
+                        ctx,
+                        split_dwarf,
+                    )
+                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+                }
+            }
+            Filetype::Archive => {
+                let mut cache = ReadCache::new(
+                    vfs.open(filename)
+                        .with_context(|| format!("Failed to open `{}`", filename.display()))?,
+                );
+                // Some tools write archives without members as empty files.
+                if (&cache).len() == Ok(0) {
+                    return Ok(());
+                }
+                // Collect the members up front, since parsing them needs exclusive access to the
+                // cache, so that we can discard what we read from each member once we're done.
+                let members = ArchiveFile::parse(&cache)
+                    .map_err(|error| {
+                        anyhow!("Failed to parse archive `{}`: {error}", filename.display())
+                    })?
+                    .members()
+                    .filter_map(|member| {
+                        let member = member.ok()?;
+                        Some((
+                            ObjectFilePath::in_archive(filename, member.name()),
+                            member.file_range(),
+                        ))
+                    })
+                    .collect::<Vec<_>>();
+                for (object_file_path, (offset, size)) in members {
+                    self.process_object_file_range(
+                        &object_file_path,
+                        &mut cache,
+                        offset,
+                        size,
+                        checker,
+                        ctx,
+                        split_dwarf,
+                    )
+                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+                }
+            }
+            Filetype::Other => {
+                let mut cache = ReadCache::new(
+                    vfs.open(filename)
+                        .with_context(|| format!("Failed to read `{}`", filename.display()))?,
+                );
+                let object_file_path = ObjectFilePath::non_archive(filename);
+                self.process_whole_file(&object_file_path, &mut cache, checker, ctx, split_dwarf)
+                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn process_whole_file<R: Read + Seek>(
+        &mut self,
+        filename: &ObjectFilePath,
+        cache: &mut ReadCache<R>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let size = (&*cache)
+            .len()
+            .map_err(|()| anyhow!("Failed to get the size of {filename}"))?;
+        self.process_object_file_range(filename, cache, 0, size, checker, ctx, split_dwarf)
+    }
+
+    /// Processes the object file occupying `size` bytes at `offset` in `cache`. Objects up to
+    /// `max_buffered_object_size` are read all at once, since that's faster than reading each part
+    /// as it's needed. Larger objects are parsed in place, so that we only read the parts that we
+    /// need, e.g. not the debug info. Either way, what was read is discarded afterwards.
+    #[allow(clippy::too_many_arguments)]
+    fn process_object_file_range<R: Read + Seek>(
+        &mut self,
+        filename: &ObjectFilePath,
+        cache: &mut ReadCache<R>,
+        offset: u64,
+        size: u64,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let read_error = || anyhow!("Failed to read {filename}");
+        let result = if size <= self.max_buffered_object_size {
+            let bytes = (&*cache)
+                .read_bytes_at(offset, size)
+                .map_err(|()| read_error())?;
+            self.process_object_file(
+                filename,
+                bytes,
+                sha256_hex(bytes),
+                checker,
+                ctx,
+                split_dwarf,
+            )
+        } else {
+            let mut hasher = Sha256::new();
+            let mut chunk_offset = 0;
+            while chunk_offset < size {
+                let chunk_size = (size - chunk_offset).min(HASH_CHUNK_SIZE);
+                hasher.update(
+                    (&*cache)
+                        .read_bytes_at(offset + chunk_offset, chunk_size)
+                        .map_err(|()| read_error())?,
+                );
+                cache.clear();
+                chunk_offset += chunk_size;
+            }
+            self.process_object_file(
+                filename,
+                cache.range(offset, size),
+                format!("{:x}", hasher.finalize()),
+                checker,
+                ctx,
+                split_dwarf,
+            )
+        };
+        cache.clear();
+        result
+    }
+
+    /// Processes an unlinked object file - as opposed to an executable or a shared object, which
+    /// has been linked.
+    fn process_object_file<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        data: R,
+        object_hash: String,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        debug!("Processing object file {filename}");
+
+        if is_llvm_bitcode(data.read_bytes_at(0, 4).unwrap_or_default()) {
+            debug!("Skipping {filename}, since it contains LLVM bitcode");
+            self.objects_found += 1;
+            self.outputs.bitcode_objects.push(filename.clone());
+            return Ok(());
+        }
+        if !is_object_file(data) {
+            // With pipelined builds, cargo can give us `.rmeta` files, which contain only metadata.
+            debug!("Skipping {filename}, since it isn't an object file");
+            return Ok(());
+        }
+        self.objects_found += 1;
+        let obj = object::File::parse(data).context("Failed to parse object file")?;
+        if checker.config.raw.common.weak_overrides == WeakOverrideAttribution::Overridden {
+            // This doesn't depend on what we find in the object, so is done even when the rest
+            // comes from the cache.
+            self.record_weak_overrides(filename, &obj, checker);
+        }
+        self.native_pkg_id = native_object_pkg_id(
+            filename,
+            obj.symbols()
+                .filter(|symbol| symbol.is_definition())
+                .map(|symbol| Symbol::borrowed(symbol.name_bytes().unwrap_or_default())),
+            checker,
+        )
+        .cloned();
+        if self.native_pkg_id.is_some() && checker.config.raw.detects_ffi() {
+            self.native_definitions.extend(
+                obj.symbols()
+                    .filter(|symbol| symbol.is_definition() && symbol.is_global())
+                    .filter_map(|symbol| {
+                        Some(Symbol::borrowed(symbol.name_bytes().ok()?).to_heap())
+                    }),
+            );
+        }
+        // Native objects aren't cached, since the package that we attribute them to depends on
+        // where they are, not just on their contents.
+        let Some(scan_cache) = self.scan_cache.filter(|_| self.native_pkg_id.is_none()) else {
+            return self.process_object(filename, &obj, checker, ctx, split_dwarf);
+        };
+        let start = self.timings.now();
+        let key = scan_cache.key(&object_hash, &obj, self.bin);
+        let used_cached = match scan_cache.load(&key) {
+            Some(cached) => self.use_cached(cached, &obj, checker)?,
+            None => false,
+        };
+        self.timings.add_timing(start, "Check scan cache");
+        if used_cached {
+            return Ok(());
+        }
+        // If an earlier object in this file had one of our COMDAT groups, then we skip that group's
+        // sections, so what we find won't be complete enough to store.
+        let complete = ObjectIndex::new(&obj)
+            .groups()
+            .all(|group| !self.groups.contains(group));
+        // Collect what we find in this object separately, so that we can store it in the cache.
+        let api_usages = std::mem::take(&mut self.new_api_usages);
+        let group_api_usages = std::mem::take(&mut self.group_api_usages);
+        let back_references = self.back_references.as_mut().map(std::mem::take);
+        let calls = std::mem::take(&mut self.calls);
+        let roots = std::mem::take(&mut self.roots);
+        self.process_object(filename, &obj, checker, ctx, split_dwarf)?;
+        let object_api_usages = std::mem::replace(&mut self.new_api_usages, api_usages);
+        let object_group_usages = std::mem::replace(&mut self.group_api_usages, group_api_usages);
+        let object_references =
+            std::mem::replace(&mut self.back_references, back_references).unwrap_or_default();
+        let object_calls = std::mem::replace(&mut self.calls, calls);
+        let object_roots = std::mem::replace(&mut self.roots, roots);
+        let start = self.timings.now();
+        if complete
+            && let Some(entry) = CachedObject::from_results(
+                &obj,
+                self.bin,
+                &object_api_usages,
+                &object_group_usages,
+                &object_references,
+                &object_calls,
+                &object_roots,
+            )
+            && let Err(error) = scan_cache.store(&key, &entry)
+        {
+            // The cache is just an optimisation, so failing to write to it isn't fatal.
+            info!("Failed to store scan results for {filename}: {error:#}");
+        }
+        self.timings.add_timing(start, "Store scan cache entry");
+        for (key, usages) in object_api_usages {
+            self.new_api_usages.entry(key).or_default().extend(usages);
+        }
+        for (group, usages) in object_group_usages {
+            let group_usages = self.group_api_usages.entry(group).or_default();
+            for (key, usages) in usages {
+                group_usages.entry(key).or_default().extend(usages);
+            }
+        }
+        if let Some(back_references) = self.back_references.as_mut() {
+            back_references.extend(object_references);
+        }
+        self.calls.extend(object_calls);
+        self.roots.extend(object_roots);
+        Ok(())
+    }
+
+    /// Adds API usages and references from a cache entry for `obj`. Returns false, having added
+    /// nothing, if the entry turns out not to be applicable. As when we process the object, usages
+    /// from COMDAT groups that an earlier object in the file had are discarded, while those from
+    /// other groups are kept separate until `merge`. References, calls and roots are by symbol, so
+    /// are the same whichever copy of a group the linker kept.
+    fn use_cached<'data, R: ReadRef<'data>>(
+        &mut self,
+        cached: CachedObject,
+        obj: &object::File<'data, R>,
+        checker: &Checker,
+    ) -> Result<bool> {
+        let to_single_usage = |cached_usage: CachedUsage| -> Option<SingleApiUsage> {
+            let bin_location = cached_usage.location.to_bin_location(self.bin)?;
+            // Packages can be moved around without changing the objects that use them, so check
+            // that we'd still attribute the usage to the same package.
+            if !checker
+                .opt_pkg_ids_from_source_path(cached_usage.source_location.filename())
+                .is_some_and(|pkg_ids| pkg_ids.contains(&cached_usage.pkg_id))
+            {
+                return None;
+            }
+            let scope = checker.permission_scope(&cached_usage.pkg_id, &self.bin.crate_sel);
+            Some(SingleApiUsage {
+                pkg_id: cached_usage.pkg_id,
+                scope,
+                api: cached_usage.api,
+                usage: ApiUsage {
+                    bin_location,
+                    bin_path: self.bin.filename.clone(),
+                    permission_scope: scope,
+                    source_location: cached_usage.source_location,
+                    outer_location: cached_usage.outer_location,
+                    from: cached_usage.from,
+                    to: cached_usage.to,
+                    to_name: cached_usage.to_name,
+                    to_source: cached_usage.to_source,
+                    kind: cached_usage.kind,
+                    origin: cached_usage.origin,
+                    debug_data: None,
+                    callers: Vec::new(),
+                    inline_chain: Vec::new(),
+                },
+            })
+        };
+        let Some(usages) = cached
+            .usages
+            .into_iter()
+            .map(to_single_usage)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(false);
+        };
+        let mut group_usages = Vec::with_capacity(cached.group_usages.len());
+        for group in cached.group_usages {
+            let Some(usages) = group
+                .usages
+                .into_iter()
+                .map(to_single_usage)
+                .collect::<Option<Vec<_>>>()
+            else {
+                return Ok(false);
+            };
+            group_usages.push((group.group, usages));
+        }
+        let mut references = Vec::with_capacity(cached.references.len());
+        for reference in cached.references {
+            let (Some(bin_location), Some(target_address)) = (
+                reference.location.to_bin_location(self.bin),
+                self.bin.symbol_addresses.get(&reference.target),
+            ) else {
+                return Ok(false);
+            };
+            references.push((bin_location, *target_address));
+        }
+        let mut calls = Vec::with_capacity(cached.calls.len());
+        for call in cached.calls {
+            let (Some(from), Some(to)) = (
+                self.bin.symbol_addresses.get(&call.from),
+                self.bin.symbol_addresses.get(&call.to),
+            ) else {
+                return Ok(false);
+            };
+            calls.push((*from, *to));
+        }
+        let mut roots = Vec::with_capacity(cached.roots.len());
+        for root in cached.roots {
+            let Some(address) = self.bin.symbol_addresses.get(&root) else {
+                return Ok(false);
+            };
+            roots.push(*address);
+        }
+        for usage in usages {
+            self.new_api_usages
+                .entry(usage.group_key())
+                .or_default()
+                .push(usage);
+        }
+        let discarded_groups = self.add_groups(&ObjectIndex::new(obj));
+        for (group, usages) in group_usages {
+            if discarded_groups.contains(&group) {
+                continue;
+            }
+            let kept = self.group_api_usages.entry(group).or_default();
+            for usage in usages {
+                kept.entry(usage.group_key()).or_default().push(usage);
+            }
+        }
+        if let Some(back_references) = self.back_references.as_mut() {
+            back_references.extend(references);
+        }
+        self.calls.extend(calls);
+        self.roots.extend(roots);
+        Ok(true)
+    }
+
+    /// Finds the references from the sections of `obj` and the API usages that they result in.
+    fn process_object<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        obj: &object::File<'data, R>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        if self.bin.symbol_only {
+            return self.process_object_symbols(filename, obj, checker);
+        }
+        let object_index = ObjectIndex::new(obj);
+        let ignore_unreachable = checker.config.raw.common.ignore_unreachable;
+        let section_symbol_addresses = if ignore_unreachable {
+            self.section_symbol_addresses(obj)
+        } else {
+            FxHashMap::default()
+        };
+        let discarded_groups = self.add_groups(&object_index);
+        for section in obj.sections() {
+            let group = object_index.group(&section);
+            if group.is_some_and(|group| discarded_groups.contains(group)) {
+                debug!(
+                    "Skipping section `{}` since the linker keeps its group from an earlier object",
+                    section.name().unwrap_or("")
+                );
+                continue;
+            }
+            self.process_section_in_group(group, |collector| {
+                collector.process_section(
+                    filename,
+                    obj,
+                    &object_index,
+                    &section,
+                    &section_symbol_addresses,
+                    checker,
+                    ctx,
+                    split_dwarf,
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Records the COMDAT groups in `object_index`, returning those that we'd already seen in an
+    /// earlier object. The linker discards those copies.
+    fn add_groups<'data, R: ReadRef<'data>>(
+        &mut self,
+        object_index: &ObjectIndex<'_, 'data, R>,
+    ) -> FxHashSet<Symbol<'static>> {
+        let mut discarded = FxHashSet::default();
+        let in_object: FxHashSet<&Symbol> = object_index.groups().collect();
+        for group in in_object {
+            let group = group.to_heap();
+            if !self.groups.insert(group.clone()) {
+                discarded.insert(group);
+            }
+        }
+        discarded
+    }
+
+    /// Runs `process`, which processes a section that's in `group`, if any. API usages from a
+    /// section in a group are kept separate until `merge`.
+    fn process_section_in_group(
+        &mut self,
+        group: Option<&Symbol>,
+        process: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        let Some(group) = group else {
+            return process(self);
+        };
+        let api_usages = std::mem::take(&mut self.new_api_usages);
+        let result = process(self);
+        let section_usages = std::mem::replace(&mut self.new_api_usages, api_usages);
+        let group_usages = self.group_api_usages.entry(group.to_heap()).or_default();
+        for (key, usages) in section_usages {
+            group_usages.entry(key).or_default().extend(usages);
+        }
+        result
+    }
+
+    /// Processes the relocations in `section`, attributing each to the symbol that contains it.
+    #[allow(clippy::too_many_arguments)]
+    fn process_section<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        obj: &object::File<'data, R>,
+        object_index: &ObjectIndex<'_, 'data, R>,
+        section: &object::Section<'data, '_, R>,
+        section_symbol_addresses: &FxHashMap<SectionIndex, Vec<u64>>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let ignore_unreachable = checker.config.raw.common.ignore_unreachable;
+        let section_name = section.name().unwrap_or("");
+        if checker.config.raw.common.detect_before_main && is_init_section(section_name) {
+            self.process_init_section(object_index, section, checker, ctx, split_dwarf)?;
+        }
+        let Some(first_sym_info) = object_index.first_symbol(section) else {
+            debug!("Skipping section `{section_name}` due to lack of debug info");
+            if ignore_unreachable && is_root_section(section_name) {
+                self.add_roots_from_relocations(
+                    object_index,
+                    section.relocations().map(|(_, rel)| rel),
+                )?;
+            }
+            return Ok(());
+        };
+        let section_symbols = object_index.section_symbols(section);
+        if section_symbols.is_empty() {
+            let relocations: Vec<_> = section.relocations().collect();
+            self.process_symbol_relocations(
+                filename,
+                obj,
+                object_index,
+                section,
+                first_sym_info,
+                &relocations,
+                section_symbol_addresses
+                    .get(&section.index())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+                checker,
+                ctx,
+                split_dwarf,
+            )?;
+            return Ok(());
+        }
+        let mut relocations_by_symbol: BTreeMap<usize, Vec<(u64, object::Relocation)>> =
+            BTreeMap::new();
+        for (offset, rel) in section.relocations() {
+            relocations_by_symbol
+                .entry(enclosing_symbol_index(
+                    section_symbols,
+                    offset,
+                    &self.bin.symbol_addresses,
+                ))
+                .or_default()
+                .push((offset, rel));
+        }
+        for (index, relocations) in relocations_by_symbol {
+            self.process_symbol_relocations(
+                filename,
+                obj,
+                object_index,
+                section,
+                &section_symbols[index],
+                &relocations,
+                &[],
+                checker,
+                ctx,
+                split_dwarf,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Finds API usages in `obj` going only by symbol names, for binaries that lack the debug info
+    /// or the symbol table that `process_object` relies on. References are attributed to the
+    /// symbol containing them and from there to the package that the symbol's name starts with.
+    /// Without debug info, we don't know source locations, so usages are reported as being in the
+    /// object file.
+    fn process_object_symbols<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        obj: &object::File<'data, R>,
+        checker: &Checker,
+    ) -> Result<()> {
+        let object_index = ObjectIndex::new(obj);
+        let location = SourceLocation::new(Path::new(&filename.to_string()), 0, None);
+        // A stripped binary has no symbols, so we can't tell what the linker discarded. Instead, we
+        // treat everything defined in the object as being in the binary.
+        let object_symbols: FxHashMap<Symbol, u64>;
+        let bin_symbols = if self.bin.symbol_addresses.is_empty() {
+            object_symbols = obj
+                .symbols()
+                .filter(|symbol| symbol.is_definition())
+                .filter_map(|symbol| symbol.name_bytes().ok())
+                .filter(|name| !name.is_empty())
+                .map(|name| (Symbol::borrowed(name), 0))
+                .collect();
+            &object_symbols
+        } else {
+            &self.bin.symbol_addresses
+        };
+        let discarded_groups = self.add_groups(&object_index);
+        for section in obj.sections() {
+            let Some(first_sym_info) = object_index.first_symbol(&section) else {
+                continue;
+            };
+            let group = object_index.group(&section);
+            if group.is_some_and(|group| discarded_groups.contains(group)) {
+                continue;
+            }
+            let section_name = section.name().unwrap_or("");
+            let section_symbols = object_index.section_symbols(&section);
+            let from_executable = is_executable_section(&section);
+            self.process_section_in_group(group, |collector| {
+                for (offset, rel) in section.relocations() {
+                    let sym_info = if section_symbols.is_empty() {
+                        first_sym_info
+                    } else {
+                        &section_symbols
+                            [enclosing_symbol_index(section_symbols, offset, bin_symbols)]
+                    };
+                    let Some(&symbol_start) = bin_symbols.get(&sym_info.symbol) else {
+                        continue;
+                    };
+                    let mut target_symbols = Vec::new();
+                    object_index.add_target_symbols(
+                        &rel,
+                        &mut target_symbols,
+                        &mut FxHashSet::default(),
+                        bin_symbols,
+                    )?;
+                    let from = Node {
+                        names: collector.bin.get_symbol_and_name(&sym_info.symbol),
+                        location_fetcher: LocationFetcher::AlreadyResolved(&location),
+                    };
+                    let bin_location = BinLocation {
+                        address: symbol_start + offset.saturating_sub(sym_info.offset),
+                        symbol_start,
+                    };
+                    let kind = ReferenceKind::classify(
+                        is_branch(obj.architecture(), &rel),
+                        from_executable,
+                        false,
+                        false,
+                    );
+                    for target_symbol in target_symbols {
+                        let target = collector.bin.get_symbol_and_name(&target_symbol);
+                        collector.process_reference(
+                            bin_location,
+                            None,
+                            &from,
+                            &target,
+                            kind,
+                            Some(RelocationSite {
+                                kind: rel.kind(),
+                                object_file: filename,
+                                section: section_name,
+                                offset,
+                            }),
+                            checker,
+                            None,
+                        )?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Processes `relocations`, which are from within `sym_info` in `section`.
+    /// `other_section_symbols` are the addresses in the binary of symbols in the section whose
+    /// references are also attributed to `sym_info`.
+    #[allow(clippy::too_many_arguments)]
+    fn process_symbol_relocations<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        obj: &object::File<'data, R>,
+        object_index: &ObjectIndex<'_, 'data, R>,
+        section: &object::Section<'data, '_, R>,
+        sym_info: &SymbolInfo,
+        relocations: &[(u64, object::Relocation)],
+        other_section_symbols: &[u64],
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let section_name = section.name().unwrap_or("");
+        let ignore_unreachable = checker.config.raw.common.ignore_unreachable;
+        let Some(symbol_address_in_bin) = self.bin.symbol_addresses.get(&sym_info.symbol).cloned()
+        else {
+            debug!(
+                "Skipping section `{}` because symbol `{}` doesn't appear in exe/so",
+                section_name, sym_info.symbol
+            );
+            return Ok(());
+        };
+        if sym_info.binding == SymbolBinding::Weak
+            && !self.bin.weak_symbols.contains(&sym_info.symbol)
+        {
+            // The symbol's address in the binary is that of the overriding definition, so if we
+            // processed this section, we'd attribute what the weak definition references to
+            // the overriding code. The weak definition isn't used, so we skip it.
+            debug!(
+                "Skipping section `{}` because weak symbol `{}` was overridden",
+                section_name, sym_info.symbol
+            );
+            return Ok(());
+        }
+        let fallback_source_location = match self.bin.symbol_debug_info.get(&sym_info.symbol) {
+            Some(debug_info) => debug_info.source_location(),
+            // Native code that a build script built often lacks debug info, but we still know
+            // which package it came from.
+            None if self.native_pkg_id.is_some() => {
+                SourceLocation::new(Path::new(&filename.to_string()), 0, None)
+            }
+            None => {
+                // We don't know what this code does, so anything that it references might be
+                // reachable.
+                if ignore_unreachable {
+                    self.add_roots_from_relocations(
+                        object_index,
+                        relocations.iter().map(|(_, rel)| rel),
+                    )?;
+                }
+                return Ok(());
+            }
+        };
+        if ignore_unreachable && is_root_section(section_name) {
+            self.roots.insert(symbol_address_in_bin);
+        }
+        let mut frames = FrameCache::new(ctx, split_dwarf);
+        // References from the section are attributed to `sym_info`, so if any other symbol in
+        // the section is reachable, then so are the section's references. This isn't needed when
+        // references are attributed to the symbols that contain them.
+        for address in other_section_symbols {
+            if *address != symbol_address_in_bin {
+                self.calls.insert((*address, symbol_address_in_bin));
+            }
+        }
+        let from_executable = is_executable_section(section);
+        let code = if from_executable {
+            section.data().unwrap_or_default()
+        } else {
+            &[]
+        };
+        let debug_data = self.debug_enabled.then(|| {
+            UsageDebugData::Relocation(RelocationDebugData {
+                bin_path: self.bin.filename.clone(),
+                object_file_path: filename.clone(),
+                section_name: section_name.to_owned(),
+            })
+        });
+
+        for &(offset, ref rel) in relocations {
+            let start = self.timings.now();
+            let mut target_symbols = Vec::new();
+            object_index.add_target_symbols(
+                rel,
+                &mut target_symbols,
+                &mut FxHashSet::default(),
+                &self.bin.symbol_addresses,
+            )?;
+
+            let bin_location = BinLocation {
+                address: symbol_address_in_bin + offset.saturating_sub(sym_info.offset),
+                symbol_start: symbol_address_in_bin,
+            };
+            let target_executable = target_symbols.iter().any(|symbol| {
+                self.bin
+                    .symbol_addresses
+                    .get(symbol)
+                    .is_some_and(|address| self.bin.is_executable_address(*address))
+            });
+            let branch = is_branch(obj.architecture(), rel)
+                || usize::try_from(offset).is_ok_and(|offset| {
+                    disassemble::is_branch_at(obj.architecture(), code, offset)
+                });
+            let kind = ReferenceKind::classify(
+                branch,
+                from_executable,
+                object_index.targets_vtable(rel, &self.bin.symbol_addresses)?,
+                target_executable,
+            );
+            self.timings.add_timing(start, "Find relocation targets");
+            self.process_references_from(
+                bin_location,
+                &sym_info.symbol,
+                &fallback_source_location,
+                target_symbols,
+                Some(RelocationSite {
+                    kind: rel.kind(),
+                    object_file: filename,
+                    section: section_name,
+                    offset,
+                }),
+                kind,
+                checker,
+                &mut frames,
+                debug_data.as_ref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Records the weak symbols defined by `obj` that were overridden in the binary, so that API
+    /// usages by the overriding definitions can be attributed to the package of `obj`.
+    fn record_weak_overrides<'data, R: ReadRef<'data>>(
+        &mut self,
+        filename: &ObjectFilePath,
+        obj: &object::File<'data, R>,
+        checker: &Checker,
+    ) {
+        let Some(pkg_id) = crate_name_for_object(filename)
+            .and_then(|crate_name| checker.crate_index.name_prefix_to_pkg_id().get(crate_name))
+        else {
+            return;
+        };
+        for obj_symbol in obj.symbols() {
+            if !obj_symbol.is_weak() || !obj_symbol.is_definition() {
+                continue;
+            }
+            let symbol = Symbol::borrowed(obj_symbol.name_bytes().unwrap_or_default());
+            if self.bin.weak_symbols.contains(&symbol) {
+                continue;
+            }
+            if let Some(address) = self.bin.symbol_addresses.get(&symbol) {
+                self.weak_overrides.insert(*address, pkg_id.clone());
+            }
+        }
+    }
+
+    /// Moves API usages from overriding definitions of weak symbols to the packages that provided
+    /// the weak definitions. See `record_weak_overrides`.
+    fn reattribute_weak_overrides(&mut self, checker: &Checker) {
+        if self.weak_overrides.is_empty() {
+            return;
+        }
+        let bin_sel = &self.bin.crate_sel;
+        for mut api_usage in std::mem::take(&mut self.new_api_usages)
+            .into_values()
+            .flatten()
+        {
+            if let Some(pkg_id) = self
+                .weak_overrides
+                .get(&api_usage.usage.bin_location.symbol_start)
+            {
+                api_usage.pkg_id = pkg_id.clone();
+                api_usage.scope = checker.permission_scope(pkg_id, bin_sel);
+                api_usage.usage.permission_scope = api_usage.scope;
+            }
+            self.new_api_usages
+                .entry(api_usage.group_key())
+                .or_default()
+                .push(api_usage);
+        }
+    }
+
+    /// Adds the targets of `relocations` as roots.
+    fn add_roots_from_relocations<'data, R: ReadRef<'data>>(
+        &mut self,
+        object_index: &ObjectIndex<'_, 'data, R>,
+        relocations: impl IntoIterator<Item = impl Borrow<object::Relocation>>,
+    ) -> Result<()> {
+        let mut target_symbols = Vec::new();
+        for rel in relocations {
+            object_index.add_target_symbols(
+                rel.borrow(),
+                &mut target_symbols,
+                &mut FxHashSet::default(),
+                &self.bin.symbol_addresses,
+            )?;
+        }
+        self.roots.extend(
+            target_symbols
+                .iter()
+                .filter_map(|symbol| self.bin.symbol_addresses.get(symbol)),
+        );
+        Ok(())
+    }
+
+    /// Returns the addresses in the binary of the symbols in each section of `obj`.
+    fn section_symbol_addresses<'data, R: ReadRef<'data>>(
+        &self,
+        obj: &object::File<'data, R>,
+    ) -> FxHashMap<SectionIndex, Vec<u64>> {
+        let mut addresses: FxHashMap<SectionIndex, Vec<u64>> = FxHashMap::default();
+        for symbol in obj.symbols() {
+            if let (Some(section_index), Ok(name)) = (symbol.section_index(), symbol.name_bytes())
+                && let Some(address) = self.bin.symbol_addresses.get(&Symbol::borrowed(name))
+            {
+                addresses.entry(section_index).or_default().push(*address);
+            }
+        }
+        addresses
+    }
+
+    /// Removes API usages from functions that aren't reachable from the binary's entry points or
+    /// from our other roots. See `is_root_section`.
+    fn remove_unreachable_usages(&mut self) {
+        let graph = CallGraph::new(self.calls.iter().copied());
+        let reachable =
+            graph.reachable_from(self.roots.iter().chain(&self.bin.entry_points).copied());
+        self.new_api_usages.retain(|_, usages| {
+            usages.retain(|u| reachable.contains(&u.usage.bin_location.symbol_start));
+            !usages.is_empty()
+        });
+    }
+
+    /// Processes references to `target_symbols` from `bin_location`, which is within
+    /// `outer_symbol`. Debug info is used to determine the function, possibly inlined, that the
+    /// references originated from. `relocation` is the relocation that produced the references, if
+    /// any.
+    #[allow(clippy::too_many_arguments)]
+    fn process_references_from(
+        &mut self,
+        bin_location: BinLocation,
+        outer_symbol: &Symbol,
+        fallback_source_location: &SourceLocation,
+        target_symbols: Vec<Symbol>,
+        relocation: Option<RelocationSite>,
+        kind: ReferenceKind,
+        checker: &Checker,
+        frames: &mut FrameCache<'_, 'input>,
+        debug_data: Option<&UsageDebugData>,
+    ) -> Result<()> {
+        let start = self.timings.now();
+        // Use debug info to determine the function that the reference originated from.
+        let frame = frames.innermost(bin_location.address)?;
+        let start = self.timings.add_timing(start, "Find reference frames");
+        let location_fetcher = LocationFetcher::FrameWithFallback {
+            frame_location: frame.location,
+            fallback: fallback_source_location,
+        };
+        let frame_symbol = frame.function.map(Symbol::borrowed);
+
+        let from_symbol = frame_symbol.as_ref().unwrap_or(outer_symbol);
+        let from = Node {
+            names: self.bin.get_symbol_and_name(from_symbol),
+            location_fetcher,
+        };
+        let mut non_inlined_from = None;
+        if frame_symbol.as_ref() != Some(outer_symbol) {
+            non_inlined_from = Some(Node {
+                names: self.bin.get_symbol_and_name(outer_symbol),
+                location_fetcher: LocationFetcher::AlreadyResolved(fallback_source_location),
+            });
+        }
+        let mut caller_pkg_ids = None;
+        for target_symbol in target_symbols {
+            if let Some(target_address) = self.bin.symbol_addresses.get(&target_symbol) {
+                let caller_pkg_ids = caller_pkg_ids.get_or_insert_with(|| {
+                    self.pkg_ids_at(bin_location.symbol_start, outer_symbol, checker)
+                        .to_vec()
+                });
+                if checker.config.raw.common.ignore_unreachable
+                    || self
+                        .pkg_ids_at(*target_address, &target_symbol, checker)
+                        .iter()
+                        .any(|pkg_id| caller_pkg_ids.contains(pkg_id))
+                {
+                    self.calls
+                        .insert((bin_location.symbol_start, *target_address));
+                }
+                if let Some(back_references) = self.back_references.as_mut() {
+                    back_references.push((bin_location, *target_address));
+                }
+                if let Some(graph_references) = self.graph_references.as_mut() {
+                    graph_references.push(GraphReference {
+                        location: bin_location,
+                        target_address: *target_address,
+                        relocation: relocation.map(|site| site.kind),
+                    });
+                }
+            }
+            let target = self.bin.get_symbol_and_name(&target_symbol);
+            self.process_reference(
+                bin_location,
+                non_inlined_from.as_ref(),
+                &from,
+                &target,
+                kind,
+                relocation,
+                checker,
+                debug_data,
+            )?;
+        }
+        self.timings.add_timing(start, "Process references");
+        Ok(())
+    }
+
+    /// Finds references that might not have relocations by looking for direct branches in the code
+    /// of functions from packages with `disassemble` set. See `disassemble.rs`.
+    fn process_direct_branches(
+        &mut self,
+        bin_obj: &object::File<'input>,
+        symbols_obj: &object::File<'input>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let architecture = bin_obj.architecture();
+        if !disassemble::is_supported(architecture) {
+            info!("Skipping disassembly, since {architecture:?} isn't supported");
+            return Ok(());
+        }
+        let mut symbols_by_address: FxHashMap<u64, Symbol<'input>> = FxHashMap::default();
+        for (symbol, address) in &self.bin.symbol_addresses {
+            symbols_by_address
+                .entry(*address)
+                .or_insert_with(|| symbol.clone());
+        }
+        let bin_sel = self.bin.crate_sel.clone();
+        for sym in symbols_obj.symbols() {
+            if sym.kind() != SymbolKind::Text || sym.size() == 0 {
+                continue;
+            }
+            let symbol = Symbol::borrowed(sym.name_bytes()?);
+            let Some(debug_info) = self.bin.symbol_debug_info.get(&symbol) else {
+                continue;
+            };
+            let fallback_source_location = debug_info.source_location();
+            let Some(pkg_ids) =
+                checker.opt_pkg_ids_from_source_path(fallback_source_location.filename())
+            else {
+                continue;
+            };
+            let enabled = pkg_ids.iter().any(|pkg_id| {
+                let perm_sel =
+                    PermSel::with_scope(pkg_id, checker.permission_scope(pkg_id, &bin_sel));
+                checker
+                    .config
+                    .permissions
+                    .get(&perm_sel)
+                    .is_some_and(|pkg_config| pkg_config.disassemble)
+            });
+            if !enabled {
+                continue;
+            }
+            let Some(code) = function_code(bin_obj, sym.address(), sym.size())? else {
+                continue;
+            };
+            let mut frames = FrameCache::new(ctx, split_dwarf);
+            for (address, target_address) in
+                disassemble::direct_branches(architecture, code, sym.address())
+            {
+                // We only accept branches to the start of a symbol other than the current one.
+                // Anything else is most likely a branch within the function, or on x86-64, not a
+                // branch at all.
+                if target_address == sym.address() {
+                    continue;
+                }
+                let Some(target) = symbols_by_address.get(&target_address) else {
+                    continue;
+                };
+                let bin_location = BinLocation {
+                    address,
+                    symbol_start: sym.address(),
+                };
+                self.process_references_from(
+                    bin_location,
+                    &symbol,
+                    &fallback_source_location,
+                    vec![target.clone()],
+                    None,
+                    ReferenceKind::Call,
+                    checker,
+                    &mut frames,
+                    None,
+                )?;
+            }
+        }
+        Ok(())
     }
-    collector.emit_shortest_api_usages();
-    checker.timings.add_timing(start, "Process object files");
-    Ok(collector.outputs)
-}
 
-impl ScanOutputs {
-    pub(crate) fn problems(&self, checker: &mut Checker) -> Result<ProblemList> {
-        let mut problems: ProblemList = self.base_problems.clone();
-        for api_usages in self.api_usages.values() {
-            checker.api_used(api_usages, &mut problems)?;
+    /// Reports system call instructions in the code of functions from packages other than `libc`
+    /// as usages of the `raw_syscall` API. Code from the standard library isn't attributed to any
+    /// package, so is skipped. Code that makes system calls itself can't be found from the symbols
+    /// that it references.
+    fn process_raw_syscalls(
+        &mut self,
+        bin_obj: &object::File<'input>,
+        symbols_obj: &object::File<'input>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let architecture = bin_obj.architecture();
+        if !disassemble::is_supported(architecture) {
+            info!("Skipping search for raw syscalls, since {architecture:?} isn't supported");
+            return Ok(());
         }
-        checker.possible_exported_api_problems(&self.possible_exported_apis, &mut problems);
-
-        Ok(problems)
+        for sym in symbols_obj.symbols() {
+            if sym.kind() != SymbolKind::Text || sym.size() == 0 {
+                continue;
+            }
+            let symbol = Symbol::borrowed(sym.name_bytes()?);
+            let Some(debug_info) = self.bin.symbol_debug_info.get(&symbol) else {
+                continue;
+            };
+            let fallback_source_location = debug_info.source_location();
+            let Some(pkg_ids) =
+                checker.opt_pkg_ids_from_source_path(fallback_source_location.filename())
+            else {
+                continue;
+            };
+            if pkg_ids
+                .iter()
+                .all(|pkg_id| pkg_id.pkg_name().as_ref() == "libc")
+            {
+                continue;
+            }
+            let Some(code) = function_code(bin_obj, sym.address(), sym.size())? else {
+                continue;
+            };
+            for address in disassemble::syscalls(architecture, code, sym.address()) {
+                let bin_location = BinLocation {
+                    address,
+                    symbol_start: sym.address(),
+                };
+                self.process_built_in_usage(
+                    RAW_SYSCALL_API,
+                    ReferenceKind::Syscall,
+                    bin_location,
+                    &symbol,
+                    &fallback_source_location,
+                    checker,
+                    ctx,
+                    split_dwarf,
+                )?;
+            }
+        }
+        Ok(())
     }
-}
 
-impl<'input> ApiUsageCollector<'input, '_> {
-    fn process_file(
+    /// Reports functions from packages other than `libc` that contain inline assembly, or that
+    /// come from assembly source files, as usages of the `asm` API. Inline assembly is found by
+    /// looking for instructions that the line table attributes to lines of an `asm!` invocation.
+    fn process_inline_asm(
         &mut self,
-        filename: &Path,
+        symbols_obj: &object::File<'input>,
         checker: &Checker,
-        ctx: &addr2line::Context<EndianSlice<'input, LittleEndian>>,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
     ) -> Result<()> {
-        let mut buffer = Vec::new();
-        match Filetype::from_filename(filename) {
-            Filetype::Archive => {
-                let mut archive = Archive::new(File::open(filename)?);
-                while let Some(entry_result) = archive.next_entry() {
-                    let Ok(mut entry) = entry_result else {
-                        continue;
-                    };
-                    buffer.clear();
-                    entry.read_to_end(&mut buffer)?;
-                    let object_file_path = ObjectFilePath::in_archive(filename, &entry)?;
-                    self.process_object_file_bytes(&object_file_path, &buffer, checker, ctx)
-                        .with_context(|| format!("Failed to process {object_file_path}"))?;
+        let mut asm_lines = inline_asm::AsmLines::default();
+        for sym in symbols_obj.symbols() {
+            if sym.kind() != SymbolKind::Text || sym.size() == 0 {
+                continue;
+            }
+            let symbol = Symbol::borrowed(sym.name_bytes()?);
+            let Some(debug_info) = self.bin.symbol_debug_info.get(&symbol) else {
+                continue;
+            };
+            let fallback_source_location = debug_info.source_location();
+            if checker
+                .opt_pkg_ids_from_source_path(fallback_source_location.filename())
+                .is_none()
+            {
+                continue;
+            }
+            let mut addresses = Vec::new();
+            if inline_asm::is_assembly_source(fallback_source_location.filename()) {
+                addresses.push(sym.address());
+            } else {
+                let mut seen_lines = FxHashSet::default();
+                for (address, _, location) in
+                    ctx.find_location_range(sym.address(), sym.address() + sym.size())?
+                {
+                    if let (Some(file), Some(line)) = (location.file, location.line)
+                        && asm_lines.contains(Path::new(file), line)
+                        && seen_lines.insert((file, line))
+                    {
+                        addresses.push(address);
+                    }
                 }
             }
-            Filetype::Other => {
-                let file_bytes = std::fs::read(filename)
-                    .with_context(|| format!("Failed to read `{}`", filename.display()))?;
-                let object_file_path = ObjectFilePath::non_archive(filename);
-                self.process_object_file_bytes(&object_file_path, &file_bytes, checker, ctx)
-                    .with_context(|| format!("Failed to process {object_file_path}"))?;
+            for address in addresses {
+                let bin_location = BinLocation {
+                    address,
+                    symbol_start: sym.address(),
+                };
+                self.process_built_in_usage(
+                    ASM_API,
+                    ReferenceKind::Asm,
+                    bin_location,
+                    &symbol,
+                    &fallback_source_location,
+                    checker,
+                    ctx,
+                    split_dwarf,
+                )?;
             }
         }
         Ok(())
     }
 
-    /// Processes an unlinked object file - as opposed to an executable or a shared object, which
-    /// has been linked.
-    fn process_object_file_bytes(
+    /// Records each function that `section` points to as a usage of the built-in `before_main` API.
+    /// See `is_init_section`.
+    fn process_init_section<'data, R: ReadRef<'data>>(
         &mut self,
-        filename: &ObjectFilePath,
-        file_bytes: &[u8],
+        object_index: &ObjectIndex<'_, 'data, R>,
+        section: &object::Section<'data, '_, R>,
         checker: &Checker,
-        ctx: &addr2line::Context<EndianSlice<'input, LittleEndian>>,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
     ) -> Result<()> {
-        debug!("Processing object file {filename}");
+        for (offset, rel) in section.relocations() {
+            let Some(symbol) = object_index.pointed_to_symbol(
+                section,
+                offset,
+                &rel,
+                &self.bin.symbol_addresses,
+            )?
+            else {
+                continue;
+            };
+            let (Some(&address), Some(debug_info)) = (
+                self.bin.symbol_addresses.get(&symbol),
+                self.bin.symbol_debug_info.get(&symbol),
+            ) else {
+                continue;
+            };
+            self.process_built_in_usage(
+                BEFORE_MAIN_API,
+                ReferenceKind::BeforeMain,
+                BinLocation {
+                    address,
+                    symbol_start: address,
+                },
+                &symbol,
+                &debug_info.source_location(),
+                checker,
+                ctx,
+                split_dwarf,
+            )?;
+        }
+        Ok(())
+    }
 
-        let obj = object::File::parse(file_bytes).context("Failed to parse object file")?;
-        let object_index = ObjectIndex::new(&obj);
-        for section in obj.sections() {
-            let section_name = section.name().unwrap_or("");
-            let Some(first_sym_info) = object_index.first_symbol(&section) else {
-                debug!("Skipping section `{section_name}` due to lack of debug info");
+    /// Records a usage of the built-in API `api_name` by the instruction at `bin_location`, which
+    /// is in the function `outer_symbol`. Unlike other APIs, these are found from the code itself
+    /// rather than from a referenced symbol.
+    #[allow(clippy::too_many_arguments)]
+    fn process_built_in_usage(
+        &mut self,
+        api_name: &str,
+        kind: ReferenceKind,
+        bin_location: BinLocation,
+        outer_symbol: &Symbol,
+        fallback_source_location: &SourceLocation,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let mut frames = split_dwarf.resolve(ctx.find_frames(bin_location.address))?;
+        let (frame_fn_name, frame_location) = frames
+            .next()?
+            .map(|frame| (frame.function, frame.location))
+            .unwrap_or((None, None));
+        let location = LocationFetcher::FrameWithFallback {
+            frame_location,
+            fallback: fallback_source_location,
+        }
+        .location()?;
+        let frame_symbol = frame_fn_name
+            .as_ref()
+            .map(|fn_name| Symbol::borrowed(&fn_name.name));
+        let from_symbol = frame_symbol.as_ref().unwrap_or(outer_symbol);
+        let from = self.bin.get_symbol_and_name(from_symbol);
+        let outer_location =
+            (from_symbol != outer_symbol).then(|| fallback_source_location.clone());
+        // The instruction may have been inlined from another package, so we go by the location of
+        // the instruction itself.
+        let Some(pkg_ids) = checker.opt_pkg_ids(&self.bin.attribution_query(
+            location.filename(),
+            &from,
+            Some(outer_symbol),
+        )) else {
+            return Ok(());
+        };
+        let target = Symbol::borrowed(api_name.as_bytes()).to_heap();
+        for pkg_id in pkg_ids.iter() {
+            if pkg_id.pkg_name().as_ref() == "libc" {
                 continue;
+            }
+            let scope = checker.permission_scope(pkg_id, &self.bin.crate_sel);
+            let api_usage = SingleApiUsage {
+                pkg_id: pkg_id.clone(),
+                scope,
+                api: ApiName::new(api_name),
+                usage: ApiUsage {
+                    bin_location,
+                    bin_path: self.bin.filename.clone(),
+                    permission_scope: scope,
+                    source_location: location.clone(),
+                    outer_location: outer_location.clone(),
+                    from: from.symbol_or_debug_name()?,
+                    to: SymbolOrDebugName::Symbol(target.clone()),
+                    to_name: crate::names::split_simple(api_name),
+                    to_source: NameSource::Symbol(target.clone()),
+                    kind,
+                    origin: UsageOrigin::new(None, &pkg_ids),
+                    debug_data: None,
+                    callers: Vec::new(),
+                    inline_chain: Vec::new(),
+                },
             };
-            let Some(symbol_address_in_bin) = self
-                .bin
-                .symbol_addresses
-                .get(&first_sym_info.symbol)
-                .cloned()
-            else {
-                debug!(
-                    "Skipping section `{}` because symbol `{}` doesn't appear in exe/so",
-                    section_name, first_sym_info.symbol
-                );
+            self.new_api_usages
+                .entry(api_usage.group_key())
+                .or_default()
+                .push(api_usage);
+        }
+        Ok(())
+    }
+
+    /// Processes the references from each function in a wasm module to other functions.
+    fn process_wasm_references(
+        &mut self,
+        module: &WasmModule<'input>,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        for function in &module.functions {
+            let (Some(name), Some(address)) = (function.name, function.address) else {
                 continue;
             };
-            let Some(debug_info) = self.bin.symbol_debug_info.get(&first_sym_info.symbol) else {
+            let symbol = Symbol::borrowed(name.as_bytes());
+            let Some(debug_info) = self.bin.symbol_debug_info.get(&symbol) else {
                 continue;
             };
             let fallback_source_location = debug_info.source_location();
-            let debug_data = self.debug_enabled.then(|| {
-                UsageDebugData::Relocation(RelocationDebugData {
-                    bin_path: self.bin.filename.clone(),
-                    object_file_path: filename.clone(),
-                    section_name: section_name.to_owned(),
-                })
-            });
-
-            for (offset, rel) in section.relocations() {
-                let mut target_symbols = Vec::new();
-                let rel = &rel;
-                object_index.add_target_symbols(
-                    rel,
-                    &mut target_symbols,
-                    &mut FxHashSet::default(),
-                    &self.bin.symbol_addresses,
-                )?;
-
-                // Use debug info to determine the function that the reference originated from.
-                let offset_in_bin = symbol_address_in_bin + offset - first_sym_info.offset;
-                let mut frames = ctx.find_frames(offset_in_bin).skip_all_loads()?;
-                let (frame_fn_name, frame_location) = frames
-                    .next()?
-                    .map(|frame| (frame.function, frame.location))
-                    .unwrap_or((None, None));
-                let location_fetcher = LocationFetcher::FrameWithFallback {
-                    frame_location,
-                    fallback: &fallback_source_location,
+            let mut frames = FrameCache::new(ctx, split_dwarf);
+            for &(reference_address, target_index, kind) in &function.references {
+                let Some(target_name) = module
+                    .functions
+                    .get(target_index as usize)
+                    .and_then(|target| target.name)
+                else {
+                    continue;
                 };
-                let frame_symbol = frame_fn_name
-                    .as_ref()
-                    .map(|fn_name| Symbol::borrowed(&fn_name.name));
                 let bin_location = BinLocation {
-                    address: offset_in_bin,
-                    symbol_start: symbol_address_in_bin,
-                };
-
-                let from_symbol = frame_symbol.as_ref().unwrap_or(&first_sym_info.symbol);
-                let from = Node {
-                    names: self.bin.get_symbol_and_name(from_symbol),
-                    location_fetcher,
+                    address: reference_address,
+                    symbol_start: address,
                 };
-                let mut non_inlined_from = None;
-                if frame_symbol.as_ref() != Some(&first_sym_info.symbol) {
-                    non_inlined_from = Some(Node {
-                        names: self.bin.get_symbol_and_name(&first_sym_info.symbol),
-                        location_fetcher: LocationFetcher::AlreadyResolved(
-                            &fallback_source_location,
-                        ),
-                    });
-                }
-                for target_symbol in target_symbols {
-                    if let Some(target_address) = self.bin.symbol_addresses.get(&target_symbol)
-                        && let Some(b) = self.backtracer.as_mut()
-                    {
-                        b.add_reference(bin_location, *target_address);
-                    }
-                    let target = self.bin.get_symbol_and_name(&target_symbol);
-                    self.process_reference(
-                        bin_location,
-                        non_inlined_from.as_ref(),
-                        &from,
-                        &target,
-                        checker,
-                        debug_data.as_ref(),
-                    )?;
-                }
+                self.process_references_from(
+                    bin_location,
+                    &symbol,
+                    &fallback_source_location,
+                    vec![Symbol::borrowed(target_name.as_bytes())],
+                    None,
+                    kind,
+                    checker,
+                    &mut frames,
+                    None,
+                )?;
             }
         }
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_reference(
         &mut self,
         bin_location: BinLocation,
         non_inlined_from: Option<&Node>,
         from: &Node,
         target: &SymbolAndName,
+        kind: ReferenceKind,
+        relocation: Option<RelocationSite>,
         checker: &Checker,
         debug_data: Option<&UsageDebugData>,
     ) -> Result<(), anyhow::Error> {
         trace!("{} -> {target}", from.names);
+        if checker.config.raw.common.ignore_instrumentation
+            && (crate::instrumentation::is_instrumentation(target)
+                || crate::instrumentation::is_instrumentation(&from.names)
+                || non_inlined_from
+                    .is_some_and(|node| crate::instrumentation::is_instrumentation(&node.names)))
+        {
+            return Ok(());
+        }
 
         let mut from_apis = FxHashSet::default();
         self.bin
             .names_and_apis_do(&from.names, checker, |_, _, apis| {
-                from_apis.extend(apis.iter());
+                from_apis.extend(apis.iter().cloned());
                 Ok(())
             })?;
         let mut lazy_location = None;
         let mut lazy_crate_names = None;
         let bin_path = self.bin.filename.clone();
         let bin_sel = self.bin.crate_sel.clone();
+        let bin = self.bin;
+        let native_pkg_id = self.native_pkg_id.as_ref();
+        let outer_symbol = non_inlined_from.unwrap_or(from).names.symbol.as_ref();
         self.bin
             .names_and_apis_do(target, checker, |name, name_source, apis| {
                 // For the majority of references we expect no APIs to match. We defer computation
@@ -420,20 +2383,27 @@ impl<'input> ApiUsageCollector<'input, '_> {
                 }
                 let location = lazy_location.as_ref().unwrap();
                 if lazy_crate_names.is_none() {
-                    lazy_crate_names = Some(checker.pkg_ids_from_source_path(location.filename())?);
+                    let query =
+                        bin.attribution_query(location.filename(), &from.names, outer_symbol);
+                    lazy_crate_names = Some(if let Some(pkg_id) = native_pkg_id {
+                        Cow::Owned(vec![pkg_id.clone()])
+                    } else if bin.symbol_only {
+                        checker.pkg_ids_from_name(&query)
+                    } else {
+                        checker.pkg_ids(&query)?
+                    });
                 }
                 let crate_names = lazy_crate_names.as_ref().unwrap();
 
                 for pkg_id in crate_names.as_ref() {
-                    // If a package references another symbol within the same package,
-                    // ignore it.
-                    // TODO: This should be use the crate name form (i.e. with underscores, not
-                    // hyphens).
-                    if name.starts_with(pkg_id.name_str()) {
+                    // If a package references another symbol within the same package, ignore
+                    // it. This is what allows a workspace crate to provide an API that's
+                    // restricted for other crates, while using it freely itself.
+                    if name.starts_with(&pkg_id.crate_name()) {
                         continue;
                     }
                     for api in apis {
-                        if from_apis.contains(&api) {
+                        if from_apis.contains(api) {
                             continue;
                         }
                         let outer_location = non_inlined_from
@@ -441,19 +2411,23 @@ impl<'input> ApiUsageCollector<'input, '_> {
                             .transpose()?;
                         let api_usage = SingleApiUsage {
                             pkg_id: pkg_id.clone(),
-                            scope: PermissionScope::determine(pkg_id, &bin_sel),
+                            scope: checker.permission_scope(pkg_id, &bin_sel),
                             api: api.clone(),
                             usage: ApiUsage {
                                 bin_location,
                                 bin_path: bin_path.clone(),
-                                permission_scope: PermissionScope::determine(pkg_id, &bin_sel),
+                                permission_scope: checker.permission_scope(pkg_id, &bin_sel),
                                 source_location: location.clone(),
                                 outer_location,
                                 from: from.names.symbol_or_debug_name()?,
                                 to: target.symbol_or_debug_name()?,
                                 to_name: name.clone(),
                                 to_source: name_source.to_owned(),
+                                kind,
+                                origin: UsageOrigin::new(relocation, crate_names),
                                 debug_data: debug_data.cloned(),
+                                callers: Vec::new(),
+                                inline_chain: Vec::new(),
                             },
                         };
                         self.new_api_usages
@@ -467,34 +2441,313 @@ impl<'input> ApiUsageCollector<'input, '_> {
         Ok(())
     }
 
-    fn emit_shortest_api_usages(&mut self) {
+    /// Drops usages of the `ffi` API where the referenced symbol isn't foreign. Every symbol that
+    /// isn't mangled by Rust matches the API, so this is where we check where each came from. A
+    /// symbol is foreign if it's defined by native code that a package built, or it's imported from
+    /// a shared library other than the C library, or it's defined by Rust code in a package other
+    /// than the one referencing it, e.g. a `#[no_mangle]` function. We only know all the symbols
+    /// that native objects define once we've processed every object file.
+    fn retain_foreign_usages(&mut self, checker: &Checker) {
+        let ffi_api = ApiName::new(FFI_API);
+        let bin = self.bin;
+        let native_definitions = &self.native_definitions;
+        let is_foreign = |usage: &SingleApiUsage| {
+            // The API only matches names from symbols, so this is the referenced symbol even when
+            // `to` is a debug name.
+            let NameSource::Symbol(symbol) = &usage.usage.to_source else {
+                return false;
+            };
+            if native_definitions.contains(symbol) || bin.foreign_imports.contains(symbol) {
+                return true;
+            }
+            bin.symbol_debug_info.get(symbol).is_some_and(|debug_info| {
+                checker
+                    .opt_pkg_ids_from_source_path(debug_info.source_location().filename())
+                    .is_some_and(|pkg_ids| !pkg_ids.is_empty() && !pkg_ids.contains(&usage.pkg_id))
+            })
+        };
+        self.new_api_usages.retain(|_, usages| {
+            usages.retain(|usage| usage.api != ffi_api || is_foreign(usage));
+            !usages.is_empty()
+        });
+    }
+
+    fn emit_api_usages(&mut self, report_mode: ReportMode) {
         // New API usages are grouped by their deduplication key, which doesn't include the target
-        // symbol. We then output only the API usage with the shortest target symbol.
-        for api_usages in std::mem::take(&mut self.new_api_usages).into_values() {
-            if let Some(shortest_target_usage) =
-                api_usages
-                    .into_iter()
-                    .min_by_key(|u| match &u.usage.to_source {
-                        NameSource::Symbol(sym) => sym.len(),
-                        NameSource::DebugName(debug_name) => debug_name.name.len(),
-                    })
+        // symbol. Unless we've been asked to report all of them, we then output only the API usage
+        // with the shortest target symbol.
+        for mut api_usages in std::mem::take(&mut self.new_api_usages).into_values() {
+            // Denied items match everything under them, like any other API, but only a reference to
+            // exactly the item counts.
+            api_usages.retain(|usage| {
+                crate::deny_list::denied_path(&usage.api)
+                    .is_none_or(|path| crate::deny_list::is_exact_match(path, &usage.usage.to_name))
+            });
+            if report_mode == ReportMode::Shortest
+                && let Some(shortest_target_usage) =
+                    api_usages
+                        .drain(..)
+                        .min_by_key(|u| match &u.usage.to_source {
+                            NameSource::Symbol(sym) => sym.len(),
+                            NameSource::DebugName(debug_name) => debug_name.name.len(),
+                        })
             {
-                self.outputs
-                    .api_usages
-                    .entry((
-                        shortest_target_usage.pkg_id.clone(),
-                        shortest_target_usage.api.clone(),
-                    ))
+                api_usages.push(shortest_target_usage);
+            }
+            for usage in api_usages {
+                if let Some(runtime) = AsyncRuntime::from_api_name(&usage.api) {
+                    self.outputs
+                        .async_runtimes
+                        .entry(runtime)
+                        .or_default()
+                        .insert(usage.pkg_id);
+                    continue;
+                }
+                if let Some(source) = NondeterminismSource::from_api_name(&usage.api) {
+                    self.outputs
+                        .nondeterminism
+                        .entry(source)
+                        .or_default()
+                        .insert(usage.pkg_id);
+                    continue;
+                }
+                let outputs = if PanicPath::from_api_name(&usage.api).is_some() {
+                    &mut self.outputs.panic_usages
+                } else if crate::alloc_audit::is_alloc_api(&usage.api) {
+                    &mut self.outputs.alloc_usages
+                } else if crate::deny_list::denied_path(&usage.api).is_some() {
+                    &mut self.outputs.denied_usages
+                } else {
+                    &mut self.outputs.api_usages
+                };
+                outputs
+                    .entry((usage.pkg_id.clone(), usage.api.clone()))
                     .or_insert_with(|| ApiUsages {
-                        pkg_id: shortest_target_usage.pkg_id.clone(),
-                        scope: shortest_target_usage.scope,
-                        api_name: shortest_target_usage.api.clone(),
+                        pkg_id: usage.pkg_id.clone(),
+                        scope: usage.scope,
+                        api_name: usage.api.clone(),
                         usages: Default::default(),
+                        feature_fix: None,
+                        usage_pins: Vec::new(),
                     })
                     .usages
-                    .push(shortest_target_usage.usage);
+                    .push(usage.usage);
+            }
+        }
+    }
+
+    /// Fills in the compilation unit of each API usage's origin.
+    fn add_compilation_units(
+        &mut self,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) {
+        let mut by_address: FxHashMap<u64, Option<PathBuf>> = FxHashMap::default();
+        for usages in self.outputs.api_usages.values_mut() {
+            for usage in &mut usages.usages {
+                let address = usage.bin_location.address;
+                usage.origin.compilation_unit = by_address
+                    .entry(address)
+                    .or_insert_with(|| {
+                        split_dwarf
+                            .resolve(ctx.find_dwarf_and_unit(address))
+                            .and_then(|unit| unit.name)
+                            .map(|name| PathBuf::from(OsStr::from_bytes(name.slice())))
+                    })
+                    .clone();
+            }
+        }
+    }
+
+    /// Fills in the chain of inlined functions leading to each API usage, from the frames that the
+    /// debug info has for the usage's address.
+    fn find_inline_chains(
+        &mut self,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<()> {
+        let mut by_address: FxHashMap<u64, Vec<InlineFrame>> = FxHashMap::default();
+        for usages in self.outputs.api_usages.values_mut() {
+            for usage in &mut usages.usages {
+                let address = usage.bin_location.address;
+                usage.inline_chain = match by_address.entry(address) {
+                    Entry::Occupied(entry) => entry.get().clone(),
+                    Entry::Vacant(entry) => entry
+                        .insert(self.bin.inline_chain(address, ctx, split_dwarf)?)
+                        .clone(),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a fact for each symbol that has debug info, recording where it's defined and the
+    /// packages that it's attributed to.
+    fn symbol_facts(&mut self, checker: &Checker) -> Result<Vec<SymbolFact>> {
+        let bin = self.bin;
+        let mut facts = Vec::new();
+        for (symbol, debug_info) in &bin.symbol_debug_info {
+            let Some(address) = bin.symbol_addresses.get(symbol) else {
+                continue;
+            };
+            let name = bin.get_symbol_and_name(symbol).symbol_or_debug_name()?;
+            facts.push(SymbolFact {
+                bin_path: bin.filename.to_path_buf(),
+                symbol: symbol.to_string(),
+                name: shorten_for_json(&name),
+                crate_name: symbol.crate_name().map(str::to_owned),
+                packages: self
+                    .pkg_ids_at(*address, symbol, checker)
+                    .iter()
+                    .map(|pkg_id| format!("{} {}", pkg_id.name_str(), pkg_id.version()))
+                    .collect(),
+                location: debug_info.source_location(),
+            });
+        }
+        Ok(facts)
+    }
+
+    /// Returns the packages that `symbol`, which is at `address`, belongs to according to its debug
+    /// info.
+    fn pkg_ids_at(&mut self, address: u64, symbol: &Symbol, checker: &Checker) -> &[PackageId] {
+        let bin = self.bin;
+        self.pkg_ids_by_address.entry(address).or_insert_with(|| {
+            bin.symbol_debug_info
+                .get(symbol)
+                .and_then(|debug_info| {
+                    let source_location = debug_info.source_location();
+                    let names = bin.get_symbol_and_name(symbol);
+                    checker
+                        .opt_pkg_ids(&bin.attribution_query(
+                            source_location.filename(),
+                            &names,
+                            Some(symbol),
+                        ))
+                        .map(|pkg_ids| pkg_ids.into_owned())
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Fills in the callers of each API usage. See `call_graph.rs`.
+    fn find_call_paths(&mut self, checker: &Checker) -> Result<()> {
+        let bin = self.bin;
+        let graph = CallGraph::new(self.calls.drain());
+        let mut symbols_by_address: FxHashMap<u64, &Symbol> = FxHashMap::default();
+        for (symbol, address) in &bin.symbol_addresses {
+            if bin.symbol_debug_info.contains_key(symbol) {
+                symbols_by_address.entry(*address).or_insert(symbol);
+            }
+        }
+        let mut api_usages = std::mem::take(&mut self.outputs.api_usages);
+        for api_usages in api_usages.values_mut() {
+            let pkg_id = &api_usages.pkg_id;
+            for usage in &mut api_usages.usages {
+                let path = graph.path_to(usage.bin_location.symbol_start, |address| {
+                    symbols_by_address.get(&address).is_some_and(|symbol| {
+                        self.pkg_ids_at(address, symbol, checker).contains(pkg_id)
+                    })
+                });
+                let mut callers = path
+                    .iter()
+                    .filter_map(|address| symbols_by_address.get(address))
+                    .map(|symbol| bin.get_symbol_and_name(symbol).symbol_or_debug_name())
+                    .collect::<Result<Vec<_>>>()?;
+                // The path ends with the function containing the usage, which is generally the same
+                // as `from`, unless `from` was inlined into it.
+                if callers.last() == Some(&usage.from) {
+                    callers.pop();
+                }
+                usage.callers = callers;
+            }
+        }
+        self.outputs.api_usages = api_usages;
+        Ok(())
+    }
+
+    /// Returns a graph of the references that we found and of which functions use which APIs.
+    fn reference_graph(
+        &mut self,
+        checker: &Checker,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+    ) -> Result<ReferenceGraph> {
+        let bin = self.bin;
+        // Where there are multiple symbols at an address, we prefer one with debug info, since
+        // that's what tells us which package the symbol belongs to.
+        let mut symbols_by_address: FxHashMap<u64, &Symbol> = FxHashMap::default();
+        for (symbol, address) in &bin.symbol_addresses {
+            let existing = symbols_by_address.entry(*address).or_insert(symbol);
+            if !bin.symbol_debug_info.contains_key(*existing)
+                && bin.symbol_debug_info.contains_key(symbol)
+            {
+                *existing = symbol;
+            }
+        }
+        let mut references = self.graph_references.take().unwrap_or_default();
+        references.sort_by_key(|r| (r.location.address, r.target_address));
+        let mut addresses: Vec<u64> = references
+            .iter()
+            .flat_map(|r| [r.location.symbol_start, r.target_address])
+            .chain(
+                self.outputs
+                    .api_usages
+                    .values()
+                    .flat_map(|usages| &usages.usages)
+                    .map(|usage| usage.bin_location.symbol_start),
+            )
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        let mut graph = ReferenceGraph::new(bin.filename.clone());
+        for address in addresses {
+            if let Some(symbol) = symbols_by_address.get(&address) {
+                let names = bin.get_symbol_and_name(symbol);
+                let name = names.symbol_or_debug_name()?.to_string();
+                let raw_symbol = symbol.to_string();
+                graph.add_symbol(
+                    address,
+                    GraphSymbol {
+                        symbol: (raw_symbol != name).then_some(raw_symbol),
+                        name,
+                        pkg_ids: self.pkg_ids_at(address, symbol, checker).to_vec(),
+                        source_location: bin
+                            .symbol_debug_info
+                            .get(*symbol)
+                            .map(|debug_info| debug_info.source_location()),
+                    },
+                );
+            }
+        }
+        for reference in references {
+            let source_location =
+                ctx.find_location(reference.location.address)?
+                    .and_then(|location| {
+                        Some(SourceLocation::new(
+                            Path::new(location.file?),
+                            location.line?,
+                            location.column,
+                        ))
+                    });
+            graph.add_reference(
+                reference.location.symbol_start,
+                reference.target_address,
+                reference.relocation.map(|kind| format!("{kind:?}")),
+                reference.location.address,
+                source_location,
+            );
+        }
+        for usages in self.outputs.api_usages.values() {
+            for usage in &usages.usages {
+                graph.add_api_usage(
+                    usage.bin_location.symbol_start,
+                    &usages.api_name,
+                    usage.to_name.to_string(),
+                    usage.bin_location.address,
+                    usage.source_location.clone(),
+                );
             }
         }
+        Ok(graph)
     }
 
     fn find_possible_exports(&mut self, checker: &Checker) {
@@ -519,6 +2772,10 @@ impl<'input> ApiUsageCollector<'input, '_> {
                 .unwrap_or_else(|| Cow::Owned(Vec::new()))
                 .as_ref()
             {
+                // With rustdoc's view of what's public, we don't need to guess from symbol names.
+                if checker.has_rustdoc_exports(pkg_id) {
+                    continue;
+                }
                 if found.insert((pkg_id.clone(), api_name)) {
                     // Macros can sometimes result in symbols being attributed to lower-level
                     // crates, so we only consider exported APIs that start with the crate name we
@@ -531,7 +2788,7 @@ impl<'input> ApiUsageCollector<'input, '_> {
                         .push(PossibleExportedApi {
                             pkg_id: pkg_id.to_owned(),
                             api: ApiName::clone(api_name),
-                            symbol: symbol.to_heap(),
+                            symbol: Some(symbol.to_heap()),
                         });
                 }
             }
@@ -569,10 +2826,47 @@ impl LocationFetcher<'_> {
     }
 }
 
-impl<'obj, 'data> ObjectIndex<'obj, 'data> {
-    fn new(obj: &'obj object::File<'data>) -> Self {
+impl<'obj, 'data, R: ReadRef<'data>> ObjectIndex<'obj, 'data, R> {
+    /// Returns the signatures of the COMDAT groups that sections of the object are in. A group
+    /// with several sections is returned once per section.
+    fn groups(&self) -> impl Iterator<Item = &Symbol<'data>> {
+        self.section_infos
+            .iter()
+            .filter_map(|section_info| section_info.group.as_ref())
+    }
+
+    fn new(obj: &'obj object::File<'data, R>) -> Self {
         let max_section_index = obj.sections().map(|s| s.index().0).max().unwrap_or(0);
         let mut section_infos = vec![SectionInfo::default(); max_section_index + 1];
+        // Sections in relocatable ELF objects all have address 0, but in Mach-O objects they don't,
+        // so we subtract the section address to get offsets that we can compare with those of
+        // relocations.
+        let mut section_addresses = vec![0; max_section_index + 1];
+        for section in obj.sections() {
+            section_addresses[section.index().0] = section.address();
+        }
+        for comdat in obj.comdats() {
+            // With these kinds, the linker keeps the first copy of the group that it sees. For the
+            // others, e.g. keeping the largest, we don't know which copy was kept, so we process
+            // them all.
+            if !matches!(
+                comdat.kind(),
+                ComdatKind::Any
+                    | ComdatKind::NoDuplicates
+                    | ComdatKind::SameSize
+                    | ComdatKind::ExactMatch
+            ) {
+                continue;
+            }
+            let Ok(name) = comdat.name_bytes() else {
+                continue;
+            };
+            for section_index in comdat.sections() {
+                if let Some(section_info) = section_infos.get_mut(section_index.0) {
+                    section_info.group = Some(Symbol::borrowed(name));
+                }
+            }
+        }
         for obj_symbol in obj.symbols() {
             let name = obj_symbol.name_bytes().unwrap_or_default();
             if name.is_empty() || !obj_symbol.is_definition() {
@@ -581,17 +2875,27 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
             let Some(section_index) = obj_symbol.section_index() else {
                 continue;
             };
-            let section_info = &mut section_infos[section_index.0];
-            let symbol_is_first_in_section = section_info
-                .first_symbol
-                .as_ref()
-                .map(|existing| obj_symbol.address() < existing.offset)
-                .unwrap_or(true);
-            if symbol_is_first_in_section {
-                section_info.first_symbol = Some(SymbolInfo {
-                    symbol: Symbol::borrowed(name),
-                    offset: obj_symbol.address(),
-                });
+            let Some(section_info) = section_infos.get_mut(section_index.0) else {
+                continue;
+            };
+            section_info.symbols.push(SymbolInfo {
+                symbol: Symbol::borrowed(name),
+                offset: obj_symbol
+                    .address()
+                    .saturating_sub(section_addresses[section_index.0]),
+                binding: SymbolBinding::of(&obj_symbol),
+            });
+        }
+        for section_info in &mut section_infos {
+            // A stable sort, so that amongst equally preferred symbols, the first listed wins.
+            section_info
+                .symbols
+                .sort_by_key(|symbol_info| (symbol_info.offset, symbol_info.binding));
+            section_info.first_symbol = section_info.symbols.first().cloned();
+            if section_info.symbols.iter().all(|symbol_info| {
+                Some(symbol_info.offset) == section_info.symbols.first().map(|first| first.offset)
+            }) {
+                section_info.symbols.clear();
             }
         }
         Self { obj, section_infos }
@@ -607,11 +2911,15 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
         visited: &mut FxHashSet<SectionIndex>,
         bin_symbols: &FxHashMap<Symbol, u64>,
     ) -> Result<()> {
+        if !is_reference(self.obj.architecture(), rel.flags()) {
+            return Ok(());
+        }
         match self.get_symbol_or_section(rel.target(), bin_symbols)? {
-            SymbolOrSection::Symbol(symbol) => {
+            None => {}
+            Some(SymbolOrSection::Symbol(symbol)) => {
                 symbols_out.push(symbol);
             }
-            SymbolOrSection::Section(section_index) => {
+            Some(SymbolOrSection::Section(section_index)) => {
                 if !visited.insert(section_index) {
                     // We've already visited this section.
                     return Ok(());
@@ -626,12 +2934,13 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
     }
 
     /// Returns either symbol or the section index for a relocation target, giving preference to the
-    /// symbol.
+    /// symbol. Returns `None` for relocations without a target, e.g. RISC-V's `R_RISCV_RELAX`,
+    /// which marks the previous relocation as one that the linker may relax.
     fn get_symbol_or_section(
         &self,
         target_in: RelocationTarget,
         bin_symbols: &FxHashMap<Symbol, u64>,
-    ) -> Result<SymbolOrSection<'data>> {
+    ) -> Result<Option<SymbolOrSection<'data>>> {
         let section_index = match target_in {
             RelocationTarget::Symbol(symbol_index) => {
                 let Ok(symbol) = self.obj.symbol_by_index(symbol_index) else {
@@ -641,33 +2950,259 @@ impl<'obj, 'data> ObjectIndex<'obj, 'data> {
                 if !name.is_empty() {
                     let sym = Symbol::borrowed(name);
                     if bin_symbols.contains_key(&sym) || symbol.section_index().is_none() {
-                        return Ok(SymbolOrSection::Symbol(sym));
+                        return Ok(Some(SymbolOrSection::Symbol(sym)));
                     }
                 }
                 symbol.section_index().ok_or_else(|| {
                     anyhow!("Relocation target has empty name and no section index")
                 })?
             }
+            // Some relocations, e.g. non-extern relocations in Mach-O objects, reference a section
+            // directly rather than via a symbol. We treat these the same as a symbol with an empty
+            // name.
+            RelocationTarget::Section(section_index) => section_index,
+            RelocationTarget::Absolute => return Ok(None),
             _ => bail!("Unsupported relocation kind {target_in:?}"),
         };
         let section_info = &self
             .section_infos
             .get(section_index.0)
-            .ok_or_else(|| anyhow!("Unnamed symbol has invalid section index"))?;
+            .ok_or_else(|| anyhow!("Relocation target has invalid section index"))?;
         if let Some(first_symbol_info) = section_info.first_symbol.as_ref()
             && bin_symbols.contains_key(&first_symbol_info.symbol)
         {
-            return Ok(SymbolOrSection::Symbol(first_symbol_info.symbol.clone()));
+            return Ok(Some(SymbolOrSection::Symbol(
+                first_symbol_info.symbol.clone(),
+            )));
+        }
+        Ok(Some(SymbolOrSection::Section(section_index)))
+    }
+
+    /// Returns the symbol that the pointer written by `rel` at `offset` in `section` points to.
+    /// Pointers to functions that aren't exported, e.g. static C functions, are often written as
+    /// the address of the section that contains them plus an addend, so we find the symbol that
+    /// contains that offset, as we do for references from a section with several symbols.
+    fn pointed_to_symbol(
+        &self,
+        section: &object::Section<'data, '_, R>,
+        offset: u64,
+        rel: &object::Relocation,
+        bin_symbols: &FxHashMap<Symbol, u64>,
+    ) -> Result<Option<Symbol<'data>>> {
+        let target_section = match rel.target() {
+            RelocationTarget::Section(section_index) => section_index,
+            RelocationTarget::Symbol(symbol_index) => {
+                let symbol = self.obj.symbol_by_index(symbol_index)?;
+                match symbol.section_index() {
+                    Some(section_index)
+                        if symbol.kind() == SymbolKind::Section
+                            || symbol.name_bytes().unwrap_or_default().is_empty() =>
+                    {
+                        section_index
+                    }
+                    _ => {
+                        return Ok(
+                            match self.get_symbol_or_section(rel.target(), bin_symbols)? {
+                                Some(SymbolOrSection::Symbol(symbol)) => Some(symbol),
+                                _ => None,
+                            },
+                        );
+                    }
+                }
+            }
+            _ => return Ok(None),
+        };
+        let addend = if rel.has_implicit_addend() {
+            // The addend is whatever's already stored where the pointer goes. For Mach-O, that's
+            // the address of the target, so we subtract the address of its section below.
+            let data = section.data()?;
+            let start = usize::try_from(offset)?;
+            let bytes = data
+                .get(start..start + usize::from(rel.size() / 8))
+                .filter(|bytes| bytes.len() <= 8)
+                .ok_or_else(|| anyhow!("Relocation at offset {offset:#x} is out of bounds"))?;
+            let mut value = [0; 8];
+            if self.obj.is_little_endian() {
+                value[..bytes.len()].copy_from_slice(bytes);
+                u64::from_le_bytes(value)
+            } else {
+                value[8 - bytes.len()..].copy_from_slice(bytes);
+                u64::from_be_bytes(value)
+            }
+        } else {
+            rel.addend() as u64
+        };
+        let section_offset =
+            addend.wrapping_sub(self.obj.section_by_index(target_section)?.address());
+        let Some(section_info) = self.section_infos.get(target_section.0) else {
+            return Ok(None);
+        };
+        if section_info.symbols.is_empty() {
+            return Ok(section_info
+                .first_symbol
+                .as_ref()
+                .map(|symbol_info| symbol_info.symbol.clone()));
+        }
+        Ok(Some(
+            section_info.symbols
+                [enclosing_symbol_index(&section_info.symbols, section_offset, bin_symbols)]
+            .symbol
+            .clone(),
+        ))
+    }
+
+    /// Returns whether `rel` refers to an anonymous section that looks like a vtable. The targets
+    /// of such a relocation are the functions in the vtable. See `add_target_symbols`.
+    fn targets_vtable(
+        &self,
+        rel: &object::Relocation,
+        bin_symbols: &FxHashMap<Symbol, u64>,
+    ) -> Result<bool> {
+        let Some(SymbolOrSection::Section(section_index)) =
+            self.get_symbol_or_section(rel.target(), bin_symbols)?
+        else {
+            return Ok(false);
+        };
+        let section = self.obj.section_by_index(section_index)?;
+        if is_executable_section(&section) {
+            return Ok(false);
         }
-        Ok(SymbolOrSection::Section(section_index))
+        // A vtable starts with a pointer to the drop function, which may be null, then has the size
+        // and alignment of the type, then pointers to the trait's methods.
+        let pointer_size = if self.obj.is_64() { 8 } else { 4 };
+        let mut offsets = section.relocations().map(|(offset, _)| offset);
+        Ok(section.size() >= 3 * pointer_size
+            && section.size() % pointer_size == 0
+            && offsets.all(|offset| {
+                offset % pointer_size == 0 && !(1..3).contains(&(offset / pointer_size))
+            }))
     }
 
     /// Returns information about the first symbol in the section.
-    fn first_symbol(&self, section: &object::Section) -> Option<&SymbolInfo<'data>> {
+    fn first_symbol(&self, section: &object::Section<'data, '_, R>) -> Option<&SymbolInfo<'data>> {
         self.section_infos
             .get(section.index().0)
             .and_then(|section_info| section_info.first_symbol.as_ref())
     }
+
+    /// Returns the symbols defined at more than one offset in `section`, or an empty slice if
+    /// everything in the section belongs to its first symbol.
+    fn section_symbols(&self, section: &object::Section<'data, '_, R>) -> &[SymbolInfo<'data>] {
+        self.section_infos
+            .get(section.index().0)
+            .map(|section_info| section_info.symbols.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the signature of the COMDAT group that `section` belongs to, if any.
+    fn group(&self, section: &object::Section<'data, '_, R>) -> Option<&Symbol<'data>> {
+        self.section_infos
+            .get(section.index().0)
+            .and_then(|section_info| section_info.group.as_ref())
+    }
+}
+
+/// Returns the index in `symbols`, which must be sorted as for `SectionInfo::symbols`, of the symbol
+/// that contains `offset`. That's the last symbol that starts at or before `offset`. Where several
+/// symbols start at the same offset, we prefer one that's in the binary. References from before
+/// the first symbol are attributed to the first symbol.
+fn enclosing_symbol_index(
+    symbols: &[SymbolInfo],
+    offset: u64,
+    bin_symbols: &FxHashMap<Symbol, u64>,
+) -> usize {
+    let end = symbols.partition_point(|symbol_info| symbol_info.offset <= offset);
+    let Some(last) = end.checked_sub(1).map(|index| &symbols[index]) else {
+        return 0;
+    };
+    let start = symbols.partition_point(|symbol_info| symbol_info.offset < last.offset);
+    symbols[start..end]
+        .iter()
+        .position(|symbol_info| bin_symbols.contains_key(&symbol_info.symbol))
+        .map_or(start, |index| start + index)
+}
+
+impl SymbolBinding {
+    fn of<'data, R: ReadRef<'data>>(symbol: &object::Symbol<'data, '_, R>) -> Self {
+        if let SymbolFlags::Elf { st_info, .. } = symbol.flags()
+            && st_info & 0xf == object::elf::STT_GNU_IFUNC
+        {
+            SymbolBinding::Ifunc
+        } else if symbol.is_weak() {
+            SymbolBinding::Weak
+        } else {
+            SymbolBinding::Strong
+        }
+    }
+}
+
+/// Returns the name of the crate that an object file was compiled from, going by the naming
+/// conventions that cargo uses, e.g. `libfoo-1234abcd.rlib` or `foo-1234abcd.foo.cgu.0.rcgu.o`.
+fn crate_name_for_object(path: &ObjectFilePath) -> Option<&str> {
+    let stem = path.outer.file_name()?.to_str()?;
+    let stem = if path.inner.is_some() {
+        stem.strip_prefix("lib")?
+    } else {
+        stem
+    };
+    let (crate_name, _) = stem.split_once('-')?;
+    Some(crate_name)
+}
+
+/// Returns the package whose build script built the object at `path`, if the object contains native
+/// code rather than Rust code. `defined_symbols` are the symbols that the object defines. Objects
+/// that cc or cmake built have no Rust symbols and generally nothing in their debug info that
+/// ties them to a package. They're either in an archive in the build script's OUT_DIR or, when
+/// rustc bundles that archive, members of the package's rlib.
+fn native_object_pkg_id<'checker, 'data>(
+    path: &ObjectFilePath,
+    mut defined_symbols: impl Iterator<Item = Symbol<'data>>,
+    checker: &'checker Checker,
+) -> Option<&'checker PackageId> {
+    if defined_symbols.any(|symbol| symbol.is_mangled()) {
+        return None;
+    }
+    checker
+        .pkg_id_for_out_dir_path(&path.outer)
+        .or_else(|| checker.pkg_id_for_out_dir_path(path.inner.as_deref()?))
+        .or_else(|| {
+            path.inner.as_ref()?;
+            checker
+                .crate_index
+                .name_prefix_to_pkg_id()
+                .get(crate_name_for_object(path)?)
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolVersion {
+    /// The symbol name didn't have a version.
+    None,
+
+    /// A version separated by `@@`. This is the version that unversioned references bind to.
+    Default,
+
+    /// A version separated by a single `@`.
+    NonDefault,
+}
+
+/// Splits a GNU symbol version suffix, e.g. `@GLIBC_2.14` or `@@GLIBC_2.14`, off a symbol name.
+/// MSVC mangled names, which start with `?`, use `@` as part of the mangling, so are left alone.
+fn split_symbol_version(name: &[u8]) -> (&[u8], SymbolVersion) {
+    if name.first() == Some(&b'?') {
+        return (name, SymbolVersion::None);
+    }
+    match name.iter().position(|b| *b == b'@') {
+        Some(at) if at > 0 => {
+            let version = if name.get(at + 1) == Some(&b'@') {
+                SymbolVersion::Default
+            } else {
+                SymbolVersion::NonDefault
+            };
+            (&name[..at], version)
+        }
+        _ => (name, SymbolVersion::None),
+    }
 }
 
 enum SymbolOrSection<'data> {
@@ -676,17 +3211,105 @@ enum SymbolOrSection<'data> {
 }
 
 impl<'symbol, 'input: 'symbol> BinInfo<'input> {
-    fn load_symbols(&mut self, obj: &object::File) -> Result<()> {
+    fn load_wasm_symbols(&mut self, module: &WasmModule<'input>) {
+        for function in &module.functions {
+            if let (Some(name), Some(address)) = (function.name, function.address) {
+                self.symbol_addresses
+                    .insert(Symbol::borrowed(name.as_bytes()), address);
+            }
+        }
+    }
+
+    fn load_symbols(&mut self, obj: &object::File, strip_versions: bool) -> Result<()> {
+        let strip_versions = strip_versions && obj.format() == object::BinaryFormat::Elf;
         for sym in obj.symbols() {
-            let symbol = &Symbol::borrowed(sym.name_bytes()?);
+            let (name, version) = if strip_versions {
+                split_symbol_version(sym.name_bytes()?)
+            } else {
+                (sym.name_bytes()?, SymbolVersion::None)
+            };
+            let symbol = &Symbol::borrowed(name);
             if !symbol.is_look_through() {
-                self.symbol_addresses
-                    .insert(symbol.to_heap(), sym.address());
+                // When there are multiple versions of a symbol, references without a version bind
+                // to the default version.
+                if version == SymbolVersion::NonDefault {
+                    self.symbol_addresses
+                        .entry(symbol.to_heap())
+                        .or_insert(sym.address());
+                } else {
+                    self.symbol_addresses
+                        .insert(symbol.to_heap(), sym.address());
+                }
+            }
+            if sym.is_weak() && sym.is_definition() {
+                self.weak_symbols.insert(symbol.to_heap());
+            }
+            if sym.is_global() && sym.is_definition() && !symbol.is_mangled() {
+                self.entry_points.push(sym.address());
             }
         }
+        self.executable_ranges = obj
+            .sections()
+            .filter(is_executable_section)
+            .map(|section| section.address()..section.address() + section.size())
+            .collect();
         Ok(())
     }
 
+    /// Returns what we know about the symbols in this binary that are named `name`, either exactly
+    /// or once demangled.
+    fn explain_symbol(&self, name: &str, checker: &Checker) -> Vec<SymbolExplanation> {
+        let wanted = Symbol::borrowed(name.as_bytes());
+        let mut symbols: Vec<&Symbol> = self
+            .symbol_addresses
+            .keys()
+            .chain(self.symbol_debug_info.keys())
+            .filter(|symbol| **symbol == wanted || symbol.to_string() == name)
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+            .into_iter()
+            .map(|symbol| {
+                let debug_info = self.symbol_debug_info.get(symbol);
+                let source_location = debug_info.map(|info| info.source_location());
+                let in_rust_std = source_location
+                    .as_ref()
+                    .is_some_and(|location| checker.is_in_rust_std(location.filename()));
+                let pkg_ids = debug_info
+                    .zip(source_location.as_ref())
+                    .filter(|_| !in_rust_std)
+                    .and_then(|(info, location)| {
+                        checker.opt_pkg_ids(&AttributionQuery {
+                            source_path: location.filename(),
+                            name_crate: symbol.crate_name(),
+                            crate_root: info.crate_root(),
+                        })
+                    })
+                    .map(|pkg_ids| pkg_ids.into_owned())
+                    .unwrap_or_default();
+                SymbolExplanation {
+                    bin_path: self.filename.clone(),
+                    symbol: symbol.to_heap(),
+                    address: self.symbol_addresses.get(symbol).copied(),
+                    weak: self.weak_symbols.contains(symbol),
+                    debug_name: debug_info
+                        .and_then(|info| info.name.as_ref())
+                        .map(|name| name.to_heap()),
+                    source_location,
+                    pkg_ids,
+                    in_rust_std,
+                }
+            })
+            .collect()
+    }
+
+    fn is_executable_address(&self, address: u64) -> bool {
+        self.executable_ranges
+            .iter()
+            .any(|range| range.contains(&address))
+    }
+
     fn get_symbol_and_name(&self, symbol: &Symbol<'symbol>) -> SymbolAndName<'symbol> {
         let mut result = SymbolAndName {
             symbol: Some(symbol.clone()),
@@ -697,6 +3320,38 @@ impl<'symbol, 'input: 'symbol> BinInfo<'input> {
         }
         result
     }
+
+    /// Returns the functions that were inlined into one another to produce the code at `address`,
+    /// outermost first, or nothing if the code there wasn't inlined.
+    fn inline_chain(
+        &self,
+        address: u64,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
+    ) -> Result<Vec<InlineFrame>> {
+        let mut chain = Vec::new();
+        let mut frames = split_dwarf.resolve(ctx.find_frames(address))?;
+        while let Some(frame) = frames.next()? {
+            let Some(function) = frame.function.as_ref() else {
+                continue;
+            };
+            chain.push(InlineFrame {
+                function: self
+                    .get_symbol_and_name(&Symbol::borrowed(function.name.slice()))
+                    .symbol_or_debug_name()?,
+                location: frame
+                    .location
+                    .as_ref()
+                    .and_then(|location| SourceLocation::try_from(location).ok()),
+            });
+        }
+        if chain.len() < 2 {
+            return Ok(Vec::new());
+        }
+        // Frames are innermost first.
+        chain.reverse();
+        Ok(chain)
+    }
 }
 
 impl TryFrom<&addr2line::Location<'_>> for SourceLocation {
@@ -715,14 +3370,31 @@ impl TryFrom<&addr2line::Location<'_>> for SourceLocation {
 }
 
 impl BinInfo<'_> {
+    /// Returns a query for attributing code from `source_path` in the function `names`, which is
+    /// either `outer_symbol` or was inlined into it.
+    fn attribution_query<'a>(
+        &self,
+        source_path: &'a Path,
+        names: &'a SymbolAndName,
+        outer_symbol: Option<&Symbol>,
+    ) -> AttributionQuery<'a> {
+        AttributionQuery {
+            source_path,
+            name_crate: names.crate_name(),
+            crate_root: outer_symbol
+                .and_then(|symbol| self.symbol_debug_info.get(symbol))
+                .and_then(|debug_info| debug_info.crate_root()),
+        }
+    }
+
     /// Runs `callback` for each name in `symbol` or in the name obtained for the debug information
     /// for `symbol`. Also supplies information about the name source and a set of APIs that match
     /// the name.
-    fn names_and_apis_do<'checker>(
-        &mut self,
+    fn names_and_apis_do(
+        &self,
         symbol_and_name: &SymbolAndName,
-        checker: &'checker Checker,
-        mut callback: impl FnMut(Name, NameSource, &'checker FxHashSet<ApiName>) -> Result<()>,
+        checker: &Checker,
+        mut callback: impl FnMut(Name, NameSource, &FxHashSet<ApiName>) -> Result<()>,
     ) -> Result<()> {
         // If we've previously observed that this symbol has no APIs associated with it, then skip
         // it.
@@ -730,8 +3402,7 @@ impl BinInfo<'_> {
             .symbol
             .as_ref()
             .and_then(|symbol| self.symbol_has_no_apis.get(symbol))
-            .cloned()
-            .unwrap_or(false)
+            .is_some_and(|no_apis| no_apis.load(Ordering::Relaxed))
         {
             return Ok(());
         }
@@ -748,7 +3419,7 @@ impl BinInfo<'_> {
                     (callback)(
                         name.create_name()?,
                         NameSource::DebugName(debug_name.to_heap()),
-                        apis,
+                        &apis,
                     )?;
                 }
             }
@@ -762,7 +3433,7 @@ impl BinInfo<'_> {
                     (callback)(
                         name.create_name()?,
                         NameSource::Symbol(symbol.clone()),
-                        apis,
+                        &apis,
                     )?;
                 }
             }
@@ -773,15 +3444,15 @@ impl BinInfo<'_> {
             // The need to call `to_heap` here is just to get past an annoying variance issue.
             // Fortunately it doesn't seem to affect performance significantly, so probably the
             // optimiser is able to get rid of the allocation.
-            if let Some(x) = self.symbol_has_no_apis.get_mut(&symbol.to_heap()) {
-                *x = true;
+            if let Some(no_apis) = self.symbol_has_no_apis.get(&symbol.to_heap()) {
+                no_apis.store(true, Ordering::Relaxed);
             }
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) enum NameSource<'symbol> {
     Symbol(Symbol<'symbol>),
     DebugName(DebugName<'static>),
@@ -805,18 +3476,220 @@ impl Display for NameSource<'_> {
     }
 }
 
+/// Returns the byte order of `obj`, which we need in order to read its DWARF. This is usually, but
+/// not always, little endian.
+pub(crate) fn endian(obj: &object::File) -> RunTimeEndian {
+    if obj.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    }
+}
+
+/// Returns the code for the function of `size` bytes at `address`.
+fn function_code<'data>(
+    obj: &object::File<'data>,
+    address: u64,
+    size: u64,
+) -> Result<Option<&'data [u8]>> {
+    let Some(section) = obj.sections().find(|section| {
+        section.kind() == SectionKind::Text
+            && section.address() <= address
+            && address + size <= section.address() + section.size()
+    }) else {
+        return Ok(None);
+    };
+    Ok(section.data_range(address, size)?)
+}
+
 /// Loads section `id` from `obj`.
 fn load_section<'data>(
     obj: &object::File<'data>,
     id: gimli::SectionId,
-) -> Result<Cow<'data, [u8]>, gimli::Error> {
+) -> Result<Cow<'data, [u8]>> {
     let Some(section) = obj.section_by_name(id.name()) else {
         return Ok(Cow::Borrowed([].as_slice()));
     };
-    let Ok(data) = section.uncompressed_data() else {
-        return Ok(Cow::Borrowed([].as_slice()));
+    section_data(&section)
+}
+
+/// Returns the contents of `section`, decompressing it if necessary. Debug sections may be
+/// compressed with either zlib or zstd.
+fn section_data<'data>(section: &object::Section<'data, '_>) -> Result<Cow<'data, [u8]>> {
+    let name = section.name().unwrap_or("?");
+    let compressed = section
+        .compressed_data()
+        .with_context(|| format!("Failed to read section `{name}`"))?;
+    match compressed.format {
+        CompressionFormat::None | CompressionFormat::Zlib | CompressionFormat::Zstandard => {}
+        format => bail!("Section `{name}` uses unsupported compression {format:?}"),
+    }
+    compressed
+        .decompress()
+        .with_context(|| format!("Failed to decompress section `{name}`"))
+}
+
+/// Returns whether the contents of sections named `name` might be used without anything that we can
+/// see referencing them. e.g. `.init_array` contains pointers to functions that run before main and
+/// sections with custom names may be found via linker-generated `__start_` symbols. We treat
+/// everything except the standard sections for code and data in this way. Debug info and unwind
+/// information reference all functions, but don't make any of them reachable.
+fn is_root_section(name: &str) -> bool {
+    const NON_ROOT_PREFIXES: &[&str] = &[
+        ".text",
+        ".data",
+        ".rodata",
+        ".bss",
+        ".tdata",
+        ".tbss",
+        ".gcc_except_table",
+        ".eh_frame",
+        ".debug",
+        ".zdebug",
+        ".note",
+        ".comment",
+        ".llvm",
+        ".rustc",
+        ".group",
+        ".stack_sizes",
+    ];
+    !NON_ROOT_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Returns whether sections named `name` contain pointers to functions that the loader or the C
+/// runtime calls before `main`, e.g. those registered by the `ctor` crate or by statics with
+/// `#[link_section = ".init_array"]`. These are the ELF sections, their Mach-O equivalent and the
+/// sections that the MSVC C runtime uses for initialisers.
+fn is_init_section(name: &str) -> bool {
+    const ELF_SECTIONS: &[&str] = &[".init_array", ".preinit_array", ".ctors"];
+    ELF_SECTIONS.iter().any(|elf_section| {
+        name.strip_prefix(elf_section)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    }) || name == "__mod_init_func"
+        || name.starts_with(".CRT$XC")
+        || name.starts_with(".CRT$XI")
+}
+
+/// Returns whether `section` contains code.
+fn is_executable_section<'data, R: ReadRef<'data>>(
+    section: &object::Section<'data, '_, R>,
+) -> bool {
+    // Sections in a separate debug file have no contents, so their kind doesn't say that they
+    // contain code, but their flags still do.
+    section.kind() == SectionKind::Text
+        || matches!(section.flags(), SectionFlags::Elf { sh_flags }
+            if sh_flags & u64::from(object::elf::SHF_EXECINSTR) != 0)
+}
+
+/// Returns whether `rel` is for the target of a call or jump instruction, as opposed to for taking
+/// the address of the target.
+fn is_branch(architecture: Architecture, rel: &object::Relocation) -> bool {
+    rel.kind() == RelocationKind::PltRelative
+        || matches!(
+            rel.encoding(),
+            RelocationEncoding::X86Branch
+                | RelocationEncoding::AArch64Call
+                | RelocationEncoding::LoongArchBranch
+        )
+        || is_branch_relocation_type(architecture, rel.flags())
+}
+
+/// Returns whether relocations with `flags` are for calls or jumps, for relocation types that the
+/// object crate doesn't give a kind or encoding for.
+fn is_branch_relocation_type(architecture: Architecture, flags: RelocationFlags) -> bool {
+    let RelocationFlags::Elf { r_type } = flags else {
+        return false;
+    };
+    match architecture {
+        // A `b`, generally a tail call.
+        Architecture::Aarch64 => r_type == object::elf::R_AARCH64_JUMP26,
+        Architecture::Riscv32 | Architecture::Riscv64 => matches!(
+            r_type,
+            object::elf::R_RISCV_CALL
+                | object::elf::R_RISCV_CALL_PLT
+                | object::elf::R_RISCV_JAL
+                | object::elf::R_RISCV_RVC_JUMP
+        ),
+        _ => false,
+    }
+}
+
+/// Returns whether relocations with `flags` refer to something that the code uses. RISC-V has
+/// relocations that refer to other things. An address is loaded with a pair of instructions, the
+/// first of which has a relocation for the high bits of the target. The relocation for the low bits
+/// refers to a label on the first instruction, not to the target. Label differences, e.g. in jump
+/// tables, have a relocation that adds one label and one that subtracts the other. If we followed
+/// these, code would appear to reference itself.
+fn is_reference(architecture: Architecture, flags: RelocationFlags) -> bool {
+    let RelocationFlags::Elf { r_type } = flags else {
+        return true;
     };
-    Ok(data)
+    !matches!(architecture, Architecture::Riscv32 | Architecture::Riscv64)
+        || !matches!(
+            r_type,
+            object::elf::R_RISCV_PCREL_LO12_I
+                | object::elf::R_RISCV_PCREL_LO12_S
+                | object::elf::R_RISCV_SUB6
+                | object::elf::R_RISCV_SUB8
+                | object::elf::R_RISCV_SUB16
+                | object::elf::R_RISCV_SUB32
+                | object::elf::R_RISCV_SUB64
+                | object::elf::R_RISCV_SUB_ULEB128
+        )
+}
+
+/// Returns whether `bytes` is LLVM bitcode, either raw or in a bitcode wrapper. Objects built with
+/// linker-plugin LTO contain bitcode rather than machine code.
+fn is_llvm_bitcode(bytes: &[u8]) -> bool {
+    const RAW_MAGIC: &[u8] = b"BC\xc0\xde";
+    const WRAPPER_MAGIC: &[u8] = &[0xde, 0xc0, 0x17, 0x0b];
+    bytes.starts_with(RAW_MAGIC) || bytes.starts_with(WRAPPER_MAGIC)
+}
+
+/// Returns whether `data` is an object file in a format that we can parse, as opposed to some other
+/// artifact, e.g. an `.rmeta` file.
+fn is_object_file<'data>(data: impl ReadRef<'data>) -> bool {
+    matches!(
+        object::FileKind::parse(data),
+        Ok(object::FileKind::Elf32
+            | object::FileKind::Elf64
+            | object::FileKind::MachO32
+            | object::FileKind::MachO64
+            | object::FileKind::Coff
+            | object::FileKind::CoffBig
+            | object::FileKind::Pe32
+            | object::FileKind::Pe64
+            | object::FileKind::Xcoff32
+            | object::FileKind::Xcoff64)
+    )
+}
+
+/// Returns the files for crates in `paths` for which none of the files contained any object
+/// files. A crate can have several files, e.g. `libfoo-1234abcd.rlib` and `libfoo-1234abcd.rmeta`.
+/// Files other than `.rlib` and `.rmeta` files, e.g. C static libraries, aren't checked, since
+/// they can legitimately be empty.
+fn crates_without_objects(paths: &[PathBuf], files_without_objects: &[PathBuf]) -> Vec<PathBuf> {
+    let without_objects: FxHashSet<&Path> =
+        files_without_objects.iter().map(PathBuf::as_path).collect();
+    let mut crates: BTreeMap<PathBuf, (bool, Vec<&Path>)> = BTreeMap::new();
+    for path in paths {
+        if !path
+            .extension()
+            .is_some_and(|extension| extension == "rlib" || extension == "rmeta")
+        {
+            continue;
+        }
+        let (has_objects, crate_paths) = crates.entry(path.with_extension("")).or_default();
+        *has_objects |= !without_objects.contains(path.as_path());
+        crate_paths.push(path);
+    }
+    crates
+        .into_values()
+        .filter(|(has_objects, _)| !has_objects)
+        .flat_map(|(_, crate_paths)| crate_paths.into_iter().map(Path::to_owned))
+        .collect()
 }
 
 impl Filetype {
@@ -824,7 +3697,7 @@ impl Filetype {
         let Some(extension) = filename.extension() else {
             return Filetype::Other;
         };
-        if extension == "rlib" || extension == ".a" {
+        if extension == "rlib" || extension == "a" {
             Filetype::Archive
         } else {
             Filetype::Other
@@ -847,6 +3720,84 @@ pub(crate) struct ApiUsageGroupKey {
     source_location: SourceLocation,
 }
 
+impl CachedObject {
+    /// Creates a cache entry from what we found in `obj`. Returns `None` if any of the locations
+    /// can't be expressed relative to a symbol from the object, which shouldn't happen.
+    fn from_results<'data, R: ReadRef<'data>>(
+        obj: &object::File<'data, R>,
+        bin: &BinInfo,
+        api_usages: &FxHashMap<ApiUsageGroupKey, Vec<SingleApiUsage>>,
+        group_api_usages: &FxHashMap<
+            Symbol<'static>,
+            FxHashMap<ApiUsageGroupKey, Vec<SingleApiUsage>>,
+        >,
+        references: &[(BinLocation, u64)],
+        calls: &FxHashSet<(u64, u64)>,
+        roots: &FxHashSet<u64>,
+    ) -> Option<Self> {
+        let mut symbols_by_address = FxHashMap::default();
+        for symbol in obj.symbols() {
+            let symbol = Symbol::borrowed(symbol.name_bytes().unwrap_or_default());
+            if let Some(address) = bin.symbol_addresses.get(&symbol) {
+                symbols_by_address.entry(*address).or_insert(symbol);
+            }
+        }
+        let cached_usage = |single: &SingleApiUsage| {
+            let usage = &single.usage;
+            Some(CachedUsage {
+                pkg_id: single.pkg_id.clone(),
+                api: single.api.clone(),
+                location: RelativeLocation::from_bin_location(
+                    usage.bin_location,
+                    &symbols_by_address,
+                )?,
+                source_location: usage.source_location.clone(),
+                outer_location: usage.outer_location.clone(),
+                from: usage.from.clone(),
+                to: usage.to.clone(),
+                to_name: usage.to_name.clone(),
+                to_source: usage.to_source.clone(),
+                kind: usage.kind,
+                origin: usage.origin.clone(),
+            })
+        };
+        let mut entry = CachedObject {
+            usages: api_usages
+                .values()
+                .flatten()
+                .map(cached_usage)
+                .collect::<Option<_>>()?,
+            ..CachedObject::default()
+        };
+        for (group, usages) in group_api_usages {
+            entry.group_usages.push(CachedGroupUsages {
+                group: group.clone(),
+                usages: usages
+                    .values()
+                    .flatten()
+                    .map(cached_usage)
+                    .collect::<Option<_>>()?,
+            });
+        }
+        for (bin_location, target_address) in references {
+            entry.references.push(CachedReference {
+                location: RelativeLocation::from_bin_location(*bin_location, &symbols_by_address)?,
+                target: symbols_by_address.get(target_address)?.to_heap(),
+            });
+        }
+        for (from, to) in calls {
+            entry.calls.push(CachedCall {
+                from: symbols_by_address.get(from)?.to_heap(),
+                to: symbols_by_address.get(to)?.to_heap(),
+            });
+        }
+        for root in roots {
+            entry.roots.push(symbols_by_address.get(root)?.to_heap());
+        }
+        Some(entry)
+    }
+}
+
 impl SingleApiUsage {
     fn group_key(&self) -> ApiUsageGroupKey {
         ApiUsageGroupKey {
@@ -859,6 +3810,58 @@ impl SingleApiUsage {
     }
 }
 
+/// How an API usage was found and which packages it could have been attributed to. Unlike
+/// `UsageDebugData`, this is always recorded, since it's included in facts so that external tools
+/// can check our reasoning.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct UsageOrigin {
+    /// The object file containing the relocation that produced the reference. For an archive, this
+    /// is the archive itself. `None` if the usage wasn't found via a relocation, e.g. it came from
+    /// debug info about inlined functions or from disassembly.
+    pub(crate) object_file: Option<PathBuf>,
+
+    /// The member of the archive `object_file` containing the relocation.
+    pub(crate) archive_member: Option<PathBuf>,
+
+    /// The section containing the relocation.
+    pub(crate) section: Option<String>,
+
+    /// The offset of the relocation within `section`.
+    pub(crate) relocation_offset: Option<u64>,
+
+    /// The name of the DWARF compilation unit containing the code that made the reference. Only
+    /// filled in if facts are being consumed, since looking it up isn't free. See
+    /// `ApiUsageCollector::add_compilation_units`.
+    pub(crate) compilation_unit: Option<PathBuf>,
+
+    /// The packages that the source file of the reference belongs to. The usage is attributed to
+    /// each of these, other than any that the target belongs to.
+    pub(crate) package_candidates: Vec<PackageId>,
+}
+
+impl UsageOrigin {
+    fn new(relocation: Option<RelocationSite>, package_candidates: &[PackageId]) -> Self {
+        Self {
+            object_file: relocation.map(|site| site.object_file.outer.clone()),
+            archive_member: relocation.and_then(|site| site.object_file.inner.clone()),
+            section: relocation.map(|site| site.section.to_owned()),
+            relocation_offset: relocation.map(|site| site.offset),
+            compilation_unit: None,
+            package_candidates: package_candidates.to_vec(),
+        }
+    }
+}
+
+/// A relocation from which we found references.
+#[derive(Clone, Copy)]
+struct RelocationSite<'a> {
+    kind: RelocationKind,
+    object_file: &'a ObjectFilePath,
+    section: &'a str,
+    /// The offset of the relocation within `section`.
+    offset: u64,
+}
+
 /// Additional information that might be useful for debugging. Only available when --debug is
 /// passed.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -881,13 +3884,14 @@ pub(crate) struct InlinedDebugData {
 }
 
 impl InlinedDebugData {
-    fn from_offset(
+    fn from_offset<'input>(
         low_pc: Option<u64>,
-        ctx: &addr2line::Context<EndianSlice<LittleEndian>>,
+        ctx: &addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
     ) -> Result<InlinedDebugData> {
         let mut frames = Vec::new();
         if let Some(offset) = low_pc {
-            let mut frame_iter = ctx.find_frames(offset).skip_all_loads()?;
+            let mut frame_iter = split_dwarf.resolve(ctx.find_frames(offset))?;
             while let Some(frame) = frame_iter.next()? {
                 if let Some(function) = frame.function.as_ref() {
                     frames.push(Symbol::borrowed(function.name.slice()).to_string());
@@ -897,3 +3901,903 @@ impl InlinedDebugData {
         Ok(InlinedDebugData { frames, low_pc })
     }
 }
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::Read { path, .. } => write!(f, "Failed to read `{}`", path.display()),
+            ScanError::CorruptBinary { path, .. } => {
+                write!(f, "Failed to parse {}", path.display())
+            }
+            ScanError::DebugInfo { path, .. } => write!(
+                f,
+                "Failed while processing debug info for `{}`",
+                path.display()
+            ),
+            ScanError::CorruptObject { path, .. } => {
+                write!(f, "Failed to process `{}`", path.display())
+            }
+            ScanError::Other(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Read { source, .. } => Some(source),
+            ScanError::CorruptBinary { source, .. }
+            | ScanError::DebugInfo { source, .. }
+            | ScanError::CorruptObject { source, .. } => Some(source.as_ref()),
+            ScanError::Other(error) => error.source(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiUsageCollector;
+    use super::BinInfo;
+    use super::CachedObject;
+    use super::NameSource;
+    use super::ScanCache;
+    use super::ScanError;
+    use super::ScanOutputs;
+    use super::ScanSettings;
+    use super::SingleApiUsage;
+    use super::SymbolBinding;
+    use super::SymbolInfo;
+    use super::SymbolVersion;
+    use super::UsageOrigin;
+    use super::crate_name_for_object;
+    use super::crates_without_objects;
+    use super::enclosing_symbol_index;
+    use super::is_branch_relocation_type;
+    use super::is_init_section;
+    use super::is_llvm_bitcode;
+    use super::is_reference;
+    use super::native_object_pkg_id;
+    use super::object_file_path::ObjectFilePath;
+    use super::section_data;
+    use super::split_symbol_version;
+    use crate::Args;
+    use crate::checker::ApiUsage;
+    use crate::checker::BinLocation;
+    use crate::checker::Checker;
+    use crate::checker::ReferenceKind;
+    use crate::config::ApiName;
+    use crate::config::permissions::PermissionScope;
+    use crate::crate_index::CrateIndex;
+    use crate::crate_index::CrateSel;
+    use crate::crate_index::testing::pkg_id;
+    use crate::link_info::testing::link_info;
+    use crate::location::SourceLocation;
+    use crate::names::SymbolOrDebugName;
+    use crate::problem::Problem;
+    use crate::symbol::Symbol;
+    use crate::timing::TimingCollector;
+    use crate::tmpdir::TempDir;
+    use crate::vfs::testing::MemoryFs;
+    use object::Architecture;
+    use object::Object;
+    use object::ObjectSection;
+    use object::RelocationFlags;
+    use rustc_hash::FxHashMap;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    const BITCODE: &[u8] = b"BC\xc0\xde bitcode";
+
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = ar::Builder::new(Vec::new());
+        for (name, data) in members {
+            builder
+                .append(
+                    &ar::Header::new(name.as_bytes().to_vec(), data.len() as u64),
+                    *data,
+                )
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn bin_info() -> BinInfo<'static> {
+        BinInfo {
+            filename: Arc::from(Path::new("/bin/foo")),
+            crate_sel: CrateSel::primary(pkg_id("foo")),
+            symbol_addresses: Default::default(),
+            symbol_has_no_apis: Default::default(),
+            symbol_debug_info: Default::default(),
+            weak_symbols: Default::default(),
+            entry_points: Vec::new(),
+            executable_ranges: Vec::new(),
+            symbol_only: false,
+            foreign_imports: Default::default(),
+        }
+    }
+
+    /// Returns the paths of the bitcode objects found in `paths`. Since bitcode is set aside
+    /// without being parsed, this lets us check which objects we found in each kind of file.
+    fn bitcode_objects(fs: MemoryFs, paths: &[&str]) -> anyhow::Result<Vec<String>> {
+        let mut checker = Checker::new(
+            Arc::new(TempDir::new(None).unwrap()),
+            PathBuf::default(),
+            Arc::new(Args::default()),
+            Arc::from(Path::new("")),
+            None,
+            Arc::new(CrateIndex::default()),
+            PathBuf::default(),
+        );
+        checker.vfs = Arc::new(fs);
+        let bin = bin_info();
+        let ctx = addr2line::Context::from_dwarf(gimli::Dwarf::default())?;
+        let scan = |max_buffered_object_size| -> anyhow::Result<Vec<String>> {
+            let mut collector = ApiUsageCollector::new(
+                &bin,
+                None,
+                false,
+                false,
+                false,
+                TimingCollector::new(false),
+            );
+            collector.max_buffered_object_size = max_buffered_object_size;
+            for path in paths {
+                collector.process_file(Path::new(path), &checker, &ctx, &Default::default())?;
+            }
+            Ok(collector
+                .outputs
+                .bitcode_objects
+                .iter()
+                .map(|path| path.to_string())
+                .collect())
+        };
+        let result = scan(super::MAX_BUFFERED_OBJECT_SIZE);
+        // Parsing objects in place should find the same objects as reading them all at once.
+        assert_eq!(
+            scan(0).map_err(|error| error.to_string()),
+            result
+                .as_ref()
+                .map(Clone::clone)
+                .map_err(|error| error.to_string())
+        );
+        result
+    }
+
+    #[test]
+    fn archive_layouts() {
+        let mut fs = MemoryFs::default();
+        fs.add(
+            "/build/libfoo.rlib",
+            archive(&[("a.o", BITCODE), ("b.o", BITCODE)]),
+        );
+        fs.add("/build/empty.a", archive(&[]));
+        fs.add("/build/plain.o", BITCODE);
+        fs.add(
+            "/build/libthin.a",
+            b"!<thin>\n\
+            //                                              10        `\n\
+            /abs/d.o/\n\
+            c.o/            0           0     0     644     4         `\n\
+            /0              0           0     0     644     4         `\n"
+                .to_vec(),
+        );
+        fs.add("/build/c.o", BITCODE);
+        fs.add("/abs/d.o", BITCODE);
+        assert_eq!(
+            bitcode_objects(
+                fs,
+                &[
+                    "/build/libfoo.rlib",
+                    "/build/empty.a",
+                    "/build/plain.o",
+                    "/build/libthin.a"
+                ]
+            )
+            .unwrap(),
+            [
+                "/build/libfoo.rlib[a.o]",
+                "/build/libfoo.rlib[b.o]",
+                "/build/plain.o",
+                "/build/libthin.a[c.o]",
+                "/build/libthin.a[/abs/d.o]",
+            ]
+        );
+    }
+
+    #[test]
+    fn llvm_bitcode_magic() {
+        assert!(is_llvm_bitcode(BITCODE));
+        assert!(is_llvm_bitcode(b"\xde\xc0\x17\x0b\0\0\0\0"));
+        assert!(!is_llvm_bitcode(b"\x7fELF\x02\x01\x01\0"));
+        assert!(!is_llvm_bitcode(b"BC"));
+    }
+
+    #[test]
+    fn non_object_artifacts() {
+        const RMETA: &[u8] = b"rust\0\0\0\x08metadata";
+        let mut fs = MemoryFs::default();
+        fs.add("/build/libfoo-1234abcd.rmeta", RMETA.to_vec());
+        fs.add(
+            "/build/libbar-1234abcd.rlib",
+            archive(&[("lib.rmeta", RMETA), ("bar.o", BITCODE)]),
+        );
+        let mut checker = crate::checker::testing::checker_for_testing();
+        checker.vfs = Arc::new(fs);
+        let bin = bin_info();
+        let ctx = addr2line::Context::from_dwarf(gimli::Dwarf::default()).unwrap();
+        let mut collector =
+            ApiUsageCollector::new(&bin, None, false, false, false, TimingCollector::new(false));
+        let paths = [
+            PathBuf::from("/build/libfoo-1234abcd.rmeta"),
+            PathBuf::from("/build/libbar-1234abcd.rlib"),
+        ];
+        for path in &paths {
+            collector
+                .process_file(path, &checker, &ctx, &Default::default())
+                .unwrap();
+        }
+        assert_eq!(
+            collector.outputs.bitcode_objects,
+            [ObjectFilePath::in_archive(
+                Path::new("/build/libbar-1234abcd.rlib"),
+                b"bar.o"
+            )]
+        );
+        assert_eq!(collector.outputs.files_without_objects, paths[..1]);
+        assert_eq!(
+            crates_without_objects(&paths, &collector.outputs.files_without_objects),
+            paths[..1]
+        );
+    }
+
+    #[test]
+    fn crates_missing_objects() {
+        let paths: Vec<PathBuf> = [
+            "/t/deps/libfoo-1234abcd.rlib",
+            "/t/deps/libfoo-1234abcd.rmeta",
+            "/t/deps/libbar-1234abcd.rmeta",
+            "/t/build/out/libempty.a",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        // A crate's `.rmeta` doesn't matter if its `.rlib` has objects.
+        assert!(crates_without_objects(&paths, &[paths[1].clone()]).is_empty());
+        assert_eq!(
+            crates_without_objects(&paths, &paths[1..]),
+            [Path::new("/t/deps/libbar-1234abcd.rmeta")]
+        );
+        assert_eq!(
+            crates_without_objects(&paths, &paths),
+            [
+                Path::new("/t/deps/libbar-1234abcd.rmeta"),
+                Path::new("/t/deps/libfoo-1234abcd.rlib"),
+                Path::new("/t/deps/libfoo-1234abcd.rmeta"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unusual_member_names() {
+        // Archives written by foreign build systems can have long member names, which GNU archives
+        // store in a separate table, and names that aren't valid UTF-8.
+        let long_name = format!("{}.o", "x".repeat(300));
+        let names = vec![long_name.as_bytes().to_vec(), b"caf\xe9.o".to_vec()];
+        let mut builder = ar::GnuBuilder::new(Vec::new(), names.clone());
+        for name in names {
+            builder
+                .append(&ar::Header::new(name, BITCODE.len() as u64), BITCODE)
+                .unwrap();
+        }
+        let mut fs = MemoryFs::default();
+        fs.add("/build/libc.a", builder.into_inner().unwrap());
+        assert_eq!(
+            bitcode_objects(fs, &["/build/libc.a"]).unwrap(),
+            [
+                format!("/build/libc.a[{long_name}]"),
+                "/build/libc.a[caf\u{fffd}.o]".to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_thin_archive_member() {
+        let mut fs = MemoryFs::default();
+        fs.add(
+            "/build/libthin.a",
+            b"!<thin>\nc.o/            0           0     0     644     4         `\n".to_vec(),
+        );
+        let error = bitcode_objects(fs, &["/build/libthin.a"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to read `/build/c.o`, referenced by thin archive `/build/libthin.a`"
+        );
+    }
+
+    #[test]
+    fn scan_error_kinds() {
+        let mut fs = MemoryFs::default();
+        fs.add("/bin/garbage", b"not a binary".to_vec());
+        let mut checker = Checker::new(
+            Arc::new(TempDir::new(None).unwrap()),
+            PathBuf::default(),
+            Arc::new(Args::default()),
+            Arc::from(Path::new("")),
+            None,
+            Arc::new(CrateIndex::default()),
+            PathBuf::default(),
+        );
+        checker.vfs = Arc::new(fs);
+        let mut scan = |output_file| {
+            let link_info = link_info(CrateSel::primary(pkg_id("foo")), output_file);
+            super::scan_objects(&[], &link_info, &mut checker)
+                .err()
+                .unwrap()
+        };
+        let ScanError::Read { source, .. } = scan("/bin/missing") else {
+            panic!("Expected read error");
+        };
+        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        let error = scan("/bin/garbage");
+        assert!(
+            matches!(error, ScanError::CorruptBinary { .. }),
+            "Unexpected error: {error:#}"
+        );
+
+        // Errors keep their kind when passed up via anyhow with extra context.
+        let error = anyhow::Error::from(error).context("Failed to check binary");
+        assert!(matches!(
+            error.downcast_ref::<ScanError>(),
+            Some(ScanError::CorruptBinary { .. })
+        ));
+    }
+
+    #[test]
+    fn object_crate_names() {
+        let in_archive = |archive: &str, member: &str| ObjectFilePath {
+            outer: PathBuf::from(archive),
+            inner: Some(PathBuf::from(member)),
+        };
+        let path = in_archive(
+            "/t/deps/libfoo_bar-1234abcd.rlib",
+            "foo_bar-1234abcd.cgu.0.rcgu.o",
+        );
+        assert_eq!(crate_name_for_object(&path), Some("foo_bar"));
+        let path = ObjectFilePath::non_archive(Path::new("/t/deps/app-5678.app.cgu.0.rcgu.o"));
+        assert_eq!(crate_name_for_object(&path), Some("app"));
+        assert_eq!(
+            crate_name_for_object(&in_archive("/t/out/z.a", "z.o")),
+            None
+        );
+    }
+
+    #[test]
+    fn symbol_versions() {
+        assert_eq!(
+            split_symbol_version(b"memcpy@GLIBC_2.2.5"),
+            (&b"memcpy"[..], SymbolVersion::NonDefault)
+        );
+        assert_eq!(
+            split_symbol_version(b"memcpy@@GLIBC_2.14"),
+            (&b"memcpy"[..], SymbolVersion::Default)
+        );
+        assert_eq!(
+            split_symbol_version(b"memcpy"),
+            (&b"memcpy"[..], SymbolVersion::None)
+        );
+        assert_eq!(
+            split_symbol_version(b"?foo@@YAXXZ"),
+            (&b"?foo@@YAXXZ"[..], SymbolVersion::None)
+        );
+    }
+
+    #[test]
+    fn comdat_groups() {
+        let bin = bin_info();
+        let usage = |object_file: &str| SingleApiUsage {
+            pkg_id: pkg_id("foo"),
+            scope: PermissionScope::All,
+            api: ApiName::from("fs"),
+            usage: ApiUsage {
+                bin_location: BinLocation {
+                    address: 0x1000,
+                    symbol_start: 0x1000,
+                },
+                bin_path: bin.filename.clone(),
+                permission_scope: PermissionScope::All,
+                source_location: SourceLocation::new(Path::new("/ws/foo/inline.h"), 3, None),
+                outer_location: None,
+                from: SymbolOrDebugName::Symbol(Symbol::borrowed(b"_Z6inlinev").to_heap()),
+                to: SymbolOrDebugName::Symbol(Symbol::borrowed(b"fopen").to_heap()),
+                to_name: crate::names::split_simple("extern::fopen"),
+                to_source: NameSource::Symbol(Symbol::borrowed(b"fopen").to_heap()),
+                kind: ReferenceKind::Call,
+                origin: UsageOrigin {
+                    object_file: Some(object_file.into()),
+                    ..UsageOrigin::default()
+                },
+                debug_data: None,
+                callers: Vec::new(),
+                inline_chain: Vec::new(),
+            },
+        };
+        // Collectors for two objects that both contain the group for `inline`.
+        let collector = |object_file: &str| {
+            let mut collector = ApiUsageCollector::new(
+                &bin,
+                None,
+                false,
+                false,
+                false,
+                TimingCollector::new(false),
+            );
+            let group = Symbol::borrowed(b"_Z6inlinev").to_heap();
+            collector.groups.insert(group.clone());
+            collector.process_section_in_group(Some(&group), |collector| {
+                let usage = usage(object_file);
+                collector
+                    .new_api_usages
+                    .entry(usage.group_key())
+                    .or_default()
+                    .push(usage);
+                Ok(())
+            })?;
+            anyhow::Ok(collector)
+        };
+        let mut merged =
+            ApiUsageCollector::new(&bin, None, false, false, false, TimingCollector::new(false));
+        merged.merge(collector("a.o").unwrap());
+        merged.merge(collector("b.o").unwrap());
+        let usages: Vec<_> = merged.new_api_usages.values().flatten().collect();
+        assert_eq!(usages.len(), 1);
+        assert_eq!(
+            usages[0].usage.origin.object_file.as_deref(),
+            Some(Path::new("a.o"))
+        );
+    }
+
+    /// Emits a reference from package `foo` to each of `targets` via the pseudo-API `api`. Returns
+    /// the outputs, which shouldn't contain any ordinary API usages.
+    fn scan_pseudo_api_usages(api: &ApiName, targets: &[&str]) -> ScanOutputs {
+        let bin = bin_info();
+        let mut collector =
+            ApiUsageCollector::new(&bin, None, false, false, false, TimingCollector::new(false));
+        for to in targets {
+            let usage = SingleApiUsage {
+                pkg_id: pkg_id("foo"),
+                scope: PermissionScope::All,
+                api: api.clone(),
+                usage: ApiUsage {
+                    bin_location: BinLocation {
+                        address: 0x1000,
+                        symbol_start: 0x1000,
+                    },
+                    bin_path: bin.filename.clone(),
+                    permission_scope: PermissionScope::All,
+                    source_location: SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 3, None),
+                    outer_location: None,
+                    from: SymbolOrDebugName::Symbol(Symbol::borrowed(b"foo_clean").to_heap()),
+                    to: SymbolOrDebugName::Symbol(Symbol::borrowed(to.as_bytes()).to_heap()),
+                    to_name: crate::names::split_simple(to),
+                    to_source: NameSource::Symbol(Symbol::borrowed(to.as_bytes()).to_heap()),
+                    kind: ReferenceKind::Call,
+                    origin: UsageOrigin::default(),
+                    debug_data: None,
+                    callers: Vec::new(),
+                    inline_chain: Vec::new(),
+                },
+            };
+            collector
+                .new_api_usages
+                .entry(usage.group_key())
+                .or_default()
+                .push(usage);
+        }
+        collector.emit_api_usages(crate::config::ReportMode::All);
+        assert!(collector.outputs.api_usages.is_empty());
+        collector.outputs
+    }
+
+    #[test]
+    fn denied_references() {
+        let api = crate::deny_list::pseudo_apis(&crate::config::DenyConfig {
+            symbols: vec!["std::fs::remove_dir_all".to_owned()],
+        })
+        .next()
+        .unwrap()
+        .0;
+        let scan = |targets: &[&str]| scan_pseudo_api_usages(&api, targets);
+        // Returns the names of the references to denied items that remain from `targets`.
+        let denied = |targets: &[&str]| {
+            scan(targets)
+                .denied_usages
+                .values()
+                .flat_map(|usages| &usages.usages)
+                .map(|usage| usage.to_name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            denied(&["std::fs::remove_dir_all"]),
+            ["std::fs::remove_dir_all"]
+        );
+        assert!(denied(&["std::fs::remove_dir_all::inner"]).is_empty());
+        assert_eq!(
+            denied(&["std::fs::remove_dir_all::inner", "std::fs::remove_dir_all"]),
+            ["std::fs::remove_dir_all"]
+        );
+
+        let outputs = scan(&["std::fs::remove_dir_all"]);
+        let mut checker = crate::checker::testing::checker_for_testing();
+        let problems = outputs.problems(&mut checker).unwrap().take();
+        assert!(matches!(problems[..], [Problem::DeniedSymbol(_)]));
+        checker.update_config(
+            crate::config::testing::parse(
+                r#"
+                    [pkg.foo]
+                    allow_denied_symbols = ["std::fs::remove_dir_all"]
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(outputs.problems(&mut checker).unwrap().is_empty());
+    }
+
+    #[test]
+    fn disallowed_alloc() {
+        let api = crate::alloc_audit::pseudo_apis().next().unwrap().0;
+        let outputs = scan_pseudo_api_usages(&api, &["__rustc::__rust_alloc"]);
+        assert!(outputs.denied_usages.is_empty());
+        let mut checker = crate::checker::testing::checker_for_testing();
+        assert!(outputs.problems(&mut checker).unwrap().is_empty());
+        checker.update_config(
+            crate::config::testing::parse(
+                r#"
+                    [pkg.foo]
+                    no_alloc = true
+                "#,
+            )
+            .unwrap(),
+        );
+        let problems = outputs.problems(&mut checker).unwrap().take();
+        assert!(matches!(problems[..], [Problem::DisallowedAlloc(_)]));
+    }
+
+    #[test]
+    fn scan_cache_with_comdat_groups() {
+        // Almost every object that rustc produces has a COMDAT group for
+        // `DW.ref.rust_eh_personality`, so an ordinary rlib is enough.
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("lib.rs"),
+            "pub fn names() -> Vec<String> { vec![String::from(\"a\")] }",
+        )
+        .unwrap();
+        let rlib = dir.path().join("libnames.rlib");
+        let status = std::process::Command::new("rustc")
+            .current_dir(dir.path())
+            .args([
+                "--crate-type=rlib",
+                "--edition=2021",
+                "-Cdebuginfo=2",
+                "-Ccodegen-units=1",
+                "lib.rs",
+                "-o",
+            ])
+            .arg(&rlib)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let checker = crate::checker::testing::checker_for_testing();
+        let mut bin = bin_info();
+        // A symbol that isn't in the object, so doesn't affect the cache key, which we add as a
+        // root to the cache entry so that we can tell when the entry is used.
+        let marker = Symbol::borrowed(b"marker").to_heap();
+        bin.symbol_addresses.insert(marker.clone(), 0x42);
+        let cache_dir = dir.path().join("cache");
+        let cache = ScanCache::new(cache_dir.clone(), &ScanSettings::default()).unwrap();
+        let ctx = addr2line::Context::from_dwarf(gimli::Dwarf::default()).unwrap();
+        let scan = || {
+            let mut collector = ApiUsageCollector::new(
+                &bin,
+                Some(&cache),
+                false,
+                false,
+                false,
+                TimingCollector::new(false),
+            );
+            collector
+                .process_file(&rlib, &checker, &ctx, &Default::default())
+                .unwrap();
+            collector
+        };
+        let group = Symbol::borrowed(b"DW.ref.rust_eh_personality").to_heap();
+        let first = scan();
+        assert!(first.groups.contains(&group));
+        assert!(!first.roots.contains(&0x42));
+
+        // There's an entry for the code and one for the object that holds the rlib's metadata.
+        for path in std::fs::read_dir(&cache_dir).unwrap() {
+            let path = path.unwrap().path();
+            let mut entry: CachedObject =
+                serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            entry.roots.push(marker.clone());
+            std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        }
+
+        let second = scan();
+        assert!(second.roots.contains(&0x42));
+        // The group is still recorded, so that later copies of it get discarded.
+        assert!(second.groups.contains(&group));
+    }
+
+    #[test]
+    fn native_objects() {
+        let mut checker = crate::checker::testing::checker_for_testing();
+        checker.crate_index = crate::crate_index::testing::index_with_package_names(&["foo_sys"]);
+        let c_symbols = || [Symbol::borrowed(b"foo_init")].into_iter();
+        // Rustc bundled the build script's static library into the rlib.
+        let member = ObjectFilePath::in_archive(
+            Path::new("/t/debug/deps/libfoo_sys-1234abcd.rlib"),
+            b"shim.o",
+        );
+        assert_eq!(
+            native_object_pkg_id(&member, c_symbols(), &checker),
+            Some(&pkg_id("foo_sys"))
+        );
+        let rust_symbols = [
+            Symbol::borrowed(b"foo_init"),
+            Symbol::borrowed(b"_ZN7foo_sys4init17h0123456789abcdefE"),
+        ];
+        assert_eq!(
+            native_object_pkg_id(&member, rust_symbols.into_iter(), &checker),
+            None
+        );
+        let object = ObjectFilePath::non_archive(Path::new("/t/debug/deps/shim-1234abcd.o"));
+        assert_eq!(native_object_pkg_id(&object, c_symbols(), &checker), None);
+    }
+
+    #[test]
+    fn relocation_types() {
+        let elf = |r_type| RelocationFlags::Elf { r_type };
+        for architecture in [Architecture::Riscv64, Architecture::Riscv32] {
+            assert!(is_branch_relocation_type(
+                architecture,
+                elf(object::elf::R_RISCV_CALL_PLT)
+            ));
+            assert!(!is_branch_relocation_type(
+                architecture,
+                elf(object::elf::R_RISCV_PCREL_HI20)
+            ));
+            assert!(is_reference(
+                architecture,
+                elf(object::elf::R_RISCV_PCREL_HI20)
+            ));
+            assert!(!is_reference(
+                architecture,
+                elf(object::elf::R_RISCV_PCREL_LO12_I)
+            ));
+            assert!(is_reference(architecture, elf(object::elf::R_RISCV_ADD32)));
+            assert!(!is_reference(architecture, elf(object::elf::R_RISCV_SUB32)));
+        }
+        assert!(is_branch_relocation_type(
+            Architecture::Aarch64,
+            elf(object::elf::R_AARCH64_JUMP26)
+        ));
+        assert!(!is_branch_relocation_type(
+            Architecture::Aarch64,
+            elf(object::elf::R_AARCH64_ADR_PREL_PG_HI21)
+        ));
+        // Relocation types are numbered separately for each architecture.
+        assert!(!is_branch_relocation_type(
+            Architecture::X86_64,
+            elf(object::elf::R_RISCV_CALL_PLT)
+        ));
+        assert!(is_reference(
+            Architecture::X86_64,
+            elf(object::elf::R_RISCV_PCREL_LO12_I)
+        ));
+    }
+
+    #[test]
+    fn init_section_targets() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // `init` isn't exported, so the pointer to it in `.init_array` is written as the address of
+        // `.text` plus the offset of `init`, which isn't the first function.
+        std::fs::write(
+            dir.path().join("init.c"),
+            indoc::indoc! {"
+                static int counter;
+                static void helper(void) { counter++; }
+                __attribute__((constructor)) static void init(void) { helper(); }
+                int get(void) { return counter; }
+            "},
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.c"),
+            "int get(void);\nint main(void) { return get(); }\n",
+        )
+        .unwrap();
+        let cc = |args: &[&str]| {
+            let status = std::process::Command::new("cc")
+                .current_dir(dir.path())
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        cc(&["-g", "-c", "init.c", "-o", "init.o"]);
+        cc(&["-g", "main.c", "init.o", "-o", "app"]);
+
+        let mut checker = crate::checker::testing::checker_for_testing();
+        checker.crate_index = crate::crate_index::testing::index_with_package_dirs(&[(
+            "foo",
+            dir.path().to_str().unwrap(),
+        )]);
+        // Otherwise everything counts as being in the standard library.
+        checker.sysroot = Arc::from(Path::new("/sysroot"));
+        checker
+            .update_config(crate::config::testing::parse("detect_before_main = true\n").unwrap());
+        let link_info = link_info(
+            CrateSel::primary(pkg_id("foo")),
+            dir.path().join("app").to_str().unwrap(),
+        );
+        let (outputs, _) =
+            super::scan_objects(&[dir.path().join("init.o")], &link_info, &mut checker).unwrap();
+        let from: Vec<String> = outputs
+            .api_usages()
+            .filter(|usages| usages.api_name == ApiName::new(crate::config::BEFORE_MAIN_API))
+            .flat_map(|usages| &usages.usages)
+            .map(|usage| usage.from.to_string())
+            .collect();
+        assert_eq!(from, ["init"]);
+    }
+
+    #[test]
+    fn bitcode_archive_members() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.c"), "int main(void) { return 0; }\n").unwrap();
+        let status = std::process::Command::new("cc")
+            .current_dir(dir.path())
+            .args(["-g", "main.c", "-o", "app"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let lib = dir.path().join("liblto.a");
+        std::fs::write(&lib, archive(&[("lto.o", BITCODE)])).unwrap();
+
+        let mut checker = crate::checker::testing::checker_for_testing();
+        // Otherwise everything counts as being in the standard library.
+        checker.sysroot = Arc::from(Path::new("/sysroot"));
+        let link_info = link_info(
+            CrateSel::primary(pkg_id("foo")),
+            dir.path().join("app").to_str().unwrap(),
+        );
+        let (outputs, _) =
+            super::scan_objects(std::slice::from_ref(&lib), &link_info, &mut checker).unwrap();
+        let problems = outputs.problems(&mut checker).unwrap().take();
+        let [Problem::LtoBitcode(bitcode)] = problems.as_slice() else {
+            panic!("Unexpected problems {problems:?}");
+        };
+        assert_eq!(
+            bitcode.object_paths,
+            [ObjectFilePath::in_archive(&lib, b"lto.o")]
+        );
+    }
+
+    #[test]
+    fn init_sections() {
+        assert!(is_init_section(".init_array"));
+        assert!(is_init_section(".init_array.00099"));
+        assert!(is_init_section(".ctors.65535"));
+        assert!(is_init_section("__mod_init_func"));
+        assert!(is_init_section(".CRT$XCU"));
+        assert!(!is_init_section(".init_arrays"));
+        assert!(!is_init_section(".text.init_array"));
+        assert!(!is_init_section(".fini_array"));
+    }
+
+    #[test]
+    fn enclosing_symbols() {
+        let symbol_info = |name: &'static str, offset, binding| SymbolInfo {
+            symbol: Symbol::borrowed(name.as_bytes()),
+            offset,
+            binding,
+        };
+        // As for a C object compiled without `-ffunction-sections`.
+        let symbols = [
+            symbol_info("foo", 0x10, SymbolBinding::Strong),
+            symbol_info("bar_alias", 0x40, SymbolBinding::Strong),
+            symbol_info("bar", 0x40, SymbolBinding::Weak),
+            symbol_info("baz", 0x80, SymbolBinding::Strong),
+        ];
+        let mut bin_symbols = FxHashMap::default();
+        for name in ["foo", "bar", "bar_alias", "baz"] {
+            bin_symbols.insert(Symbol::borrowed(name.as_bytes()), 0x1000);
+        }
+        let enclosing = |offset, bin_symbols: &FxHashMap<Symbol, u64>| {
+            enclosing_symbol_index(&symbols, offset, bin_symbols)
+        };
+        assert_eq!(enclosing(0x10, &bin_symbols), 0);
+        assert_eq!(enclosing(0x3f, &bin_symbols), 0);
+        assert_eq!(enclosing(0x40, &bin_symbols), 1);
+        assert_eq!(enclosing(0x7f, &bin_symbols), 1);
+        assert_eq!(enclosing(0x200, &bin_symbols), 3);
+        // References from before the first symbol go to the first symbol.
+        assert_eq!(enclosing(0, &bin_symbols), 0);
+        // Where symbols share an offset, we prefer one that the binary has.
+        bin_symbols.remove(&Symbol::borrowed(b"bar_alias"));
+        assert_eq!(enclosing(0x50, &bin_symbols), 2);
+        bin_symbols.remove(&Symbol::borrowed(b"bar"));
+        assert_eq!(enclosing(0x50, &bin_symbols), 1);
+    }
+
+    #[test]
+    fn compressed_debug_sections() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("plain.c");
+        std::fs::write(&source, "int answer(void) { return 42; }\n").unwrap();
+        let object_path = dir.path().join("plain.o");
+        let status = std::process::Command::new("cc")
+            .args(["-g", "-c"])
+            .arg(&source)
+            .arg("-o")
+            .arg(&object_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let debug_info = |bytes: &[u8]| -> anyhow::Result<Vec<u8>> {
+            let obj = object::File::parse(bytes).unwrap();
+            let section = obj.section_by_name(".debug_info").unwrap();
+            Ok(section_data(&section)?.into_owned())
+        };
+        let plain = std::fs::read(&object_path).unwrap();
+        let expected = debug_info(&plain).unwrap();
+        assert!(!expected.is_empty());
+
+        let compress = |format: &str| {
+            let output = dir.path().join(format!("{format}.o"));
+            let status = std::process::Command::new("objcopy")
+                .arg(format!("--compress-debug-sections={format}"))
+                .arg(&object_path)
+                .arg(&output)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            std::fs::read(output).unwrap()
+        };
+        let zlib = compress("zlib");
+        let zstd = compress("zstd");
+        assert_ne!(zlib, plain);
+        assert_ne!(zstd, zlib);
+        assert_eq!(debug_info(&zlib).unwrap(), expected);
+        assert_eq!(debug_info(&zstd).unwrap(), expected);
+
+        // The compression header is at the start of the section's data and starts with the type of
+        // compression. For ELF, `object` rejects types that it doesn't know, rather than returning
+        // `CompressionFormat::Unknown`, but either way we should get an error naming the section.
+        let offset = {
+            let obj = object::File::parse(zlib.as_slice()).unwrap();
+            let section = obj.section_by_name(".debug_info").unwrap();
+            section.file_range().unwrap().0 as usize
+        };
+        let mut unknown = zlib.clone();
+        unknown[offset..offset + 4].copy_from_slice(&99_u32.to_le_bytes());
+        let error = format!("{:#}", debug_info(&unknown).unwrap_err());
+        assert!(error.contains("`.debug_info`"), "{error}");
+        assert!(error.to_lowercase().contains("unsupported"), "{error}");
+
+        // Corrupt the compressed data that follows the 24 byte header.
+        let mut corrupt = zlib;
+        for byte in &mut corrupt[offset + 24..offset + 32] {
+            *byte = !*byte;
+        }
+        let error = debug_info(&corrupt).unwrap_err();
+        assert!(
+            error.to_string().contains("Failed to decompress"),
+            "{error:#}"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::split_dwarf::SplitDwarfSections;
 use crate::checker::BinLocation;
 use crate::location::SourceLocation;
 use anyhow::Context;
@@ -15,6 +16,9 @@ pub(crate) struct Backtracer {
 
     bin_bytes: Vec<u8>,
 
+    /// The path that `bin_bytes` was read from. Used to locate split debug info.
+    bin_path: Option<Arc<Path>>,
+
     sysroot: Arc<Path>,
 }
 
@@ -31,6 +35,7 @@ impl Backtracer {
             sysroot,
             back_references: Default::default(),
             bin_bytes: Default::default(),
+            bin_path: None,
         }
     }
 
@@ -42,7 +47,8 @@ impl Backtracer {
             .push(bin_location);
     }
 
-    pub(crate) fn provide_bin_bytes(&mut self, bin_bytes: Vec<u8>) {
+    pub(crate) fn provide_bin_bytes(&mut self, bin_path: Arc<Path>, bin_bytes: Vec<u8>) {
+        self.bin_path = Some(bin_path);
         self.bin_bytes = bin_bytes;
     }
 
@@ -62,14 +68,19 @@ impl Backtracer {
             )
         })?;
         let owned_dwarf_sections = DwarfSections::load(|id| super::load_section(&obj, id))?;
-        let dwarf = owned_dwarf_sections
-            .borrow(|section| gimli::EndianSlice::new(section, gimli::LittleEndian));
+        let endian = super::endian(&obj);
+        let dwarf = owned_dwarf_sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+        let split_dwarf_sections = match self.bin_path.as_ref() {
+            Some(bin_path) => SplitDwarfSections::load(&dwarf, bin_path)?,
+            None => SplitDwarfSections::default(),
+        };
+        let split_dwarf = split_dwarf_sections.borrow(&dwarf)?;
         let ctx = addr2line::Context::from_dwarf(dwarf)
             .context("Failed in addr2line during backtrace")?;
 
         let mut backtrace: Vec<Frame> = Vec::new();
         for address in addresses {
-            let mut frame_iter = ctx.find_frames(address).skip_all_loads()?;
+            let mut frame_iter = split_dwarf.resolve(ctx.find_frames(address))?;
             let mut first = true;
             while let Some(frame) = frame_iter.next()? {
                 let name = frame
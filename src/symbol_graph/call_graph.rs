@@ -0,0 +1,112 @@
+//! A graph of which functions in the binary call which others. A single `from`/`to` pair often
+//! isn't enough to understand why an API usage exists, so we use this to find a chain of calls
+//! leading to the function containing the usage, starting from a function that isn't called from
+//! elsewhere in the same crate - generally one of the crate's public functions. It's also used to
+//! find which functions are reachable from the binary's entry points, when `ignore_unreachable` is
+//! set.
+
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+use std::collections::VecDeque;
+
+/// Stop searching for a call path after visiting this many functions. Call graphs can be large and
+/// a long path wouldn't be much help anyway.
+const MAX_VISITED: usize = 10_000;
+
+#[derive(Default)]
+pub(super) struct CallGraph {
+    /// For each function address, the addresses of the functions that call it.
+    callers: FxHashMap<u64, Vec<u64>>,
+
+    /// For each function address, the addresses of the functions that it calls.
+    callees: FxHashMap<u64, Vec<u64>>,
+}
+
+impl CallGraph {
+    /// Builds a graph from pairs of the addresses of calling and called functions.
+    pub(super) fn new(calls: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        let mut graph = Self::default();
+        for (caller, callee) in calls {
+            if caller != callee {
+                graph.callers.entry(callee).or_default().push(caller);
+                graph.callees.entry(caller).or_default().push(callee);
+            }
+        }
+        graph
+    }
+
+    /// Returns the shortest chain of calls ending with the function at `address`, that starts from
+    /// a function with no callers for which `in_crate` returns true. Only functions for which
+    /// `in_crate` returns true are included. The result starts with the outermost function and ends
+    /// with `address`.
+    pub(super) fn path_to(&self, address: u64, mut in_crate: impl FnMut(u64) -> bool) -> Vec<u64> {
+        let mut called_by: FxHashMap<u64, u64> = FxHashMap::default();
+        let mut visited = FxHashSet::default();
+        visited.insert(address);
+        let mut queue = VecDeque::from([address]);
+        let mut start = address;
+        while let Some(current) = queue.pop_front() {
+            start = current;
+            let mut has_callers = false;
+            for &caller in self.callers.get(&current).into_iter().flatten() {
+                if !in_crate(caller) {
+                    continue;
+                }
+                has_callers = true;
+                if visited.len() < MAX_VISITED && visited.insert(caller) {
+                    called_by.insert(caller, current);
+                    queue.push_back(caller);
+                }
+            }
+            if !has_callers {
+                break;
+            }
+        }
+        let mut path = vec![start];
+        while let Some(next) = called_by.get(path.last().unwrap()) {
+            path.push(*next);
+        }
+        path
+    }
+
+    /// Returns the addresses of all functions that can be reached from `roots`, including the roots
+    /// themselves.
+    pub(super) fn reachable_from(&self, roots: impl IntoIterator<Item = u64>) -> FxHashSet<u64> {
+        let mut reachable = FxHashSet::default();
+        let mut pending: Vec<u64> = roots.into_iter().collect();
+        while let Some(address) = pending.pop() {
+            if reachable.insert(address) {
+                pending.extend(self.callees.get(&address).into_iter().flatten());
+            }
+        }
+        reachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallGraph;
+
+    #[test]
+    fn shortest_path_from_crate_entry() {
+        // 1 and 2 are entry points. 1 -> 3 -> 4 -> 5 and 2 -> 5. 6 is from another crate.
+        let graph = CallGraph::new([(1, 3), (3, 4), (4, 5), (2, 5), (6, 1), (5, 5), (4, 3)]);
+        let in_crate = |address| address != 6;
+        assert_eq!(graph.path_to(5, in_crate), vec![2, 5]);
+        assert_eq!(graph.path_to(4, in_crate), vec![1, 3, 4]);
+        assert_eq!(graph.path_to(1, in_crate), vec![1]);
+        assert_eq!(graph.path_to(1, |_| true), vec![6, 1]);
+        assert_eq!(graph.path_to(42, in_crate), vec![42]);
+    }
+
+    #[test]
+    fn reachable_functions() {
+        // 1 -> 2 -> 3 -> 1 and 4 -> 5.
+        let graph = CallGraph::new([(1, 2), (2, 3), (3, 1), (4, 5)]);
+        let mut reachable: Vec<u64> = graph.reachable_from([2]).into_iter().collect();
+        reachable.sort();
+        assert_eq!(reachable, vec![1, 2, 3]);
+        assert_eq!(graph.reachable_from([5, 6]).len(), 2);
+        assert!(graph.reachable_from([]).is_empty());
+    }
+}
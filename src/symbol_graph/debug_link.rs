@@ -0,0 +1,159 @@
+//! Support for binaries whose debug info has been moved to a separate file, e.g. with `objcopy
+//! --only-keep-debug`. Such binaries reference their debug file via a build ID and/or a
+//! `.gnu_debuglink` section. We look for the debug file in the same places as GDB does and then,
+//! if enabled, ask debuginfod servers.
+
+use super::ScanError;
+use super::debuginfod;
+use anyhow::Context;
+use anyhow::Result;
+use log::info;
+use object::Object;
+use std::ffi::OsStr;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The global directory in which distributions install separate debug files.
+const DEBUG_FILE_DIRECTORY: &str = "/usr/lib/debug";
+
+/// If the binary in `bin_bytes`, which was read from `bin_path`, has no debug info of its own, but
+/// references a separate debug file, then returns the contents of that file. If `use_debuginfod` is
+/// set and the debug file can't be found locally, then we try to fetch it by build ID.
+pub(crate) fn load_separate_debug_file(
+    bin_bytes: &[u8],
+    bin_path: &Path,
+    use_debuginfod: bool,
+) -> Result<Option<Vec<u8>>> {
+    let obj = object::File::parse(bin_bytes).map_err(|error| ScanError::CorruptBinary {
+        path: bin_path.to_owned(),
+        source: error.into(),
+    })?;
+    if obj.section_by_name(".debug_info").is_some() {
+        return Ok(None);
+    }
+    let build_id = obj.build_id()?;
+    if let Some(build_id) = build_id {
+        let path = build_id_path(Path::new(DEBUG_FILE_DIRECTORY), build_id);
+        if let Ok(bytes) = std::fs::read(&path) {
+            info!("Using debug file `{}`", path.display());
+            return Ok(Some(bytes));
+        }
+    }
+    if let Some(bytes) = load_debuglink_file(&obj, bin_path)? {
+        return Ok(Some(bytes));
+    }
+    if use_debuginfod && let Some(build_id) = build_id {
+        let bytes = debuginfod::fetch_debuginfo(build_id).with_context(|| {
+            format!(
+                "Failed to fetch debug info for `{}` from debuginfod",
+                bin_path.display()
+            )
+        })?;
+        if bytes.is_none() {
+            info!(
+                "No debuginfod server has debug info for build ID {}",
+                debuginfod::hex(build_id)
+            );
+        }
+        return Ok(bytes);
+    }
+    Ok(None)
+}
+
+/// Looks for the file referenced by the `.gnu_debuglink` section of `obj`, if any.
+fn load_debuglink_file(obj: &object::File, bin_path: &Path) -> Result<Option<Vec<u8>>> {
+    let Some((filename, crc)) = obj.gnu_debuglink()? else {
+        return Ok(None);
+    };
+    for path in debuglink_paths(bin_path, Path::new(OsStr::from_bytes(filename))) {
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        if crc32(&bytes) != crc {
+            info!(
+                "Ignoring debug file `{}` due to CRC mismatch",
+                path.display()
+            );
+            continue;
+        }
+        info!("Using debug file `{}`", path.display());
+        return Ok(Some(bytes));
+    }
+    info!(
+        "Debug file `{}` referenced by `{}` not found",
+        String::from_utf8_lossy(filename),
+        bin_path.display()
+    );
+    Ok(None)
+}
+
+fn build_id_path(debug_dir: &Path, build_id: &[u8]) -> PathBuf {
+    let hex = debuginfod::hex(build_id);
+    let (dir, rest) = hex.split_at(2.min(hex.len()));
+    debug_dir
+        .join(".build-id")
+        .join(dir)
+        .join(format!("{rest}.debug"))
+}
+
+/// Returns the locations where we might find the debug file named `filename` for `bin_path`.
+fn debuglink_paths(bin_path: &Path, filename: &Path) -> Vec<PathBuf> {
+    let bin_dir = bin_path.parent().unwrap_or(Path::new(""));
+    let mut paths = vec![
+        bin_dir.join(filename),
+        bin_dir.join(".debug").join(filename),
+    ];
+    if let Ok(relative_dir) = bin_dir.strip_prefix("/") {
+        paths.push(
+            Path::new(DEBUG_FILE_DIRECTORY)
+                .join(relative_dir)
+                .join(filename),
+        );
+    }
+    paths
+}
+
+/// The CRC used by .gnu_debuglink, which is the same as used by zlib.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_id_path;
+    use super::crc32;
+    use super::debuglink_paths;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn check_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn debug_file_paths() {
+        assert_eq!(
+            build_id_path(Path::new("/usr/lib/debug"), &[0xab, 0xcd, 0xef]),
+            Path::new("/usr/lib/debug/.build-id/ab/cdef.debug")
+        );
+        assert_eq!(
+            debuglink_paths(Path::new("/opt/bin/foo"), Path::new("foo.debug")),
+            vec![
+                PathBuf::from("/opt/bin/foo.debug"),
+                PathBuf::from("/opt/bin/.debug/foo.debug"),
+                PathBuf::from("/usr/lib/debug/opt/bin/foo.debug"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! A minimal debuginfod client. debuginfod servers serve debug info keyed by build ID. We use this
+//! when a binary has had its debug info stripped and the debug file can't be found locally. See
+//! https://sourceware.org/elfutils/Debuginfod.html. Rather than embedding an HTTP client, we invoke
+//! `curl`, which needs to be on the PATH. Fetched files are cached using the same layout as the
+//! elfutils client, so that the cache can be shared with other tools such as GDB.
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use log::info;
+use log::warn;
+use object::Object;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Returns the debug info for `build_id`, either from the cache or by fetching it from one of the
+/// servers listed in DEBUGINFOD_URLS. Returns `None` if no server has it.
+pub(crate) fn fetch_debuginfo(build_id: &[u8]) -> Result<Option<Vec<u8>>> {
+    let Some(cache_dir) = cache_dir() else {
+        bail!("Unable to determine debuginfod cache directory. Set DEBUGINFOD_CACHE_PATH");
+    };
+    let urls = std::env::var("DEBUGINFOD_URLS").unwrap_or_default();
+    fetch_with_cache(build_id, &cache_dir, &urls)
+}
+
+/// Returns the debug info for `build_id` from `cache_dir` or else from one of the servers in
+/// `urls`. Files, whether cached or fetched, that turn out to be for some other build ID are
+/// discarded, since we'd otherwise be trusting them to tell us what the binary does.
+fn fetch_with_cache(build_id: &[u8], cache_dir: &Path, urls: &str) -> Result<Option<Vec<u8>>> {
+    let build_id_hex = hex(build_id);
+    let cached_path = cache_dir.join(&build_id_hex).join("debuginfo");
+    if let Ok(bytes) = std::fs::read(&cached_path) {
+        if has_build_id(&bytes, build_id) {
+            info!("Using cached debuginfod file `{}`", cached_path.display());
+            return Ok(Some(bytes));
+        }
+        warn!(
+            "Removing cached debuginfod file `{}`, since it isn't for build ID {build_id_hex}",
+            cached_path.display()
+        );
+        std::fs::remove_file(&cached_path)
+            .with_context(|| format!("Failed to remove `{}`", cached_path.display()))?;
+    }
+    for url in server_urls(urls) {
+        if let Some(bytes) = fetch_from_server(url, build_id, &cached_path)? {
+            return Ok(Some(bytes));
+        }
+    }
+    Ok(None)
+}
+
+fn fetch_from_server(server: &str, build_id: &[u8], cached_path: &Path) -> Result<Option<Vec<u8>>> {
+    let url = debuginfo_url(server, &hex(build_id));
+    let dir = cached_path.parent().unwrap();
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory `{}`", dir.display()))?;
+    // Download to a temporary file so that we never leave a partial file in the cache.
+    let tmp_path = dir.join(format!(".debuginfo.{}.tmp", std::process::id()));
+    info!("Fetching `{url}`");
+    let output = Command::new("curl")
+        .arg("--fail")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--output")
+        .arg(&tmp_path)
+        .arg(&url)
+        .output()
+        .context("Failed to run `curl`, which is required for --debuginfod")?;
+    if !output.status.success() {
+        // Servers respond with 404 if they don't have the file, so this isn't an error.
+        info!(
+            "Failed to fetch `{url}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        let _ = std::fs::remove_file(&tmp_path);
+        return Ok(None);
+    }
+    let bytes = std::fs::read(&tmp_path)
+        .with_context(|| format!("Failed to read `{}`", tmp_path.display()))?;
+    if !has_build_id(&bytes, build_id) {
+        warn!("Ignoring `{url}`, since it isn't for the requested build ID");
+        let _ = std::fs::remove_file(&tmp_path);
+        return Ok(None);
+    }
+    std::fs::rename(&tmp_path, cached_path).with_context(|| {
+        format!(
+            "Failed to move `{}` to `{}`",
+            tmp_path.display(),
+            cached_path.display()
+        )
+    })?;
+    Ok(Some(bytes))
+}
+
+/// Returns whether `bytes` is an object file whose `.note.gnu.build-id` is `build_id`.
+fn has_build_id(bytes: &[u8], build_id: &[u8]) -> bool {
+    object::File::parse(bytes)
+        .ok()
+        .and_then(|obj| obj.build_id().ok().flatten().map(|id| id == build_id))
+        .unwrap_or(false)
+}
+
+/// Returns the servers listed in `urls`, which like DEBUGINFOD_URLS is whitespace separated.
+fn server_urls(urls: &str) -> impl Iterator<Item = &str> {
+    urls.split_whitespace()
+        .map(|url| url.trim_end_matches('/'))
+        .filter(|url| !url.is_empty())
+}
+
+fn debuginfo_url(server: &str, build_id: &str) -> String {
+    format!("{server}/buildid/{build_id}/debuginfo")
+}
+
+/// Returns the cache directory, following the same rules as the elfutils client.
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("DEBUGINFOD_CACHE_PATH") {
+        return Some(PathBuf::from(path));
+    }
+    if let Some(path) = std::env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(path).join("debuginfod_client"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache/debuginfod_client"))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::debuginfo_url;
+    use super::fetch_with_cache;
+    use super::hex;
+    use super::server_urls;
+    use std::path::Path;
+
+    #[test]
+    fn debuginfod_urls() {
+        assert_eq!(
+            server_urls(" https://a.example.com/  https://b.example.com\n").collect::<Vec<_>>(),
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(server_urls("").count(), 0);
+        assert_eq!(
+            debuginfo_url("https://a.example.com", &hex(&[0x0a, 0xbc])),
+            "https://a.example.com/buildid/0abc/debuginfo"
+        );
+    }
+
+    /// Links an empty shared object with build ID `build_id`.
+    fn with_build_id(dir: &Path, build_id: &[u8]) -> Vec<u8> {
+        let path = dir.join(format!("{}.so", hex(build_id)));
+        let status = std::process::Command::new("cc")
+            .args(["-shared", "-nostdlib", "-x", "c", "/dev/null", "-o"])
+            .arg(&path)
+            .arg(format!("-Wl,--build-id=0x{}", hex(build_id)))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn build_id_verification() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let wanted = [0xab; 20];
+        let other = [0xcd; 20];
+        let server = dir.path().join("server");
+        let cache = dir.path().join("cache");
+        let serve = |bytes: &[u8]| {
+            let path = server.join("buildid").join(hex(&wanted)).join("debuginfo");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, bytes).unwrap();
+        };
+        let urls = format!("file://{}", server.display());
+        let cached_path = cache.join(hex(&wanted)).join("debuginfo");
+
+        // A server that gives us the wrong file is ignored and nothing is cached.
+        serve(&with_build_id(dir.path(), &other));
+        assert_eq!(fetch_with_cache(&wanted, &cache, &urls).unwrap(), None);
+        assert!(!cached_path.exists());
+
+        let good = with_build_id(dir.path(), &wanted);
+        serve(&good);
+        assert_eq!(
+            fetch_with_cache(&wanted, &cache, &urls).unwrap().as_ref(),
+            Some(&good)
+        );
+        assert_eq!(std::fs::read(&cached_path).unwrap(), good);
+
+        // A bad cache entry is removed, then we fetch again.
+        std::fs::write(&cached_path, b"not an object").unwrap();
+        assert_eq!(
+            fetch_with_cache(&wanted, &cache, &urls).unwrap().as_ref(),
+            Some(&good)
+        );
+        std::fs::write(&cached_path, b"not an object").unwrap();
+        assert_eq!(fetch_with_cache(&wanted, &cache, "").unwrap(), None);
+        assert!(!cached_path.exists());
+    }
+}
@@ -0,0 +1,223 @@
+//! A fallback for finding references that don't show up as relocations. e.g. a call to a function
+//! in the same section gets resolved by the assembler and after linker relaxation, some calls have
+//! no relocation at all. This is opt-in per package, since it's slower and, on x86-64, can give
+//! false positives.
+//!
+//! We don't do full disassembly. We only look for direct calls and jumps. On aarch64, instructions
+//! are fixed width, so this is exact. On x86-64, we don't know where instructions start, so we
+//! consider every offset and rely on the caller only accepting targets that are the start of a
+//! known symbol. Indirect branches, e.g. via jump tables, aren't found.
+//!
+//! We also look at the instructions around relocations to tell whether they're for calls. This
+//! matters on x86-64, where calls to functions in other crates go via the GOT and so have the same
+//! relocations as code that takes the address of a function.
+
+use object::Architecture;
+
+/// Returns whether we're able to find branches for `architecture`.
+pub(crate) fn is_supported(architecture: Architecture) -> bool {
+    matches!(architecture, Architecture::X86_64 | Architecture::Aarch64)
+}
+
+/// Returns the address of each direct call or jump in `code`, which starts at `address`, together
+/// with the address that it branches to.
+pub(crate) fn direct_branches(
+    architecture: Architecture,
+    code: &[u8],
+    address: u64,
+) -> Vec<(u64, u64)> {
+    match architecture {
+        Architecture::X86_64 => x86_64_branches(code, address),
+        Architecture::Aarch64 => aarch64_branches(code, address),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns whether the relocation at `offset` in `code` is for an instruction that calls or jumps
+/// to the relocation's target.
+pub(crate) fn is_branch_at(architecture: Architecture, code: &[u8], offset: usize) -> bool {
+    match architecture {
+        Architecture::X86_64 => x86_64_is_branch_at(code, offset),
+        Architecture::Aarch64 => code
+            .get(offset..offset + 4)
+            .is_some_and(|word| (word[3] >> 2) & 0x1f == 0b00101),
+        _ => false,
+    }
+}
+
+/// Returns the address of each instruction in `code`, which starts at `address`, that makes a system
+/// call directly rather than via a function in libc.
+pub(crate) fn syscalls(architecture: Architecture, code: &[u8], address: u64) -> Vec<u64> {
+    match architecture {
+        Architecture::X86_64 => x86_64_syscalls(code, address),
+        Architecture::Aarch64 => aarch64_syscalls(code, address),
+        _ => Vec::new(),
+    }
+}
+
+/// Recognises `call rel32`, `jmp rel32`, `call *disp32(%rip)`, `jmp *disp32(%rip)` and a load of
+/// a function address from the GOT into a register that's then immediately called or jumped to.
+fn x86_64_is_branch_at(code: &[u8], offset: usize) -> bool {
+    let before = |n: usize| offset.checked_sub(n).and_then(|i| code.get(i)).copied();
+    if matches!(before(1), Some(0xe8 | 0xe9)) {
+        return true;
+    }
+    if before(2) == Some(0xff) && matches!(before(1), Some(0x15 | 0x25)) {
+        return true;
+    }
+    // mov disp32(%rip), %reg
+    let (Some(rex @ (0x48 | 0x4c)), Some(0x8b), Some(modrm)) = (before(3), before(2), before(1))
+    else {
+        return false;
+    };
+    if modrm & 0xc7 != 0x05 {
+        return false;
+    }
+    let reg = (modrm >> 3) & 7;
+    // call *%reg or jmp *%reg. Registers r8-r15 need a REX.B prefix.
+    let next = &code[(offset + 4).min(code.len())..];
+    let next = if rex == 0x4c {
+        match next {
+            [0x41, rest @ ..] => rest,
+            _ => return false,
+        }
+    } else {
+        next
+    };
+    matches!(next, [0xff, modrm, ..] if *modrm == 0xd0 | reg || *modrm == 0xe0 | reg)
+}
+
+/// Finds `call rel32` (E8) and `jmp rel32` (E9).
+fn x86_64_branches(code: &[u8], address: u64) -> Vec<(u64, u64)> {
+    const INSTRUCTION_LEN: usize = 5;
+    let mut branches = Vec::new();
+    for (offset, window) in code.windows(INSTRUCTION_LEN).enumerate() {
+        if window[0] != 0xe8 && window[0] != 0xe9 {
+            continue;
+        }
+        let displacement = i32::from_le_bytes(window[1..].try_into().unwrap());
+        let next = address + (offset + INSTRUCTION_LEN) as u64;
+        branches.push((
+            address + offset as u64,
+            next.wrapping_add_signed(i64::from(displacement)),
+        ));
+    }
+    branches
+}
+
+/// Finds `b` and `bl`. Instructions are always little endian, regardless of the data endianness.
+fn aarch64_branches(code: &[u8], address: u64) -> Vec<(u64, u64)> {
+    let mut branches = Vec::new();
+    for (index, word) in code.chunks_exact(4).enumerate() {
+        let instruction = u32::from_le_bytes(word.try_into().unwrap());
+        // The top bit distinguishes `bl` from `b`, the next 5 bits are the opcode.
+        if (instruction >> 26) & 0x1f != 0b00101 {
+            continue;
+        }
+        // Sign extend the 26 bit immediate, which is in units of instructions.
+        let imm26 = ((instruction << 6) as i32) >> 6;
+        let from = address + index as u64 * 4;
+        branches.push((from, from.wrapping_add_signed(i64::from(imm26) * 4)));
+    }
+    branches
+}
+
+/// Finds `syscall` (0F 05), `sysenter` (0F 34) and `int 0x80` (CD 80). As with branches, we
+/// consider every offset, so these bytes may occasionally be part of some other instruction.
+fn x86_64_syscalls(code: &[u8], address: u64) -> Vec<u64> {
+    code.windows(2)
+        .enumerate()
+        .filter(|(_, window)| matches!(window, [0x0f, 0x05] | [0x0f, 0x34] | [0xcd, 0x80]))
+        .map(|(offset, _)| address + offset as u64)
+        .collect()
+}
+
+/// Finds `svc #imm`.
+fn aarch64_syscalls(code: &[u8], address: u64) -> Vec<u64> {
+    code.chunks_exact(4)
+        .enumerate()
+        .filter(|(_, word)| {
+            u32::from_le_bytes((*word).try_into().unwrap()) & 0xffe0_001f == 0xd400_0001
+        })
+        .map(|(index, _)| address + index as u64 * 4)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::direct_branches;
+    use super::is_branch_at;
+    use super::syscalls;
+    use object::Architecture;
+
+    #[test]
+    fn x86_64() {
+        // nop; call +0x10; jmp -0x20
+        let code = [
+            0x90, 0xe8, 0x10, 0, 0, 0, 0xe9, 0xe0, 0xff, 0xff, 0xff, 0x90,
+        ];
+        assert_eq!(
+            direct_branches(Architecture::X86_64, &code, 0x1000),
+            vec![(0x1001, 0x1016), (0x1006, 0x100b - 0x20)]
+        );
+    }
+
+    #[test]
+    fn aarch64() {
+        // bl +8; nop; b -4
+        let code = [
+            0x02, 0x00, 0x00, 0x94, 0x1f, 0x20, 0x03, 0xd5, 0xff, 0xff, 0xff, 0x17,
+        ];
+        assert_eq!(
+            direct_branches(Architecture::Aarch64, &code, 0x1000),
+            vec![(0x1000, 0x1008), (0x1008, 0x1004)]
+        );
+    }
+
+    #[test]
+    fn x86_64_relocations() {
+        let is_branch = |code: &[u8], offset| is_branch_at(Architecture::X86_64, code, offset);
+        // call rel32
+        assert!(is_branch(&[0xe8, 0, 0, 0, 0], 1));
+        // call *0x0(%rip)
+        assert!(is_branch(&[0xff, 0x15, 0, 0, 0, 0], 2));
+        // mov 0x0(%rip),%rax; call *%rax
+        assert!(is_branch(&[0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xff, 0xd0], 3));
+        // mov 0x0(%rip),%r11; call *%r11
+        assert!(is_branch(
+            &[0x4c, 0x8b, 0x1d, 0, 0, 0, 0, 0x41, 0xff, 0xd3],
+            3
+        ));
+        // mov 0x0(%rip),%rax; call *%rcx
+        assert!(!is_branch(&[0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xff, 0xd1], 3));
+        // mov 0x0(%rip),%rsi; followed by something other than a call.
+        assert!(!is_branch(&[0x48, 0x8b, 0x35, 0, 0, 0, 0, 0x90], 3));
+        // lea 0x0(%rip),%rdi
+        assert!(!is_branch(&[0x48, 0x8d, 0x3d, 0, 0, 0, 0], 3));
+        assert!(!is_branch(&[0, 0, 0, 0], 0));
+    }
+
+    #[test]
+    fn aarch64_relocations() {
+        // bl; adrp
+        let code = [0x00, 0x00, 0x00, 0x94, 0x00, 0x00, 0x00, 0x90];
+        assert!(is_branch_at(Architecture::Aarch64, &code, 0));
+        assert!(!is_branch_at(Architecture::Aarch64, &code, 4));
+        assert!(!is_branch_at(Architecture::Aarch64, &code, 8));
+    }
+
+    #[test]
+    fn syscall_instructions() {
+        // mov $0x3c,%eax; syscall; int $0x80
+        let code = [0xb8, 0x3c, 0, 0, 0, 0x0f, 0x05, 0xcd, 0x80];
+        assert_eq!(
+            syscalls(Architecture::X86_64, &code, 0x1000),
+            vec![0x1005, 0x1007]
+        );
+        // mov x8, #93; svc #0; nop
+        let code = [
+            0xa8, 0x0b, 0x80, 0xd2, 0x01, 0x00, 0x00, 0xd4, 0x1f, 0x20, 0x03, 0xd5,
+        ];
+        assert_eq!(syscalls(Architecture::Aarch64, &code, 0x1000), vec![0x1004]);
+    }
+}
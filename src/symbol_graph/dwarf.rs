@@ -1,3 +1,7 @@
+use super::split_dwarf;
+use super::split_dwarf::SplitDwarf;
+use crate::build_settings::BuildSettings;
+use crate::build_settings::producer_optimisation;
 use crate::checker::BinLocation;
 use crate::checker::Checker;
 use crate::location::SourceLocation;
@@ -13,22 +17,28 @@ use gimli::Attribute;
 use gimli::AttributeValue;
 use gimli::Dwarf;
 use gimli::EndianSlice;
-use gimli::LittleEndian;
+use gimli::RunTimeEndian;
 use gimli::Unit;
 use gimli::UnitOffset;
 use rustc_hash::FxHashMap;
 use std::ffi::OsStr;
 use std::os::unix::prelude::OsStrExt;
 use std::path::Path;
+use std::path::PathBuf;
 
 #[derive(Default)]
 pub(crate) struct DebugArtifacts<'input> {
     pub(crate) symbol_debug_info: FxHashMap<Symbol<'input>, SymbolDebugInfo<'input>>,
     pub(crate) inlined_functions: Vec<InlinedFunction<'input>>,
+
+    /// For each unit, the path of its main source file and how it appears to have been built.
+    pub(crate) unit_settings: Vec<(PathBuf, BuildSettings)>,
 }
 
 pub(crate) struct SymbolDebugInfo<'input> {
     compdir: &'input Path,
+    /// The name of the compilation unit containing the symbol.
+    unit_name: Option<&'input OsStr>,
     directory: Option<&'input OsStr>,
     path_name: &'input OsStr,
     line: u32,
@@ -65,18 +75,34 @@ impl CallLocation<'_> {
 
 impl<'input> DebugArtifacts<'input> {
     pub(crate) fn from_dwarf(
-        dwarf: &Dwarf<EndianSlice<'input, LittleEndian>>,
+        dwarf: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &SplitDwarf<'input>,
         checker: &Checker,
     ) -> Result<Self> {
         let mut scanner = DwarfScanner::default();
         scanner.index_units(dwarf)?;
         scanner.scan(dwarf, checker)?;
-        Ok(scanner.out)
+        let mut out = std::mem::take(&mut scanner.out);
+        for skeleton in &scanner.units {
+            let Some(split) = skeleton.dwo_id.and_then(|dwo_id| split_dwarf.get(dwo_id)) else {
+                continue;
+            };
+            // References within a split unit are relative to the split file, so each split unit
+            // gets its own scanner.
+            let mut split_scanner = DwarfScanner {
+                out,
+                ..Default::default()
+            };
+            split_scanner.index_split_unit(split, skeleton)?;
+            split_scanner.scan(split, checker)?;
+            out = split_scanner.out;
+        }
+        Ok(out)
     }
 }
 
 impl<'input> DwarfScanner<'input> {
-    fn index_units(&mut self, dwarf: &Dwarf<EndianSlice<'input, LittleEndian>>) -> Result<()> {
+    fn index_units(&mut self, dwarf: &Dwarf<EndianSlice<'input, RunTimeEndian>>) -> Result<()> {
         let mut unit_headers = dwarf.units();
         while let Some(header) = unit_headers.next()? {
             let Some(debug_offset) = header.offset().to_debug_info_offset(&header) else {
@@ -89,9 +115,24 @@ impl<'input> DwarfScanner<'input> {
         Ok(())
     }
 
+    fn index_split_unit(
+        &mut self,
+        dwarf: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
+        skeleton: &Unit<EndianSlice<'input, RunTimeEndian>>,
+    ) -> Result<()> {
+        let Some(unit) = split_dwarf::split_unit(dwarf, skeleton)? else {
+            return Ok(());
+        };
+        if let Some(debug_offset) = unit.header.offset().to_debug_info_offset(&unit.header) {
+            self.unit_offsets.push(debug_offset);
+            self.units.push(unit);
+        }
+        Ok(())
+    }
+
     fn scan(
         &mut self,
-        dwarf: &Dwarf<EndianSlice<'input, LittleEndian>>,
+        dwarf: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
         checker: &Checker,
     ) -> Result<()> {
         for unit in &self.units {
@@ -108,6 +149,7 @@ impl<'input> DwarfScanner<'input> {
                 compdir,
             };
 
+            let mut settings_scanner = UnitSettingsScanner::default();
             let mut entries = unit_state.unit.entries_raw(None)?;
             while !entries.is_empty() {
                 let Some(abbrev) = entries.read_abbreviation()? else {
@@ -116,6 +158,7 @@ impl<'input> DwarfScanner<'input> {
                     continue;
                 };
                 let tag = abbrev.tag();
+                settings_scanner.observe(abbrev);
                 let mut inline_scanner = InlinedFunctionScanner {
                     names: Default::default(),
                     low_pc: None,
@@ -156,6 +199,18 @@ impl<'input> DwarfScanner<'input> {
                     }
                 } else if tag == gimli::DW_TAG_namespace || tag == gimli::DW_TAG_structure_type {
                     namespace = unit_state.scan_namespace(&mut entries, abbrev.attributes())?;
+                } else if tag == gimli::DW_TAG_compile_unit {
+                    for spec in abbrev.attributes() {
+                        let attr = entries.read_attribute(*spec)?;
+                        if attr.name() == gimli::DW_AT_producer {
+                            settings_scanner.producer = Some(
+                                unit_state
+                                    .attr_string(attr.value())?
+                                    .to_string_lossy()
+                                    .into_owned(),
+                            );
+                        }
+                    }
                 } else {
                     entries.skip_attributes(abbrev.attributes())?;
                 }
@@ -167,6 +222,13 @@ impl<'input> DwarfScanner<'input> {
                     })
                 }
             }
+            if let Some(name) = unit.name
+                && let Some(settings) = settings_scanner.finish()
+            {
+                self.out
+                    .unit_settings
+                    .push((unit_source_path(compdir, name.slice()), settings));
+            }
         }
         Ok(())
     }
@@ -174,7 +236,7 @@ impl<'input> DwarfScanner<'input> {
     fn unit_containing(
         &self,
         offset: gimli::DebugInfoOffset,
-    ) -> Result<&Unit<EndianSlice<'input, LittleEndian>>> {
+    ) -> Result<&Unit<EndianSlice<'input, RunTimeEndian>>> {
         match self.unit_offsets.binary_search(&offset) {
             Ok(0) | Err(0) => {}
             Ok(index) | Err(index) => {
@@ -193,8 +255,8 @@ impl<'input> DwarfScanner<'input> {
 /// from the offset, we can determine information about the subprogram's attributes, but not about
 /// the namespace in which it's contained.
 fn get_subprogram_namespaces(
-    unit: &Unit<EndianSlice<LittleEndian>, usize>,
-    dwarf: &Dwarf<EndianSlice<LittleEndian>>,
+    unit: &Unit<EndianSlice<RunTimeEndian>, usize>,
+    dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
 ) -> Result<FxHashMap<UnitOffset, Namespace>> {
     let mut subprogram_namespaces: FxHashMap<UnitOffset, Namespace> = Default::default();
     let mut stack: Vec<Option<Namespace>> = Vec::new();
@@ -246,9 +308,9 @@ fn get_subprogram_namespaces(
 }
 
 struct UnitState<'input, 'dwarf> {
-    dwarf: &'dwarf Dwarf<EndianSlice<'input, LittleEndian>>,
+    dwarf: &'dwarf Dwarf<EndianSlice<'input, RunTimeEndian>>,
     frames: Vec<FrameState<'input>>,
-    unit: &'dwarf Unit<EndianSlice<'input, LittleEndian>, usize>,
+    unit: &'dwarf Unit<EndianSlice<'input, RunTimeEndian>, usize>,
     compdir: &'input Path,
     subprogram_namespaces: FxHashMap<UnitOffset, Namespace>,
 }
@@ -256,14 +318,14 @@ struct UnitState<'input, 'dwarf> {
 impl<'input> UnitState<'input, '_> {
     fn attr_string(
         &self,
-        attr: AttributeValue<EndianSlice<'input, LittleEndian>, usize>,
-    ) -> Result<gimli::EndianSlice<'input, LittleEndian>> {
+        attr: AttributeValue<EndianSlice<'input, RunTimeEndian>, usize>,
+    ) -> Result<gimli::EndianSlice<'input, RunTimeEndian>> {
         Ok(self.dwarf.attr_string(self.unit, attr)?)
     }
 
     fn get_directory_and_filename(
         &self,
-        file_index: AttributeValue<EndianSlice<'input, LittleEndian>, usize>,
+        file_index: AttributeValue<EndianSlice<'input, RunTimeEndian>, usize>,
     ) -> Result<(Option<&'input OsStr>, &'input OsStr), anyhow::Error> {
         let header = self.line_program_header()?;
         let gimli::AttributeValue::FileIndex(file_index) = file_index else {
@@ -284,7 +346,7 @@ impl<'input> UnitState<'input, '_> {
 
     fn line_program_header(
         &self,
-    ) -> Result<&gimli::LineProgramHeader<gimli::EndianSlice<'input, LittleEndian>>> {
+    ) -> Result<&gimli::LineProgramHeader<gimli::EndianSlice<'input, RunTimeEndian>>> {
         let line_program = self
             .unit
             .line_program
@@ -295,7 +357,7 @@ impl<'input> UnitState<'input, '_> {
 
     fn get_symbol_and_name(
         &self,
-        attr: gimli::Attribute<EndianSlice<'input, LittleEndian>>,
+        attr: gimli::Attribute<EndianSlice<'input, RunTimeEndian>>,
         scanner: &DwarfScanner<'input>,
         max_depth: u32,
     ) -> Result<SymbolAndName<'input>> {
@@ -319,7 +381,7 @@ impl<'input> UnitState<'input, '_> {
 
     fn get_symbol_and_name_in_unit(
         &self,
-        unit: &Unit<EndianSlice<'input, LittleEndian>, usize>,
+        unit: &Unit<EndianSlice<'input, RunTimeEndian>, usize>,
         unit_offset: UnitOffset,
         max_depth: u32,
         scanner: &DwarfScanner<'input>,
@@ -359,7 +421,7 @@ impl<'input> UnitState<'input, '_> {
 
     fn scan_namespace(
         &self,
-        entries: &mut gimli::EntriesRaw<EndianSlice<'input, LittleEndian>>,
+        entries: &mut gimli::EntriesRaw<EndianSlice<'input, RunTimeEndian>>,
         attributes: &[gimli::AttributeSpecification],
     ) -> Result<Option<Namespace>> {
         // TODO: See if we can reduce duplication between this function and
@@ -399,7 +461,7 @@ impl<'input> UnitState<'input, '_> {
 struct DwarfScanner<'input> {
     out: DebugArtifacts<'input>,
     unit_offsets: Vec<gimli::DebugInfoOffset>,
-    units: Vec<gimli::Unit<EndianSlice<'input, LittleEndian>>>,
+    units: Vec<gimli::Unit<EndianSlice<'input, RunTimeEndian>>>,
 }
 
 impl SymbolDebugInfo<'_> {
@@ -411,21 +473,29 @@ impl SymbolDebugInfo<'_> {
         filename.push(self.path_name);
         SourceLocation::new(filename, self.line, self.column)
     }
+
+    /// Returns the root source file of the compilation unit containing the symbol.
+    pub(crate) fn crate_root(&self) -> Option<PathBuf> {
+        Some(crate::checker::attribution::crate_root(
+            self.compdir,
+            self.unit_name?,
+        ))
+    }
 }
 
 #[derive(Default)]
 struct SymbolDebugInfoScanner<'input> {
-    name: Option<AttributeValue<EndianSlice<'input, LittleEndian>>>,
-    linkage_name: Option<AttributeValue<EndianSlice<'input, LittleEndian>>>,
+    name: Option<AttributeValue<EndianSlice<'input, RunTimeEndian>>>,
+    linkage_name: Option<AttributeValue<EndianSlice<'input, RunTimeEndian>>>,
     line: Option<u32>,
     column: Option<u32>,
-    file_index: Option<AttributeValue<EndianSlice<'input, LittleEndian>>>,
+    file_index: Option<AttributeValue<EndianSlice<'input, RunTimeEndian>>>,
 }
 
 impl<'input> SymbolDebugInfoScanner<'input> {
     fn handle_attribute(
         &mut self,
-        attr: Attribute<EndianSlice<'input, LittleEndian>>,
+        attr: Attribute<EndianSlice<'input, RunTimeEndian>>,
     ) -> Result<()> {
         match attr.name() {
             gimli::DW_AT_name => {
@@ -484,6 +554,10 @@ impl<'input> SymbolDebugInfoScanner<'input> {
             SymbolDebugInfo {
                 name: name.map(|n| DebugName::new(unit_state.namespace(), n)),
                 compdir: unit_state.compdir,
+                unit_name: unit_state
+                    .unit
+                    .name
+                    .map(|name| OsStr::from_bytes(name.slice())),
                 directory,
                 path_name,
                 line,
@@ -493,8 +567,81 @@ impl<'input> SymbolDebugInfoScanner<'input> {
     }
 }
 
+/// Facts about a unit from which we infer how it was built.
+#[derive(Default)]
+struct UnitSettingsScanner {
+    producer: Option<String>,
+    has_subprograms: bool,
+
+    /// Whether we've seen any types or variables. These are only present with full debug info.
+    has_types_or_variables: bool,
+
+    /// The number of variables and parameters that have a location.
+    located_variables: usize,
+
+    /// How many of `located_variables` have location lists rather than a single location. Without
+    /// optimisation, variables mostly live in a single stack slot, although LLVM still uses
+    /// location lists for a few, so we look at the proportion.
+    location_lists: usize,
+}
+
+impl UnitSettingsScanner {
+    fn observe(&mut self, abbrev: &gimli::Abbreviation) {
+        match abbrev.tag() {
+            gimli::DW_TAG_subprogram => self.has_subprograms = true,
+            gimli::DW_TAG_base_type | gimli::DW_TAG_pointer_type | gimli::DW_TAG_structure_type => {
+                self.has_types_or_variables = true;
+            }
+            gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                self.has_types_or_variables = true;
+                if let Some(spec) = abbrev
+                    .attributes()
+                    .iter()
+                    .find(|spec| spec.name() == gimli::DW_AT_location)
+                {
+                    self.located_variables += 1;
+                    if matches!(
+                        spec.form(),
+                        gimli::DW_FORM_sec_offset | gimli::DW_FORM_loclistx
+                    ) {
+                        self.location_lists += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the settings for the unit, or `None` if it contains no code.
+    fn finish(self) -> Option<BuildSettings> {
+        if !self.has_subprograms {
+            return None;
+        }
+        let producer_optimised = self
+            .producer
+            .as_deref()
+            .and_then(producer_optimisation)
+            .unwrap_or(false);
+        Some(BuildSettings {
+            producers: self.producer.into_iter().collect(),
+            optimised: self.location_lists * 4 > self.located_variables || producer_optimised,
+            reduced_debug_info: !self.has_types_or_variables,
+        })
+    }
+}
+
+/// Returns the path of the main source file of a unit. rustc names units like
+/// `src/lib.rs/@/foo.1a2b3c-cgu.0`, so we drop everything from the `/@/`.
+fn unit_source_path(compdir: &Path, name: &[u8]) -> PathBuf {
+    let name = name
+        .windows(3)
+        .position(|w| w == b"/@/")
+        .map_or(name, |end| &name[..end]);
+    compdir.join(OsStr::from_bytes(name))
+}
+
 fn path_from_opt_slice<'input>(
-    slice: Option<gimli::EndianSlice<'input, gimli::LittleEndian>>,
+    slice: Option<gimli::EndianSlice<'input, gimli::RunTimeEndian>>,
 ) -> &'input Path {
     slice
         .map(|dir| Path::new(OsStr::from_bytes(dir.slice())))
@@ -528,7 +675,7 @@ pub(crate) struct CallLocation<'input> {
 impl<'input> InlinedFunctionScanner<'input> {
     fn handle_attribute<'dwarf>(
         &mut self,
-        attr: Attribute<EndianSlice<'input, LittleEndian>>,
+        attr: Attribute<EndianSlice<'input, RunTimeEndian>>,
         unit_state: &UnitState<'input, 'dwarf>,
         scanner: &DwarfScanner<'input>,
     ) -> Result<()> {
@@ -0,0 +1,163 @@
+//! A cache of the innermost frame that debug info gives for each address that we look up. We look
+//! up the frame for every relocation and relocations are often in code from the same few lines, so
+//! rather than asking addr2line each time, we remember the range of addresses that each answer
+//! applies to. That's the row of the line table containing the address. Each row covers addresses
+//! with the same source location and where inlined code starts or ends, the source location
+//! changes, so the innermost function is also the same for the whole row.
+
+use super::split_dwarf::SplitDwarf;
+use anyhow::Result;
+use gimli::EndianSlice;
+use gimli::RunTimeEndian;
+
+pub(super) struct FrameCache<'ctx, 'input> {
+    ctx: &'ctx addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+    split_dwarf: &'ctx SplitDwarf<'input>,
+
+    /// Sorted by address. The ranges don't overlap, since rows of the line table don't.
+    frames: Vec<CachedFrame<'ctx, 'input>>,
+}
+
+struct CachedFrame<'ctx, 'input> {
+    start: u64,
+    end: u64,
+    frame: InnermostFrame<'ctx, 'input>,
+}
+
+/// What we need from the innermost frame at an address.
+pub(super) struct InnermostFrame<'ctx, 'input> {
+    /// The name of the function, which may have been inlined.
+    pub(super) function: Option<&'input [u8]>,
+    pub(super) location: Option<addr2line::Location<'ctx>>,
+}
+
+impl<'ctx, 'input> FrameCache<'ctx, 'input> {
+    pub(super) fn new(
+        ctx: &'ctx addr2line::Context<EndianSlice<'input, RunTimeEndian>>,
+        split_dwarf: &'ctx SplitDwarf<'input>,
+    ) -> Self {
+        Self {
+            ctx,
+            split_dwarf,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the innermost frame for the code at `address`.
+    pub(super) fn innermost(&mut self, address: u64) -> Result<InnermostFrame<'ctx, 'input>> {
+        let index = self.frames.partition_point(|cached| cached.end <= address);
+        if let Some(cached) = self.frames.get(index)
+            && cached.start <= address
+        {
+            return Ok(cached.frame.clone());
+        }
+        let mut frames = self.split_dwarf.resolve(self.ctx.find_frames(address))?;
+        let frame = frames
+            .next()?
+            .map(|frame| InnermostFrame {
+                function: frame.function.map(|function| function.name.slice()),
+                location: frame.location,
+            })
+            .unwrap_or(InnermostFrame {
+                function: None,
+                location: None,
+            });
+        let row = self.ctx.find_location_range(address, address + 1)?.next();
+        if let Some((start, len, _)) = row
+            && start <= address
+            && address - start < len
+        {
+            self.frames.insert(
+                index,
+                CachedFrame {
+                    start,
+                    end: start + len,
+                    frame: frame.clone(),
+                },
+            );
+        }
+        Ok(frame)
+    }
+}
+
+impl Clone for InnermostFrame<'_, '_> {
+    fn clone(&self) -> Self {
+        Self {
+            function: self.function,
+            // `Location` doesn't implement `Clone`.
+            location: self.location.as_ref().map(|location| addr2line::Location {
+                file: location.file,
+                line: location.line,
+                column: location.column,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameCache;
+    use crate::symbol_graph::split_dwarf::SplitDwarf;
+    use gimli::DwarfSections;
+    use object::Object;
+    use object::ObjectSection;
+    use object::ObjectSymbol;
+    use object::SymbolKind;
+
+    /// Checks that for the code of some functions in the test binary, which has debug info, we get
+    /// the same answers from the cache as from addr2line.
+    #[test]
+    fn same_as_addr2line() {
+        let bytes = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let obj = object::File::parse(bytes.as_slice()).unwrap();
+        let endian = if obj.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let sections = DwarfSections::load(|id| {
+            anyhow::Ok(
+                obj.section_by_name(id.name())
+                    .and_then(|section| section.data().ok())
+                    .unwrap_or_default(),
+            )
+        })
+        .unwrap();
+        let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+        let ctx = addr2line::Context::from_dwarf(dwarf).unwrap();
+        let split_dwarf = SplitDwarf::default();
+        type Parts<'a> = Option<(Option<&'a str>, Option<u32>, Option<u32>)>;
+        fn location<'a>(location: Option<&addr2line::Location<'a>>) -> Parts<'a> {
+            location.map(|location| (location.file, location.line, location.column))
+        }
+        let mut checked = 0;
+        for symbol in obj.symbols() {
+            if symbol.kind() != SymbolKind::Text
+                || symbol.size() == 0
+                || !symbol.name().is_ok_and(|name| name.contains("frame_cache"))
+            {
+                continue;
+            }
+            let mut cache = FrameCache::new(&ctx, &split_dwarf);
+            for address in symbol.address()..symbol.address() + symbol.size() {
+                let cached = cache.innermost(address).unwrap();
+                let mut frames = split_dwarf.resolve(ctx.find_frames(address)).unwrap();
+                let frame = frames.next().unwrap();
+                assert_eq!(
+                    cached.function,
+                    frame
+                        .as_ref()
+                        .and_then(|frame| frame.function.as_ref())
+                        .map(|function| function.name.slice())
+                );
+                assert_eq!(
+                    location(cached.location.as_ref()),
+                    location(frame.as_ref().and_then(|frame| frame.location.as_ref()))
+                );
+                checked += 1;
+            }
+            assert!(cache.frames.len() > 1);
+        }
+        assert!(checked > 0);
+    }
+}
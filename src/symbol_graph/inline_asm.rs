@@ -0,0 +1,149 @@
+//! Finding of inline assembly. There's nothing in the machine code or the debug info that marks
+//! instructions as having come from inline assembly, so we tokenise the source files that the line
+//! table refers to and find the lines covered by `asm!` and similar macros. Any instructions that
+//! the line table attributes to one of those lines came from inline assembly.
+
+use ra_ap_rustc_lexer::TokenKind;
+use rustc_hash::FxHashMap;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::path::PathBuf;
+
+const ASM_MACROS: &[&str] = &["asm", "global_asm", "naked_asm"];
+
+/// Extensions of files that are assembly source, e.g. compiled by the `cc` crate.
+const ASSEMBLY_EXTENSIONS: &[&str] = &["s", "S", "asm"];
+
+/// The lines of each source file that are within invocations of an assembly macro. Files are read
+/// the first time that they're queried.
+#[derive(Default)]
+pub(super) struct AsmLines {
+    by_file: FxHashMap<PathBuf, Vec<RangeInclusive<u32>>>,
+}
+
+impl AsmLines {
+    pub(super) fn contains(&mut self, path: &Path, line: u32) -> bool {
+        if !self.by_file.contains_key(path) {
+            let ranges = std::fs::read_to_string(path)
+                .map(|source| asm_line_ranges(&source))
+                .unwrap_or_default();
+            self.by_file.insert(path.to_owned(), ranges);
+        }
+        self.by_file[path].iter().any(|range| range.contains(&line))
+    }
+}
+
+/// Returns whether `path` is for an assembly source file.
+pub(super) fn is_assembly_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ASSEMBLY_EXTENSIONS.contains(&ext))
+}
+
+/// Returns the range of lines covered by each assembly macro invocation in `source`.
+fn asm_line_ranges(source: &str) -> Vec<RangeInclusive<u32>> {
+    enum State {
+        Searching,
+        /// We've seen the name of an assembly macro on `start_line`.
+        Name {
+            start_line: u32,
+        },
+        /// We've seen `!` after the name.
+        Bang {
+            start_line: u32,
+        },
+        /// We're within the macro's delimiters.
+        Body {
+            start_line: u32,
+            depth: u32,
+        },
+    }
+    let mut ranges = Vec::new();
+    let mut state = State::Searching;
+    let mut line = 1;
+    let mut offset = 0;
+    for token in ra_ap_rustc_lexer::tokenize(source, ra_ap_rustc_lexer::FrontmatterAllowed::No) {
+        let text = &source[offset..offset + token.len as usize];
+        offset += token.len as usize;
+        let token_line = line;
+        line += text.matches('\n').count() as u32;
+        if matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            continue;
+        }
+        let is_open = matches!(
+            token.kind,
+            TokenKind::OpenParen | TokenKind::OpenBrace | TokenKind::OpenBracket
+        );
+        let is_close = matches!(
+            token.kind,
+            TokenKind::CloseParen | TokenKind::CloseBrace | TokenKind::CloseBracket
+        );
+        state = match state {
+            State::Body { start_line, depth } if is_close && depth == 1 => {
+                ranges.push(start_line..=token_line);
+                State::Searching
+            }
+            State::Body { start_line, depth } => State::Body {
+                start_line,
+                depth: if is_open {
+                    depth + 1
+                } else if is_close {
+                    depth - 1
+                } else {
+                    depth
+                },
+            },
+            State::Name { start_line } if token.kind == TokenKind::Bang => {
+                State::Bang { start_line }
+            }
+            State::Bang { start_line } if is_open => State::Body {
+                start_line,
+                depth: 1,
+            },
+            _ if token.kind == TokenKind::Ident && ASM_MACROS.contains(&text) => State::Name {
+                start_line: token_line,
+            },
+            _ => State::Searching,
+        };
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::asm_line_ranges;
+    use super::is_assembly_source;
+    use std::path::Path;
+
+    #[test]
+    fn asm_macros() {
+        let source = indoc::indoc! {r#"
+            fn foo() -> u64 {
+                let x: u64;
+                unsafe {
+                    core::arch::asm!(
+                        "mov {}, 1", // (not a close paren
+                        out(reg) x,
+                    );
+                }
+                x
+            }
+            global_asm!("nop");
+            fn asm() {}
+            const S: &str = "asm!(nop)";
+            naked_asm! { "ret" }
+        "#};
+        assert_eq!(asm_line_ranges(source), vec![4..=7, 11..=11, 14..=14]);
+    }
+
+    #[test]
+    fn assembly_sources() {
+        assert!(is_assembly_source(Path::new("src/start.S")));
+        assert!(is_assembly_source(Path::new("src/start.s")));
+        assert!(!is_assembly_source(Path::new("src/lib.rs")));
+        assert!(!is_assembly_source(Path::new("asm")));
+    }
+}
@@ -1,7 +1,6 @@
-use anyhow::Context;
-use anyhow::Result;
+use std::ffi::OsStr;
 use std::fmt::Display;
-use std::fs::File;
+use std::os::unix::prelude::OsStrExt;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -21,19 +20,22 @@ impl ObjectFilePath {
         }
     }
 
-    pub(crate) fn in_archive(archive: &Path, entry: &ar::Entry<File>) -> Result<Self> {
-        let inner = PathBuf::from(
-            std::str::from_utf8(entry.header().identifier()).with_context(|| {
-                format!(
-                    "An archive entry in `{}` is not valid UTF-8",
-                    archive.display()
-                )
-            })?,
-        );
-        Ok(Self {
+    /// A member of a regular archive. Member names are kept as bytes, since they needn't be valid
+    /// UTF-8.
+    pub(crate) fn in_archive(archive: &Path, member_name: &[u8]) -> Self {
+        Self {
             outer: archive.to_owned(),
-            inner: Some(inner),
-        })
+            inner: Some(PathBuf::from(OsStr::from_bytes(member_name))),
+        }
+    }
+
+    /// A member of a thin archive. The member's contents live in a separate file, but we still name
+    /// it via the archive, since that's what was passed to the linker.
+    pub(crate) fn in_thin_archive(archive: &Path, member_name: &Path) -> Self {
+        Self {
+            outer: archive.to_owned(),
+            inner: Some(member_name.to_owned()),
+        }
     }
 }
 
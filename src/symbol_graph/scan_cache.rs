@@ -0,0 +1,319 @@
+//! An on-disk cache of the API usages found in each object file, so that when only a few objects
+//! have changed since the last run, we don't need to process all the rest again.
+//!
+//! What we find in an object depends on more than just its contents. It depends on the API
+//! definitions and also on the binary that it was linked into, since we ignore symbols that the
+//! linker discarded, and we report locations as addresses in the binary. The cache key therefore
+//! includes the API definitions and which of the object's symbols are present in the binary.
+//! Addresses are stored relative to a symbol from the object, so that entries can be reused after
+//! the binary gets relinked and everything moves.
+
+use super::BinInfo;
+use crate::checker::BinLocation;
+use crate::checker::ReferenceKind;
+use crate::config::ApiConfig;
+use crate::config::ApiName;
+use crate::config::AttributionStrategy;
+use crate::config::RemapPathPrefix;
+use crate::crate_index::PackageId;
+use crate::location::SourceLocation;
+use crate::names::Name;
+use crate::names::SymbolOrDebugName;
+use crate::symbol::Symbol;
+use crate::symbol_graph::NameSource;
+use crate::symbol_graph::UsageOrigin;
+use anyhow::Context;
+use anyhow::Result;
+use object::Object;
+use object::ObjectSymbol;
+use object::ReadRef;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Changes whenever the format of cache entries or the way we scan changes.
+const CACHE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub(super) struct ScanCache {
+    dir: PathBuf,
+
+    /// A hash of everything other than the object itself and the binary that affects what we find.
+    config_hash: String,
+}
+
+/// Everything other than the object itself and the binary that affects what we find in an object.
+#[derive(Serialize, Default)]
+pub(super) struct ScanSettings {
+    pub(super) apis: BTreeMap<ApiName, ApiConfig>,
+    pub(super) pseudo_apis: Vec<(ApiName, Cow<'static, str>)>,
+    pub(super) attribution: Vec<AttributionStrategy>,
+    pub(super) remaps: Vec<RemapPathPrefix>,
+    /// References are only needed for backtraces and take up a lot of space, so entries only
+    /// contain references if this is set. We keep separate entries for with and without references.
+    pub(super) backtraces: bool,
+    /// Whether entries contain all calls, rather than just calls between packages.
+    pub(super) all_calls: bool,
+    pub(super) ignore_instrumentation: bool,
+}
+
+/// Everything that we found in a single object file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub(super) struct CachedObject {
+    pub(super) usages: Vec<CachedUsage>,
+    /// Usages from sections in COMDAT groups. These are kept separate, since whether we keep them
+    /// depends on whether an earlier object in the binary had the same group.
+    #[serde(default)]
+    pub(super) group_usages: Vec<CachedGroupUsages>,
+    pub(super) references: Vec<CachedReference>,
+    pub(super) calls: Vec<CachedCall>,
+    pub(super) roots: Vec<Symbol<'static>>,
+}
+
+/// An API usage, minus the parts that we can recompute from the binary.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct CachedUsage {
+    pub(super) pkg_id: PackageId,
+    pub(super) api: ApiName,
+    pub(super) location: RelativeLocation,
+    pub(super) source_location: SourceLocation,
+    pub(super) outer_location: Option<SourceLocation>,
+    pub(super) from: SymbolOrDebugName,
+    pub(super) to: SymbolOrDebugName,
+    pub(super) to_name: Name,
+    pub(super) to_source: NameSource<'static>,
+    pub(super) kind: ReferenceKind,
+    pub(super) origin: UsageOrigin,
+}
+
+/// The API usages from the sections of the COMDAT group with signature `group`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct CachedGroupUsages {
+    pub(super) group: Symbol<'static>,
+    pub(super) usages: Vec<CachedUsage>,
+}
+
+/// A reference from somewhere in the object to `target`. Used for backtraces.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct CachedReference {
+    pub(super) location: RelativeLocation,
+    pub(super) target: Symbol<'static>,
+}
+
+/// A call from function `from` in the object to function `to`. Used for call paths.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct CachedCall {
+    pub(super) from: Symbol<'static>,
+    pub(super) to: Symbol<'static>,
+}
+
+/// A location within the binary, expressed as an offset from the start of a symbol.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(super) struct RelativeLocation {
+    pub(super) symbol: Symbol<'static>,
+    pub(super) offset: u64,
+}
+
+impl ScanCache {
+    /// Creates a cache that stores entries in `dir` for objects scanned with `settings`.
+    pub(super) fn new(dir: PathBuf, settings: &ScanSettings) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_VERSION);
+        hasher.update(serde_json::to_vec(settings)?);
+        Ok(Self {
+            dir,
+            config_hash: format!("{:x}", hasher.finalize()),
+        })
+    }
+
+    /// Returns the cache key for the object file whose contents hash to `object_hash`, which was
+    /// parsed as `obj` and linked into `bin`.
+    pub(super) fn key<'data, R: ReadRef<'data>>(
+        &self,
+        object_hash: &str,
+        obj: &object::File<'data, R>,
+        bin: &BinInfo,
+    ) -> String {
+        self.key_from_parts(
+            object_hash,
+            obj.symbols().map(|symbol| {
+                let symbol = Symbol::borrowed(symbol.name_bytes().unwrap_or_default());
+                (
+                    bin.symbol_addresses.contains_key(&symbol),
+                    bin.symbol_debug_info.contains_key(&symbol),
+                    bin.weak_symbols.contains(&symbol),
+                )
+            }),
+        )
+    }
+
+    /// Computes a key from the hash of an object file and, for each symbol in the object, whether
+    /// the binary has an address and debug info for that symbol and whether it's weak in the
+    /// binary.
+    fn key_from_parts(
+        &self,
+        object_hash: &str,
+        symbols_in_bin: impl Iterator<Item = (bool, bool, bool)>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.config_hash);
+        hasher.update(object_hash);
+        for (has_address, has_debug_info, weak) in symbols_in_bin {
+            hasher.update([u8::from(has_address)
+                | (u8::from(has_debug_info) << 1)
+                | (u8::from(weak) << 2)]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Returns the entry for `key` if there is one. Entries that can't be read, e.g. because
+    /// they're corrupt, are treated as absent, since we can always just recompute them.
+    pub(super) fn load(&self, key: &str) -> Option<CachedObject> {
+        let bytes = std::fs::read(self.entry_path(key)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub(super) fn store(&self, key: &str, entry: &CachedObject) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create `{}`", self.dir.display()))?;
+        // Multiple threads, or even multiple runs, can store the same entry at once, so we write to
+        // a temporary file, then rename it into place.
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(&serde_json::to_vec(entry)?)?;
+        let path = self.entry_path(key);
+        file.persist(&path)
+            .with_context(|| format!("Failed to write `{}`", path.display()))?;
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl RelativeLocation {
+    /// Returns a location relative to whichever of `symbols_by_address` contains `bin_location`.
+    pub(super) fn from_bin_location(
+        bin_location: BinLocation,
+        symbols_by_address: &FxHashMap<u64, Symbol>,
+    ) -> Option<Self> {
+        Some(Self {
+            symbol: symbols_by_address
+                .get(&bin_location.symbol_start)?
+                .to_heap(),
+            offset: bin_location.address - bin_location.symbol_start,
+        })
+    }
+
+    /// Returns the location in the binary, or `None` if our symbol isn't in the binary.
+    pub(super) fn to_bin_location(&self, bin: &BinInfo) -> Option<BinLocation> {
+        let symbol_start = *bin.symbol_addresses.get(&self.symbol)?;
+        Some(BinLocation {
+            address: symbol_start + self.offset,
+            symbol_start,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedCall;
+    use super::CachedGroupUsages;
+    use super::CachedObject;
+    use super::CachedReference;
+    use super::RelativeLocation;
+    use super::ScanCache;
+    use super::ScanSettings;
+    use crate::config::ApiConfig;
+    use crate::config::ApiName;
+    use crate::config::AttributionStrategy;
+    use crate::config::RemapPathPrefix;
+    use crate::prebuilt::sha256_hex;
+    use crate::symbol::Symbol;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn cache_keys_and_entries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let settings = ScanSettings {
+            backtraces: true,
+            ..ScanSettings::default()
+        };
+        let cache = ScanCache::new(dir.path().to_owned(), &settings).unwrap();
+        let key = |cache: &ScanCache, bytes: &[u8], present: &[bool]| {
+            cache.key_from_parts(&sha256_hex(bytes), present.iter().map(|p| (*p, *p, false)))
+        };
+        let base = key(&cache, b"obj", &[true, false]);
+        assert_eq!(base, key(&cache, b"obj", &[true, false]));
+        assert_ne!(base, key(&cache, b"obj2", &[true, false]));
+        // If the linker discards a symbol, then what we find may change.
+        assert_ne!(base, key(&cache, b"obj", &[true, true]));
+        let mut apis = BTreeMap::new();
+        apis.insert(ApiName::from("fs"), ApiConfig::default());
+        let variants = [
+            ScanSettings {
+                apis,
+                ..ScanSettings::default()
+            },
+            ScanSettings {
+                all_calls: true,
+                ..ScanSettings::default()
+            },
+            ScanSettings {
+                attribution: vec![AttributionStrategy::CompilationUnit],
+                ..ScanSettings::default()
+            },
+            ScanSettings {
+                remaps: vec![RemapPathPrefix {
+                    from: "/home/me/foo".into(),
+                    to: "/build".into(),
+                }],
+                ..ScanSettings::default()
+            },
+            ScanSettings {
+                pseudo_apis: vec![(ApiName::from("determinism:time"), "std::time".into())],
+                ..ScanSettings::default()
+            },
+            ScanSettings {
+                ignore_instrumentation: true,
+                ..ScanSettings::default()
+            },
+        ];
+        for variant in variants {
+            let variant = ScanSettings {
+                backtraces: true,
+                ..variant
+            };
+            let other = ScanCache::new(dir.path().to_owned(), &variant).unwrap();
+            assert_ne!(base, key(&other, b"obj", &[true, false]));
+        }
+
+        assert_eq!(cache.load(&base), None);
+        let entry = CachedObject {
+            usages: Vec::new(),
+            group_usages: vec![CachedGroupUsages {
+                group: Symbol::borrowed(b"DW.ref.rust_eh_personality").to_heap(),
+                usages: Vec::new(),
+            }],
+            references: vec![CachedReference {
+                location: RelativeLocation {
+                    symbol: Symbol::borrowed(b"foo").to_heap(),
+                    offset: 8,
+                },
+                target: Symbol::borrowed(b"bar").to_heap(),
+            }],
+            calls: vec![CachedCall {
+                from: Symbol::borrowed(b"foo").to_heap(),
+                to: Symbol::borrowed(b"bar").to_heap(),
+            }],
+            roots: vec![Symbol::borrowed(b"baz").to_heap()],
+        };
+        cache.store(&base, &entry).unwrap();
+        assert_eq!(cache.load(&base), Some(entry));
+    }
+}
@@ -0,0 +1,323 @@
+//! Support for debug info that has been split out of the binary. With `-C split-debuginfo=unpacked`,
+//! the binary contains only a skeleton unit for each compilation unit, with the rest of the debug
+//! info left in a `.dwo` file next to the object file. With `-C split-debuginfo=packed`, those
+//! `.dwo` files are combined into a single `.dwp` package next to the binary.
+
+use anyhow::Context;
+use anyhow::Result;
+use gimli::DebugLineOffset;
+use gimli::Dwarf;
+use gimli::DwarfPackageSections;
+use gimli::DwarfSections;
+use gimli::DwoId;
+use gimli::EndianSlice;
+use gimli::Reader;
+use gimli::RunTimeEndian;
+use gimli::Section;
+use gimli::Unit;
+use log::info;
+use object::Object;
+use rustc_hash::FxHashMap;
+use std::ffi::OsStr;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Owned copies of the split debug info sections referenced by a binary. These need to outlive
+/// everything that we derive from them, so are loaded separately from `SplitDwarf`.
+#[derive(Default)]
+pub(crate) struct SplitDwarfSections {
+    dwo_ids: Vec<DwoId>,
+    dwos: FxHashMap<DwoId, DwarfSections<Vec<u8>>>,
+    package: Option<DwarfPackageSections<Vec<u8>>>,
+}
+
+/// The split DWARF for each skeleton unit in a binary.
+#[derive(Default)]
+pub(crate) struct SplitDwarf<'input> {
+    by_dwo_id: FxHashMap<DwoId, Arc<Dwarf<EndianSlice<'input, RunTimeEndian>>>>,
+}
+
+impl SplitDwarfSections {
+    /// Locates and loads the split debug info for any skeleton units in `dwarf`, which was read
+    /// from `bin_path`. If there's a `.dwp` next to the binary, then it's used in preference to
+    /// individual `.dwo` files.
+    pub(crate) fn load(dwarf: &Dwarf<EndianSlice<RunTimeEndian>>, bin_path: &Path) -> Result<Self> {
+        let skeletons = skeleton_units(dwarf)?;
+        let mut sections = SplitDwarfSections {
+            dwo_ids: skeletons.iter().map(|(dwo_id, _)| *dwo_id).collect(),
+            ..Default::default()
+        };
+        if skeletons.is_empty() {
+            return Ok(sections);
+        }
+        if let Some(dwp_path) = dwp_paths(bin_path).into_iter().find(|path| path.exists()) {
+            let bytes = std::fs::read(&dwp_path)
+                .with_context(|| format!("Failed to read `{}`", dwp_path.display()))?;
+            let obj = object::File::parse(bytes.as_slice())
+                .with_context(|| format!("Failed to parse `{}`", dwp_path.display()))?;
+            sections.package = Some(DwarfPackageSections::load(|id| load_dwo_section(&obj, id))?);
+            return Ok(sections);
+        }
+        for (dwo_id, path) in skeletons {
+            // If the .dwo has gone missing, we still have the skeleton unit, so we carry on with
+            // what we've got rather than failing.
+            let Ok(bytes) = std::fs::read(&path) else {
+                info!("Split debug info `{}` not found", path.display());
+                continue;
+            };
+            let obj = object::File::parse(bytes.as_slice())
+                .with_context(|| format!("Failed to parse `{}`", path.display()))?;
+            sections.dwos.insert(
+                dwo_id,
+                DwarfSections::load(|id| load_dwo_section(&obj, id))?,
+            );
+        }
+        Ok(sections)
+    }
+
+    /// Builds the split DWARF for each skeleton unit in `parent`, which should be the same DWARF
+    /// that was passed to `load`.
+    pub(crate) fn borrow<'input>(
+        &'input self,
+        parent: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
+    ) -> Result<SplitDwarf<'input>> {
+        let mut split = SplitDwarf::default();
+        // Split DWARF files are produced for the same target as the binary, so have the same byte
+        // order.
+        let endian = parent.debug_info.reader().endian();
+        if let Some(package) = self.package.as_ref() {
+            let package = package.borrow(
+                |section| EndianSlice::new(section, endian),
+                EndianSlice::new(&[], endian),
+            )?;
+            for dwo_id in &self.dwo_ids {
+                if let Some(mut dwarf) = package.find_cu(*dwo_id, parent)? {
+                    inherit_line_strings(&mut dwarf, parent);
+                    split.by_dwo_id.insert(*dwo_id, Arc::new(dwarf));
+                }
+            }
+        }
+        for (dwo_id, sections) in &self.dwos {
+            let mut dwarf = sections.borrow(|section| EndianSlice::new(section, endian));
+            dwarf.make_dwo(parent);
+            inherit_line_strings(&mut dwarf, parent);
+            split.by_dwo_id.insert(*dwo_id, Arc::new(dwarf));
+        }
+        Ok(split)
+    }
+}
+
+impl<'input> SplitDwarf<'input> {
+    pub(crate) fn get(
+        &self,
+        dwo_id: DwoId,
+    ) -> Option<&Arc<Dwarf<EndianSlice<'input, RunTimeEndian>>>> {
+        self.by_dwo_id.get(&dwo_id)
+    }
+
+    /// Runs an addr2line lookup to completion, supplying split DWARF whenever it's requested.
+    pub(crate) fn resolve<L>(&self, mut lookup: addr2line::LookupResult<L>) -> L::Output
+    where
+        L: addr2line::LookupContinuation<Buf = EndianSlice<'input, RunTimeEndian>>,
+    {
+        loop {
+            lookup = match lookup {
+                addr2line::LookupResult::Output(output) => return output,
+                addr2line::LookupResult::Load { load, continuation } => {
+                    continuation.resume(self.get(load.dwo_id).cloned())
+                }
+            };
+        }
+    }
+}
+
+/// Returns the unit in `dwarf` that corresponds to `skeleton`, with the attributes that split
+/// units inherit from their skeleton filled in.
+pub(crate) fn split_unit<'input>(
+    dwarf: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
+    skeleton: &Unit<EndianSlice<'input, RunTimeEndian>>,
+) -> Result<Option<Unit<EndianSlice<'input, RunTimeEndian>>>> {
+    let mut unit_headers = dwarf.units();
+    while let Some(header) = unit_headers.next()? {
+        let mut unit = dwarf.unit(header)?;
+        if unit.dwo_id != skeleton.dwo_id {
+            continue;
+        }
+        unit.copy_relocated_attributes(skeleton);
+        if unit.comp_dir.is_none() {
+            unit.comp_dir = skeleton.comp_dir;
+        }
+        // Split units don't have a DW_AT_stmt_list. In DWARF 5, the file table that their
+        // DW_AT_decl_file attributes refer to is at the start of .debug_line.dwo. With the GNU
+        // extension to DWARF 4, it's the skeleton's line table.
+        if unit.line_program.is_none() {
+            unit.line_program = dwarf
+                .debug_line
+                .program(
+                    DebugLineOffset(0),
+                    unit.header.address_size(),
+                    unit.comp_dir,
+                    unit.name,
+                )
+                .ok()
+                .or_else(|| skeleton.line_program.clone());
+        }
+        return Ok(Some(unit));
+    }
+    Ok(None)
+}
+
+/// LLVM only emits .debug_line.dwo when there are type units, otherwise split units use the
+/// skeleton's line table (see `split_unit`). In DWARF 5, the file names in that line table are
+/// generally in the parent's .debug_line_str, which split files don't have, so we take it from the
+/// parent.
+fn inherit_line_strings<'input>(
+    dwarf: &mut Dwarf<EndianSlice<'input, RunTimeEndian>>,
+    parent: &Dwarf<EndianSlice<'input, RunTimeEndian>>,
+) {
+    dwarf.debug_line_str = parent.debug_line_str;
+}
+
+/// Returns the DWO ID and .dwo path of each skeleton unit in `dwarf`.
+fn skeleton_units(dwarf: &Dwarf<EndianSlice<RunTimeEndian>>) -> Result<Vec<(DwoId, PathBuf)>> {
+    let mut skeletons = Vec::new();
+    let mut unit_headers = dwarf.units();
+    while let Some(header) = unit_headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(dwo_id) = unit.dwo_id else {
+            continue;
+        };
+        let Some(dwo_name) = unit.dwo_name()? else {
+            continue;
+        };
+        let dwo_name = dwarf.attr_string(&unit, dwo_name)?;
+        let mut path = unit
+            .comp_dir
+            .map(|dir| PathBuf::from(OsStr::from_bytes(dir.slice())))
+            .unwrap_or_default();
+        path.push(OsStr::from_bytes(dwo_name.slice()));
+        skeletons.push((dwo_id, path));
+    }
+    Ok(skeletons)
+}
+
+/// Returns the locations where we might find a .dwp for `bin_path`. rustc replaces any extension
+/// of the binary, whereas other tools generally append to the full filename.
+fn dwp_paths(bin_path: &Path) -> [PathBuf; 2] {
+    let mut appended = bin_path.as_os_str().to_owned();
+    appended.push(".dwp");
+    [bin_path.with_extension("dwp"), PathBuf::from(appended)]
+}
+
+fn load_dwo_section(obj: &object::File, id: gimli::SectionId) -> Result<Vec<u8>> {
+    let Some(section) = id.dwo_name().and_then(|name| obj.section_by_name(name)) else {
+        return Ok(Vec::new());
+    };
+    Ok(super::section_data(&section)?.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SplitDwarfSections;
+    use super::dwp_paths;
+    use super::split_unit;
+    use gimli::AttributeValue;
+    use gimli::DwarfSections;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    /// In DWARF 5, LLVM gives split units the skeleton's line table, whose file names are
+    /// `DW_FORM_line_strp` references into the binary's .debug_line_str. Check that we can resolve
+    /// where a function in a split unit was declared.
+    #[test]
+    fn split_unit_line_strings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "#[inline(never)]\nfn answer() -> u32 { 42 }\nfn main() { std::process::exit(answer() as i32) }\n",
+        )
+        .unwrap();
+        let status = std::process::Command::new("rustc")
+            .current_dir(dir.path())
+            .args([
+                "-Cdebuginfo=2",
+                "-Csplit-debuginfo=unpacked",
+                "-Cdwarf-version=5",
+                "-Ccodegen-units=1",
+                "main.rs",
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let bin_path = dir.path().join("main");
+        let bytes = std::fs::read(&bin_path).unwrap();
+        let obj = object::File::parse(bytes.as_slice()).unwrap();
+        let sections = DwarfSections::load(|id| super::super::load_section(&obj, id)).unwrap();
+        let endian = super::super::endian(&obj);
+        let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+        let split_sections = SplitDwarfSections::load(&dwarf, &bin_path).unwrap();
+        let split = split_sections.borrow(&dwarf).unwrap();
+
+        let mut declaration = None;
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next().unwrap() {
+            let skeleton = dwarf.unit(header).unwrap();
+            let Some(split_dwarf) = skeleton.dwo_id.and_then(|dwo_id| split.get(dwo_id)) else {
+                continue;
+            };
+            let unit = split_unit(split_dwarf, &skeleton).unwrap().unwrap();
+            let mut entries = unit.entries();
+            while let Some(entry) = entries.next_dfs().unwrap() {
+                let name = entry
+                    .attr_value(gimli::DW_AT_name)
+                    .and_then(|name| split_dwarf.attr_string(&unit, name).ok());
+                if entry.tag() != gimli::DW_TAG_subprogram
+                    || name.map(|n| n.slice()) != Some(b"answer")
+                {
+                    continue;
+                }
+                let Some(AttributeValue::FileIndex(file_index)) =
+                    entry.attr_value(gimli::DW_AT_decl_file)
+                else {
+                    panic!("`answer` has no DW_AT_decl_file");
+                };
+                let line = entry
+                    .attr_value(gimli::DW_AT_decl_line)
+                    .and_then(|l| l.udata_value());
+                let program = unit.line_program.as_ref().unwrap();
+                let file = program.header().file(file_index).unwrap();
+                assert!(
+                    matches!(file.path_name(), AttributeValue::DebugLineStrRef(_)),
+                    "Unexpected form for file name {:?}",
+                    file.path_name()
+                );
+                let file_name = split_dwarf.attr_string(&unit, file.path_name()).unwrap();
+                declaration = Some((
+                    String::from_utf8_lossy(file_name.slice()).into_owned(),
+                    line,
+                ));
+            }
+        }
+        assert_eq!(declaration, Some(("main.rs".to_owned(), Some(2))));
+    }
+
+    #[test]
+    fn dwp_path_candidates() {
+        assert_eq!(
+            dwp_paths(Path::new("/target/deps/foo-abc123")),
+            [
+                PathBuf::from("/target/deps/foo-abc123.dwp"),
+                PathBuf::from("/target/deps/foo-abc123.dwp"),
+            ]
+        );
+        assert_eq!(
+            dwp_paths(Path::new("/target/deps/libfoo.so")),
+            [
+                PathBuf::from("/target/deps/libfoo.dwp"),
+                PathBuf::from("/target/deps/libfoo.so.dwp"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Support for GNU thin archives, e.g. as produced by `ar rcT`. A thin archive has the same headers
+//! as a regular archive, but the member contents aren't stored in the archive. Instead, member names
+//! are paths to the object files, relative to the directory containing the archive.
+
+use crate::vfs::Vfs;
+use anyhow::Context;
+use anyhow::Result;
+use object::read::archive::ArchiveFile;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+const THIN_MAGIC: &[u8] = b"!<thin>\n";
+
+/// Returns whether the file at `path` is a thin archive.
+pub(crate) fn is_thin_archive(vfs: &dyn Vfs, path: &Path) -> Result<bool> {
+    let mut magic = [0; THIN_MAGIC.len()];
+    let mut file = vfs
+        .open(path)
+        .with_context(|| format!("Failed to open `{}`", path.display()))?;
+    // Files too short to contain the magic aren't thin archives.
+    Ok(file.read_exact(&mut magic).is_ok() && magic == THIN_MAGIC)
+}
+
+/// A member of a thin archive.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ThinMember {
+    /// The name of the member as recorded in the archive.
+    pub(crate) name: PathBuf,
+
+    /// Where the member's contents can be found.
+    pub(crate) path: PathBuf,
+}
+
+/// Returns the members of the thin archive `bytes`, which was read from `archive_path`.
+pub(crate) fn members(archive_path: &Path, bytes: &[u8]) -> Result<Vec<ThinMember>> {
+    let archive = ArchiveFile::parse(bytes)
+        .with_context(|| format!("Failed to parse thin archive `{}`", archive_path.display()))?;
+    let archive_dir = archive_path.parent().unwrap_or(Path::new(""));
+    archive
+        .members()
+        .map(|member| {
+            let name = PathBuf::from(OsStr::from_bytes(member?.name()));
+            Ok(ThinMember {
+                path: archive_dir.join(&name),
+                name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThinMember;
+    use super::members;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn thin_archive_members() {
+        let bytes = b"\
+            !<thin>\n\
+            //                                              28        `\n\
+            sub/long_name.o/\n\
+            /abs/bb.o/\n\
+            a.o/            0           0     0     644     4         `\n\
+            /0              0           0     0     644     4         `\n\
+            /17             0           0     0     644     4         `\n";
+        assert_eq!(
+            members(Path::new("/build/libfoo.a"), bytes).unwrap(),
+            vec![
+                ThinMember {
+                    name: PathBuf::from("a.o"),
+                    path: PathBuf::from("/build/a.o"),
+                },
+                ThinMember {
+                    name: PathBuf::from("sub/long_name.o"),
+                    path: PathBuf::from("/build/sub/long_name.o"),
+                },
+                ThinMember {
+                    name: PathBuf::from("/abs/bb.o"),
+                    path: PathBuf::from("/abs/bb.o"),
+                },
+            ]
+        );
+    }
+}
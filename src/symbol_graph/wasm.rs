@@ -0,0 +1,175 @@
+//! Support for WebAssembly modules, e.g. from wasm32-unknown-unknown or wasm32-wasip1. A linked
+//! wasm module has no relocations, but unlike machine code, wasm can be decoded exactly, so we find
+//! references by looking at the call instructions in each function. Function names come from the
+//! name section. DWARF for wasm is stored in custom sections and uses offsets within the code section
+//! as addresses, so that's what we use as addresses too.
+
+use crate::checker::ReferenceKind;
+use anyhow::Context;
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use wasmparser::KnownCustom;
+use wasmparser::Name;
+use wasmparser::Operator;
+use wasmparser::Parser;
+use wasmparser::Payload;
+use wasmparser::TypeRef;
+
+pub(crate) fn is_wasm(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\0asm")
+}
+
+pub(crate) struct WasmModule<'data> {
+    /// All functions, indexed by function index. Imported functions come first.
+    pub(crate) functions: Vec<WasmFunction<'data>>,
+
+    /// Custom sections other than the name section, keyed by name. e.g. `.debug_info`.
+    custom_sections: FxHashMap<&'data str, &'data [u8]>,
+}
+
+#[derive(Default)]
+pub(crate) struct WasmFunction<'data> {
+    pub(crate) name: Option<&'data str>,
+
+    /// The offset of the function body within the code section. `None` for imported functions.
+    pub(crate) address: Option<u64>,
+
+    /// The address of each instruction in this function that references another function, together
+    /// with the index of the function referenced and whether the instruction calls it or just takes
+    /// a reference to it.
+    pub(crate) references: Vec<(u64, u32, ReferenceKind)>,
+}
+
+impl<'data> WasmModule<'data> {
+    pub(crate) fn parse(bytes: &'data [u8]) -> Result<Self> {
+        let mut module = WasmModule {
+            functions: Vec::new(),
+            custom_sections: FxHashMap::default(),
+        };
+        let mut code_start = 0;
+        let mut next_body = 0;
+        for payload in Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::ImportSection(imports) => {
+                    for import in imports.into_imports() {
+                        let import = import?;
+                        if matches!(import.ty, TypeRef::Func(_) | TypeRef::FuncExact(_)) {
+                            module.functions.push(WasmFunction {
+                                name: Some(import.name),
+                                ..WasmFunction::default()
+                            });
+                        }
+                    }
+                    next_body = module.functions.len();
+                }
+                Payload::FunctionSection(functions) => {
+                    module
+                        .functions
+                        .extend((0..functions.count()).map(|_| WasmFunction::default()));
+                }
+                Payload::CodeSectionStart { range, .. } => {
+                    code_start = range.start;
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let index = next_body;
+                    next_body += 1;
+                    let function = module
+                        .functions
+                        .get_mut(index)
+                        .context("Wasm code section has more entries than function section")?;
+                    function.address = Some((body.range().start - code_start) as u64);
+                    let mut operators = body.get_operators_reader()?;
+                    while !operators.eof() {
+                        let (operator, offset) = operators.read_with_offset()?;
+                        let (target, kind) = match operator {
+                            Operator::Call { function_index }
+                            | Operator::ReturnCall { function_index } => {
+                                (function_index, ReferenceKind::Call)
+                            }
+                            Operator::RefFunc { function_index } => {
+                                (function_index, ReferenceKind::Data)
+                            }
+                            _ => continue,
+                        };
+                        function
+                            .references
+                            .push(((offset - code_start) as u64, target, kind));
+                    }
+                }
+                Payload::CustomSection(reader) => match reader.as_known() {
+                    KnownCustom::Name(names) => {
+                        for name in names {
+                            let Name::Function(function_names) = name? else {
+                                continue;
+                            };
+                            for naming in function_names {
+                                let naming = naming?;
+                                if let Some(function) =
+                                    module.functions.get_mut(naming.index as usize)
+                                {
+                                    function.name = Some(naming.name);
+                                }
+                            }
+                        }
+                    }
+                    _ => {
+                        module.custom_sections.insert(reader.name(), reader.data());
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    /// Returns the contents of the custom section `name`, or an empty slice if there's no such
+    /// section.
+    pub(crate) fn custom_section(&self, name: &str) -> &'data [u8] {
+        self.custom_sections.get(name).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WasmModule;
+    use crate::checker::ReferenceKind;
+
+    #[test]
+    fn parse_module() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // Type section: one type, () -> ().
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            // Import section: env.imp of type 0.
+            0x02, 0x0b, 0x01, 0x03, b'e', b'n', b'v', 0x03, b'i', b'm', b'p', 0x00, 0x00,
+            // Function section: two functions of type 0.
+            0x03, 0x03, 0x02, 0x00, 0x00,
+            // Code section: function 1 calls 2, function 2 calls the import.
+            0x0a, 0x0b, 0x02,
+            0x04, 0x00, 0x10, 0x02, 0x0b,
+            0x04, 0x00, 0x10, 0x00, 0x0b,
+            // Custom section: name section naming functions 1 and 2.
+            0x00, 0x0e, 0x04, b'n', b'a', b'm', b'e',
+            0x01, 0x07, 0x02, 0x01, 0x01, b'a', 0x02, 0x01, b'b',
+            // Custom section: `.debug_info`.
+            0x00, 0x0d, 0x0b, b'.', b'd', b'e', b'b', b'u', b'g', b'_', b'i', b'n', b'f', b'o', 0x2a,
+        ];
+        let module = WasmModule::parse(&bytes).unwrap();
+        let names: Vec<_> = module.functions.iter().map(|f| f.name).collect();
+        assert_eq!(names, vec![Some("imp"), Some("a"), Some("b")]);
+        assert_eq!(module.functions[0].address, None);
+        assert_eq!(module.functions[1].address, Some(2));
+        assert_eq!(
+            module.functions[1].references,
+            vec![(3, 2, ReferenceKind::Call)]
+        );
+        assert_eq!(module.functions[2].address, Some(7));
+        assert_eq!(
+            module.functions[2].references,
+            vec![(8, 0, ReferenceKind::Call)]
+        );
+        assert_eq!(module.custom_section(".debug_info"), &[0x2a]);
+        assert!(module.custom_section(".debug_line").is_empty());
+    }
+}
@@ -5,7 +5,9 @@ use std::time::Instant;
 
 use rustc_hash::FxHashMap;
 
-/// Records how long different parts of execution take.
+/// Records how long different parts of execution take. Timing is only collected if enabled at
+/// runtime (via --print-timing) and the "timing" feature is enabled at compile time. When disabled,
+/// we don't even read the clock, so it's fine to use from hot paths.
 #[derive(Default)]
 pub(crate) struct TimingCollector {
     enabled: bool,
@@ -17,6 +19,10 @@ pub(crate) struct TimingCollector {
     timings: FxHashMap<&'static str, Duration>,
 }
 
+/// A point in time from which a timing can be measured. Only captured if timing is enabled.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timestamp(Option<Instant>);
+
 impl TimingCollector {
     pub(crate) fn new(enabled: bool) -> Self {
         Self {
@@ -26,14 +32,44 @@ impl TimingCollector {
         }
     }
 
+    /// Returns an empty collector that's enabled if we are. Used for collecting timings in places
+    /// where we don't have mutable access to this collector. See `merge`.
+    pub(crate) fn child(&self) -> Self {
+        Self::new(self.enabled)
+    }
+
+    fn is_enabled(&self) -> bool {
+        // Checking the feature first means that if it's disabled, the compiler can remove all
+        // timing code.
+        cfg!(feature = "timing") && self.enabled
+    }
+
+    /// Returns the current time if timing is enabled.
+    #[inline]
+    pub(crate) fn now(&self) -> Timestamp {
+        Timestamp(self.is_enabled().then(Instant::now))
+    }
+
     /// Adds duration since `start` to the timing category `timing`. Returns the time now, which can
     /// optionally be used to record the time to the next event.
-    pub(crate) fn add_timing(&mut self, start: Instant, timing: &'static str) -> Instant {
+    #[inline]
+    pub(crate) fn add_timing(&mut self, start: Timestamp, timing: &'static str) -> Timestamp {
+        let Some(start) = start.0.filter(|_| self.is_enabled()) else {
+            return Timestamp(None);
+        };
         let now = Instant::now();
-        if !self.enabled {
-            return now;
+        self.record(timing, now - start);
+        Timestamp(Some(now))
+    }
+
+    /// Adds all timings from `other` to our timings.
+    pub(crate) fn merge(&mut self, other: &TimingCollector) {
+        for timing in &other.order {
+            self.record(timing, other.timings[timing]);
         }
-        let elapsed = now - start;
+    }
+
+    fn record(&mut self, timing: &'static str, elapsed: Duration) {
         match self.timings.entry(timing) {
             Entry::Occupied(mut entry) => {
                 *entry.get_mut() += elapsed;
@@ -43,15 +79,51 @@ impl TimingCollector {
                 self.order.push(timing);
             }
         }
-        now
     }
 }
 
 impl Display for TimingCollector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !cfg!(feature = "timing") {
+            writeln!(
+                f,
+                "Timing not available. Rebuild with the `timing` feature enabled."
+            )?;
+        }
         for key in &self.order {
             writeln!(f, "{key}: {:0.3}s", self.timings[key].as_secs_f32())?
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TimingCollector;
+
+    #[test]
+    fn disabled_timing_records_nothing() {
+        let mut timings = TimingCollector::new(false);
+        let start = timings.now();
+        timings.add_timing(start, "a");
+        assert_eq!(timings.to_string(), TimingCollector::default().to_string());
+        assert!(timings.order.is_empty());
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn enabled_timing() {
+        let mut timings = TimingCollector::new(true);
+        let start = timings.now();
+        let next = timings.add_timing(start, "a");
+        timings.add_timing(next, "b");
+        timings.add_timing(start, "a");
+        assert_eq!(timings.order, vec!["a", "b"]);
+
+        let mut child = timings.child();
+        child.add_timing(child.now(), "c");
+        child.add_timing(child.now(), "a");
+        timings.merge(&child);
+        assert_eq!(timings.order, vec!["a", "b", "c"]);
+    }
+}
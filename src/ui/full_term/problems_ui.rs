@@ -6,6 +6,7 @@ use super::update_counter;
 use crate::checker::ApiUsage;
 use crate::checker::BinLocation;
 use crate::checker::Checker;
+use crate::checker::ReferenceKind;
 use crate::config::Config;
 use crate::config_editor;
 use crate::config_editor::ConfigEditor;
@@ -14,6 +15,7 @@ use crate::config_editor::EditOpts;
 use crate::crate_index::CrateIndex;
 use crate::crate_index::PackageId;
 use crate::location::SourceLocation;
+use crate::name_display::shorten;
 use crate::problem::OffTreeApiUsage;
 use crate::problem::Problem;
 use crate::problem_store::ProblemId;
@@ -221,6 +223,12 @@ impl ProblemsUi {
                 }
                 self.enter_usage_mode();
             }
+            (Mode::SelectProblem, KeyCode::Char('w')) => {
+                self.dispute_selected_problem()?;
+                if self.problem_index >= self.problem_store.lock().len() {
+                    self.problem_index = 0;
+                }
+            }
             (Mode::SelectProblem, KeyCode::Char('t')) => {
                 self.modes.push(Mode::ShowPackageTree);
             }
@@ -592,6 +600,22 @@ impl ProblemsUi {
         Ok(())
     }
 
+    /// Records the currently selected problem as looking wrong, then resolves it, so that analysis
+    /// can proceed. Problems that were already disputed are just resolved.
+    fn dispute_selected_problem(&self) -> Result<()> {
+        let mut pstore_lock = self.problem_store.lock();
+        let Some((index, problem)) = pstore_lock.deduplicated_into_iter().nth(self.problem_index)
+        else {
+            return Ok(());
+        };
+        if !matches!(problem, Problem::Disputed(..)) {
+            let config = self.checker.lock().unwrap().config.clone();
+            crate::disputes::record(&crate::disputes::path(&self.config_path), problem, &config)?;
+        }
+        pstore_lock.resolve(index);
+        Ok(())
+    }
+
     fn current_edit_supports_comments(&self) -> bool {
         let pstore_lock = self.problem_store.lock();
         let config = self.checker.lock().unwrap().config.clone();
@@ -819,6 +843,10 @@ fn render_help(f: &mut Frame, mode: Option<&Mode>) {
                     "Select and show details of each usage (API/unsafe only)",
                 ),
                 ("t", "Show tree of crate dependencies to this crate"),
+                (
+                    "w",
+                    "This looks wrong: record the problem as disputed and skip it",
+                ),
                 ("up", "Select previous problem"),
                 ("down", "Select next problem"),
                 ("a", "Enable auto-apply for problems with only one edit"),
@@ -990,15 +1018,37 @@ impl DisplayUsage for ApiUsage {
     }
 
     fn list_display(&self) -> String {
-        format!("{} -> {}", self.from, self.to_source)
+        let from = shorten(&self.from);
+        let to = shorten(&self.to_source);
+        if self.kind == ReferenceKind::Call {
+            format!("{from} -> {to}")
+        } else {
+            format!("{from} -> {to} ({})", self.kind)
+        }
     }
 
+    /// Unlike the list, details always have full names, since this is where you go to see them.
     fn details(&self) -> Vec<(&'static str, String)> {
-        vec![
+        let mut details = vec![
             ("From", self.from.to_string()),
             ("To", self.to.to_string()),
             ("Matched name", self.to_name.to_string()),
-        ]
+            ("Reference", self.kind.to_string()),
+        ];
+        if !self.callers.is_empty() {
+            let call_path: Vec<String> = self.call_path().map(|name| name.to_string()).collect();
+            details.push(("Call path", call_path.join(" -> ")));
+        }
+        for (index, frame) in self.inline_chain.iter().enumerate() {
+            let key = if index == 0 { "Inline chain" } else { "" };
+            let arrow = if index == 0 { "" } else { "-> " };
+            let value = match &frame.location {
+                Some(location) => format!("{arrow}{} at {location}", frame.function),
+                None => format!("{arrow}{}", frame.function),
+            };
+            details.push((key, value));
+        }
+        details
     }
 
     fn bin_location(&self) -> Option<(&Path, BinLocation)> {
@@ -101,9 +101,17 @@ impl super::UserInterface for NullUi {
                         self.accept_all_single_edits(&mut pstore)?;
                     }
 
+                    let migration_baseline =
+                        self.checker.lock().unwrap().migration_baseline.clone();
                     let mut has_errors = false;
                     for (_, problem) in pstore.deduplicated_into_iter() {
                         let mut severity = problem.severity();
+                        if migration_baseline
+                            .as_ref()
+                            .is_some_and(|baseline| baseline.contains(problem))
+                        {
+                            severity = Severity::Warning;
+                        }
                         if self.args.command.is_some() && severity == Severity::Warning {
                             // When running for example `cackle test`, not everything will be
                             // analysed, so unused warnings are expected. As such, we suppress all
@@ -186,6 +194,7 @@ mod tests {
             target_dir,
             args.clone(),
             sysroot.into(),
+            None,
             crate_index,
             config_path.clone(),
         )));
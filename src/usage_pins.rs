@@ -0,0 +1,122 @@
+//! Allowances for individual API usages, as an alternative to allowing a package a whole API. Each
+//! allowance records the source file and line of the usage, relative to the package root, together
+//! with a hash of the lines around it. If the code there changes, the hash no longer matches, so the
+//! allowance stops applying and the usage is reported again for review.
+
+use crate::checker::ApiUsage;
+use crate::config::AllowedUsage;
+use crate::config::ApiName;
+use crate::vfs::Vfs;
+use std::path::Path;
+
+/// How many lines either side of a usage are included in its hash.
+const CONTEXT_LINES: usize = 2;
+
+/// Returns the hash of the lines around `line` (1-based) of `source`, or `None` if the source doesn't
+/// have that many lines. Trailing whitespace is ignored, so that reformatting with a different
+/// editor doesn't invalidate allowances.
+fn context_hash(source: &str, line: u32) -> Option<String> {
+    let index = (line as usize).checked_sub(1)?;
+    let lines: Vec<&str> = source.lines().map(str::trim_end).collect();
+    if index >= lines.len() {
+        return None;
+    }
+    let start = index.saturating_sub(CONTEXT_LINES);
+    let end = (index + CONTEXT_LINES + 1).min(lines.len());
+    Some(crate::prebuilt::sha256_hex(
+        lines[start..end].join("\n").as_bytes(),
+    ))
+}
+
+/// Returns the allowance that would permit `usage` of `api` in its current form, or `None` if the
+/// usage isn't in a source file under `pkg_dir` that we can read.
+pub(crate) fn pin(
+    usage: &ApiUsage,
+    api: &ApiName,
+    pkg_dir: &Path,
+    vfs: &dyn Vfs,
+) -> Option<AllowedUsage> {
+    let location = &usage.source_location;
+    let file = location.filename().strip_prefix(pkg_dir).ok()?;
+    let source = vfs.read_to_string(location.filename()).ok()?;
+    Some(AllowedUsage {
+        api: api.clone(),
+        file: file.to_str()?.replace('\\', "/"),
+        line: location.line(),
+        hash: context_hash(&source, location.line())?,
+    })
+}
+
+/// Returns whether `usage` of `api` is permitted by one of `allowed`. An allowance for the same
+/// location whose hash no longer matches the code doesn't count.
+pub(crate) fn is_allowed(
+    allowed: &[AllowedUsage],
+    usage: &ApiUsage,
+    api: &ApiName,
+    pkg_dir: &Path,
+    vfs: &dyn Vfs,
+) -> bool {
+    if allowed.is_empty() {
+        return false;
+    }
+    let Some(current) = pin(usage, api, pkg_dir, vfs) else {
+        return false;
+    };
+    allowed.contains(&current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::context_hash;
+    use super::is_allowed;
+    use super::pin;
+    use crate::checker::testing::api_usage;
+    use crate::config::ApiName;
+    use crate::vfs::testing::MemoryFs;
+    use std::path::Path;
+
+    const SOURCE: &str = "fn a() {}\n\nfn b() {\n    std::fs::read(\"x\");\n}\n";
+
+    #[test]
+    fn hashes() {
+        assert_eq!(context_hash(SOURCE, 4), context_hash(SOURCE, 4));
+        assert_ne!(context_hash(SOURCE, 4), context_hash(SOURCE, 3));
+        assert_eq!(
+            context_hash(SOURCE, 4),
+            context_hash(&SOURCE.replace(";\n", ";  \n"), 4)
+        );
+        assert!(context_hash(SOURCE, 1).is_some());
+        assert!(context_hash(SOURCE, 0).is_none());
+        assert!(context_hash(SOURCE, 6).is_none());
+    }
+
+    #[test]
+    fn drift() {
+        let fs_api = ApiName::from("fs");
+        let mut usage = api_usage("foo", "foo::b", "std::fs::read");
+        usage.source_location =
+            crate::location::SourceLocation::new(Path::new("/ws/foo/src/lib.rs"), 4, None);
+        let pkg_dir = Path::new("/ws/foo");
+        let mut vfs = MemoryFs::default();
+        vfs.add("/ws/foo/src/lib.rs", SOURCE);
+        let allowed = pin(&usage, &fs_api, pkg_dir, &vfs).unwrap();
+        assert_eq!(allowed.file, "src/lib.rs");
+        assert_eq!(allowed.line, 4);
+        let allowed = vec![allowed];
+        assert!(is_allowed(&allowed, &usage, &fs_api, pkg_dir, &vfs));
+        assert!(!is_allowed(
+            &allowed,
+            &usage,
+            &ApiName::from("net"),
+            pkg_dir,
+            &vfs
+        ));
+
+        // Changing nearby code invalidates the allowance.
+        vfs.add(
+            "/ws/foo/src/lib.rs",
+            SOURCE.replace("\"x\"", "\"/etc/passwd\""),
+        );
+        assert!(!is_allowed(&allowed, &usage, &fs_api, pkg_dir, &vfs));
+    }
+}
@@ -0,0 +1,115 @@
+//! Access to the files that we analyse - binaries, object files, archives and config. Going via a
+//! trait rather than directly to `std::fs` means that tests can run the scanner and the config
+//! loader against files that only exist in memory.
+
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+
+/// A file that we've opened for reading.
+pub(crate) trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub(crate) trait Vfs: Send + Sync {
+    /// Replaces the contents of `buffer` with the contents of the file at `path`.
+    fn read_into(&self, path: &Path, buffer: &mut Vec<u8>) -> std::io::Result<()>;
+
+    /// Opens the file at `path` for reading. Used for files that we'd prefer not to read into
+    /// memory all at once, such as archives, which we read parts of as they're needed.
+    fn open<'a>(&'a self, path: &Path) -> std::io::Result<Box<dyn ReadSeek + 'a>>;
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.read_into(path, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// The actual filesystem.
+pub(crate) struct RealFs;
+
+impl Vfs for RealFs {
+    fn read_into(&self, path: &Path, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+        buffer.clear();
+        std::fs::File::open(path)?.read_to_end(buffer)?;
+        Ok(())
+    }
+
+    fn open<'a>(&'a self, path: &Path) -> std::io::Result<Box<dyn ReadSeek + 'a>> {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::ReadSeek;
+    use super::Vfs;
+    use rustc_hash::FxHashMap;
+    use std::io::Cursor;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    /// A filesystem that only contains the files that have been added to it.
+    #[derive(Default)]
+    pub(crate) struct MemoryFs {
+        files: FxHashMap<PathBuf, Vec<u8>>,
+    }
+
+    impl MemoryFs {
+        pub(crate) fn add(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+            self.files.insert(path.into(), contents.into());
+        }
+
+        fn get(&self, path: &Path) -> std::io::Result<&[u8]> {
+            self.files.get(path).map(Vec::as_slice).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("`{}` doesn't exist", path.display()),
+                )
+            })
+        }
+    }
+
+    impl Vfs for MemoryFs {
+        fn read_into(&self, path: &Path, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+            buffer.clear();
+            buffer.extend_from_slice(self.get(path)?);
+            Ok(())
+        }
+
+        fn open<'a>(&'a self, path: &Path) -> std::io::Result<Box<dyn ReadSeek + 'a>> {
+            Ok(Box::new(Cursor::new(self.get(path)?)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Vfs;
+    use super::testing::MemoryFs;
+    use std::io::Read;
+    use std::path::Path;
+
+    #[test]
+    fn memory_fs() {
+        let mut fs = MemoryFs::default();
+        fs.add("/a/b.txt", "hello");
+        assert_eq!(fs.read_to_string(Path::new("/a/b.txt")).unwrap(), "hello");
+        let mut contents = Vec::new();
+        fs.open(Path::new("/a/b.txt"))
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"hello");
+        let error = fs.read(Path::new("/a/c.txt")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        fs.add("/bad.txt", [0xff]);
+        assert!(fs.read_to_string(Path::new("/bad.txt")).is_err());
+    }
+}